@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Decode arbitrary input as the arguments or results of an NFSv3 or MOUNT procedure. The first
+// byte of the input picks the type, and the rest is decoded as that type. Decoding must fail
+// cleanly rather than panic, and a value that does decode must survive encoding and decoding again.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nfs3::{mount_proto::*, nfs3_xdr::*};
+
+macro_rules! fuzz_types {
+    ($($ty:ty),* $(,)?) => {
+        fuzz_target!(|data: &[u8]| {
+            let Some((&selector, input)) = data.split_first() else {
+                return;
+            };
+            let decoders: &[fn(&[u8])] = &[$(
+                |mut input| {
+                    let mut value = <$ty>::default();
+                    if value.deserialize(&mut input).is_err() {
+                        return;
+                    }
+                    let mut again = <$ty>::default();
+                    again
+                        .deserialize(&mut value.serialize_alloc().as_slice())
+                        .expect("a re-encoded value should decode");
+                    assert_eq!(value, again);
+                }
+            ),*];
+            decoders[selector as usize % decoders.len()](input);
+        });
+    };
+}
+
+fuzz_types!(
+    // MOUNT
+    MountArgs,
+    MountResult,
+    MountList,
+    Exports,
+    // NFSv3
+    GetAttrArgs,
+    GetAttrResult,
+    LookupArgs,
+    LookupResult,
+    ReadArgs,
+    ReadResult,
+    ReadDirArgs,
+    ReadDirResult,
+    ReadDirPlusArgs,
+    ReadDirPlusResult,
+    WriteArgs,
+    WriteResult,
+    CommitArgs,
+    CommitResult,
+    CreateArgs,
+    CreateResult,
+    MkdirArgs,
+    MkdirResult,
+    RemoveArgs,
+    RemoveResult,
+);
@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Decode arbitrary input as an RPC message header. Decoding must fail cleanly rather than panic,
+// and a message that does decode must survive encoding and decoding again. (It need not encode to
+// the same bytes: padding is not required to be zero when it is decoded.)
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rpc_protocol::RpcMessage;
+
+fuzz_target!(|data: &[u8]| {
+    let mut msg = RpcMessage::default();
+    if msg.deserialize(&mut &data[..]).is_err() {
+        return;
+    }
+
+    let mut again = RpcMessage::default();
+    again
+        .deserialize(&mut msg.serialize_alloc().as_slice())
+        .expect("a re-encoded message should decode");
+    assert_eq!(msg, again);
+});
@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The stable public API of the suite, re-exported from the crates that implement it, so that
+// programs built on the suite need not depend on how it is divided into crates, which changes as it
+// is refactored.
+//
+// Everything named here follows semver: while the version is 0.x, an item is only removed, renamed,
+// or changed incompatibly in a release that raises the minor version, and in 1.0 and later, only in
+// one that raises the major version. The generated protocol types follow their XDR specifications,
+// and only change with them, or with the representation that `xdr_codegen` gives every type. Items
+// of the underlying crates that are not re-exported here may change in any release.
+
+/// XDR encoding, for the code that `xdr_codegen` generates, and the compiler itself, for build
+/// scripts, with the `codegen` feature.
+pub mod xdr {
+    pub use xdr_lib::{
+        check_encoding, display, BufferTooSmall, Decode, DeserializeError, Encode, EncodingError,
+        Result,
+    };
+
+    #[cfg(feature = "codegen")]
+    pub use xdr_codegen::{build_helper, BuildError, BuildHelper, Compiler};
+}
+
+/// The RPC protocol (RFC 5531): calls and replies, the client, and RPC programs for servers.
+pub mod rpc {
+    pub use rpc_protocol::{
+        AuthError, AuthFlavor, AuthSysParms, Call, Error, OpaqueAuth, PeerCredentials, Principal,
+        ProtocolError, RpcCallError,
+    };
+
+    /// Making RPC calls.
+    #[cfg(feature = "client")]
+    pub mod client {
+        pub use rpc_protocol::client::{
+            do_rpc_call, do_rpc_call_with_auth, Connection, ReadTimeout,
+        };
+    }
+
+    /// RPC programs, which dispatch calls to procedures, and the threaded server that runs them.
+    #[cfg(feature = "server")]
+    pub mod server {
+        pub use rpc_protocol::server::{
+            bind_tcp, null_procedure, typed_procedure, Authenticated, Authenticator,
+            ConnectionTimeouts, Listener, PeerPolicy, ReplyParts, RpcProcedure, RpcProgram,
+            RpcResult, SysAuthenticator,
+        };
+    }
+
+    /// Limits on the rate at which servers answer each client, and each class of calls.
+    #[cfg(feature = "server")]
+    pub mod throttle {
+        pub use rpc_protocol::throttle::{Class, Classifier, Limits, Throttle};
+    }
+
+    /// The io_uring based server (Linux only).
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    pub mod ring {
+        pub use rpc_protocol::ring::{
+            ConnectionLimits, RingCall, RingProcedure, RingResult, RingSizes, RpcServer,
+            StateUpdater,
+        };
+    }
+}
+
+/// The types of the NFS v3, MOUNT, and NFS v4.0 protocols, generated from their XDR
+/// specifications.
+pub mod protocol {
+    pub use ::nfs3::mount_proto as mount;
+    pub use ::nfs3::nfs3_xdr as nfs3;
+    pub use ::nfs4::nfs4_prot as nfs4;
+}
+
+/// The MOUNT client, which NFS v3 clients use to get the root file handle of an export.
+#[cfg(feature = "client")]
+pub mod mount {
+    pub use ::nfs3::mount::client::{
+        connect, credential, mnt, mount, umnt, Error, MountedExport, CLIENT_FLAVORS,
+    };
+}
+
+/// The configuration of exports and the servers, and the lists of clients that each export is
+/// shared with.
+#[cfg(feature = "server")]
+pub mod exports {
+    pub use ::nfs3::clients::{check, Aliases, ClientEntry, ClientSpec, Clients, Network};
+    pub use ::nfs3::config::{Config, Error, ExportConfig, Flavor, NetgroupSource, Squash};
+    pub use ::nfs3::netgroup::{NetgroupBackend, NetgroupFile, SystemNetgroups};
+    pub use ::nfs3::resolver::Resolver;
+}
+
+/// The NFS v4.0 COMPOUND procedure, which servers implement with the `Operations` trait.
+pub mod nfs4 {
+    pub use ::nfs4::compound::{compound, CompoundState, OpFailure, OpResult, Operations};
+}
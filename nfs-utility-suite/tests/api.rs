@@ -0,0 +1,49 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Programs written against the facade, which should keep building as the crates under it change.
+
+use std::net::{IpAddr, SocketAddr, TcpStream};
+
+use nfs_utility_suite::{
+    exports::{Aliases, Clients, Squash},
+    protocol::nfs3::{FileHandle, GetAttrArgs},
+    rpc::{client, server},
+};
+
+#[test]
+fn rpc_program_and_client() {
+    let listener = server::bind_tcp(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    let address = listener.local_addr().unwrap();
+    let procedures: Vec<Option<server::RpcProcedure<()>>> = vec![Some(server::null_procedure)];
+    let mut program = server::RpcProgram::new(400_000, 1, 1, procedures, ());
+    std::thread::spawn(move || program.run_blocking_tcp_server(listener));
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    let result = client::do_rpc_call(&mut stream, 400_000, 1, 0, &[]).unwrap();
+    assert!(result.is_empty());
+}
+
+#[test]
+fn export_clients() {
+    let entries = ["10.1.0.0/16(no_root_squash)".to_string(), "*".to_string()];
+    let clients = Clients::resolve(&entries, &Aliases::new()).unwrap();
+
+    let admin: IpAddr = "10.1.2.3".parse().unwrap();
+    assert!(clients.allows(admin));
+    assert_eq!(clients.squash(admin), Some(Squash::None));
+    assert_eq!(clients.squash("192.0.2.1".parse().unwrap()), None);
+}
+
+#[test]
+fn protocol_types() {
+    let args = GetAttrArgs {
+        object: FileHandle {
+            data: vec![1, 2, 3],
+        },
+    };
+    let bytes = args.serialize_alloc();
+    let mut after = GetAttrArgs::default();
+    after.deserialize(&mut bytes.as_slice()).unwrap();
+    assert_eq!(args, after);
+}
@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Measures how well `nfs_server` keeps answering a client while others flood it with connections
+// that each send one bogus record, over loopback TCP:
+//
+//     cargo bench -p nfs3 --bench garbage_calls
+//
+// Each flood is of one kind of record: one that is not a call at all, which ends its connection as
+// soon as the fixed part of its header has been checked (see `rpc_protocol::peek_call()`), and a
+// call for another program, which is refused with PROG_UNAVAIL without being decoded. Reported are
+// the NULL calls per second that a well-behaved client gets answered meanwhile, and the bogus
+// connections per second that the server gets through.
+//
+// GARBAGE_CALLS_SECONDS sets how long each flood lasts (2 by default), and GARBAGE_CALLS_THREADS how
+// many connections are flooding at once (4 by default).
+
+#![cfg(target_os = "linux")]
+
+use std::{
+    env, fs,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    process::{Child, Command},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use rpc_protocol::{client, CallBody, OpaqueAuth, RpcMessage, RpcMessageBody};
+
+const NFS_PROGRAM: u32 = 100003;
+
+/// The size of each bogus record, without its record mark.
+const RECORD_SIZE: usize = 1024;
+
+/// An `nfs_server` that is killed when dropped.
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn main() {
+    let seconds: u64 = env_number("GARBAGE_CALLS_SECONDS", 2);
+    let threads: usize = env_number("GARBAGE_CALLS_THREADS", 4);
+    let duration = Duration::from_secs(seconds);
+
+    let dir = env::temp_dir().join(format!("nfs_garbage_calls_{}", std::process::id()));
+    let export = dir.join("export");
+    fs::create_dir_all(&export).unwrap();
+
+    let port = free_port();
+    let _server = Server(
+        Command::new(env!("CARGO_BIN_EXE_nfs_server"))
+            .args(["--port", &port.to_string()])
+            .arg("--export")
+            .arg(&export)
+            .arg("--handle-key")
+            .arg(dir.join("handle_key"))
+            .spawn()
+            .unwrap(),
+    );
+    drop(connect(port));
+
+    let floods: [(&str, Option<Vec<u8>>); 3] = [
+        ("none", None),
+        ("not a call", Some(not_a_call())),
+        ("wrong program", Some(wrong_program())),
+    ];
+
+    println!("{threads} flooding connections at a time, for {seconds} s each:");
+    println!(
+        "{:>14} {:>14} {:>16}",
+        "flood", "NULL calls/s", "connections/s"
+    );
+    for (name, record) in floods {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flooded = Arc::new(AtomicU64::new(0));
+        let flooders: Vec<_> = record
+            .iter()
+            .flat_map(|record| (0..threads).map(move |_| record.clone()))
+            .map(|record| {
+                let (stop, flooded) = (stop.clone(), flooded.clone());
+                thread::spawn(move || flood(port, &record, &stop, &flooded))
+            })
+            .collect();
+
+        let mut stream = connect(port);
+        let start = Instant::now();
+        let mut calls = 0;
+        while start.elapsed() < duration {
+            client::do_rpc_call(&mut stream, NFS_PROGRAM, 3, 0, &[]).unwrap();
+            calls += 1;
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+
+        stop.store(true, Ordering::Relaxed);
+        for flooder in flooders {
+            flooder.join().unwrap();
+        }
+
+        println!(
+            "{name:>14} {:>14.0} {:>16.0}",
+            calls as f64 / elapsed,
+            flooded.load(Ordering::Relaxed) as f64 / elapsed
+        );
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+/// Connect, send `record`, and read whatever the server sends back until it, or the client once it
+/// has a reply, ends the connection, over and over until `stop` is set.
+fn flood(port: u16, record: &[u8], stop: &AtomicBool, flooded: &AtomicU64) {
+    let mut reply = [0; 256];
+    while !stop.load(Ordering::Relaxed) {
+        let mut stream = connect(port);
+        if stream.write_all(record).is_err() {
+            continue;
+        }
+        // Either the reply, or the end of the connection:
+        let _ = stream.read(&mut reply);
+        flooded.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A record whose message type is neither a call nor a reply.
+fn not_a_call() -> Vec<u8> {
+    let mut record = marked(&[0xa5; RECORD_SIZE]);
+    record[8..12].copy_from_slice(&7_u32.to_be_bytes());
+    record
+}
+
+/// A well-formed call to a program that the server does not serve, padded to the record size.
+fn wrong_program() -> Vec<u8> {
+    let mut call = RpcMessage {
+        xid: 1,
+        body: RpcMessageBody::Call(CallBody {
+            rpcvers: 2,
+            prog: 400_999,
+            vers: 1,
+            proc: 1,
+            cred: OpaqueAuth::none(),
+            verf: OpaqueAuth::none(),
+        }),
+    }
+    .serialize_alloc();
+    call.resize(RECORD_SIZE, 0);
+
+    marked(&call)
+}
+
+/// `message` as a record of one fragment.
+fn marked(message: &[u8]) -> Vec<u8> {
+    let mut record = (message.len() as u32 | 1 << 31).to_be_bytes().to_vec();
+    record.extend_from_slice(message);
+    record
+}
+
+fn env_number<T: std::str::FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn free_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+fn connect(port: u16) -> TcpStream {
+    let start = Instant::now();
+    loop {
+        match TcpStream::connect(("127.0.0.1", port)) {
+            Ok(stream) => return stream,
+            Err(e) => assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "could not connect to nfs_server: {e}"
+            ),
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
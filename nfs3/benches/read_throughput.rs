@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Measures how fast `nfs_server` serves READs of several sizes with each way of reading the data of
+// a READ reply (see `export.read_method`) and of sending it (see `nfs_server.read_replies`), over
+// loopback TCP:
+//
+//     cargo bench -p nfs3 --bench read_throughput
+//
+// The file is read from the page cache, so this measures the server's copies and system calls
+// rather than storage. Over loopback, zero-copy sends are completed by copying after all, so
+// send-zc mostly saves the copies the server makes itself; splice saves every copy. The read
+// methods only matter when the data is copied into the reply: pread copies it out of the page cache
+// in one system call, while mmap maps it and copies it out of the mapping, which costs more system
+// calls, and page faults, per READ.
+//
+// READ_THROUGHPUT_MIB sets how many MiB are read with each mode and size (256 by default).
+
+#![cfg(target_os = "linux")]
+
+use std::{
+    env, fs,
+    net::{TcpListener, TcpStream},
+    process::{Child, Command},
+    thread,
+    time::{Duration, Instant},
+};
+
+use nfs3::{file_handle::FileHandleMap, nfs3_xdr::*, read_digest};
+use rpc_protocol::OpaqueAuth;
+
+/// The sizes of the READs, up to the server's largest.
+const READ_SIZES: [u32; 4] = [4 * 1024, 64 * 1024, 256 * 1024, 1024 * 1024];
+
+/// The size of the file read, which is read over as many times as needed.
+const FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Each way of serving READs: its name, `nfs_server.read_replies`, and `export.read_method`.
+const MODES: [(&str, &str, &str); 4] = [
+    ("pread", "copy", "pread"),
+    ("mmap", "copy", "mmap"),
+    ("splice", "splice", "pread"),
+    ("send-zc", "send-zc", "pread"),
+];
+
+/// An `nfs_server` that is killed when dropped.
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn main() {
+    let total_mib: u64 = env::var("READ_THROUGHPUT_MIB")
+        .ok()
+        .and_then(|mib| mib.parse().ok())
+        .unwrap_or(256);
+
+    let dir = env::temp_dir().join(format!("nfs_read_throughput_{}", std::process::id()));
+    let export = dir.join("export");
+    fs::create_dir_all(&export).unwrap();
+    let path = export.join("data");
+    let data: Vec<u8> = (0..FILE_SIZE).map(|i| (i % 251) as u8).collect();
+    fs::write(&path, &data).unwrap();
+
+    let handle_key = dir.join("handle_key");
+    let mut handles = FileHandleMap::new(&handle_key).unwrap();
+    handles.add_export(&export).unwrap();
+    let file = FileHandle {
+        data: handles.handle_for(&path).unwrap(),
+    };
+
+    println!("Reading {total_mib} MiB in READs of each size, in MiB/s:");
+    print!("{:>8}", "");
+    for size in READ_SIZES {
+        print!(" {:>9}", format!("{} KiB", size / 1024));
+    }
+    println!();
+
+    for (name, read_replies, read_method) in MODES {
+        let config = dir.join(format!("{name}.toml"));
+        fs::write(
+            &config,
+            format!(
+                "[export]\npath = {export:?}\nhandle_key = {handle_key:?}\n\
+                 read_method = \"{read_method}\"\n\n\
+                 [nfs_server]\nread_replies = \"{read_replies}\"\n"
+            ),
+        )
+        .unwrap();
+
+        let port = free_port();
+        let _server = Server(
+            Command::new(env!("CARGO_BIN_EXE_nfs_server"))
+                .arg("--config")
+                .arg(&config)
+                .args(["--port", &port.to_string()])
+                .spawn()
+                .unwrap(),
+        );
+        let mut stream = connect(port);
+
+        // Once to warm up, and again to measure:
+        read_file(&mut stream, &file, &data, READ_SIZES[3], FILE_SIZE);
+        print!("{name:>8}");
+        for size in READ_SIZES {
+            let start = Instant::now();
+            read_file(&mut stream, &file, &data, size, total_mib * 1024 * 1024);
+            let elapsed = start.elapsed();
+            print!(" {:>9.1}", total_mib as f64 / elapsed.as_secs_f64());
+        }
+        println!();
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+/// Read `total` bytes of the file in READs of `size` bytes, from its start, over and over, checking
+/// the data against `data`.
+fn read_file(stream: &mut TcpStream, file: &FileHandle, data: &[u8], size: u32, total: u64) {
+    let mut read = 0;
+    while read < total {
+        let offset = read % FILE_SIZE;
+        let args = ReadArgs {
+            file: file.clone(),
+            offset,
+            count: size,
+        };
+
+        let (res, _) = read_digest::read(stream, &args, OpaqueAuth::none(), false).unwrap();
+        let ReadResult::Ok(res) = res else {
+            panic!("READ at {offset} failed");
+        };
+        assert_eq!(res.count, size, "short READ at {offset}");
+        assert!(
+            res.data == data[offset as usize..][..size as usize],
+            "wrong data at {offset}"
+        );
+
+        read += u64::from(res.count);
+    }
+}
+
+fn free_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+fn connect(port: u16) -> TcpStream {
+    let start = Instant::now();
+    loop {
+        match TcpStream::connect(("127.0.0.1", port)) {
+            Ok(stream) => return stream,
+            Err(e) => assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "could not connect to nfs_server: {e}"
+            ),
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
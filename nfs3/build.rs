@@ -1,7 +1,19 @@
 fn main() {
-    xdr_codegen::Compiler::new()
-        .file("mount_proto.x")
-        .file("nfs3_xdr.x")
-        .run()
-        .expect("That should have worked. :(");
+    xdr_codegen::build_helper()
+        .options(|c| {
+            c.derive_serde()
+                .enable_deserialize_from()
+                .enable_display()
+                .display_timestamp("NfsTime")
+                .display_size("Size")
+                // These are described in status.rs:
+                .skip_display("NfsResult")
+                .skip_display("MountStatus")
+        })
+        .spec("mount_proto.x")
+        .spec("nsm_proto.x")
+        .spec("nfs3_xdr.x")
+        .spec("suite_proto.x")
+        .spec("control_proto.x")
+        .run();
 }
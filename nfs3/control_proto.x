@@ -0,0 +1,77 @@
+/*
+ * A private protocol through which exportfs administers the exports of mountd and the NFS server
+ * while they run. Each daemon serves it on a UNIX domain socket of its own, so who may use it is
+ * decided by the permissions of the socket, and by the credentials of the caller that the kernel
+ * reports for it (SO_PEERCRED), which are checked before any change is made. Like suite_proto.x, it is not part of any standard, and
+ * uses a program number from the range that RFC 5531 leaves to be defined by users.
+ */
+
+const CONTROL_MAXPATHLEN = 1024;
+const CONTROL_MAXNAMELEN = 255;
+
+typedef string ControlPath<CONTROL_MAXPATHLEN>;
+typedef string ControlName<CONTROL_MAXNAMELEN>;
+
+/* How the cookie verifiers of directory listings are chosen (see nfs3::config::CookieVerifier): */
+enum CookieVerifierKind {
+    Mtime   = 0,
+    Entries = 1,
+    Zero    = 2
+};
+
+/* How the data of READs is read from files (see nfs3::config::ReadMethod): */
+enum ReadMethodKind {
+    Pread = 0,
+    Mmap  = 1
+};
+
+struct ExportEntry {
+    ControlPath        path;
+    bool               read_only;
+    CookieVerifierKind cookie_verifier;
+    ReadMethodKind     read_method;
+    ControlName        clients<>;  /* see nfs3::clients; any client may mount if there are none */
+    ControlPath        snapshots;  /* shown as .snapshot in the export (see nfs3::config); none if empty */
+};
+
+struct ExportList {
+    ExportEntry exports<>;
+};
+
+struct ExportPath {
+    ControlPath path;
+};
+
+/* Why a request failed, if it did: */
+union ControlResult switch (bool failed) {
+case TRUE:
+    string reason<>;
+case FALSE:
+    void;
+};
+
+/* A named count of something done with an export, such as the bytes read from it: */
+struct Counter {
+    ControlName    name;
+    unsigned hyper value;
+};
+
+struct ExportStats {
+    ControlPath path;
+    Counter     counters<>;
+};
+
+struct StatsList {
+    ExportStats exports<>;
+};
+
+program CONTROL_PROG {
+    version CONTROL_VERS {
+        void          CONTROLPROC_NULL(void)         = 0;
+        ExportList    CONTROLPROC_LIST(void)         = 1;
+        ControlResult CONTROLPROC_ADD(ExportEntry)   = 2;
+        ControlResult CONTROLPROC_REMOVE(ExportPath) = 3;
+        ControlResult CONTROLPROC_FLUSH(void)        = 4;
+        StatsList     CONTROLPROC_STATS(void)        = 5;
+    } = 1;
+} = 0x20059101;
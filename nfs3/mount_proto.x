@@ -19,6 +19,10 @@ enum MountStatus {
     ServerFault = 10006  /* A failure on the server */
 };
 
+struct MountArgs {
+    DirPath  dirpath;
+};
+
 struct MountResultOk {
     FileHandle   fhandle;
     int        auth_flavors<>;
@@ -63,9 +67,9 @@ struct Exports {
 program MOUNT_PROGRAM {
    version MOUNT_V3 {
         void      MOUNTPROC3_NULL(void)    = 0;
-        MountResult MOUNTPROC3_MNT(dirpath)  = 1;
+        MountResult MOUNTPROC3_MNT(MountArgs) = 1;
         MountList MOUNTPROC3_DUMP(void)    = 2;
-        void      MOUNTPROC3_UMNT(dirpath) = 3;
+        void      MOUNTPROC3_UMNT(DirPath) = 3;
         void      MOUNTPROC3_UMNTALL(void) = 4;
         Exports   MOUNTPROC3_EXPORT(void)  = 5;
     } = 3;
@@ -3,6 +3,18 @@ const NFS3_COOKIEVERFSIZE = 8;
 const NFS3_CREATEVERFSIZE = 8;
 const NFS3_WRITEVERFSIZE = 8;
 
+const ACCESS3_READ    = 0x0001;
+const ACCESS3_LOOKUP  = 0x0002;
+const ACCESS3_MODIFY  = 0x0004;
+const ACCESS3_EXTEND  = 0x0008;
+const ACCESS3_DELETE  = 0x0010;
+const ACCESS3_EXECUTE = 0x0020;
+
+const FSF3_LINK        = 0x0001;
+const FSF3_SYMLINK     = 0x0002;
+const FSF3_HOMOGENEOUS = 0x0008;
+const FSF3_CANSETTIME  = 0x0010;
+
 typedef unsigned hyper uint64;
 typedef hyper int64;
 typedef unsigned long uint32;
@@ -105,9 +117,611 @@ case Ok:
 default:
 	void;
 };
+
+enum StableHow {
+	Unstable = 0,
+	DataSync = 1,
+	FileSync = 2
+};
+
+struct WccAttributes {
+	Size      size;
+	NfsTime   mtime;
+	NfsTime   ctime;
+};
+
+union PreOpAttributes switch (bool attributes_follow) {
+case TRUE:
+	WccAttributes  attributes;
+case FALSE:
+	void;
+};
+
+union PostOpAttributes switch (bool attributes_follow) {
+case TRUE:
+	FileAttributes  attributes;
+case FALSE:
+	void;
+};
+
+struct WccData {
+	PreOpAttributes   before;
+	PostOpAttributes  after;
+};
+
+struct AccessArgs {
+	FileHandle  object;
+	uint32      access;
+};
+
+struct AccessSuccess {
+	PostOpAttributes  obj_attributes;
+	uint32            access;
+};
+
+struct AccessFailure {
+	PostOpAttributes  obj_attributes;
+};
+
+union AccessResult switch (NfsResult status) {
+case Ok:
+	AccessSuccess  resok;
+default:
+	AccessFailure  resfail;
+};
+
+struct DirOpArgs {
+	FileHandle  dir;
+	string      name<>;
+};
+
+struct LookupArgs {
+	DirOpArgs  what;
+};
+
+struct LookupSuccess {
+	FileHandle        object;
+	PostOpAttributes  obj_attributes;
+	PostOpAttributes  dir_attributes;
+};
+
+struct LookupFailure {
+	PostOpAttributes  dir_attributes;
+};
+
+union LookupResult switch (NfsResult status) {
+case Ok:
+	LookupSuccess  resok;
+default:
+	LookupFailure  resfail;
+};
+
+struct ReadlinkArgs {
+	FileHandle  symlink;
+};
+
+struct ReadlinkSuccess {
+	PostOpAttributes  symlink_attributes;
+	string            data<>;
+};
+
+struct ReadlinkFailure {
+	PostOpAttributes  symlink_attributes;
+};
+
+union ReadlinkResult switch (NfsResult status) {
+case Ok:
+	ReadlinkSuccess  resok;
+default:
+	ReadlinkFailure  resfail;
+};
+
+struct ReadArgs {
+	FileHandle  file;
+	Offset      offset;
+	Count       count;
+};
+
+struct ReadSuccess {
+	PostOpAttributes  file_attributes;
+	Count             count;
+	bool              eof;
+	opaque            data<>;
+};
+
+struct ReadFailure {
+	PostOpAttributes  file_attributes;
+};
+
+union ReadResult switch (NfsResult status) {
+case Ok:
+	ReadSuccess  resok;
+default:
+	ReadFailure  resfail;
+};
+
+struct ReadDirArgs {
+	FileHandle  dir;
+	Cookie      cookie;
+	CookieVerf  cookieverf;
+	Count       count;
+};
+
+struct Entry {
+	FileId    fileid;
+	string    name<>;
+	Cookie    cookie;
+	Entry     *nextentry;
+};
+
+struct DirList {
+	Entry  *entries;
+	bool   eof;
+};
+
+struct ReadDirSuccess {
+	PostOpAttributes  dir_attributes;
+	CookieVerf        cookieverf;
+	DirList           reply;
+};
+
+struct ReadDirFailure {
+	PostOpAttributes  dir_attributes;
+};
+
+union ReadDirResult switch (NfsResult status) {
+case Ok:
+	ReadDirSuccess  resok;
+default:
+	ReadDirFailure  resfail;
+};
+
+union PostOpFileHandle switch (bool handle_follows) {
+case TRUE:
+	FileHandle  handle;
+case FALSE:
+	void;
+};
+
+struct ReadDirPlusArgs {
+	FileHandle  dir;
+	Cookie      cookie;
+	CookieVerf  cookieverf;
+	Count       dircount;
+	Count       maxcount;
+};
+
+struct EntryPlus {
+	FileId            fileid;
+	string            name<>;
+	Cookie            cookie;
+	PostOpAttributes  name_attributes;
+	PostOpFileHandle  name_handle;
+	EntryPlus         *nextentry;
+};
+
+struct DirListPlus {
+	EntryPlus  *entries;
+	bool       eof;
+};
+
+struct ReadDirPlusSuccess {
+	PostOpAttributes  dir_attributes;
+	CookieVerf        cookieverf;
+	DirListPlus       reply;
+};
+
+struct ReadDirPlusFailure {
+	PostOpAttributes  dir_attributes;
+};
+
+union ReadDirPlusResult switch (NfsResult status) {
+case Ok:
+	ReadDirPlusSuccess  resok;
+default:
+	ReadDirPlusFailure  resfail;
+};
+
+struct WriteArgs {
+	FileHandle  file;
+	Offset      offset;
+	Count       count;
+	StableHow   stable;
+	opaque      data<>;
+};
+
+struct WriteSuccess {
+	WccData    file_wcc;
+	Count      count;
+	StableHow  committed;
+	WriteVerf  verf;
+};
+
+struct WriteFailure {
+	WccData  file_wcc;
+};
+
+union WriteResult switch (NfsResult status) {
+case Ok:
+	WriteSuccess  resok;
+default:
+	WriteFailure  resfail;
+};
+
+struct CommitArgs {
+	FileHandle  file;
+	Offset      offset;
+	Count       count;
+};
+
+struct CommitSuccess {
+	WccData    file_wcc;
+	WriteVerf  verf;
+};
+
+struct CommitFailure {
+	WccData  file_wcc;
+};
+
+union CommitResult switch (NfsResult status) {
+case Ok:
+	CommitSuccess  resok;
+default:
+	CommitFailure  resfail;
+};
+
+union SetMode switch (bool set_it) {
+case TRUE:
+	Mode  mode;
+case FALSE:
+	void;
+};
+
+union SetUid switch (bool set_it) {
+case TRUE:
+	Uid  uid;
+case FALSE:
+	void;
+};
+
+union SetGid switch (bool set_it) {
+case TRUE:
+	Gid  gid;
+case FALSE:
+	void;
+};
+
+union SetSize switch (bool set_it) {
+case TRUE:
+	Size  size;
+case FALSE:
+	void;
+};
+
+enum TimeHow {
+	DontChange      = 0,
+	SetToServerTime = 1,
+	SetToClientTime = 2
+};
+
+union SetAtime switch (TimeHow set_it) {
+case DontChange:
+	void;
+case SetToServerTime:
+	void;
+case SetToClientTime:
+	NfsTime  atime;
+};
+
+union SetMtime switch (TimeHow set_it) {
+case DontChange:
+	void;
+case SetToServerTime:
+	void;
+case SetToClientTime:
+	NfsTime  mtime;
+};
+
+struct SetAttributes {
+	SetMode   mode;
+	SetUid    uid;
+	SetGid    gid;
+	SetSize   size;
+	SetAtime  atime;
+	SetMtime  mtime;
+};
+
+union SetAttrGuard switch (bool check) {
+case TRUE:
+	NfsTime  obj_ctime;
+case FALSE:
+	void;
+};
+
+struct SetAttrArgs {
+	FileHandle     object;
+	SetAttributes  new_attributes;
+	SetAttrGuard   guard;
+};
+
+struct SetAttrSuccess {
+	WccData  obj_wcc;
+};
+
+struct SetAttrFailure {
+	WccData  obj_wcc;
+};
+
+union SetAttrResult switch (NfsResult status) {
+case Ok:
+	SetAttrSuccess  resok;
+default:
+	SetAttrFailure  resfail;
+};
+
+enum CreateMode {
+	Unchecked = 0,
+	Guarded   = 1,
+	Exclusive = 2
+};
+
+union CreateHow switch (CreateMode mode) {
+case Unchecked:
+	SetAttributes  obj_attributes;
+case Guarded:
+	SetAttributes  obj_attributes;
+case Exclusive:
+	CreateVerf     verf;
+};
+
+struct CreateArgs {
+	DirOpArgs  where;
+	CreateHow  how;
+};
+
+struct CreateSuccess {
+	PostOpFileHandle  obj;
+	PostOpAttributes  obj_attributes;
+	WccData           dir_wcc;
+};
+
+struct CreateFailure {
+	WccData  dir_wcc;
+};
+
+union CreateResult switch (NfsResult status) {
+case Ok:
+	CreateSuccess  resok;
+default:
+	CreateFailure  resfail;
+};
+
+struct MkdirArgs {
+	DirOpArgs      where;
+	SetAttributes  attributes;
+};
+
+struct MkdirSuccess {
+	PostOpFileHandle  obj;
+	PostOpAttributes  obj_attributes;
+	WccData           dir_wcc;
+};
+
+struct MkdirFailure {
+	WccData  dir_wcc;
+};
+
+union MkdirResult switch (NfsResult status) {
+case Ok:
+	MkdirSuccess  resok;
+default:
+	MkdirFailure  resfail;
+};
+
+struct RemoveArgs {
+	DirOpArgs  object;
+};
+
+struct RemoveSuccess {
+	WccData  dir_wcc;
+};
+
+struct RemoveFailure {
+	WccData  dir_wcc;
+};
+
+union RemoveResult switch (NfsResult status) {
+case Ok:
+	RemoveSuccess  resok;
+default:
+	RemoveFailure  resfail;
+};
+
+struct SymlinkData {
+	SetAttributes  symlink_attributes;
+	string         symlink_data<>;
+};
+
+struct SymlinkArgs {
+	DirOpArgs    where;
+	SymlinkData  symlink;
+};
+
+struct SymlinkSuccess {
+	PostOpFileHandle  obj;
+	PostOpAttributes  obj_attributes;
+	WccData           dir_wcc;
+};
+
+struct SymlinkFailure {
+	WccData  dir_wcc;
+};
+
+union SymlinkResult switch (NfsResult status) {
+case Ok:
+	SymlinkSuccess  resok;
+default:
+	SymlinkFailure  resfail;
+};
+
+struct DeviceData {
+	SetAttributes  dev_attributes;
+	SpecData       spec;
+};
+
+union MknodData switch (FileType type) {
+case Chr:
+case Blk:
+	DeviceData  device;
+case Sock:
+case Fifo:
+	SetAttributes  pipe_attributes;
+default:
+	void;
+};
+
+struct MknodArgs {
+	DirOpArgs  where;
+	MknodData  what;
+};
+
+struct MknodSuccess {
+	PostOpFileHandle  obj;
+	PostOpAttributes  obj_attributes;
+	WccData           dir_wcc;
+};
+
+struct MknodFailure {
+	WccData  dir_wcc;
+};
+
+union MknodResult switch (NfsResult status) {
+case Ok:
+	MknodSuccess  resok;
+default:
+	MknodFailure  resfail;
+};
+
+struct RenameArgs {
+	DirOpArgs  from;
+	DirOpArgs  to;
+};
+
+struct RenameSuccess {
+	WccData  fromdir_wcc;
+	WccData  todir_wcc;
+};
+
+struct RenameFailure {
+	WccData  fromdir_wcc;
+	WccData  todir_wcc;
+};
+
+union RenameResult switch (NfsResult status) {
+case Ok:
+	RenameSuccess  resok;
+default:
+	RenameFailure  resfail;
+};
+
+struct LinkArgs {
+	FileHandle  file;
+	DirOpArgs   link;
+};
+
+struct LinkSuccess {
+	PostOpAttributes  file_attributes;
+	WccData           linkdir_wcc;
+};
+
+struct LinkFailure {
+	PostOpAttributes  file_attributes;
+	WccData           linkdir_wcc;
+};
+
+union LinkResult switch (NfsResult status) {
+case Ok:
+	LinkSuccess  resok;
+default:
+	LinkFailure  resfail;
+};
+
+struct FsStatArgs {
+	FileHandle  fsroot;
+};
+
+struct FsStatSuccess {
+	PostOpAttributes  obj_attributes;
+	Size              tbytes;
+	Size              fbytes;
+	Size              abytes;
+	Size              tfiles;
+	Size              ffiles;
+	Size              afiles;
+	uint32            invarsec;
+};
+
+struct FsStatFailure {
+	PostOpAttributes  obj_attributes;
+};
+
+union FsStatResult switch (NfsResult status) {
+case Ok:
+	FsStatSuccess  resok;
+default:
+	FsStatFailure  resfail;
+};
+
+struct FsInfoArgs {
+	FileHandle  fsroot;
+};
+
+struct FsInfoSuccess {
+	PostOpAttributes  obj_attributes;
+	uint32            rtmax;
+	uint32            rtpref;
+	uint32            rtmult;
+	uint32            wtmax;
+	uint32            wtpref;
+	uint32            wtmult;
+	uint32            dtpref;
+	Size              maxfilesize;
+	NfsTime           time_delta;
+	uint32            properties;
+};
+
+struct FsInfoFailure {
+	PostOpAttributes  obj_attributes;
+};
+
+union FsInfoResult switch (NfsResult status) {
+case Ok:
+	FsInfoSuccess  resok;
+default:
+	FsInfoFailure  resfail;
+};
+
 program NFS_PROGRAM {
 	version NFS_V3 {
 		void NULL(void)                    = 0;
 		GetAttrResult GETATTR(GetAttrArgs) = 1;
+		SetAttrResult SETATTR(SetAttrArgs) = 2;
+		LookupResult LOOKUP(LookupArgs)    = 3;
+		AccessResult ACCESS(AccessArgs)    = 4;
+		ReadlinkResult READLINK(ReadlinkArgs) = 5;
+		ReadResult READ(ReadArgs)          = 6;
+		WriteResult WRITE(WriteArgs)       = 7;
+		CreateResult CREATE(CreateArgs)    = 8;
+		MkdirResult MKDIR(MkdirArgs)       = 9;
+		SymlinkResult SYMLINK(SymlinkArgs) = 10;
+		MknodResult MKNOD(MknodArgs)       = 11;
+		RemoveResult REMOVE(RemoveArgs)    = 12;
+		RenameResult RENAME(RenameArgs)    = 14;
+		LinkResult LINK(LinkArgs)          = 15;
+		ReadDirResult READDIR(ReadDirArgs) = 16;
+		ReadDirPlusResult READDIRPLUS(ReadDirPlusArgs) = 17;
+		FsStatResult FSSTAT(FsStatArgs)    = 18;
+		FsInfoResult FSINFO(FsInfoArgs)    = 19;
+		CommitResult COMMIT(CommitArgs)    = 21;
 	} = 3;
 } = 100003;
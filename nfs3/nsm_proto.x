@@ -0,0 +1,82 @@
+/*
+ * The Network Status Monitor (NSM) protocol, as specified in X/Open CAE Specification C702,
+ * "Protocols for Interworking: XNFS, Version 3W", chapter 11.
+ */
+
+const SM_MAXSTRLEN = 1024;  /* Maximum bytes in a host name */
+const SM_PRIVSIZE  = 16;    /* Bytes of private data returned in callbacks */
+
+typedef string MonName<SM_MAXSTRLEN>;
+
+struct SmName {
+    MonName mon_name;
+};
+
+enum SmResult {
+    Success = 0,
+    Fail = 1
+};
+
+struct SmStatResult {
+    SmResult res_stat;
+    int      state;
+};
+
+struct SmStat {
+    int state;
+};
+
+/* The RPC procedure to call back when the monitored host's status changes: */
+struct MyId {
+    MonName my_name;
+    int     my_prog;
+    int     my_vers;
+    int     my_proc;
+};
+
+struct MonId {
+    MonName mon_name;  /* The host to monitor */
+    MyId    my_id;
+};
+
+struct Mon {
+    MonId  mon_id;
+    opaque private[SM_PRIVSIZE];
+};
+
+/* The argument of SM_NOTIFY, sent by a host after it restarts: */
+struct StatChange {
+    MonName mon_name;
+    int     state;
+};
+
+/* The argument of the callback to the procedure named by MyId: */
+struct Status {
+    MonName mon_name;
+    int     state;
+    opaque  private[SM_PRIVSIZE];
+};
+
+/*
+ * Not part of the protocol: these hold the lists of monitored hosts and of hosts yet to be
+ * notified of a restart, which statd keeps on disk.
+ */
+struct MonitorList {
+    Mon monitors<>;
+};
+
+struct NotifyList {
+    SmName hosts<>;
+};
+
+program SM_PROG {
+    version SM_VERS {
+        void         SM_NULL(void)       = 0;
+        SmStatResult SM_STAT(SmName)     = 1;
+        SmStatResult SM_MON(Mon)         = 2;
+        SmStat       SM_UNMON(MonId)     = 3;
+        SmStat       SM_UNMON_ALL(MyId)  = 4;
+        void         SM_SIMU_CRASH(void) = 5;
+        void         SM_NOTIFY(StatChange) = 6;
+    } = 1;
+} = 100024;
@@ -0,0 +1,373 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Administers the exports of a running mountd and nfs_server through their control sockets (see
+// nfs3::control), in the manner of exportfs(8) from nfs-utils:
+//
+//     exportfs --config /etc/nfs3.toml                 # list the exports
+//     exportfs --config /etc/nfs3.toml -o ro /srv/pub  # export /srv/pub read-only
+//     exportfs --config /etc/nfs3.toml 10.1.0.0/16,node17:/srv/scratch  # to those clients only
+//     exportfs --config /etc/nfs3.toml 'admin1(no_root_squash),*:/srv'  # trusting admin1's root
+//     exportfs --config /etc/nfs3.toml -u /srv/pub     # stop exporting it
+//     exportfs --config /etc/nfs3.toml -f              # flush mountd's mount list
+//     exportfs --config /etc/nfs3.toml --stats         # show what was done with each export
+//
+// Changes are made to both daemons, and last until they exit or reload their configuration. The
+// clients of an export are given as in `export.clients` (see nfs3::clients), with IPv6 addresses in
+// brackets, as in "[fd00::17]:/srv/scratch". Clients shadowed by earlier ones are warned about.
+
+use std::{
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Parser, ValueEnum};
+
+use nfs3::{
+    clients::{ClientEntry, Clients},
+    config::{Config, CookieVerifier, ReadMethod},
+    control,
+    control_proto::{ExportEntry, ExportStats},
+};
+
+#[derive(Parser)]
+struct Cli {
+    /// The configuration file of the daemons, whose `mountd.control_socket` and
+    /// `nfs_server.control_socket` keys name their control sockets.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// mountd's control socket, in place of the one in the configuration file.
+    #[arg(long)]
+    mountd_socket: Option<PathBuf>,
+
+    /// nfs_server's control socket, in place of the one in the configuration file.
+    #[arg(long)]
+    nfs_server_socket: Option<PathBuf>,
+
+    /// Export options, separated by commas: "ro" or "rw" (the default), "cookieverf=mtime" (the
+    /// default), "cookieverf=entries", or "cookieverf=zero" to choose the cookie verifiers of
+    /// directory listings (see `export.cookie_verifier` in the configuration file), and
+    /// "read=pread" (the default) or "read=mmap" to choose how the NFS server reads the data of
+    /// READs (see `export.read_method`), and "snapshots=DIR" to show the snapshots in DIR
+    /// read-only as `.snapshot` in the root of the export (see `export.snapshots`).
+    #[arg(short, long)]
+    options: Option<String>,
+
+    /// Stop exporting the directory.
+    #[arg(short, long, requires = "dir")]
+    unexport: bool,
+
+    /// Flush mountd's list of mounted clients.
+    #[arg(short, long, conflicts_with_all = ["dir", "unexport"])]
+    flush: bool,
+
+    /// Show how many times each export has been mounted, read from, and written to.
+    #[arg(long, conflicts_with_all = ["dir", "unexport", "flush"])]
+    stats: bool,
+
+    /// List the options of each export as well.
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// The directory to export, or to stop exporting, as an absolute path. The clients it is
+    /// exported to may be given before it, as in CLIENT[,CLIENT...]:/DIR, in place of any client.
+    /// Without a directory, the exports are listed.
+    dir: Option<String>,
+}
+
+/// A daemon with a control socket.
+struct Daemon {
+    name: &'static str,
+    socket: PathBuf,
+}
+
+impl Daemon {
+    fn connect(&self) -> Result<UnixStream, String> {
+        UnixStream::connect(&self.socket).map_err(|e| {
+            format!(
+                "{}: could not connect to {}: {e}",
+                self.name,
+                self.socket.display()
+            )
+        })
+    }
+
+    fn list(&self) -> Result<Vec<ExportEntry>, String> {
+        control::list(&mut self.connect()?).map_err(|e| format!("{}: {e}", self.name))
+    }
+
+    fn add(&self, export: &ExportEntry) -> Result<(), String> {
+        control::add(&mut self.connect()?, export).map_err(|e| format!("{}: {e}", self.name))
+    }
+
+    fn remove(&self, path: &Path) -> Result<(), String> {
+        control::remove(&mut self.connect()?, path).map_err(|e| format!("{}: {e}", self.name))
+    }
+
+    fn flush(&self) -> Result<(), String> {
+        control::flush(&mut self.connect()?).map_err(|e| format!("{}: {e}", self.name))
+    }
+
+    fn stats(&self) -> Result<Vec<ExportStats>, String> {
+        control::stats(&mut self.connect()?).map_err(|e| format!("{}: {e}", self.name))
+    }
+}
+
+impl Cli {
+    /// The daemons to administer: nfs_server first, so that an export is served before mountd
+    /// hands out its file handle.
+    fn daemons(&self) -> Result<Vec<Daemon>, String> {
+        let config = self.load_config()?;
+
+        let sockets = [
+            (
+                "nfs_server",
+                &self.nfs_server_socket,
+                config.nfs_server.control_socket,
+            ),
+            ("mountd", &self.mountd_socket, config.mountd.control_socket),
+        ];
+        let daemons: Vec<_> = sockets
+            .into_iter()
+            .filter_map(|(name, flag, configured)| {
+                let socket = flag.clone().or(configured)?;
+                Some(Daemon { name, socket })
+            })
+            .collect();
+
+        if daemons.is_empty() {
+            return Err("No control socket is configured; give one with --config, \
+                 --mountd-socket, or --nfs-server-socket"
+                .into());
+        }
+
+        Ok(daemons)
+    }
+
+    fn load_config(&self) -> Result<Config, String> {
+        match &self.config {
+            Some(path) => Config::load(path).map_err(|e| format!("Invalid configuration: {e}")),
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// The export of `dir` to `clients` that the options ask for.
+    fn export_entry(&self, clients: &[String], dir: &Path) -> Result<ExportEntry, String> {
+        let mut read_only = false;
+        let mut cookie_verifier = CookieVerifier::default();
+        let mut read_method = ReadMethod::default();
+        let mut snapshots = None;
+        for option in self.options.iter().flat_map(|o| o.split(',')) {
+            match option.split_once('=') {
+                None if option == "ro" => read_only = true,
+                None if option == "rw" => read_only = false,
+                Some(("cookieverf", value)) => {
+                    cookie_verifier = CookieVerifier::from_str(value, false)
+                        .map_err(|_| format!("Unsupported cookie verifier \"{value}\""))?;
+                }
+                Some(("read", value)) => {
+                    read_method = ReadMethod::from_str(value, false)
+                        .map_err(|_| format!("Unsupported read method \"{value}\""))?;
+                }
+                Some(("snapshots", value)) => snapshots = Some(PathBuf::from(value)),
+                _ => return Err(format!("Unsupported export option \"{option}\"")),
+            }
+        }
+
+        Ok(ExportEntry::new(dir, read_only)
+            .with_cookie_verifier(cookie_verifier)
+            .with_read_method(read_method)
+            .with_snapshots(snapshots.as_deref())
+            .with_clients(clients))
+    }
+}
+
+/// Split an argument of the form CLIENT[,CLIENT...]:/DIR, or just /DIR, into its clients and its
+/// directory. Colons within brackets are part of an IPv6 address, and the brackets are dropped.
+fn split_clients(arg: &str) -> Result<(Vec<String>, PathBuf), String> {
+    if arg.starts_with('/') {
+        return Ok((Vec::new(), arg.into()));
+    }
+
+    let mut depth = 0;
+    let separator = arg.char_indices().find(|&(i, c)| {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ => {}
+        }
+        depth == 0 && arg[i..].starts_with(":/")
+    });
+    let Some((i, _)) = separator else {
+        return Err(format!(
+            "{arg}: the directory must be an absolute path, given as /DIR or CLIENT:/DIR"
+        ));
+    };
+
+    let clients = arg[..i]
+        .split(',')
+        .map(|client| {
+            let client = client.replace(['[', ']'], "");
+            client
+                .parse::<ClientEntry>()
+                .map(|_| client)
+                .map_err(|e| format!("{arg}: {e}"))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok((clients, arg[i + 1..].into()))
+}
+
+fn main() -> ExitCode {
+    let args = Cli::parse();
+
+    let res = args.daemons().and_then(|daemons| match &args.dir {
+        Some(arg) => match split_clients(arg)? {
+            (clients, _) if args.unexport && !clients.is_empty() => Err(format!(
+                "{arg}: an export is unexported from every client, so none may be given"
+            )),
+            (_, dir) if args.unexport => unexport(&daemons, &dir),
+            (clients, dir) => {
+                warn_shadowed(&clients, &args.load_config()?)?;
+                export(&daemons, &args.export_entry(&clients, &dir)?)
+            }
+        },
+        None if args.flush => daemons.iter().try_for_each(Daemon::flush),
+        None if args.stats => stats(&daemons),
+        None => list(&daemons, args.verbose),
+    });
+
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("exportfs: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Warn of the clients that an earlier client, as resolved with the aliases of `config`, shadows.
+fn warn_shadowed(clients: &[String], config: &Config) -> Result<(), String> {
+    let resolved = Clients::resolve(clients, &config.clients)?;
+    for (shadowed, by) in resolved.shadowed() {
+        eprintln!(
+            "exportfs: warning: {} is shadowed by {}, which matches every client it does",
+            clients[shadowed], clients[by]
+        );
+    }
+
+    Ok(())
+}
+
+/// Add the export to each daemon. If a daemon refuses it, it is removed from those that took it.
+fn export(daemons: &[Daemon], export: &ExportEntry) -> Result<(), String> {
+    for (i, daemon) in daemons.iter().enumerate() {
+        if let Err(e) = daemon.add(export) {
+            for added in &daemons[..i] {
+                if let Err(e) = added.remove(export.path()) {
+                    eprintln!("exportfs: {e}");
+                }
+            }
+
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove the export from each daemon, mountd first, so that it stops handing out file handles
+/// before the NFS server stops honoring them.
+fn unexport(daemons: &[Daemon], dir: &Path) -> Result<(), String> {
+    let mut res = Ok(());
+    for daemon in daemons.iter().rev() {
+        if let Err(e) = daemon.remove(dir) {
+            eprintln!("exportfs: {e}");
+            res = Err(format!(
+                "{} was not unexported from every daemon",
+                dir.display()
+            ));
+        }
+    }
+
+    res
+}
+
+/// Print the exports of the daemons, noting any export that only some of them have.
+fn list(daemons: &[Daemon], verbose: bool) -> Result<(), String> {
+    let lists = daemons
+        .iter()
+        .map(Daemon::list)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut exports: Vec<&ExportEntry> = Vec::new();
+    for export in lists.iter().flatten() {
+        if !exports.iter().any(|e| e.path == export.path) {
+            exports.push(export);
+        }
+    }
+
+    let width = exports.iter().map(|e| e.path.len()).max().unwrap_or(0);
+    for export in exports {
+        let mut line = format!("{:width$}", export.path().display().to_string());
+        let clients = export.get_clients();
+        if !clients.is_empty() {
+            line += &format!("  {}", clients.join(","));
+        }
+        if verbose {
+            line += if export.read_only { "  (ro" } else { "  (rw" };
+            let cookie_verifier = export.get_cookie_verifier();
+            if cookie_verifier != CookieVerifier::default() {
+                let value = cookie_verifier.to_possible_value().unwrap();
+                line += &format!(",cookieverf={}", value.get_name());
+            }
+            let read_method = export.get_read_method();
+            if read_method != ReadMethod::default() {
+                let value = read_method.to_possible_value().unwrap();
+                line += &format!(",read={}", value.get_name());
+            }
+            if let Some(snapshots) = export.get_snapshots() {
+                line += &format!(",snapshots={}", snapshots.display());
+            }
+            line += ")";
+        }
+
+        let missing: Vec<_> = daemons
+            .iter()
+            .zip(&lists)
+            .filter(|(_, list)| !list.iter().any(|e| e.path == export.path))
+            .map(|(daemon, _)| daemon.name)
+            .collect();
+        if !missing.is_empty() {
+            line += &format!("  [not exported by {}]", missing.join(", "));
+        }
+
+        println!("{}", line.trim_end());
+    }
+
+    Ok(())
+}
+
+/// Print the counters that each daemon keeps for each export.
+fn stats(daemons: &[Daemon]) -> Result<(), String> {
+    let mut exports: Vec<ExportStats> = Vec::new();
+    for daemon in daemons {
+        for stats in daemon.stats()? {
+            match exports.iter_mut().find(|e| e.path == stats.path) {
+                Some(export) => export.counters.extend(stats.counters),
+                None => exports.push(stats),
+            }
+        }
+    }
+
+    for export in exports {
+        println!("{}", export.path().display());
+        for counter in export.counters {
+            println!("    {}: {}", counter.name.display(), counter.value);
+        }
+    }
+
+    Ok(())
+}
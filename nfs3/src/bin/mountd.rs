@@ -1,47 +1,335 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
-use std::net::TcpListener;
+use std::{
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
-use rpc_protocol::{server::*, Call};
+use clap::Parser;
+use log::*;
 
-use nfs3::{mount_proto::procedures::*, mount_proto::*};
+use rpc_protocol::{
+    metrics::Metrics, record::RecordLimits, server::*, tls, trace::Tracer, AuthFlavor,
+    AuthSysParms, Call,
+};
+
+use nfs3::{
+    capabilities,
+    clients::{Aliases, Clients},
+    config::{self, Config},
+    control::{self, ExportControl},
+    control_proto::{ExportEntry, ExportStats},
+    file_handle::FileHandleMap,
+    mount_proto::procedures::*,
+    mount_proto::*,
+    resolver::Resolver,
+    suite_proto::Capabilities,
+};
+
+#[derive(Parser)]
+struct Cli {
+    /// The configuration file, which may be shared with the NFS server. The flags below override
+    /// it.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// The port to listen on, in place of the port of `mountd.listen` (20048 by default).
+    #[arg(long)]
+    port: Option<u16>,
+
+    /// The directory to export (/test/nfs/export by default).
+    #[arg(long)]
+    export: Option<PathBuf>,
+
+    /// The file holding the key that protects file handles (/test/nfs/handle_key by default). It is
+    /// created if it does not exist, and must be shared with the NFS server.
+    #[arg(long)]
+    handle_key: Option<PathBuf>,
+
+    /// Check every reply before sending it: that its length is a multiple of 4, and that decoding
+    /// and encoding it again gives the same bytes, which catches padding that is not zero. Malformed
+    /// replies are logged as errors, and sent anyway. This is for debugging mountd's encoders.
+    #[arg(long)]
+    check_replies: bool,
+
+    /// Serve this suite's capability report (see `nfs3::capabilities`) on this port. It is not
+    /// served unless a port is given.
+    #[arg(long)]
+    capabilities_port: Option<u16>,
+
+    /// Serve counts of the calls to each procedure, and how long they took, on this port, as a
+    /// Prometheus metrics endpoint (see `rpc_protocol::metrics`). They are not served unless a port
+    /// is given.
+    #[arg(long)]
+    metrics_port: Option<u16>,
+
+    /// Where SIGUSR1 starts writing a trace of the calls received and replies sent, in pcap format
+    /// if the name ends in ".pcap", for Wireshark, or as text otherwise (see
+    /// `rpc_protocol::trace`). A second SIGUSR1 stops the trace, and a third starts a new one in its
+    /// place. Tracing is not possible unless a file is given.
+    #[arg(long)]
+    trace_file: Option<PathBuf>,
+
+    /// The UNIX domain socket through which exportfs changes the exports while mountd runs, in
+    /// place of `mountd.control_socket`. There is none unless one is given.
+    #[arg(long)]
+    control_socket: Option<PathBuf>,
+}
+
+impl Cli {
+    /// The configuration file, or the defaults without one, with the flags applied.
+    fn config(&self) -> Result<Config, config::Error> {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+
+        if let Some(port) = self.port {
+            config.mountd.listen.set_port(port);
+        }
+        if let Some(export) = &self.export {
+            config.export.path = export.clone();
+        }
+        if let Some(handle_key) = &self.handle_key {
+            config.export.handle_key = handle_key.clone();
+        }
+        if let Some(control_socket) = &self.control_socket {
+            config.mountd.control_socket = Some(control_socket.clone());
+        }
+        config.mountd.check_replies |= self.check_replies;
+
+        Ok(config)
+    }
+}
+
+/// An exported directory.
+struct Export {
+    /// The canonical path of the directory.
+    root: PathBuf,
+
+    /// Whether the NFS server refuses changes to it. This is only reported to exportfs.
+    read_only: bool,
+
+    /// The clients that may mount it.
+    clients: Clients,
+
+    /// The number of successful MNT calls for the directory or one below it.
+    mount_requests: u64,
+}
+
+impl Export {
+    fn new(path: &Path, read_only: bool, clients: Clients) -> io::Result<Self> {
+        Ok(Self {
+            root: path.canonicalize()?,
+            read_only,
+            clients,
+            mount_requests: 0,
+        })
+    }
+
+    /// Whether the client that made `call` may mount the export. A call whose address is not known
+    /// may only mount an export that any client may.
+    fn allows(&self, call: &Call) -> bool {
+        self.clients.allows_any()
+            || call
+                .get_peer_address()
+                .is_some_and(|address| self.clients.allows(address.ip()))
+    }
+}
+
+/// A client that has mounted a directory, and not yet unmounted it.
+#[derive(PartialEq)]
+struct Mount {
+    /// The machine name in the client's AUTH_SYS credential, which is all that a call says about
+    /// the client.
+    host: String,
+    dir: PathBuf,
+}
 
 struct MountState {
-    exports: Exports,
+    exports: Vec<Export>,
+    handles: FileHandleMap,
+
+    /// The auth flavors the NFS server accepts, in order of preference.
+    auth_flavors: Vec<i32>,
+
+    /// The client aliases of the configuration, for exports added with exportfs.
+    aliases: Aliases,
+
+    /// Resolves the host names of the clients of every export.
+    resolver: Arc<Resolver>,
+
+    /// The mount list, as returned by DUMP. It is kept when the configuration is reloaded, and
+    /// cleared by exportfs.
+    mounts: Vec<Mount>,
 }
 
+/// The state of the mount service, whose exports are replaced when the configuration is reloaded.
+type SharedState = Arc<Mutex<MountState>>;
+
 impl MountState {
-    fn new() -> Self {
-        Self {
-            exports: Exports {
-                inner: vec![ExportNode {
-                    dir: "/test/nfs/export".into(),
-                    groups: Groups {
-                        inner: vec![GroupNode {
-                            name: "localhost".into(),
-                        }],
-                    },
-                }],
-            },
+    fn new(config: &Config) -> io::Result<Self> {
+        let export = &config.export;
+        let mut handles = FileHandleMap::new(&export.handle_key)?;
+        handles.add_export(&export.path)?;
+
+        let resolver = export.resolver();
+        let clients = Clients::resolve_with(&export.clients, &config.clients, &resolver)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        Ok(Self {
+            exports: vec![Export::new(&export.path, export.read_only, clients)?],
+            handles,
+            auth_flavors: config.auth.flavors.iter().map(|f| f.number()).collect(),
+            aliases: config.clients.clone(),
+            resolver,
+            mounts: Vec::new(),
+        })
+    }
+
+    /// Switch to the exports of `new`, keeping the mount list and the counts of the exports that
+    /// are kept.
+    fn replace_exports(&mut self, mut new: MountState) {
+        for export in new.exports.iter_mut() {
+            if let Some(old) = self.exports.iter().find(|e| e.root == export.root) {
+                export.mount_requests = old.mount_requests;
+            }
         }
+        new.mounts = std::mem::take(&mut self.mounts);
+
+        *self = new;
+    }
+
+    /// The export that `path`, which must be canonical, is in.
+    fn export(&self, path: &Path) -> Option<&Export> {
+        let root = self.handles.export_of(path)?;
+
+        self.exports.iter().find(|e| e.root == root)
+    }
+
+    /// Like `export()`, but mutable.
+    fn export_mut(&mut self, path: &Path) -> Option<&mut Export> {
+        let root = self.handles.export_of(path)?;
+
+        self.exports.iter_mut().find(|e| e.root == root)
     }
 }
 
 fn main() {
-    env_logger::init();
+    let args = Cli::parse();
+    let config = match args.config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration: {e}");
+            return;
+        }
+    };
+    config.log.init();
+
+    let state = match MountState::new(&config) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("Could not export {}: {e}", config.export.path.display());
+            return;
+        }
+    };
+
+    let state = Arc::new(Mutex::new(state));
+    let address = config.mountd.listen;
+    let capabilities_port = args.capabilities_port;
+    let metrics_port = args.metrics_port;
+    let check_replies = config.mountd.check_replies;
+    let record_limits = RecordLimits {
+        max_record: config.mountd.max_record,
+        ..RecordLimits::default()
+    };
+    let timeouts = config.mountd.connection_timeouts();
+    let tls = match (&config.mountd.tls_certificate, &config.mountd.tls_key) {
+        (Some(certificate), Some(key)) => match tls::server_config(certificate, key) {
+            Ok(tls_config) => Some(tls::TlsPolicy {
+                config: tls_config,
+                required: config.mountd.tls_required,
+            }),
+            Err(e) => {
+                eprintln!("Could not set up TLS: {e}");
+                return;
+            }
+        },
+        _ => None,
+    };
+    let tracer = args.trace_file.clone().map(|path| {
+        let tracer = Tracer::new(address.port());
+        config::trace_on_sigusr1(tracer.clone(), path).map(|()| tracer)
+    });
+    let tracer = match tracer.transpose() {
+        Ok(tracer) => tracer,
+        Err(e) => {
+            eprintln!("Could not handle SIGUSR1: {e}");
+            return;
+        }
+    };
+    let shared = state.clone();
+    if let Err(e) = config::on_sighup(move || reload(&args, &shared)) {
+        eprintln!("Could not handle SIGHUP: {e}");
+        return;
+    }
+
+    if let Some(path) = &config.mountd.control_socket {
+        let policy = match config::control_policy(config.mountd.control_group.as_deref()) {
+            Ok(policy) => policy,
+            Err(e) => {
+                eprintln!("mountd.control_group: {e}");
+                return;
+            }
+        };
+        if let Err(e) = control::serve(path, Control(state.clone()), policy) {
+            eprintln!("Could not listen on {}: {e}", path.display());
+            return;
+        }
+    }
 
-    let procedures: Vec<Option<RpcProcedure<MountState>>> = vec![
+    if let Some(port) = capabilities_port {
+        let listener = bind_tcp(SocketAddr::new(address.ip(), port))
+            .unwrap_or_else(|e| panic!("Could not listen on port {port}: {e}"));
+        capabilities::serve(listener, Capabilities::new("mountd"));
+    }
+
+    let metrics = match metrics_port {
+        Some(port) => match bind_tcp(SocketAddr::new(address.ip(), port)) {
+            Ok(listener) => {
+                let metrics = Metrics::default();
+                metrics.serve_prometheus(listener);
+                Some(metrics)
+            }
+            Err(e) => {
+                eprintln!("Could not listen on port {port}: {e}");
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let procedures: Vec<Option<RpcProcedure<SharedState>>> = vec![
         None,
-        None, // mount
-        None, // dump
-        None, // umount
+        Some(mount),
+        Some(dump),
+        Some(umount),
         None, // umountall
         Some(export),
     ];
 
-    let handle = std::thread::spawn(|| {
-        let state = MountState::new();
+    let listener = match bind_tcp(address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Could not listen on {address}: {e}");
+            return;
+        }
+    };
+
+    let handle = std::thread::spawn(move || {
         let mut server = RpcProgram::new(
             MOUNT_PROGRAM,
             MOUNT_V3::VERSION,
@@ -49,12 +337,25 @@ fn main() {
             procedures,
             state,
         );
-
-        let listener = TcpListener::bind("0.0.0.0:20048").unwrap();
+        server.set_procedure_names(proc_name);
+        server.set_record_limits(record_limits);
+        server.set_connection_timeouts(timeouts);
+        if check_replies {
+            server.set_reply_check(check_result);
+        }
+        if let Some(metrics) = metrics {
+            server.set_metrics(metrics);
+        }
+        if let Some(tracer) = tracer {
+            server.set_tracer(tracer);
+        }
+        if let Some(tls) = tls {
+            server.set_tls(tls);
+        }
         server.run_blocking_tcp_server(listener);
     });
 
-    if let Err(e) = announce_self() {
+    if let Err(e) = announce_self(address) {
         eprintln!("Could not set mountd address in RPCBIND server: {e}");
         return;
     }
@@ -62,24 +363,310 @@ fn main() {
     let _ = handle.join();
 }
 
-fn export(_call: &Call, state: &mut MountState) -> RpcResult {
-    RpcResult::Success(state.exports.serialize_alloc())
+/// Read the configuration again, and switch to the export it describes, in place of any exports
+/// added with exportfs. If the configuration is invalid or the export cannot be set up, the current
+/// exports are kept.
+fn reload(args: &Cli, state: &SharedState) {
+    let config = match args.config() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Not reloading, since the configuration is invalid: {e}");
+            return;
+        }
+    };
+
+    config.log.reload();
+    match MountState::new(&config) {
+        Ok(new_state) => {
+            state.lock().unwrap().replace_exports(new_state);
+            info!(
+                "Reloaded configuration: exporting {}",
+                config.export.path.display()
+            );
+        }
+        Err(e) => error!(
+            "Not reloading, since {} cannot be exported: {e}",
+            config.export.path.display()
+        ),
+    }
+}
+
+fn export(_call: &Call, state: &mut SharedState) -> RpcResult {
+    let state = state.lock().unwrap();
+
+    let exports = Exports {
+        inner: state
+            .exports
+            .iter()
+            .map(|export| ExportNode {
+                dir: export.root.clone().into(),
+                groups: Groups {
+                    inner: match export.clients.entries() {
+                        [] => vec![GroupNode { name: "*".into() }],
+                        _ => export
+                            .clients
+                            .names()
+                            .map(|name| GroupNode {
+                                name: name.to_string().into(),
+                            })
+                            .collect(),
+                    },
+                },
+            })
+            .collect(),
+    };
+
+    RpcResult::Success(exports.serialize_alloc())
 }
 
-/// Tell the RPCBIND server that the mount service is now running:
-fn announce_self() -> Result<(), rpc_protocol::Error> {
-    let service = rpcbind::RpcService {
-        prog: MOUNT_PROGRAM,
-        vers: MOUNT_V3::VERSION,
-        netid: "tcp".into(),
-        addr: "0.0.0.0.78.80".into(),
-        owner: "superuser".into(),
+fn dump(_call: &Call, state: &mut SharedState) -> RpcResult {
+    let state = state.lock().unwrap();
+
+    let list = MountList {
+        inner: state
+            .mounts
+            .iter()
+            .map(|mount| MountBody {
+                hostname: mount.host.clone().into(),
+                directory: mount.dir.clone().into(),
+            })
+            .collect(),
     };
 
-    rpcbind::client::set(
-        service,
+    RpcResult::Success(list.serialize_alloc())
+}
+
+/// The machine name in the AUTH_SYS credential of `call`, if it has one.
+fn caller_host(call: &Call) -> String {
+    let credential = call.get_credential();
+    let mut params = AuthSysParms::default();
+    if credential.flavor == AuthFlavor::Sys && params.deserialize(&mut &credential.body[..]).is_ok()
+    {
+        return params.machinename.to_string_lossy().into_owned();
+    }
+
+    "(unknown)".into()
+}
+
+/// `path` with symbolic links resolved, and whether it exists. If it does not, the part of it that
+/// does is resolved, and the rest appended, so that the export it would be in can still be found.
+/// None if the rest is not plain names, or no part of it exists.
+fn resolve(path: &Path) -> Option<(PathBuf, bool)> {
+    if let Ok(resolved) = path.canonicalize() {
+        return Some((resolved, true));
+    }
+
+    let (resolved, rest) = path
+        .ancestors()
+        .skip(1)
+        .find_map(|a| Some((a.canonicalize().ok()?, path.strip_prefix(a).ok()?)))?;
+    if !rest
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_)))
+    {
+        return None;
+    }
+
+    Some((resolved.join(rest), false))
+}
+
+fn mount(call: &Call, state: &mut SharedState) -> RpcResult {
+    let mut args = MountArgs::default();
+    let mut arg = call.arg;
+    if args.deserialize(&mut arg).is_err() {
+        return RpcResult::GarbageArgs;
+    }
+
+    let path = PathBuf::from(args.dirpath);
+    debug!("MNT: {}", path.display());
+
+    // The client may name the export through a symbolic link. Whether the path exists, and is a
+    // directory, is only told to clients of the export that it is in, so that other clients cannot
+    // probe the server's file system:
+    let Some((path, exists)) = resolve(&path) else {
+        return RpcResult::Success(MountStatus::Access.serialize_alloc());
+    };
+
+    let mut state = state.lock().unwrap();
+    let Some(export) = state.export(&path) else {
+        debug!("MNT of {} refused: not in an export", path.display());
+        return RpcResult::Success(MountStatus::Access.serialize_alloc());
+    };
+    if !export.allows(call) {
+        let client = call.get_peer_address().map(|a| a.ip().to_string());
+        warn!(
+            "MNT of {} refused for {}, which is not one of the export's clients",
+            path.display(),
+            client.as_deref().unwrap_or("a client of unknown address")
+        );
+        return RpcResult::Success(MountStatus::Access.serialize_alloc());
+    }
+
+    if !exists {
+        return RpcResult::Success(MountStatus::NoEnt.serialize_alloc());
+    }
+    if !path.is_dir() {
+        return RpcResult::Success(MountStatus::NotDir.serialize_alloc());
+    }
+
+    match state.handles.handle_for(&path) {
+        Ok(fhandle) => {
+            if let Some(export) = state.export_mut(&path) {
+                export.mount_requests += 1;
+            }
+            let mount = Mount {
+                host: caller_host(call),
+                dir: path,
+            };
+            if !state.mounts.contains(&mount) {
+                state.mounts.push(mount);
+            }
+
+            let result = MountResult::Ok(MountResultOk {
+                fhandle,
+                auth_flavors: state.auth_flavors.clone(),
+            });
+            RpcResult::Success(result.serialize_alloc())
+        }
+        Err(e) => {
+            warn!("MNT of {} refused: {e}", path.display());
+            let status = match e.kind() {
+                io::ErrorKind::NotFound => MountStatus::NoEnt,
+                io::ErrorKind::PermissionDenied => MountStatus::Access,
+                _ => MountStatus::Io,
+            };
+            RpcResult::Success(status.serialize_alloc())
+        }
+    }
+}
+
+/// UMNT removes the caller's entry for the directory from the mount list.
+fn umount(call: &Call, state: &mut SharedState) -> RpcResult {
+    let mut args = MountArgs::default();
+    let mut arg = call.arg;
+    if args.deserialize(&mut arg).is_err() {
+        return RpcResult::GarbageArgs;
+    }
+
+    let path = PathBuf::from(args.dirpath);
+    debug!("UMNT: {}", path.display());
+
+    let mount = Mount {
+        host: caller_host(call),
+        dir: path.canonicalize().unwrap_or(path),
+    };
+    state.lock().unwrap().mounts.retain(|m| *m != mount);
+
+    RpcResult::Success(Vec::new())
+}
+
+/// Answers control requests from exportfs.
+struct Control(SharedState);
+
+impl ExportControl for Control {
+    fn list(&mut self) -> Vec<ExportEntry> {
+        let state = self.0.lock().unwrap();
+
+        state
+            .exports
+            .iter()
+            .map(|e| ExportEntry::new(&e.root, e.read_only).with_clients(e.clients.entries()))
+            .collect()
+    }
+
+    fn add(&mut self, entry: &ExportEntry) -> Result<(), String> {
+        let mut state = self.0.lock().unwrap();
+
+        let clients = Clients::resolve_with(&entry.get_clients(), &state.aliases, &state.resolver)?;
+        Export::new(entry.path(), entry.read_only, clients)
+            .and_then(|export| {
+                state.handles.add_export(&export.root)?;
+                info!("Exporting {}", export.root.display());
+                state.exports.push(export);
+                Ok(())
+            })
+            .map_err(|e| format!("Could not export {}: {e}", entry.path().display()))
+    }
+
+    fn remove(&mut self, path: &Path) -> Result<(), String> {
+        let mut state = self.0.lock().unwrap();
+
+        let root = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        state
+            .handles
+            .remove_export(&root)
+            .map_err(|e| e.to_string())?;
+        state.exports.retain(|e| e.root != root);
+        info!("No longer exporting {}", root.display());
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        let mut state = self.0.lock().unwrap();
+
+        info!(
+            "Flushing {} entries from the mount list",
+            state.mounts.len()
+        );
+        state.mounts.clear();
+
+        Ok(())
+    }
+
+    fn stats(&mut self) -> Vec<ExportStats> {
+        let state = &*self.0.lock().unwrap();
+
+        state
+            .exports
+            .iter()
+            .map(|export| {
+                let mounts = state
+                    .mounts
+                    .iter()
+                    .filter(|m| state.handles.export_of(&m.dir) == Some(export.root.as_path()))
+                    .count();
+
+                ExportStats::new(&export.root)
+                    .counter("mounts", mounts as u64)
+                    .counter("mount-requests", export.mount_requests)
+            })
+            .collect()
+    }
+}
+
+/// Tell the RPCBIND server that the mount service is now running at `address`:
+fn announce_self(address: SocketAddr) -> Result<(), rpc_protocol::Error> {
+    rpcbind::client::set_tcp(
+        MOUNT_PROGRAM,
+        MOUNT_V3::VERSION,
+        address,
         rpcbind::RpcbindServerAddress::Tcp("0.0.0.0:111".to_string()),
     )?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_resolved() {
+        let dir = std::env::temp_dir().join(format!("mountd_resolve_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("export")).unwrap();
+        std::os::unix::fs::symlink(dir.join("export"), dir.join("link")).unwrap();
+        let export = dir.join("export").canonicalize().unwrap();
+
+        assert_eq!(resolve(&dir.join("link")), Some((export.clone(), true)));
+        assert_eq!(
+            resolve(&dir.join("link/missing/sub")),
+            Some((export.join("missing/sub"), false))
+        );
+        // Paths that do not exist are not resolved past their parent directories:
+        assert_eq!(resolve(&dir.join("export/missing/../../link")), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,12 +1,39 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
-use std::{io, net::TcpStream};
+use std::{
+    fmt::{self, Debug},
+    fs::File,
+    io::{self, Read, Write},
+    net::TcpStream,
+    path::{Component, Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
 
-use ::nfs3::{nfs3_xdr::procedures::*, nfs3_xdr::*};
-use rpc_protocol::client::*;
+use ::nfs3::{
+    capabilities, mount,
+    nfs3_xdr::{procedures::*, *},
+    read_digest, write_digest,
+};
+use rpc_protocol::{client::*, AuthFlavor, OpaqueAuth};
+use xdr_lib::display;
+
+/// How long to wait for the RPCBIND server and the mount service.
+const MOUNT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many bytes to ask for in each READ call.
+const READ_CHUNK: u32 = 64 * 1024;
+
+/// How many bytes to send in each WRITE call. This is small enough for a WRITE call to fit in one
+/// receive buffer of this crate's NFS server.
+const WRITE_CHUNK: usize = 2048;
+
+/// How many bytes of directory entries to ask for in each READDIR or READDIRPLUS call.
+const READDIR_CHUNK: u32 = 8192;
 
 #[derive(Debug, Parser)]
 struct Cli {
@@ -16,51 +43,882 @@ struct Cli {
     #[arg(long, default_value_t = 2049)]
     port: u16,
 
+    /// The port of the mount service, which is used to get the file handle of the export when
+    /// a file is named by its path. By default, the port is looked up with the RPCBIND server on
+    /// the host.
+    #[arg(long)]
+    mount_port: Option<u16>,
+
+    /// The auth flavor to send NFS calls with. By default, the strongest flavor that both this
+    /// client and the server support is used, as listed by the server when the export is mounted;
+    /// calls made without mounting the export are sent with AUTH_NONE.
+    #[arg(long, value_enum)]
+    auth: Option<Auth>,
+
+    /// The exported directory that paths are relative to.
+    #[arg(long, default_value = "/test/nfs/export")]
+    export: PathBuf,
+
+    /// How to print procedure results.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
     #[clap(subcommand)]
     command: Command,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Auth {
+    /// AUTH_NONE: calls are anonymous.
+    None,
+
+    /// AUTH_SYS: calls carry the user and group ids of this process.
+    Sys,
+}
+
+impl Auth {
+    fn flavor(self) -> AuthFlavor {
+        match self {
+            Self::None => AuthFlavor::None,
+            Self::Sys => AuthFlavor::Sys,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum OutputFormat {
+    /// A human-readable summary of the result.
+    Text,
+
+    /// The decoded result as JSON.
+    Json,
+
+    /// The XDR encoding of the result as received from the server, in hexadecimal.
+    Hex,
+}
+
 #[derive(Debug, Subcommand)]
 enum Command {
-    /// Perform a getattr RPC.
+    /// Print the attributes of a file.
     Getattr {
+        #[command(flatten)]
+        target: Target,
+    },
+
+    /// Print the file handle and attributes of a file.
+    Lookup {
+        /// The path of the file, relative to the export.
+        path: PathBuf,
+    },
+
+    /// Read a file, and write its contents to stdout or to a local file.
+    ///
+    /// With `--output json` or `--output hex`, the result of each READ is printed instead.
+    Read {
+        #[command(flatten)]
+        target: Target,
+
+        #[arg(long, default_value_t = 0)]
+        offset: u64,
+
+        /// The number of bytes to read. By default, the file is read up to its end.
+        #[arg(long)]
+        count: Option<u64>,
+
+        /// Ask the server for a checksum of the data, and verify it. Only supported by this
+        /// crate's NFS server.
+        #[arg(long)]
+        digest: bool,
+
+        /// Write the data to this local file instead of stdout.
+        #[arg(long)]
+        out_file: Option<PathBuf>,
+    },
+
+    /// Write the contents of stdin or of a local file to an existing file.
+    Write {
+        /// The path of the file, relative to the export.
+        path: PathBuf,
+
+        #[arg(long, default_value_t = 0)]
+        offset: u64,
+
+        /// How the server must store the data before replying. UNSTABLE writes are followed by a
+        /// COMMIT.
+        #[arg(long, value_enum, default_value_t = Stability::Unstable)]
+        stable: Stability,
+
+        /// Read the data from this local file instead of stdin.
+        #[arg(long)]
+        in_file: Option<PathBuf>,
+
+        /// Send a checksum of the data of each WRITE for the server to check it against, and
+        /// verify that it did. Only supported by this crate's NFS server, with `--integrity`.
+        #[arg(long)]
+        digest: bool,
+    },
+
+    /// List the entries of a directory.
+    Readdir {
+        /// The path of the directory, relative to the export.
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// Print the type, mode, link count, owner, group, and size of each entry, like `ls -l`.
         #[arg(short, long)]
-        filehandle: u64,
+        long: bool,
+    },
+
+    /// Create a directory.
+    Mkdir {
+        /// The path of the new directory, relative to the export.
+        path: PathBuf,
+
+        /// The mode of the new directory, in octal.
+        #[arg(long, default_value = "755", value_parser = parse_mode)]
+        mode: u32,
+    },
+
+    /// Remove a file.
+    Remove {
+        /// The path of the file, relative to the export.
+        path: PathBuf,
+    },
+
+    /// Print the version, enabled features, and limits of one of this suite's daemons. No NFS
+    /// server is needed.
+    Capabilities {
+        /// The port the daemon was given with `--capabilities-port`.
+        port: u16,
     },
 }
 
-fn main() -> io::Result<()> {
-    let args = Cli::parse();
-    eprintln!("{args:?}");
+/// A file, named either by its path or by its file handle.
+#[derive(Debug, Args)]
+struct Target {
+    /// The path of the file, relative to the export.
+    #[arg(required_unless_present = "filehandle")]
+    path: Option<PathBuf>,
+
+    /// The file handle, in hexadecimal.
+    #[arg(short, long, value_parser = parse_filehandle, conflicts_with = "path")]
+    filehandle: Option<FileHandle>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Stability {
+    Unstable,
+    DataSync,
+    FileSync,
+}
+
+impl From<Stability> for StableHow {
+    fn from(stable: Stability) -> Self {
+        match stable {
+            Stability::Unstable => StableHow::Unstable,
+            Stability::DataSync => StableHow::DataSync,
+            Stability::FileSync => StableHow::FileSync,
+        }
+    }
+}
+
+fn parse_filehandle(s: &str) -> Result<FileHandle, String> {
+    if !s.len().is_multiple_of(2) {
+        return Err("file handle must have an even number of hex digits".to_string());
+    }
+
+    let data = (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(FileHandle { data })
+}
+
+fn parse_mode(s: &str) -> Result<u32, String> {
+    match u32::from_str_radix(s, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err("mode must be an octal number no greater than 7777".to_string()),
+    }
+}
+
+#[derive(Debug)]
+enum Error {
+    Rpc(rpc_protocol::Error),
+    Io(io::Error),
+    Digest(read_digest::Error),
+    WriteDigest(write_digest::Error),
+
+    /// The result of the named procedure could not be decoded.
+    Decode(&'static str),
+
+    /// The export could not be mounted.
+    Mount(mount::client::Error),
+
+    /// The named procedure failed.
+    Nfs(&'static str, NfsResult),
+
+    /// The path does not name a file that can be created or removed.
+    InvalidPath(PathBuf),
+
+    /// The server's write verifier changed between the WRITEs and the COMMIT, meaning it restarted
+    /// and may have lost the data.
+    VerifierChanged,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Rpc(e) => write!(f, "{e}"),
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Digest(e) => write!(f, "{e}"),
+            Self::WriteDigest(e) => write!(f, "{e}"),
+            Self::Decode(procedure) => write!(f, "Could not decode {procedure} result"),
+            Self::Mount(e) => write!(f, "{e}"),
+            Self::Nfs(procedure, status) => write!(f, "{procedure} failed: {status}"),
+            Self::InvalidPath(path) => write!(f, "Invalid path: {}", path.display()),
+            Self::VerifierChanged => write!(
+                f,
+                "The server restarted before the data was committed; it may have been lost"
+            ),
+        }
+    }
+}
+
+impl From<rpc_protocol::Error> for Error {
+    fn from(e: rpc_protocol::Error) -> Self {
+        Self::Rpc(e)
+    }
+}
+
+impl From<mount::client::Error> for Error {
+    fn from(e: mount::client::Error) -> Self {
+        Self::Mount(e)
+    }
+}
 
-    let mut stream = TcpStream::connect(format!("{}:{}", args.hostname, args.port))?;
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
 
-    match args.command {
-        Command::Getattr { filehandle } => do_getattr(&mut stream, filehandle),
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Io(e.into())
     }
 }
 
-fn do_getattr(stream: &mut TcpStream, fh: u64) -> io::Result<()> {
-    let arg = GetAttrArgs {
-        object: FileHandle {
-            data: Vec::from(fh.to_be_bytes()),
-        },
+/// The result of an NFS procedure: a union whose `Ok` arm holds `Success`.
+trait ProcedureResult: Default + Serialize {
+    type Success;
+
+    fn decode(&mut self, input: &mut &[u8]) -> xdr_lib::Result<()>;
+
+    fn success(self) -> Option<Self::Success>;
+}
+
+macro_rules! procedure_results {
+    ($($result:ident => $success:ident),* $(,)?) => {
+        $(
+            impl ProcedureResult for $result {
+                type Success = $success;
+
+                fn decode(&mut self, input: &mut &[u8]) -> xdr_lib::Result<()> {
+                    self.deserialize(input)
+                }
+
+                fn success(self) -> Option<$success> {
+                    self.ok()
+                }
+            }
+        )*
     };
+}
 
-    let arg = arg.serialize_alloc();
+procedure_results!(
+    GetAttrResult => GetAttrSuccess,
+    LookupResult => LookupSuccess,
+    WriteResult => WriteSuccess,
+    MkdirResult => MkdirSuccess,
+    RemoveResult => RemoveSuccess,
+    ReadDirResult => ReadDirSuccess,
+    ReadDirPlusResult => ReadDirPlusSuccess,
+    CommitResult => CommitSuccess,
+);
 
-    let res = do_rpc_call(stream, NFS_PROGRAM, NFS_V3::VERSION, NFS_V3::GETATTR, &arg);
+fn procedure_name(procedure: u32) -> &'static str {
+    proc_name(NFS_V3::VERSION, procedure).unwrap_or("NFS procedure")
+}
 
-    match res {
-        Ok(bytes) => {
-            let mut res = GetAttrResult::default();
-            res.deserialize(&mut bytes.as_slice()).unwrap();
-            eprintln!("Success: {res:?}");
+/// The status of a procedure result, which is encoded first in every result.
+fn result_status(raw: &[u8]) -> Option<NfsResult> {
+    let mut status = NfsResult::default();
+    status.deserialize(&mut &raw[..]).ok()?;
+
+    Some(status)
+}
+
+struct Client {
+    stream: TcpStream,
+    format: OutputFormat,
+
+    hostname: String,
+    mount_port: Option<u16>,
+    export: PathBuf,
+
+    /// The flavor given with `--auth`, if any.
+    auth: Option<AuthFlavor>,
+
+    /// The credential NFS calls are sent with.
+    cred: OpaqueAuth,
+
+    /// The file handle of the export, once it has been mounted.
+    root: Option<FileHandle>,
+}
+
+impl Client {
+    fn new(args: &Cli) -> Result<Self, Error> {
+        Ok(Self {
+            stream: TcpStream::connect((args.hostname.as_str(), args.port))?,
+            format: args.output,
+            hostname: args.hostname.clone(),
+            mount_port: args.mount_port,
+            export: args.export.clone(),
+            auth: args.auth.map(Auth::flavor),
+            cred: args
+                .auth
+                .and_then(|auth| mount::client::credential(&auth.flavor()))
+                .unwrap_or_else(OpaqueAuth::none),
+            root: None,
+        })
+    }
+
+    /// Print a procedure result in the JSON or hex format. `raw` is the XDR encoding that `res` was
+    /// decoded from. In the text format, each command prints its results itself.
+    fn print_result<T: Serialize>(&self, res: &T, raw: &[u8]) -> Result<(), Error> {
+        match self.format {
+            OutputFormat::Text => {}
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(res)?),
+            OutputFormat::Hex => println!("{}", hex(raw)),
+        };
+
+        Ok(())
+    }
+
+    /// Call an NFS procedure, and return its result if it succeeded. If `print` is set, the result
+    /// is printed in the JSON or hex format whether or not it succeeded.
+    fn call<R: ProcedureResult>(
+        &mut self,
+        procedure: u32,
+        args: &[u8],
+        print: bool,
+    ) -> Result<R::Success, Error> {
+        let (_, raw) = do_rpc_call_with_auth(
+            &mut self.stream,
+            NFS_PROGRAM,
+            NFS_V3::VERSION,
+            procedure,
+            self.cred.clone(),
+            OpaqueAuth::none(),
+            args,
+        )?;
+
+        let name = procedure_name(procedure);
+        let mut res = R::default();
+        res.decode(&mut raw.as_slice())
+            .map_err(|_| Error::Decode(name))?;
+
+        if print {
+            self.print_result(&res, &raw)?;
         }
-        Err(e) => {
-            eprintln!("{e:?}");
+
+        let status = result_status(&raw).ok_or(Error::Decode(name))?;
+        res.success().ok_or(Error::Nfs(name, status))
+    }
+
+    /// The file handle of the export, which is mounted on first use.
+    fn root(&mut self) -> Result<FileHandle, Error> {
+        if let Some(root) = &self.root {
+            return Ok(root.clone());
+        }
+
+        let export = match self.mount_port {
+            Some(port) => {
+                let mut stream = TcpStream::connect((self.hostname.as_str(), port))?;
+                mount::client::mnt(&mut stream, &self.export)?
+            }
+            None => mount::client::mount(&self.hostname, &self.export, MOUNT_TIMEOUT)?,
+        };
+
+        let flavor = match &self.auth {
+            Some(flavor) => export.select_flavor(std::slice::from_ref(flavor))?,
+            None => export.select_flavor(mount::client::CLIENT_FLAVORS)?,
+        };
+        // Every flavor that can be selected is one that credentials can be made for:
+        self.cred = mount::client::credential(&flavor).unwrap();
+
+        let root = export.root;
+        self.root = Some(root.clone());
+
+        Ok(root)
+    }
+
+    /// Look up each component of `path` in turn, starting from the root of the export. Returns the
+    /// result of the last LOOKUP, or None if `path` names the root itself.
+    ///
+    /// If `print` is set, the result of the last LOOKUP is printed in the JSON or hex format.
+    fn resolve(&mut self, path: &Path, print: bool) -> Result<Option<LookupSuccess>, Error> {
+        let names: Vec<_> = path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_os_string()),
+                Component::ParentDir => Some("..".into()),
+                Component::RootDir | Component::CurDir | Component::Prefix(_) => None,
+            })
+            .collect();
+
+        let mut dir = self.root()?;
+        let mut found = None;
+        let count = names.len();
+
+        for (i, name) in names.into_iter().enumerate() {
+            let args = LookupArgs {
+                what: DirOpArgs { dir, name },
+            };
+
+            let last = i + 1 == count;
+            let res =
+                self.call::<LookupResult>(NFS_V3::LOOKUP, &args.serialize_alloc(), print && last)?;
+
+            dir = res.object.clone();
+            found = Some(res);
+        }
+
+        Ok(found)
+    }
+
+    /// The file handle of the file at `path`.
+    fn handle_for(&mut self, path: &Path) -> Result<FileHandle, Error> {
+        match self.resolve(path, false)? {
+            Some(found) => Ok(found.object),
+            None => self.root(),
+        }
+    }
+
+    fn target_handle(&mut self, target: Target) -> Result<FileHandle, Error> {
+        match (target.filehandle, target.path) {
+            (Some(filehandle), _) => Ok(filehandle),
+            (None, Some(path)) => self.handle_for(&path),
+            (None, None) => unreachable!("clap requires a path or a file handle"),
+        }
+    }
+
+    /// The file handle of the directory that holds `path`, and the name of `path` in it.
+    fn parent_of(&mut self, path: &Path) -> Result<DirOpArgs, Error> {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(Error::InvalidPath(path.to_path_buf()));
+        };
+
+        Ok(DirOpArgs {
+            dir: self.handle_for(parent)?,
+            name: name.to_os_string(),
+        })
+    }
+
+    fn getattr(&mut self, target: Target) -> Result<(), Error> {
+        let object = self.target_handle(target)?;
+        let args = GetAttrArgs { object };
+
+        let res = self.call::<GetAttrResult>(NFS_V3::GETATTR, &args.serialize_alloc(), true)?;
+        if self.format == OutputFormat::Text {
+            print_attributes(&res.obj_attributes);
+        }
+
+        Ok(())
+    }
+
+    fn lookup(&mut self, path: &Path) -> Result<(), Error> {
+        let Some(res) = self.resolve(path, true)? else {
+            // The path names the root of the export, which is not looked up:
+            let root = self.root()?;
+            if self.format == OutputFormat::Text {
+                println!("handle: {}", hex(&root.data));
+            }
+            return Ok(());
+        };
+
+        if self.format == OutputFormat::Text {
+            println!("handle: {}", hex(&res.object.data));
+            if let Some(attributes) = &res.obj_attributes.inner {
+                print_attributes(attributes);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &mut self,
+        target: Target,
+        mut offset: u64,
+        count: Option<u64>,
+        digest: bool,
+        out_file: Option<PathBuf>,
+    ) -> Result<(), Error> {
+        let file = self.target_handle(target)?;
+
+        let mut out: Box<dyn Write> = match out_file {
+            Some(path) => Box::new(File::create(path)?),
+            None if self.format == OutputFormat::Text => Box::new(io::stdout().lock()),
+            None => Box::new(io::sink()),
+        };
+
+        let mut remaining = count.unwrap_or(u64::MAX);
+        while remaining > 0 {
+            let args = ReadArgs {
+                file: file.clone(),
+                offset,
+                count: remaining.min(READ_CHUNK as u64) as u32,
+            };
+
+            let (res, raw) = read_digest::read(&mut self.stream, &args, self.cred.clone(), digest)
+                .map_err(|e| match e {
+                    read_digest::Error::Rpc(e) => Error::Rpc(e),
+                    e => Error::Digest(e),
+                })?;
+            self.print_result(&res, &raw)?;
+
+            let ReadResult::Ok(res) = res else {
+                let status = result_status(&raw).ok_or(Error::Decode("READ"))?;
+                return Err(Error::Nfs("READ", status));
+            };
+
+            out.write_all(&res.data)?;
+            offset += res.data.len() as u64;
+            remaining -= res.data.len() as u64;
+
+            if res.eof || res.data.is_empty() {
+                break;
+            }
+        }
+
+        out.flush()?;
+
+        Ok(())
+    }
+
+    fn write(
+        &mut self,
+        path: &Path,
+        mut offset: u64,
+        stable: Stability,
+        in_file: Option<PathBuf>,
+        digest: bool,
+    ) -> Result<(), Error> {
+        let file = self.handle_for(path)?;
+
+        let mut data = Vec::new();
+        match in_file {
+            Some(in_file) => File::open(in_file)?.read_to_end(&mut data)?,
+            None => io::stdin().lock().read_to_end(&mut data)?,
+        };
+
+        let start = offset;
+        let mut verf = None;
+        let mut unstable = false;
+        let mut rest = data.as_slice();
+
+        while !rest.is_empty() {
+            let chunk = &rest[..rest.len().min(WRITE_CHUNK)];
+            let args = WriteArgs {
+                file: file.clone(),
+                offset,
+                count: chunk.len() as u32,
+                stable: stable.into(),
+                data: chunk.to_vec(),
+            };
+
+            let res = match digest {
+                true => self.write_with_digest(&args)?,
+                false => self.call::<WriteResult>(NFS_V3::WRITE, &args.serialize_alloc(), true)?,
+            };
+            if verf.is_some_and(|verf| verf != res.verf) {
+                return Err(Error::VerifierChanged);
+            }
+            verf = Some(res.verf);
+            unstable |= res.committed == StableHow::Unstable;
+
+            // The server may write less than it was sent, in which case the rest is sent again:
+            let written = (res.count as usize).min(chunk.len());
+            offset += written as u64;
+            rest = &rest[written..];
+        }
+
+        if unstable {
+            let args = CommitArgs {
+                file,
+                offset: start,
+                count: data.len() as u32,
+            };
+
+            let res = self.call::<CommitResult>(NFS_V3::COMMIT, &args.serialize_alloc(), true)?;
+            if verf.is_some_and(|verf| verf != res.verf) {
+                return Err(Error::VerifierChanged);
+            }
+        }
+
+        if self.format == OutputFormat::Text {
+            println!("wrote {} bytes to {}", data.len(), path.display());
+        }
+
+        Ok(())
+    }
+
+    /// Like `call()` for a WRITE, sending a checksum of its data, which the server must check.
+    fn write_with_digest(&mut self, args: &WriteArgs) -> Result<WriteSuccess, Error> {
+        let (res, raw) = write_digest::write(&mut self.stream, args, self.cred.clone(), true)
+            .map_err(|e| match e {
+                write_digest::Error::Rpc(e) => Error::Rpc(e),
+                e => Error::WriteDigest(e),
+            })?;
+        self.print_result(&res, &raw)?;
+
+        match res {
+            WriteResult::Ok(res) => Ok(res),
+            _ => {
+                let status = result_status(&raw).ok_or(Error::Decode("WRITE"))?;
+                Err(Error::Nfs("WRITE", status))
+            }
+        }
+    }
+
+    fn readdir(&mut self, path: &Path, long: bool) -> Result<(), Error> {
+        let dir = self.handle_for(path)?;
+        let mut cookie = 0;
+        let mut cookieverf = [0; NFS3_COOKIEVERFSIZE as usize];
+
+        loop {
+            let eof = if long {
+                let args = ReadDirPlusArgs {
+                    dir: dir.clone(),
+                    cookie,
+                    cookieverf,
+                    dircount: READDIR_CHUNK,
+                    maxcount: READDIR_CHUNK * 4,
+                };
+
+                let res = self.call::<ReadDirPlusResult>(
+                    NFS_V3::READDIRPLUS,
+                    &args.serialize_alloc(),
+                    true,
+                )?;
+                cookieverf = res.cookieverf;
+
+                for entry in res.reply.entries.iter() {
+                    cookie = entry.cookie;
+                    if self.format == OutputFormat::Text {
+                        print_long_entry(entry);
+                    }
+                }
+
+                res.reply.eof
+            } else {
+                let args = ReadDirArgs {
+                    dir: dir.clone(),
+                    cookie,
+                    cookieverf,
+                    count: READDIR_CHUNK,
+                };
+
+                let res =
+                    self.call::<ReadDirResult>(NFS_V3::READDIR, &args.serialize_alloc(), true)?;
+                cookieverf = res.cookieverf;
+
+                for entry in res.reply.entries.iter() {
+                    cookie = entry.cookie;
+                    if self.format == OutputFormat::Text {
+                        println!("{}", entry.name.display());
+                    }
+                }
+
+                res.reply.eof
+            };
+
+            if eof {
+                return Ok(());
+            }
+        }
+    }
+
+    fn mkdir(&mut self, path: &Path, mode: u32) -> Result<(), Error> {
+        let args = MkdirArgs {
+            r#where: self.parent_of(path)?,
+            attributes: SetAttributes {
+                mode: SetMode { inner: Some(mode) },
+                ..Default::default()
+            },
+        };
+
+        let res = self.call::<MkdirResult>(NFS_V3::MKDIR, &args.serialize_alloc(), true)?;
+        if self.format == OutputFormat::Text {
+            println!("created {}", path.display());
+            if let Some(handle) = &res.obj.inner {
+                println!("handle: {}", hex(&handle.data));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, path: &Path) -> Result<(), Error> {
+        let args = RemoveArgs {
+            object: self.parent_of(path)?,
+        };
+
+        let _ = self.call::<RemoveResult>(NFS_V3::REMOVE, &args.serialize_alloc(), true)?;
+        if self.format == OutputFormat::Text {
+            println!("removed {}", path.display());
+        }
+
+        Ok(())
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    display::Hex(bytes).to_string()
+}
+
+fn file_type_name(file_type: &FileType) -> &'static str {
+    match file_type {
+        FileType::Reg => "regular file",
+        FileType::Dir => "directory",
+        FileType::Blk => "block device",
+        FileType::Chr => "character device",
+        FileType::Lnk => "symbolic link",
+        FileType::Sock => "socket",
+        FileType::Fifo => "fifo",
+    }
+}
+
+fn print_attributes(attributes: &FileAttributes) {
+    println!("type:   {}", file_type_name(&attributes.r#type));
+    println!("mode:   {:04o}", attributes.mode);
+    println!("nlink:  {}", attributes.nlink);
+    println!("uid:    {}", attributes.uid);
+    println!("gid:    {}", attributes.gid);
+    println!("size:   {}", display::Size(attributes.size));
+    println!("used:   {}", display::Size(attributes.used));
+    println!("fsid:   {:#x}", attributes.fsid);
+    println!("fileid: {}", attributes.fileid);
+    println!("atime:  {}", attributes.atime);
+    println!("mtime:  {}", attributes.mtime);
+    println!("ctime:  {}", attributes.ctime);
+}
+
+/// The type and permissions of a file in the style of `ls -l`, such as "drwxr-xr-x".
+fn mode_string(attributes: &FileAttributes) -> String {
+    let file_type = match attributes.r#type {
+        FileType::Reg => '-',
+        FileType::Dir => 'd',
+        FileType::Blk => 'b',
+        FileType::Chr => 'c',
+        FileType::Lnk => 'l',
+        FileType::Sock => 's',
+        FileType::Fifo => 'p',
+    };
+
+    let mut s = String::from(file_type);
+    for shift in [6, 3, 0] {
+        let bits = attributes.mode >> shift;
+        s.push(if bits & 4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 2 != 0 { 'w' } else { '-' });
+        s.push(if bits & 1 != 0 { 'x' } else { '-' });
+    }
+
+    s
+}
+
+fn print_long_entry(entry: &EntryPlus) {
+    match &entry.name_attributes.inner {
+        Some(attributes) => println!(
+            "{} {:>3} {:>5} {:>5} {:>10} {}",
+            mode_string(attributes),
+            attributes.nlink,
+            attributes.uid,
+            attributes.gid,
+            attributes.size,
+            entry.name.display()
+        ),
+        None => println!("{:?} {}", "?", entry.name.display()),
+    }
+}
+
+fn print_capabilities(hostname: &str, port: u16, format: OutputFormat) -> Result<(), Error> {
+    let mut stream = TcpStream::connect((hostname, port))?;
+    let (report, raw) = capabilities::capabilities(&mut stream)?;
+
+    match format {
+        OutputFormat::Text => {
+            println!("daemon:   {}", report.daemon.to_string_lossy());
+            println!("version:  {}", report.release.to_string_lossy());
+            let features: Vec<_> = report
+                .features
+                .iter()
+                .map(|f| f.to_string_lossy())
+                .collect();
+            println!("features: {}", features.join(" "));
+            for limit in &report.limits {
+                println!("{}: {}", limit.name.to_string_lossy(), limit.value);
+            }
         }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Hex => println!("{}", hex(&raw)),
     };
 
     Ok(())
 }
+
+fn main() -> ExitCode {
+    let args = Cli::parse();
+
+    if let Command::Capabilities { port } = args.command {
+        return exit_code(print_capabilities(&args.hostname, port, args.output));
+    }
+
+    let res = Client::new(&args).and_then(|mut client| match args.command {
+        Command::Getattr { target } => client.getattr(target),
+        Command::Lookup { path } => client.lookup(&path),
+        Command::Read {
+            target,
+            offset,
+            count,
+            digest,
+            out_file,
+        } => client.read(target, offset, count, digest, out_file),
+        Command::Write {
+            path,
+            offset,
+            stable,
+            in_file,
+            digest,
+        } => client.write(&path, offset, stable, in_file, digest),
+        Command::Readdir { path, long } => client.readdir(&path, long),
+        Command::Mkdir { path, mode } => client.mkdir(&path, mode),
+        Command::Remove { path } => client.remove(&path),
+        Command::Capabilities { .. } => unreachable!(),
+    });
+
+    exit_code(res)
+}
+
+fn exit_code(res: Result<(), Error>) -> ExitCode {
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("nfs_cli: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
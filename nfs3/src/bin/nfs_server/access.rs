@@ -0,0 +1,207 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The ACCESS procedure, which tells a client which kinds of access to a file its caller has.
+//
+// Access is evaluated from the mode, owner, and group of the file, for the caller's identity as
+// mapped by `identity`: the owner bits apply to the owner of the file, the group bits to members of
+// its group, and the other bits to everyone else. The root user (when not squashed) may read and
+// modify any file, and execute any file that anyone can execute. Nothing that would change the
+// export is granted when it is read-only. As RFC 1813 allows, the result is only a hint: the server
+// still checks the permissions of each operation when it is performed.
+
+use log::*;
+use nfs3::nfs3_xdr::*;
+use rpc_protocol::{ring::*, Principal};
+
+use crate::{results::*, ServerState};
+
+const READ: u32 = 0o4;
+const WRITE: u32 = 0o2;
+const EXECUTE: u32 = 0o1;
+
+pub fn access(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, AccessArgs);
+
+    debug!("ACCESS: {:#x}", args.access);
+
+    reply::<AccessResult>(|| {
+        let path = state.lookup(call, &args.object.data)?;
+        let attributes = state.vfs.getattr(&path)?;
+
+        Ok(AccessSuccess {
+            access: granted(
+                &attributes,
+                &state.principal(call, &path),
+                args.access,
+                state.is_read_only(&path),
+            ),
+            obj_attributes: PostOpAttributes {
+                inner: Some(attributes),
+            },
+        })
+    })
+}
+
+/// The subset of the ACCESS3 bits in `requested` that `principal` is granted to a file with the
+/// given attributes.
+fn granted(file: &FileAttributes, principal: &Principal, requested: u32, read_only: bool) -> u32 {
+    let mode = file.mode;
+    let is_dir = file.r#type == FileType::Dir;
+
+    // The permission bits that apply to the caller, or None for root:
+    let bits = match principal {
+        Principal::Unix { uid: 0, .. } => None,
+        Principal::Unix { uid, .. } if *uid == file.uid => Some(mode >> 6),
+        Principal::Unix { gid, gids, .. } if *gid == file.gid || gids.contains(&file.gid) => {
+            Some(mode >> 3)
+        }
+        _ => Some(mode),
+    };
+    let allowed = |perm: u32| match bits {
+        Some(bits) => bits & perm == perm,
+        // Root can read and write anything, but only execute what someone can execute:
+        None if perm & EXECUTE != 0 => mode & 0o111 != 0 || is_dir,
+        None => true,
+    };
+
+    let mut granted = 0;
+    if allowed(READ) {
+        granted |= ACCESS3_READ;
+    }
+    if is_dir {
+        if allowed(EXECUTE) {
+            granted |= ACCESS3_LOOKUP;
+        }
+        // Adding or removing an entry needs search permission as well:
+        if allowed(WRITE | EXECUTE) && !read_only {
+            granted |= ACCESS3_MODIFY | ACCESS3_EXTEND | ACCESS3_DELETE;
+        }
+    } else {
+        if allowed(EXECUTE) {
+            granted |= ACCESS3_EXECUTE;
+        }
+        if allowed(WRITE) && !read_only {
+            granted |= ACCESS3_MODIFY | ACCESS3_EXTEND;
+        }
+    }
+
+    granted & requested
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: u32 = ACCESS3_READ
+        | ACCESS3_LOOKUP
+        | ACCESS3_MODIFY
+        | ACCESS3_EXTEND
+        | ACCESS3_DELETE
+        | ACCESS3_EXECUTE;
+
+    const RW: u32 = ACCESS3_READ | ACCESS3_MODIFY | ACCESS3_EXTEND;
+
+    fn user(uid: u32, gid: u32, gids: &[u32]) -> Principal {
+        Principal::Unix {
+            uid,
+            gid,
+            gids: gids.to_vec(),
+        }
+    }
+
+    /// A file of type `type` and mode `mode`, owned by user 1000 and group 100.
+    fn file(r#type: FileType, mode: u32) -> FileAttributes {
+        FileAttributes {
+            r#type,
+            mode,
+            uid: 1000,
+            gid: 100,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn files() {
+        let owner = user(1000, 10, &[]);
+        let group = user(1001, 10, &[100]);
+        let other = user(1002, 10, &[10]);
+        let root = user(0, 0, &[]);
+
+        let file = file(FileType::Reg, 0o754);
+        assert_eq!(granted(&file, &owner, ALL, false), RW | ACCESS3_EXECUTE);
+        assert_eq!(
+            granted(&file, &group, ALL, false),
+            ACCESS3_READ | ACCESS3_EXECUTE
+        );
+        assert_eq!(
+            granted(&file, &user(1001, 100, &[]), ALL, false),
+            ACCESS3_READ | ACCESS3_EXECUTE
+        );
+        assert_eq!(granted(&file, &other, ALL, false), ACCESS3_READ);
+        assert_eq!(
+            granted(&file, &Principal::Anonymous, ALL, false),
+            ACCESS3_READ
+        );
+        assert_eq!(granted(&file, &root, ALL, false), RW | ACCESS3_EXECUTE);
+
+        // Nothing that changes a read-only export:
+        assert_eq!(
+            granted(&file, &owner, ALL, true),
+            ACCESS3_READ | ACCESS3_EXECUTE
+        );
+
+        // Only what was asked for:
+        assert_eq!(
+            granted(&file, &owner, ACCESS3_EXTEND, false),
+            ACCESS3_EXTEND
+        );
+        assert_eq!(granted(&file, &other, ACCESS3_MODIFY, false), 0);
+
+        // The owner's bits apply to the owner, even if the group's or others' grant more:
+        let file = FileAttributes {
+            mode: 0o077,
+            ..file
+        };
+        assert_eq!(granted(&file, &owner, ALL, false), 0);
+
+        // Root may only execute files that someone can execute:
+        let file = FileAttributes {
+            mode: 0o666,
+            ..file
+        };
+        assert_eq!(granted(&file, &root, ALL, false), RW);
+    }
+
+    #[test]
+    fn directories() {
+        let owner = user(1000, 10, &[]);
+        let group = user(1001, 10, &[100]);
+        let other = user(1002, 10, &[10]);
+        let root = user(0, 0, &[]);
+
+        let modify = ACCESS3_MODIFY | ACCESS3_EXTEND | ACCESS3_DELETE;
+        let dir = file(FileType::Dir, 0o751);
+        assert_eq!(
+            granted(&dir, &owner, ALL, false),
+            ACCESS3_READ | ACCESS3_LOOKUP | modify
+        );
+        assert_eq!(
+            granted(&dir, &group, ALL, false),
+            ACCESS3_READ | ACCESS3_LOOKUP
+        );
+        assert_eq!(granted(&dir, &other, ALL, false), ACCESS3_LOOKUP);
+        assert_eq!(
+            granted(&dir, &root, ALL, false),
+            ACCESS3_READ | ACCESS3_LOOKUP | modify
+        );
+        assert_eq!(
+            granted(&dir, &root, ALL, true),
+            ACCESS3_READ | ACCESS3_LOOKUP
+        );
+
+        // Changing entries needs search permission too:
+        let dir = file(FileType::Dir, 0o600);
+        assert_eq!(granted(&dir, &owner, ALL, false), ACCESS3_READ);
+    }
+}
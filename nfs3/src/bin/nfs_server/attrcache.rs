@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// A cache of the attributes that GETATTR replies with, for metadata heavy workloads such as builds
+// and `ls -lR`, whose clients get the attributes of the same files over and over.
+//
+// `AttrCache` keeps the attributes of each file handle that GETATTR was called on for a short time,
+// its TTL, rather than asking the backend again. The cache is off unless a TTL is configured. The
+// attributes of a file are forgotten whenever a procedure changes it: SETATTR and WRITE forget
+// those of their file, the procedures that add or remove an entry those of its directory, and
+// REMOVE, RENAME, and LINK those of the file whose entry or link count they change as well. Changes
+// made other than through the server are seen once the TTL has passed, much as clients see them
+// once their own attribute caches expire.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use nfs3::nfs3_xdr::FileAttributes;
+
+/// The cache is swept of expired entries when it grows to this many, at least.
+const MIN_SWEEP_SIZE: usize = 1024;
+
+/// The attributes of a file, as they were when they were cached.
+struct Cached {
+    attributes: FileAttributes,
+    path: PathBuf,
+    expires: Instant,
+}
+
+/// The attributes of the files that GETATTR was recently called on, by file handle.
+pub struct AttrCache {
+    ttl: Duration,
+    entries: HashMap<Vec<u8>, Cached>,
+
+    /// The handle of the entry at each path in `entries`.
+    handles: HashMap<PathBuf, Vec<u8>>,
+
+    /// Expired entries are swept when the cache grows past this many.
+    next_sweep: usize,
+}
+
+impl AttrCache {
+    /// A cache that keeps attributes for `ttl`. With a TTL of 0, nothing is cached.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: HashMap::new(),
+            handles: HashMap::new(),
+            next_sweep: MIN_SWEEP_SIZE,
+        }
+    }
+
+    /// The attributes of the file of `handle`, at `path`, if they were cached less than the TTL ago.
+    pub fn get(&mut self, handle: &[u8], path: &Path) -> Option<FileAttributes> {
+        let cached = self.entries.get(handle)?;
+        if cached.path == path && cached.expires > Instant::now() {
+            return Some(cached.attributes);
+        }
+
+        // The handle's file has moved, or the attributes have expired:
+        let path = cached.path.clone();
+        self.forget(&path);
+
+        None
+    }
+
+    /// Keep `attributes` as those of the file of `handle`, at `path`.
+    pub fn insert(&mut self, handle: &[u8], path: &Path, attributes: FileAttributes) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        if self.entries.len() >= self.next_sweep {
+            let now = Instant::now();
+            self.entries.retain(|_, cached| cached.expires > now);
+            let entries = &self.entries;
+            self.handles
+                .retain(|_, handle| entries.contains_key(handle));
+            self.next_sweep = MIN_SWEEP_SIZE.max(self.entries.len() * 2);
+        }
+
+        // Another handle whose file was at `path` no longer is, and this one's earlier path no
+        // longer leads to its file:
+        self.forget(path);
+        if let Some(earlier) = self.entries.get(handle).map(|c| c.path.clone()) {
+            self.forget(&earlier);
+        }
+
+        self.handles.insert(path.to_path_buf(), handle.to_vec());
+        self.entries.insert(
+            handle.to_vec(),
+            Cached {
+                attributes,
+                path: path.to_path_buf(),
+                expires: Instant::now() + self.ttl,
+            },
+        );
+    }
+
+    /// Forget the attributes of the file at `path`, which a procedure changed.
+    pub fn forget(&mut self, path: &Path) {
+        if let Some(handle) = self.handles.remove(path) {
+            self.entries.remove(&handle);
+        }
+    }
+
+    /// Forget every file's attributes, as when the exports change.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.handles.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes(size: u64) -> FileAttributes {
+        FileAttributes {
+            size,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn attributes_expire_and_are_forgotten() {
+        let (file, dir) = (Path::new("/export/dir/file"), Path::new("/export/dir"));
+
+        let mut cache = AttrCache::new(Duration::from_secs(60));
+        assert_eq!(cache.get(b"file", file), None);
+        cache.insert(b"file", file, attributes(1));
+        cache.insert(b"dir", dir, attributes(2));
+        assert_eq!(cache.get(b"file", file).unwrap().size, 1);
+
+        // Changing a file forgets only its own attributes:
+        cache.forget(file);
+        assert_eq!(cache.get(b"file", file), None);
+        assert_eq!(cache.get(b"dir", dir).unwrap().size, 2);
+
+        // A handle whose file has moved is a miss, and a new file at a cached path replaces the
+        // file that was there:
+        cache.insert(b"file", file, attributes(1));
+        assert_eq!(cache.get(b"file", Path::new("/export/moved")), None);
+        cache.insert(b"file", file, attributes(1));
+        cache.insert(b"new", file, attributes(3));
+        assert_eq!(cache.get(b"file", file), None);
+        assert_eq!(cache.get(b"new", file).unwrap().size, 3);
+
+        cache.clear();
+        assert_eq!(cache.get(b"dir", dir), None);
+
+        let mut expiring = AttrCache::new(Duration::from_millis(10));
+        expiring.insert(b"file", file, attributes(1));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(expiring.get(b"file", file), None);
+
+        // Nothing is kept with no TTL:
+        let mut off = AttrCache::new(Duration::ZERO);
+        off.insert(b"file", file, attributes(1));
+        assert_eq!(off.get(b"file", file), None);
+    }
+}
@@ -0,0 +1,656 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Implementations of the procedures that look up, create, rename, and remove directory entries:
+// LOOKUP, CREATE, MKDIR, MKNOD, REMOVE, and RENAME. Symbolic and hard links are made in `links`.
+//
+// A name must be a single component: names containing a "/" are refused with NFS3ERR_ACCES, as the
+// Linux server does, so that a client cannot reach outside of a directory by naming a path. Looking
+// up ".." in the root of the export returns the root itself. The `.snapshot` directory of an export
+// that shows its snapshots is looked up through the overlay (see `snapshots`).
+
+use std::{
+    ffi::OsStr,
+    io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use log::*;
+
+use nfs3::nfs3_xdr::*;
+use rpc_protocol::{
+    ring::{RingCall, RingResult},
+    Principal,
+};
+
+use crate::{files::*, results::*, vfs::VfsBackend, ServerState};
+
+/// The mode of a directory created without one.
+const DEFAULT_DIR_MODE: u32 = 0o755;
+
+/// The mode of a regular file created without one.
+const DEFAULT_FILE_MODE: u32 = 0o644;
+
+/// Distinguishes the temporary files made by concurrent exclusive creates.
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Check that `name` names an entry in a directory.
+pub fn check_name(name: &OsStr) -> Result<(), NfsError> {
+    if name.is_empty() {
+        return Err(NfsError(NfsResult::NoEnt));
+    }
+
+    if name.as_bytes().contains(&b'/') {
+        return Err(NfsError(NfsResult::Acces));
+    }
+
+    Ok(())
+}
+
+/// Check that `name` names an entry that can be created or removed, which "." and ".." cannot.
+pub fn check_new_name(name: &OsStr) -> Result<(), NfsError> {
+    check_name(name)?;
+
+    if name == "." || name == ".." {
+        return Err(NfsError(NfsResult::Inval));
+    }
+
+    Ok(())
+}
+
+pub fn lookup(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, LookupArgs);
+
+    let name = args.what.name;
+    debug!("LOOKUP: {}", name.to_string_lossy());
+
+    reply::<LookupResult>(|| {
+        let dir = state.lookup(call, &args.what.dir.data)?;
+
+        let dir_attributes = post_op_attributes(&*state.vfs, &dir);
+        let failure = || LookupFailure { dir_attributes };
+
+        check_name(&name).or_fail(failure)?;
+
+        if !dir_attributes
+            .inner
+            .is_some_and(|a| a.r#type == FileType::Dir)
+        {
+            return Err(NfsError(NfsResult::NotDir).with(failure()));
+        }
+
+        let (path, handle) = lookup_handle(state, &dir, &name).or_fail(failure)?;
+
+        Ok(LookupSuccess {
+            object: FileHandle { data: handle },
+            obj_attributes: post_op_attributes(&*state.vfs, &path),
+            dir_attributes,
+        })
+    })
+}
+
+/// Find the path and file handle of the entry `name` in `dir`.
+fn lookup_handle(
+    state: &mut ServerState,
+    dir: &Path,
+    name: &OsStr,
+) -> io::Result<(PathBuf, Vec<u8>)> {
+    state.refresh_snapshots(dir);
+    if let Some(path) = state.overlay_entry(dir, name) {
+        let handle = state.handles.handle_for(&path)?;
+        return Ok((path, handle));
+    }
+
+    if name == "." {
+        return Ok((dir.to_path_buf(), state.handles.handle_for(dir)?));
+    }
+
+    if name == ".." {
+        let parent = dir.parent().unwrap_or(dir);
+        return match state.handles.handle_for(parent) {
+            Ok(handle) => Ok((parent.to_path_buf(), handle)),
+            // The parent of the root of the export is the root itself:
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                Ok((dir.to_path_buf(), state.handles.handle_for(dir)?))
+            }
+            Err(e) => Err(e),
+        };
+    }
+
+    let path = state.vfs.lookup(dir, name)?;
+    let handle = state.handles.handle_for(&path)?;
+
+    Ok((path, handle))
+}
+
+/// CREATE makes a regular file. For UNCHECKED and GUARDED creates, the new file is given the
+/// attributes set by the client, as SETATTR would set them, and its mode exactly as given, whatever
+/// the server's umask; it is owned by the caller's mapped identity (see `identity`) unless the
+/// client sets an owner that it may give the file. UNCHECKED opens the file if it already exists,
+/// and only applies the size to it.
+pub fn create(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, CreateArgs);
+
+    let name = args.r#where.name;
+    debug!("CREATE: {}", name.to_string_lossy());
+
+    reply::<CreateResult>(|| {
+        let dir = state.lookup(call, &args.r#where.dir.data)?;
+        let path = dir.join(&name);
+
+        let vfs = &*state.vfs;
+        let (res, dir_wcc) = with_wcc(vfs, &dir, || -> Result<(), NfsError> {
+            state.check_writable(&dir)?;
+            check_new_name(&name)?;
+            state.check_not_overlay(&dir, &name)?;
+
+            let res = match &args.how {
+                CreateHow::Unchecked(attributes) => create_file(vfs, &path, attributes, false),
+                CreateHow::Guarded(attributes) => create_file(vfs, &path, attributes, true),
+                CreateHow::Exclusive(verf) => create_exclusive(vfs, &path, verf),
+            };
+
+            res.map_err(|e| {
+                debug!("CREATE of {} failed: {e}", path.display());
+                e.into()
+            })
+        });
+
+        state.attributes.forget(&dir);
+        res.or_fail(|| CreateFailure { dir_wcc })?;
+
+        Ok(CreateSuccess {
+            obj: PostOpFileHandle {
+                inner: state
+                    .handles
+                    .handle_for(&path)
+                    .ok()
+                    .map(|data| FileHandle { data }),
+            },
+            obj_attributes: post_op_attributes(vfs, &path),
+            dir_wcc,
+        })
+    })
+}
+
+/// The file is created without permissions and given its mode once its other attributes are set, so
+/// that it is never open to others before it has its owner. If the attributes cannot be set, the new
+/// file is removed again.
+fn create_file(
+    vfs: &dyn VfsBackend,
+    path: &Path,
+    attributes: &SetAttributes,
+    guarded: bool,
+) -> io::Result<()> {
+    match vfs.create(path, 0o000, attributes.size.inner) {
+        Ok(()) => set_new_attributes(vfs, path, attributes, DEFAULT_FILE_MODE),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists && !guarded => {
+            let size = SetAttributes {
+                size: attributes.size,
+                ..SetAttributes::default()
+            };
+            vfs.setattr(path, &size)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Give the file just made at `path` the attributes set by the client but its size, and its mode,
+/// or `default_mode` if it was not set. If they cannot be set, the file is removed again.
+pub fn set_new_attributes(
+    vfs: &dyn VfsBackend,
+    path: &Path,
+    attributes: &SetAttributes,
+    default_mode: u32,
+) -> io::Result<()> {
+    let attributes = SetAttributes {
+        mode: SetMode {
+            inner: Some(attributes.mode.inner.unwrap_or(default_mode)),
+        },
+        size: SetSize { inner: None },
+        ..*attributes
+    };
+
+    vfs.setattr(path, &attributes).inspect_err(|_| {
+        let _ = vfs.remove(path);
+    })
+}
+
+/// Create the file at `path` for an EXCLUSIVE CREATE, recording the client's verifier in the file's
+/// atime and mtime, as the Linux server does. If the file already exists with the same verifier,
+/// this is a retransmission of a create that succeeded, and succeeds again; otherwise it fails with
+/// EEXIST. The client is expected to set the file's real times with SETATTR once it is created.
+///
+/// The file is made under a temporary name and given its verifier before being linked into place,
+/// so that it never appears at `path` without one, even if the server fails in between.
+fn create_exclusive(vfs: &dyn VfsBackend, path: &Path, verf: &[u8; 8]) -> io::Result<()> {
+    let (atime, mtime) = verifier_times(verf);
+    let temp = path.with_file_name(format!(
+        ".nfs-create-{}-{}",
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    vfs.create(&temp, DEFAULT_FILE_MODE, None)?;
+
+    let times = SetAttributes {
+        atime: SetAtime::SetToClientTime(atime),
+        mtime: SetMtime::SetToClientTime(mtime),
+        ..SetAttributes::default()
+    };
+    let res = vfs
+        .setattr(&temp, &times)
+        .and_then(|()| vfs.link(&temp, path));
+    let _ = vfs.remove(&temp);
+
+    match res {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists && has_verifier(vfs, path, verf) => {
+            Ok(())
+        }
+        res => res,
+    }
+}
+
+/// The atime and mtime that record an exclusive create verifier: each holds four of its bytes as a
+/// number of seconds.
+fn verifier_times(verf: &[u8; 8]) -> (NfsTime, NfsTime) {
+    let time = |bytes: &[u8]| NfsTime {
+        seconds: u32::from_be_bytes(bytes.try_into().unwrap()),
+        nseconds: 0,
+    };
+
+    (time(&verf[..4]), time(&verf[4..]))
+}
+
+fn has_verifier(vfs: &dyn VfsBackend, path: &Path, verf: &[u8; 8]) -> bool {
+    vfs.getattr(path)
+        .is_ok_and(|a| a.r#type == FileType::Reg && (a.atime, a.mtime) == verifier_times(verf))
+}
+
+/// Only the mode of the new directory is taken from the attributes given by the client; the owner
+/// is chosen as for CREATE.
+pub fn mkdir(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, MkdirArgs);
+
+    let name = args.r#where.name;
+    debug!("MKDIR: {}", name.to_string_lossy());
+
+    reply::<MkdirResult>(|| {
+        let dir = state.lookup(call, &args.r#where.dir.data)?;
+        let path = dir.join(&name);
+
+        let vfs = &*state.vfs;
+        let (res, dir_wcc) = with_wcc(vfs, &dir, || -> Result<(), NfsError> {
+            state.check_writable(&dir)?;
+            check_new_name(&name)?;
+            state.check_not_overlay(&dir, &name)?;
+
+            let mode = args.attributes.mode.inner.unwrap_or(DEFAULT_DIR_MODE);
+            vfs.mkdir(&path, mode).map_err(|e| {
+                debug!("MKDIR of {} failed: {e}", path.display());
+                e.into()
+            })
+        });
+
+        state.attributes.forget(&dir);
+        res.or_fail(|| MkdirFailure { dir_wcc })?;
+
+        Ok(MkdirSuccess {
+            obj: PostOpFileHandle {
+                inner: state
+                    .handles
+                    .handle_for(&path)
+                    .ok()
+                    .map(|data| FileHandle { data }),
+            },
+            obj_attributes: post_op_attributes(vfs, &path),
+            dir_wcc,
+        })
+    })
+}
+
+/// MKNOD makes a FIFO, a UNIX domain socket, or a device file, if `nfs_server.mknod` allows it, and
+/// fails with NFS3ERR_NOTSUPP otherwise. Only a caller mapped to root may make a device file, since
+/// the server may make one even when the caller's identity could not. The new file is given its
+/// attributes as a file made by CREATE is.
+pub fn mknod(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, MknodArgs);
+
+    let name = args.r#where.name;
+    debug!("MKNOD: {}", name.to_string_lossy());
+
+    reply::<MknodResult>(|| {
+        let dir = state.lookup(call, &args.r#where.dir.data)?;
+        let path = dir.join(&name);
+
+        let vfs = &*state.vfs;
+        let (res, dir_wcc) = with_wcc(vfs, &dir, || -> Result<(), NfsError> {
+            state.check_writable(&dir)?;
+            check_new_name(&name)?;
+            state.check_not_overlay(&dir, &name)?;
+            if !state.mknod {
+                return Err(NfsResult::NotSupp.into());
+            }
+
+            let (r#type, attributes, device) = match &args.what {
+                MknodData::Chr(device) => (FileType::Chr, &device.dev_attributes, Some(device)),
+                MknodData::Blk(device) => (FileType::Blk, &device.dev_attributes, Some(device)),
+                MknodData::Sock(attributes) => (FileType::Sock, attributes, None),
+                MknodData::Fifo(attributes) => (FileType::Fifo, attributes, None),
+                MknodData::Default => return Err(NfsResult::Badtype.into()),
+            };
+            let is_root = matches!(state.principal(call, &dir), Principal::Unix { uid: 0, .. });
+            if device.is_some() && !is_root {
+                return Err(NfsResult::Perm.into());
+            }
+
+            vfs.mknod(
+                &path,
+                r#type,
+                device.map_or_else(SpecData::default, |d| d.spec),
+            )
+            .and_then(|()| set_new_attributes(vfs, &path, attributes, DEFAULT_FILE_MODE))
+            .map_err(|e| {
+                debug!("MKNOD of {} failed: {e}", path.display());
+                e.into()
+            })
+        });
+
+        state.attributes.forget(&dir);
+        res.or_fail(|| MknodFailure { dir_wcc })?;
+
+        Ok(MknodSuccess {
+            obj: PostOpFileHandle {
+                inner: state
+                    .handles
+                    .handle_for(&path)
+                    .ok()
+                    .map(|data| FileHandle { data }),
+            },
+            obj_attributes: post_op_attributes(vfs, &path),
+            dir_wcc,
+        })
+    })
+}
+
+/// REMOVE removes any kind of file other than a directory, which is refused with NFS3ERR_ISDIR.
+pub fn remove(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, RemoveArgs);
+
+    let name = args.object.name;
+    debug!("REMOVE: {}", name.to_string_lossy());
+
+    reply::<RemoveResult>(|| {
+        let dir = state.lookup(call, &args.object.dir.data)?;
+        let path = dir.join(&name);
+        let removed = state.verified_handle(&path);
+
+        let vfs = &*state.vfs;
+        let (res, dir_wcc) = with_wcc(vfs, &dir, || -> Result<(), NfsError> {
+            state.check_writable(&dir)?;
+            check_new_name(&name)?;
+            state.check_not_overlay(&dir, &name)?;
+
+            vfs.remove(&path).map_err(|e| {
+                debug!("REMOVE of {} failed: {e}", path.display());
+                e.into()
+            })
+        });
+        if res.is_ok() {
+            state.fds.forget(&path);
+            state.forget_unverified(removed);
+        }
+        state.attributes.forget(&dir);
+        state.attributes.forget(&path);
+
+        res.or_fail(|| RemoveFailure { dir_wcc })?;
+
+        Ok(RemoveSuccess { dir_wcc })
+    })
+}
+
+/// RENAME moves an entry with rename(2), so an existing entry at the destination is replaced
+/// atomically, as rename(2) allows: a file by a file, or a directory by an empty directory. Both
+/// directories report their weak cache consistency data. An entry cannot be moved to another
+/// export, which fails with NFS3ERR_XDEV.
+pub fn rename(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, RenameArgs);
+
+    let (from, to) = (args.from.name, args.to.name);
+    debug!(
+        "RENAME: {} to {}",
+        from.to_string_lossy(),
+        to.to_string_lossy()
+    );
+
+    reply::<RenameResult>(|| {
+        let from_dir = state.lookup(call, &args.from.dir.data)?;
+        let to_dir = state.lookup(call, &args.to.dir.data)?;
+        let from_path = from_dir.join(&from);
+        let to_path = to_dir.join(&to);
+        // The uncommitted writes to the file moved are kept, since its handle is unchanged, but
+        // those to a file that it replaces are not:
+        let replaced = state.verified_handle(&to_path);
+
+        let vfs = &*state.vfs;
+        let ((res, todir_wcc), fromdir_wcc) = with_wcc(vfs, &from_dir, || {
+            with_wcc(vfs, &to_dir, || -> Result<(), NfsError> {
+                state.check_writable(&from_dir)?;
+                state.check_writable(&to_dir)?;
+                check_new_name(&from)?;
+                check_new_name(&to)?;
+                state.check_not_overlay(&from_dir, &from)?;
+                state.check_not_overlay(&to_dir, &to)?;
+                if state.handles.export_of(&from_dir) != state.handles.export_of(&to_dir) {
+                    return Err(NfsResult::XDev.into());
+                }
+
+                vfs.rename(&from_path, &to_path).map_err(|e| {
+                    debug!(
+                        "RENAME of {} to {} failed: {e}",
+                        from_path.display(),
+                        to_path.display()
+                    );
+                    e.into()
+                })
+            })
+        });
+
+        if res.is_ok() {
+            state.fds.forget(&from_path);
+            state.fds.forget(&to_path);
+            state.forget_unverified(replaced);
+        }
+        for path in [&from_dir, &to_dir, &from_path, &to_path] {
+            state.attributes.forget(path);
+        }
+
+        res.or_fail(|| RenameFailure {
+            fromdir_wcc,
+            todir_wcc,
+        })?;
+
+        // The handle of the file now leads to its new path, rather than to a rescan of the export:
+        let _ = state.handles.handle_for(&to_path);
+
+        Ok(RenameSuccess {
+            fromdir_wcc,
+            todir_wcc,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{fs, os::unix::fs::MetadataExt};
+
+    use crate::{memfs::MemFs, vfs::LocalFs};
+
+    #[test]
+    fn exclusive_create_is_idempotent() {
+        let dir = std::env::temp_dir().join(format!("dirops_exclusive_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let local = LocalFs::exporting(&[&dir]);
+        let path = dir.join("file");
+
+        create_exclusive(&local, &path, b"verifier").unwrap();
+        assert!(has_verifier(&local, &path, b"verifier"));
+
+        // A retransmission of the same CREATE succeeds, and leaves the file alone:
+        fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o600)).unwrap();
+        create_exclusive(&local, &path, b"verifier").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().mode() & 0o777, 0o600);
+
+        // A CREATE from another client, or a later one from the same client, fails:
+        let e = create_exclusive(&local, &path, b"verifie2").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+
+        // An existing file without the verifier is never mistaken for one that was created:
+        fs::write(dir.join("other"), b"data").unwrap();
+        let e = create_exclusive(&local, &dir.join("other"), b"verifier").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+
+        // No temporary files are left behind:
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn created_files_get_their_attributes() {
+        let dir = std::env::temp_dir().join(format!("dirops_create_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let local = LocalFs::exporting(&[&dir]);
+        let path = dir.join("file");
+
+        // The mode is given exactly, whatever the umask:
+        let attributes = SetAttributes {
+            mode: SetMode { inner: Some(0o666) },
+            size: SetSize { inner: Some(10) },
+            mtime: SetMtime::SetToClientTime(NfsTime {
+                seconds: 1_000_000,
+                nseconds: 0,
+            }),
+            ..SetAttributes::default()
+        };
+        create_file(&local, &path, &attributes, true).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.mode() & 0o7777, 0o666);
+        assert_eq!(metadata.len(), 10);
+        assert_eq!(metadata.mtime(), 1_000_000);
+
+        let e = create_file(&local, &path, &attributes, true).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+
+        // An UNCHECKED create of an existing file only applies the size:
+        let truncate = SetAttributes {
+            mode: SetMode { inner: Some(0o600) },
+            size: SetSize { inner: Some(0) },
+            ..SetAttributes::default()
+        };
+        create_file(&local, &path, &truncate, false).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.mode() & 0o7777, 0o666);
+        assert_eq!(metadata.len(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn special_files_get_their_attributes() {
+        let dir = std::env::temp_dir().join(format!("dirops_special_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let local = LocalFs::exporting(&[&dir]);
+
+        let fifo = dir.join("fifo");
+        local
+            .mknod(&fifo, FileType::Fifo, SpecData::default())
+            .unwrap();
+        set_new_attributes(&local, &fifo, &SetAttributes::default(), DEFAULT_FILE_MODE).unwrap();
+        let metadata = fs::symlink_metadata(&fifo).unwrap();
+        assert!(std::os::unix::fs::FileTypeExt::is_fifo(
+            &metadata.file_type()
+        ));
+        assert_eq!(metadata.mode() & 0o7777, DEFAULT_FILE_MODE);
+
+        // A size cannot be given to anything but a regular file, and is never set on a new one:
+        let sized = SetAttributes {
+            size: SetSize { inner: Some(4) },
+            ..SetAttributes::default()
+        };
+        set_new_attributes(&local, &fifo, &sized, DEFAULT_FILE_MODE).unwrap();
+
+        // The file is removed if its attributes cannot be set:
+        let other = SetAttributes {
+            atime: SetAtime::SetToClientTime(NfsTime {
+                seconds: 0,
+                nseconds: 2_000_000_000,
+            }),
+            ..SetAttributes::default()
+        };
+        set_new_attributes(&local, &fifo, &other, DEFAULT_FILE_MODE).unwrap_err();
+        assert!(fs::symlink_metadata(&fifo).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn entries_in_memory() {
+        let vfs = MemFs::default();
+        let dir = Path::new("/dir");
+        vfs.mkdir(dir, DEFAULT_DIR_MODE).unwrap();
+        let path = dir.join("file");
+
+        // Exclusive creates behave as they do on the local file system:
+        create_exclusive(&vfs, &path, b"verifier").unwrap();
+        create_exclusive(&vfs, &path, b"verifier").unwrap();
+        let e = create_exclusive(&vfs, &path, b"verifie2").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(vfs.readdir(dir, 0).unwrap().count(), 1);
+
+        let attributes = SetAttributes {
+            mode: SetMode { inner: Some(0o600) },
+            size: SetSize { inner: Some(3) },
+            ..SetAttributes::default()
+        };
+        let other = dir.join("other");
+        create_file(&vfs, &other, &attributes, true).unwrap();
+        let created = vfs.getattr(&other).unwrap();
+        assert_eq!((created.mode, created.size), (0o600, 3));
+
+        // A file replaces a file, but not a directory, and a directory only an empty one:
+        vfs.rename(&other, &path).unwrap();
+        assert_eq!(vfs.getattr(&path).unwrap().fileid, created.fileid);
+        assert_eq!(
+            nfs_status(&vfs.getattr(&other).unwrap_err()),
+            NfsResult::NoEnt
+        );
+        let sub = dir.join("sub");
+        vfs.mkdir(&sub, DEFAULT_DIR_MODE).unwrap();
+        let e = vfs.rename(&path, &sub).unwrap_err();
+        assert_eq!(nfs_status(&e), NfsResult::IsDir);
+        vfs.create(&sub.join("file"), 0o644, None).unwrap();
+        let e = vfs.rename(&dir.join("empty"), &sub).unwrap_err();
+        assert_eq!(nfs_status(&e), NfsResult::NoEnt);
+        vfs.mkdir(&dir.join("empty"), DEFAULT_DIR_MODE).unwrap();
+        let e = vfs.rename(&dir.join("empty"), &sub).unwrap_err();
+        assert_eq!(nfs_status(&e), NfsResult::NotEmpty);
+        let e = vfs.rename(dir, &sub.join("dir")).unwrap_err();
+        assert_eq!(nfs_status(&e), NfsResult::Inval);
+
+        // A file outlives its first name while it has another:
+        vfs.link(&path, &dir.join("link")).unwrap();
+        assert_eq!(vfs.getattr(&path).unwrap().nlink, 2);
+        vfs.remove(&path).unwrap();
+        assert_eq!(vfs.getattr(&dir.join("link")).unwrap().nlink, 1);
+        let e = vfs.remove(&sub).unwrap_err();
+        assert_eq!(nfs_status(&e), NfsResult::IsDir);
+    }
+}
@@ -0,0 +1,354 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The server's exports, and what has been read from and written to each.
+//
+// The exports start as the one in the configuration, and can be changed while the server runs
+// through its control socket (see nfs3::control). Requests on the socket are handled on a thread
+// of their own, which passes them to the ring's thread through a `StateUpdater`, so that they are
+// applied between calls.
+
+use std::{
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use log::*;
+use nfs3::{
+    clients::{Aliases, Clients},
+    config::{CookieVerifier, ReadMethod},
+    control::ExportControl,
+    control_proto::{ExportEntry, ExportStats},
+    file_handle::FileHandleMap,
+    nfs3_xdr::{FileHandle, NfsResult},
+    resolver::Resolver,
+};
+use rpc_protocol::{
+    ring::StateUpdater,
+    throttle::{Class, Limits},
+    Call, Principal,
+};
+
+use crate::{
+    identity::{self, Identity},
+    snapshots, ServerState,
+};
+
+/// An exported directory.
+pub struct Export {
+    /// The canonical path of the directory.
+    pub root: PathBuf,
+
+    /// Whether operations that would change the export fail with NFS3ERR_ROFS.
+    pub read_only: bool,
+
+    /// How the cookie verifiers of listings of the export's directories are chosen.
+    pub cookie_verifier: CookieVerifier,
+
+    /// How the data of READs is read from the export's files.
+    pub read_method: ReadMethod,
+
+    /// The clients that may use the export, and how each is squashed if not as configured. Calls
+    /// from others fail with NFS3ERR_ACCES.
+    pub clients: Clients,
+
+    /// The canonical path of the directory of snapshots shown as `.snapshot` in the root, if any
+    /// (see `snapshots`).
+    pub snapshots: Option<PathBuf>,
+
+    /// The limits on the calls for the export, from every client together.
+    pub limits: Limits,
+
+    counters: Counters,
+}
+
+/// Counts of what has been done with an export.
+#[derive(Clone, Copy, Default)]
+struct Counters {
+    reads: u64,
+    read_bytes: u64,
+    writes: u64,
+    write_bytes: u64,
+}
+
+impl Export {
+    /// Canonicalize `path`, and that of `snapshots`, so that they can be compared with the paths of
+    /// files in the export.
+    pub fn new(
+        path: &Path,
+        read_only: bool,
+        cookie_verifier: CookieVerifier,
+        read_method: ReadMethod,
+        clients: Clients,
+        snapshots: Option<&Path>,
+        limits: Limits,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            root: path.canonicalize()?,
+            read_only,
+            cookie_verifier,
+            read_method,
+            clients,
+            snapshots: snapshots.map(Path::canonicalize).transpose()?,
+            limits,
+            counters: Counters::default(),
+        })
+    }
+
+    /// Make the files of the export, and of its snapshots, addressable by `handles`.
+    pub fn add_to(&self, handles: &mut FileHandleMap) -> io::Result<()> {
+        handles.add_export(&self.root)?;
+        if let Some(dir) = &self.snapshots {
+            if let Err(e) = snapshots::add(handles, dir) {
+                let _ = handles.remove_export(&self.root);
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the file at `path`, which must be canonical, is in the export's snapshots.
+    pub fn in_snapshots(&self, path: &Path) -> bool {
+        self.snapshots
+            .as_ref()
+            .is_some_and(|dir| path.starts_with(dir))
+    }
+
+    /// Whether a client at `address` may use the export. A client whose address is not known may
+    /// only use an export that any client may.
+    fn allows(&self, address: Option<SocketAddr>) -> bool {
+        self.clients.allows_any() || address.is_some_and(|a| self.clients.allows(a.ip()))
+    }
+
+    fn stats(&self) -> ExportStats {
+        let counters = &self.counters;
+
+        ExportStats::new(&self.root)
+            .counter("reads", counters.reads)
+            .counter("read-bytes", counters.read_bytes)
+            .counter("writes", counters.writes)
+            .counter("write-bytes", counters.write_bytes)
+    }
+}
+
+impl ServerState {
+    /// Find the path of the file identified by `handle`, as `FileHandleMap::lookup()` does, failing
+    /// with NFS3ERR_ACCES if the client that made `call` may not use the export the file is in. If
+    /// the export squashes the client's callers differently than configured, the file system
+    /// credentials are switched to the caller's identity in the export.
+    pub fn lookup(&mut self, call: &Call, handle: &[u8]) -> Result<PathBuf, NfsResult> {
+        let path = self.handles.lookup(handle)?;
+
+        let address = call.get_peer_address();
+        let export = self.export_of(&path);
+        if export.is_some_and(|e| !e.allows(address)) {
+            let client = address.map(|a| a.ip().to_string());
+            debug!(
+                "Refusing access to {} from {}, which is not one of the export's clients",
+                path.display(),
+                client.as_deref().unwrap_or("a client of unknown address")
+            );
+            return Err(NfsResult::Acces);
+        }
+
+        if let Some(identity) = self.export_identity(call, &path) {
+            if let Err(e) = identity::assume(&identity) {
+                warn!("Could not switch to uid {}: {e}", identity.uid);
+                return Err(NfsResult::ServerFault);
+            }
+        }
+
+        Ok(path)
+    }
+
+    /// The caller of `call`, as the export that the file at `path` is in maps it.
+    pub fn principal(&self, call: &Call, path: &Path) -> Principal {
+        match self.export_identity(call, path) {
+            Some(identity) => identity.principal(),
+            None => call.get_principal().clone(),
+        }
+    }
+
+    /// The identity of the caller of `call` in the export that the file at `path` is in, if the
+    /// export squashes the client that made the call differently than configured.
+    fn export_identity(&self, call: &Call, path: &Path) -> Option<Identity> {
+        let address = call.get_peer_address()?;
+        let squash = self.export_of(path)?.clients.squash(address.ip())?;
+
+        (squash != self.identities.squash).then(|| self.identities.map_call(call, squash))
+    }
+
+    /// The export that the file at `path` is in, which must be canonical, as the paths that the
+    /// file handle map returns are. The files of an export's snapshots are in the export.
+    fn export_of(&self, path: &Path) -> Option<&Export> {
+        let root = self.handles.export_of(path)?;
+
+        self.exports
+            .iter()
+            .find(|e| e.root == root || e.in_snapshots(root))
+    }
+
+    /// The class of `call` for the throttle: the export that the file handle at the start of its
+    /// arguments is in, if the export is limited. The arguments of every procedure but NULL start
+    /// with a file handle.
+    pub fn export_class(call: &Call, state: &ServerState) -> Option<Class> {
+        if state.exports.iter().all(|e| e.limits.is_unlimited()) {
+            return None;
+        }
+
+        let mut handle = FileHandle::default();
+        handle.deserialize(&mut &*call.arg).ok()?;
+        let root = state.handles.export_of_handle(&handle.data)?;
+        let export = state
+            .exports
+            .iter()
+            .find(|e| e.root == root || e.in_snapshots(root))?;
+
+        (!export.limits.is_unlimited()).then(|| Class {
+            name: export.root.display().to_string(),
+            limits: export.limits,
+        })
+    }
+
+    fn export_mut(&mut self, path: &Path) -> Option<&mut Export> {
+        let root = self.handles.export_of(path)?;
+
+        self.exports
+            .iter_mut()
+            .find(|e| e.root == root || e.in_snapshots(root))
+    }
+
+    /// Whether the file at `path` is read-only: because its export is, or because it is in the
+    /// export's snapshots.
+    pub fn is_read_only(&self, path: &Path) -> bool {
+        self.export_of(path)
+            .is_none_or(|e| e.read_only || e.in_snapshots(path))
+    }
+
+    /// How the cookie verifiers of listings of the directory at `path` are chosen.
+    pub fn cookie_verifier(&self, path: &Path) -> CookieVerifier {
+        self.export_of(path)
+            .map_or(CookieVerifier::default(), |e| e.cookie_verifier)
+    }
+
+    /// How the data of READs is read from the file at `path`.
+    pub fn read_method(&self, path: &Path) -> ReadMethod {
+        self.export_of(path)
+            .map_or(ReadMethod::default(), |e| e.read_method)
+    }
+
+    /// Count a READ of `bytes` bytes from the file at `path`.
+    pub fn record_read(&mut self, path: &Path, bytes: usize) {
+        if let Some(export) = self.export_mut(path) {
+            export.counters.reads += 1;
+            export.counters.read_bytes += bytes as u64;
+        }
+    }
+
+    /// Count a WRITE of `bytes` bytes to the file at `path`.
+    pub fn record_write(&mut self, path: &Path, bytes: usize) {
+        if let Some(export) = self.export_mut(path) {
+            export.counters.writes += 1;
+            export.counters.write_bytes += bytes as u64;
+        }
+    }
+
+    /// Switch to `exports`, whose files `handles` maps, and to the client aliases `aliases` and the
+    /// resolver `resolver`. The counts of an export that is kept are kept too.
+    pub fn replace_exports(
+        &mut self,
+        handles: nfs3::file_handle::FileHandleMap,
+        exports: Vec<Export>,
+        aliases: Aliases,
+        resolver: Arc<Resolver>,
+    ) {
+        let mut exports = exports;
+        for export in exports.iter_mut() {
+            if let Some(old) = self.exports.iter().find(|e| e.root == export.root) {
+                export.counters = old.counters;
+            }
+        }
+
+        self.handles = handles;
+        self.exports = exports;
+        self.fds.clear();
+        self.attributes.clear();
+        self.aliases = aliases;
+        self.resolver = resolver;
+    }
+
+    fn add_export(&mut self, entry: &ExportEntry) -> io::Result<()> {
+        let clients = Clients::resolve_with(&entry.get_clients(), &self.aliases, &self.resolver)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let export = Export::new(
+            entry.path(),
+            entry.read_only,
+            entry.get_cookie_verifier(),
+            entry.get_read_method(),
+            clients,
+            entry.get_snapshots(),
+            Limits::default(),
+        )?;
+        export.add_to(&mut self.handles)?;
+        self.exports.push(export);
+
+        Ok(())
+    }
+
+    fn remove_export(&mut self, path: &Path) -> io::Result<()> {
+        let root = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.handles.remove_export(&root)?;
+        if let Some(dir) = self.snapshots_in(&root).map(Path::to_path_buf) {
+            snapshots::remove(&mut self.handles, &dir);
+        }
+        self.exports.retain(|e| e.root != root);
+        self.fds.forget(&root);
+
+        Ok(())
+    }
+}
+
+/// Answers control requests by passing them to the ring's thread.
+pub struct Control(pub StateUpdater<ServerState>);
+
+impl ExportControl for Control {
+    fn list(&mut self) -> Vec<ExportEntry> {
+        self.0.query(|state| {
+            state
+                .exports
+                .iter()
+                .map(|e| {
+                    ExportEntry::new(&e.root, e.read_only)
+                        .with_cookie_verifier(e.cookie_verifier)
+                        .with_read_method(e.read_method)
+                        .with_snapshots(e.snapshots.as_deref())
+                        .with_clients(e.clients.entries())
+                })
+                .collect()
+        })
+    }
+
+    fn add(&mut self, export: &ExportEntry) -> Result<(), String> {
+        let entry = export.clone();
+
+        self.0
+            .query(move |state| state.add_export(&entry))
+            .map_err(|e| format!("Could not export {}: {e}", export.path().display()))
+    }
+
+    fn remove(&mut self, path: &Path) -> Result<(), String> {
+        let path = path.to_path_buf();
+
+        self.0
+            .query(move |state| state.remove_export(&path))
+            .map_err(|e| e.to_string())
+    }
+
+    fn stats(&mut self) -> Vec<ExportStats> {
+        self.0
+            .query(|state| state.exports.iter().map(Export::stats).collect())
+    }
+}
@@ -0,0 +1,316 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// A cache of the local files that READ, WRITE, and COMMIT open, so that a run of calls on one file
+// opens it once.
+//
+// Opening a file resolves its path again beneath its export (see `resolve`), one directory at a
+// time, which costs more than a small READ or WRITE. `FdCache` keeps the files it opened open, keyed
+// by the file handle of the call and the access it was opened for, and closes the least recently
+// used one once it holds as many as its capacity. A hit costs a dup(2) of the cached descriptor.
+//
+// A descriptor is only used again by the identity that opened it, since permissions are checked
+// when a file is opened, not when it is read or written. For the same reason, a file is forgotten
+// when SETATTR changes it. The files at a path, and beneath it, are forgotten when REMOVE or RENAME
+// remove or replace it, so that a removed file is not kept open, and a new one at its path is never
+// mistaken for it.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use log::*;
+use nix::fcntl::OFlag;
+
+use crate::identity::{self, Identity};
+
+/// The cache's statistics are logged after every this many lookups.
+const STATS_INTERVAL: u64 = 4096;
+
+/// A file handle, and the access flags that its file was opened with.
+type Key = (Vec<u8>, i32);
+
+/// What has been counted of the lookups in an `FdCache`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FdCacheStats {
+    /// Lookups that found their file open.
+    pub hits: u64,
+
+    /// Lookups that opened their file.
+    pub misses: u64,
+
+    /// Files closed to make room for others.
+    pub evictions: u64,
+
+    /// Files closed because they were changed, removed, or replaced.
+    pub invalidations: u64,
+}
+
+impl FdCacheStats {
+    /// The share of lookups that found their file open, from 0 to 1.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+}
+
+impl fmt::Display for FdCacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses ({:.1}% hit rate), {} evicted, {} invalidated",
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.0,
+            self.evictions,
+            self.invalidations
+        )
+    }
+}
+
+/// An open file in the cache.
+struct Cached {
+    file: File,
+    path: PathBuf,
+
+    /// The identity that opened it, if the server switches identities.
+    identity: Option<Identity>,
+
+    /// When it was last used, on the cache's clock.
+    used: u64,
+}
+
+/// The most recently used local files, open.
+pub struct FdCache {
+    capacity: usize,
+    files: HashMap<Key, Cached>,
+
+    /// The keys of `files` by when they were last used, least recently first.
+    lru: BTreeMap<u64, Key>,
+
+    /// Counts the lookups.
+    clock: u64,
+
+    stats: FdCacheStats,
+}
+
+impl FdCache {
+    /// A cache that keeps up to `capacity` files open. With a capacity of 0, nothing is cached.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            files: HashMap::new(),
+            lru: BTreeMap::new(),
+            clock: 0,
+            stats: FdCacheStats::default(),
+        }
+    }
+
+    /// The file of `handle`, at `path`, opened with the access of `flags`: a duplicate of the cached
+    /// descriptor if there is one, or else the file that `open` opens, which is cached.
+    pub fn open(
+        &mut self,
+        handle: &[u8],
+        path: &Path,
+        flags: OFlag,
+        open: impl FnOnce() -> io::Result<File>,
+    ) -> io::Result<File> {
+        if self.capacity == 0 {
+            return open();
+        }
+
+        self.clock += 1;
+        if self.clock.is_multiple_of(STATS_INTERVAL) {
+            info!("fd cache: {}", self.stats);
+        }
+
+        let key = (handle.to_vec(), (flags & OFlag::O_ACCMODE).bits());
+        let identity = identity::current();
+        if let Some(cached) = self.files.get_mut(&key) {
+            // A handle whose file has moved is looked up again, and opened by its new path:
+            if cached.identity == identity && cached.path == path {
+                self.lru.remove(&cached.used);
+                cached.used = self.clock;
+                self.lru.insert(self.clock, key);
+                self.stats.hits += 1;
+
+                return cached.file.try_clone();
+            }
+
+            self.close(&key);
+        }
+
+        self.stats.misses += 1;
+        let file = open()?;
+
+        while self.files.len() >= self.capacity {
+            let Some((_, oldest)) = self.lru.pop_first() else {
+                break;
+            };
+            self.files.remove(&oldest);
+            self.stats.evictions += 1;
+        }
+
+        self.files.insert(
+            key.clone(),
+            Cached {
+                file: file.try_clone()?,
+                path: path.to_path_buf(),
+                identity,
+                used: self.clock,
+            },
+        );
+        self.lru.insert(self.clock, key);
+
+        Ok(file)
+    }
+
+    /// Close the files at `path`, and beneath it if it is a directory.
+    pub fn forget(&mut self, path: &Path) {
+        let keys: Vec<Key> = self
+            .files
+            .iter()
+            .filter(|(_, cached)| cached.path.starts_with(path))
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in keys {
+            self.close(&key);
+            self.stats.invalidations += 1;
+        }
+    }
+
+    /// Close every file, as when the exports change.
+    pub fn clear(&mut self) {
+        self.stats.invalidations += self.files.len() as u64;
+        self.files.clear();
+        self.lru.clear();
+    }
+
+    fn close(&mut self, key: &Key) {
+        if let Some(cached) = self.files.remove(key) {
+            self.lru.remove(&cached.used);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{cell::Cell, fs, os::unix::fs::FileExt};
+
+    #[test]
+    fn least_recently_used_files_are_closed() {
+        let dir = std::env::temp_dir().join(format!("fdcache_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["a", "b", "c"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+
+        let mut cache = FdCache::new(2);
+        let opened = Cell::new(0);
+        let mut open = |name: &str, flags: OFlag| {
+            let path = dir.join(name);
+            let file = cache
+                .open(name.as_bytes(), &path, flags, || {
+                    opened.set(opened.get() + 1);
+                    File::open(&path)
+                })
+                .unwrap();
+            // The duplicates share the offset of the cached descriptor, which is never used:
+            let mut data = [0; 1];
+            file.read_exact_at(&mut data, 0).unwrap();
+            assert_eq!(data, name.as_bytes());
+        };
+
+        open("a", OFlag::O_RDONLY);
+        open("a", OFlag::O_RDONLY);
+        open("b", OFlag::O_RDONLY);
+        assert_eq!(opened.get(), 2);
+
+        // "a" was used before "b", so "c" takes its place:
+        open("c", OFlag::O_RDONLY);
+        open("b", OFlag::O_RDONLY);
+        assert_eq!(opened.get(), 3);
+        open("a", OFlag::O_RDONLY);
+        open("b", OFlag::O_RDONLY);
+        assert_eq!(opened.get(), 4);
+
+        // Other access is another file, while the rest of the flags are not:
+        open("a", OFlag::O_RDONLY | OFlag::O_NONBLOCK);
+        assert_eq!(opened.get(), 4);
+        open("a", OFlag::O_RDWR);
+        open("a", OFlag::O_RDWR);
+        assert_eq!(opened.get(), 5);
+
+        assert_eq!(
+            cache.stats,
+            FdCacheStats {
+                hits: 5,
+                misses: 5,
+                evictions: 3,
+                invalidations: 0,
+            }
+        );
+        assert_eq!(cache.stats.hit_rate(), 0.5);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn forgotten_files_are_opened_again() {
+        let dir = std::env::temp_dir().join(format!("fdcache_forget_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("file"), b"").unwrap();
+        fs::write(dir.join("sub/file"), b"").unwrap();
+
+        let mut cache = FdCache::new(8);
+        let misses = |cache: &mut FdCache, name: &str| {
+            let path = dir.join(name);
+            let before = cache.stats.misses;
+            cache
+                .open(name.as_bytes(), &path, OFlag::O_RDONLY, || {
+                    File::open(&path)
+                })
+                .unwrap();
+            cache.stats.misses - before
+        };
+
+        assert_eq!(misses(&mut cache, "file"), 1);
+        assert_eq!(misses(&mut cache, "sub/file"), 1);
+        assert_eq!(misses(&mut cache, "file"), 0);
+
+        // Forgetting a directory forgets what is beneath it, and nothing else:
+        cache.forget(&dir.join("sub"));
+        assert_eq!(misses(&mut cache, "file"), 0);
+        assert_eq!(misses(&mut cache, "sub/file"), 1);
+
+        cache.forget(&dir.join("file"));
+        assert_eq!(cache.stats.invalidations, 2);
+        assert_eq!(misses(&mut cache, "file"), 1);
+
+        // A handle is opened again if its file was found at another path:
+        let moved = dir.join("moved");
+        fs::rename(dir.join("file"), &moved).unwrap();
+        cache
+            .open(b"file", &moved, OFlag::O_RDONLY, || File::open(&moved))
+            .unwrap();
+        assert_eq!(cache.stats.misses, 5);
+
+        // Nothing is kept with no capacity:
+        let mut none = FdCache::new(0);
+        assert_eq!(misses(&mut none, "sub/file"), 0);
+        assert_eq!(none.stats, FdCacheStats::default());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
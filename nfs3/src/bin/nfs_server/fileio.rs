@@ -0,0 +1,402 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Positional reads, writes, and syncs of the files in the export, with full-count semantics.
+//
+// pread(2) and pwrite(2) may transfer fewer bytes than asked for, and any of these calls may fail
+// with EINTR. The procedures use these functions instead, so that a READ is only short at the end
+// of the file, and a WRITE either writes all of its data or fails. Errors from the OS are passed
+// on unchanged, so that `nfs_status()` can map them to the matching NFS status.
+//
+// `read_mapped_at()` reads through a memory mapping of the file instead of pread(2), for exports
+// that are set to (`export.read_method = "mmap"`). A file that is truncated while it is copied out
+// of its mapping would raise SIGBUS, which ends the process, so the copy runs under a handler that
+// maps a page of zeros over the page that faulted and lets the copy go on, after which the read is
+// made again with pread(2). SIGBUS from anywhere else is passed on to the handler that was there
+// before.
+
+use std::{
+    cell::Cell,
+    fs::File,
+    io,
+    os::unix::{fs::FileExt, io::AsRawFd},
+    ptr,
+    sync::{
+        atomic::{compiler_fence, AtomicUsize, Ordering},
+        Once, OnceLock,
+    },
+};
+
+use nfs3::nfs3_xdr::StableHow;
+
+/// Retry `f` for as long as it is interrupted by a signal.
+fn retry_interrupted<T>(mut f: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    loop {
+        match f() {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            res => return res,
+        }
+    }
+}
+
+/// Fill `buf` from `offset`, stopping early only at the end of the file. Returns the number of
+/// bytes read.
+pub fn read_full_at(file: &impl FileExt, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match retry_interrupted(|| file.read_at(&mut buf[filled..], offset + filled as u64))? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+
+    Ok(filled)
+}
+
+/// Fill `buf` from `offset`. Fails with `UnexpectedEof` if the file ends first.
+pub fn read_exact_at(file: &impl FileExt, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    let len = buf.len();
+    match read_full_at(file, buf, offset)? {
+        n if n == len => Ok(()),
+        n => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("file ended after {n} of {len} bytes at offset {offset}"),
+        )),
+    }
+}
+
+/// Reads shorter than this are made with pread(2) even by `read_mapped_at()`, since setting up and
+/// tearing down a mapping costs more than it saves for them (see benches/read_throughput.rs).
+const MIN_MAPPED_READ: usize = 64 * 1024;
+
+thread_local! {
+    /// The start and length of the mapping that the thread is copying from, if any.
+    static COPYING: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+
+    /// Whether the copy touched pages of the mapping past the end of the file.
+    static TRUNCATED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The action for SIGBUS before `on_sigbus()` was installed.
+static PREVIOUS_SIGBUS: OnceLock<libc::sigaction> = OnceLock::new();
+
+/// The page size, found before the handler is installed, since sysconf(3) is not async-signal-safe.
+static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+fn install_sigbus_handler() {
+    static INSTALL: Once = Once::new();
+
+    INSTALL.call_once(|| {
+        // SAFETY: sysconf() has no preconditions.
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        PAGE_SIZE.store(page, Ordering::Relaxed);
+
+        // SAFETY: the actions are plain data, which sigaction() only reads from and writes to, and
+        // on_sigbus() has the signature that SA_SIGINFO asks for.
+        unsafe {
+            let mut previous: libc::sigaction = std::mem::zeroed();
+            libc::sigaction(libc::SIGBUS, ptr::null(), &mut previous);
+            let _ = PREVIOUS_SIGBUS.set(previous);
+
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigbus as *const () as usize;
+            action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGBUS, &action, ptr::null_mut());
+        }
+    });
+}
+
+extern "C" fn on_sigbus(
+    signal: libc::c_int,
+    info: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
+    // SAFETY: the kernel passes a valid siginfo_t to a handler installed with SA_SIGINFO.
+    let address = unsafe { (*info).si_addr() } as usize;
+    let (start, len) = COPYING.with(Cell::get);
+    if (start..start + len).contains(&address) {
+        let page = PAGE_SIZE.load(Ordering::Relaxed);
+        // SAFETY: the page is one of the mapping being copied from, which nothing else refers to,
+        // and which the copy only reads, so replacing it with zeros only changes what is read.
+        let zeros = unsafe {
+            libc::mmap(
+                (address - address % page) as *mut libc::c_void,
+                page,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED,
+                -1,
+                0,
+            )
+        };
+        if zeros != libc::MAP_FAILED {
+            TRUNCATED.with(|truncated| truncated.set(true));
+            return;
+        }
+    }
+
+    let Some(previous) = PREVIOUS_SIGBUS.get() else {
+        return;
+    };
+    match previous.sa_sigaction {
+        // With the default action restored, the fault ends the process when it happens again:
+        libc::SIG_DFL | libc::SIG_IGN => {
+            // SAFETY: signal() is async-signal-safe, and restores the default action.
+            unsafe { libc::signal(libc::SIGBUS, libc::SIG_DFL) };
+        }
+        handler if previous.sa_flags & libc::SA_SIGINFO != 0 => {
+            // SAFETY: a handler installed with SA_SIGINFO takes these arguments.
+            let handler: extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void) =
+                unsafe { std::mem::transmute(handler) };
+            handler(signal, info, context);
+        }
+        handler => {
+            // SAFETY: a handler installed without SA_SIGINFO takes only the signal number.
+            let handler: extern "C" fn(libc::c_int) = unsafe { std::mem::transmute(handler) };
+            handler(signal);
+        }
+    }
+}
+
+/// Fill `buf` from `offset` as `read_full_at()` does, but by mapping the range of the file into
+/// memory and copying it from there, after advising the kernel to read it ahead. Short reads, and
+/// files that cannot be mapped, such as those of file systems without mmap support, are read with
+/// pread(2) instead, as are reads of files that are truncated while they are copied.
+pub fn read_mapped_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    copy_mapped_at(file, buf, offset, || {})
+}
+
+/// `read_mapped_at()`, calling `before_copy` once the file is mapped.
+fn copy_mapped_at(
+    file: &File,
+    buf: &mut [u8],
+    offset: u64,
+    before_copy: impl FnOnce(),
+) -> io::Result<usize> {
+    let size = file.metadata()?.len();
+    let len = size.saturating_sub(offset).min(buf.len() as u64) as usize;
+    if len < MIN_MAPPED_READ {
+        return read_full_at(file, buf, offset);
+    }
+
+    install_sigbus_handler();
+
+    // The mapping must start at a page boundary:
+    let page = PAGE_SIZE.load(Ordering::Relaxed) as u64;
+    let start = offset - offset % page;
+    let skip = (offset - start) as usize;
+    let map_len = skip + len;
+
+    // SAFETY: a new read-only mapping is asked for, which does not alias any memory of the process.
+    let map = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            map_len,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            start as libc::off_t,
+        )
+    };
+    if map == libc::MAP_FAILED {
+        let e = io::Error::last_os_error();
+        return match e.raw_os_error() {
+            Some(libc::ENODEV | libc::EACCES | libc::EINVAL) => read_full_at(file, buf, offset),
+            _ => Err(e),
+        };
+    }
+
+    before_copy();
+
+    COPYING.with(|copying| copying.set((map as usize, map_len)));
+    TRUNCATED.with(|truncated| truncated.set(false));
+    compiler_fence(Ordering::SeqCst);
+    // SAFETY: the range is that of the mapping, which holds `len` bytes of the file after `skip`,
+    // and is unmapped only once they have been copied out of it. Pages past the end of the file
+    // are replaced with zeros by on_sigbus() as they are touched.
+    unsafe {
+        // The advice only affects how the file is read ahead, so it may fail harmlessly:
+        libc::madvise(map, map_len, libc::MADV_SEQUENTIAL);
+        libc::madvise(map, map_len, libc::MADV_WILLNEED);
+        ptr::copy_nonoverlapping(map.cast::<u8>().add(skip), buf.as_mut_ptr(), len);
+    }
+    compiler_fence(Ordering::SeqCst);
+    COPYING.with(|copying| copying.set((0, 0)));
+    // SAFETY: nothing refers to the mapping any more.
+    unsafe { libc::munmap(map, map_len) };
+
+    if TRUNCATED.with(Cell::get) {
+        return read_full_at(file, buf, offset);
+    }
+
+    Ok(len)
+}
+
+/// Write all of `data` at `offset`. Fails with `WriteZero` if the file stops accepting data without
+/// reporting an error.
+pub fn write_full_at(file: &impl FileExt, data: &[u8], offset: u64) -> io::Result<()> {
+    let mut written = 0;
+    while written < data.len() {
+        match retry_interrupted(|| file.write_at(&data[written..], offset + written as u64))? {
+            0 => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    format!("wrote {written} of {} bytes at offset {offset}", data.len()),
+                ))
+            }
+            n => written += n,
+        }
+    }
+
+    Ok(())
+}
+
+/// Flush `file` as far as a WRITE with the stability level `stable` requires: not at all for
+/// UNSTABLE, its data for DATA_SYNC, and its data and metadata for FILE_SYNC.
+pub fn sync(file: &File, stable: &StableHow) -> io::Result<()> {
+    match stable {
+        StableHow::Unstable => Ok(()),
+        StableHow::DataSync => retry_interrupted(|| file.sync_data()),
+        StableHow::FileSync => retry_interrupted(|| file.sync_all()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+
+    /// A file in memory whose reads and writes transfer at most `max` bytes at a time, and fail
+    /// with EINTR every other call.
+    struct Stingy {
+        data: RefCell<Vec<u8>>,
+        max: usize,
+        calls: Cell<usize>,
+    }
+
+    impl Stingy {
+        fn new(data: &[u8], max: usize) -> Self {
+            Self {
+                data: RefCell::new(data.to_vec()),
+                max,
+                calls: Cell::new(0),
+            }
+        }
+
+        fn interrupt(&self) -> io::Result<()> {
+            self.calls.set(self.calls.get() + 1);
+            match self.calls.get() % 2 {
+                0 => Err(io::ErrorKind::Interrupted.into()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    impl FileExt for Stingy {
+        fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+            self.interrupt()?;
+            let data = self.data.borrow();
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(self.max).min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            Ok(n)
+        }
+
+        fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+            self.interrupt()?;
+            let mut data = self.data.borrow_mut();
+            let n = buf.len().min(self.max);
+            let end = offset as usize + n;
+            if data.len() < end {
+                data.resize(end, 0);
+            }
+            data[offset as usize..end].copy_from_slice(&buf[..n]);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn short_transfers_are_completed() {
+        let contents: Vec<u8> = (0..100).collect();
+        let file = Stingy::new(&contents, 7);
+
+        let mut buf = [0; 50];
+        assert_eq!(read_full_at(&file, &mut buf, 10).unwrap(), 50);
+        assert_eq!(buf[..], contents[10..60]);
+
+        // Only short at the end of the file:
+        assert_eq!(read_full_at(&file, &mut buf, 80).unwrap(), 20);
+        assert_eq!(buf[..20], contents[80..]);
+        let e = read_exact_at(&file, &mut buf, 80).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+
+        write_full_at(&file, &[0xff; 30], 90).unwrap();
+        let data = file.data.borrow();
+        assert_eq!(data.len(), 120);
+        assert_eq!(data[..90], contents[..90]);
+        assert!(data[90..].iter().all(|&b| b == 0xff));
+    }
+
+    #[test]
+    fn mapped_reads() {
+        let path = std::env::temp_dir().join(format!("fileio_mapped_{}", std::process::id()));
+        let contents: Vec<u8> = (0..300_000).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &contents).unwrap();
+        let file = File::open(&path).unwrap();
+
+        // From an offset that is not on a page boundary:
+        let mut buf = vec![0; 100_000];
+        assert_eq!(read_mapped_at(&file, &mut buf, 3000).unwrap(), 100_000);
+        assert_eq!(buf[..], contents[3000..103_000]);
+
+        // Short only at the end of the file, and empty past it:
+        assert_eq!(read_mapped_at(&file, &mut buf, 220_000).unwrap(), 80_000);
+        assert_eq!(buf[..80_000], contents[220_000..]);
+        assert_eq!(read_mapped_at(&file, &mut buf, 299_000).unwrap(), 1000);
+        assert_eq!(buf[..1000], contents[299_000..]);
+        assert_eq!(read_mapped_at(&file, &mut buf, 400_000).unwrap(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn truncated_mapped_reads() {
+        let path = std::env::temp_dir().join(format!("fileio_truncated_{}", std::process::id()));
+        let contents: Vec<u8> = (0..300_000).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &contents).unwrap();
+        let file = File::open(&path).unwrap();
+
+        // The file is truncated once it is mapped, so the copy touches pages past its end, and the
+        // read is short instead of ending the process:
+        let mut buf = vec![0; 200_000];
+        let truncate = || {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_len(10_000)
+                .unwrap()
+        };
+        assert_eq!(
+            copy_mapped_at(&file, &mut buf, 3000, truncate).unwrap(),
+            7000
+        );
+        assert_eq!(buf[..7000], contents[3000..10_000]);
+
+        // Reads go on as before:
+        assert_eq!(read_mapped_at(&file, &mut buf, 0).unwrap(), 10_000);
+        std::fs::write(&path, &contents).unwrap();
+        assert_eq!(read_mapped_at(&file, &mut buf, 0).unwrap(), 200_000);
+        assert_eq!(buf[..], contents[..200_000]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn stalled_writes_fail() {
+        let file = Stingy::new(&[], 0);
+        let e = write_full_at(&file, &[1; 8], 0).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::WriteZero);
+    }
+}
@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Helpers shared by the procedure implementations for mapping between the local file system and the
+// NFS representations of file attributes and errors, and for the weak cache consistency data of the
+// files in a `VfsBackend`.
+
+use std::{
+    fs::Metadata,
+    io,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::Path,
+};
+
+use nfs3::nfs3_xdr::*;
+
+use crate::vfs::VfsBackend;
+
+/// Convert the metadata of a local file into NFS file attributes.
+pub fn file_attributes(metadata: &Metadata) -> FileAttributes {
+    let file_type = metadata.file_type();
+    let r#type = if file_type.is_dir() {
+        FileType::Dir
+    } else if file_type.is_symlink() {
+        FileType::Lnk
+    } else if file_type.is_block_device() {
+        FileType::Blk
+    } else if file_type.is_char_device() {
+        FileType::Chr
+    } else if file_type.is_socket() {
+        FileType::Sock
+    } else if file_type.is_fifo() {
+        FileType::Fifo
+    } else {
+        FileType::Reg
+    };
+
+    let rdev = metadata.rdev();
+
+    FileAttributes {
+        r#type,
+        mode: metadata.mode() & 0o7777,
+        nlink: metadata.nlink() as u32,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        size: metadata.size(),
+        used: metadata.blocks() * 512,
+        rdev: SpecData {
+            specdata1: libc::major(rdev),
+            specdata2: libc::minor(rdev),
+        },
+        fsid: metadata.dev(),
+        fileid: metadata.ino(),
+        atime: nfs_time(metadata.atime(), metadata.atime_nsec()),
+        mtime: nfs_time(metadata.mtime(), metadata.mtime_nsec()),
+        ctime: nfs_time(metadata.ctime(), metadata.ctime_nsec()),
+    }
+}
+
+fn nfs_time(seconds: i64, nseconds: i64) -> NfsTime {
+    NfsTime {
+        seconds: seconds as u32,
+        nseconds: nseconds as u32,
+    }
+}
+
+/// The attributes saved before an operation for weak cache consistency checking.
+pub fn pre_op_attributes(vfs: &dyn VfsBackend, path: &Path) -> PreOpAttributes {
+    let inner = vfs.getattr(path).ok().map(|a| WccAttributes {
+        size: a.size,
+        mtime: a.mtime,
+        ctime: a.ctime,
+    });
+
+    PreOpAttributes { inner }
+}
+
+/// The attributes of a file after an operation completes.
+pub fn post_op_attributes(vfs: &dyn VfsBackend, path: &Path) -> PostOpAttributes {
+    PostOpAttributes {
+        inner: vfs.getattr(path).ok(),
+    }
+}
+
+/// Run `op`, which may change the file at `path`, and return its result along with the weak cache
+/// consistency data of the file: its size and times from before `op` runs, and its attributes from
+/// after. Every procedure that changes a file or directory reports this data for it, whether or not
+/// the change succeeded, so that clients can tell whether their cached attributes are still valid.
+pub fn with_wcc<T>(vfs: &dyn VfsBackend, path: &Path, op: impl FnOnce() -> T) -> (T, WccData) {
+    let before = pre_op_attributes(vfs, path);
+    let res = op();
+    let wcc = WccData {
+        before,
+        after: post_op_attributes(vfs, path),
+    };
+
+    (res, wcc)
+}
+
+/// Map an I/O error from the local file system to the closest NFS status.
+pub fn nfs_status(e: &io::Error) -> NfsResult {
+    let Some(errno) = e.raw_os_error() else {
+        return NfsResult::Io;
+    };
+
+    match nix::errno::Errno::from_raw(errno) {
+        nix::Error::EPERM => NfsResult::Perm,
+        nix::Error::ENOENT => NfsResult::NoEnt,
+        nix::Error::ENXIO => NfsResult::Nxio,
+        nix::Error::EACCES => NfsResult::Acces,
+        nix::Error::EEXIST => NfsResult::Exist,
+        nix::Error::EXDEV => NfsResult::XDev,
+        nix::Error::ENODEV => NfsResult::NoDev,
+        nix::Error::ENOTDIR => NfsResult::NotDir,
+        nix::Error::EISDIR => NfsResult::IsDir,
+        nix::Error::EINVAL => NfsResult::Inval,
+        nix::Error::EFBIG => NfsResult::FBig,
+        nix::Error::ENOSPC => NfsResult::NoSpc,
+        nix::Error::EROFS => NfsResult::RoFs,
+        nix::Error::EMLINK => NfsResult::MLink,
+        nix::Error::ENAMETOOLONG => NfsResult::NameTooLong,
+        nix::Error::ENOTEMPTY => NfsResult::NotEmpty,
+        nix::Error::EDQUOT => NfsResult::Dquot,
+        nix::Error::ESTALE => NfsResult::Stale,
+        _ => NfsResult::Io,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::vfs::LocalFs;
+
+    #[test]
+    fn wcc_of_a_changed_file() {
+        let path = std::env::temp_dir().join(format!("files_wcc_{}", std::process::id()));
+        fs::write(&path, b"abc").unwrap();
+        let local = LocalFs::exporting(&[&std::env::temp_dir()]);
+
+        let (res, wcc) = with_wcc(&local, &path, || fs::write(&path, b"abcdef"));
+        res.unwrap();
+        assert_eq!(wcc.before.inner.unwrap().size, 3);
+        assert_eq!(wcc.after.inner.unwrap().size, 6);
+
+        // A file that the operation removes has no attributes after it:
+        let (res, wcc) = with_wcc(&local, &path, || fs::remove_file(&path));
+        res.unwrap();
+        assert_eq!(wcc.before.inner.unwrap().size, 6);
+        assert!(wcc.after.inner.is_none());
+
+        // Nor before, if it did not exist:
+        let (res, wcc) = with_wcc(&local, &path, || fs::metadata(&path));
+        assert!(res.is_err());
+        assert!(wcc.before.inner.is_none() && wcc.after.inner.is_none());
+    }
+}
@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Implementation of the FSSTAT and FSINFO procedures, which describe the file system of an export.
+//
+// FSSTAT reports the space and files of the file system that the root of the export is on, as
+// statvfs(3) gives them, whichever file of the export its handle is of. FSINFO reports the sizes
+// that the server is set up for, so that clients size their calls to fit: the largest READ it
+// replies to in full, the largest WRITE whose call fits in its receive buffers, which
+// `nfs_server.max_record` may lower, and READDIR replies as large as READs.
+
+use log::*;
+use nfs3::nfs3_xdr::*;
+use rpc_protocol::ring::*;
+
+use crate::{files::*, read::MAX_READ, results::*, ServerState};
+
+/// The multiple that READs and WRITEs should be sized to, the size of a page.
+const TRANSFER_MULTIPLE: u32 = 4096;
+
+pub fn fsstat(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, FsStatArgs);
+
+    reply::<FsStatResult>(|| {
+        let path = state.lookup(call, &args.fsroot.data)?;
+        let root = state.handles.export_of(&path).unwrap_or(&path);
+        debug!("FSSTAT: {}", root.display());
+
+        let obj_attributes = post_op_attributes(&*state.vfs, &path);
+        let stats = state
+            .vfs
+            .fsstat(root)
+            .inspect_err(|e| warn!("FSSTAT of {} failed: {e}", root.display()))
+            .or_fail(|| FsStatFailure { obj_attributes })?;
+
+        Ok(FsStatSuccess {
+            obj_attributes,
+            tbytes: stats.total_bytes,
+            fbytes: stats.free_bytes,
+            abytes: stats.available_bytes,
+            tfiles: stats.total_files,
+            ffiles: stats.free_files,
+            afiles: stats.available_files,
+            // The file system may change at any time:
+            invarsec: 0,
+        })
+    })
+}
+
+pub fn fsinfo(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, FsInfoArgs);
+
+    reply::<FsInfoResult>(|| {
+        let path = state.lookup(call, &args.fsroot.data)?;
+        debug!("FSINFO: {}", path.display());
+
+        Ok(FsInfoSuccess {
+            obj_attributes: post_op_attributes(&*state.vfs, &path),
+            ..info(state.max_write)
+        })
+    })
+}
+
+/// The FSINFO of a server that writes up to `max_write` bytes at once, without attributes.
+fn info(max_write: u32) -> FsInfoSuccess {
+    FsInfoSuccess {
+        obj_attributes: PostOpAttributes::default(),
+        rtmax: MAX_READ,
+        rtpref: MAX_READ,
+        rtmult: TRANSFER_MULTIPLE,
+        wtmax: max_write,
+        wtpref: match max_write / TRANSFER_MULTIPLE {
+            0 => max_write,
+            pages => pages * TRANSFER_MULTIPLE,
+        },
+        wtmult: TRANSFER_MULTIPLE,
+        dtpref: MAX_READ,
+        maxfilesize: i64::MAX as u64,
+        time_delta: NfsTime {
+            seconds: 0,
+            nseconds: 1,
+        },
+        properties: FSF3_LINK | FSF3_SYMLINK | FSF3_HOMOGENEOUS | FSF3_CANSETTIME,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    use crate::{
+        memfs::MemFs,
+        vfs::{LocalFs, VfsBackend},
+        write::{max_write, MAX_WRITE, MAX_WRITE_CALL},
+    };
+
+    #[test]
+    fn sizes_follow_the_largest_call() {
+        let full = info(max_write(MAX_WRITE_CALL));
+        assert_eq!(
+            (full.rtmax, full.rtpref, full.dtpref),
+            (MAX_READ, MAX_READ, MAX_READ)
+        );
+        assert_eq!((full.wtmax, full.wtpref), (MAX_WRITE, MAX_WRITE));
+
+        // A smaller largest call only fits smaller WRITEs, preferably of whole pages:
+        let overhead = MAX_WRITE_CALL - MAX_WRITE;
+        let smaller = info(max_write(16 * 1024));
+        assert_eq!(smaller.wtmax, 16 * 1024 - overhead);
+        assert_eq!(smaller.wtpref, smaller.wtmax / 4096 * 4096);
+        assert!(smaller.wtpref < smaller.wtmax);
+        let small = info(max_write(4096));
+        assert_eq!(small.wtpref, small.wtmax);
+    }
+
+    #[test]
+    fn space_of_the_backends() {
+        let local = LocalFs::exporting(&[&std::env::temp_dir()]);
+        let memory = MemFs::default();
+        let backends: [(&dyn VfsBackend, &Path); 2] =
+            [(&local, &std::env::temp_dir()), (&memory, Path::new("/"))];
+        for (vfs, root) in backends {
+            let stats = vfs.fsstat(root).unwrap();
+            assert!(stats.total_bytes > 0 && stats.total_bytes >= stats.free_bytes);
+            assert!(stats.free_bytes >= stats.available_bytes);
+            assert!(stats.total_files >= stats.free_files);
+        }
+
+        let e = memory.fsstat(Path::new("/missing")).unwrap_err();
+        assert_eq!(e.raw_os_error(), Some(libc::ENOENT));
+    }
+}
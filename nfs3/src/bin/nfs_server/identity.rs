@@ -0,0 +1,249 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Mapping the identities of callers to the local user and group ids that their file system
+// operations are performed as.
+//
+// Calls with a credential flavor that is not configured are rejected with AUTH_TOOWEAK. Each call's
+// AUTH_SYS credential is mapped as configured: the root user is squashed to the anonymous user
+// unless `--squash none` is given, and every user is with `--squash all`. Calls without an AUTH_SYS
+// credential are always anonymous. When the server runs as root, it then switches its file system
+// credentials (see setfsuid(2)) to the mapped identity, so that permission checks and the owners of
+// new files are those of the caller. setfsuid(2) and setfsgid(2) change only the calling thread,
+// but glibc's setgroups() changes the supplementary groups of every thread in the process, so the
+// server must do all of its file system work on one thread, that of the ring.
+//
+// An export may squash the callers of some of its clients differently (see nfs3::clients), which
+// is only known once the file handle of a call has been looked up: `ServerState::lookup()` then maps
+// the credential again, and switches to that identity instead.
+
+use std::cell::RefCell;
+
+use log::*;
+use nfs3::config::{Flavor, Squash};
+use nix::unistd::{self, Gid, Uid};
+use rpc_protocol::{
+    server::{Authenticated, Authenticator, SysAuthenticator},
+    AuthError, Call, Principal,
+};
+
+/// The user and groups that a call's file system operations are performed as.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Identity {
+    pub uid: u32,
+    pub gid: u32,
+    pub gids: Vec<u32>,
+}
+
+/// Maps the principals of calls to local identities.
+#[derive(Clone, Debug)]
+pub struct IdentityMap {
+    /// The credential flavors that calls may have.
+    pub flavors: Vec<Flavor>,
+
+    pub squash: Squash,
+    pub anon_uid: u32,
+    pub anon_gid: u32,
+}
+
+impl IdentityMap {
+    fn anonymous(&self) -> Identity {
+        Identity {
+            uid: self.anon_uid,
+            gid: self.anon_gid,
+            gids: Vec::new(),
+        }
+    }
+
+    /// The identity that a call from `principal` is performed as.
+    pub fn map(&self, principal: &Principal) -> Identity {
+        self.map_squashed(principal, self.squash)
+    }
+
+    /// The identity that `call` is performed as with `squash` in place of the configured squash,
+    /// mapped from its credential again.
+    pub fn map_call(&self, call: &Call, squash: Squash) -> Identity {
+        match SysAuthenticator.authenticate(call) {
+            Ok(authenticated) => self.map_squashed(&authenticated.principal, squash),
+            Err(_) => self.anonymous(),
+        }
+    }
+
+    /// The identity that a call from `principal` is performed as, with `squash` in place of the
+    /// configured squash.
+    pub fn map_squashed(&self, principal: &Principal, squash: Squash) -> Identity {
+        let Principal::Unix { uid, gid, gids } = principal else {
+            return self.anonymous();
+        };
+
+        match squash {
+            Squash::All => self.anonymous(),
+            Squash::Root => {
+                let squash = |id: u32, anon: u32| if id == 0 { anon } else { id };
+                Identity {
+                    uid: squash(*uid, self.anon_uid),
+                    gid: squash(*gid, self.anon_gid),
+                    gids: gids.iter().map(|&g| squash(g, self.anon_gid)).collect(),
+                }
+            }
+            Squash::None => Identity {
+                uid: *uid,
+                gid: *gid,
+                gids: gids.clone(),
+            },
+        }
+    }
+}
+
+impl Identity {
+    /// The principal that procedures see for a call performed as this identity.
+    pub fn principal(&self) -> Principal {
+        Principal::Unix {
+            uid: self.uid,
+            gid: self.gid,
+            gids: self.gids.clone(),
+        }
+    }
+}
+
+thread_local! {
+    /// Whether the server can switch its file system credentials.
+    static SWITCH: bool = unistd::geteuid().is_root();
+
+    /// The identity that the file system credentials of this thread were last switched to.
+    static CURRENT: RefCell<Option<Identity>> = const { RefCell::new(None) };
+}
+
+/// Switch the file system credentials of this thread, and the supplementary groups of the whole
+/// process, to `identity`, if the server runs as root.
+pub fn assume(identity: &Identity) -> nix::Result<()> {
+    if !SWITCH.with(|switch| *switch) || CURRENT.with_borrow(|c| c.as_ref() == Some(identity)) {
+        return Ok(());
+    }
+
+    // Forget the current identity until the switch succeeds, so a failed switch is retried.
+    CURRENT.set(None);
+
+    // The groups come first: setgroups(2) needs CAP_SETGID, and setfsuid(2) to a user other than
+    // root drops the file system capabilities, but keeps CAP_SETGID.
+    let gids: Vec<Gid> = identity.gids.iter().map(|&g| Gid::from_raw(g)).collect();
+    unistd::setgroups(&gids)?;
+    unistd::setfsgid(Gid::from_raw(identity.gid));
+    unistd::setfsuid(Uid::from_raw(identity.uid));
+
+    CURRENT.set(Some(identity.clone()));
+
+    Ok(())
+}
+
+/// The identity that this thread last switched to, if it has.
+pub fn current() -> Option<Identity> {
+    CURRENT.with_borrow(Clone::clone)
+}
+
+/// The user and group that new files of this thread are owned by: those of the identity it last
+/// switched to, or the server's own if it has not switched.
+pub fn owner() -> (u32, u32) {
+    CURRENT.with_borrow(|current| match current {
+        Some(identity) => (identity.uid, identity.gid),
+        None => (unistd::geteuid().as_raw(), unistd::getegid().as_raw()),
+    })
+}
+
+/// Authenticates calls as `SysAuthenticator` does, and gives procedures the mapped identity of the
+/// caller as its principal.
+///
+/// When the server runs as root, the file system credentials of the thread are also switched to
+/// that identity. Authentication happens on the same thread, right before each call is dispatched,
+/// so the procedure runs with the caller's credentials.
+pub struct IdentityMapper {
+    map: IdentityMap,
+}
+
+impl IdentityMapper {
+    pub fn new(map: IdentityMap) -> Self {
+        if !unistd::geteuid().is_root() {
+            warn!(
+                "Not running as root: file system operations are performed as the server's own \
+                 user, whatever the identity of the caller"
+            );
+        }
+
+        Self { map }
+    }
+}
+
+impl Authenticator for IdentityMapper {
+    fn authenticate(&mut self, call: &Call) -> Result<Authenticated, AuthError> {
+        let flavor = &call.get_credential().flavor;
+        if !self.map.flavors.iter().any(|f| f.matches(flavor)) {
+            debug!("CALL with {flavor:?} credential, which is not accepted");
+            return Err(AuthError::TooWeak);
+        }
+
+        let authenticated = SysAuthenticator.authenticate(call)?;
+
+        let identity = self.map.map(&authenticated.principal);
+        if let Err(e) = assume(&identity) {
+            warn!("Could not switch to uid {}: {e}", identity.uid);
+            return Err(AuthError::Failed);
+        }
+
+        Ok(Authenticated {
+            principal: identity.principal(),
+            verifier: authenticated.verifier,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(squash: Squash) -> IdentityMap {
+        IdentityMap {
+            flavors: vec![Flavor::Sys],
+            squash,
+            anon_uid: 99,
+            anon_gid: 98,
+        }
+    }
+
+    fn unix(uid: u32, gid: u32, gids: &[u32]) -> Principal {
+        Principal::Unix {
+            uid,
+            gid,
+            gids: gids.to_vec(),
+        }
+    }
+
+    fn identity(uid: u32, gid: u32, gids: &[u32]) -> Identity {
+        Identity {
+            uid,
+            gid,
+            gids: gids.to_vec(),
+        }
+    }
+
+    #[test]
+    fn squashing() {
+        let root = unix(0, 0, &[0, 10]);
+        let user = unix(1000, 100, &[100, 0]);
+        let anonymous = identity(99, 98, &[]);
+
+        let none = map(Squash::None);
+        assert_eq!(none.map(&root), identity(0, 0, &[0, 10]));
+        assert_eq!(none.map(&user), identity(1000, 100, &[100, 0]));
+        assert_eq!(none.map(&Principal::Anonymous), anonymous);
+
+        let squash_root = map(Squash::Root);
+        assert_eq!(squash_root.map(&root), identity(99, 98, &[98, 10]));
+        assert_eq!(squash_root.map(&user), identity(1000, 100, &[100, 98]));
+        assert_eq!(squash_root.map(&Principal::Anonymous), anonymous);
+
+        let all = map(Squash::All);
+        assert_eq!(all.map(&root), anonymous);
+        assert_eq!(all.map(&user), anonymous);
+        assert_eq!(all.map(&Principal::Named("alice".into())), anonymous);
+    }
+}
@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Implementations of the procedures for symbolic and hard links: SYMLINK, READLINK, and LINK.
+//
+// The target of a symbolic link is stored as the client gave it, and is never followed by the
+// server: it is resolved by the client, relative to the client's view of the file system. A hard
+// link can only be made within the export that the file is in, as with RENAME.
+
+use log::*;
+
+use nfs3::nfs3_xdr::*;
+use rpc_protocol::ring::{RingCall, RingResult};
+
+use crate::{
+    dirops::{check_new_name, set_new_attributes},
+    files::*,
+    results::*,
+    ServerState,
+};
+
+/// The mode that symbolic links are made with, which is never checked.
+const SYMLINK_MODE: u32 = 0o777;
+
+/// SYMLINK makes a symbolic link, which is given the owner and times set by the client; its mode
+/// cannot be set.
+pub fn symlink(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, SymlinkArgs);
+
+    let name = args.r#where.name;
+    let target = args.symlink.symlink_data;
+    debug!(
+        "SYMLINK: {} to {}",
+        name.to_string_lossy(),
+        target.to_string_lossy()
+    );
+
+    reply::<SymlinkResult>(|| {
+        let dir = state.lookup(call, &args.r#where.dir.data)?;
+        let path = dir.join(&name);
+
+        let vfs = &*state.vfs;
+        let (res, dir_wcc) = with_wcc(vfs, &dir, || -> Result<(), NfsError> {
+            state.check_writable(&dir)?;
+            check_new_name(&name)?;
+            state.check_not_overlay(&dir, &name)?;
+            if target.is_empty() {
+                return Err(NfsResult::Inval.into());
+            }
+
+            vfs.symlink(&target, &path)
+                .and_then(|()| {
+                    set_new_attributes(vfs, &path, &args.symlink.symlink_attributes, SYMLINK_MODE)
+                })
+                .map_err(|e| {
+                    debug!("SYMLINK of {} failed: {e}", path.display());
+                    e.into()
+                })
+        });
+
+        state.attributes.forget(&dir);
+        res.or_fail(|| SymlinkFailure { dir_wcc })?;
+
+        Ok(SymlinkSuccess {
+            obj: PostOpFileHandle {
+                inner: state
+                    .handles
+                    .handle_for(&path)
+                    .ok()
+                    .map(|data| FileHandle { data }),
+            },
+            obj_attributes: post_op_attributes(vfs, &path),
+            dir_wcc,
+        })
+    })
+}
+
+/// READLINK of a file that is not a symbolic link fails with NFS3ERR_INVAL.
+pub fn readlink(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, ReadlinkArgs);
+
+    reply::<ReadlinkResult>(|| {
+        let path = state.lookup(call, &args.symlink.data)?;
+        debug!("READLINK: {}", path.display());
+
+        let symlink_attributes = post_op_attributes(&*state.vfs, &path);
+        let data =
+            (state.vfs.readlink(&path)).or_fail(|| ReadlinkFailure { symlink_attributes })?;
+
+        Ok(ReadlinkSuccess {
+            symlink_attributes,
+            data,
+        })
+    })
+}
+
+/// LINK makes a hard link to a file other than a directory, which fails with NFS3ERR_PERM as
+/// link(2) does.
+pub fn link(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, LinkArgs);
+
+    let name = args.link.name;
+    debug!("LINK: {}", name.to_string_lossy());
+
+    reply::<LinkResult>(|| {
+        let file = state.lookup(call, &args.file.data)?;
+        let dir = state.lookup(call, &args.link.dir.data)?;
+
+        let vfs = &*state.vfs;
+        let (res, linkdir_wcc) = with_wcc(vfs, &dir, || -> Result<(), NfsError> {
+            state.check_writable(&dir)?;
+            check_new_name(&name)?;
+            state.check_not_overlay(&dir, &name)?;
+            if state.handles.export_of(&file) != state.handles.export_of(&dir) {
+                return Err(NfsResult::XDev.into());
+            }
+
+            let path = dir.join(&name);
+            vfs.link(&file, &path).map_err(|e| {
+                debug!(
+                    "LINK of {} to {} failed: {e}",
+                    path.display(),
+                    file.display()
+                );
+                e.into()
+            })
+        });
+
+        state.attributes.forget(&dir);
+        state.attributes.forget(&file);
+
+        // Taken after the link is made, so that a client sees the file's new link count:
+        let file_attributes = post_op_attributes(vfs, &file);
+        res.or_fail(|| LinkFailure {
+            file_attributes,
+            linkdir_wcc,
+        })?;
+
+        Ok(LinkSuccess {
+            file_attributes,
+            linkdir_wcc,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{fs, path::Path};
+
+    use crate::{
+        memfs::MemFs,
+        vfs::{LocalFs, VfsBackend},
+    };
+
+    #[test]
+    fn symlinks_keep_their_target() {
+        let dir = std::env::temp_dir().join(format!("links_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let local = LocalFs::exporting(&[&dir]);
+
+        let memory = MemFs::default();
+        let backends: [(&dyn VfsBackend, &Path); 2] = [(&local, &dir), (&memory, Path::new("/"))];
+        for (vfs, dir) in backends {
+            let path = dir.join("link");
+
+            // The target is stored as given, even if it leads nowhere, or out of the export:
+            vfs.symlink("../../etc/passwd".as_ref(), &path).unwrap();
+            let attributes = SetAttributes {
+                mode: SetMode { inner: Some(0o600) },
+                mtime: SetMtime::SetToClientTime(NfsTime {
+                    seconds: 1_000_000,
+                    nseconds: 0,
+                }),
+                ..SetAttributes::default()
+            };
+            set_new_attributes(vfs, &path, &attributes, SYMLINK_MODE).unwrap();
+            assert_eq!(vfs.readlink(&path).unwrap(), "../../etc/passwd");
+            let symlink = vfs.getattr(&path).unwrap();
+            assert_eq!(symlink.r#type, FileType::Lnk);
+            assert_eq!(symlink.mode, 0o777);
+            assert_eq!(symlink.mtime.seconds, 1_000_000);
+
+            let e = vfs.readlink(dir).unwrap_err();
+            assert_eq!(nfs_status(&e), NfsResult::Inval);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,669 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// A file system kept in memory: the `memory` backend (see `nfs3::config::Backend`), for
+// benchmarks and tests that should not wait on storage, or that cannot write to it.
+//
+// `MemFs` behaves as the local file system would, failing with the same errnos, but checks no
+// permissions: new files are owned by the caller's mapped identity (see `identity::owner()`), but
+// anyone may do anything to them. Directories list their entries in the order of their names, and
+// the cookie of an entry is its position in that order, counting from 1. Its space is the memory
+// of the host, and the number of its files is only limited by the file ids.
+//
+// The root of the tree is at the path of the export, and takes the inode number and generation of
+// the export directory, so that the file handle that mountd gives out for the export, which it
+// makes from the local directory, names the root of the tree too. The other files have file ids of
+// their own, which are never reused, and no generation; `MemFs` is the `FileSystem` that the file
+// handle map finds them through.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::{OsStr, OsString},
+    fs, io,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use nix::sys::sysinfo::sysinfo;
+
+use nfs3::{
+    file_handle::{FileId, FileSystem, LocalFileSystem},
+    nfs3_xdr::*,
+};
+
+use crate::{
+    files::file_attributes,
+    identity,
+    vfs::{DirEntry, Entries, FsStats, VfsBackend},
+};
+
+/// The file id of the root directory of a tree that does not mirror an export directory.
+const ROOT: u64 = 1;
+
+/// The fsid reported for every file.
+const FSID: u64 = 0x6d656d;
+
+/// A tree of files in memory.
+#[derive(Debug)]
+pub struct MemFs {
+    /// The generation of the root directory.
+    root_generation: u32,
+
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// The path of the root directory, which every path must be under.
+    root_path: PathBuf,
+
+    /// The file id of the root directory.
+    root: u64,
+
+    nodes: HashMap<u64, Node>,
+    next_fileid: u64,
+}
+
+#[derive(Debug)]
+struct Node {
+    attributes: FileAttributes,
+    contents: Contents,
+}
+
+#[derive(Debug)]
+enum Contents {
+    File(Vec<u8>),
+    Dir(BTreeMap<OsString, u64>),
+    Symlink(OsString),
+    Special,
+}
+
+fn error(errno: i32) -> io::Error {
+    io::Error::from_raw_os_error(errno)
+}
+
+fn now() -> NfsTime {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+
+    NfsTime {
+        seconds: now.as_secs() as u32,
+        nseconds: now.subsec_nanos(),
+    }
+}
+
+impl MemFs {
+    /// An empty tree at `root`, with symbolic links resolved as they are in the exports, whose root
+    /// directory has the identity, mode, owner, and times of the local directory there.
+    pub fn new(root: &Path) -> io::Result<Self> {
+        let root = &root.canonicalize()?;
+        let id = LocalFileSystem.identify(root)?;
+        if !id.is_dir {
+            return Err(error(libc::ENOTDIR));
+        }
+        let local = file_attributes(&fs::symlink_metadata(root)?);
+
+        let mut tree = Self::with_root(root, id.ino, LocalFileSystem.generation(root, &id));
+        let attributes = &mut tree.inner.get_mut().unwrap().root_mut().attributes;
+        attributes.mode = local.mode;
+        attributes.uid = local.uid;
+        attributes.gid = local.gid;
+        attributes.atime = local.atime;
+        attributes.mtime = local.mtime;
+        attributes.ctime = local.ctime;
+
+        Ok(tree)
+    }
+
+    fn with_root(root_path: &Path, root: u64, root_generation: u32) -> Self {
+        let mut node = Node::new(root, FileType::Dir, 0o755, Contents::Dir(BTreeMap::new()));
+        node.attributes.nlink = 2;
+
+        Self {
+            root_generation,
+            inner: Mutex::new(Inner {
+                root_path: root_path.to_path_buf(),
+                root,
+                nodes: HashMap::from([(root, node)]),
+                next_fileid: 1,
+            }),
+        }
+    }
+}
+
+/// An empty tree at "/".
+impl Default for MemFs {
+    fn default() -> Self {
+        Self::with_root(Path::new("/"), ROOT, 0)
+    }
+}
+
+impl Node {
+    fn new(fileid: u64, r#type: FileType, mode: u32, contents: Contents) -> Self {
+        let now = now();
+        let (uid, gid) = identity::owner();
+
+        Self {
+            attributes: FileAttributes {
+                r#type,
+                mode: mode & 0o7777,
+                nlink: 1,
+                uid,
+                gid,
+                fsid: FSID,
+                fileid,
+                atime: now,
+                mtime: now,
+                ctime: now,
+                ..FileAttributes::default()
+            },
+            contents,
+        }
+    }
+
+    fn entries(&self) -> io::Result<&BTreeMap<OsString, u64>> {
+        match &self.contents {
+            Contents::Dir(entries) => Ok(entries),
+            _ => Err(error(libc::ENOTDIR)),
+        }
+    }
+
+    fn entries_mut(&mut self) -> io::Result<&mut BTreeMap<OsString, u64>> {
+        match &mut self.contents {
+            Contents::Dir(entries) => Ok(entries),
+            _ => Err(error(libc::ENOTDIR)),
+        }
+    }
+
+    /// Record that the node's contents changed.
+    fn modified(&mut self) {
+        let now = now();
+        self.attributes.mtime = now;
+        self.attributes.ctime = now;
+    }
+}
+
+impl Inner {
+    fn node(&self, fileid: u64) -> &Node {
+        &self.nodes[&fileid]
+    }
+
+    fn node_mut(&mut self, fileid: u64) -> &mut Node {
+        self.nodes.get_mut(&fileid).unwrap()
+    }
+
+    fn root_mut(&mut self) -> &mut Node {
+        self.node_mut(self.root)
+    }
+
+    /// The file id of the file at `path`, which must be under the root. Symbolic links along the
+    /// way are not followed, so a path through one fails with ENOTDIR.
+    fn resolve(&self, path: &Path) -> io::Result<u64> {
+        let Ok(path) = path.strip_prefix(&self.root_path) else {
+            return Err(error(libc::ENOENT));
+        };
+
+        let mut fileid = self.root;
+        for component in path.components() {
+            match component {
+                Component::RootDir | Component::CurDir => {}
+                Component::Normal(name) => {
+                    fileid = *self
+                        .node(fileid)
+                        .entries()?
+                        .get(name)
+                        .ok_or_else(|| error(libc::ENOENT))?;
+                }
+                Component::ParentDir | Component::Prefix(_) => return Err(error(libc::EINVAL)),
+            }
+        }
+
+        Ok(fileid)
+    }
+
+    /// The file id of the directory that `path` is an entry of, and the name of the entry.
+    fn parent<'a>(&self, path: &'a Path) -> io::Result<(u64, &'a OsStr)> {
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(error(libc::EINVAL));
+        };
+        let dir = self.resolve(dir)?;
+        self.node(dir).entries()?;
+
+        Ok((dir, name))
+    }
+
+    /// Add a new node with `contents` at `path`, where there must not be an entry yet.
+    fn add(
+        &mut self,
+        path: &Path,
+        r#type: FileType,
+        mode: u32,
+        contents: Contents,
+    ) -> io::Result<()> {
+        let (dir, name) = self.parent(path)?;
+        if self.node(dir).entries()?.contains_key(name) {
+            return Err(error(libc::EEXIST));
+        }
+
+        while self.nodes.contains_key(&self.next_fileid) {
+            self.next_fileid += 1;
+        }
+        let fileid = self.next_fileid;
+        self.next_fileid += 1;
+        let mut node = Node::new(fileid, r#type, mode, contents);
+        if r#type == FileType::Dir {
+            node.attributes.nlink = 2;
+            self.node_mut(dir).attributes.nlink += 1;
+        }
+        self.nodes.insert(fileid, node);
+
+        let dir = self.node_mut(dir);
+        dir.entries_mut()?.insert(name.to_os_string(), fileid);
+        dir.modified();
+
+        Ok(())
+    }
+
+    /// Drop a link to `fileid`, and the node itself once nothing links to it.
+    fn unlink(&mut self, fileid: u64) {
+        let node = self.node_mut(fileid);
+        node.attributes.ctime = now();
+        let min_links = match node.contents {
+            Contents::Dir(_) => 2,
+            _ => 1,
+        };
+        if node.attributes.nlink > min_links {
+            node.attributes.nlink -= 1;
+        } else {
+            self.nodes.remove(&fileid);
+        }
+    }
+
+    /// Whether `ancestor` is `fileid`, or a directory that it is in.
+    fn contains(&self, ancestor: u64, fileid: u64) -> bool {
+        ancestor == fileid
+            || self.node(ancestor).entries().is_ok_and(|entries| {
+                entries.values().any(|&child| {
+                    self.node(child).entries().is_ok() && self.contains(child, fileid)
+                })
+            })
+    }
+}
+
+impl VfsBackend for MemFs {
+    fn getattr(&self, path: &Path) -> io::Result<FileAttributes> {
+        let inner = self.inner.lock().unwrap();
+        let fileid = inner.resolve(path)?;
+
+        Ok(inner.node(fileid).attributes)
+    }
+
+    fn setattr(&self, path: &Path, attributes: &SetAttributes) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let fileid = inner.resolve(path)?;
+        let node = inner.node_mut(fileid);
+
+        if let Some(size) = attributes.size.inner {
+            let Contents::File(data) = &mut node.contents else {
+                return Err(error(libc::EINVAL));
+            };
+            data.resize(size as usize, 0);
+            node.attributes.size = size;
+            node.attributes.used = size;
+            node.modified();
+        }
+
+        let file = &mut node.attributes;
+        if let Some(uid) = attributes.uid.inner {
+            file.uid = uid;
+        }
+        if let Some(gid) = attributes.gid.inner {
+            file.gid = gid;
+        }
+        if let Some(mode) = attributes.mode.inner {
+            if file.r#type != FileType::Lnk {
+                file.mode = mode & 0o7777;
+            }
+        }
+
+        match attributes.atime {
+            SetAtime::DontChange => {}
+            SetAtime::SetToServerTime => file.atime = now(),
+            SetAtime::SetToClientTime(time) => file.atime = time,
+        }
+        match attributes.mtime {
+            SetMtime::DontChange => {}
+            SetMtime::SetToServerTime => file.mtime = now(),
+            SetMtime::SetToClientTime(time) => file.mtime = time,
+        }
+        file.ctime = now();
+
+        Ok(())
+    }
+
+    fn lookup(&self, dir: &Path, name: &OsStr) -> io::Result<PathBuf> {
+        let path = dir.join(name);
+        let inner = self.inner.lock().unwrap();
+        inner.node(inner.resolve(dir)?).entries()?;
+        inner.resolve(&path)?;
+
+        Ok(path)
+    }
+
+    fn read(&self, path: &Path, offset: u64, count: u32) -> io::Result<(Vec<u8>, bool)> {
+        let inner = self.inner.lock().unwrap();
+        let data = match &inner.node(inner.resolve(path)?).contents {
+            Contents::File(data) => data,
+            Contents::Dir(_) => return Err(error(libc::EISDIR)),
+            _ => return Err(error(libc::EINVAL)),
+        };
+
+        let start = (offset as usize).min(data.len());
+        let end = start.saturating_add(count as usize).min(data.len());
+
+        Ok((data[start..end].to_vec(), end == data.len()))
+    }
+
+    fn write(&self, path: &Path, offset: u64, data: &[u8], _stable: &StableHow) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let fileid = inner.resolve(path)?;
+        let node = inner.node_mut(fileid);
+        let contents = match &mut node.contents {
+            Contents::File(contents) => contents,
+            Contents::Dir(_) => return Err(error(libc::EISDIR)),
+            _ => return Err(error(libc::EINVAL)),
+        };
+
+        let end = offset as usize + data.len();
+        if contents.len() < end {
+            contents.resize(end, 0);
+        }
+        contents[offset as usize..end].copy_from_slice(data);
+        node.attributes.size = contents.len() as u64;
+        node.attributes.used = node.attributes.size;
+        node.modified();
+
+        Ok(())
+    }
+
+    fn readdir(&self, dir: &Path, cookie: u64) -> io::Result<Entries> {
+        let inner = self.inner.lock().unwrap();
+        let entries: Vec<_> = inner
+            .node(inner.resolve(dir)?)
+            .entries()?
+            .iter()
+            .enumerate()
+            .skip(cookie as usize)
+            .map(|(i, (name, &fileid))| {
+                Ok(DirEntry {
+                    name: name.clone(),
+                    fileid,
+                    cookie: i as u64 + 1,
+                })
+            })
+            .collect();
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn create(&self, path: &Path, mode: u32, size: Option<u64>) -> io::Result<()> {
+        let size = size.unwrap_or(0);
+        let mut inner = self.inner.lock().unwrap();
+        inner.add(
+            path,
+            FileType::Reg,
+            mode,
+            Contents::File(vec![0; size as usize]),
+        )?;
+        let fileid = inner.resolve(path)?;
+        let attributes = &mut inner.node_mut(fileid).attributes;
+        attributes.size = size;
+        attributes.used = size;
+
+        Ok(())
+    }
+
+    fn mkdir(&self, path: &Path, mode: u32) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.add(path, FileType::Dir, mode, Contents::Dir(BTreeMap::new()))
+    }
+
+    fn mknod(&self, path: &Path, r#type: FileType, device: SpecData) -> io::Result<()> {
+        let rdev = match r#type {
+            FileType::Chr | FileType::Blk => device,
+            FileType::Sock | FileType::Fifo => SpecData::default(),
+            _ => return Err(error(libc::EINVAL)),
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.add(path, r#type, 0, Contents::Special)?;
+        let fileid = inner.resolve(path)?;
+        inner.node_mut(fileid).attributes.rdev = rdev;
+
+        Ok(())
+    }
+
+    fn symlink(&self, target: &OsStr, path: &Path) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.add(
+            path,
+            FileType::Lnk,
+            0o777,
+            Contents::Symlink(target.to_os_string()),
+        )?;
+        let fileid = inner.resolve(path)?;
+        inner.node_mut(fileid).attributes.size = target.len() as u64;
+
+        Ok(())
+    }
+
+    fn readlink(&self, path: &Path) -> io::Result<OsString> {
+        let inner = self.inner.lock().unwrap();
+        match &inner.node(inner.resolve(path)?).contents {
+            Contents::Symlink(target) => Ok(target.clone()),
+            _ => Err(error(libc::EINVAL)),
+        }
+    }
+
+    fn link(&self, file: &Path, path: &Path) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let fileid = inner.resolve(file)?;
+        if inner.node(fileid).entries().is_ok() {
+            return Err(error(libc::EPERM));
+        }
+        let (dir, name) = inner.parent(path)?;
+        if inner.node(dir).entries()?.contains_key(name) {
+            return Err(error(libc::EEXIST));
+        }
+
+        let dir = inner.node_mut(dir);
+        dir.entries_mut()?.insert(name.to_os_string(), fileid);
+        dir.modified();
+        let node = inner.node_mut(fileid);
+        node.attributes.nlink += 1;
+        node.attributes.ctime = now();
+
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let (dir, name) = inner.parent(path)?;
+        let fileid = inner.resolve(path)?;
+        if inner.node(fileid).entries().is_ok() {
+            return Err(error(libc::EISDIR));
+        }
+
+        let dir = inner.node_mut(dir);
+        dir.entries_mut()?.remove(name);
+        dir.modified();
+        inner.unlink(fileid);
+
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let (from_dir, from_name) = inner.parent(from)?;
+        let (to_dir, to_name) = inner.parent(to)?;
+        let fileid = inner.resolve(from)?;
+        let is_dir = inner.node(fileid).entries().is_ok();
+
+        // A directory cannot be moved into itself:
+        if is_dir && inner.contains(fileid, to_dir) {
+            return Err(error(libc::EINVAL));
+        }
+
+        let replaced = inner.node(to_dir).entries()?.get(to_name).copied();
+        if let Some(replaced) = replaced {
+            if replaced == fileid {
+                return Ok(());
+            }
+            match (is_dir, inner.node(replaced).entries()) {
+                (false, Ok(_)) => return Err(error(libc::EISDIR)),
+                (true, Err(_)) => return Err(error(libc::ENOTDIR)),
+                (true, Ok(entries)) if !entries.is_empty() => return Err(error(libc::ENOTEMPTY)),
+                _ => {}
+            }
+        }
+
+        let from_node = inner.node_mut(from_dir);
+        from_node.entries_mut()?.remove(from_name);
+        from_node.modified();
+        if is_dir {
+            from_node.attributes.nlink -= 1;
+        }
+        let to_node = inner.node_mut(to_dir);
+        to_node
+            .entries_mut()?
+            .insert(to_name.to_os_string(), fileid);
+        to_node.modified();
+        if is_dir {
+            to_node.attributes.nlink += 1;
+        }
+        if let Some(replaced) = replaced {
+            if is_dir {
+                inner.node_mut(to_dir).attributes.nlink -= 1;
+                inner.nodes.remove(&replaced);
+            } else {
+                inner.unlink(replaced);
+            }
+        }
+        inner.node_mut(fileid).attributes.ctime = now();
+
+        Ok(())
+    }
+
+    fn fsstat(&self, path: &Path) -> io::Result<FsStats> {
+        let inner = self.inner.lock().unwrap();
+        inner.resolve(path)?;
+
+        let memory = sysinfo()?;
+        let free_files = u64::MAX - inner.nodes.len() as u64;
+
+        Ok(FsStats {
+            total_bytes: memory.ram_total(),
+            free_bytes: memory.ram_unused(),
+            available_bytes: memory.ram_unused(),
+            total_files: u64::MAX,
+            free_files,
+            available_files: free_files,
+        })
+    }
+}
+
+impl FileSystem for MemFs {
+    /// Paths are already canonical, since the tree's symbolic links are never followed, and the
+    /// path of its root, with those of the directories above it, is canonical too.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let inner = self.inner.lock().unwrap();
+        if !inner.root_path.starts_with(path) {
+            inner.resolve(path)?;
+        }
+
+        Ok(path.to_path_buf())
+    }
+
+    fn identify(&self, path: &Path) -> io::Result<FileId> {
+        let inner = self.inner.lock().unwrap();
+        let attributes = &inner.node(inner.resolve(path)?).attributes;
+
+        Ok(FileId {
+            dev: attributes.fsid,
+            ino: attributes.fileid,
+            is_dir: attributes.r#type == FileType::Dir,
+            is_file: attributes.r#type == FileType::Reg,
+        })
+    }
+
+    fn generation(&self, _path: &Path, id: &FileId) -> u32 {
+        match id.ino == self.inner.lock().unwrap().root {
+            true => self.root_generation,
+            false => 0,
+        }
+    }
+
+    fn entries(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let inner = self.inner.lock().unwrap();
+        let entries = inner.node(inner.resolve(dir)?).entries()?;
+
+        Ok(entries.keys().map(|name| dir.join(name)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use nfs3::file_handle::FileHandleMap;
+
+    #[test]
+    fn handles_name_files_in_memory() {
+        let dir = std::env::temp_dir().join(format!("memfs_handles_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("export")).unwrap();
+        let export = dir.join("export").canonicalize().unwrap();
+        let key = dir.join("key");
+
+        let memory = Arc::new(MemFs::new(&export).unwrap());
+        let mut handles = FileHandleMap::with_file_system(&key, memory.clone()).unwrap();
+        handles.add_export(&export).unwrap();
+
+        // The root has the handle that mountd gives out from the local directory:
+        let mut local = FileHandleMap::new(&key).unwrap();
+        local.add_export(&export).unwrap();
+        let root = handles.handle_for(&export).unwrap();
+        assert_eq!(root, local.handle_for(&export).unwrap());
+        assert_eq!(handles.lookup(&root), Ok(export.clone()));
+
+        let sub = export.join("sub");
+        memory.mkdir(&sub, 0o755).unwrap();
+        memory.create(&sub.join("file"), 0o644, Some(4)).unwrap();
+        let file = handles.handle_for(&sub.join("file")).unwrap();
+        assert!(fs::read_dir(&export).unwrap().next().is_none());
+
+        // A map that has not seen the file finds it by scanning the tree:
+        let mut handles = FileHandleMap::with_file_system(&key, memory.clone()).unwrap();
+        handles.add_export(&export).unwrap();
+        assert_eq!(handles.lookup(&file), Ok(sub.join("file")));
+
+        memory
+            .rename(&sub.join("file"), &export.join("moved"))
+            .unwrap();
+        assert_eq!(handles.lookup(&file), Ok(export.join("moved")));
+        memory.remove(&export.join("moved")).unwrap();
+        assert_eq!(handles.lookup(&file), Err(NfsResult::Stale));
+
+        // Paths outside the tree are not in it, even where the local file system has files:
+        assert_eq!(
+            memory.getattr(&dir).unwrap_err().raw_os_error(),
+            Some(libc::ENOENT)
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -4,57 +4,692 @@
 #[cfg(target_os = "linux")]
 use {
     clap::Parser,
-    nfs3::nfs3_xdr::{procedures::*, *},
-    rpc_protocol::{server::RpcResult, Call},
+    log::*,
+    nfs3::{
+        capabilities,
+        clients::{Aliases, Clients},
+        config::{self, Backend, Config, ExportConfig, Integrity, ReadReplies, Squash},
+        control,
+        file_handle::{FileHandleMap, FileSystem},
+        nfs3_xdr::{procedures::*, *},
+        resolver::Resolver,
+        suite_proto::Capabilities,
+    },
+    nix::fcntl::OFlag,
+    rpc_protocol::{
+        metrics::Metrics, ring::*, server::bind_tcp, throttle::Throttle, trace::Tracer,
+    },
+    std::{
+        io,
+        net::SocketAddr,
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::Duration,
+    },
 };
 
 #[cfg(target_os = "linux")]
-mod ring;
+mod access;
+#[cfg(target_os = "linux")]
+mod attrcache;
+#[cfg(target_os = "linux")]
+mod dirops;
+#[cfg(target_os = "linux")]
+mod exports;
+#[cfg(target_os = "linux")]
+mod fdcache;
+#[cfg(target_os = "linux")]
+mod fileio;
+#[cfg(target_os = "linux")]
+mod files;
+#[cfg(target_os = "linux")]
+mod fsinfo;
+#[cfg(target_os = "linux")]
+mod identity;
+#[cfg(target_os = "linux")]
+mod links;
+#[cfg(target_os = "linux")]
+mod memfs;
+#[cfg(target_os = "linux")]
+mod read;
+#[cfg(target_os = "linux")]
+mod readdir;
+#[cfg(target_os = "linux")]
+mod resolve;
+#[cfg(target_os = "linux")]
+mod results;
+#[cfg(target_os = "linux")]
+mod setattr;
+#[cfg(target_os = "linux")]
+mod snapshots;
+#[cfg(all(target_os = "linux", test))]
+mod testing;
+#[cfg(target_os = "linux")]
+mod verify;
+#[cfg(target_os = "linux")]
+mod vfs;
+#[cfg(target_os = "linux")]
+mod write;
 
 #[cfg(target_os = "linux")]
-use crate::ring::*;
+use crate::{
+    exports::Export,
+    memfs::MemFs,
+    results::*,
+    vfs::{LocalFs, VfsBackend},
+};
 
 #[cfg(target_os = "linux")]
 #[derive(Parser)]
 struct Cli {
-    #[arg(long, default_value_t = 2049)]
-    port: u16,
+    /// The configuration file, which may be shared with mountd. The flags below override it.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// The port to listen on, in place of the port of `nfs_server.listen` (2049 by default).
+    #[arg(long)]
+    port: Option<u16>,
+
+    /// The directory to export (/test/nfs/export by default).
+    #[arg(long)]
+    export: Option<PathBuf>,
+
+    /// The file holding the key that protects file handles (/test/nfs/handle_key by default). It is
+    /// created if it does not exist, and must be shared with mountd.
+    #[arg(long)]
+    handle_key: Option<PathBuf>,
+
+    /// How many seconds a file handle must go unused before the server may forget the path of its
+    /// file, if the file has been removed (60 by default).
+    #[arg(long)]
+    handle_retention: Option<u64>,
+
+    /// Which callers to map to the anonymous user: none (only those without an AUTH_SYS
+    /// credential), root (the default), or all.
+    #[arg(long, value_enum)]
+    squash: Option<Squash>,
+
+    /// The uid of the anonymous user (65534 by default).
+    #[arg(long)]
+    anon_uid: Option<u32>,
+
+    /// The gid of the anonymous user (65534 by default).
+    #[arg(long)]
+    anon_gid: Option<u32>,
+
+    /// Export the directory read-only: operations that would change it fail with NFS3ERR_ROFS.
+    #[arg(long)]
+    read_only: bool,
+
+    /// A directory of snapshots of the export, in place of `export.snapshots`, to show read-only as
+    /// `.snapshot` in the root of the export.
+    #[arg(long)]
+    snapshots: Option<PathBuf>,
+
+    /// Read back and checksum the data of every stable WRITE and COMMIT to the export before
+    /// replying. This protects against silent data corruption at the cost of reading every byte
+    /// written back from storage; the time spent is logged periodically.
+    #[arg(long)]
+    verify_writes: bool,
+
+    /// What to do with the checksums that clients send with the data of WRITEs, in place of
+    /// `nfs_server.integrity` (see `nfs3::write_digest`): ignore them (off, the default), log the
+    /// WRITEs whose data does not match and carry them out anyway (log), or refuse those with
+    /// GARBAGE_ARGS (reject).
+    #[arg(long, value_enum)]
+    integrity: Option<Integrity>,
+
+    /// Check every reply before sending it: that its length is a multiple of 4, and that decoding
+    /// and encoding it again gives the same bytes, which catches padding that is not zero. Malformed
+    /// replies are logged as errors, and sent anyway. This is for debugging the server's encoders.
+    #[arg(long)]
+    check_replies: bool,
+
+    /// Let clients make FIFOs, UNIX domain sockets, and, as root, device files with MKNOD, which
+    /// otherwise fails with NFS3ERR_NOTSUPP.
+    #[arg(long)]
+    mknod: bool,
+
+    /// How READ replies send file data, in place of `nfs_server.read_replies`: copy it into the
+    /// reply (the default), splice it from the page cache to the socket, or read it into buffers
+    /// registered with io_uring and send those with zero-copy sends (send-zc). Replies are copied
+    /// anyway while they are traced or checked, and when a digest is asked for.
+    #[arg(long, value_enum)]
+    read_replies: Option<ReadReplies>,
+
+    /// Where to keep the files of the export, in place of `nfs_server.backend`: in the export
+    /// directory (local, the default), or in memory, starting out empty and lost when the server
+    /// exits. The memory backend is for benchmarks and tests: it checks no permissions, and needs
+    /// the copy READ replies, without mapped READs, snapshots, or write verification.
+    #[arg(long, value_enum)]
+    backend: Option<Backend>,
+
+    /// How many local files READ, WRITE, and COMMIT may keep open between calls, in place of
+    /// `nfs_server.fd_cache` (256 by default). 0 opens every file for each call.
+    #[arg(long)]
+    fd_cache: Option<usize>,
+
+    /// How many milliseconds GETATTR may reply with the attributes it replied with before, unless
+    /// the file was changed since, in place of `nfs_server.attribute_ttl`. Attributes are not
+    /// cached by default.
+    #[arg(long)]
+    attribute_ttl: Option<u64>,
+
+    /// Serve this suite's capability report (see `nfs3::capabilities`) on this port. It is not
+    /// served unless a port is given.
+    #[arg(long)]
+    capabilities_port: Option<u16>,
+
+    /// Serve counts of the calls to each procedure, and how long they took, on this port, as a
+    /// Prometheus metrics endpoint (see `rpc_protocol::metrics`). They are not served unless a port
+    /// is given.
+    #[arg(long)]
+    metrics_port: Option<u16>,
+
+    /// Where SIGUSR1 starts writing a trace of the calls received and replies sent, in pcap format
+    /// if the name ends in ".pcap", for Wireshark, or as text otherwise (see
+    /// `rpc_protocol::trace`). A second SIGUSR1 stops the trace, and a third starts a new one in its
+    /// place. Tracing is not possible unless a file is given.
+    #[arg(long)]
+    trace_file: Option<PathBuf>,
+
+    /// The UNIX domain socket through which exportfs changes the exports while the server runs, in
+    /// place of `nfs_server.control_socket`. There is none unless one is given.
+    #[arg(long)]
+    control_socket: Option<PathBuf>,
 }
 
 #[cfg(target_os = "linux")]
-struct ServerState {}
+impl Cli {
+    /// The configuration file, or the defaults without one, with the flags applied.
+    fn config(&self) -> Result<Config, config::Error> {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+
+        if let Some(port) = self.port {
+            config.nfs_server.listen.set_port(port);
+        }
+        if let Some(export) = &self.export {
+            config.export.path = export.clone();
+        }
+        if let Some(handle_key) = &self.handle_key {
+            config.export.handle_key = handle_key.clone();
+        }
+        if let Some(retention) = self.handle_retention {
+            config.export.handle_retention = retention;
+        }
+        if let Some(snapshots) = &self.snapshots {
+            config.export.snapshots = Some(snapshots.clone());
+        }
+        if let Some(squash) = self.squash {
+            config.auth.squash = squash;
+        }
+        if let Some(anon_uid) = self.anon_uid {
+            config.auth.anon_uid = anon_uid;
+        }
+        if let Some(anon_gid) = self.anon_gid {
+            config.auth.anon_gid = anon_gid;
+        }
+        if let Some(integrity) = self.integrity {
+            config.nfs_server.integrity = integrity;
+        }
+        if let Some(read_replies) = self.read_replies {
+            config.nfs_server.read_replies = read_replies;
+        }
+        if let Some(backend) = self.backend {
+            config.nfs_server.backend = backend;
+        }
+        if let Some(fd_cache) = self.fd_cache {
+            config.nfs_server.fd_cache = fd_cache;
+        }
+        if let Some(attribute_ttl) = self.attribute_ttl {
+            config.nfs_server.attribute_ttl = attribute_ttl;
+        }
+        if let Some(control_socket) = &self.control_socket {
+            config.nfs_server.control_socket = Some(control_socket.clone());
+        }
+        config.export.read_only |= self.read_only;
+        config.nfs_server.verify_writes |= self.verify_writes;
+        config.nfs_server.check_replies |= self.check_replies;
+        config.nfs_server.mknod |= self.mknod;
+        config.validate()?;
+
+        Ok(config)
+    }
+}
 
 #[cfg(target_os = "linux")]
-fn main() {
-    env_logger::init();
+struct ServerState {
+    /// The file system that the procedures operate on.
+    vfs: Arc<dyn VfsBackend>,
+
+    /// Maps file handles to the files in the exports.
+    handles: FileHandleMap,
+
+    exports: Vec<Export>,
+
+    /// The client aliases of the configuration, for exports added through the control socket.
+    aliases: Aliases,
+
+    /// Resolves the host names of the clients of every export.
+    resolver: Arc<Resolver>,
 
+    /// How callers are mapped to local identities, for clients that an export squashes
+    /// differently than configured.
+    identities: identity::IdentityMap,
+
+    /// Returned in WRITE and COMMIT replies so that clients can detect a server restart.
+    write_verifier: [u8; NFS3_WRITEVERFSIZE as usize],
+
+    /// Present if written data is verified before WRITE and COMMIT succeed.
+    verifier: Option<verify::WriteVerifier>,
+
+    /// What is done with the digests that clients send with the data of WRITEs.
+    integrity: Integrity,
+
+    /// The cookie verifiers of directories listed with `CookieVerifier::Entries`.
+    listing_verifiers: readdir::VerifierCache,
+
+    /// Whether MKNOD may make special files.
+    mknod: bool,
+
+    /// The local files that READ, WRITE, and COMMIT keep open.
+    fds: fdcache::FdCache,
+
+    /// The attributes that GETATTR recently replied with.
+    attributes: attrcache::AttrCache,
+
+    /// The largest WRITE whose call the server accepts, which FSINFO reports.
+    max_write: u32,
+}
+
+#[cfg(target_os = "linux")]
+impl ServerState {
+    /// The state of a server that serves `exports`, whose files `handles` maps, from `vfs`, as
+    /// `config` has it, accepting calls of up to `max_call_size` bytes.
+    fn new(
+        config: &Config,
+        vfs: Arc<dyn VfsBackend>,
+        handles: FileHandleMap,
+        exports: Vec<Export>,
+        resolver: Arc<Resolver>,
+        max_call_size: u32,
+    ) -> Self {
+        Self {
+            vfs,
+            handles,
+            exports,
+            aliases: config.clients.clone(),
+            resolver,
+            identities: identity::IdentityMap {
+                flavors: config.auth.flavors.clone(),
+                squash: config.auth.squash,
+                anon_uid: config.auth.anon_uid,
+                anon_gid: config.auth.anon_gid,
+            },
+            write_verifier: write::new_write_verifier(),
+            verifier: config
+                .nfs_server
+                .verify_writes
+                .then(verify::WriteVerifier::default),
+            integrity: config.nfs_server.integrity,
+            listing_verifiers: readdir::VerifierCache::default(),
+            mknod: config.nfs_server.mknod,
+            fds: fdcache::FdCache::new(config.nfs_server.fd_cache),
+            attributes: attrcache::AttrCache::new(Duration::from_millis(
+                config.nfs_server.attribute_ttl,
+            )),
+            max_write: write::max_write(max_call_size),
+        }
+    }
+
+    /// Fail with NFS3ERR_ROFS if the export that the file at `path` is in is read-only.
+    fn check_writable(&self, path: &std::path::Path) -> Result<(), NfsError> {
+        if self.is_read_only(path) {
+            return Err(NfsResult::RoFs.into());
+        }
+
+        Ok(())
+    }
+
+    /// The local file of `handle`, at `path`, opened with `flags`, from the cache of open files.
+    fn open_file(&mut self, handle: &[u8], path: &Path, flags: OFlag) -> io::Result<std::fs::File> {
+        let vfs = &*self.vfs;
+        self.fds.open(handle, path, flags, || vfs.open(path, flags))
+    }
+
+    /// The handle of the file at `path`, if written data is verified, so that the uncommitted
+    /// writes to it can be forgotten once it is removed or replaced.
+    fn verified_handle(&mut self, path: &Path) -> Option<Vec<u8>> {
+        self.verifier.as_ref()?;
+        self.handles.handle_for(path).ok()
+    }
+
+    /// Forget the uncommitted writes to the file of `handle`, from `verified_handle()`.
+    fn forget_unverified(&mut self, handle: Option<Vec<u8>>) {
+        if let (Some(verifier), Some(handle)) = (self.verifier.as_mut(), handle) {
+            verifier.forget(&handle);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn main() {
     let args = Cli::parse();
-    let address = format!("127.0.0.1:{}", args.port);
+    let config = args
+        .config()
+        .unwrap_or_else(|e| panic!("Invalid configuration: {e}"));
+    config.log.init();
+
+    let export = &config.export;
+    let (vfs, files): (Arc<dyn VfsBackend>, Arc<dyn FileSystem>) = match config.nfs_server.backend {
+        Backend::Local => {
+            let local = Arc::new(LocalFs::default());
+            (local.clone(), local)
+        }
+        Backend::Memory => {
+            let memory = Arc::new(
+                MemFs::new(&export.path)
+                    .unwrap_or_else(|e| panic!("Could not export {}: {e}", export.path.display())),
+            );
+            info!("Keeping the files of {} in memory", export.path.display());
+            (memory.clone(), memory)
+        }
+    };
+    let resolver = export.resolver();
+    let (handles, exports) = configured_exports(export, &config.clients, &resolver, &files)
+        .unwrap_or_else(|e| panic!("Could not export {}: {e}", export.path.display()));
+
+    let max_call_size = config
+        .nfs_server
+        .max_record
+        .map_or(write::MAX_WRITE_CALL, |max| {
+            max.saturating_add(4).min(write::MAX_WRITE_CALL)
+        });
+    let state = ServerState::new(&config, vfs, handles, exports, resolver, max_call_size);
+    let identities = state.identities.clone();
+
+    let mut procedure_map =
+        ProcedureMap::new(NFS_PROGRAM, NFS_V3::VERSION, NFS_V3::VERSION, procedures());
+    procedure_map.set_procedure_names(proc_name);
+    if config.nfs_server.check_replies {
+        procedure_map.set_reply_check(check_result);
+    }
+    let metrics = Metrics::default();
+    if args.metrics_port.is_some() {
+        procedure_map.set_metrics(metrics.clone());
+    }
+    let tracer = Tracer::new(config.nfs_server.listen.port());
+    if args.trace_file.is_some() {
+        procedure_map.set_tracer(tracer.clone());
+    }
+    procedure_map.set_authenticator(identity::IdentityMapper::new(identities));
+    let throttle = Throttle::new(config.nfs_server.client_limits());
+    procedure_map.set_throttle(throttle, Some(ServerState::export_class));
+
+    let sizes = RingSizes {
+        entries: config.nfs_server.ring_entries,
+        max_call_size,
+        receive_memory: config.nfs_server.receive_memory,
+    };
+    let mut server = RpcServer::new(config.nfs_server.listen, procedure_map, state, sizes).unwrap();
+    server.set_connection_limits(ConnectionLimits {
+        max_in_flight: config.nfs_server.max_in_flight,
+        reply_memory: config.nfs_server.reply_memory,
+    });
+    server.set_connection_timeouts(config.nfs_server.connection_timeouts());
+    let file_sends = match config.nfs_server.read_replies {
+        ReadReplies::Copy => None,
+        ReadReplies::Splice => Some(FileSends::Splice),
+        ReadReplies::SendZc => Some(FileSends::SendZc),
+    };
+    if let Some(how) = file_sends {
+        server
+            .send_files(how, read::MAX_READ_REPLY)
+            .unwrap_or_else(|e| panic!("Could not set up {how:?} READ replies: {e}"));
+    }
+    let updater = server
+        .state_updater()
+        .unwrap_or_else(|e| panic!("Could not set up state updates: {e}"));
+
+    let capabilities_port = args.capabilities_port;
+    let metrics_port = args.metrics_port;
+    if let Some(path) = args.trace_file.clone() {
+        config::trace_on_sigusr1(tracer, path)
+            .unwrap_or_else(|e| panic!("Could not handle SIGUSR1: {e}"));
+    }
+    let sighup_updater = updater.clone();
+    config::on_sighup(move || reload(&args, &sighup_updater, &files))
+        .unwrap_or_else(|e| panic!("Could not handle SIGHUP: {e}"));
 
-    let state = ServerState {};
+    // Threads that do not take signals are only started now, so that they have them blocked:
+    if let Some(port) = metrics_port {
+        let listener = bind_tcp(SocketAddr::new(config.nfs_server.listen.ip(), port))
+            .unwrap_or_else(|e| panic!("Could not listen on port {port}: {e}"));
+        metrics.serve_prometheus(listener);
+    }
 
-    let procedures: Vec<Option<RingProcedure<ServerState>>> = vec![None, Some(getattr)];
-    let procedure_map =
-        ProcedureMap::new(NFS_PROGRAM, NFS_V3::VERSION, NFS_V3::VERSION, procedures);
+    if let Some(path) = &config.nfs_server.control_socket {
+        let policy = config::control_policy(config.nfs_server.control_group.as_deref())
+            .unwrap_or_else(|e| panic!("nfs_server.control_group: {e}"));
+        control::serve(path, exports::Control(updater), policy)
+            .unwrap_or_else(|e| panic!("Could not listen on {}: {e}", path.display()));
+    }
 
-    let mut server = RpcServer::new(&address, procedure_map, state).unwrap();
+    if let Some(port) = capabilities_port {
+        let capabilities = Capabilities::new("nfs_server")
+            .feature("read-only", export.read_only)
+            .feature("snapshots", export.snapshots.is_some())
+            .feature("verify-writes", config.nfs_server.verify_writes)
+            .feature(
+                "write-digests",
+                config.nfs_server.integrity != Integrity::Off,
+            )
+            .feature("mknod", config.nfs_server.mknod)
+            .feature(
+                "memory-backend",
+                config.nfs_server.backend == Backend::Memory,
+            )
+            .feature("root-squash", config.auth.squash == Squash::Root)
+            .feature("all-squash", config.auth.squash == Squash::All)
+            .limit("max-read", read::MAX_READ.into())
+            .limit("max-write", write::MAX_WRITE.into())
+            .limit("handle-retention", export.handle_retention);
+        let listener = bind_tcp(SocketAddr::new(config.nfs_server.listen.ip(), port))
+            .unwrap_or_else(|e| panic!("Could not listen on port {port}: {e}"));
+        capabilities::serve(listener, capabilities);
+    }
 
     server.main_loop().unwrap();
 }
 
+/// The procedures of NFS version 3, indexed by number. NULL is answered by the ring itself.
 #[cfg(target_os = "linux")]
-fn getattr(call: &Call, _state: &mut ServerState) -> RingResult {
-    let arg = call.arg;
-    eprintln!("in getattr impl: {arg:?}");
+fn procedures() -> Vec<Option<RingProcedure<ServerState>>> {
+    let mut procedures: Vec<Option<RingProcedure<ServerState>>> = vec![None; 22];
+    procedures[NFS_V3::GETATTR as usize] = Some(getattr);
+    procedures[NFS_V3::SETATTR as usize] = Some(setattr::setattr);
+    procedures[NFS_V3::LOOKUP as usize] = Some(dirops::lookup);
+    procedures[NFS_V3::ACCESS as usize] = Some(access::access);
+    procedures[NFS_V3::READLINK as usize] = Some(links::readlink);
+    procedures[NFS_V3::READ as usize] = Some(read::read);
+    procedures[NFS_V3::WRITE as usize] = Some(write::write);
+    procedures[NFS_V3::CREATE as usize] = Some(dirops::create);
+    procedures[NFS_V3::MKDIR as usize] = Some(dirops::mkdir);
+    procedures[NFS_V3::SYMLINK as usize] = Some(links::symlink);
+    procedures[NFS_V3::MKNOD as usize] = Some(dirops::mknod);
+    procedures[NFS_V3::REMOVE as usize] = Some(dirops::remove);
+    procedures[NFS_V3::RENAME as usize] = Some(dirops::rename);
+    procedures[NFS_V3::LINK as usize] = Some(links::link);
+    procedures[NFS_V3::READDIR as usize] = Some(readdir::readdir);
+    procedures[NFS_V3::READDIRPLUS as usize] = Some(readdir::readdirplus);
+    procedures[NFS_V3::FSSTAT as usize] = Some(fsinfo::fsstat);
+    procedures[NFS_V3::FSINFO as usize] = Some(fsinfo::fsinfo);
+    procedures[NFS_V3::COMMIT as usize] = Some(write::commit);
 
-    let obj_attributes = FileAttributes::default();
+    procedures
+}
+
+/// The file handle map of the files of `files` for `export`, and the export, whose clients are
+/// resolved with `aliases` and `resolver`.
+#[cfg(target_os = "linux")]
+fn configured_exports(
+    export: &ExportConfig,
+    aliases: &Aliases,
+    resolver: &Arc<Resolver>,
+    files: &Arc<dyn FileSystem>,
+) -> io::Result<(FileHandleMap, Vec<Export>)> {
+    let clients = Clients::resolve_with(&export.clients, aliases, resolver)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let exports = vec![Export::new(
+        &export.path,
+        export.read_only,
+        export.cookie_verifier,
+        export.read_method,
+        clients,
+        export.snapshots.as_deref(),
+        export.limits(),
+    )?];
+
+    let mut handles = FileHandleMap::with_file_system(&export.handle_key, files.clone())?;
+    exports[0].add_to(&mut handles)?;
+    handles.set_retention(Duration::from_secs(export.handle_retention));
+
+    Ok((handles, exports))
+}
+
+/// Read the configuration again, and switch the server to the export it describes, in place of any
+/// exports added through the control socket. If the configuration is invalid or the export cannot
+/// be set up, the server keeps its current exports. The files stay in `files`, whatever backend the
+/// configuration now names.
+#[cfg(target_os = "linux")]
+fn reload(args: &Cli, updater: &StateUpdater<ServerState>, files: &Arc<dyn FileSystem>) {
+    let config = match args.config() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Not reloading, since the configuration is invalid: {e}");
+            return;
+        }
+    };
+
+    config.log.reload();
+    let export = config.export;
+    let aliases = config.clients;
+    let resolver = export.resolver();
+    let (handles, exports) = match configured_exports(&export, &aliases, &resolver, files) {
+        Ok(configured) => configured,
+        Err(e) => {
+            error!(
+                "Not reloading, since {} cannot be exported: {e}",
+                export.path.display()
+            );
+            return;
+        }
+    };
+
+    info!(
+        "Reloaded configuration: exporting {}{}",
+        export.path.display(),
+        if export.read_only { " read-only" } else { "" }
+    );
+    updater.update(move |state| state.replace_exports(handles, exports, aliases, resolver));
+}
+
+#[cfg(target_os = "linux")]
+fn getattr(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, GetAttrArgs);
+
+    reply::<GetAttrResult>(|| {
+        let handle = &args.object.data;
+        let path = state.lookup(call, handle)?;
 
-    let result = GetAttrResult::Ok(GetAttrSuccess { obj_attributes });
+        let obj_attributes = match state.attributes.get(handle, &path) {
+            Some(attributes) => attributes,
+            None => {
+                let attributes = state.vfs.getattr(&path)?;
+                state.attributes.insert(handle, &path, attributes);
+                attributes
+            }
+        };
 
-    RingResult::Done(RpcResult::Success(result.serialize_alloc()))
+        Ok(GetAttrSuccess { obj_attributes })
+    })
 }
 
 #[cfg(not(target_os = "linux"))]
 fn main() {
     eprintln!("nfs server only supported on linux.");
 }
+
+#[cfg(all(target_os = "linux", test))]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::testing::{self, TestServer};
+
+    #[test]
+    fn reloads() {
+        let dir = std::env::temp_dir().join(format!("nfs_server_reload_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let (first, second) = (dir.join("first"), dir.join("second"));
+        for export in [&first, &second] {
+            fs::create_dir_all(export).unwrap();
+            fs::write(export.join("file"), b"data").unwrap();
+        }
+        let path = dir.join("config.toml");
+        let key = dir.join("handle_key");
+        let write_config = |export: &Path| {
+            let text = format!(
+                "[export]\npath = {:?}\nhandle_key = {:?}\n",
+                export.display().to_string(),
+                key.display().to_string()
+            );
+            fs::write(&path, text).unwrap();
+        };
+        write_config(&first);
+        let args = Cli::parse_from(["nfs_server", "--config", path.to_str().unwrap()]);
+
+        let server = TestServer::start(args.config().unwrap());
+        let mut stream = server.connect();
+        let getattr = |stream: &mut _, object: &FileHandle| {
+            let args = GetAttrArgs {
+                object: object.clone(),
+            };
+            testing::call::<GetAttrResult>(stream, NFS_V3::GETATTR, &args.serialize_alloc())
+                .map(|_| ())
+        };
+        let old = server.handle(&first.join("file"));
+        assert_eq!(getattr(&mut stream, &old), Ok(()));
+
+        // The exports and the file handles are replaced together:
+        write_config(&second);
+        reload(&args, &server.updater, &server.files);
+        let (roots, old_root) = server.updater.query({
+            let first = first.clone();
+            move |state| {
+                let roots: Vec<_> = state.exports.iter().map(|e| e.root.clone()).collect();
+                (roots, state.handles.handle_for(&first).ok())
+            }
+        });
+        assert_eq!(roots, std::slice::from_ref(&second));
+        assert_eq!(old_root, None);
+
+        // On the same connection, the handles of the export that was removed are stale, and those
+        // of the new one work:
+        assert_eq!(getattr(&mut stream, &old), Err(NfsResult::Stale));
+        let new = server.handle(&second.join("file"));
+        assert_eq!(getattr(&mut stream, &new), Ok(()));
+
+        // An invalid configuration changes nothing:
+        fs::write(&path, "[export]\npath = \"relative\"\n").unwrap();
+        reload(&args, &server.updater, &server.files);
+        assert_eq!(getattr(&mut stream, &new), Ok(()));
+        assert_eq!(getattr(&mut stream, &old), Err(NfsResult::Stale));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Implementation of the READ procedure.
+//
+// If the client asks for it, the reply carries a digest of the data; see nfs3::read_digest.
+// Otherwise, if the ring sends files and the file is a local one, the data is left in the file for
+// it to send from there, rather than read into the reply; see `RingResult::File`. Data read into
+// memory is read from the server's `VfsBackend`, or through a mapping of the local file if the
+// export is set to; see `ReadMethod`, and sent after the rest of the result without being copied
+// into it; see `RpcResult::SuccessParts`.
+
+use std::{fs::File, io};
+
+use log::*;
+use nix::fcntl::OFlag;
+
+use nfs3::{config::ReadMethod, nfs3_xdr::*, read_digest};
+use rpc_protocol::{
+    ring::{FileReply, RingCall, RingResult, MAX_REPLY_HEADER},
+    server::RpcResult,
+};
+
+use crate::{fileio, files::*, results::*, ServerState};
+
+/// The most data returned by a single READ, regardless of how much the client asks for.
+pub const MAX_READ: u32 = 1024 * 1024;
+
+/// The size of the largest READ reply: the data, the status, attributes, count, eof flag, and data
+/// length before it, and the reply header.
+pub const MAX_READ_REPLY: u32 = MAX_READ + (4 + 4 + 84 + 4 + 4 + 4) + MAX_REPLY_HEADER;
+
+/// The data of a READ.
+enum Data {
+    /// Read into memory.
+    Read(Vec<u8>),
+
+    /// Left in the file, for the ring to send: the file and the number of bytes.
+    InFile(File, u32),
+}
+
+impl Data {
+    fn len(&self) -> usize {
+        match self {
+            Self::Read(data) => data.len(),
+            Self::InFile(_, len) => *len as usize,
+        }
+    }
+}
+
+pub fn read(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, ReadArgs);
+
+    debug!("READ: {} bytes at offset {}", args.count, args.offset);
+
+    let digest = read_digest::requested(call);
+    let local = state.vfs.is_local();
+    let in_file = call.sends_files() && !digest && local;
+
+    let mut verf = None;
+    let mut file = None;
+    let mut parts = None;
+    let mut body = || {
+        let path = state.lookup(call, &args.file.data)?;
+
+        let count = args.count.min(MAX_READ);
+        let method = state.read_method(&path);
+        let res = match local {
+            true => state
+                .open_file(&args.file.data, &path, OFlag::O_RDONLY)
+                .and_then(|file| read_at(file, args.offset, count, in_file, method)),
+            false => state
+                .vfs
+                .read(&path, args.offset, count)
+                .map(|(data, eof)| (Data::Read(data), eof)),
+        };
+
+        let file_attributes = post_op_attributes(&*state.vfs, &path);
+
+        let (data, eof) = res
+            .inspect_err(|e| warn!("READ from {} failed: {e}", path.display()))
+            .or_fail(|| ReadFailure { file_attributes })?;
+
+        state.record_read(&path, data.len());
+        let count = data.len() as u32;
+        let data = match data {
+            Data::Read(data) if !digest => {
+                parts = Some(data);
+                Vec::new()
+            }
+            Data::Read(data) => data,
+            Data::InFile(f, len) => {
+                file = Some(FileReply {
+                    head: Vec::new(),
+                    file: f,
+                    offset: args.offset,
+                    len,
+                });
+                Vec::new()
+            }
+        };
+        verf = digest.then(|| read_digest::reply_verifier(&data));
+
+        Ok(ReadSuccess {
+            file_attributes,
+            count,
+            eof,
+            data,
+        })
+    };
+    let result = encode::<ReadResult>(body());
+
+    match (verf, file, parts) {
+        (Some(verf), ..) => RingResult::Done(RpcResult::SuccessWithVerifier(verf, result)),
+        (None, Some(mut file), _) => {
+            // The result was encoded with no data, so its last word is the length of the data:
+            file.head = result;
+            let at = file.head.len() - 4;
+            file.head[at..].copy_from_slice(&file.len.to_be_bytes());
+            RingResult::File(file)
+        }
+        (None, None, Some(data)) => {
+            // Likewise here, with the data and its padding sent after the result:
+            let mut head = result;
+            let at = head.len() - 4;
+            head[at..].copy_from_slice(&(data.len() as u32).to_be_bytes());
+            let padding = vec![0; (4 - data.len() % 4) % 4];
+            RingResult::Done(RpcResult::SuccessParts(vec![head, data, padding]))
+        }
+        (None, None, None) => RingResult::Done(RpcResult::Success(result)),
+    }
+}
+
+/// Read up to `count` bytes of the local `file` at `offset` with `method`, and report whether the
+/// end of the file was reached. With `in_file`, the data of a regular file is left in it, and only
+/// its length is found.
+fn read_at(
+    file: File,
+    offset: u64,
+    count: u32,
+    in_file: bool,
+    method: ReadMethod,
+) -> io::Result<(Data, bool)> {
+    let metadata = file.metadata()?;
+    if in_file && metadata.is_file() {
+        let len = metadata.len().saturating_sub(offset).min(count.into());
+        let eof = offset + len >= metadata.len();
+        return Ok((Data::InFile(file, len as u32), eof));
+    }
+
+    let mut data = vec![0; count as usize];
+    let filled = match method {
+        ReadMethod::Mmap if metadata.is_file() => fileio::read_mapped_at(&file, &mut data, offset)?,
+        _ => fileio::read_full_at(&file, &mut data, offset)?,
+    };
+    data.truncate(filled);
+
+    let eof = offset + filled as u64 >= file.metadata()?.len();
+
+    Ok((Data::Read(data), eof))
+}
@@ -0,0 +1,898 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Implementations of the READDIR and READDIRPLUS procedures.
+//
+// Entries are read from the server's `VfsBackend` one at a time, and each is encoded into the reply
+// as soon as it is read, until the reply is full or the directory ends. The server therefore never
+// holds more of a local directory than fits in one reply, however large the directory is.
+//
+// The cookie of an entry is chosen by the backend. For the local file system, as in the Linux
+// server, it is the position of the directory stream after it (see telldir(3)), so a listing
+// continues by seeking the stream to the cookie; a cookie of 0 asks for the start of the
+// directory. The "." and ".." entries are not returned. In the root of an export that shows its
+// snapshots, the `.snapshot` entry comes last, with a cookie of its own (see `snapshots`).
+//
+// How the cookie verifier is chosen is set for each export (see `CookieVerifier`). By default it is
+// derived from the directory's modification and change times, so a client that continues a listing
+// after the directory was modified gets NFS3ERR_BAD_COOKIE and must start over, rather than silently
+// skipping or repeating entries. Some clients do badly with that:
+//
+//   - In a large directory that something keeps changing, even a client that starts over correctly
+//     may never reach the end. `entries` derives the verifier from the entries themselves, so only
+//     changes to the names and file ids listed end a listing.
+//   - Clients that send back a zero verifier, rather than the one they were given, or that treat
+//     NFS3ERR_BAD_COOKIE as a hard error, need `zero`, with which the Linux server answers: the
+//     verifier is not checked at all.
+
+use std::{collections::HashMap, io, os::unix::ffi::OsStrExt, path::Path};
+
+use log::*;
+use sha2::{Digest, Sha256};
+
+use nfs3::{config::CookieVerifier, nfs3_xdr::*};
+use rpc_protocol::ring::{RingCall, RingResult};
+
+use crate::{
+    files::*,
+    results::*,
+    snapshots,
+    vfs::{DirEntry, VfsBackend},
+    ServerState,
+};
+
+/// The size of the XDR encoding of the status, cookie verifier, end of list marker, and eof flag in
+/// a READDIR or READDIRPLUS result, which are present no matter how many entries are returned.
+const FIXED_RESULT_SIZE: usize = 4 + NFS3_COOKIEVERFSIZE as usize + 4 + 4;
+
+/// The most directories whose `CookieVerifier::Entries` verifiers are remembered at once.
+const MAX_CACHED_VERIFIERS: usize = 4096;
+
+type Verifier = [u8; NFS3_COOKIEVERFSIZE as usize];
+
+/// The `CookieVerifier::Entries` verifiers of the directories listed recently, along with the
+/// times they were computed for, so that a directory is only read in full again after it changes.
+#[derive(Default)]
+pub struct VerifierCache {
+    verifiers: HashMap<(u64, u64), (Verifier, Verifier)>,
+}
+
+impl VerifierCache {
+    /// The verifier of the listing of the directory at `dir`, with `attributes`.
+    fn verifier(
+        &mut self,
+        vfs: &dyn VfsBackend,
+        dir: &Path,
+        attributes: &FileAttributes,
+    ) -> io::Result<Verifier> {
+        let key = (attributes.fsid, attributes.fileid);
+        let times = times_verifier(attributes);
+        if let Some((cached_times, verifier)) = self.verifiers.get(&key) {
+            if *cached_times == times {
+                return Ok(*verifier);
+            }
+        }
+
+        let mut hasher = Sha256::new();
+        for entry in vfs.readdir(dir, 0)? {
+            let entry = entry?;
+            hasher.update(entry.fileid.to_be_bytes());
+            hasher.update((entry.name.len() as u64).to_be_bytes());
+            hasher.update(entry.name.as_bytes());
+        }
+        let verifier = truncate(hasher);
+
+        // Directories are not forgotten one at a time, since the cache is only there to spare
+        // repeated reads of the directories being listed right now:
+        if self.verifiers.len() >= MAX_CACHED_VERIFIERS {
+            self.verifiers.clear();
+        }
+        self.verifiers.insert(key, (times, verifier));
+
+        Ok(verifier)
+    }
+}
+
+/// How the cookie verifier of a listing is chosen: the export's strategy, and the cache of verifiers
+/// that `CookieVerifier::Entries` uses.
+struct Verification<'a> {
+    strategy: CookieVerifier,
+    cache: &'a mut VerifierCache,
+}
+
+/// Open the directory at `dir` to continue a listing after `cookie`. Returns the remaining entries,
+/// and the cookie verifier for the listing. `snapshots` is the directory of snapshots that `dir`
+/// shows, if it is the root of an export that does.
+fn open_listing(
+    vfs: &dyn VfsBackend,
+    dir: &Path,
+    cookie: u64,
+    cookieverf: &[u8],
+    verification: Verification,
+    snapshots: Option<&Path>,
+) -> Result<(impl Iterator<Item = io::Result<DirEntry>>, Verifier), NfsError> {
+    let attributes = vfs.getattr(dir)?;
+    if attributes.r#type != FileType::Dir {
+        return Err(NfsResult::NotDir.into());
+    }
+
+    let verifier = match verification.strategy {
+        CookieVerifier::Mtime => times_verifier(&attributes),
+        CookieVerifier::Entries => verification.cache.verifier(vfs, dir, &attributes)?,
+        CookieVerifier::Zero => [0; NFS3_COOKIEVERFSIZE as usize],
+    };
+    let checked = verification.strategy != CookieVerifier::Zero;
+    if checked && cookie != 0 && cookieverf != verifier {
+        return Err(NfsResult::BadCookie.into());
+    }
+
+    Ok((overlaid(vfs, dir, cookie, snapshots)?, verifier))
+}
+
+/// The entries of the directory at `dir` after `cookie`. In the root of an export that shows its
+/// snapshots, the `.snapshot` entry for them follows the directory's own, in place of any real entry
+/// of that name.
+fn overlaid(
+    vfs: &dyn VfsBackend,
+    dir: &Path,
+    cookie: u64,
+    snapshots: Option<&Path>,
+) -> io::Result<impl Iterator<Item = io::Result<DirEntry>>> {
+    // Nothing follows the `.snapshot` entry:
+    let stream = match snapshots {
+        Some(_) if cookie == snapshots::COOKIE => None,
+        _ => Some(vfs.readdir(dir, cookie)?),
+    };
+
+    let overlay = snapshots
+        .filter(|_| stream.is_some())
+        .and_then(|snapshots| vfs.getattr(snapshots).ok())
+        .map(|attributes| {
+            Ok(DirEntry {
+                name: snapshots::NAME.into(),
+                fileid: attributes.fileid,
+                cookie: snapshots::COOKIE,
+            })
+        });
+    let hidden = snapshots.is_some();
+
+    Ok(stream
+        .into_iter()
+        .flatten()
+        .filter(move |entry| !(hidden && entry.as_ref().is_ok_and(|e| e.name == snapshots::NAME)))
+        .chain(overlay))
+}
+
+/// Encode entries read from `entries` onto `reply`, as the list of entries of a READDIR or
+/// READDIRPLUS result followed by its eof flag. `encode` encodes each entry, and returns None once
+/// the entry would not fit in the reply; no more entries are read after that. Fails with
+/// NFS3ERR_TOOSMALL if not even the first entry fits.
+fn encode_entries(
+    reply: &mut Vec<u8>,
+    entries: impl Iterator<Item = io::Result<DirEntry>>,
+    mut encode: impl FnMut(&DirEntry) -> Option<Vec<u8>>,
+) -> Result<(), NfsError> {
+    let mut empty = true;
+
+    for entry in entries {
+        let Some(encoded) = encode(&entry?) else {
+            if empty {
+                return Err(NfsResult::TooSmall.into());
+            }
+
+            reply.extend_from_slice(&0_i32.to_be_bytes());
+            reply.extend_from_slice(&xdr_lib::serialize_bool(&false));
+            return Ok(());
+        };
+
+        reply.extend_from_slice(&1_i32.to_be_bytes());
+        reply.extend_from_slice(&encoded);
+        empty = false;
+    }
+
+    reply.extend_from_slice(&0_i32.to_be_bytes());
+    reply.extend_from_slice(&xdr_lib::serialize_bool(&true));
+
+    Ok(())
+}
+
+/// The `CookieVerifier::Mtime` verifier of a directory with `attributes`.
+fn times_verifier(attributes: &FileAttributes) -> Verifier {
+    let mut hasher = Sha256::new();
+    for time in [attributes.mtime, attributes.ctime] {
+        hasher.update(i64::from(time.seconds).to_be_bytes());
+        hasher.update(i64::from(time.nseconds).to_be_bytes());
+    }
+
+    truncate(hasher)
+}
+
+fn truncate(hasher: Sha256) -> Verifier {
+    hasher.finalize()[..NFS3_COOKIEVERFSIZE as usize]
+        .try_into()
+        .unwrap()
+}
+
+/// The size of the XDR encoding of an optional value: the "value follows" flag, then the value.
+fn optional_size(encoded: &[u8]) -> usize {
+    4 + encoded.len()
+}
+
+pub fn readdir(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, ReadDirArgs);
+
+    debug!("READDIR: cookie {}, count {}", args.cookie, args.count);
+
+    reply_encoded::<ReadDirResult>(|| {
+        let dir = state.lookup(call, &args.dir.data)?;
+        state.refresh_snapshots(&dir);
+        let snapshots = state.snapshots_in(&dir).map(Path::to_path_buf);
+        let verification = Verification {
+            strategy: state.cookie_verifier(&dir),
+            cache: &mut state.listing_verifiers,
+        };
+
+        read_dir(&*state.vfs, &dir, &args, verification, snapshots.as_deref())
+    })
+}
+
+/// The body of the success arm of a READDIR result for the directory at `dir`, which shows the
+/// directory of snapshots `snapshots`, if any.
+fn read_dir(
+    vfs: &dyn VfsBackend,
+    dir: &Path,
+    args: &ReadDirArgs,
+    verification: Verification,
+    snapshots: Option<&Path>,
+) -> Result<Vec<u8>, Failure<ReadDirFailure>> {
+    let listing = open_listing(
+        vfs,
+        dir,
+        args.cookie,
+        &args.cookieverf,
+        verification,
+        snapshots,
+    );
+    let dir_attributes = post_op_attributes(vfs, dir);
+    let failure = || ReadDirFailure { dir_attributes };
+
+    let (entries, cookieverf) = listing.or_fail(failure)?;
+
+    let mut reply = dir_attributes.serialize_alloc();
+    reply.extend_from_slice(&cookieverf);
+
+    let mut size = FIXED_RESULT_SIZE + reply.len() - cookieverf.len();
+    encode_entries(&mut reply, entries, |entry| {
+        let encoded = Entry {
+            fileid: entry.fileid,
+            name: entry.name.clone(),
+            cookie: entry.cookie,
+        }
+        .serialize_alloc();
+
+        size += optional_size(&encoded);
+        (size <= args.count as usize).then_some(encoded)
+    })
+    .or_fail(failure)?;
+
+    Ok(reply)
+}
+
+/// Like READDIR, but also returns the attributes and file handle of each entry.
+///
+/// The `dircount` argument limits the size of just the file IDs, names, and cookies of the entries,
+/// while `maxcount` limits the size of the entire result.
+pub fn readdirplus(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, ReadDirPlusArgs);
+
+    debug!(
+        "READDIRPLUS: cookie {}, dircount {}, maxcount {}",
+        args.cookie, args.dircount, args.maxcount
+    );
+
+    reply_encoded::<ReadDirPlusResult>(|| {
+        let dir = state.lookup(call, &args.dir.data)?;
+        state.refresh_snapshots(&dir);
+        let snapshots = state.snapshots_in(&dir).map(Path::to_path_buf);
+
+        let verification = Verification {
+            strategy: state.cookie_verifier(&dir),
+            cache: &mut state.listing_verifiers,
+        };
+        let vfs = &*state.vfs;
+        let listing = open_listing(
+            vfs,
+            &dir,
+            args.cookie,
+            &args.cookieverf,
+            verification,
+            snapshots.as_deref(),
+        );
+        let dir_attributes = post_op_attributes(vfs, &dir);
+        let failure = || ReadDirPlusFailure { dir_attributes };
+
+        let (entries, cookieverf) = listing.or_fail(failure)?;
+
+        let mut reply = dir_attributes.serialize_alloc();
+        reply.extend_from_slice(&cookieverf);
+
+        let mut size = FIXED_RESULT_SIZE + reply.len() - cookieverf.len();
+        let mut dir_size = 0;
+        encode_entries(&mut reply, entries, |entry| {
+            dir_size += optional_size(
+                &Entry {
+                    fileid: entry.fileid,
+                    name: entry.name.clone(),
+                    cookie: entry.cookie,
+                }
+                .serialize_alloc(),
+            );
+
+            let path = match &snapshots {
+                Some(snapshots) if entry.name == snapshots::NAME => snapshots.clone(),
+                _ => dir.join(&entry.name),
+            };
+            let encoded = EntryPlus {
+                fileid: entry.fileid,
+                name: entry.name.clone(),
+                cookie: entry.cookie,
+                name_attributes: post_op_attributes(vfs, &path),
+                name_handle: PostOpFileHandle {
+                    inner: state
+                        .handles
+                        .handle_for(&path)
+                        .ok()
+                        .map(|data| FileHandle { data }),
+                },
+            }
+            .serialize_alloc();
+
+            size += optional_size(&encoded);
+            (size <= args.maxcount as usize && dir_size <= args.dircount as usize)
+                .then_some(encoded)
+        })
+        .or_fail(failure)?;
+
+        Ok(reply)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        cell::Cell, collections::HashSet, ffi::OsString, fs, net::TcpStream,
+        os::unix::fs::MetadataExt,
+    };
+
+    use nfs3::nfs3_xdr::procedures::NFS_V3;
+    use rpc_protocol::server::RpcResult;
+
+    use crate::{
+        memfs::MemFs,
+        testing::{self, TestServer},
+        vfs::LocalFs,
+    };
+
+    /// The local file system, exporting the temporary directory that the tests work in.
+    fn local() -> LocalFs {
+        LocalFs::exporting(&[&std::env::temp_dir()])
+    }
+
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("readdir_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    /// Call READDIR on `dir`, in an export with the default cookie verifiers, returning the encoded
+    /// result.
+    fn call(dir: &Path, cookie: u64, cookieverf: [u8; 8], count: u32) -> Vec<u8> {
+        call_showing(dir, cookie, cookieverf, count, None)
+    }
+
+    /// Like `call()`, for a `dir` that shows the directory of snapshots `snapshots`, if any.
+    fn call_showing(
+        dir: &Path,
+        cookie: u64,
+        cookieverf: [u8; 8],
+        count: u32,
+        snapshots: Option<&Path>,
+    ) -> Vec<u8> {
+        let verification = Verification {
+            strategy: CookieVerifier::Mtime,
+            cache: &mut VerifierCache::default(),
+        };
+
+        call_verified(
+            &local(),
+            dir,
+            cookie,
+            cookieverf,
+            count,
+            verification,
+            snapshots,
+        )
+    }
+
+    fn call_verified(
+        vfs: &dyn VfsBackend,
+        dir: &Path,
+        cookie: u64,
+        cookieverf: [u8; 8],
+        count: u32,
+        verification: Verification,
+        snapshots: Option<&Path>,
+    ) -> Vec<u8> {
+        let args = ReadDirArgs {
+            cookie,
+            cookieverf,
+            count,
+            ..Default::default()
+        };
+        let RingResult::Done(RpcResult::Success(encoded)) =
+            reply_encoded::<ReadDirResult>(|| read_dir(vfs, dir, &args, verification, snapshots))
+        else {
+            panic!("READDIR did not succeed");
+        };
+
+        encoded
+    }
+
+    fn status(encoded: &[u8]) -> NfsResult {
+        let mut status = NfsResult::Ok;
+        status.deserialize(&mut &encoded[..]).unwrap();
+        status
+    }
+
+    /// List all of `dir` with READDIR calls of size `count`, checking the size of each reply.
+    /// Returns the names listed, and the number of calls made.
+    fn list(dir: &Path, count: u32) -> (Vec<OsString>, usize) {
+        list_showing(&local(), dir, count, None)
+    }
+
+    fn list_showing(
+        vfs: &dyn VfsBackend,
+        dir: &Path,
+        count: u32,
+        snapshots: Option<&Path>,
+    ) -> (Vec<OsString>, usize) {
+        let (mut names, mut calls) = (Vec::new(), 0);
+        let (mut cookie, mut cookieverf) = (0, [0; 8]);
+
+        loop {
+            let verification = Verification {
+                strategy: CookieVerifier::Mtime,
+                cache: &mut VerifierCache::default(),
+            };
+            let encoded =
+                call_verified(vfs, dir, cookie, cookieverf, count, verification, snapshots);
+            assert!(encoded.len() <= count as usize, "{}", encoded.len());
+            calls += 1;
+
+            let mut result = ReadDirResult::default();
+            result.deserialize(&mut encoded.as_slice()).unwrap();
+            let ReadDirResult::Ok(success) = result else {
+                panic!("READDIR failed: {:?}", status(&encoded));
+            };
+
+            if let Some(last) = success.reply.entries.last() {
+                cookie = last.cookie;
+            }
+            cookieverf = success.cookieverf;
+            names.extend(success.reply.entries.into_iter().map(|e| e.name));
+
+            if success.reply.eof {
+                return (names, calls);
+            }
+        }
+    }
+
+    #[test]
+    fn list_in_pages() {
+        let dir = scratch_dir("pages");
+        let expected: HashSet<OsString> = (0..100).map(|i| format!("file_{i}").into()).collect();
+        for name in expected.iter() {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let (names, calls) = list(&dir, 512);
+        assert_eq!(names.len(), expected.len());
+        assert_eq!(names.into_iter().collect::<HashSet<_>>(), expected);
+        assert!(calls > 1, "{calls}");
+
+        // An empty directory is listed in one reply:
+        let empty = dir.join("file_0");
+        fs::remove_file(&empty).unwrap();
+        fs::create_dir(&empty).unwrap();
+        assert_eq!(list(&empty, 512), (Vec::new(), 1));
+
+        // A reply too small for any entry fails:
+        assert_eq!(status(&call(&dir, 0, [0; 8], 128)), NfsResult::TooSmall);
+
+        // As does continuing after the directory changed:
+        let mut result = ReadDirResult::default();
+        result
+            .deserialize(&mut call(&dir, 0, [0; 8], 512).as_slice())
+            .unwrap();
+        let success = result.ok().unwrap();
+        let cookie = success.reply.entries.last().unwrap().cookie;
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        fs::write(dir.join("new"), b"").unwrap();
+        assert_eq!(
+            status(&call(&dir, cookie, success.cookieverf, 512)),
+            NfsResult::BadCookie
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn list_memory_in_pages() {
+        let memory = MemFs::default();
+        let mut expected: Vec<OsString> = (0..100).map(|i| format!("file_{i:03}").into()).collect();
+        for name in expected.iter().rev() {
+            memory
+                .create(&Path::new("/").join(name), 0o644, None)
+                .unwrap();
+        }
+        expected.sort();
+
+        // The cookies of the backend continue each page where the last one stopped:
+        let (names, calls) = list_showing(&memory, Path::new("/"), 512, None);
+        assert_eq!(names, expected);
+        assert!(calls > 1, "{calls}");
+    }
+
+    #[test]
+    fn snapshot_entry() {
+        let dir = scratch_dir("snapshot_entry");
+        let root = dir.join("root");
+        let snapshots = dir.join("snapshots");
+        fs::create_dir(&root).unwrap();
+        fs::create_dir(&snapshots).unwrap();
+        for i in 0..40 {
+            fs::write(root.join(format!("file_{i}")), b"").unwrap();
+        }
+        // A real entry of the same name is hidden:
+        fs::write(root.join(snapshots::NAME), b"").unwrap();
+
+        // In small replies, so that `.snapshot` may have to wait for a reply of its own:
+        for count in [256, 512, 4096] {
+            let (names, _) = list_showing(&local(), &root, count, Some(&snapshots));
+            assert_eq!(names.len(), 41);
+            assert_eq!(names.last().unwrap(), snapshots::NAME);
+        }
+
+        let mut result = ReadDirResult::default();
+        let encoded = call_showing(&root, 0, [0; 8], 4096, Some(&snapshots));
+        result.deserialize(&mut encoded.as_slice()).unwrap();
+        let success = result.ok().unwrap();
+        let entry = success.reply.entries.last().unwrap();
+        assert_eq!(
+            (entry.cookie, entry.fileid),
+            (snapshots::COOKIE, fs::metadata(&snapshots).unwrap().ino())
+        );
+
+        // Continuing after it lists nothing more:
+        let encoded = call_showing(
+            &root,
+            snapshots::COOKIE,
+            success.cookieverf,
+            4096,
+            Some(&snapshots),
+        );
+        let mut result = ReadDirResult::default();
+        result.deserialize(&mut encoded.as_slice()).unwrap();
+        let success = result.ok().unwrap();
+        assert!(success.reply.entries.is_empty() && success.reply.eof);
+
+        // Without snapshots, the real entry is listed:
+        assert!(list(&root, 4096).0.contains(&snapshots::NAME.into()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cookie_verifier_strategies() {
+        let dir = scratch_dir("verifiers");
+        for i in 0..100 {
+            fs::write(dir.join(format!("file_{i}")), b"").unwrap();
+        }
+        let mut cache = VerifierCache::default();
+
+        // Read the first page of a listing, then change the directory with `change`, and return the
+        // status of the call that continues the listing with the verifier the client sends back:
+        let mut continue_after = |strategy, change: &dyn Fn(), send_back: &dyn Fn([u8; 8]) -> _| {
+            let verification = Verification {
+                strategy,
+                cache: &mut cache,
+            };
+            let mut result = ReadDirResult::default();
+            result
+                .deserialize(
+                    &mut call_verified(&local(), &dir, 0, [0; 8], 512, verification, None)
+                        .as_slice(),
+                )
+                .unwrap();
+            let success = result.ok().unwrap();
+            assert!(!success.reply.eof);
+
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            change();
+
+            let verification = Verification {
+                strategy,
+                cache: &mut cache,
+            };
+            let cookie = success.reply.entries.last().unwrap().cookie;
+            let cookieverf = send_back(success.cookieverf);
+            status(&call_verified(
+                &local(),
+                &dir,
+                cookie,
+                cookieverf,
+                512,
+                verification,
+                None,
+            ))
+        };
+
+        let unchanged = || {};
+        let touch = || fs::write(dir.join("file_0"), b"x").unwrap();
+        let create_and_remove = || {
+            fs::write(dir.join(".swp"), b"").unwrap();
+            fs::remove_file(dir.join(".swp")).unwrap();
+        };
+        let create = || fs::write(dir.join(format!("new_{}", next_name())), b"").unwrap();
+        let as_sent = |verifier| verifier;
+        let zeroed = |_| [0; 8];
+
+        // A client that sends back its verifier can continue any listing of an unchanged directory,
+        // and writing to a file does not change the directory itself:
+        for strategy in [CookieVerifier::Mtime, CookieVerifier::Entries] {
+            assert_eq!(
+                continue_after(strategy, &unchanged, &as_sent),
+                NfsResult::Ok
+            );
+            assert_eq!(continue_after(strategy, &touch, &as_sent), NfsResult::Ok);
+        }
+
+        // With mtime verifiers, it must start over after any change to the directory, even one that
+        // leaves the entries as they were, like a temporary file created and removed again:
+        let mtime = CookieVerifier::Mtime;
+        assert_eq!(
+            continue_after(mtime, &create_and_remove, &as_sent),
+            NfsResult::BadCookie
+        );
+        assert_eq!(
+            continue_after(mtime, &create, &as_sent),
+            NfsResult::BadCookie
+        );
+
+        // With verifiers of the entries, only after a change to the entries:
+        let entries = CookieVerifier::Entries;
+        assert_eq!(
+            continue_after(entries, &create_and_remove, &as_sent),
+            NfsResult::Ok
+        );
+        assert_eq!(
+            continue_after(entries, &create, &as_sent),
+            NfsResult::BadCookie
+        );
+
+        // A client that sends back a zero verifier can only continue a listing with zero verifiers,
+        // which are never checked, however the directory changed:
+        assert_eq!(
+            continue_after(mtime, &unchanged, &zeroed),
+            NfsResult::BadCookie
+        );
+        assert_eq!(
+            continue_after(entries, &unchanged, &zeroed),
+            NfsResult::BadCookie
+        );
+        let zero = CookieVerifier::Zero;
+        assert_eq!(continue_after(zero, &create, &zeroed), NfsResult::Ok);
+        assert_eq!(continue_after(zero, &create, &as_sent), NfsResult::Ok);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cookies_over_the_wire() {
+        let dir = scratch_dir("wire");
+        for i in 0..60 {
+            fs::write(dir.join(format!("file_{i}")), b"").unwrap();
+        }
+        let server = TestServer::start(testing::config(&dir));
+        let root = server.handle(&dir);
+        let mut stream = server.connect();
+
+        let readdir = |stream: &mut TcpStream, cookie, cookieverf| {
+            let args = ReadDirArgs {
+                dir: root.clone(),
+                cookie,
+                cookieverf,
+                count: 512,
+            };
+            testing::call::<ReadDirResult>(stream, NFS_V3::READDIR, &args.serialize_alloc())
+        };
+        let readdirplus = |stream: &mut TcpStream, cookie, cookieverf| {
+            let args = ReadDirPlusArgs {
+                dir: root.clone(),
+                cookie,
+                cookieverf,
+                dircount: 512,
+                maxcount: 8192,
+            };
+            testing::call::<ReadDirPlusResult>(stream, NFS_V3::READDIRPLUS, &args.serialize_alloc())
+        };
+        let cookies = |entries: &[Entry]| -> Vec<_> {
+            entries.iter().map(|e| (e.name.clone(), e.cookie)).collect()
+        };
+
+        // The first page is the same each time it is read, cookies and verifier alike:
+        let first = readdir(&mut stream, 0, [0; 8]).unwrap();
+        assert!(!first.reply.eof);
+        let again = readdir(&mut stream, 0, [0; 8]).unwrap();
+        assert_eq!(
+            (again.cookieverf, again.reply),
+            (first.cookieverf, first.reply.clone())
+        );
+        let verf = first.cookieverf;
+        let last = first.reply.entries.last().unwrap().cookie;
+
+        // READDIR and READDIRPLUS continue from the same cookies with the same verifier, and
+        // READDIRPLUS adds the handle and attributes of each entry:
+        let second = readdir(&mut stream, last, verf).unwrap();
+        let plus = readdirplus(&mut stream, last, verf).unwrap();
+        assert_eq!(plus.cookieverf, verf);
+        let plus_entries: Vec<_> = plus
+            .reply
+            .entries
+            .iter()
+            .map(|e| Entry {
+                fileid: e.fileid,
+                name: e.name.clone(),
+                cookie: e.cookie,
+            })
+            .collect();
+        let shorter = second.reply.entries.len().min(plus_entries.len());
+        assert!(shorter > 0);
+        assert_eq!(
+            cookies(&plus_entries[..shorter]),
+            cookies(&second.reply.entries[..shorter])
+        );
+        for entry in &plus.reply.entries {
+            let handle = entry.name_handle.inner.as_ref().unwrap();
+            assert_eq!(*handle, server.handle(&dir.join(&entry.name)));
+            assert_eq!(entry.name_attributes.inner.unwrap().fileid, entry.fileid);
+        }
+
+        // The pages do not overlap, and together list every entry once:
+        let mut names: HashSet<OsString> = HashSet::new();
+        let (mut cookie, mut page) = (0, first);
+        loop {
+            for entry in &page.reply.entries {
+                assert!(names.insert(entry.name.clone()), "{:?}", entry.name);
+            }
+            if page.reply.eof {
+                break;
+            }
+            cookie = page.reply.entries.last().map_or(cookie, |e| e.cookie);
+            page = readdir(&mut stream, cookie, verf).unwrap();
+        }
+        assert_eq!(names.len(), 60);
+
+        // A verifier that is not the listing's is refused, but a listing can always start over:
+        let wrong = [1; 8];
+        assert_eq!(readdir(&mut stream, last, wrong), Err(NfsResult::BadCookie));
+        assert_eq!(
+            readdirplus(&mut stream, last, wrong),
+            Err(NfsResult::BadCookie)
+        );
+        assert!(readdir(&mut stream, 0, wrong).is_ok());
+
+        // Once the directory changes, the old verifier is refused, and a new listing gets a new one:
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        fs::write(dir.join("new"), b"").unwrap();
+        assert_eq!(readdir(&mut stream, last, verf), Err(NfsResult::BadCookie));
+        assert_eq!(
+            readdirplus(&mut stream, last, verf),
+            Err(NfsResult::BadCookie)
+        );
+        let restarted = readdir(&mut stream, 0, [0; 8]).unwrap();
+        assert_ne!(restarted.cookieverf, verf);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A name that has not been used in the test directory yet.
+    fn next_name() -> u64 {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    }
+
+    #[test]
+    fn only_entries_that_fit_are_read() {
+        let read = Cell::new(0);
+        let entries = (0..1_000_000_u64).map(|i| {
+            read.set(read.get() + 1);
+            Ok(DirEntry {
+                name: format!("file_{i}").into(),
+                fileid: i,
+                cookie: i + 1,
+            })
+        });
+
+        let mut reply = Vec::new();
+        let mut encoded = 0;
+        encode_entries(&mut reply, entries, |entry| {
+            (encoded < 1000).then(|| {
+                encoded += 1;
+                Entry {
+                    fileid: entry.fileid,
+                    name: entry.name.clone(),
+                    cookie: entry.cookie,
+                }
+                .serialize_alloc()
+            })
+        })
+        .unwrap();
+
+        // One entry more than fit was read, to find that it did not:
+        assert_eq!(read.get(), 1001);
+        assert_eq!(&reply[reply.len() - 8..], [0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    /// List a directory of many files, checking that the server's memory use does not grow with the
+    /// size of the directory. The number of files can be set with NFS_READDIR_STRESS_FILES.
+    #[test]
+    #[ignore = "creates a million files; run with --ignored"]
+    fn list_stress() {
+        let files: usize = std::env::var("NFS_READDIR_STRESS_FILES")
+            .map(|n| n.parse().unwrap())
+            .unwrap_or(1_000_000);
+        let dir = scratch_dir("stress");
+        for i in 0..files {
+            fs::File::create(dir.join(format!("file_{i}"))).unwrap();
+        }
+
+        fn max_rss() -> i64 {
+            // SAFETY: `rusage` is a plain C struct of integers, for which all zeroes is valid.
+            let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+            // SAFETY: `usage` is valid for writes.
+            assert_eq!(unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) }, 0);
+            usage.ru_maxrss * 1024
+        }
+
+        let before = max_rss();
+        let mut listed = 0;
+        let (mut cookie, mut cookieverf) = (0, [0; 8]);
+        loop {
+            let mut result = ReadDirResult::default();
+            result
+                .deserialize(&mut call(&dir, cookie, cookieverf, 32768).as_slice())
+                .unwrap();
+            let success = result.ok().unwrap();
+
+            listed += success.reply.entries.len();
+            if success.reply.eof {
+                break;
+            }
+            cookie = success.reply.entries.last().unwrap().cookie;
+            cookieverf = success.cookieverf;
+        }
+        let growth = max_rss() - before;
+
+        eprintln!("listed {listed} files, memory grew by {growth} bytes");
+        assert_eq!(listed, files);
+        assert!(growth < 16 * 1024 * 1024, "{growth}");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,168 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Resolution of paths beneath the root of their export, so that no path leads a client out of it.
+//
+// The handle map only hands out handles for paths in an export, and names never contain "/", but
+// the directories along a path may have been replaced since by symbolic links, by anyone who can
+// write to the export. `LocalFs` therefore never resolves a path from "/", but from a descriptor of
+// the root of its export, with openat2(2) and RESOLVE_BENEATH: resolution fails with EXDEV if it
+// would leave the root, through "..", an absolute symbolic link, or a relative one that climbs out.
+// Symbolic links that stay beneath the root are followed.
+//
+// Kernels before 5.6 have no openat2(2). There, the path is walked one directory at a time with
+// O_NOFOLLOW, so no symbolic link is followed at all, and ".." may not climb above the root.
+
+use std::{
+    ffi::CString,
+    io,
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::{Component, Path},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use nix::{fcntl::OFlag, sys::stat::Mode};
+
+/// How a directory on the way is opened.
+const DIR_FLAGS: OFlag = OFlag::O_PATH
+    .union(OFlag::O_DIRECTORY)
+    .union(OFlag::O_CLOEXEC);
+
+/// How often openat2(2) is retried when it fails with EAGAIN, which it may while a rename races
+/// with the resolution.
+const RETRIES: usize = 8;
+
+/// Cleared once openat2(2) turns out not to exist.
+static OPENAT2: AtomicBool = AtomicBool::new(true);
+
+/// Open the directory at `path`, relative to the directory `root`, as an O_PATH descriptor. Fails
+/// with EXDEV if resolving `path` would leave `root`.
+pub fn open_dir(root: BorrowedFd, path: &Path) -> io::Result<OwnedFd> {
+    if OPENAT2.load(Ordering::Relaxed) {
+        match openat2(root, path) {
+            Err(e) if e.raw_os_error() == Some(libc::ENOSYS) => {
+                OPENAT2.store(false, Ordering::Relaxed);
+            }
+            res => return res,
+        }
+    }
+
+    walk(root, path)
+}
+
+fn openat2(root: BorrowedFd, path: &Path) -> io::Result<OwnedFd> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: open_how is a plain C struct, for which zeroes are valid, and ask for nothing.
+    let mut how: libc::open_how = unsafe { std::mem::zeroed() };
+    how.flags = DIR_FLAGS.bits() as u64;
+    how.resolve = libc::RESOLVE_BENEATH | libc::RESOLVE_NO_MAGICLINKS;
+
+    for _ in 0..RETRIES {
+        // SAFETY: `path` is a NUL-terminated string and `how` an open_how, whose size is given.
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_openat2,
+                root.as_raw_fd(),
+                path.as_ptr(),
+                &how,
+                size_of::<libc::open_how>(),
+            )
+        };
+        if fd >= 0 {
+            // SAFETY: openat2(2) returned a new descriptor, which is ours.
+            return Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) });
+        }
+
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::EAGAIN) {
+            return Err(e);
+        }
+    }
+
+    Err(io::Error::from_raw_os_error(libc::EAGAIN))
+}
+
+/// Open the directories along `path` one at a time, without following symbolic links.
+fn walk(root: BorrowedFd, path: &Path) -> io::Result<OwnedFd> {
+    let mut dirs: Vec<OwnedFd> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if dirs.pop().is_none() {
+                    return Err(io::Error::from_raw_os_error(libc::EXDEV));
+                }
+            }
+            Component::Normal(name) => {
+                let dir = dirs.last().map_or(root, |dir| dir.as_fd());
+                let next =
+                    nix::fcntl::openat(dir, name, DIR_FLAGS | OFlag::O_NOFOLLOW, Mode::empty())?;
+                dirs.push(next);
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(io::Error::from_raw_os_error(libc::EXDEV));
+            }
+        }
+    }
+
+    match dirs.pop() {
+        Some(dir) => Ok(dir),
+        None => root.try_clone_to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{fs, os::unix::fs::symlink};
+
+    #[test]
+    fn escapes_are_refused() {
+        let dir = std::env::temp_dir().join(format!("resolve_escapes_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let root = dir.join("root");
+        fs::create_dir_all(root.join("sub/inner")).unwrap();
+        fs::create_dir(dir.join("outside")).unwrap();
+        symlink("/", root.join("absolute")).unwrap();
+        symlink("../../outside", root.join("sub/climbing")).unwrap();
+        symlink("inner", root.join("sub/relative")).unwrap();
+        let root_fd: OwnedFd = fs::File::open(&root).unwrap().into();
+
+        for (name, resolve) in [
+            (
+                "openat2",
+                openat2 as fn(BorrowedFd, &Path) -> io::Result<OwnedFd>,
+            ),
+            ("walk", walk),
+        ] {
+            let resolve = |path: &str| resolve(root_fd.as_fd(), Path::new(path));
+            if resolve(".").is_err_and(|e| e.raw_os_error() == Some(libc::ENOSYS)) {
+                eprintln!("skipping {name}, which the kernel does not have");
+                continue;
+            }
+            let errno = |path: &str| resolve(path).unwrap_err().raw_os_error();
+
+            // Paths that stay beneath the root resolve, ".." included:
+            resolve("sub/inner").unwrap();
+            resolve("sub/inner/../../sub").unwrap();
+            resolve(".").unwrap();
+
+            // Those that leave it do not, whatever leads them out:
+            assert_eq!(errno(".."), Some(libc::EXDEV), "{name}");
+            assert_eq!(errno("sub/../.."), Some(libc::EXDEV), "{name}");
+            assert_eq!(errno("/tmp"), Some(libc::EXDEV), "{name}");
+            assert!(resolve("absolute").is_err(), "{name}");
+            assert!(resolve("absolute/tmp").is_err(), "{name}");
+            assert!(resolve("sub/climbing").is_err(), "{name}");
+
+            // A relative link beneath the root is followed by openat2(2) only:
+            assert_eq!(resolve("sub/relative").is_ok(), name == "openat2", "{name}");
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
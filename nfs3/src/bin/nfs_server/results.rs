@@ -0,0 +1,266 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Helpers for decoding the arguments of procedures and encoding their results.
+//
+// The body of a procedure is a closure passed to `reply()`, which returns the success arm of the
+// procedure's result, or a `Failure` holding the status and the body of the failure arm. Errors
+// from the local file system and from file handle lookups convert into a `Failure` with `?`, with
+// the default failure body; where the body has to carry attributes, `or_fail()` attaches them:
+//
+//     reply::<RemoveResult>(|| {
+//         let dir = state.lookup(call, &args.object.dir.data)?;
+//         ...
+//         res.or_fail(|| RemoveFailure { dir_wcc: dir_wcc.clone() })?;
+//
+//         Ok(RemoveSuccess { dir_wcc })
+//     })
+
+use std::io;
+
+use nfs3::nfs3_xdr::*;
+use rpc_protocol::{ring::RingResult, server::RpcResult};
+
+use crate::{files::nfs_status, ServerState};
+
+/// Decode the arguments of a procedure as `$args`, or return GARBAGE_ARGS from the procedure if they
+/// cannot be decoded.
+macro_rules! decode_args {
+    ($call:expr, $args:ty) => {{
+        let mut args = <$args>::default();
+        let mut arg = $call.arg;
+        if args.deserialize(&mut arg).is_err() {
+            return rpc_protocol::ring::RingResult::Done(
+                rpc_protocol::server::RpcResult::GarbageArgs,
+            );
+        }
+        args
+    }};
+}
+pub(crate) use decode_args;
+
+/// The status of a failed operation. Converts from an I/O error using `nfs_status()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NfsError(pub NfsResult);
+
+impl NfsError {
+    /// Attach the body of the failure arm of a procedure's result.
+    pub fn with<F>(self, body: F) -> Failure<F> {
+        Failure {
+            status: self.0,
+            body: Box::new(body),
+        }
+    }
+}
+
+impl From<NfsResult> for NfsError {
+    fn from(status: NfsResult) -> Self {
+        Self(status)
+    }
+}
+
+impl From<io::Error> for NfsError {
+    fn from(e: io::Error) -> Self {
+        Self(nfs_status(&e))
+    }
+}
+
+/// A failed procedure: the status, and the body of the failure arm of the procedure's result. The
+/// body is boxed so that results with a `Failure` are not much larger than the success arm.
+#[derive(Debug)]
+pub struct Failure<F> {
+    status: NfsResult,
+    body: Box<F>,
+}
+
+impl<F: Default> From<NfsError> for Failure<F> {
+    fn from(e: NfsError) -> Self {
+        e.with(F::default())
+    }
+}
+
+impl<F: Default> From<NfsResult> for Failure<F> {
+    fn from(status: NfsResult) -> Self {
+        NfsError(status).into()
+    }
+}
+
+impl<F: Default> From<io::Error> for Failure<F> {
+    fn from(e: io::Error) -> Self {
+        NfsError::from(e).into()
+    }
+}
+
+pub trait OrFail<T> {
+    /// Turn an error into a `Failure` whose body is produced by `body`.
+    fn or_fail<F>(self, body: impl FnOnce() -> F) -> Result<T, Failure<F>>;
+}
+
+impl<T, E: Into<NfsError>> OrFail<T> for Result<T, E> {
+    fn or_fail<F>(self, body: impl FnOnce() -> F) -> Result<T, Failure<F>> {
+        self.map_err(|e| e.into().with(body()))
+    }
+}
+
+/// The result union of a procedure, which holds `Success` for NFS3_OK, and `Failure` for every other
+/// status.
+pub trait ProcedureResult {
+    type Success;
+    type Failure;
+
+    fn encode_success(success: Self::Success) -> Vec<u8>;
+
+    fn encode_failure_body(body: &Self::Failure) -> Vec<u8>;
+
+    /// Decode a result as a client would: the success arm, or the status of a failure.
+    #[cfg(test)]
+    fn decode(raw: &[u8]) -> Result<Self::Success, NfsResult>;
+}
+
+macro_rules! procedure_results {
+    ($($result:ident => $success:ident, $failure:ident),* $(,)?) => {
+        $(
+            impl ProcedureResult for $result {
+                type Success = $success;
+                type Failure = $failure;
+
+                fn encode_success(success: $success) -> Vec<u8> {
+                    Self::Ok(success).serialize_alloc()
+                }
+
+                fn encode_failure_body(body: &$failure) -> Vec<u8> {
+                    body.serialize_alloc()
+                }
+
+                #[cfg(test)]
+                fn decode(raw: &[u8]) -> Result<$success, NfsResult> {
+                    let mut result = Self::default();
+                    result.deserialize(&mut &raw[..]).unwrap();
+                    match result {
+                        Self::Ok(success) => Ok(success),
+                        Self::Default(_) => Err(decode_status(raw)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+procedure_results!(
+    SetAttrResult => SetAttrSuccess, SetAttrFailure,
+    LookupResult => LookupSuccess, LookupFailure,
+    AccessResult => AccessSuccess, AccessFailure,
+    ReadlinkResult => ReadlinkSuccess, ReadlinkFailure,
+    ReadResult => ReadSuccess, ReadFailure,
+    WriteResult => WriteSuccess, WriteFailure,
+    CreateResult => CreateSuccess, CreateFailure,
+    MkdirResult => MkdirSuccess, MkdirFailure,
+    SymlinkResult => SymlinkSuccess, SymlinkFailure,
+    MknodResult => MknodSuccess, MknodFailure,
+    RemoveResult => RemoveSuccess, RemoveFailure,
+    RenameResult => RenameSuccess, RenameFailure,
+    LinkResult => LinkSuccess, LinkFailure,
+    ReadDirResult => ReadDirSuccess, ReadDirFailure,
+    ReadDirPlusResult => ReadDirPlusSuccess, ReadDirPlusFailure,
+    FsStatResult => FsStatSuccess, FsStatFailure,
+    FsInfoResult => FsInfoSuccess, FsInfoFailure,
+    CommitResult => CommitSuccess, CommitFailure,
+);
+
+/// The failure arm of GETATTR is void.
+impl ProcedureResult for GetAttrResult {
+    type Success = GetAttrSuccess;
+    type Failure = ();
+
+    fn encode_success(success: GetAttrSuccess) -> Vec<u8> {
+        Self::Ok(success).serialize_alloc()
+    }
+
+    fn encode_failure_body(_body: &()) -> Vec<u8> {
+        Vec::new()
+    }
+
+    #[cfg(test)]
+    fn decode(raw: &[u8]) -> Result<GetAttrSuccess, NfsResult> {
+        let mut result = Self::default();
+        result.deserialize(&mut &raw[..]).unwrap();
+        match result {
+            Self::Ok(success) => Ok(success),
+            _ => Err(decode_status(raw)),
+        }
+    }
+}
+
+/// The status of an encoded result, which every result starts with.
+#[cfg(test)]
+fn decode_status(raw: &[u8]) -> NfsResult {
+    let mut status = NfsResult::default();
+    status.deserialize(&mut &raw[..]).unwrap();
+
+    status
+}
+
+/// Encode the result of a procedure.
+///
+/// The generated `Default` variants of the result unions do not record which status they were
+/// created for, so failure results are encoded by hand: the status, followed by the body of the
+/// failure arm.
+pub fn encode<R: ProcedureResult>(res: Result<R::Success, Failure<R::Failure>>) -> Vec<u8> {
+    match res {
+        Ok(success) => R::encode_success(success),
+        Err(failure) => {
+            let mut buf = failure.status.serialize_alloc();
+            buf.extend_from_slice(&R::encode_failure_body(&failure.body));
+            buf
+        }
+    }
+}
+
+/// Run the body of a procedure, and reply with its result.
+pub fn reply<R: ProcedureResult>(
+    body: impl FnOnce() -> Result<R::Success, Failure<R::Failure>>,
+) -> RingResult<ServerState> {
+    RingResult::Done(RpcResult::Success(encode::<R>(body())))
+}
+
+/// Like `reply()`, for procedures that encode the body of their success arm themselves, such as
+/// READDIR, which encodes each entry as it is read.
+pub fn reply_encoded<R: ProcedureResult>(
+    body: impl FnOnce() -> Result<Vec<u8>, Failure<R::Failure>>,
+) -> RingResult<ServerState> {
+    let res = match body() {
+        Ok(encoded) => {
+            let mut buf = NfsResult::Ok.serialize_alloc();
+            buf.extend_from_slice(&encoded);
+            buf
+        }
+        Err(failure) => encode::<R>(Err(failure)),
+    };
+
+    RingResult::Done(RpcResult::Success(res))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_carry_status_and_body() {
+        let body = RemoveFailure::default();
+        let mut expected = NfsResult::NoEnt.serialize_alloc();
+        expected.extend_from_slice(&body.serialize_alloc());
+
+        // An I/O error converts to a failure with the default body:
+        let res = || -> Result<RemoveSuccess, Failure<RemoveFailure>> {
+            std::fs::metadata("/nonexistent/path")?;
+            unreachable!()
+        };
+        assert_eq!(encode::<RemoveResult>(res()), expected);
+
+        let res = Err::<(), _>(NfsResult::NoEnt).or_fail(RemoveFailure::default);
+        assert_eq!(
+            encode::<RemoveResult>(res.map(|()| RemoveSuccess::default())),
+            expected
+        );
+    }
+}
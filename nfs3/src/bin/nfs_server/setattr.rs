@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The implementation of SETATTR, which changes the mode, owner, size, and times of a file.
+//
+// The attributes are set with the calls a local program would make, as the caller's mapped identity
+// (see `identity`), so the local file system decides who may change what: changing the owner, or
+// the mode or times of a file that the caller does not own, fails with NFS3ERR_PERM, as chown(2)
+// and chmod(2) do. The size is set first, since truncating a file changes its mtime, then the
+// owner, since a change of owner clears the set-user-ID and set-group-ID bits, then the mode, and
+// the times last. The mode of a symbolic link is left as it is, as the Linux server does, since it
+// cannot be changed.
+//
+// A client may guard the call with the ctime that it last saw. If the file's ctime differs, it has
+// changed since, and the call fails with NFS3ERR_NOT_SYNC without changing anything, so that the
+// client does not undo another client's change.
+
+use std::path::Path;
+
+use log::*;
+
+use nfs3::nfs3_xdr::*;
+use rpc_protocol::ring::{RingCall, RingResult};
+
+use crate::{files::*, results::*, vfs::VfsBackend, ServerState};
+
+pub fn setattr(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, SetAttrArgs);
+
+    reply::<SetAttrResult>(|| {
+        let path = state.lookup(call, &args.object.data)?;
+        debug!("SETATTR: {}", path.display());
+
+        let vfs = &*state.vfs;
+        let (res, obj_wcc) = with_wcc(vfs, &path, || -> Result<(), NfsError> {
+            state.check_writable(&path)?;
+            check_guard(vfs, &path, args.guard.inner.as_ref())?;
+
+            vfs.setattr(&path, &args.new_attributes).map_err(|e| {
+                debug!("SETATTR of {} failed: {e}", path.display());
+                e.into()
+            })
+        });
+        if res.is_ok() {
+            state.fds.forget(&path);
+        }
+        state.attributes.forget(&path);
+
+        res.or_fail(|| SetAttrFailure { obj_wcc })?;
+
+        Ok(SetAttrSuccess { obj_wcc })
+    })
+}
+
+/// Fail with NFS3ERR_NOT_SYNC if the ctime of the file at `path` is not `ctime`, when there is one.
+fn check_guard(vfs: &dyn VfsBackend, path: &Path, ctime: Option<&NfsTime>) -> Result<(), NfsError> {
+    let Some(ctime) = ctime else {
+        return Ok(());
+    };
+
+    if vfs.getattr(path)?.ctime != *ctime {
+        return Err(NfsResult::NotSync.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        fs,
+        os::unix::fs::MetadataExt,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::vfs::LocalFs;
+
+    #[test]
+    fn attributes_and_guard() {
+        let dir = std::env::temp_dir().join(format!("setattr_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let local = LocalFs::exporting(&[&dir]);
+        let path = dir.join("file");
+        fs::write(&path, b"some data").unwrap();
+
+        let attributes = SetAttributes {
+            mode: SetMode { inner: Some(0o600) },
+            size: SetSize { inner: Some(4) },
+            mtime: SetMtime::SetToClientTime(NfsTime {
+                seconds: 1_000_000,
+                nseconds: 5,
+            }),
+            ..SetAttributes::default()
+        };
+        local.setattr(&path, &attributes).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.mode() & 0o7777, 0o600);
+        assert_eq!(fs::read(&path).unwrap(), b"some");
+        assert_eq!(
+            metadata.modified().unwrap(),
+            SystemTime::UNIX_EPOCH + Duration::new(1_000_000, 5)
+        );
+        // An attribute that is not set is left alone:
+        assert_ne!(metadata.accessed().unwrap(), metadata.modified().unwrap());
+
+        let ctime = file_attributes(&metadata).ctime;
+        check_guard(&local, &path, Some(&ctime)).unwrap();
+        check_guard(&local, &path, None).unwrap();
+        let stale = NfsTime {
+            seconds: ctime.seconds - 1,
+            ..ctime
+        };
+        assert_eq!(
+            check_guard(&local, &path, Some(&stale)),
+            Err(NfsError(NfsResult::NotSync))
+        );
+
+        // Only a regular file has a size to set:
+        let size = SetAttributes {
+            size: SetSize { inner: Some(0) },
+            ..SetAttributes::default()
+        };
+        let e = local.setattr(&dir, &size).unwrap_err();
+        assert_eq!(nfs_status(&e), NfsResult::Inval);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,184 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The `.snapshot` directory that an export with `export.snapshots` shows in its root, so that users
+// can restore files from the snapshots of the export themselves.
+//
+// The directory is an overlay on the export: it is not in the export's root, but LOOKUP finds it
+// there, READDIR and READDIRPLUS list it after the root's own entries, and ".." in it leads back to
+// the root. What it holds is the configured directory of snapshots, such as `.zfs/snapshot`, each
+// of which is added to the file handle map as an export of its own, since the snapshots of a file
+// system share its inode numbers. Snapshots that appear or go away while the server runs are
+// noticed when the directory is listed or looked up in.
+//
+// Everything in the directory is read-only, whether or not the export is: changes fail with
+// NFS3ERR_ROFS, and ACCESS does not grant them. A real entry named `.snapshot` in the root is hidden,
+// and cannot be created or removed.
+
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::*;
+use nfs3::{file_handle::FileHandleMap, nfs3_xdr::NfsResult};
+
+use crate::{results::NfsError, ServerState};
+
+/// The name of the directory of snapshots in the root of an export.
+pub const NAME: &str = ".snapshot";
+
+/// The cookie of the `.snapshot` entry in a listing of the root of an export, which is not a
+/// position that telldir(3) returns, since those are non-negative `long`s.
+pub const COOKIE: u64 = u64::MAX;
+
+/// Make the directory of snapshots at `dir`, and each snapshot in it, addressable by file handles.
+pub fn add(handles: &mut FileHandleMap, dir: &Path) -> io::Result<()> {
+    handles.add_export(dir)?;
+    refresh(handles, dir);
+
+    Ok(())
+}
+
+/// Stop making the directory of snapshots at `dir`, and the snapshots in it, addressable.
+pub fn remove(handles: &mut FileHandleMap, dir: &Path) {
+    for snapshot in snapshots(handles, dir) {
+        let _ = handles.remove_export(&snapshot);
+    }
+    let _ = handles.remove_export(dir);
+}
+
+/// Add the snapshots that have appeared in `dir` since they were last added, and remove those that
+/// have gone, whose handles become stale.
+pub fn refresh(handles: &mut FileHandleMap, dir: &Path) {
+    let added = snapshots(handles, dir);
+    for snapshot in added.iter().filter(|s| !s.is_dir()) {
+        debug!("snapshot {} has gone", snapshot.display());
+        let _ = handles.remove_export(snapshot);
+    }
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("could not list the snapshots in {}: {e}", dir.display());
+            return;
+        }
+    };
+    // Symbolic links are left out, since they would resolve to a directory outside of `dir`:
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|t| t.is_dir()) && !added.contains(&path) {
+            if let Err(e) = handles.add_export(&path) {
+                warn!("could not add snapshot {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+/// The snapshots in `dir` that `handles` has.
+fn snapshots(handles: &FileHandleMap, dir: &Path) -> Vec<PathBuf> {
+    handles
+        .exports()
+        .filter(|root| root.parent() == Some(dir))
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+impl ServerState {
+    /// The directory of snapshots shown in `dir`, if `dir` is the root of an export that shows one.
+    pub fn snapshots_in(&self, dir: &Path) -> Option<&Path> {
+        self.exports
+            .iter()
+            .find(|e| e.root == dir)?
+            .snapshots
+            .as_deref()
+    }
+
+    /// The root of the export whose directory of snapshots is `dir`, if it is one.
+    fn root_of_snapshots(&self, dir: &Path) -> Option<&Path> {
+        self.exports
+            .iter()
+            .find(|e| e.snapshots.as_deref() == Some(dir))
+            .map(|e| e.root.as_path())
+    }
+
+    /// Where the entry `name` in `dir` leads, if it is part of the overlay: `.snapshot` in the root
+    /// of an export that shows its snapshots, and ".." in the directory of snapshots.
+    pub fn overlay_entry(&self, dir: &Path, name: &OsStr) -> Option<PathBuf> {
+        if name == NAME {
+            return self.snapshots_in(dir).map(Path::to_path_buf);
+        }
+        if name == ".." {
+            return self.root_of_snapshots(dir).map(Path::to_path_buf);
+        }
+
+        None
+    }
+
+    /// Notice the snapshots that have appeared or gone, if `dir` is a directory of snapshots, before
+    /// its entries are looked up or listed.
+    pub fn refresh_snapshots(&mut self, dir: &Path) {
+        if self.root_of_snapshots(dir).is_some() {
+            refresh(&mut self.handles, dir);
+        }
+    }
+
+    /// Fail with NFS3ERR_ROFS if `name` in `dir` is the `.snapshot` directory, which cannot be
+    /// created or removed.
+    pub fn check_not_overlay(&self, dir: &Path, name: &OsStr) -> Result<(), NfsError> {
+        if name == NAME && self.snapshots_in(dir).is_some() {
+            return Err(NfsResult::RoFs.into());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_come_and_go() {
+        let dir = std::env::temp_dir().join(format!("snapshots_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let snapshots = dir.join("snapshots");
+        for snapshot in ["monday", "tuesday"] {
+            fs::create_dir_all(snapshots.join(snapshot)).unwrap();
+            fs::write(snapshots.join(snapshot).join("file"), snapshot).unwrap();
+        }
+        let snapshots = snapshots.canonicalize().unwrap();
+        std::os::unix::fs::symlink(snapshots.join("monday"), snapshots.join("latest")).unwrap();
+
+        let mut handles = FileHandleMap::new(&dir.join("handle_key")).unwrap();
+        add(&mut handles, &snapshots).unwrap();
+        assert_eq!(handles.exports().count(), 3);
+
+        // The same file in two snapshots has two handles:
+        let monday = snapshots.join("monday/file");
+        let handle = handles.handle_for(&monday).unwrap();
+        assert_ne!(
+            handle,
+            handles.handle_for(&snapshots.join("tuesday/file")).unwrap()
+        );
+        assert_eq!(handles.export_of(&monday), monday.parent());
+
+        fs::create_dir(snapshots.join("wednesday")).unwrap();
+        fs::remove_file(snapshots.join("latest")).unwrap();
+        fs::remove_dir_all(snapshots.join("monday")).unwrap();
+        refresh(&mut handles, &snapshots);
+        let mut added = super::snapshots(&handles, &snapshots);
+        added.sort();
+        assert_eq!(
+            added,
+            [snapshots.join("tuesday"), snapshots.join("wednesday")]
+        );
+        assert_eq!(handles.lookup(&handle), Err(NfsResult::Stale));
+
+        remove(&mut handles, &snapshots);
+        assert_eq!(handles.exports().count(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
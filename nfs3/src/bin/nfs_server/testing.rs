@@ -0,0 +1,114 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// A server running the NFS procedures on the loopback address, for the tests of procedures, which
+// need the `RingCall` of a real call, and of what the server does between calls.
+
+use std::{
+    net::{SocketAddr, TcpStream},
+    path::Path,
+    sync::{mpsc, Arc},
+    thread,
+};
+
+use nfs3::{
+    config::Config,
+    file_handle::FileSystem,
+    nfs3_xdr::{procedures::*, *},
+};
+use rpc_protocol::{client::do_rpc_call, ring::*};
+
+use crate::{
+    configured_exports, procedures, results::ProcedureResult, vfs::LocalFs, write, ServerState,
+};
+
+/// The largest call that the server receives: twice that of a WRITE of `write::MAX_WRITE` bytes,
+/// so that tests can send WRITEs of more data than is written.
+const MAX_CALL_SIZE: u32 = 2 * write::MAX_WRITE_CALL;
+
+/// A server on a thread of its own, exporting the local directory of its configuration.
+pub struct TestServer {
+    pub address: SocketAddr,
+
+    /// Updates and queries the state of the server.
+    pub updater: StateUpdater<ServerState>,
+
+    /// The file system that the server's file handles are made for.
+    pub files: Arc<dyn FileSystem>,
+}
+
+impl TestServer {
+    /// Start a server as `config` has it, on a port of its own on the loopback address, whatever
+    /// `nfs_server.listen` is. Calls are not authenticated, so the server's thread never switches
+    /// its file system credentials.
+    pub fn start(config: Config) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let local = Arc::new(LocalFs::default());
+            let files: Arc<dyn FileSystem> = local.clone();
+            let export = &config.export;
+            let resolver = export.resolver();
+            let (handles, exports) =
+                configured_exports(export, &config.clients, &resolver, &files).unwrap();
+            let state = ServerState::new(&config, local, handles, exports, resolver, MAX_CALL_SIZE);
+
+            let procedure_map =
+                ProcedureMap::new(NFS_PROGRAM, NFS_V3::VERSION, NFS_V3::VERSION, procedures());
+            let sizes = RingSizes {
+                entries: 64,
+                max_call_size: MAX_CALL_SIZE,
+                receive_memory: 0,
+            };
+            let address = "127.0.0.1:0".parse().unwrap();
+            let mut server = RpcServer::new(address, procedure_map, state, sizes).unwrap();
+            let updater = server.state_updater().unwrap();
+            sender
+                .send((server.local_addr().unwrap(), updater, files))
+                .unwrap();
+            server.main_loop().unwrap();
+        });
+
+        let (address, updater, files) = receiver.recv().unwrap();
+        Self {
+            address,
+            updater,
+            files,
+        }
+    }
+
+    /// The file handle of the file at `path`, which must be canonical.
+    pub fn handle(&self, path: &Path) -> FileHandle {
+        let path = path.to_path_buf();
+        let data = self
+            .updater
+            .query(move |state| state.handles.handle_for(&path))
+            .unwrap();
+
+        FileHandle { data }
+    }
+
+    pub fn connect(&self) -> TcpStream {
+        TcpStream::connect(self.address).unwrap()
+    }
+}
+
+/// A configuration that exports `dir`, with its handle key in a file next to it.
+pub fn config(dir: &Path) -> Config {
+    let mut config = Config::default();
+    config.export.path = dir.to_path_buf();
+    config.export.handle_key = dir.with_extension("key");
+
+    config
+}
+
+/// Call `procedure` with the encoded `args` on `stream`, and decode its result: the success arm,
+/// or the status of a failure.
+pub fn call<R: ProcedureResult>(
+    stream: &mut TcpStream,
+    procedure: u32,
+    args: &[u8],
+) -> Result<R::Success, NfsResult> {
+    let raw = do_rpc_call(stream, NFS_PROGRAM, NFS_V3::VERSION, procedure, args).unwrap();
+
+    R::decode(&raw)
+}
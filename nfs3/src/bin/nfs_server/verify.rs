@@ -0,0 +1,316 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Write verification: an optional mode in which data is read back from stable storage and checked
+// against a checksum of what the client sent before a stable WRITE or a COMMIT is acknowledged.
+//
+// This roughly doubles the I/O done for every byte written, and each verification waits for a read
+// from the device, since the page cache is dropped for the range first so that the read does not
+// just return the data that was just written into it. The time spent is reported by VerifyStats.
+//
+// The checksums of UNSTABLE writes are kept by file handle until a COMMIT covers them, so that they
+// follow a file that is renamed, and are dropped when the file is removed. A client that writes
+// without committing could otherwise grow them without bound, so only so many are kept for each
+// file, and in all: past that, the oldest writes to a file, or new writes once the total is
+// reached, go unverified.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io,
+    os::fd::AsRawFd,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use log::*;
+
+/// How many verifications to perform between each logging of the statistics.
+const STATS_INTERVAL: u64 = 1024;
+
+/// The most uncommitted writes whose checksums are kept for one file.
+const MAX_PENDING_PER_FILE: usize = 1024;
+
+/// The most uncommitted writes whose checksums are kept in all.
+const MAX_PENDING: usize = 64 * 1024;
+
+/// A range of an UNSTABLE write that has not yet been verified by a COMMIT.
+struct PendingRange {
+    offset: u64,
+    len: u64,
+    crc: u32,
+}
+
+impl PendingRange {
+    fn end(&self) -> u64 {
+        self.offset + self.len
+    }
+
+    fn overlaps(&self, other: &PendingRange) -> bool {
+        self.offset < other.end() && other.offset < self.end()
+    }
+}
+
+/// Statistics about the cost of write verification.
+#[derive(Default)]
+pub struct VerifyStats {
+    /// The number of ranges that were read back.
+    ranges: u64,
+
+    /// The number of bytes that were read back.
+    bytes: u64,
+
+    /// The number of ranges whose contents did not match what was written.
+    mismatches: u64,
+
+    /// The number of UNSTABLE writes that went unverified because too many were pending.
+    unverified: u64,
+
+    /// The total time spent dropping cached pages, reading back, and checksumming.
+    elapsed: Duration,
+}
+
+impl fmt::Display for VerifyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.elapsed.as_secs_f64();
+        let rate = if secs > 0.0 {
+            self.bytes as f64 / secs / (1024.0 * 1024.0)
+        } else {
+            0.0
+        };
+
+        write!(
+            f,
+            "verified {} ranges ({} bytes) in {:.3}s ({rate:.1} MiB/s), {} mismatches, \
+             {} unverified",
+            self.ranges, self.bytes, secs, self.mismatches, self.unverified
+        )
+    }
+}
+
+/// Tracks the data that must be verified before a WRITE or COMMIT may succeed.
+#[derive(Default)]
+pub struct WriteVerifier {
+    /// Checksums of the UNSTABLE writes to each file since its last COMMIT, by file handle.
+    pending: HashMap<Vec<u8>, Vec<PendingRange>>,
+
+    /// The number of ranges in `pending`.
+    pending_ranges: usize,
+
+    stats: VerifyStats,
+}
+
+impl WriteVerifier {
+    /// Remember the checksum of an UNSTABLE write so that it can be verified by a later COMMIT.
+    ///
+    /// A write that overlaps earlier uncommitted writes replaces them, so the parts of those
+    /// earlier writes that it did not overwrite go unverified.
+    pub fn record_unstable(&mut self, handle: &[u8], offset: u64, data: &[u8]) {
+        let range = PendingRange {
+            offset,
+            len: data.len() as u64,
+            crc: crc32fast::hash(data),
+        };
+
+        self.supersede(handle, &range);
+        if self.pending_ranges >= MAX_PENDING {
+            self.stats.unverified += 1;
+            return;
+        }
+
+        let pending = self.pending.entry(handle.to_vec()).or_default();
+        if pending.len() >= MAX_PENDING_PER_FILE {
+            pending.remove(0);
+            self.pending_ranges -= 1;
+            self.stats.unverified += 1;
+        }
+        pending.push(range);
+        self.pending_ranges += 1;
+    }
+
+    /// Forget the uncommitted writes to the file with `handle`, which has been removed.
+    pub fn forget(&mut self, handle: &[u8]) {
+        if let Some(pending) = self.pending.remove(handle) {
+            self.pending_ranges -= pending.len();
+        }
+    }
+
+    /// Verify a stable write to the file with `handle` at `path`, which has already been flushed to
+    /// `file`.
+    pub fn verify_write(
+        &mut self,
+        handle: &[u8],
+        path: &Path,
+        file: &File,
+        offset: u64,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let range = PendingRange {
+            offset,
+            len: data.len() as u64,
+            crc: crc32fast::hash(data),
+        };
+
+        // The stable write supersedes any uncommitted writes to the same range:
+        self.supersede(handle, &range);
+
+        self.check(path, file, &range)
+    }
+
+    /// Verify the uncommitted writes to the file with `handle` that fall in the range being
+    /// committed, which has already been flushed to `file`. A `count` of 0 means the range extends
+    /// to the end of the file.
+    ///
+    /// Every write in the range is checked, even once one has failed, and the first failure is
+    /// returned.
+    pub fn verify_commit(
+        &mut self,
+        handle: &[u8],
+        path: &Path,
+        file: &File,
+        offset: u64,
+        count: u32,
+    ) -> io::Result<()> {
+        let Some(pending) = self.pending.remove(handle) else {
+            return Ok(());
+        };
+        self.pending_ranges -= pending.len();
+
+        let end = if count == 0 {
+            u64::MAX
+        } else {
+            offset.saturating_add(count as u64)
+        };
+
+        let (committed, remaining): (Vec<_>, Vec<_>) = pending
+            .into_iter()
+            .partition(|r| r.offset >= offset && r.end() <= end);
+
+        if !remaining.is_empty() {
+            self.pending_ranges += remaining.len();
+            self.pending.insert(handle.to_vec(), remaining);
+        }
+
+        let mut res = Ok(());
+        for range in committed.iter() {
+            let checked = self.check(path, file, range);
+            if res.is_ok() {
+                res = checked;
+            }
+        }
+
+        res
+    }
+
+    /// Drop the uncommitted writes to the file with `handle` that `range` overlaps.
+    fn supersede(&mut self, handle: &[u8], range: &PendingRange) {
+        let Some(pending) = self.pending.get_mut(handle) else {
+            return;
+        };
+
+        let before = pending.len();
+        pending.retain(|r| !r.overlaps(range));
+        self.pending_ranges -= before - pending.len();
+        if pending.is_empty() {
+            self.pending.remove(handle);
+        }
+    }
+
+    fn check(&mut self, path: &Path, file: &File, range: &PendingRange) -> io::Result<()> {
+        let start = Instant::now();
+        let res = read_back_crc(file, range.offset, range.len);
+
+        self.stats.ranges += 1;
+        self.stats.bytes += range.len;
+        self.stats.elapsed += start.elapsed();
+
+        if self.stats.ranges.is_multiple_of(STATS_INTERVAL) {
+            info!("write verification: {}", self.stats);
+        }
+
+        let crc = res?;
+        if crc != range.crc {
+            self.stats.mismatches += 1;
+            error!(
+                "write verification of {} failed: {} bytes at offset {} have checksum {crc:#010x}, \
+                 expected {:#010x} ({})",
+                path.display(),
+                range.len,
+                range.offset,
+                range.crc,
+                self.stats
+            );
+            return Err(io::Error::other(
+                "read back data does not match what was written",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Drop a range of a file from the page cache, then read it back from storage and checksum it.
+fn read_back_crc(file: &File, offset: u64, len: u64) -> io::Result<u32> {
+    // SAFETY: posix_fadvise only takes integer arguments and does not access memory.
+    let res = unsafe {
+        libc::posix_fadvise(
+            file.as_raw_fd(),
+            offset as libc::off_t,
+            len as libc::off_t,
+            libc::POSIX_FADV_DONTNEED,
+        )
+    };
+    if res != 0 {
+        return Err(io::Error::from_raw_os_error(res));
+    }
+
+    let mut buf = vec![0; len as usize];
+    crate::fileio::read_exact_at(file, &mut buf, offset)?;
+
+    Ok(crc32fast::hash(&buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    #[test]
+    fn pending_writes() {
+        let path = std::env::temp_dir().join(format!("verify_pending_{}", std::process::id()));
+        fs::write(&path, b"aaaabbbbcccc").unwrap();
+        let file = File::open(&path).unwrap();
+        let mut verifier = WriteVerifier::default();
+
+        // Every range committed is checked, though the first does not match:
+        verifier.record_unstable(b"file", 0, b"xxxx");
+        verifier.record_unstable(b"file", 4, b"bbbb");
+        verifier.record_unstable(b"file", 8, b"yyyy");
+        assert!(verifier.verify_commit(b"file", &path, &file, 0, 0).is_err());
+        assert_eq!(verifier.stats.ranges, 3);
+        assert_eq!(verifier.stats.mismatches, 2);
+        assert_eq!(verifier.pending_ranges, 0);
+
+        // The writes to a removed file are forgotten:
+        verifier.record_unstable(b"removed", 0, b"aaaa");
+        verifier.forget(b"removed");
+        assert!(verifier.pending.is_empty());
+        assert_eq!(verifier.pending_ranges, 0);
+
+        // Only so many writes are kept for each file, and in all:
+        for i in 0..MAX_PENDING_PER_FILE as u64 + 1 {
+            verifier.record_unstable(b"file", i, b"a");
+        }
+        assert_eq!(verifier.pending[&b"file"[..]].len(), MAX_PENDING_PER_FILE);
+        assert_eq!(verifier.stats.unverified, 1);
+        for i in 0..MAX_PENDING as u64 {
+            verifier.record_unstable(&i.to_be_bytes(), 0, b"a");
+        }
+        assert_eq!(verifier.pending_ranges, MAX_PENDING);
+        assert_eq!(verifier.stats.unverified, 1 + MAX_PENDING_PER_FILE as u64);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,571 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The file system backend that the procedures operate on, once they have found the path of the file
+// a call is about.
+//
+// `VfsBackend` has one method for each thing a procedure does to a file: get or set its
+// attributes, read or write its data, list a directory, and make, link, rename, or remove an entry.
+// Failures are reported as `io::Error`s with the errno that the local file system would give, so
+// that `nfs_status()` maps them to the same NFS status whatever the backend. The server uses
+// `LocalFs`, which operates on the local file system as the caller's mapped identity (see
+// `identity`), unless it is configured to keep its files in memory, in `MemFs` (see `memfs`).
+//
+// Some features need a file descriptor of a local file, and are only available with `LocalFs`:
+// READ replies that the ring sends from the file, mapped READs, COMMIT's fsync, which is submitted
+// to the ring, write verification, and the snapshots overlay (see `snapshots`).
+//
+// `LocalFs` resolves paths beneath the root of their export, from a descriptor of the root that it
+// keeps open while the directory is exported, so that a directory replaced by a symbolic link
+// cannot lead a call out of the export (see `resolve`).
+
+use std::{
+    ffi::{OsStr, OsString},
+    fmt,
+    fs::{self, File, Permissions},
+    io,
+    os::{
+        fd::{AsFd, AsRawFd, IntoRawFd, OwnedFd},
+        unix::{ffi::OsStrExt, fs::PermissionsExt},
+    },
+    path::{Path, PathBuf},
+    ptr::NonNull,
+    sync::RwLock,
+};
+
+use nix::{
+    fcntl::{readlinkat, renameat, AtFlags, OFlag},
+    sys::{
+        stat::{self, makedev, utimensat, SFlag, UtimensatFlags},
+        statvfs::statvfs,
+        time::TimeSpec,
+    },
+    unistd::{fchownat, linkat, symlinkat, truncate, unlinkat, Gid, Uid, UnlinkatFlags},
+};
+
+use nfs3::file_handle::{FileId, FileSystem, LocalFileSystem};
+use nfs3::nfs3_xdr::*;
+
+use crate::{fileio, files::file_attributes, resolve};
+
+/// The entries of a directory after a cookie, in the order of the directory.
+pub type Entries = Box<dyn Iterator<Item = io::Result<DirEntry>>>;
+
+/// How much space and how many files a file system has: in all, free, and free for callers other
+/// than root.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FsStats {
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+    pub available_bytes: u64,
+    pub total_files: u64,
+    pub free_files: u64,
+    pub available_files: u64,
+}
+
+/// An entry read from a directory. Its cookie is where a listing continues after it.
+#[derive(Debug)]
+pub struct DirEntry {
+    pub name: OsString,
+    pub fileid: u64,
+    pub cookie: u64,
+}
+
+/// A file system that the procedures operate on. Paths are absolute, and a symbolic link is never
+/// followed: each method operates on the link itself.
+pub trait VfsBackend: fmt::Debug + Send + Sync {
+    /// Whether the paths are those of files on the local file system, which the server may open
+    /// itself.
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    /// Open the local file at `path` with `flags`, for what needs a file descriptor. Only a local
+    /// backend can, and others fail with EOPNOTSUPP.
+    fn open(&self, _path: &Path, _flags: OFlag) -> io::Result<File> {
+        Err(io::Error::from_raw_os_error(libc::EOPNOTSUPP))
+    }
+
+    /// The attributes of the file at `path`.
+    fn getattr(&self, path: &Path) -> io::Result<FileAttributes>;
+
+    /// Apply the attributes that a client set to the file at `path`: the size, which only a regular
+    /// file has, then the owner, the mode, which a symbolic link does not have, and the times.
+    fn setattr(&self, path: &Path, attributes: &SetAttributes) -> io::Result<()>;
+
+    /// The path of the entry `name` in the directory at `dir`, if there is one. Fails with ENOTDIR
+    /// if `dir` is not a directory.
+    fn lookup(&self, dir: &Path, name: &OsStr) -> io::Result<PathBuf>;
+
+    /// Up to `count` bytes of the file at `path` from `offset`, which are only fewer at the end of
+    /// the file, and whether the end was reached.
+    fn read(&self, path: &Path, offset: u64, count: u32) -> io::Result<(Vec<u8>, bool)>;
+
+    /// Write all of `data` to the file at `path` at `offset`, and flush it as `stable` requires.
+    fn write(&self, path: &Path, offset: u64, data: &[u8], stable: &StableHow) -> io::Result<()>;
+
+    /// The entries of the directory at `dir` after `cookie`, or from its start for a cookie of 0,
+    /// without "." and "..".
+    fn readdir(&self, dir: &Path, cookie: u64) -> io::Result<Entries>;
+
+    /// Make a regular file at `path` with `mode` and, if given, `size`. Fails with EEXIST if there
+    /// is already an entry at `path`.
+    fn create(&self, path: &Path, mode: u32, size: Option<u64>) -> io::Result<()>;
+
+    fn mkdir(&self, path: &Path, mode: u32) -> io::Result<()>;
+
+    /// Make a FIFO, a socket, or a device file at `path`. Only a device file has a `device`.
+    fn mknod(&self, path: &Path, r#type: FileType, device: SpecData) -> io::Result<()>;
+
+    /// Make a symbolic link at `path`, to `target` as it is given.
+    fn symlink(&self, target: &OsStr, path: &Path) -> io::Result<()>;
+
+    /// The target of the symbolic link at `path`. Fails with EINVAL for any other file.
+    fn readlink(&self, path: &Path) -> io::Result<OsString>;
+
+    /// Make a hard link at `path` to the file at `file`, which may not be a directory.
+    fn link(&self, file: &Path, path: &Path) -> io::Result<()>;
+
+    /// Remove the entry at `path`, which may not be a directory.
+    fn remove(&self, path: &Path) -> io::Result<()>;
+
+    /// Move the entry at `from` to `to`, replacing what is there as rename(2) does.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// The space and files of the file system that the file at `path` is on.
+    fn fsstat(&self, path: &Path) -> io::Result<FsStats>;
+}
+
+/// The local file system, as seen by the server's current file system credentials.
+///
+/// Every path is resolved beneath the root of the export it is in (see `resolve`), which the file
+/// handle map tells it of as the `FileSystem` of the map, and a path in no export is refused with
+/// EACCES. The last component of a path is never followed.
+#[derive(Debug, Default)]
+pub struct LocalFs {
+    roots: RwLock<Vec<Root>>,
+}
+
+/// The root directory of an export.
+#[derive(Debug)]
+struct Root {
+    path: PathBuf,
+    dir: OwnedFd,
+
+    /// How many file handle maps export it.
+    exports: usize,
+}
+
+impl LocalFs {
+    /// A file system whose exports are `roots`, without a file handle map.
+    #[cfg(test)]
+    pub fn exporting(roots: &[&Path]) -> Self {
+        let local = Self::default();
+        for root in roots {
+            local.exported(root).unwrap();
+        }
+
+        local
+    }
+
+    /// The directory that the entry at `path` is in, opened beneath the root of its export, and
+    /// the name of the entry, which is "." for the root itself.
+    fn at<'a>(&self, path: &'a Path) -> io::Result<(OwnedFd, &'a OsStr)> {
+        let roots = self.roots.read().unwrap();
+        let Some(root) = roots
+            .iter()
+            .filter(|root| path.starts_with(&root.path))
+            .max_by_key(|root| root.path.as_os_str().len())
+        else {
+            return Err(io::Error::from_raw_os_error(libc::EACCES));
+        };
+
+        let relative = path.strip_prefix(&root.path).unwrap();
+        match (relative.parent(), relative.file_name()) {
+            (Some(dir), Some(name)) if dir.as_os_str().is_empty() => {
+                Ok((root.dir.try_clone()?, name))
+            }
+            (Some(dir), Some(name)) => Ok((resolve::open_dir(root.dir.as_fd(), dir)?, name)),
+            _ if relative.as_os_str().is_empty() => Ok((root.dir.try_clone()?, OsStr::new("."))),
+            _ => Err(io::Error::from_raw_os_error(libc::EINVAL)),
+        }
+    }
+
+    /// An O_PATH descriptor of the file at `path`, through which its attributes can be changed
+    /// without resolving its path again.
+    fn open_path(&self, path: &Path) -> io::Result<File> {
+        self.open(path, OFlag::O_PATH)
+    }
+}
+
+/// The path through which the file that `file` is open on can be opened again, or changed, as
+/// though by its own path.
+fn fd_path(file: &File) -> PathBuf {
+    PathBuf::from(format!("/proc/self/fd/{}", file.as_raw_fd()))
+}
+
+impl FileSystem for LocalFs {
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        LocalFileSystem.canonicalize(path)
+    }
+
+    fn identify(&self, path: &Path) -> io::Result<FileId> {
+        LocalFileSystem.identify(path)
+    }
+
+    fn generation(&self, path: &Path, id: &FileId) -> u32 {
+        LocalFileSystem.generation(path, id)
+    }
+
+    fn entries(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        LocalFileSystem.entries(dir)
+    }
+
+    fn exported(&self, root: &Path) -> io::Result<()> {
+        let mut roots = self.roots.write().unwrap();
+        match roots.iter_mut().find(|r| r.path == root) {
+            Some(existing) => existing.exports += 1,
+            None => roots.push(Root {
+                path: root.to_path_buf(),
+                dir: nix::fcntl::open(
+                    root,
+                    OFlag::O_PATH | OFlag::O_DIRECTORY,
+                    stat::Mode::empty(),
+                )?,
+                exports: 1,
+            }),
+        }
+
+        Ok(())
+    }
+
+    fn unexported(&self, root: &Path) {
+        let mut roots = self.roots.write().unwrap();
+        if let Some(index) = roots.iter().position(|r| r.path == root) {
+            roots[index].exports -= 1;
+            if roots[index].exports == 0 {
+                roots.swap_remove(index);
+            }
+        }
+    }
+}
+
+impl VfsBackend for LocalFs {
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    fn open(&self, path: &Path, flags: OFlag) -> io::Result<File> {
+        let (dir, name) = self.at(path)?;
+        let fd = nix::fcntl::openat(
+            dir,
+            name,
+            flags | OFlag::O_NOFOLLOW | OFlag::O_CLOEXEC,
+            stat::Mode::empty(),
+        )?;
+
+        Ok(fd.into())
+    }
+
+    fn getattr(&self, path: &Path) -> io::Result<FileAttributes> {
+        Ok(file_attributes(&self.open_path(path)?.metadata()?))
+    }
+
+    /// The size and mode are changed through the file's descriptor, since a symbolic link that
+    /// replaced it would be followed by its path.
+    fn setattr(&self, path: &Path, attributes: &SetAttributes) -> io::Result<()> {
+        let file = self.open_path(path)?;
+        let metadata = file.metadata()?;
+
+        if let Some(size) = attributes.size.inner {
+            if !metadata.is_file() {
+                return Err(io::Error::from_raw_os_error(libc::EINVAL));
+            }
+            truncate(&fd_path(&file), size as libc::off_t)?;
+        }
+
+        let (dir, name) = self.at(path)?;
+        let (uid, gid) = (attributes.uid.inner, attributes.gid.inner);
+        if uid.is_some() || gid.is_some() {
+            fchownat(
+                &dir,
+                name,
+                uid.map(Uid::from_raw),
+                gid.map(Gid::from_raw),
+                AtFlags::AT_SYMLINK_NOFOLLOW,
+            )?;
+        }
+
+        if let Some(mode) = attributes.mode.inner {
+            if !metadata.is_symlink() {
+                fs::set_permissions(fd_path(&file), Permissions::from_mode(mode & 0o7777))?;
+            }
+        }
+
+        let atime = match attributes.atime {
+            SetAtime::DontChange => TimeSpec::UTIME_OMIT,
+            SetAtime::SetToServerTime => TimeSpec::UTIME_NOW,
+            SetAtime::SetToClientTime(time) => time_spec(time),
+        };
+        let mtime = match attributes.mtime {
+            SetMtime::DontChange => TimeSpec::UTIME_OMIT,
+            SetMtime::SetToServerTime => TimeSpec::UTIME_NOW,
+            SetMtime::SetToClientTime(time) => time_spec(time),
+        };
+        if atime != TimeSpec::UTIME_OMIT || mtime != TimeSpec::UTIME_OMIT {
+            utimensat(&dir, name, &atime, &mtime, UtimensatFlags::NoFollowSymlink)?;
+        }
+
+        Ok(())
+    }
+
+    fn lookup(&self, dir: &Path, name: &OsStr) -> io::Result<PathBuf> {
+        // The directory is opened with O_DIRECTORY, which fails with ENOTDIR for anything else:
+        let path = dir.join(name);
+        self.open_path(&path)?;
+
+        Ok(path)
+    }
+
+    fn read(&self, path: &Path, offset: u64, count: u32) -> io::Result<(Vec<u8>, bool)> {
+        let file = self.open(path, OFlag::O_RDONLY)?;
+        let mut data = vec![0; count as usize];
+        let filled = fileio::read_full_at(&file, &mut data, offset)?;
+        data.truncate(filled);
+
+        let eof = offset + filled as u64 >= file.metadata()?.len();
+
+        Ok((data, eof))
+    }
+
+    fn write(&self, path: &Path, offset: u64, data: &[u8], stable: &StableHow) -> io::Result<()> {
+        let file = self.open(path, OFlag::O_WRONLY)?;
+        fileio::write_full_at(&file, data, offset)?;
+
+        fileio::sync(&file, stable)
+    }
+
+    fn readdir(&self, dir: &Path, cookie: u64) -> io::Result<Entries> {
+        let dir = self.open(dir, OFlag::O_RDONLY | OFlag::O_DIRECTORY)?;
+
+        Ok(Box::new(DirStream::open(dir.into(), cookie)?))
+    }
+
+    /// The size is set through the new file, which its mode may not let the caller open again.
+    fn create(&self, path: &Path, mode: u32, size: Option<u64>) -> io::Result<()> {
+        let (dir, name) = self.at(path)?;
+        let file: File = nix::fcntl::openat(
+            &dir,
+            name,
+            OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_CLOEXEC,
+            stat::Mode::from_bits_truncate(mode),
+        )?
+        .into();
+
+        if let Some(size) = size {
+            file.set_len(size).inspect_err(|_| {
+                let _ = unlinkat(&dir, name, UnlinkatFlags::NoRemoveDir);
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn mkdir(&self, path: &Path, mode: u32) -> io::Result<()> {
+        let (dir, name) = self.at(path)?;
+
+        Ok(stat::mkdirat(
+            dir,
+            name,
+            stat::Mode::from_bits_truncate(mode),
+        )?)
+    }
+
+    fn mknod(&self, path: &Path, r#type: FileType, device: SpecData) -> io::Result<()> {
+        let (kind, dev) = match r#type {
+            FileType::Chr => (SFlag::S_IFCHR, device),
+            FileType::Blk => (SFlag::S_IFBLK, device),
+            FileType::Sock => (SFlag::S_IFSOCK, SpecData::default()),
+            FileType::Fifo => (SFlag::S_IFIFO, SpecData::default()),
+            _ => return Err(io::Error::from_raw_os_error(libc::EINVAL)),
+        };
+        let dev = makedev(dev.specdata1.into(), dev.specdata2.into());
+        let (dir, name) = self.at(path)?;
+
+        Ok(stat::mknodat(dir, name, kind, stat::Mode::empty(), dev)?)
+    }
+
+    fn symlink(&self, target: &OsStr, path: &Path) -> io::Result<()> {
+        let (dir, name) = self.at(path)?;
+
+        Ok(symlinkat(target, dir, name)?)
+    }
+
+    fn readlink(&self, path: &Path) -> io::Result<OsString> {
+        let (dir, name) = self.at(path)?;
+
+        Ok(readlinkat(dir, name)?)
+    }
+
+    fn link(&self, file: &Path, path: &Path) -> io::Result<()> {
+        let (from_dir, from_name) = self.at(file)?;
+        let (to_dir, to_name) = self.at(path)?;
+
+        Ok(linkat(
+            from_dir,
+            from_name,
+            to_dir,
+            to_name,
+            AtFlags::empty(),
+        )?)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        let (dir, name) = self.at(path)?;
+
+        Ok(unlinkat(dir, name, UnlinkatFlags::NoRemoveDir)?)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let (from_dir, from_name) = self.at(from)?;
+        let (to_dir, to_name) = self.at(to)?;
+
+        Ok(renameat(from_dir, from_name, to_dir, to_name)?)
+    }
+
+    fn fsstat(&self, path: &Path) -> io::Result<FsStats> {
+        let stats = statvfs(&fd_path(&self.open_path(path)?))?;
+        let fragment = stats.fragment_size() as u64;
+
+        Ok(FsStats {
+            total_bytes: stats.blocks() as u64 * fragment,
+            free_bytes: stats.blocks_free() as u64 * fragment,
+            available_bytes: stats.blocks_available() as u64 * fragment,
+            total_files: stats.files() as u64,
+            free_files: stats.files_free() as u64,
+            available_files: stats.files_available() as u64,
+        })
+    }
+}
+
+fn time_spec(time: NfsTime) -> TimeSpec {
+    TimeSpec::new(time.seconds.into(), time.nseconds.into())
+}
+
+/// Reads the entries of a local directory in the order of its directory stream. As in the Linux
+/// server, the cookie of an entry is the position of the stream after it (see telldir(3)), so a
+/// listing continues by seeking the stream to the cookie.
+struct DirStream {
+    dir: NonNull<libc::DIR>,
+}
+
+impl DirStream {
+    /// Read the directory that `dir` is open on, positioned after the entry with the given cookie.
+    fn open(dir: OwnedFd, cookie: u64) -> io::Result<Self> {
+        let fd = dir.into_raw_fd();
+        // SAFETY: `fd` is an open file descriptor, whose ownership passes to the stream.
+        let Some(dir) = NonNull::new(unsafe { libc::fdopendir(fd) }) else {
+            let e = io::Error::last_os_error();
+            // SAFETY: fdopendir(3) failed, so `fd` is still ours to close.
+            unsafe { libc::close(fd) };
+            return Err(e);
+        };
+
+        if cookie != 0 {
+            // SAFETY: `dir` is an open directory stream.
+            unsafe { libc::seekdir(dir.as_ptr(), cookie as libc::c_long) };
+        }
+
+        Ok(Self { dir })
+    }
+}
+
+impl Iterator for DirStream {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // readdir(3) only reports errors through errno:
+            nix::errno::Errno::clear();
+
+            // SAFETY: `dir` is an open directory stream, and the entry it returns stays valid until
+            // the next call on the stream.
+            let entry = unsafe { libc::readdir64(self.dir.as_ptr()).as_ref() };
+            let Some(entry) = entry else {
+                return match nix::errno::Errno::last_raw() {
+                    0 => None,
+                    errno => Some(Err(io::Error::from_raw_os_error(errno))),
+                };
+            };
+
+            // SAFETY: the name of an entry is a NUL-terminated string.
+            let name = OsStr::from_bytes(
+                unsafe { std::ffi::CStr::from_ptr(entry.d_name.as_ptr()) }.to_bytes(),
+            );
+            if name == "." || name == ".." {
+                continue;
+            }
+
+            return Some(Ok(DirEntry {
+                name: name.to_os_string(),
+                fileid: entry.d_ino,
+                // SAFETY: `dir` is an open directory stream.
+                cookie: unsafe { libc::telldir(self.dir.as_ptr()) } as u64,
+            }));
+        }
+    }
+}
+
+impl Drop for DirStream {
+    fn drop(&mut self) {
+        // SAFETY: `dir` is an open directory stream, which is not used again.
+        unsafe { libc::closedir(self.dir.as_ptr()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn paths_stay_in_their_export() {
+        let dir = std::env::temp_dir().join(format!("vfs_export_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let root = dir.join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir(dir.join("outside")).unwrap();
+        fs::write(dir.join("outside/secret"), b"secret").unwrap();
+        let local = LocalFs::exporting(&[&root]);
+
+        local.getattr(&root).unwrap();
+        local.getattr(&root.join("sub")).unwrap();
+        let e = local.getattr(&dir.join("outside/secret")).unwrap_err();
+        assert_eq!(e.raw_os_error(), Some(libc::EACCES));
+
+        // A directory replaced by a link out of the export, however it leads there, leads nowhere:
+        fs::remove_dir(root.join("sub")).unwrap();
+        for target in [dir.join("outside"), PathBuf::from("../outside")] {
+            let _ = fs::remove_file(root.join("sub"));
+            symlink(&target, root.join("sub")).unwrap();
+            let secret = root.join("sub/secret");
+
+            assert!(local.getattr(&secret).is_err(), "{}", target.display());
+            assert!(local.read(&secret, 0, 6).is_err());
+            assert!(local
+                .write(&secret, 0, b"public", &StableHow::FileSync)
+                .is_err());
+            assert!(local.remove(&secret).is_err());
+            assert!(local.create(&root.join("sub/new"), 0o644, None).is_err());
+
+            // The link itself is what its own path names:
+            let attributes = local.getattr(&root.join("sub")).unwrap();
+            assert_eq!(attributes.r#type, FileType::Lnk);
+        }
+        assert_eq!(fs::read(dir.join("outside/secret")).unwrap(), b"secret");
+        assert!(!dir.join("outside/new").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,437 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Implementations of the WRITE and COMMIT procedures.
+//
+// UNSTABLE writes are left in the page cache and are only made durable by a later COMMIT (or by
+// the kernel's own writeback). DATA_SYNC and FILE_SYNC writes are flushed before the reply is sent.
+// Every WRITE and COMMIT reply carries the server's write verifier, which changes each time the
+// server starts, so that clients can detect that uncommitted data may have been lost and resend it.
+//
+// The data of a WRITE is not copied out of the buffer it was received in: see `decode_write_args()`.
+// It is written through the server's `VfsBackend`.
+//
+// If write verification is enabled, stable WRITEs and COMMITs only succeed once the data has been
+// read back from the local file and checked; see verify.rs. Otherwise, a COMMIT's file is flushed by
+// an fsync submitted to the ring, so that the server answers other calls while it is.
+//
+// If the server's integrity mode is not off, the data of a WRITE whose client sent a digest of it is
+// checked against that digest before it is written, and the reply carries the digest of the data as
+// received; see nfs3::write_digest.
+
+use std::{
+    io,
+    os::fd::AsRawFd,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use io_uring::{opcode, types};
+use log::*;
+use nix::fcntl::OFlag;
+
+use nfs3::{config::Integrity, nfs3_xdr::*, write_digest};
+use rpc_protocol::{
+    ring::{ArgBuffer, RingCall, RingResult, MAX_CALL_HEADER},
+    server::RpcResult,
+    OpaqueAuth,
+};
+
+use crate::{fileio, files::*, results::*, ServerState};
+
+/// The most data written by a single WRITE, regardless of how much the client sends. This is the
+/// largest WRITE that the server's receive buffers are sized for.
+pub const MAX_WRITE: u32 = 64 * 1024;
+
+/// The size of a WRITE call of `MAX_WRITE` bytes: the data, the other arguments (a file handle of
+/// up to `FHSIZE` bytes, the offset, count, stability, and data length), and the call header.
+pub const MAX_WRITE_CALL: u32 = MAX_WRITE + (4 + FHSIZE + 8 + 4 + 4 + 4) + MAX_CALL_HEADER;
+
+/// The largest WRITE whose call fits in `max_call_size` bytes: `MAX_WRITE`, unless the server only
+/// accepts smaller calls.
+pub fn max_write(max_call_size: u32) -> u32 {
+    max_call_size
+        .min(MAX_WRITE_CALL)
+        .saturating_sub(MAX_WRITE_CALL - MAX_WRITE)
+}
+
+/// Create a write verifier that is unique to this instance of the server.
+pub fn new_write_verifier() -> [u8; NFS3_WRITEVERFSIZE as usize] {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time should be after the epoch");
+
+    (now.as_nanos() as u64).to_be_bytes()
+}
+
+/// Decode the arguments of a WRITE, except for the data, which is retained in the receive buffer
+/// rather than copied into `WriteArgs::data` (which is left empty).
+fn decode_write_args(call: &RingCall) -> xdr_lib::Result<(WriteArgs, ArgBuffer)> {
+    let mut args = WriteArgs::default();
+    let mut arg = call.arg;
+
+    FileHandle::deserialize(&mut args.file, &mut arg)?;
+    xdr_lib::get_u64(&mut args.offset, &mut arg)?;
+    xdr_lib::get_u32(&mut args.count, &mut arg)?;
+    StableHow::deserialize(&mut args.stable, &mut arg)?;
+
+    let mut len = 0;
+    xdr_lib::get_u32(&mut len, &mut arg)?;
+    let Some(data) = arg.get(..len as usize) else {
+        return Err(xdr_lib::DeserializeError);
+    };
+
+    Ok((args, call.retain(data)))
+}
+
+/// Check the data of a WRITE against the digest its client sent, if any, as the server's integrity
+/// mode has it. Returns the verifier to reply with, if the data was checked, or None if the WRITE is
+/// refused.
+fn check_digest(
+    integrity: Integrity,
+    digest: Option<u32>,
+    data: &[u8],
+) -> Option<Option<OpaqueAuth>> {
+    let Some(expected) = digest.filter(|_| integrity != Integrity::Off) else {
+        return Some(None);
+    };
+
+    let actual = crc32c::crc32c(data);
+    if actual != expected {
+        warn!(
+            "WRITE data arrived with checksum {actual:#010x}, but was sent with {expected:#010x}"
+        );
+        if integrity == Integrity::Reject {
+            return None;
+        }
+    }
+
+    Some(Some(write_digest::reply_verifier(data)))
+}
+
+pub fn write(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let Ok((args, data)) = decode_write_args(call) else {
+        return RingResult::Done(RpcResult::GarbageArgs);
+    };
+
+    debug!(
+        "WRITE: {} bytes at offset {}, {:?}",
+        args.count, args.offset, args.stable
+    );
+
+    // The digest covers all of the data sent, however much of it is written:
+    let Some(verf) = check_digest(state.integrity, write_digest::requested(call), &data[..]) else {
+        return RingResult::Done(RpcResult::GarbageArgs);
+    };
+
+    let mut body = || {
+        let path = state.lookup(call, &args.file.data)?;
+
+        // The count must not exceed the amount of data actually sent, nor the most the server
+        // writes at once:
+        let count = (args.count as usize)
+            .min(data.len())
+            .min(MAX_WRITE as usize);
+
+        let data = &data[..count];
+        let vfs = state.vfs.clone();
+        let handle = &args.file.data;
+        let (res, file_wcc) = with_wcc(&*vfs, &path, || -> Result<(), NfsError> {
+            state.check_writable(&path)?;
+
+            let written = match vfs.is_local() {
+                true => state
+                    .open_file(handle, &path, OFlag::O_WRONLY)
+                    .and_then(|file| {
+                        fileio::write_full_at(&file, data, args.offset)?;
+                        fileio::sync(&file, &args.stable)
+                    }),
+                false => vfs.write(&path, args.offset, data, &args.stable),
+            };
+
+            written
+                .and_then(|()| {
+                    if state.verifier.is_none() {
+                        return Ok(());
+                    }
+                    let file = match args.stable {
+                        StableHow::Unstable => None,
+                        _ => Some(state.open_file(handle, &path, OFlag::O_RDONLY)?),
+                    };
+                    let verifier = state.verifier.as_mut().unwrap();
+                    match file {
+                        None => verifier.record_unstable(handle, args.offset, data),
+                        Some(file) => {
+                            verifier.verify_write(handle, &path, &file, args.offset, data)?
+                        }
+                    }
+
+                    Ok(())
+                })
+                .map_err(|e| {
+                    warn!("WRITE to {} failed: {e}", path.display());
+                    e.into()
+                })
+        });
+
+        state.attributes.forget(&path);
+        res.or_fail(|| WriteFailure { file_wcc })?;
+        state.record_write(&path, count);
+
+        Ok(WriteSuccess {
+            file_wcc,
+            count: count as u32,
+            committed: args.stable,
+            verf: state.write_verifier,
+        })
+    };
+    let result = encode::<WriteResult>(body());
+
+    RingResult::Done(match verf {
+        Some(verf) => RpcResult::SuccessWithVerifier(verf, result),
+        None => RpcResult::Success(result),
+    })
+}
+
+/// COMMIT flushes the whole file rather than just the requested range, which RFC 1813 permits.
+pub fn commit(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    let args = decode_args!(call, CommitArgs);
+
+    debug!("COMMIT: {} bytes at offset {}", args.count, args.offset);
+
+    if state.verifier.is_none() {
+        return commit_in_ring(call, &args, state);
+    }
+
+    reply::<CommitResult>(|| {
+        let path = state.lookup(call, &args.file.data)?;
+
+        let vfs = state.vfs.clone();
+        let (res, file_wcc) = with_wcc(&*vfs, &path, || {
+            let flags = match state.verifier {
+                Some(_) => OFlag::O_RDWR,
+                None => OFlag::O_WRONLY,
+            };
+            state
+                .open_file(&args.file.data, &path, flags)
+                .and_then(|file| {
+                    fileio::sync(&file, &StableHow::FileSync)?;
+
+                    if let Some(verifier) = state.verifier.as_mut() {
+                        verifier.verify_commit(
+                            &args.file.data,
+                            &path,
+                            &file,
+                            args.offset,
+                            args.count,
+                        )?;
+                    }
+
+                    Ok(())
+                })
+        });
+
+        res.inspect_err(|e| warn!("COMMIT of {} failed: {e}", path.display()))
+            .or_fail(|| CommitFailure { file_wcc })?;
+
+        Ok(CommitSuccess {
+            file_wcc,
+            verf: state.write_verifier,
+        })
+    })
+}
+
+/// Like `commit()`, without write verification, flushing the file with an fsync submitted to the
+/// ring, and replying once it completes.
+fn commit_in_ring(
+    call: &RingCall,
+    args: &CommitArgs,
+    state: &mut ServerState,
+) -> RingResult<ServerState> {
+    let path = match state.lookup(call, &args.file.data) {
+        Ok(path) => path,
+        Err(status) => return reply::<CommitResult>(|| Err(status.into())),
+    };
+
+    let before = pre_op_attributes(&*state.vfs, &path);
+    if !state.vfs.is_local() {
+        // Whatever the backend keeps is as stable as it gets:
+        let res = state.vfs.getattr(&path).map(drop);
+        return committed(&path, before, res, state);
+    }
+    let file = match state.open_file(&args.file.data, &path, OFlag::O_WRONLY) {
+        Ok(file) => file,
+        Err(e) => return committed(&path, before, Err(e), state),
+    };
+
+    let fsync = opcode::Fsync::new(types::Fd(file.as_raw_fd())).build();
+
+    // SAFETY: the file is kept open by the continuation until the fsync completes.
+    unsafe {
+        RingResult::more_io(fsync, move |res, state: &mut ServerState| {
+            drop(file);
+            committed(&path, before, res.map(drop), state)
+        })
+    }
+}
+
+/// Reply to a COMMIT of the file at `path`, whose attributes before it were `before`, once flushing
+/// it has finished with `res`.
+fn committed(
+    path: &Path,
+    before: PreOpAttributes,
+    res: io::Result<()>,
+    state: &ServerState,
+) -> RingResult<ServerState> {
+    let file_wcc = WccData {
+        before,
+        after: post_op_attributes(&*state.vfs, path),
+    };
+
+    reply::<CommitResult>(|| {
+        res.inspect_err(|e| warn!("COMMIT of {} failed: {e}", path.display()))
+            .or_fail(|| CommitFailure { file_wcc })?;
+
+        Ok(CommitSuccess {
+            file_wcc,
+            verf: state.write_verifier,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{fs, net::TcpStream};
+
+    use nfs3::nfs3_xdr::procedures::NFS_V3;
+
+    use crate::testing::{self, TestServer};
+
+    fn write(
+        stream: &mut TcpStream,
+        file: &FileHandle,
+        offset: u64,
+        count: u32,
+        stable: StableHow,
+        data: &[u8],
+    ) -> WriteSuccess {
+        let args = WriteArgs {
+            file: file.clone(),
+            offset,
+            count,
+            stable,
+            data: data.to_vec(),
+        };
+
+        testing::call::<WriteResult>(stream, NFS_V3::WRITE, &args.serialize_alloc()).unwrap()
+    }
+
+    fn commit(stream: &mut TcpStream, file: &FileHandle, offset: u64, count: u32) -> CommitSuccess {
+        let args = CommitArgs {
+            file: file.clone(),
+            offset,
+            count,
+        };
+
+        testing::call::<CommitResult>(stream, NFS_V3::COMMIT, &args.serialize_alloc()).unwrap()
+    }
+
+    /// Write and commit a file on a server that verifies written data if `verify_writes` is set.
+    fn write_and_commit(name: &str, verify_writes: bool) {
+        let dir = std::env::temp_dir().join(format!("{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file");
+        fs::write(&path, b"").unwrap();
+
+        let mut config = testing::config(&dir);
+        config.nfs_server.verify_writes = verify_writes;
+        let server = TestServer::start(config);
+        let file = server.handle(&path);
+        let mut stream = server.connect();
+
+        // A WRITE is committed as it asks to be, and every reply carries the same verifier:
+        let unstable = write(&mut stream, &file, 0, 5, StableHow::Unstable, b"hello");
+        assert_eq!(unstable.count, 5);
+        assert_eq!(unstable.committed, StableHow::Unstable);
+        let verf = unstable.verf;
+
+        let stable = write(&mut stream, &file, 5, 6, StableHow::FileSync, b" world");
+        assert_eq!(stable.count, 6);
+        assert_eq!(stable.committed, StableHow::FileSync);
+        assert_eq!(stable.verf, verf);
+        assert_eq!(fs::read(&path).unwrap(), b"hello world");
+
+        // The count is capped at the data sent, and at the most written at once:
+        let short = write(&mut stream, &file, 11, 100, StableHow::Unstable, b"!");
+        assert_eq!(short.count, 1);
+        assert_eq!(short.verf, verf);
+
+        let data = vec![b'x'; MAX_WRITE as usize + 100];
+        let long = write(
+            &mut stream,
+            &file,
+            12,
+            data.len() as u32,
+            StableHow::Unstable,
+            &data,
+        );
+        assert_eq!(long.count, MAX_WRITE);
+        assert_eq!(long.verf, verf);
+        assert_eq!(
+            fs::metadata(&path).unwrap().len(),
+            12 + u64::from(MAX_WRITE)
+        );
+
+        // A COMMIT of a range, and of the whole file, replies with the same verifier:
+        let range = commit(&mut stream, &file, 12, 4096);
+        assert_eq!(range.verf, verf);
+        let whole = commit(&mut stream, &file, 0, 0);
+        assert_eq!(whole.verf, verf);
+        assert_eq!(
+            whole.file_wcc.after.inner.map(|a| a.size),
+            Some(12 + u64::from(MAX_WRITE))
+        );
+
+        // The verifier holds across connections:
+        let mut other = server.connect();
+        assert_eq!(commit(&mut other, &file, 0, 5).verf, verf);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writes_and_commits() {
+        write_and_commit("write_commit", false);
+    }
+
+    #[test]
+    fn verified_writes_and_commits() {
+        write_and_commit("write_commit_verified", true);
+    }
+
+    #[test]
+    fn check_digests() {
+        let data = b"some file data";
+        let sent = Some(crc32c::crc32c(data));
+        let wrong = Some(crc32c::crc32c(b"some file dat4"));
+        let verf = Some(write_digest::reply_verifier(data));
+
+        // Digests are ignored when the mode is off, and data without one is never checked:
+        assert_eq!(check_digest(Integrity::Off, sent, data), Some(None));
+        assert_eq!(check_digest(Integrity::Off, wrong, data), Some(None));
+        assert_eq!(check_digest(Integrity::Reject, None, data), Some(None));
+
+        assert_eq!(check_digest(Integrity::Log, sent, data), Some(verf.clone()));
+        assert_eq!(
+            check_digest(Integrity::Reject, sent, data),
+            Some(verf.clone())
+        );
+
+        // Data that does not match is written anyway only when logging:
+        assert_eq!(check_digest(Integrity::Log, wrong, data), Some(verf));
+        assert_eq!(check_digest(Integrity::Reject, wrong, data), None);
+    }
+}
@@ -1,53 +1,171 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
-use std::net::TcpStream;
+use std::{collections::BTreeSet, net::TcpStream};
 
 use clap::Parser;
 
-use nfs3::mount_proto::*;
-use rpc_protocol::client::*;
+use nfs3::{
+    mount::client::{dump, export},
+    mount_proto::{ExportNode, MountBody},
+};
 
+/// Show the exports of an NFS server, or the clients that have mounted them, like the showmount(8)
+/// of nfs-utils. By default, the hosts that have mounted an export are listed.
 #[derive(Parser)]
+#[command(name = "showmount")]
 struct Cli {
-    #[arg(long, default_value = "localhost")]
-    hostname: String,
+    /// The host whose mount service to ask.
+    #[arg(default_value = "localhost")]
+    host: String,
 
+    /// The host to ask, in place of the host argument.
+    #[arg(long, hide = true)]
+    hostname: Option<String>,
+
+    /// The port of the mount service.
     #[arg(long, default_value_t = 20048)]
     port: u16,
 
+    /// List the exports, with the clients that may mount each.
+    #[arg(short, long, conflicts_with_all = ["all", "directories"])]
+    exports: bool,
+
+    /// List each mount, as the host and the directory that it mounted.
+    #[arg(short, long, conflicts_with = "directories")]
+    all: bool,
+
+    /// List the directories that have been mounted.
     #[arg(short, long)]
-    verbose: bool,
+    directories: bool,
+
+    /// Leave out the line that says what is listed.
+    #[arg(long)]
+    no_headers: bool,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Cli::parse();
-    let server_address = format!("{}:{}", args.hostname, args.port);
-    let mut stream = TcpStream::connect(&server_address)?;
+    let host = args.hostname.as_deref().unwrap_or(&args.host);
+    let mut stream = TcpStream::connect((host, args.port))?;
 
-    let res = do_rpc_call(
-        &mut stream,
-        procedures::MOUNT_PROGRAM,
-        procedures::MOUNT_V3::VERSION,
-        procedures::MOUNT_V3::MOUNTPROC3_EXPORT,
-        &[0u8; 0],
-    )?;
+    let (header, lines) = if args.exports {
+        (
+            format!("Export list for {host}:"),
+            export_lines(&export(&mut stream)?),
+        )
+    } else {
+        let mounts = dump(&mut stream)?;
+        let (header, shown): (_, fn(&MountBody) -> String) = if args.all {
+            ("All mount points on", |m| {
+                format!("{}:{}", m.hostname.display(), m.directory.display())
+            })
+        } else if args.directories {
+            ("Directories on", |m| m.directory.display().to_string())
+        } else {
+            ("Hosts on", |m| m.hostname.display().to_string())
+        };
 
-    let mut export_list = Exports::default();
-    export_list.deserialize(&mut res.as_slice())?;
+        (format!("{header} {host}:"), mount_lines(&mounts, shown))
+    };
 
-    print_exports(&args.hostname, export_list);
+    if !args.no_headers {
+        println!("{header}");
+    }
+    for line in lines {
+        println!("{line}");
+    }
 
     Ok(())
 }
 
-fn print_exports(hostname: &str, list: Exports) {
-    println!("Export list for {hostname}:");
-    for export in list.inner {
-        print!("{} ", export.dir.display());
-        for group in export.groups.inner {
-            print!("{} ", group.name.display());
-        }
-        println!();
+/// Each export's directory, padded to line up, and the clients that may mount it, or
+/// "(everyone)".
+fn export_lines(exports: &[ExportNode]) -> Vec<String> {
+    let width = exports
+        .iter()
+        .map(|e| e.dir.display().to_string().len())
+        .max()
+        .unwrap_or(0);
+
+    exports
+        .iter()
+        .map(|e| {
+            let groups: Vec<_> = e
+                .groups
+                .inner
+                .iter()
+                .map(|g| g.name.display().to_string())
+                .collect();
+            let groups = match groups.is_empty() {
+                true => "(everyone)".to_string(),
+                false => groups.join(","),
+            };
+
+            format!("{:<width$} {groups}", e.dir.display())
+        })
+        .collect()
+}
+
+/// What `shown` makes of each mount, sorted, without duplicates.
+fn mount_lines(mounts: &[MountBody], shown: fn(&MountBody) -> String) -> Vec<String> {
+    mounts
+        .iter()
+        .map(shown)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nfs3::mount_proto::{GroupNode, Groups};
+
+    #[test]
+    fn lines() {
+        let export = |dir: &str, groups: &[&str]| ExportNode {
+            dir: dir.into(),
+            groups: Groups {
+                inner: groups
+                    .iter()
+                    .map(|name| GroupNode {
+                        name: (*name).into(),
+                    })
+                    .collect(),
+            },
+        };
+        let exports = [
+            export("/srv/nfs", &["10.0.0.0/8", "login1"]),
+            export("/home", &[]),
+        ];
+        assert_eq!(
+            export_lines(&exports),
+            ["/srv/nfs 10.0.0.0/8,login1", "/home    (everyone)"]
+        );
+
+        let mount = |host: &str, dir: &str| MountBody {
+            hostname: host.into(),
+            directory: dir.into(),
+        };
+        let mounts = [
+            mount("node2", "/srv/nfs"),
+            mount("node1", "/srv/nfs"),
+            mount("node1", "/home"),
+            mount("node1", "/srv/nfs"),
+        ];
+        assert_eq!(
+            mount_lines(&mounts, |m| m.hostname.display().to_string()),
+            ["node1", "node2"]
+        );
+        assert_eq!(
+            mount_lines(&mounts, |m| format!(
+                "{}:{}",
+                m.hostname.display(),
+                m.directory.display()
+            )),
+            ["node1:/home", "node1:/srv/nfs", "node2:/srv/nfs"]
+        );
     }
 }
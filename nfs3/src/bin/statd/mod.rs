@@ -0,0 +1,475 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// A server for the Network Status Monitor (NSM) protocol, which the NFSv3 lock manager relies on to
+// recover locks when a host restarts.
+//
+// The local lock manager asks statd to monitor each host it holds or grants locks for (SM_MON).
+// When one of those hosts restarts, its statd sends this one an SM_NOTIFY, and statd calls the lock
+// manager back so it can release or reclaim the locks. Likewise, each time this statd starts, it
+// advances the local state number and notifies every host that was monitored before the restart.
+//
+// statd assumes it is started once each time the host boots, before the lock manager.
+//
+// Only the local lock manager may ask statd to monitor hosts, or to stop: SM_MON, SM_UNMON, and
+// SM_UNMON_ALL are refused unless they come from a loopback address, as in rpc.statd. Otherwise a
+// remote host could register a monitor naming any local program and procedure, and then have statd
+// call it from the loopback address with an SM_NOTIFY. The list of monitors is also limited in
+// size, since it is saved on every change.
+
+use std::{
+    ffi::OsStr,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+use clap::Parser;
+use log::*;
+
+use nfs3::{
+    capabilities,
+    config::LogConfig,
+    nsm_proto::{procedures::*, *},
+    suite_proto::Capabilities,
+};
+use rpc_protocol::{server::*, Call};
+
+mod notify;
+mod store;
+
+use notify::Callback;
+use store::Store;
+
+/// The most hosts monitored at once, counting each program that monitors a host separately.
+const MAX_MONITORS: usize = 1024;
+
+#[derive(Parser)]
+struct Cli {
+    /// The address to listen on, IPv4 or IPv6. The IPv6 wildcard address, "::", accepts IPv4
+    /// connections too.
+    #[arg(long, default_value = "0.0.0.0")]
+    address: IpAddr,
+
+    #[arg(long, default_value_t = 662)]
+    port: u16,
+
+    /// The directory in which the state number and the lists of monitored hosts are kept.
+    #[arg(long, default_value = "/test/nfs/statd")]
+    state_dir: PathBuf,
+
+    /// The name by which other hosts know this one, sent in restart notifications. Defaults to the
+    /// host name.
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Serve this suite's capability report (see `nfs3::capabilities`) on this port. It is not
+    /// served unless a port is given.
+    #[arg(long)]
+    capabilities_port: Option<u16>,
+}
+
+struct StatdState {
+    store: Store,
+
+    /// The local state number, which is odd while statd is running.
+    state: i32,
+
+    /// The hosts monitored for the lock manager.
+    monitors: MonitorList,
+
+    /// Sends the lock manager callbacks to the thread that makes them.
+    callbacks: Sender<Callback>,
+}
+
+fn main() {
+    LogConfig::default().init();
+
+    let args = Cli::parse();
+    let name = match args.name {
+        Some(name) => name,
+        None => nix::unistd::gethostname()
+            .expect("Could not get host name")
+            .to_string_lossy()
+            .into_owned(),
+    };
+
+    let store = Store::new(&args.state_dir).unwrap_or_else(|e| {
+        panic!(
+            "Could not create state directory {}: {e}",
+            args.state_dir.display()
+        )
+    });
+
+    let (state, to_notify) = restart(&store).unwrap_or_else(|e| {
+        panic!(
+            "Could not update state in {}: {e}",
+            args.state_dir.display()
+        )
+    });
+    info!("starting with state number {state}");
+
+    let (callbacks, receiver) = mpsc::channel();
+    thread::spawn(|| notify::run_callbacks(receiver));
+
+    let state = StatdState {
+        store: store.clone(),
+        state,
+        monitors: MonitorList::default(),
+        callbacks,
+    };
+    let state_number = state.state;
+
+    let procedures: Vec<Option<RpcProcedure<StatdState>>> = vec![
+        None,
+        Some(stat),
+        Some(mon),
+        Some(unmon),
+        Some(unmon_all),
+        None, // simu_crash
+        Some(notify),
+    ];
+
+    let address = SocketAddr::new(args.address, args.port);
+    let listener =
+        bind_tcp(address).unwrap_or_else(|e| panic!("Could not listen on {address}: {e}"));
+
+    let handle = thread::spawn(move || {
+        let mut server = RpcProgram::new(
+            SM_PROG,
+            SM_VERS::VERSION,
+            SM_VERS::VERSION,
+            procedures,
+            state,
+        );
+        server.set_procedure_names(proc_name);
+        server.run_blocking_tcp_server(listener);
+    });
+
+    if let Err(e) = announce_self(address) {
+        eprintln!("Could not set statd address in RPCBIND server: {e}");
+        return;
+    }
+
+    if let Some(port) = args.capabilities_port {
+        let capabilities = Capabilities::new("statd").limit("max-name-len", SM_MAXSTRLEN.into());
+        let listener = bind_tcp(SocketAddr::new(args.address, port))
+            .unwrap_or_else(|e| panic!("Could not listen on port {port}: {e}"));
+        capabilities::serve(listener, capabilities);
+    }
+
+    if !to_notify.hosts.is_empty() {
+        thread::spawn(move || notify::notify_hosts(store, to_notify, name, state_number));
+    }
+
+    let _ = handle.join();
+}
+
+/// Record a restart: advance the state number, and move the hosts that were monitored before the
+/// restart to the list of hosts to notify. The monitors themselves are dropped, since the lock
+/// manager that asked for them has restarted too.
+fn restart(store: &Store) -> std::io::Result<(i32, NotifyList)> {
+    let state = store.next_state()?;
+
+    let mut to_notify = store.load_notify()?;
+    for mon in store.load_monitors()?.monitors {
+        let host = mon.mon_id.mon_name;
+        if !to_notify
+            .hosts
+            .iter()
+            .any(|h| same_host(&h.mon_name, &host))
+        {
+            to_notify.hosts.push(SmName { mon_name: host });
+        }
+    }
+
+    store.save_notify(&to_notify)?;
+    store.save_monitors(&MonitorList::default())?;
+
+    Ok((state, to_notify))
+}
+
+/// Host names are compared without regard to case, as in DNS.
+fn same_host(a: &OsStr, b: &OsStr) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+fn stat_result(res_stat: SmResult, state: &StatdState) -> RpcResult {
+    let result = SmStatResult {
+        res_stat,
+        state: state.state,
+    };
+
+    RpcResult::Success(result.serialize_alloc())
+}
+
+fn stat(call: &Call, state: &mut StatdState) -> RpcResult {
+    let mut args = SmName::default();
+    let mut arg = call.arg;
+    if args.deserialize(&mut arg).is_err() {
+        return RpcResult::GarbageArgs;
+    }
+
+    if args.mon_name.is_empty() {
+        return stat_result(SmResult::Fail, state);
+    }
+
+    stat_result(SmResult::Success, state)
+}
+
+/// Whether `call` came from this host, over a loopback address.
+fn is_local(call: &Call) -> bool {
+    call.get_peer_address()
+        .is_some_and(|address| address.ip().to_canonical().is_loopback())
+}
+
+fn mon(call: &Call, state: &mut StatdState) -> RpcResult {
+    let mut args = Mon::default();
+    let mut arg = call.arg;
+    if args.deserialize(&mut arg).is_err() {
+        return RpcResult::GarbageArgs;
+    }
+
+    if !is_local(call) {
+        warn!(
+            "SM_MON from {} refused: only local callers may monitor hosts",
+            peer_label(call)
+        );
+        return stat_result(SmResult::Fail, state);
+    }
+
+    let mon_id = &args.mon_id;
+    debug!(
+        "SM_MON: {} for program {} version {} procedure {}",
+        mon_id.mon_name.to_string_lossy(),
+        mon_id.my_id.my_prog,
+        mon_id.my_id.my_vers,
+        mon_id.my_id.my_proc
+    );
+
+    if mon_id.mon_name.is_empty() {
+        return stat_result(SmResult::Fail, state);
+    }
+
+    let mut monitors = state.monitors.clone();
+    let count = monitors.monitors.len();
+    match monitors
+        .monitors
+        .iter_mut()
+        .find(|m| same_host(&m.mon_id.mon_name, &mon_id.mon_name) && m.mon_id.my_id == mon_id.my_id)
+    {
+        Some(existing) => existing.private = args.private,
+        None if count >= MAX_MONITORS => {
+            warn!(
+                "SM_MON of {} refused: already monitoring {MAX_MONITORS} hosts",
+                mon_id.mon_name.to_string_lossy()
+            );
+            return stat_result(SmResult::Fail, state);
+        }
+        None => monitors.monitors.push(args),
+    }
+
+    if let Err(e) = state.store.save_monitors(&monitors) {
+        error!("could not save monitor list: {e}");
+        return stat_result(SmResult::Fail, state);
+    }
+    state.monitors = monitors;
+
+    stat_result(SmResult::Success, state)
+}
+
+/// Stop monitoring the hosts for which `remove` is true, and return the state number.
+fn remove_monitors(state: &mut StatdState, remove: impl Fn(&Mon) -> bool) -> RpcResult {
+    let before = state.monitors.monitors.len();
+    state.monitors.monitors.retain(|m| !remove(m));
+
+    if state.monitors.monitors.len() != before {
+        if let Err(e) = state.store.save_monitors(&state.monitors) {
+            error!("could not save monitor list: {e}");
+        }
+    }
+
+    RpcResult::Success(SmStat { state: state.state }.serialize_alloc())
+}
+
+fn unmon(call: &Call, state: &mut StatdState) -> RpcResult {
+    let mut args = MonId::default();
+    let mut arg = call.arg;
+    if args.deserialize(&mut arg).is_err() {
+        return RpcResult::GarbageArgs;
+    }
+
+    debug!("SM_UNMON: {}", args.mon_name.to_string_lossy());
+
+    if !is_local(call) {
+        warn!("SM_UNMON from {} refused", peer_label(call));
+        return remove_monitors(state, |_| false);
+    }
+
+    remove_monitors(state, |m| {
+        same_host(&m.mon_id.mon_name, &args.mon_name) && m.mon_id.my_id == args.my_id
+    })
+}
+
+fn unmon_all(call: &Call, state: &mut StatdState) -> RpcResult {
+    let mut args = MyId::default();
+    let mut arg = call.arg;
+    if args.deserialize(&mut arg).is_err() {
+        return RpcResult::GarbageArgs;
+    }
+
+    debug!(
+        "SM_UNMON_ALL: program {} version {} procedure {}",
+        args.my_prog, args.my_vers, args.my_proc
+    );
+
+    if !is_local(call) {
+        warn!("SM_UNMON_ALL from {} refused", peer_label(call));
+        return remove_monitors(state, |_| false);
+    }
+
+    remove_monitors(state, |m| m.mon_id.my_id == args)
+}
+
+fn peer_label(call: &Call) -> String {
+    call.get_peer_address()
+        .map_or_else(|| "an unknown peer".to_string(), |a| a.ip().to_string())
+}
+
+/// A monitored host has restarted: call back everyone monitoring it.
+///
+/// The caller's address is not checked against the name it claims, so any host that can reach
+/// statd can trigger the callbacks for any monitored host.
+fn notify(call: &Call, state: &mut StatdState) -> RpcResult {
+    let mut args = StatChange::default();
+    let mut arg = call.arg;
+    if args.deserialize(&mut arg).is_err() {
+        return RpcResult::GarbageArgs;
+    }
+
+    info!(
+        "{} restarted with state number {}",
+        args.mon_name.to_string_lossy(),
+        args.state
+    );
+
+    for mon in state
+        .monitors
+        .monitors
+        .iter()
+        .filter(|m| same_host(&m.mon_id.mon_name, &args.mon_name))
+    {
+        let callback = Callback {
+            my_id: mon.mon_id.my_id.clone(),
+            status: Status {
+                mon_name: args.mon_name.clone(),
+                state: args.state,
+                private: mon.private,
+            },
+        };
+
+        if state.callbacks.send(callback).is_err() {
+            error!("callback thread has exited");
+            return RpcResult::SystemErr;
+        }
+    }
+
+    RpcResult::Success(Vec::new())
+}
+
+/// Tell the RPCBIND server that the status monitor is now running at `address`:
+fn announce_self(address: SocketAddr) -> Result<(), rpc_protocol::Error> {
+    rpcbind::client::set_tcp(
+        SM_PROG,
+        SM_VERS::VERSION,
+        address,
+        rpcbind::RpcbindServerAddress::Tcp("0.0.0.0:111".to_string()),
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rpc_protocol::{callback::Callback as RpcCall, decode_call, OpaqueAuth};
+
+    fn test_state(name: &str) -> (StatdState, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("statd_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (callbacks, _) = mpsc::channel();
+        let state = StatdState {
+            store: Store::new(&dir).unwrap(),
+            state: 1,
+            monitors: MonitorList::default(),
+            callbacks,
+        };
+
+        (state, dir)
+    }
+
+    /// Make an SM_MON call for `host` from `peer`, and return whether it succeeded.
+    fn monitor(state: &mut StatdState, host: &str, peer: &str) -> bool {
+        let args = Mon {
+            mon_id: MonId {
+                mon_name: host.into(),
+                my_id: MyId {
+                    my_name: "localhost".into(),
+                    my_prog: 100021,
+                    my_vers: 4,
+                    my_proc: 16,
+                },
+            },
+            private: [0; SM_PRIVSIZE as usize],
+        };
+        let record = RpcCall {
+            prog: SM_PROG,
+            vers: SM_VERS::VERSION,
+            proc: SM_VERS::SM_MON,
+            cred: OpaqueAuth::none(),
+            arg: args.serialize_alloc(),
+        }
+        .encode(1);
+        let mut call = decode_call(&record[4..]).unwrap();
+        call.set_peer_address(Some(peer.parse().unwrap()));
+
+        let RpcResult::Success(result) = mon(&call, state) else {
+            panic!("SM_MON failed");
+        };
+        let mut res = SmStatResult::default();
+        res.deserialize(&mut result.as_slice()).unwrap();
+
+        res.res_stat == SmResult::Success
+    }
+
+    #[test]
+    fn remote_monitors_are_refused() {
+        let (mut state, dir) = test_state("remote");
+
+        assert!(!monitor(&mut state, "client", "192.0.2.1:700"));
+        assert!(state.monitors.monitors.is_empty());
+        assert!(monitor(&mut state, "client", "127.0.0.1:700"));
+        assert!(monitor(&mut state, "other", "[::ffff:127.0.0.1]:700"));
+        assert_eq!(state.store.load_monitors().unwrap().monitors.len(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn monitors_are_limited() {
+        let (mut state, dir) = test_state("limited");
+
+        for i in 0..MAX_MONITORS {
+            assert!(monitor(&mut state, &format!("host{i}"), "127.0.0.1:700"));
+        }
+        assert!(!monitor(&mut state, "one_more", "127.0.0.1:700"));
+        assert_eq!(state.monitors.monitors.len(), MAX_MONITORS);
+
+        // A host that is already monitored may still be monitored again:
+        assert!(monitor(&mut state, "host0", "127.0.0.1:700"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
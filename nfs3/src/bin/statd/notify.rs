@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The RPC calls that statd makes: SM_NOTIFY calls to the hosts that monitored this one before it
+// restarted, and callbacks to the local lock manager when a monitored host restarts.
+//
+// Both are made from their own threads, so that an unreachable host cannot stall the statd
+// service, and so that the lock manager can call statd while statd is calling it back.
+
+use std::{
+    net::TcpStream,
+    sync::mpsc::Receiver,
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::*;
+
+use nfs3::nsm_proto::{procedures::*, *};
+use rpc_protocol::client::do_rpc_call;
+
+use crate::store::Store;
+
+/// How long to wait for a host to accept a connection or to answer a call.
+const CALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait before first retrying the hosts that could not be notified. The delay doubles
+/// after each attempt, up to MAX_RETRY_DELAY.
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// How long to keep trying to notify a host before giving up. Hosts that were not notified stay in
+/// the notify list, so they are tried again the next time statd starts.
+const NOTIFY_PERIOD: Duration = Duration::from_secs(15 * 60);
+
+/// A call to the lock manager, telling it that a host it monitors has restarted.
+pub struct Callback {
+    pub my_id: MyId,
+    pub status: Status,
+}
+
+/// Tell each host in `list` that this host, which it knows as `name`, has restarted with the new
+/// state number `state`, retrying the hosts that cannot be reached.
+pub fn notify_hosts(store: Store, mut list: NotifyList, name: String, state: i32) {
+    let args = StatChange {
+        mon_name: name.into(),
+        state,
+    }
+    .serialize_alloc();
+
+    let start = Instant::now();
+    let mut delay = FIRST_RETRY_DELAY;
+
+    loop {
+        let before = list.hosts.len();
+        list.hosts.retain(|host| {
+            let host = host.mon_name.to_string_lossy();
+            match call(&host, SM_PROG, SM_VERS::VERSION, SM_VERS::SM_NOTIFY, &args) {
+                Ok(()) => {
+                    info!("notified {host} of restart");
+                    false
+                }
+                Err(e) => {
+                    debug!("could not notify {host} of restart: {e}");
+                    true
+                }
+            }
+        });
+
+        if list.hosts.len() != before {
+            if let Err(e) = store.save_notify(&list) {
+                error!("could not save notify list: {e}");
+            }
+        }
+
+        if list.hosts.is_empty() {
+            return;
+        }
+
+        if start.elapsed() >= NOTIFY_PERIOD {
+            for host in list.hosts.iter() {
+                warn!(
+                    "giving up on notifying {} of restart until statd restarts",
+                    host.mon_name.to_string_lossy()
+                );
+            }
+            return;
+        }
+
+        thread::sleep(delay);
+        delay = (delay * 2).min(MAX_RETRY_DELAY);
+    }
+}
+
+/// Make the callbacks received from `callbacks`, until the sending side is dropped.
+///
+/// The callbacks are always made to the local host, whatever host is named in `my_id`, so that
+/// statd cannot be used to make calls to arbitrary hosts.
+pub fn run_callbacks(callbacks: Receiver<Callback>) {
+    for Callback { my_id, status } in callbacks.iter() {
+        let res = call(
+            "127.0.0.1",
+            my_id.my_prog as u32,
+            my_id.my_vers as u32,
+            my_id.my_proc as u32,
+            &status.serialize_alloc(),
+        );
+
+        if let Err(e) = res {
+            warn!(
+                "could not tell program {} version {} that {} restarted: {e}",
+                my_id.my_prog,
+                my_id.my_vers,
+                status.mon_name.to_string_lossy()
+            );
+        }
+    }
+}
+
+/// Call a procedure of a program on `host`, using the RPCBIND server there to find the program.
+fn call(
+    host: &str,
+    prog: u32,
+    vers: u32,
+    proc: u32,
+    arg: &[u8],
+) -> Result<(), rpc_protocol::Error> {
+    let addr = rpcbind::client::lookup(host, prog, vers, CALL_TIMEOUT)?;
+
+    let mut stream = TcpStream::connect_timeout(&addr, CALL_TIMEOUT)?;
+    stream.set_read_timeout(Some(CALL_TIMEOUT))?;
+    stream.set_write_timeout(Some(CALL_TIMEOUT))?;
+
+    do_rpc_call(&mut stream, prog, vers, proc, arg)?;
+
+    Ok(())
+}
@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The state that statd keeps on disk, so that it survives a restart: the local state number, the
+// list of monitored hosts, and the list of hosts that still have to be told about the last restart.
+//
+// Every file is replaced atomically: it is written to a temporary file which is flushed and then
+// renamed over the old one, so a crash leaves either the old or the new contents in place.
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use nfs3::nsm_proto::*;
+
+const STATE_FILE: &str = "state";
+const MONITOR_FILE: &str = "monitors";
+const NOTIFY_FILE: &str = "notify";
+
+/// The directory holding statd's persistent state.
+#[derive(Clone)]
+pub struct Store {
+    dir: PathBuf,
+}
+
+impl Store {
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// Advance the local state number to the next odd number, which signals that this host has
+    /// restarted and is up, and store it. Returns the new state number.
+    pub fn next_state(&self) -> io::Result<i32> {
+        let path = self.dir.join(STATE_FILE);
+        let state = match fs::read(&path) {
+            Ok(bytes) => match <[u8; 4]>::try_from(bytes.as_slice()) {
+                Ok(bytes) => i32::from_be_bytes(bytes),
+                Err(_) => return Err(invalid_data(&path)),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+
+        let state = if state % 2 == 0 {
+            state.wrapping_add(1)
+        } else {
+            state.wrapping_add(2)
+        };
+        self.write(STATE_FILE, &state.to_be_bytes())?;
+
+        Ok(state)
+    }
+
+    pub fn load_monitors(&self) -> io::Result<MonitorList> {
+        let mut list = MonitorList::default();
+        self.read(MONITOR_FILE, |bytes| list.deserialize(bytes).is_ok())?;
+
+        Ok(list)
+    }
+
+    pub fn save_monitors(&self, list: &MonitorList) -> io::Result<()> {
+        self.write(MONITOR_FILE, &list.serialize_alloc())
+    }
+
+    pub fn load_notify(&self) -> io::Result<NotifyList> {
+        let mut list = NotifyList::default();
+        self.read(NOTIFY_FILE, |bytes| list.deserialize(bytes).is_ok())?;
+
+        Ok(list)
+    }
+
+    pub fn save_notify(&self, list: &NotifyList) -> io::Result<()> {
+        self.write(NOTIFY_FILE, &list.serialize_alloc())
+    }
+
+    /// Decode the contents of file `name` with `decode`, if the file exists.
+    fn read(&self, name: &str, decode: impl FnOnce(&mut &[u8]) -> bool) -> io::Result<()> {
+        let path = self.dir.join(name);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        if !decode(&mut bytes.as_slice()) {
+            return Err(invalid_data(&path));
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, name: &str, contents: &[u8]) -> io::Result<()> {
+        let path = self.dir.join(name);
+        let tmp = self.dir.join(format!("{name}.new"));
+
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+
+        fs::rename(&tmp, &path)?;
+        File::open(&self.dir)?.sync_all()
+    }
+}
+
+fn invalid_data(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} is corrupt", path.display()),
+    )
+}
@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The suite's private capability protocol (suite_proto.x), through which each daemon reports the
+// version of the suite it was built from, its enabled features, and its limits.
+//
+// A daemon describes itself and serves the report on a port of its own:
+//
+//     let capabilities = Capabilities::new("mountd").limit("max-exports", 1);
+//     capabilities::serve(TcpListener::bind("0.0.0.0:20049")?, capabilities);
+//
+// and anything else in the suite can then ask for it:
+//
+//     let capabilities = capabilities::capabilities(&mut TcpStream::connect("host:20049")?)?;
+//
+// This is also the smallest complete example in the suite of a program defined in a .x file, with
+// a client and a server built on the generated code.
+
+#[cfg(feature = "nfs3-server")]
+use std::{net::TcpListener, thread};
+
+#[cfg(feature = "nfs3-client")]
+use std::io::{Read, Write};
+
+#[cfg(any(feature = "nfs3-client", feature = "nfs3-server"))]
+use crate::suite_proto::procedures::*;
+use crate::suite_proto::*;
+
+impl Capabilities {
+    /// The capabilities of `daemon`, built from this version of the suite, with no features or
+    /// limits.
+    pub fn new(daemon: &str) -> Self {
+        Self {
+            daemon: daemon.into(),
+            release: env!("CARGO_PKG_VERSION").into(),
+            features: Vec::new(),
+            limits: Vec::new(),
+        }
+    }
+
+    /// Add `feature` to the enabled features if `enabled` is set.
+    pub fn feature(mut self, feature: &str, enabled: bool) -> Self {
+        if enabled {
+            self.features.push(feature.into());
+        }
+
+        self
+    }
+
+    /// Add a limit.
+    pub fn limit(mut self, name: &str, value: u64) -> Self {
+        self.limits.push(Limit {
+            name: name.into(),
+            value,
+        });
+
+        self
+    }
+
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// The value of the limit called `name`, if the daemon reported one.
+    pub fn get_limit(&self, name: &str) -> Option<u64> {
+        self.limits.iter().find(|l| l.name == name).map(|l| l.value)
+    }
+}
+
+#[cfg(feature = "nfs3-server")]
+fn report(
+    _call: &rpc_protocol::Call,
+    capabilities: &mut Capabilities,
+) -> rpc_protocol::server::RpcResult {
+    rpc_protocol::server::RpcResult::Success(capabilities.serialize_alloc())
+}
+
+/// The RPC program that reports `capabilities`.
+#[cfg(feature = "nfs3-server")]
+pub fn program(capabilities: Capabilities) -> rpc_protocol::server::RpcProgram<Capabilities> {
+    use rpc_protocol::server::*;
+
+    let procedures: Vec<Option<RpcProcedure<Capabilities>>> = vec![None, Some(report)];
+    let mut program = RpcProgram::new(
+        SUITE_PROG,
+        SUITE_VERS::VERSION,
+        SUITE_VERS::VERSION,
+        procedures,
+        capabilities,
+    );
+    program.set_procedure_names(proc_name);
+
+    program
+}
+
+/// Serve `capabilities` to connections on `listener`, from a new thread.
+#[cfg(feature = "nfs3-server")]
+pub fn serve(listener: TcpListener, capabilities: Capabilities) -> thread::JoinHandle<()> {
+    thread::spawn(move || program(capabilities).run_blocking_tcp_server(listener))
+}
+
+/// Call SUITEPROC_CAPABILITIES, and return the capabilities the daemon reported. Returns the raw
+/// XDR encoding of the result as well.
+#[cfg(feature = "nfs3-client")]
+pub fn capabilities<S: Read + Write>(
+    stream: &mut S,
+) -> Result<(Capabilities, Vec<u8>), rpc_protocol::Error> {
+    let raw = rpc_protocol::client::do_rpc_call(
+        stream,
+        SUITE_PROG,
+        SUITE_VERS::VERSION,
+        SUITE_VERS::SUITEPROC_CAPABILITIES,
+        &[],
+    )?;
+
+    let mut capabilities = Capabilities::default();
+    capabilities
+        .deserialize(&mut raw.as_slice())
+        .map_err(|_| rpc_protocol::Error::Protocol(rpc_protocol::ProtocolError::Decode))?;
+
+    Ok((capabilities, raw))
+}
+
+#[cfg(all(test, feature = "nfs3-client", feature = "nfs3-server"))]
+mod tests {
+    use super::*;
+
+    use test_harness::spawn_program;
+
+    #[test]
+    fn report_capabilities() {
+        let reported = Capabilities::new("test")
+            .feature("enabled", true)
+            .feature("disabled", false)
+            .limit("max-io", 1 << 40);
+
+        let mut client = spawn_program(program(reported.clone()));
+
+        let (capabilities, raw) = capabilities(&mut client).unwrap();
+        assert_eq!(capabilities, reported);
+        assert_eq!(raw, reported.serialize_alloc());
+
+        assert_eq!(capabilities.release, env!("CARGO_PKG_VERSION"));
+        assert!(capabilities.has_feature("enabled"));
+        assert!(!capabilities.has_feature("disabled"));
+        assert_eq!(capabilities.get_limit("max-io"), Some(1 << 40));
+        assert_eq!(capabilities.get_limit("max-other"), None);
+    }
+}
@@ -0,0 +1,715 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Which clients may use an export, decided by the address that their calls come from.
+//
+// An export is given a list of clients (`export.clients` in the configuration file, or the CLIENT
+// part of `exportfs CLIENT:/DIR`), each of which is one of:
+//
+//     *                  any client, as with an empty list
+//     10.1.2.3           one address, IPv4 or IPv6
+//     10.1.0.0/16        a network
+//     node17             an alias from the `[clients]` table of the configuration file
+//     node17.example     a host name, which stands for every address (A and AAAA) it resolves to
+//     *.example          a pattern of host names, in which * stands for any characters, dots
+//                        included, and ? for any one character
+//     @cluster           a netgroup, from /etc/netgroup or the name service (see netgroup.rs)
+//
+// An alias names a list of addresses, networks, and host names that make up one logical client.
+// Nodes with several interfaces, such as an InfiniBand interface (IPoIB) and an Ethernet one, may
+// mount through either, so an alias lists them all:
+//
+//     [clients]
+//     node17 = ["10.1.0.17", "10.2.0.17", "node17-ib"]
+//
+// Host names are resolved through a `Resolver`, which keeps each answer for `export.name_ttl`
+// seconds, when the exports are set up and again once the answer expires. A client matches a
+// pattern if its address reverse resolves to a name that fits the pattern, and that name resolves
+// back to the address. A name that does not resolve is logged and left out, so that the clients it
+// would have admitted are refused until it resolves. A client is in a netgroup if its name, or its
+// address if it has no name, is a member of the group.
+//
+// An entry may end in a squash option in parentheses, "root_squash", "no_root_squash", or
+// "all_squash", which the NFS server applies to the callers of the clients it matches in place of
+// `auth.squash`. The first entry that matches a client decides, as in an exports(5) file, so a few
+// admin hosts can be trusted with root while everyone else is squashed:
+//
+//     clients = ["admin1(no_root_squash)", "10.1.0.0/16(root_squash)"]
+//
+// An entry that matches no client that an earlier entry does not already match is shadowed, and
+// never decides anything; it is logged as a warning when the exports are set up.
+//
+//     let resolver = config.export.resolver();
+//     let clients = Clients::resolve_with(&export.clients, &config.clients, &resolver)?;
+//     if !clients.allows(address.ip()) {
+//         // refuse the call
+//     }
+
+use std::{collections::BTreeMap, fmt, net::IpAddr, str::FromStr, sync::Arc};
+
+use log::*;
+
+use crate::{config::Squash, resolver::Resolver};
+
+/// The aliases of the `[clients]` table of the configuration file: each name, and the entries it
+/// stands for.
+pub type Aliases = BTreeMap<String, Vec<String>>;
+
+/// One entry of a list of clients, as written.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClientSpec {
+    /// Any client.
+    Any,
+
+    /// The addresses of a network, or a single address.
+    Network(Network),
+
+    /// An alias or a host name.
+    Name(String),
+
+    /// A pattern of host names, with the wildcards * and ?, in lower case.
+    Pattern(String),
+
+    /// A netgroup, written with a leading "@".
+    Netgroup(String),
+}
+
+impl FromStr for ClientSpec {
+    type Err = String;
+
+    fn from_str(entry: &str) -> Result<Self, String> {
+        if entry == "*" {
+            return Ok(Self::Any);
+        }
+
+        if entry.contains(['/', ':']) || entry.parse::<IpAddr>().is_ok() {
+            return entry.parse().map(Self::Network);
+        }
+
+        if let Some(netgroup) = entry.strip_prefix('@') {
+            let valid = |c: char| c.is_ascii_graphic() && !matches!(c, '(' | ')' | ',');
+            if netgroup.is_empty() || !netgroup.chars().all(valid) {
+                return Err(format!("\"{entry}\" is not a netgroup"));
+            }
+
+            return Ok(Self::Netgroup(netgroup.to_string()));
+        }
+
+        let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '*' | '?');
+        if entry.is_empty() || !entry.chars().all(valid) {
+            return Err(format!(
+                "\"{entry}\" is not an address, a network, a name, a pattern, or a netgroup"
+            ));
+        }
+
+        if entry.contains(['*', '?']) {
+            return Ok(Self::Pattern(entry.to_ascii_lowercase()));
+        }
+
+        Ok(Self::Name(entry.to_string()))
+    }
+}
+
+/// One entry of the clients of an export, as written: the clients, and the squash option that
+/// applies to them, if any.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientEntry {
+    pub spec: ClientSpec,
+    pub squash: Option<Squash>,
+}
+
+impl FromStr for ClientEntry {
+    type Err = String;
+
+    fn from_str(entry: &str) -> Result<Self, String> {
+        let Some((spec, option)) = entry.strip_suffix(')').and_then(|e| e.split_once('(')) else {
+            return Ok(Self {
+                spec: entry.parse()?,
+                squash: None,
+            });
+        };
+
+        let squash = match option {
+            "root_squash" => Squash::Root,
+            "no_root_squash" => Squash::None,
+            "all_squash" => Squash::All,
+            _ => {
+                return Err(format!(
+                    "\"{option}\" is not root_squash, no_root_squash, or all_squash"
+                ))
+            }
+        };
+
+        Ok(Self {
+            spec: spec.parse()?,
+            squash: Some(squash),
+        })
+    }
+}
+
+/// The addresses that share their first `prefix` bits with `address`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Network {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    /// The network of just `address`.
+    pub fn host(address: IpAddr) -> Self {
+        let address = address.to_canonical();
+        let prefix = if address.is_ipv4() { 32 } else { 128 };
+
+        Self { address, prefix }
+    }
+
+    /// Whether `address` is in the network. IPv4 addresses mapped into IPv6 (::ffff:a.b.c.d), as a
+    /// dual-stack socket reports IPv4 peers, are treated as the IPv4 addresses they map.
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.address, address.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                (u32::from(network) ^ u32::from(address)) & mask == 0
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                (u128::from(network) ^ u128::from(address)) & mask == 0
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether every address of `other` is in the network.
+    fn covers(&self, other: &Network) -> bool {
+        self.prefix <= other.prefix && self.contains(other.address)
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    /// Parse an address, or a network written as an address and a prefix length, such as
+    /// "10.1.0.0/16" or "fd00::/8".
+    fn from_str(text: &str) -> Result<Self, String> {
+        let (address, prefix) = match text.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (text, None),
+        };
+        let address: IpAddr = address
+            .parse()
+            .map_err(|_| format!("\"{address}\" is not an IP address"))?;
+
+        let host = Self::host(address);
+        let Some(prefix) = prefix else {
+            return Ok(host);
+        };
+        // A mapped IPv4 address was made canonical, which shortens its prefix by 96 bits:
+        let max = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix
+            .parse::<u8>()
+            .ok()
+            .filter(|&prefix| prefix <= max)
+            .ok_or_else(|| format!("\"{prefix}\" is not a prefix length from 0 to {max}"))?;
+        let prefix = prefix - (max - host.prefix).min(prefix);
+
+        Ok(Self { prefix, ..host })
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+/// Check that the entries of a list of clients can be parsed, and, for the members of an alias, that
+/// none of them names one of `aliases`, or has a squash option. Returns the index of the first bad
+/// entry, and what is wrong with it.
+pub fn check(entries: &[String], aliases: Option<&Aliases>) -> Result<(), (usize, String)> {
+    for (i, entry) in entries.iter().enumerate() {
+        let spec = match aliases {
+            Some(_) => entry.parse::<ClientSpec>(),
+            None => entry.parse::<ClientEntry>().map(|e| e.spec),
+        }
+        .map_err(|e| (i, e))?;
+        if let (ClientSpec::Name(name), Some(aliases)) = (spec, aliases) {
+            if aliases.contains_key(&name) {
+                return Err((
+                    i,
+                    format!("\"{name}\" is an alias, which cannot name another"),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The clients that may use an export.
+#[derive(Clone, Debug, Default)]
+pub struct Clients {
+    /// The entries as they were given, to be reported.
+    entries: Vec<String>,
+
+    /// What each entry expanded to, in order. Any client may use the export if there are none.
+    rules: Vec<Rule>,
+
+    /// Resolves the host names and patterns of the rules.
+    resolver: Arc<Resolver>,
+}
+
+/// The clients that one entry expanded to, and its squash option.
+#[derive(Clone, Debug, Default)]
+struct Rule {
+    /// Whether any client matches.
+    any: bool,
+
+    networks: Vec<Network>,
+
+    /// Host names, which match the addresses they resolve to.
+    names: Vec<String>,
+
+    /// Patterns of host names, which match the clients whose names fit them.
+    patterns: Vec<String>,
+
+    /// Netgroups, which match the clients that are members.
+    netgroups: Vec<String>,
+
+    squash: Option<Squash>,
+}
+
+impl Rule {
+    fn matches(&self, address: IpAddr, resolver: &Resolver) -> bool {
+        let address = address.to_canonical();
+
+        self.any
+            || self.networks.iter().any(|n| n.contains(address))
+            || self
+                .names
+                .iter()
+                .any(|name| resolver.addresses(name).contains(&address))
+            || (!self.patterns.is_empty()
+                && resolver
+                    .name(address)
+                    .is_some_and(|name| self.patterns.iter().any(|pattern| fits(&name, pattern))))
+            || self
+                .netgroups
+                .iter()
+                .any(|netgroup| resolver.in_netgroup(netgroup, address))
+    }
+
+    /// The networks of the rule and the addresses of its names, as they resolve now.
+    fn resolved(&self, resolver: &Resolver) -> Vec<Network> {
+        let addresses = self.names.iter().flat_map(|name| resolver.addresses(name));
+
+        self.networks
+            .iter()
+            .copied()
+            .chain(addresses.map(Network::host))
+            .collect()
+    }
+
+    /// Whether every client of `other` is a client of this rule too, as the names of both resolve
+    /// now. A pattern or netgroup is only covered by the same one, and a rule with a name that does not
+    /// resolve, or that matches no clients, is not covered by anything, since the names it failed
+    /// to resolve may resolve later.
+    fn covers(&self, other: &Rule, resolver: &Resolver) -> bool {
+        if self.any {
+            return true;
+        }
+        if other.any || other.names.iter().any(|n| resolver.addresses(n).is_empty()) {
+            return false;
+        }
+
+        let mine = self.resolved(resolver);
+        let theirs = other.resolved(resolver);
+        (!theirs.is_empty() || !other.patterns.is_empty() || !other.netgroups.is_empty())
+            && theirs.iter().all(|t| mine.iter().any(|m| m.covers(t)))
+            && other.patterns.iter().all(|p| self.patterns.contains(p))
+            && other.netgroups.iter().all(|g| self.netgroups.contains(g))
+    }
+}
+
+/// Whether the host `name` fits `pattern`, in which * stands for any characters, including none,
+/// and ? for any one character.
+fn fits(name: &str, pattern: &str) -> bool {
+    let (name, pattern) = (name.as_bytes(), pattern.as_bytes());
+    // Where the last * was in the pattern, and where in the name it has matched up to:
+    let mut star = None;
+    let (mut n, mut p) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c.eq_ignore_ascii_case(&name[n]) => {
+                n += 1;
+                p += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    n = star_n + 1;
+                    p = star_p + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+impl Clients {
+    /// Resolve a list of entries, expanding the aliases in `aliases` and looking up the host names
+    /// with a resolver of their own. Fails if an entry cannot be parsed. Entries shadowed by earlier
+    /// ones are logged.
+    pub fn resolve(entries: &[String], aliases: &Aliases) -> Result<Self, String> {
+        Self::resolve_with(entries, aliases, &Arc::default())
+    }
+
+    /// Resolve a list of entries as `resolve` does, with `resolver`, which looks the host names up
+    /// again as its answers expire, and may be shared with the clients of other exports.
+    pub fn resolve_with(
+        entries: &[String],
+        aliases: &Aliases,
+        resolver: &Arc<Resolver>,
+    ) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for entry in entries {
+            let ClientEntry { spec, squash } = entry.parse()?;
+            let mut rule = Rule {
+                squash,
+                ..Rule::default()
+            };
+            let mut add = |spec: ClientSpec| match spec {
+                ClientSpec::Any => rule.any = true,
+                ClientSpec::Network(network) => rule.networks.push(network),
+                ClientSpec::Name(host) => {
+                    resolver.addresses(&host);
+                    rule.names.push(host);
+                }
+                ClientSpec::Pattern(pattern) => rule.patterns.push(pattern),
+                ClientSpec::Netgroup(netgroup) => rule.netgroups.push(netgroup),
+            };
+            match spec {
+                ClientSpec::Name(name) if aliases.contains_key(&name) => {
+                    for member in &aliases[&name] {
+                        add(member.parse()?);
+                    }
+                }
+                spec => add(spec),
+            }
+            rules.push(rule);
+        }
+
+        let clients = Self {
+            entries: entries.to_vec(),
+            rules,
+            resolver: resolver.clone(),
+        };
+        for (shadowed, by) in clients.shadowed() {
+            warn!(
+                "Client \"{}\" is shadowed by \"{}\", which comes before it and matches every \
+                 client it does",
+                clients.entries[shadowed], clients.entries[by]
+            );
+        }
+
+        Ok(clients)
+    }
+
+    /// Whether a client at `address` may use the export.
+    pub fn allows(&self, address: IpAddr) -> bool {
+        self.rules.is_empty()
+            || self
+                .rules
+                .iter()
+                .any(|r| r.matches(address, &self.resolver))
+    }
+
+    /// Whether any client may use the export.
+    pub fn allows_any(&self) -> bool {
+        self.rules.is_empty() || self.rules.iter().any(|r| r.any)
+    }
+
+    /// The squash option of the first entry that matches a client at `address`, if it has one.
+    pub fn squash(&self, address: IpAddr) -> Option<Squash> {
+        self.rules
+            .iter()
+            .find(|r| r.matches(address, &self.resolver))?
+            .squash
+    }
+
+    /// The entries that can never match a client first, since an earlier entry matches every client
+    /// that they do, each with the index of the first such earlier entry.
+    pub fn shadowed(&self) -> Vec<(usize, usize)> {
+        self.rules
+            .iter()
+            .enumerate()
+            .filter_map(|(i, rule)| {
+                let by = self.rules[..i]
+                    .iter()
+                    .position(|r| r.covers(rule, &self.resolver))?;
+                Some((i, by))
+            })
+            .collect()
+    }
+
+    /// The entries as they were given.
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// The clients of the entries, without their squash options, as EXPORT lists them.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .map(|e| e.split_once('(').map_or(e.as_str(), |(name, _)| name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::netgroup::NetgroupFile;
+
+    fn clients(entries: &[&str], aliases: &Aliases) -> Clients {
+        let entries: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+        Clients::resolve(&entries, aliases).unwrap()
+    }
+
+    fn ip(text: &str) -> IpAddr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn networks() {
+        let network: Network = "10.1.0.0/16".parse().unwrap();
+        assert!(network.contains(ip("10.1.200.3")));
+        assert!(network.contains(ip("::ffff:10.1.0.1")));
+        assert!(!network.contains(ip("10.2.0.1")));
+        assert!(!network.contains(ip("::1")));
+
+        let network: Network = "fd00::/8".parse().unwrap();
+        assert!(network.contains(ip("fd12::1")));
+        assert!(!network.contains(ip("fe80::1")));
+
+        let mapped: Network = "::ffff:192.168.0.0/112".parse().unwrap();
+        assert_eq!(mapped, "192.168.0.0/16".parse().unwrap());
+        assert!("0.0.0.0/0"
+            .parse::<Network>()
+            .unwrap()
+            .contains(ip("8.8.8.8")));
+
+        assert!("10.0.0.0/33".parse::<Network>().is_err());
+        assert!("10.0.0/8".parse::<Network>().is_err());
+    }
+
+    #[test]
+    fn entries() {
+        assert_eq!("*".parse(), Ok(ClientSpec::Any));
+        assert_eq!(
+            "node-1.example".parse(),
+            Ok(ClientSpec::Name("node-1.example".into()))
+        );
+        assert!(matches!("fe80::1".parse(), Ok(ClientSpec::Network(_))));
+        assert_eq!(
+            "*.Cluster.example".parse(),
+            Ok(ClientSpec::Pattern("*.cluster.example".into()))
+        );
+        assert_eq!(
+            "@compute-nodes".parse(),
+            Ok(ClientSpec::Netgroup("compute-nodes".into()))
+        );
+        assert!("@".parse::<ClientSpec>().is_err());
+        assert!("node 1".parse::<ClientSpec>().is_err());
+        assert!("".parse::<ClientSpec>().is_err());
+
+        let aliases = Aliases::from([("node".into(), vec!["10.0.0.1".into()])]);
+        let entries = ["10.0.0.0/8".to_string(), "node".to_string()];
+        assert!(check(&entries, None).is_ok());
+        assert_eq!(check(&entries[1..], Some(&aliases)).unwrap_err().0, 0);
+    }
+
+    #[test]
+    fn matching() {
+        let none = Aliases::new();
+        assert!(clients(&[], &none).allows(ip("192.0.2.1")));
+        assert!(clients(&["10.0.0.0/8", "*"], &none).allows(ip("192.0.2.1")));
+
+        let subnet = clients(&["10.0.0.0/8", "2001:db8::5"], &none);
+        assert!(subnet.allows(ip("10.9.8.7")));
+        assert!(subnet.allows(ip("2001:db8::5")));
+        assert!(!subnet.allows(ip("2001:db8::6")));
+        assert!(!subnet.allows_any());
+
+        // A logical client with an address on each of its networks, and a name for a third:
+        let aliases = Aliases::from([(
+            "node17".into(),
+            vec!["10.1.0.17".into(), "10.2.0.17".into(), "localhost".into()],
+        )]);
+        let node = clients(&["node17"], &aliases);
+        assert!(node.allows(ip("10.1.0.17")));
+        assert!(node.allows(ip("10.2.0.17")));
+        assert!(node.allows(ip("127.0.0.1")));
+        assert!(!node.allows(ip("10.1.0.18")));
+        assert_eq!(node.entries(), ["node17"]);
+
+        // A name that does not resolve admits no one:
+        assert!(!clients(&["no-such-host.invalid"], &none).allows(ip("127.0.0.1")));
+    }
+
+    #[test]
+    fn squash_options() {
+        let entry: ClientEntry = "admin1(no_root_squash)".parse().unwrap();
+        assert_eq!(entry.spec, ClientSpec::Name("admin1".into()));
+        assert_eq!(entry.squash, Some(Squash::None));
+        assert_eq!(
+            "*(all_squash)".parse::<ClientEntry>().unwrap().squash,
+            Some(Squash::All)
+        );
+        assert_eq!("10.0.0.0/8".parse::<ClientEntry>().unwrap().squash, None);
+        assert!("admin1(rw)".parse::<ClientEntry>().is_err());
+
+        // Options belong to the entries of an export, not to the members of an alias:
+        let entries = ["admin1(no_root_squash)".to_string()];
+        assert!(check(&entries, None).is_ok());
+        assert!(check(&entries, Some(&Aliases::new())).is_err());
+
+        // The first entry that matches decides:
+        let none = Aliases::new();
+        let admins = clients(
+            &["10.0.0.5(no_root_squash)", "10.0.0.0/16(root_squash)", "*"],
+            &none,
+        );
+        assert_eq!(admins.squash(ip("10.0.0.5")), Some(Squash::None));
+        assert_eq!(admins.squash(ip("10.0.0.6")), Some(Squash::Root));
+        assert_eq!(admins.squash(ip("192.0.2.1")), None);
+        assert!(admins.shadowed().is_empty());
+        assert_eq!(
+            admins.names().collect::<Vec<_>>(),
+            ["10.0.0.5", "10.0.0.0/16", "*"]
+        );
+    }
+
+    #[test]
+    fn shadowing() {
+        let aliases =
+            Aliases::from([("admins".into(), vec!["10.0.0.5".into(), "10.1.0.5".into()])]);
+        let shadowed = clients(
+            &[
+                "10.0.0.0/16(root_squash)",
+                "admins(no_root_squash)",
+                "10.0.0.5",
+                "no-such-host.invalid",
+                "*",
+                "fd00::1",
+            ],
+            &aliases,
+        );
+        // admins is not, since 10.1.0.5 is outside 10.0.0.0/16, and a name that did not resolve
+        // never is:
+        assert_eq!(shadowed.shadowed(), [(2, 0), (5, 4)]);
+        assert_eq!(shadowed.squash(ip("10.0.0.5")), Some(Squash::Root));
+        assert_eq!(shadowed.squash(ip("10.1.0.5")), Some(Squash::None));
+    }
+
+    fn forward(name: &str) -> std::io::Result<Vec<IpAddr>> {
+        match name {
+            "login1.cluster.example" => Ok(vec![ip("10.0.0.1")]),
+            "node7.rack2.cluster.example" => Ok(vec![ip("10.0.2.7")]),
+            "spoofed.cluster.example" => Ok(vec![ip("10.9.9.9")]),
+            _ => Err(std::io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn reverse(address: IpAddr) -> std::io::Result<Option<String>> {
+        Ok(match address.to_string().as_str() {
+            "10.0.0.1" => Some("login1.cluster.example".into()),
+            "10.0.2.7" => Some("node7.rack2.cluster.example".into()),
+            "10.0.3.3" => Some("spoofed.cluster.example".into()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(fits("node7.rack2.cluster.example", "*.cluster.example"));
+        assert!(fits("Login1.Cluster.example", "login?.cluster.example"));
+        assert!(fits("login1", "*"));
+        assert!(!fits("login12.cluster.example", "login?.cluster.example"));
+        assert!(!fits("cluster.example", "*.cluster.example"));
+        assert!(!fits("login1.cluster.example.org", "*.cluster.example"));
+
+        let resolver = Arc::new(Resolver::with_lookups(
+            std::time::Duration::from_secs(60),
+            forward,
+            reverse,
+        ));
+        let entries: Vec<String> = [
+            "login?.cluster.example(no_root_squash)",
+            "*.cluster.example",
+        ]
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+        let clients = Clients::resolve_with(&entries, &Aliases::new(), &resolver).unwrap();
+        assert_eq!(clients.squash(ip("10.0.0.1")), Some(Squash::None));
+        assert!(clients.allows(ip("::ffff:10.0.2.7")));
+        assert_eq!(clients.squash(ip("10.0.2.7")), None);
+        // A name that does not resolve back to the client, or no name at all, matches nothing:
+        assert!(!clients.allows(ip("10.0.3.3")));
+        assert!(!clients.allows(ip("10.0.4.4")));
+        assert!(!clients.allows_any());
+
+        let entries: Vec<String> = [
+            "*.cluster.example",
+            "login1.cluster.example",
+            "*.cluster.example",
+        ]
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+        let clients = Clients::resolve_with(&entries, &Aliases::new(), &resolver).unwrap();
+        assert!(clients.allows(ip("10.0.0.1")));
+        // A name is not known to fit a pattern until a client asks, but a repeated pattern is:
+        assert_eq!(clients.shadowed(), [(2, 0)]);
+    }
+
+    #[test]
+    fn netgroups() {
+        let path = std::env::temp_dir().join(format!("clients_netgroup_{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "login (login1.cluster.example,,)\ncompute (node7.rack2.cluster.example,,)\n\
+             cluster login compute\nunnamed (10.0.4.4,,)\n",
+        )
+        .unwrap();
+        let resolver = Arc::new(
+            Resolver::with_lookups(std::time::Duration::from_secs(60), forward, reverse)
+                .with_netgroups(NetgroupFile::new(&path)),
+        );
+
+        let entries: Vec<String> = ["@login(no_root_squash)", "@cluster", "@unnamed", "@login"]
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        let clients = Clients::resolve_with(&entries, &Aliases::new(), &resolver).unwrap();
+        assert_eq!(clients.squash(ip("10.0.0.1")), Some(Squash::None));
+        assert!(clients.allows(ip("10.0.2.7")));
+        assert_eq!(clients.squash(ip("10.0.2.7")), None);
+        // A client without a name is looked up by its address:
+        assert!(clients.allows(ip("10.0.4.4")));
+        assert!(!clients.allows(ip("10.0.3.3")));
+        assert_eq!(clients.shadowed(), [(3, 0)]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,1058 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The configuration file shared by nfs_server and mountd, given to either with `--config`.
+//
+// The file is TOML. Every key is optional, and a missing key keeps the default that the daemons use
+// without a configuration file; command line flags override the file. Both daemons can read the
+// same file, which keeps the export and the file handle key the same for each. For example:
+//
+//     [log]
+//     level = "debug"
+//
+//     [export]
+//     path = "/srv/nfs"
+//     handle_key = "/etc/nfs/handle_key"
+//     read_only = true
+//     cookie_verifier = "mtime"
+//     read_method = "pread"
+//     clients = ["admin1(no_root_squash)", "10.1.0.0/16", "login1", "*.cluster.example"]
+//     name_ttl = 300
+//     netgroups = "file"
+//     netgroup_file = "/etc/netgroup"
+//     snapshots = "/srv/nfs/.zfs/snapshot"
+//
+//     [clients]
+//     login1 = ["10.1.0.5", "10.2.0.5", "login1-ib.example"]
+//
+//     [auth]
+//     flavors = ["sys"]
+//     squash = "all"
+//
+//     [nfs_server]
+//     listen = "[::]:2049"
+//     control_socket = "/run/nfs3/nfs_server.sock"
+//     control_group = "nfsadmin"
+//     idle_timeout = 600
+//     keepalive = 60
+//
+//     [mountd]
+//     control_socket = "/run/nfs3/mountd.sock"
+//     control_group = "nfsadmin"
+//
+// Errors name the offending key, as in "nfs_server.ring_entries: must be a power of two".
+//
+// Sending either daemon SIGHUP makes it read the file again and switch to the `[export]` and
+// `[clients]` sections it finds there, without dropping client connections. File handles of a
+// directory that is no longer exported become stale, and exports added with exportfs are dropped.
+// The log level in `[log]` is switched to as well, unless RUST_LOG is set. The other sections are
+// only read at startup.
+
+use std::{
+    fmt, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, OnceLock},
+    thread,
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use log::*;
+use nix::sys::signal::{SigSet, SigmaskHow, Signal};
+use rpc_protocol::{
+    server::{ConnectionTimeouts, PeerPolicy},
+    throttle::Limits,
+    trace::{TraceFormat, Tracer},
+    AuthFlavor,
+};
+use serde::Deserialize;
+use tracing_subscriber::{prelude::*, reload, EnvFilter, Registry};
+
+use crate::{
+    clients::{self, Aliases},
+    file_handle::DEFAULT_RETENTION,
+    netgroup::{NetgroupFile, SystemNetgroups},
+    resolver::{self, Resolver},
+};
+
+/// The uid and gid of the anonymous user, "nobody", by default.
+pub const DEFAULT_ANON_ID: u32 = 65534;
+
+/// The most submission queue entries an io_uring may have.
+const MAX_RING_ENTRIES: u32 = 32768;
+
+/// The smallest limit on the size of calls, which leaves room for the largest call header and a
+/// path of the longest length that MNT takes.
+const MIN_RECORD: u32 = 4096;
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub log: LogConfig,
+    pub export: ExportConfig,
+
+    /// Aliases for the clients of exports, each standing for a list of addresses, networks, and
+    /// host names that make up one client (see `nfs3::clients`).
+    pub clients: Aliases,
+
+    pub auth: AuthConfig,
+    pub nfs_server: NfsServerConfig,
+    pub mountd: MountdConfig,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// The level to log at, such as "info" or "debug". The RUST_LOG environment variable, if set,
+    /// takes precedence.
+    pub level: String,
+}
+
+/// Replaces the filter of the log that `LogConfig::init()` started.
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+impl LogConfig {
+    /// Start logging at the configured level, to stderr.
+    ///
+    /// A server logs what happens while it handles a call in a span of the call, so each line says
+    /// which call it is of, with its XID, procedure, and client (see `rpc_protocol::server`).
+    pub fn init(&self) {
+        let (filter, handle) = reload::Layer::new(self.filter());
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(tracing_subscriber::fmt::layer().with_writer(io::stderr))
+            .init();
+        let _ = LOG_FILTER.set(handle);
+    }
+
+    /// Switch to the configured level, if `init()` started logging and RUST_LOG is not set.
+    pub fn reload(&self) {
+        let Some(handle) = LOG_FILTER.get() else {
+            return;
+        };
+        if std::env::var_os(EnvFilter::DEFAULT_ENV).is_some() {
+            return;
+        }
+
+        match handle.reload(self.filter()) {
+            Ok(()) => info!("Logging at level {}", self.level),
+            Err(e) => error!("Could not switch to the log level {}: {e}", self.level),
+        }
+    }
+
+    /// The filter of RUST_LOG, if set, or of the configured level.
+    fn filter(&self) -> EnvFilter {
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&self.level))
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "error".into(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportConfig {
+    /// The directory to export.
+    pub path: PathBuf,
+
+    /// The file holding the key that protects file handles.
+    pub handle_key: PathBuf,
+
+    /// How many seconds a file handle must go unused before the NFS server may forget the path of
+    /// its file, if the file has been removed.
+    pub handle_retention: u64,
+
+    /// Whether operations that would change the export fail with NFS3ERR_ROFS.
+    pub read_only: bool,
+
+    pub cookie_verifier: CookieVerifier,
+
+    pub read_method: ReadMethod,
+
+    /// The clients that may mount and use the export, by address, network, alias, host name, or
+    /// pattern of host names, each with an optional squash option such as
+    /// "admin1(no_root_squash)". Any client may if there are none.
+    pub clients: Vec<String>,
+
+    /// How many seconds the host names of clients, and the names of client addresses, are kept
+    /// once looked up.
+    pub name_ttl: u64,
+
+    /// Where the netgroups of clients given as "@group" are looked up.
+    pub netgroups: NetgroupSource,
+
+    /// The netgroup file, if netgroups are looked up in a file.
+    pub netgroup_file: PathBuf,
+
+    /// A directory of snapshots of the export, such as `.zfs/snapshot` or the mount points of LVM
+    /// snapshots, which the NFS server shows read-only as `.snapshot` in the root of the export.
+    pub snapshots: Option<PathBuf>,
+
+    /// The most calls per second that the NFS server answers for the export, from every client
+    /// together. There is no limit by default.
+    pub ops_per_sec: Option<u32>,
+
+    /// The most bytes of calls and replies per second that the NFS server moves for the export,
+    /// from every client together. There is no limit by default.
+    pub bytes_per_sec: Option<u64>,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            path: "/test/nfs/export".into(),
+            handle_key: "/test/nfs/handle_key".into(),
+            handle_retention: DEFAULT_RETENTION.as_secs(),
+            read_only: false,
+            cookie_verifier: CookieVerifier::default(),
+            read_method: ReadMethod::default(),
+            clients: Vec::new(),
+            name_ttl: resolver::DEFAULT_TTL.as_secs(),
+            netgroups: NetgroupSource::default(),
+            netgroup_file: resolver::DEFAULT_NETGROUP_FILE.into(),
+            snapshots: None,
+            ops_per_sec: None,
+            bytes_per_sec: None,
+        }
+    }
+}
+
+impl ExportConfig {
+    /// A resolver for the host names and netgroups of the clients, which keeps its answers for
+    /// `name_ttl`.
+    pub fn resolver(&self) -> Arc<Resolver> {
+        let resolver = Resolver::new(Duration::from_secs(self.name_ttl));
+        let resolver = match self.netgroups {
+            NetgroupSource::File => resolver.with_netgroups(NetgroupFile::new(&self.netgroup_file)),
+            NetgroupSource::Nss => resolver.with_netgroups(SystemNetgroups),
+        };
+
+        Arc::new(resolver)
+    }
+
+    /// The limits on the calls for the export, from every client together.
+    pub fn limits(&self) -> Limits {
+        Limits {
+            ops_per_sec: self.ops_per_sec,
+            bytes_per_sec: self.bytes_per_sec,
+        }
+    }
+}
+
+/// How the NFS server chooses the cookie verifiers of directory listings. A client continues a
+/// listing by sending back the cookie and verifier of the last READDIR or READDIRPLUS reply, and the
+/// server fails the call with NFS3ERR_BAD_COOKIE if the verifier no longer matches the directory,
+/// after which the client must start the listing over.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CookieVerifier {
+    /// Derived from the directory's modification and change times, so any change to the directory
+    /// ends the listings in progress. Strict, and cheap, but a large directory that changes often
+    /// may never be listed to the end.
+    #[default]
+    Mtime,
+
+    /// Derived from the names and file ids of the directory's entries, so changes that leave the
+    /// entries as they were (a file created and removed again, or new timestamps) do not end the
+    /// listings in progress. The whole directory is read to compute it, once after each change.
+    Entries,
+
+    /// Always zero, and never checked, as in the Linux server: listings are never ended, but one
+    /// that continues across a change may skip or repeat entries. For clients that send back a
+    /// zero verifier, or that do not start over after NFS3ERR_BAD_COOKIE.
+    Zero,
+}
+
+/// How the NFS server reads the data of a READ from a file of the export, when it reads it into the
+/// reply. Data that the server splices or sends from registered buffers (see `ReadReplies`) is
+/// read by the ring instead.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadMethod {
+    /// With pread(2).
+    #[default]
+    Pread,
+
+    /// By mapping the range read into memory, with advice to read it ahead, and copying it out of
+    /// the mapping. Files that cannot be mapped, and files truncated while they are being read
+    /// this way, are read with pread(2).
+    Mmap,
+}
+
+/// Where netgroups are looked up.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NetgroupSource {
+    /// In the netgroup(5) file `export.netgroup_file`.
+    #[default]
+    File,
+
+    /// Through the name service switch, with innetgr(3), which looks in the sources that
+    /// nsswitch.conf(5) lists for netgroups, such as NIS or LDAP. Only with the GNU C library.
+    Nss,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// The credential flavors the NFS server accepts, in the order mountd advertises them.
+    pub flavors: Vec<Flavor>,
+
+    pub squash: Squash,
+    pub anon_uid: u32,
+    pub anon_gid: u32,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            flavors: vec![Flavor::Sys, Flavor::None],
+            squash: Squash::Root,
+            anon_uid: DEFAULT_ANON_ID,
+            anon_gid: DEFAULT_ANON_ID,
+        }
+    }
+}
+
+/// A credential flavor that can be accepted.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Flavor {
+    /// AUTH_NONE: the caller is anonymous.
+    None,
+
+    /// AUTH_SYS: the caller gives its user and group ids.
+    Sys,
+}
+
+impl Flavor {
+    /// The number of the flavor, as in the MNT result.
+    pub fn number(&self) -> i32 {
+        match self {
+            Self::None => 0,
+            Self::Sys => 1,
+        }
+    }
+
+    pub fn matches(&self, flavor: &AuthFlavor) -> bool {
+        matches!(
+            (self, flavor),
+            (Self::None, AuthFlavor::None) | (Self::Sys, AuthFlavor::Sys)
+        )
+    }
+}
+
+/// Which callers are mapped to the anonymous user.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Squash {
+    /// Only callers without an AUTH_SYS credential.
+    None,
+
+    /// Those, and the root user (uid 0), whose group 0 is also squashed.
+    Root,
+
+    /// Every caller.
+    All,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NfsServerConfig {
+    /// The address to listen on, IPv4 or IPv6.
+    pub listen: SocketAddr,
+
+    /// Whether the data of stable WRITEs and COMMITs is read back and checked before replying.
+    pub verify_writes: bool,
+
+    /// What is done with the digests that clients send with the data of WRITEs.
+    pub integrity: Integrity,
+
+    /// Whether each reply is checked for encoding errors, such as padding that is not zero, before
+    /// it is sent.
+    pub check_replies: bool,
+
+    /// Whether MKNOD may make FIFOs, UNIX domain sockets, and, for callers mapped to root, device
+    /// files. Otherwise it fails with NFS3ERR_NOTSUPP.
+    pub mknod: bool,
+
+    /// How READ replies send the data of the file.
+    pub read_replies: ReadReplies,
+
+    /// Where the files of the export are kept.
+    pub backend: Backend,
+
+    /// How many local files READ, WRITE, and COMMIT may keep open between calls. 0 opens every file
+    /// for each call.
+    pub fd_cache: usize,
+
+    /// How many milliseconds GETATTR may reply with the attributes of a file that it replied with
+    /// before, unless the file was changed through the server. 0, the default, caches none.
+    pub attribute_ttl: u64,
+
+    /// The number of submission queue entries in the server's io_uring.
+    pub ring_entries: u32,
+
+    /// About how many bytes to give the buffers that calls are received into.
+    pub receive_memory: usize,
+
+    /// The largest call to accept, in bytes, without its record mark. A larger one ends its
+    /// connection. By default, this is the largest WRITE call; it may only be lowered.
+    pub max_record: Option<u32>,
+
+    /// The most calls that one connection may have in flight. Calls received beyond this are held
+    /// back, and the connection is not read from, until replies have been sent.
+    pub max_in_flight: usize,
+
+    /// The most bytes that the replies waiting to be sent may hold together, before calls are held
+    /// back on every connection.
+    pub reply_memory: usize,
+
+    /// The UNIX domain socket through which exportfs changes the exports while the server runs.
+    /// There is none by default.
+    pub control_socket: Option<PathBuf>,
+
+    /// The group whose members may use the control socket, besides root and the server's user.
+    pub control_group: Option<String>,
+
+    /// How many seconds a connection may sit idle, with no call in progress, before it is closed.
+    /// Connections are never closed for being idle by default.
+    pub idle_timeout: Option<u64>,
+
+    /// How many seconds a reply may take to be sent, before a client that does not read its
+    /// replies has its connection closed. There is no limit by default.
+    pub send_timeout: Option<u64>,
+
+    /// How many idle seconds pass before TCP keepalive probes are sent, to find clients that have
+    /// gone away. Keepalive is off by default.
+    pub keepalive: Option<u64>,
+
+    /// The most calls per second that each client address may make. Calls beyond this are held
+    /// back until they may be answered. There is no limit by default.
+    pub client_ops_per_sec: Option<u32>,
+
+    /// The most bytes of calls and replies per second that each client address may move. There is
+    /// no limit by default.
+    pub client_bytes_per_sec: Option<u64>,
+}
+
+impl Default for NfsServerConfig {
+    fn default() -> Self {
+        Self {
+            listen: "127.0.0.1:2049".parse().unwrap(),
+            verify_writes: false,
+            integrity: Integrity::default(),
+            check_replies: false,
+            mknod: false,
+            read_replies: ReadReplies::default(),
+            backend: Backend::default(),
+            fd_cache: 256,
+            attribute_ttl: 0,
+            ring_entries: 1024,
+            receive_memory: 32 * 1024 * 1024,
+            max_record: None,
+            max_in_flight: 128,
+            reply_memory: 64 * 1024 * 1024,
+            control_socket: None,
+            control_group: None,
+            idle_timeout: None,
+            send_timeout: None,
+            keepalive: None,
+            client_ops_per_sec: None,
+            client_bytes_per_sec: None,
+        }
+    }
+}
+
+impl NfsServerConfig {
+    /// The timeouts to give client connections.
+    pub fn connection_timeouts(&self) -> ConnectionTimeouts {
+        connection_timeouts(self.idle_timeout, self.send_timeout, self.keepalive)
+    }
+
+    /// The limits on the calls of each client.
+    pub fn client_limits(&self) -> Limits {
+        Limits {
+            ops_per_sec: self.client_ops_per_sec,
+            bytes_per_sec: self.client_bytes_per_sec,
+        }
+    }
+}
+
+/// How the NFS server sends the data of a READ reply.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReadReplies {
+    /// Read into a buffer of the reply, which is sent like any other. The data is copied out of the
+    /// page cache, and again into the socket.
+    #[default]
+    Copy,
+
+    /// Spliced from the page cache to the socket through a pipe, without being copied.
+    Splice,
+
+    /// Read into a buffer registered with the server's io_uring, which is sent with zero-copy
+    /// sends, so the data is only copied out of the page cache.
+    SendZc,
+}
+
+/// What the NFS server does with the digests that clients send with the data of WRITEs (see
+/// `nfs3::write_digest`). Digests of READ data are sent to clients that ask for them either way.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Integrity {
+    /// Ignore them, and reply as if they had not been sent.
+    #[default]
+    Off,
+
+    /// Check the data against them, and reply with the digest of the data received. Data that does
+    /// not match is logged, and written anyway.
+    Log,
+
+    /// Like `Log`, but refuse a WRITE whose data does not match with GARBAGE_ARGS, before writing
+    /// anything, so that the client can send it again.
+    Reject,
+}
+
+/// Where the NFS server keeps the files of its export.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// In the export directory, on the local file system.
+    #[default]
+    Local,
+
+    /// In memory, starting out empty, and lost when the server exits. The export directory only
+    /// gives the root its identity, so that mountd hands out the right file handle for it. For
+    /// benchmarks and tests that should not wait on storage: permissions are not checked, and
+    /// READ replies are always copied.
+    Memory,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MountdConfig {
+    /// The address to listen on, IPv4 or IPv6.
+    pub listen: SocketAddr,
+
+    /// Whether each reply is checked for encoding errors, such as padding that is not zero, before
+    /// it is sent.
+    pub check_replies: bool,
+
+    /// The largest call to accept, in bytes, without its record mark. A larger one ends its
+    /// connection. The calls of the mount protocol are small, so the default is too.
+    pub max_record: u32,
+
+    /// The UNIX domain socket through which exportfs changes the exports while mountd runs. There
+    /// is none by default.
+    pub control_socket: Option<PathBuf>,
+
+    /// The group whose members may use the control socket, besides root and mountd's user.
+    pub control_group: Option<String>,
+
+    /// A PEM file holding the certificate chain with which mountd offers RPC-with-TLS (RFC 9289),
+    /// server certificate first. TLS is not offered unless one is given, with `tls_key`.
+    pub tls_certificate: Option<PathBuf>,
+
+    /// A PEM file holding the private key of `tls_certificate`.
+    pub tls_key: Option<PathBuf>,
+
+    /// Whether calls other than to the NULL procedure are refused until the client has started
+    /// TLS.
+    pub tls_required: bool,
+
+    /// How many seconds a connection may sit idle, with no call in progress, before it is closed.
+    /// Connections are never closed for being idle by default.
+    pub idle_timeout: Option<u64>,
+
+    /// How many seconds a reply may take to be sent, before a client that does not read its
+    /// replies has its connection closed. There is no limit by default.
+    pub send_timeout: Option<u64>,
+
+    /// How many idle seconds pass before TCP keepalive probes are sent, to find clients that have
+    /// gone away. Keepalive is off by default.
+    pub keepalive: Option<u64>,
+}
+
+impl Default for MountdConfig {
+    fn default() -> Self {
+        Self {
+            listen: "0.0.0.0:20048".parse().unwrap(),
+            check_replies: false,
+            max_record: 64 * 1024,
+            control_socket: None,
+            control_group: None,
+            tls_certificate: None,
+            tls_key: None,
+            tls_required: false,
+            idle_timeout: None,
+            send_timeout: None,
+            keepalive: None,
+        }
+    }
+}
+
+impl MountdConfig {
+    /// The timeouts to give client connections.
+    pub fn connection_timeouts(&self) -> ConnectionTimeouts {
+        connection_timeouts(self.idle_timeout, self.send_timeout, self.keepalive)
+    }
+}
+
+fn connection_timeouts(
+    idle: Option<u64>,
+    send: Option<u64>,
+    keepalive: Option<u64>,
+) -> ConnectionTimeouts {
+    ConnectionTimeouts {
+        read: idle.map(Duration::from_secs),
+        write: send.map(Duration::from_secs),
+        keepalive: keepalive.map(Duration::from_secs),
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The file could not be read.
+    Io(PathBuf, io::Error),
+
+    /// The file is not valid TOML.
+    Syntax(toml::de::Error),
+
+    /// A key has a value that is not allowed: the key, and why.
+    Invalid(String, String),
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "Could not read {}: {e}", path.display()),
+            Self::Syntax(e) => write!(f, "{e}"),
+            Self::Invalid(key, reason) => write!(f, "{key}: {reason}"),
+        }
+    }
+}
+
+fn invalid(key: &str, reason: impl Into<String>) -> Error {
+    Error::Invalid(key.into(), reason.into())
+}
+
+impl Config {
+    /// Read and validate the configuration file at `path`.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = fs::read_to_string(path).map_err(|e| Error::Io(path.into(), e))?;
+
+        text.parse()
+    }
+
+    /// Check the values that are not allowed, alone or together.
+    pub fn validate(&self) -> Result<(), Error> {
+        if log::LevelFilter::from_str(&self.log.level).is_err() {
+            return Err(invalid(
+                "log.level",
+                "must be one of off, error, warn, info, debug, or trace",
+            ));
+        }
+
+        if !self.export.path.is_absolute() {
+            return Err(invalid("export.path", "must be an absolute path"));
+        }
+        if !self.export.netgroup_file.is_absolute() {
+            return Err(invalid("export.netgroup_file", "must be an absolute path"));
+        }
+
+        clients::check(&self.export.clients, None)
+            .map_err(|(i, e)| invalid(&format!("export.clients[{i}]"), e))?;
+        for (alias, members) in &self.clients {
+            if !matches!(alias.parse(), Ok(clients::ClientSpec::Name(_))) {
+                return Err(invalid(&format!("clients.{alias}"), "is not a valid alias"));
+            }
+            clients::check(members, Some(&self.clients))
+                .map_err(|(i, e)| invalid(&format!("clients.{alias}[{i}]"), e))?;
+        }
+
+        if self.auth.flavors.is_empty() {
+            return Err(invalid("auth.flavors", "must name at least one flavor"));
+        }
+
+        let entries = self.nfs_server.ring_entries;
+        if !entries.is_power_of_two() || entries > MAX_RING_ENTRIES {
+            return Err(invalid(
+                "nfs_server.ring_entries",
+                format!("must be a power of two no greater than {MAX_RING_ENTRIES}"),
+            ));
+        }
+
+        if self.nfs_server.receive_memory == 0 {
+            return Err(invalid("nfs_server.receive_memory", "must not be zero"));
+        }
+
+        for (key, max_record) in [
+            ("nfs_server.max_record", self.nfs_server.max_record),
+            ("mountd.max_record", Some(self.mountd.max_record)),
+        ] {
+            if max_record.is_some_and(|max| max < MIN_RECORD) {
+                return Err(invalid(key, format!("must be at least {MIN_RECORD}")));
+            }
+        }
+
+        if self.nfs_server.backend == Backend::Memory {
+            // These need a file on the local file system:
+            if self.nfs_server.read_replies != ReadReplies::Copy {
+                return Err(invalid(
+                    "nfs_server.read_replies",
+                    "must be copy with the memory backend",
+                ));
+            }
+            if self.export.read_method != ReadMethod::Pread {
+                return Err(invalid(
+                    "export.read_method",
+                    "must be pread with the memory backend",
+                ));
+            }
+            if self.export.snapshots.is_some() {
+                return Err(invalid(
+                    "export.snapshots",
+                    "is not possible with the memory backend",
+                ));
+            }
+            if self.nfs_server.verify_writes {
+                return Err(invalid(
+                    "nfs_server.verify_writes",
+                    "is not possible with the memory backend",
+                ));
+            }
+        }
+
+        if self.nfs_server.max_in_flight == 0 {
+            return Err(invalid("nfs_server.max_in_flight", "must not be zero"));
+        }
+
+        if self.nfs_server.reply_memory == 0 {
+            return Err(invalid("nfs_server.reply_memory", "must not be zero"));
+        }
+
+        for (key, limit) in [
+            ("export.ops_per_sec", self.export.ops_per_sec.map(u64::from)),
+            ("export.bytes_per_sec", self.export.bytes_per_sec),
+            (
+                "nfs_server.client_ops_per_sec",
+                self.nfs_server.client_ops_per_sec.map(u64::from),
+            ),
+            (
+                "nfs_server.client_bytes_per_sec",
+                self.nfs_server.client_bytes_per_sec,
+            ),
+        ] {
+            if limit == Some(0) {
+                return Err(invalid(key, "must not be zero"));
+            }
+        }
+
+        match (&self.mountd.tls_certificate, &self.mountd.tls_key) {
+            (Some(_), None) => {
+                return Err(invalid(
+                    "mountd.tls_key",
+                    "must be given with a certificate",
+                ))
+            }
+            (None, Some(_)) => {
+                return Err(invalid(
+                    "mountd.tls_certificate",
+                    "must be given with a key",
+                ))
+            }
+            (None, None) if self.mountd.tls_required => {
+                return Err(invalid(
+                    "mountd.tls_required",
+                    "needs mountd.tls_certificate and mountd.tls_key",
+                ))
+            }
+            _ => {}
+        }
+
+        for (key, seconds) in [
+            ("export.name_ttl", Some(self.export.name_ttl)),
+            ("nfs_server.idle_timeout", self.nfs_server.idle_timeout),
+            ("nfs_server.send_timeout", self.nfs_server.send_timeout),
+            ("nfs_server.keepalive", self.nfs_server.keepalive),
+            ("mountd.idle_timeout", self.mountd.idle_timeout),
+            ("mountd.send_timeout", self.mountd.send_timeout),
+            ("mountd.keepalive", self.mountd.keepalive),
+        ] {
+            if seconds == Some(0) {
+                return Err(invalid(key, "must not be zero"));
+            }
+        }
+
+        for (key, group) in [
+            ("nfs_server.control_group", &self.nfs_server.control_group),
+            ("mountd.control_group", &self.mountd.control_group),
+        ] {
+            control_policy(group.as_deref()).map_err(|e| invalid(key, e.to_string()))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Config {
+    type Err = Error;
+
+    /// Parse and validate a configuration.
+    fn from_str(text: &str) -> Result<Self, Error> {
+        let config: Self = serde_path_to_error::deserialize(toml::Deserializer::new(text))
+            .map_err(|e| match e.path().iter().next() {
+                Some(_) => Error::Invalid(e.path().to_string(), e.inner().message().to_string()),
+                None => Error::Syntax(e.into_inner()),
+            })?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+/// Who may make changes through a control socket whose `control_group` key is `group`.
+pub fn control_policy(group: Option<&str>) -> io::Result<PeerPolicy> {
+    match group {
+        Some(name) => PeerPolicy::for_group(name),
+        None => Ok(PeerPolicy::default()),
+    }
+}
+
+/// Call `reload` from a new thread each time the process receives SIGHUP.
+///
+/// See `on_signal()`, which this must be called before any other threads are started for.
+pub fn on_sighup(reload: impl FnMut() + Send + 'static) -> nix::Result<()> {
+    on_signal(Signal::SIGHUP, reload)
+}
+
+/// Call `handler` from a new thread each time the process receives `signal`.
+///
+/// The signal is blocked in the calling thread, and so in the threads it starts afterwards, leaving
+/// only the new thread to receive it. This must therefore be called before the process starts any
+/// other threads, but for ones started by this function.
+pub fn on_signal(signal: Signal, mut handler: impl FnMut() + Send + 'static) -> nix::Result<()> {
+    let mut signals = SigSet::empty();
+    signals.add(signal);
+    signals.thread_block()?;
+
+    // The new thread starts with every signal blocked, so that it takes no signal meant for a
+    // thread started by a later call:
+    let mask = SigSet::all().thread_swap_mask(SigmaskHow::SIG_BLOCK)?;
+    thread::spawn(move || loop {
+        if signals.wait().is_ok() {
+            handler();
+        }
+    });
+    mask.thread_set_mask()?;
+
+    Ok(())
+}
+
+/// Start or stop `tracer` each time the process receives SIGUSR1, starting it with a new trace at
+/// `path` in the format its name implies (see `TraceFormat::for_path()`). Like `on_signal()`, this
+/// must be called before any other threads are started.
+pub fn trace_on_sigusr1(tracer: Tracer, path: PathBuf) -> nix::Result<()> {
+    on_signal(Signal::SIGUSR1, move || {
+        if tracer.is_started() {
+            tracer.stop();
+            info!("Stopped tracing to {}", path.display());
+            return;
+        }
+
+        match tracer.start(&path, TraceFormat::for_path(&path)) {
+            Ok(()) => info!("Tracing calls and replies to {}", path.display()),
+            Err(e) => error!("Could not trace to {}: {e}", path.display()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        text.parse::<Config>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("".parse::<Config>().unwrap(), Config::default());
+
+        let config: Config = r#"
+            [log]
+            level = "debug"
+
+            [export]
+            path = "/srv/nfs"
+            read_only = true
+            cookie_verifier = "entries"
+            read_method = "mmap"
+            clients = ["10.0.0.0/8", "node1", "*.cluster.example"]
+            name_ttl = 60
+            netgroups = "nss"
+            snapshots = "/srv/snapshots"
+            bytes_per_sec = 104857600
+
+            [clients]
+            node1 = ["192.168.1.1", "fd00::1", "node1-ib"]
+
+            [auth]
+            flavors = ["sys"]
+            squash = "all"
+            anon_uid = 99
+
+            [nfs_server]
+            listen = "[::]:2049"
+            ring_entries = 256
+            max_in_flight = 16
+            check_replies = true
+            mknod = true
+            integrity = "reject"
+            read_replies = "send-zc"
+            fd_cache = 1024
+            attribute_ttl = 500
+            control_socket = "/run/nfs3/nfs_server.sock"
+            control_group = "root"
+            idle_timeout = 600
+            keepalive = 60
+            client_ops_per_sec = 5000
+        "#
+        .parse()
+        .unwrap();
+
+        assert_eq!(config.log.level, "debug");
+        assert_eq!(config.export.path, Path::new("/srv/nfs"));
+        assert_eq!(config.export.handle_key, ExportConfig::default().handle_key);
+        assert!(config.export.read_only);
+        assert_eq!(config.export.cookie_verifier, CookieVerifier::Entries);
+        assert_eq!(config.export.read_method, ReadMethod::Mmap);
+        assert_eq!(
+            config.export.clients,
+            ["10.0.0.0/8", "node1", "*.cluster.example"]
+        );
+        assert_eq!(config.export.name_ttl, 60);
+        assert_eq!(config.export.netgroups, NetgroupSource::Nss);
+        assert_eq!(
+            config.export.snapshots.as_deref(),
+            Some(Path::new("/srv/snapshots"))
+        );
+        assert_eq!(
+            config.export.limits(),
+            Limits {
+                ops_per_sec: None,
+                bytes_per_sec: Some(100 << 20),
+            }
+        );
+        assert_eq!(config.clients["node1"].len(), 3);
+        assert_eq!(config.auth.flavors, [Flavor::Sys]);
+        assert_eq!(config.auth.squash, Squash::All);
+        assert_eq!((config.auth.anon_uid, config.auth.anon_gid), (99, 65534));
+        assert!(config.nfs_server.listen.is_ipv6());
+        assert_eq!(config.nfs_server.listen.port(), 2049);
+        assert_eq!(config.nfs_server.ring_entries, 256);
+        assert_eq!(config.nfs_server.max_in_flight, 16);
+        assert_eq!(config.nfs_server.max_record, None);
+        assert!(config.nfs_server.check_replies);
+        assert!(config.nfs_server.mknod);
+        assert_eq!(config.nfs_server.integrity, Integrity::Reject);
+        assert_eq!(config.nfs_server.read_replies, ReadReplies::SendZc);
+        assert_eq!(config.nfs_server.backend, Backend::Local);
+        assert_eq!(config.nfs_server.fd_cache, 1024);
+        assert_eq!(config.nfs_server.attribute_ttl, 500);
+        assert_eq!(
+            config.nfs_server.control_socket.as_deref(),
+            Some(Path::new("/run/nfs3/nfs_server.sock"))
+        );
+        assert_eq!(config.nfs_server.control_group.as_deref(), Some("root"));
+        assert_eq!(
+            config.nfs_server.connection_timeouts(),
+            ConnectionTimeouts {
+                read: Some(Duration::from_secs(600)),
+                write: None,
+                keepalive: Some(Duration::from_secs(60)),
+            }
+        );
+        assert_eq!(
+            config.nfs_server.client_limits(),
+            Limits {
+                ops_per_sec: Some(5000),
+                bytes_per_sec: None,
+            }
+        );
+        assert_eq!(config.mountd, MountdConfig::default());
+    }
+
+    #[test]
+    fn errors_name_the_key() {
+        assert!(error("[auth]\nsquash = \"some\"").starts_with("auth.squash: "));
+        assert!(error("[auth]\nflavors = [\"sys\", \"gss\"]").starts_with("auth.flavors[1]: "));
+        assert!(error("[nfs_server]\nlisten = \"localhost\"").starts_with("nfs_server.listen: "));
+        assert!(error("[mountd]\nport = 20048").contains("unknown field `port`"));
+        assert!(error("[export]\nhandle_retention = -1").starts_with("export.handle_retention: "));
+        assert!(
+            error("[export]\ncookie_verifier = \"Zero\"").starts_with("export.cookie_verifier: ")
+        );
+        assert_eq!(
+            error("[mountd]\ncontrol_group = \"no-such-group\""),
+            "mountd.control_group: no group called \"no-such-group\""
+        );
+
+        assert_eq!(
+            error("[nfs_server]\nring_entries = 1000"),
+            "nfs_server.ring_entries: must be a power of two no greater than 32768"
+        );
+        assert_eq!(
+            error("[auth]\nflavors = []"),
+            "auth.flavors: must name at least one flavor"
+        );
+        assert!(error("[log]\nlevel = \"loud\"").starts_with("log.level: "));
+        assert_eq!(
+            error("[mountd]\nmax_record = 100"),
+            "mountd.max_record: must be at least 4096"
+        );
+        assert!(error("[nfs_server]\nmax_in_flight = 0").starts_with("nfs_server.max_in_flight: "));
+        assert!(error("[mountd]\nsend_timeout = 0").starts_with("mountd.send_timeout: "));
+        assert!(error("[export]\nname_ttl = 0").starts_with("export.name_ttl: "));
+        assert_eq!(
+            error("[nfs_server]\nclient_bytes_per_sec = 0"),
+            "nfs_server.client_bytes_per_sec: must not be zero"
+        );
+        assert_eq!(
+            error("[mountd]\ntls_certificate = \"/etc/nfs/mountd.pem\""),
+            "mountd.tls_key: must be given with a certificate"
+        );
+        assert!(error("[mountd]\ntls_required = true").starts_with("mountd.tls_required: "));
+        assert!(error("[export]\npath = \"export\"").starts_with("export.path: "));
+        assert!(error("[export]\nnetgroups = \"nis\"").starts_with("export.netgroups: "));
+        assert!(error("[export]\nclients = [\"*\", \"10.0.0.0/40\"]")
+            .starts_with("export.clients[1]: "));
+        assert!(error("[clients]\na = [\"b\"]\nb = [\"10.0.0.1\"]").starts_with("clients.a[0]: "));
+        assert!(error("[clients]\n\"a b\" = []").starts_with("clients.a b: "));
+        assert!(error("[nfs_server]\nbackend = \"tmpfs\"").starts_with("nfs_server.backend: "));
+        assert_eq!(
+            error("[nfs_server]\nbackend = \"memory\"\nread_replies = \"splice\""),
+            "nfs_server.read_replies: must be copy with the memory backend"
+        );
+        assert!(error(
+            "[export]\nsnapshots = \"/srv/snapshots\"\n[nfs_server]\nbackend = \"memory\""
+        )
+        .starts_with("export.snapshots: "));
+
+        assert!(matches!(
+            "[export\n".parse::<Config>(),
+            Err(Error::Syntax(_))
+        ));
+    }
+}
@@ -0,0 +1,482 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The suite's private control protocol (control_proto.x), through which exportfs lists, adds, and
+// removes the exports of mountd and the NFS server while they run, flushes mountd's list of mounts,
+// and reads statistics about each export.
+//
+// A daemon implements `ExportControl` for a handle to its state, and serves it on a UNIX domain
+// socket that only the daemon's user, root, and the members of an admin group may connect to:
+//
+//     control::serve(Path::new("/run/nfs3/mountd.sock"), MountdControl(state.clone()), policy)?;
+//
+// and exportfs then calls it through the socket:
+//
+//     let exports = control::list(&mut UnixStream::connect("/run/nfs3/mountd.sock")?)?;
+//
+// Changes made this way last until the daemon exits or reloads its configuration file. Each change
+// is checked against the credentials of the process that asked for it (SO_PEERCRED), and recorded
+// with them in the audit log.
+
+use std::{
+    ffi::OsString,
+    fmt, fs,
+    io::{self, Read, Write},
+    os::unix::{
+        fs::{FileTypeExt, PermissionsExt},
+        net::UnixListener,
+    },
+    path::Path,
+    thread,
+};
+
+use rpc_protocol::{server::*, Call};
+
+use crate::{
+    config::{CookieVerifier, ReadMethod},
+    control_proto::{procedures::*, *},
+};
+
+impl ExportEntry {
+    pub fn new(path: &Path, read_only: bool) -> Self {
+        Self {
+            path: path.into(),
+            read_only,
+            cookie_verifier: CookieVerifierKind::default(),
+            read_method: ReadMethodKind::default(),
+            clients: Vec::new(),
+            snapshots: Default::default(),
+        }
+    }
+
+    /// Limit the export to `clients` (see `nfs3::clients`), in place of any client.
+    pub fn with_clients(mut self, clients: &[String]) -> Self {
+        self.clients = clients.iter().map(Into::into).collect();
+
+        self
+    }
+
+    /// Set how the export's cookie verifiers are chosen, in place of the default.
+    pub fn with_cookie_verifier(mut self, verifier: CookieVerifier) -> Self {
+        self.cookie_verifier = match verifier {
+            CookieVerifier::Mtime => CookieVerifierKind::Mtime,
+            CookieVerifier::Entries => CookieVerifierKind::Entries,
+            CookieVerifier::Zero => CookieVerifierKind::Zero,
+        };
+
+        self
+    }
+
+    /// Set how the data of READs is read from the export's files, in place of the default.
+    pub fn with_read_method(mut self, method: ReadMethod) -> Self {
+        self.read_method = match method {
+            ReadMethod::Pread => ReadMethodKind::Pread,
+            ReadMethod::Mmap => ReadMethodKind::Mmap,
+        };
+
+        self
+    }
+
+    /// Show the snapshots in `dir` as `.snapshot` in the root of the export.
+    pub fn with_snapshots(mut self, dir: Option<&Path>) -> Self {
+        self.snapshots = dir.map_or_else(Default::default, Into::into);
+
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        Path::new(&self.path)
+    }
+
+    pub fn get_snapshots(&self) -> Option<&Path> {
+        (!self.snapshots.is_empty()).then(|| Path::new(&self.snapshots))
+    }
+
+    pub fn get_cookie_verifier(&self) -> CookieVerifier {
+        match self.cookie_verifier {
+            CookieVerifierKind::Mtime => CookieVerifier::Mtime,
+            CookieVerifierKind::Entries => CookieVerifier::Entries,
+            CookieVerifierKind::Zero => CookieVerifier::Zero,
+        }
+    }
+
+    pub fn get_read_method(&self) -> ReadMethod {
+        match self.read_method {
+            ReadMethodKind::Pread => ReadMethod::Pread,
+            ReadMethodKind::Mmap => ReadMethod::Mmap,
+        }
+    }
+
+    pub fn get_clients(&self) -> Vec<String> {
+        self.clients
+            .iter()
+            .map(|c| c.to_string_lossy().into_owned())
+            .collect()
+    }
+}
+
+impl ExportStats {
+    /// The statistics of the export at `path`, with no counters.
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.into(),
+            counters: Vec::new(),
+        }
+    }
+
+    /// Add a counter.
+    pub fn counter(mut self, name: &str, value: u64) -> Self {
+        self.counters.push(Counter {
+            name: name.into(),
+            value,
+        });
+
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        Path::new(&self.path)
+    }
+
+    /// The value of the counter called `name`, if the daemon reported one.
+    pub fn get_counter(&self, name: &str) -> Option<u64> {
+        self.counters
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.value)
+    }
+}
+
+/// What a daemon does when asked through the control protocol. Each method that makes a change
+/// returns why it could not, if it could not.
+pub trait ExportControl {
+    /// The current exports.
+    fn list(&mut self) -> Vec<ExportEntry>;
+
+    /// Export another directory.
+    fn add(&mut self, export: &ExportEntry) -> Result<(), String>;
+
+    /// Stop exporting the directory at `path`.
+    fn remove(&mut self, path: &Path) -> Result<(), String>;
+
+    /// Forget the clients that have mounted exports. A daemon that keeps no list of mounts has
+    /// nothing to flush.
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// The statistics of each export.
+    fn stats(&mut self) -> Vec<ExportStats>;
+}
+
+fn control_result(res: Result<(), String>) -> ControlResult {
+    ControlResult {
+        inner: res.err().map(OsString::from),
+    }
+}
+
+/// A daemon's `ExportControl`, along with who may make changes through it.
+struct Controlled<T> {
+    control: T,
+    policy: PeerPolicy,
+}
+
+/// Make the change `change` if the caller may, recording it in the audit log as `action`.
+fn checked<T: ExportControl>(
+    call: &Call,
+    controlled: &mut Controlled<T>,
+    action: fmt::Arguments,
+    change: impl FnOnce(&mut T) -> Result<(), String>,
+) -> Result<ControlResult, RpcResult> {
+    if !controlled.policy.check(call, action) {
+        return Ok(control_result(Err("Permission denied".into())));
+    }
+
+    Ok(control_result(change(&mut controlled.control)))
+}
+
+fn list_exports<T: ExportControl>(
+    _call: &Call,
+    _args: (),
+    controlled: &mut Controlled<T>,
+) -> Result<ExportList, RpcResult> {
+    Ok(ExportList {
+        exports: controlled.control.list(),
+    })
+}
+
+fn add_export<T: ExportControl>(
+    call: &Call,
+    args: ExportEntry,
+    controlled: &mut Controlled<T>,
+) -> Result<ControlResult, RpcResult> {
+    let mode = if args.read_only { "ro" } else { "rw" };
+    let action = format_args!("export {} ({mode})", args.path().display());
+    checked(call, controlled, action, |control| control.add(&args))
+}
+
+fn remove_export<T: ExportControl>(
+    call: &Call,
+    args: ExportPath,
+    controlled: &mut Controlled<T>,
+) -> Result<ControlResult, RpcResult> {
+    let path = Path::new(&args.path);
+    let action = format_args!("unexport {}", path.display());
+    checked(call, controlled, action, |control| control.remove(path))
+}
+
+fn flush_mounts<T: ExportControl>(
+    call: &Call,
+    _args: (),
+    controlled: &mut Controlled<T>,
+) -> Result<ControlResult, RpcResult> {
+    checked(call, controlled, format_args!("flush"), T::flush)
+}
+
+fn report_stats<T: ExportControl>(
+    _call: &Call,
+    _args: (),
+    controlled: &mut Controlled<T>,
+) -> Result<StatsList, RpcResult> {
+    Ok(StatsList {
+        exports: controlled.control.stats(),
+    })
+}
+
+/// The RPC program that answers control calls with `control`. Over a UNIX domain socket, only the
+/// callers that `policy` admits may make changes; anyone who can connect may list the exports and
+/// read their statistics.
+fn program<T: ExportControl>(control: T, policy: PeerPolicy) -> RpcProgram<Controlled<T>> {
+    let procedures: Vec<Option<RpcProcedure<Controlled<T>>>> = vec![
+        None,
+        Some(typed_procedure(list_exports)),
+        Some(typed_procedure(add_export)),
+        Some(typed_procedure(remove_export)),
+        Some(typed_procedure(flush_mounts)),
+        Some(typed_procedure(report_stats)),
+    ];
+    let mut program = RpcProgram::new(
+        CONTROL_PROG,
+        CONTROL_VERS::VERSION,
+        CONTROL_VERS::VERSION,
+        procedures,
+        Controlled { control, policy },
+    );
+    program.set_procedure_names(proc_name);
+
+    program
+}
+
+/// Serve `control` to connections on a UNIX domain socket at `path`, from a new thread.
+///
+/// A socket left at `path` by an earlier run is replaced, but any other kind of file is not. The
+/// socket is made accessible only to its owner after it is created, or to its owner and the group
+/// of `policy` if there is one, so the directory it is in should not be writable by others.
+pub fn serve<T: ExportControl + Send + 'static>(
+    path: &Path,
+    control: T,
+    policy: PeerPolicy,
+) -> io::Result<thread::JoinHandle<()>> {
+    if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    match policy.group {
+        Some(gid) => {
+            nix::unistd::chown(path, None, Some(nix::unistd::Gid::from_raw(gid)))?;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o660))?;
+        }
+        None => fs::set_permissions(path, fs::Permissions::from_mode(0o600))?,
+    }
+
+    Ok(thread::spawn(move || {
+        program(control, policy).run_blocking_tcp_server(listener)
+    }))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The call itself failed.
+    Rpc(rpc_protocol::Error),
+
+    /// The result could not be decoded.
+    Decode,
+
+    /// The daemon did not make the change: why not.
+    Refused(String),
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Rpc(e) => write!(f, "{e}"),
+            Self::Decode => write!(f, "Could not decode the result"),
+            Self::Refused(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+impl From<rpc_protocol::Error> for Error {
+    fn from(e: rpc_protocol::Error) -> Self {
+        Self::Rpc(e)
+    }
+}
+
+fn call<S: Read + Write>(stream: &mut S, proc: u32, arg: &[u8]) -> Result<Vec<u8>, Error> {
+    Ok(rpc_protocol::client::do_rpc_call(
+        stream,
+        CONTROL_PROG,
+        CONTROL_VERS::VERSION,
+        proc,
+        arg,
+    )?)
+}
+
+/// Call a procedure that returns a `ControlResult`.
+fn call_for_result<S: Read + Write>(stream: &mut S, proc: u32, arg: &[u8]) -> Result<(), Error> {
+    let raw = call(stream, proc, arg)?;
+
+    let mut result = ControlResult::default();
+    result
+        .deserialize(&mut raw.as_slice())
+        .map_err(|_| Error::Decode)?;
+
+    match result.inner {
+        Some(reason) => Err(Error::Refused(reason.to_string_lossy().into_owned())),
+        None => Ok(()),
+    }
+}
+
+/// Call CONTROLPROC_LIST, and return the daemon's exports.
+pub fn list<S: Read + Write>(stream: &mut S) -> Result<Vec<ExportEntry>, Error> {
+    let raw = call(stream, CONTROL_VERS::CONTROLPROC_LIST, &[])?;
+
+    let mut list = ExportList::default();
+    list.deserialize(&mut raw.as_slice())
+        .map_err(|_| Error::Decode)?;
+
+    Ok(list.exports)
+}
+
+/// Call CONTROLPROC_ADD to export another directory.
+pub fn add<S: Read + Write>(stream: &mut S, export: &ExportEntry) -> Result<(), Error> {
+    call_for_result(
+        stream,
+        CONTROL_VERS::CONTROLPROC_ADD,
+        &export.serialize_alloc(),
+    )
+}
+
+/// Call CONTROLPROC_REMOVE to stop exporting the directory at `path`.
+pub fn remove<S: Read + Write>(stream: &mut S, path: &Path) -> Result<(), Error> {
+    let args = ExportPath { path: path.into() };
+
+    call_for_result(
+        stream,
+        CONTROL_VERS::CONTROLPROC_REMOVE,
+        &args.serialize_alloc(),
+    )
+}
+
+/// Call CONTROLPROC_FLUSH to make the daemon forget the clients that have mounted exports.
+pub fn flush<S: Read + Write>(stream: &mut S) -> Result<(), Error> {
+    call_for_result(stream, CONTROL_VERS::CONTROLPROC_FLUSH, &[])
+}
+
+/// Call CONTROLPROC_STATS, and return the statistics of each of the daemon's exports.
+pub fn stats<S: Read + Write>(stream: &mut S) -> Result<Vec<ExportStats>, Error> {
+    let raw = call(stream, CONTROL_VERS::CONTROLPROC_STATS, &[])?;
+
+    let mut list = StatsList::default();
+    list.deserialize(&mut raw.as_slice())
+        .map_err(|_| Error::Decode)?;
+
+    Ok(list.exports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_harness::spawn_program;
+
+    /// A daemon that only keeps a list of exports, and counts the flushes.
+    #[derive(Default)]
+    struct Exports {
+        exports: Vec<ExportEntry>,
+        flushes: u64,
+    }
+
+    impl ExportControl for Exports {
+        fn list(&mut self) -> Vec<ExportEntry> {
+            self.exports.clone()
+        }
+
+        fn add(&mut self, export: &ExportEntry) -> Result<(), String> {
+            if self.exports.iter().any(|e| e.path == export.path) {
+                return Err(format!("{} is already exported", export.path().display()));
+            }
+            self.exports.push(export.clone());
+
+            Ok(())
+        }
+
+        fn remove(&mut self, path: &Path) -> Result<(), String> {
+            let before = self.exports.len();
+            self.exports.retain(|e| e.path() != path);
+            if self.exports.len() == before {
+                return Err(format!("{} is not exported", path.display()));
+            }
+
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), String> {
+            self.flushes += 1;
+
+            Ok(())
+        }
+
+        fn stats(&mut self) -> Vec<ExportStats> {
+            self.exports
+                .iter()
+                .map(|e| ExportStats::new(e.path()).counter("flushes", self.flushes))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn control_exports() {
+        let mut client = spawn_program(program(Exports::default(), PeerPolicy::default()));
+
+        let srv = ExportEntry::new(Path::new("/srv"), false);
+        let home = ExportEntry::new(Path::new("/home"), true)
+            .with_snapshots(Some(Path::new("/home/.zfs/snapshot")));
+        assert_eq!(srv.get_snapshots(), None);
+        add(&mut client, &srv).unwrap();
+        add(&mut client, &home).unwrap();
+        assert_eq!(list(&mut client).unwrap(), [srv.clone(), home.clone()]);
+
+        match add(&mut client, &srv) {
+            Err(Error::Refused(reason)) => assert_eq!(reason, "/srv is already exported"),
+            other => panic!("{other:?}"),
+        }
+
+        remove(&mut client, Path::new("/srv")).unwrap();
+        assert!(matches!(
+            remove(&mut client, Path::new("/srv")),
+            Err(Error::Refused(_))
+        ));
+        assert_eq!(list(&mut client).unwrap(), [home]);
+
+        flush(&mut client).unwrap();
+        let stats = stats(&mut client).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].path(), Path::new("/home"));
+        assert_eq!(stats[0].get_counter("flushes"), Some(1));
+        assert_eq!(stats[0].get_counter("reads"), None);
+    }
+}
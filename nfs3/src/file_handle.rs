@@ -0,0 +1,961 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Generation and validation of NFS v3 file handles.
+//
+// A file handle has the following layout, with all integers big-endian:
+//
+//     version (1 byte) | reserved (3 bytes) | export id (4 bytes) | inode (8 bytes) |
+//     generation (4 bytes) | MAC (16 bytes)
+//
+// The MAC is a truncated HMAC-SHA256 of the preceding bytes, keyed by a secret that is stored in a
+// key file. Since the handle names the file by its inode rather than by an in-memory index, and the
+// key is persisted, handles given out by one run of the server remain valid in the next, and mountd
+// and the NFS server agree on handles as long as they share the key file.
+//
+// The map from handles back to paths is only a cache, which is rebuilt by rescanning the export
+// when a handle is not in it. Entries for files that have been removed or replaced are collected
+// whenever the cache has doubled in size since the last collection, so that it stays proportional
+// to the number of files in the export rather than to the number of files ever looked up. Handles
+// that a rescan does not find are remembered for a while, so that a client that keeps sending a
+// stale handle does not have the export walked for each call; a file that is created with the
+// handle's inode meanwhile is found as soon as a handle is made for it.
+//
+// The map finds the inode numbers, generations, and entries of files through a `FileSystem`, which
+// is the local file system unless the map is made with `FileHandleMap::with_file_system()`.
+
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::{MetadataExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use hmac::{Hmac, Mac};
+use log::*;
+use sha2::{Digest, Sha256};
+
+use crate::nfs3_xdr::{NfsResult, FHSIZE};
+
+const HANDLE_VERSION: u8 = 1;
+const KEY_SIZE: usize = 32;
+const MAC_SIZE: usize = 16;
+const BODY_SIZE: usize = 20;
+
+/// The size in bytes of every file handle generated by a [`FileHandleMap`].
+pub const HANDLE_SIZE: usize = BODY_SIZE + MAC_SIZE;
+
+const _: () = assert!(HANDLE_SIZE <= FHSIZE as usize);
+
+/// How long an entry is kept after it was last used before it is checked for collection, unless
+/// changed with [`FileHandleMap::set_retention`].
+pub const DEFAULT_RETENTION: Duration = Duration::from_secs(60);
+
+/// The cache is not collected until it has at least this many entries.
+const MIN_COLLECTION_SIZE: usize = 1024;
+
+/// How long a handle that a rescan did not find is answered as stale without another rescan.
+const MISS_RETENTION: Duration = Duration::from_secs(60);
+
+/// The most handles remembered as missing at once. Misses past this are not remembered.
+const MAX_MISSES: usize = 4096;
+
+/// The fields of a file handle, once its MAC has been verified.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct HandleBody {
+    export_id: u32,
+    inode: u64,
+    generation: u32,
+}
+
+impl HandleBody {
+    fn encode(&self) -> [u8; BODY_SIZE] {
+        let mut buf = [0; BODY_SIZE];
+        buf[0] = HANDLE_VERSION;
+        buf[4..8].copy_from_slice(&self.export_id.to_be_bytes());
+        buf[8..16].copy_from_slice(&self.inode.to_be_bytes());
+        buf[16..20].copy_from_slice(&self.generation.to_be_bytes());
+        buf
+    }
+
+    fn decode(buf: &[u8; BODY_SIZE]) -> Option<Self> {
+        if buf[0] != HANDLE_VERSION || buf[1..4] != [0; 3] {
+            return None;
+        }
+
+        Some(Self {
+            export_id: u32::from_be_bytes(buf[4..8].try_into().unwrap()),
+            inode: u64::from_be_bytes(buf[8..16].try_into().unwrap()),
+            generation: u32::from_be_bytes(buf[16..20].try_into().unwrap()),
+        })
+    }
+}
+
+/// What a [`FileHandleMap`] needs to know of the files it maps.
+pub trait FileSystem: Send + Sync {
+    /// `path` with symbolic links resolved, as [`Path::canonicalize`] does.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// The identity of the file at `path`, which is a symbolic link itself if it names one.
+    fn identify(&self, path: &Path) -> io::Result<FileId>;
+
+    /// The inode generation number of the file at `path`, whose identity is `id`, which
+    /// distinguishes it from an earlier file that had the same inode number. 0 if there is none.
+    fn generation(&self, path: &Path, id: &FileId) -> u32;
+
+    /// The paths of the entries of the directory at `dir`, without "." and "..".
+    fn entries(&self, dir: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Called when a map starts exporting the directory at `root`, which is canonical. The export
+    /// is not added if this fails.
+    fn exported(&self, _root: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called when a map stops exporting the directory at `root`, or is dropped while exporting
+    /// it.
+    fn unexported(&self, _root: &Path) {}
+}
+
+/// The identity of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileId {
+    pub dev: u64,
+    pub ino: u64,
+    pub is_dir: bool,
+    pub is_file: bool,
+}
+
+/// The local file system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LocalFileSystem;
+
+impl FileSystem for LocalFileSystem {
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        path.canonicalize()
+    }
+
+    fn identify(&self, path: &Path) -> io::Result<FileId> {
+        let metadata = fs::symlink_metadata(path)?;
+
+        Ok(FileId {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            is_dir: metadata.is_dir(),
+            is_file: metadata.is_file(),
+        })
+    }
+
+    fn generation(&self, path: &Path, id: &FileId) -> u32 {
+        generation(path, id)
+    }
+
+    fn entries(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(dir)?.map(|entry| Ok(entry?.path())).collect()
+    }
+}
+
+struct Export {
+    id: u32,
+    root: PathBuf,
+    /// The device and inode numbers of `root`, which identify it even if it is also reachable by
+    /// another path, for example through a bind mount.
+    dev: u64,
+    ino: u64,
+}
+
+/// A file that has been looked up or handed out.
+struct Entry {
+    path: PathBuf,
+    generation: u32,
+    last_used: Instant,
+}
+
+impl Entry {
+    fn new(files: &dyn FileSystem, path: PathBuf, id: &FileId) -> Self {
+        Self {
+            generation: files.generation(&path, id),
+            path,
+            last_used: Instant::now(),
+        }
+    }
+
+    /// Whether the file this entry was created for is still at its path.
+    fn is_current(&self, files: &dyn FileSystem, inode: u64) -> bool {
+        files
+            .identify(&self.path)
+            .is_ok_and(|id| id.ino == inode && files.generation(&self.path, &id) == self.generation)
+    }
+}
+
+/// Statistics about the cache of a [`FileHandleMap`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FileHandleMapStats {
+    /// The number of files in the cache.
+    pub entries: usize,
+    /// The number of times the cache has been collected.
+    pub collections: u64,
+    /// The total number of entries removed by collections.
+    pub collected: u64,
+}
+
+/// Maps between file handles and the paths of the files they refer to.
+pub struct FileHandleMap {
+    key: [u8; KEY_SIZE],
+    files: Arc<dyn FileSystem>,
+    exports: Vec<Export>,
+    /// Files that have been looked up or handed out, indexed by export id and inode.
+    paths: HashMap<(u32, u64), Entry>,
+    /// Handles that the last rescan of their export did not find, and when it did not.
+    misses: HashMap<(u32, u64), Instant>,
+    /// Entries used more recently than this are not collected.
+    retention: Duration,
+    /// The cache is collected when it grows past this many entries.
+    next_collection: usize,
+    stats: FileHandleMapStats,
+}
+
+impl Drop for FileHandleMap {
+    fn drop(&mut self) {
+        for export in &self.exports {
+            self.files.unexported(&export.root);
+        }
+    }
+}
+
+impl FileHandleMap {
+    /// Create a map whose handles are protected by the key stored in `key_file`.
+    ///
+    /// If the key file does not exist, a new random key is generated and saved to it, readable
+    /// only by the owner.
+    pub fn new(key_file: &Path) -> io::Result<Self> {
+        Self::with_file_system(key_file, Arc::new(LocalFileSystem))
+    }
+
+    /// Create a map, as with [`FileHandleMap::new`], of the files of `files` in place of the local
+    /// file system.
+    pub fn with_file_system(key_file: &Path, files: Arc<dyn FileSystem>) -> io::Result<Self> {
+        Ok(Self {
+            key: load_or_create_key(key_file)?,
+            files,
+            exports: Vec::new(),
+            paths: HashMap::new(),
+            misses: HashMap::new(),
+            retention: DEFAULT_RETENTION,
+            next_collection: MIN_COLLECTION_SIZE,
+            stats: FileHandleMapStats::default(),
+        })
+    }
+
+    /// Set how long an entry is kept after it was last used before it may be collected.
+    pub fn set_retention(&mut self, retention: Duration) {
+        self.retention = retention;
+    }
+
+    pub fn stats(&self) -> FileHandleMapStats {
+        FileHandleMapStats {
+            entries: self.paths.len(),
+            ..self.stats
+        }
+    }
+
+    /// Make the files under `root` addressable by file handles.
+    ///
+    /// The export id is derived from the path, so it does not depend on the order in which
+    /// exports are added. Symbolic links in `root` are resolved first, and a directory can only be
+    /// exported once: since the same file would have a different handle in each export, adding a
+    /// directory that is already exported under another path, for example through a bind mount,
+    /// fails.
+    pub fn add_export(&mut self, root: &Path) -> io::Result<()> {
+        let canonical = self.files.canonicalize(root)?;
+        if canonical != root {
+            debug!(
+                "export {} resolves to {}",
+                root.display(),
+                canonical.display()
+            );
+        }
+        let root = canonical;
+
+        let file = self.files.identify(&root)?;
+        if !file.is_dir {
+            return Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("export {} is not a directory", root.display()),
+            ));
+        }
+
+        if let Some(other) = self
+            .exports
+            .iter()
+            .find(|e| e.dev == file.dev && e.ino == file.ino)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                if other.root == root {
+                    format!("{} is exported more than once", root.display())
+                } else {
+                    format!(
+                        "exports {} and {} are the same directory (device {:#x}, inode {}), \
+                         probably through a bind mount",
+                        other.root.display(),
+                        root.display(),
+                        file.dev,
+                        file.ino
+                    )
+                },
+            ));
+        }
+
+        let digest = Sha256::digest(root.as_os_str().as_encoded_bytes());
+        let id = u32::from_be_bytes(digest[..4].try_into().unwrap());
+
+        if let Some(other) = self.exports.iter().find(|e| e.id == id) {
+            return Err(io::Error::other(format!(
+                "exports {} and {} have the same export id",
+                root.display(),
+                other.root.display()
+            )));
+        }
+
+        self.files.exported(&root)?;
+        debug!("export {} has id {id:#010x}", root.display());
+        self.exports.push(Export {
+            id,
+            root,
+            dev: file.dev,
+            ino: file.ino,
+        });
+
+        Ok(())
+    }
+
+    /// Stop making the files under the export at `root` addressable. Their handles become stale,
+    /// and are valid again if the directory is exported again.
+    pub fn remove_export(&mut self, root: &Path) -> io::Result<()> {
+        let root = self
+            .files
+            .canonicalize(root)
+            .unwrap_or_else(|_| root.to_path_buf());
+        let Some(index) = self.exports.iter().position(|e| e.root == root) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not exported", root.display()),
+            ));
+        };
+
+        let id = self.exports.remove(index).id;
+        self.files.unexported(&root);
+        self.paths.retain(|&(export_id, _), _| export_id != id);
+        self.misses.retain(|&(export_id, _), _| export_id != id);
+        debug!("removed export {} with id {id:#010x}", root.display());
+
+        Ok(())
+    }
+
+    /// The roots of the exports, in the order they were added.
+    pub fn exports(&self) -> impl Iterator<Item = &Path> {
+        self.exports.iter().map(|e| e.root.as_path())
+    }
+
+    /// The root of the export that `path` is in. If exports are nested, this is the innermost one.
+    /// `path` must be canonical, as the paths this map returns are.
+    pub fn export_of(&self, path: &Path) -> Option<&Path> {
+        self.export_containing(path).map(|e| e.root.as_path())
+    }
+
+    /// The root of the export that the file identified by `handle` is in, without finding the file,
+    /// so that the file may have been removed since. None if the handle is not valid.
+    pub fn export_of_handle(&self, handle: &[u8]) -> Option<&Path> {
+        let body = self.open(handle)?;
+
+        self.exports
+            .iter()
+            .find(|e| e.id == body.export_id)
+            .map(|e| e.root.as_path())
+    }
+
+    fn export_containing(&self, path: &Path) -> Option<&Export> {
+        self.exports
+            .iter()
+            .filter(|e| path.starts_with(&e.root))
+            .max_by_key(|e| e.root.as_os_str().len())
+    }
+
+    /// Generate the file handle for the file at `path`, which must be inside one of the exports.
+    ///
+    /// If `path` names a symbolic link, the handle is for the link itself.
+    pub fn handle_for(&mut self, path: &Path) -> io::Result<Vec<u8>> {
+        let path = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => self.files.canonicalize(parent)?.join(name),
+            _ => self.files.canonicalize(path)?,
+        };
+        let Some(export) = self.export_containing(&path) else {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is not in an export", path.display()),
+            ));
+        };
+
+        let id = self.files.identify(&path)?;
+        let export_id = export.id;
+        let entry = Entry::new(&*self.files, path, &id);
+        let body = HandleBody {
+            export_id,
+            inode: id.ino,
+            generation: entry.generation,
+        };
+
+        self.insert((body.export_id, body.inode), entry);
+
+        Ok(self.seal(&body))
+    }
+
+    /// Find the path of the file identified by `handle`.
+    ///
+    /// Returns `BadHandle` if the handle was not generated with this map's key, and `Stale` if it
+    /// refers to a file that no longer exists, or that a recent rescan did not find.
+    pub fn lookup(&mut self, handle: &[u8]) -> Result<PathBuf, NfsResult> {
+        let body = self.open(handle).ok_or(NfsResult::BadHandle)?;
+        let key = (body.export_id, body.inode);
+
+        let path = match self.paths.get_mut(&key) {
+            Some(entry) if is_same_file(&*self.files, &entry.path, &body) => {
+                entry.last_used = Instant::now();
+                entry.path.clone()
+            }
+            _ => {
+                // Either the handle was issued by an earlier run of the server, or the file has
+                // been renamed since it was last seen:
+                self.paths.remove(&key);
+                if self.is_missing(key) {
+                    return Err(NfsResult::Stale);
+                }
+
+                self.rescan(body.export_id)?;
+                let Some(entry) = self.paths.get(&key) else {
+                    self.record_miss(key);
+                    return Err(NfsResult::Stale);
+                };
+                entry.path.clone()
+            }
+        };
+
+        let id = self.files.identify(&path).map_err(|_| NfsResult::Stale)?;
+        if self.files.generation(&path, &id) != body.generation {
+            return Err(NfsResult::Stale);
+        }
+
+        Ok(path)
+    }
+
+    /// Re-index every file in an export.
+    fn rescan(&mut self, export_id: u32) -> Result<(), NfsResult> {
+        let Some(export) = self.exports.iter().find(|e| e.id == export_id) else {
+            return Err(NfsResult::Stale);
+        };
+
+        let root = export.root.clone();
+        self.index(export_id, &root);
+        self.collect_if_grown();
+
+        Ok(())
+    }
+
+    /// Index the file at `path`, and everything beneath it but other exports nested in it, whose
+    /// files have handles of their own. Files that cannot be identified or read, for example
+    /// because they were removed during the walk, are skipped.
+    fn index(&mut self, export_id: u32, path: &Path) {
+        if self
+            .exports
+            .iter()
+            .any(|e| e.id != export_id && e.root == path)
+        {
+            return;
+        }
+
+        let id = match self.files.identify(path) {
+            Ok(id) => id,
+            Err(e) => {
+                warn!("could not index {}: {e}", path.display());
+                return;
+            }
+        };
+        self.paths.insert(
+            (export_id, id.ino),
+            Entry::new(&*self.files, path.to_path_buf(), &id),
+        );
+
+        if id.is_dir {
+            match self.files.entries(path) {
+                Ok(entries) => {
+                    for entry in entries {
+                        self.index(export_id, &entry);
+                    }
+                }
+                Err(e) => warn!("could not index the entries of {}: {e}", path.display()),
+            }
+        }
+    }
+
+    /// Whether a rescan recently did not find the file with `key`.
+    fn is_missing(&self, key: (u32, u64)) -> bool {
+        self.misses
+            .get(&key)
+            .is_some_and(|missed| missed.elapsed() < MISS_RETENTION)
+    }
+
+    fn record_miss(&mut self, key: (u32, u64)) {
+        if self.misses.len() >= MAX_MISSES {
+            self.misses
+                .retain(|_, missed| missed.elapsed() < MISS_RETENTION);
+        }
+        if self.misses.len() < MAX_MISSES {
+            self.misses.insert(key, Instant::now());
+        }
+    }
+
+    fn insert(&mut self, key: (u32, u64), entry: Entry) {
+        self.misses.remove(&key);
+        self.paths.insert(key, entry);
+        self.collect_if_grown();
+    }
+
+    /// Collect the cache if it has doubled in size since the last collection, so that the cost of
+    /// collecting is spread over the insertions that made it necessary.
+    fn collect_if_grown(&mut self) {
+        if self.paths.len() >= self.next_collection {
+            self.collect();
+        }
+    }
+
+    /// Remove the entries that have not been used within the retention period and whose files have
+    /// been removed, or replaced by another file with the same inode number.
+    pub fn collect(&mut self) {
+        let before = self.paths.len();
+        let now = Instant::now();
+        let retention = self.retention;
+        let files = &*self.files;
+        self.paths.retain(|&(_, inode), entry| {
+            now.duration_since(entry.last_used) < retention || entry.is_current(files, inode)
+        });
+
+        let collected = before - self.paths.len();
+        self.stats.collections += 1;
+        self.stats.collected += collected as u64;
+        self.next_collection = (2 * self.paths.len()).max(MIN_COLLECTION_SIZE);
+        debug!(
+            "collected {collected} file handle entries, {} remain",
+            self.paths.len()
+        );
+    }
+
+    fn mac(&self, body: &[u8; BODY_SIZE]) -> [u8; MAC_SIZE] {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key size");
+        mac.update(body);
+        mac.finalize().into_bytes()[..MAC_SIZE].try_into().unwrap()
+    }
+
+    fn seal(&self, body: &HandleBody) -> Vec<u8> {
+        let body = body.encode();
+
+        let mut handle = Vec::with_capacity(HANDLE_SIZE);
+        handle.extend_from_slice(&body);
+        handle.extend_from_slice(&self.mac(&body));
+        handle
+    }
+
+    fn open(&self, handle: &[u8]) -> Option<HandleBody> {
+        if handle.len() != HANDLE_SIZE {
+            return None;
+        }
+
+        let (body, tag) = handle.split_at(BODY_SIZE);
+        let body: &[u8; BODY_SIZE] = body.try_into().unwrap();
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key size");
+        mac.update(body);
+        mac.verify_truncated_left(tag).ok()?;
+
+        HandleBody::decode(body)
+    }
+}
+
+fn is_same_file(files: &dyn FileSystem, path: &Path, body: &HandleBody) -> bool {
+    files.identify(path).is_ok_and(|id| id.ino == body.inode)
+}
+
+fn load_or_create_key(key_file: &Path) -> io::Result<[u8; KEY_SIZE]> {
+    let mut key = [0; KEY_SIZE];
+
+    match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(key_file)
+    {
+        Ok(mut file) => {
+            fs::File::open("/dev/urandom")?.read_exact(&mut key)?;
+            file.write_all(&key)?;
+            file.sync_all()?;
+            info!("created file handle key {}", key_file.display());
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            fs::File::open(key_file)?.read_exact(&mut key)?;
+        }
+        Err(e) => return Err(e),
+    }
+
+    Ok(key)
+}
+
+/// The inode generation number, which distinguishes a file from an earlier file that had the same
+/// inode number, on file systems that support it.
+#[cfg(target_os = "linux")]
+fn generation(path: &Path, id: &FileId) -> u32 {
+    use std::os::fd::AsRawFd;
+
+    // Opening other types of file may block or have side effects:
+    if !id.is_file && !id.is_dir {
+        return 0;
+    }
+
+    let Ok(file) = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+        .open(path)
+    else {
+        return 0;
+    };
+
+    let mut generation: libc::c_long = 0;
+    // SAFETY: FS_IOC_GETVERSION writes a single long through the pointer.
+    let res = unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETVERSION, &mut generation) };
+    if res < 0 {
+        return 0;
+    }
+
+    generation as u32
+}
+
+#[cfg(not(target_os = "linux"))]
+fn generation(_path: &Path, _id: &FileId) -> u32 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("file_handle_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("export/sub")).unwrap();
+        fs::write(dir.join("export/sub/file"), b"data").unwrap();
+        dir
+    }
+
+    #[test]
+    fn handles_survive_restart() {
+        let dir = scratch_dir("restart");
+        let export = dir.join("export");
+        let file = export.join("sub/file");
+
+        let mut map = FileHandleMap::new(&dir.join("key")).unwrap();
+        map.add_export(&export).unwrap();
+        let handle = map.handle_for(&file).unwrap();
+        assert_eq!(handle.len(), HANDLE_SIZE);
+        assert_eq!(map.lookup(&handle), Ok(file.canonicalize().unwrap()));
+        let root = export.canonicalize().unwrap();
+        assert_eq!(map.export_of_handle(&handle), Some(root.as_path()));
+
+        let mut map = FileHandleMap::new(&dir.join("key")).unwrap();
+        map.add_export(&export).unwrap();
+        assert_eq!(map.lookup(&handle), Ok(file.canonicalize().unwrap()));
+
+        fs::remove_file(&file).unwrap();
+        assert_eq!(map.lookup(&handle), Err(NfsResult::Stale));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn forged_handles_are_rejected() {
+        let dir = scratch_dir("forged");
+        let export = dir.join("export");
+
+        let mut map = FileHandleMap::new(&dir.join("key")).unwrap();
+        map.add_export(&export).unwrap();
+        let mut handle = map.handle_for(&export.join("sub")).unwrap();
+
+        handle[15] ^= 1;
+        assert_eq!(map.lookup(&handle), Err(NfsResult::BadHandle));
+        assert_eq!(map.lookup(&handle[..8]), Err(NfsResult::BadHandle));
+        assert_eq!(map.export_of_handle(&handle), None);
+
+        let mut other = FileHandleMap::new(&dir.join("other_key")).unwrap();
+        other.add_export(&export).unwrap();
+        handle[15] ^= 1;
+        assert_eq!(other.lookup(&handle), Err(NfsResult::BadHandle));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// The local file system, but for one file that cannot be identified, counting the walks of
+    /// the directory at `root`.
+    struct Flaky {
+        root: PathBuf,
+        broken: PathBuf,
+        walks: std::sync::atomic::AtomicUsize,
+    }
+
+    impl FileSystem for Flaky {
+        fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+            LocalFileSystem.canonicalize(path)
+        }
+
+        fn identify(&self, path: &Path) -> io::Result<FileId> {
+            if path == self.broken {
+                return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+            }
+            LocalFileSystem.identify(path)
+        }
+
+        fn generation(&self, path: &Path, id: &FileId) -> u32 {
+            LocalFileSystem.generation(path, id)
+        }
+
+        fn entries(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+            if dir == self.root {
+                self.walks
+                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+            LocalFileSystem.entries(dir)
+        }
+    }
+
+    #[test]
+    fn rescans_skip_errors_and_remember_misses() {
+        let dir = scratch_dir("rescan");
+        let export = dir.join("export").canonicalize().unwrap();
+        for name in ["a", "broken", "z", "gone"] {
+            fs::write(export.join(name), b"").unwrap();
+        }
+
+        let mut map = FileHandleMap::new(&dir.join("key")).unwrap();
+        map.add_export(&export).unwrap();
+        let handles: Vec<_> = ["a", "z", "sub/file", "gone"]
+            .into_iter()
+            .map(|name| map.handle_for(&export.join(name)).unwrap())
+            .collect();
+        fs::remove_file(export.join("gone")).unwrap();
+
+        let files = Arc::new(Flaky {
+            root: export.clone(),
+            broken: export.join("broken"),
+            walks: Default::default(),
+        });
+        let mut map = FileHandleMap::with_file_system(&dir.join("key"), files.clone()).unwrap();
+        map.add_export(&export).unwrap();
+
+        // The file that cannot be identified does not keep the others from being found:
+        assert_eq!(map.lookup(&handles[0]), Ok(export.join("a")));
+        assert_eq!(map.lookup(&handles[1]), Ok(export.join("z")));
+        assert_eq!(map.lookup(&handles[2]), Ok(export.join("sub/file")));
+        let walks = || files.walks.load(std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(walks(), 1);
+
+        // A stale handle has the export walked once, rather than for each time it is sent:
+        for _ in 0..3 {
+            assert_eq!(map.lookup(&handles[3]), Err(NfsResult::Stale));
+        }
+        assert_eq!(walks(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn removed_files_are_collected() {
+        let dir = scratch_dir("collect");
+        let export = dir.join("export");
+
+        let mut map = FileHandleMap::new(&dir.join("key")).unwrap();
+        map.add_export(&export).unwrap();
+        let kept = map.handle_for(&export.join("sub/file")).unwrap();
+
+        // Recently used entries are kept even if their files are gone:
+        fs::write(export.join("recent"), b"").unwrap();
+        map.handle_for(&export.join("recent")).unwrap();
+        fs::remove_file(export.join("recent")).unwrap();
+        map.collect();
+        assert_eq!(map.stats().entries, 2);
+
+        map.set_retention(Duration::ZERO);
+        map.collect();
+        assert_eq!(
+            map.stats(),
+            FileHandleMapStats {
+                entries: 1,
+                collections: 2,
+                collected: 1,
+            }
+        );
+
+        // Growing the cache past the collection threshold collects it. The removed files are kept
+        // open so that their inodes are not reused by the files created after them:
+        let mut open = Vec::new();
+        for i in 0..MIN_COLLECTION_SIZE {
+            let path = export.join(format!("file_{i}"));
+            fs::write(&path, b"").unwrap();
+            map.handle_for(&path).unwrap();
+            open.push(fs::File::open(&path).unwrap());
+            fs::remove_file(&path).unwrap();
+        }
+        let stats = map.stats();
+        assert_eq!(stats.collections, 3);
+        // The kept file, the file whose insertion started the collection (which had not been
+        // removed yet), and the file inserted after it:
+        assert_eq!(stats.entries, 3);
+        assert_eq!(
+            map.lookup(&kept),
+            Ok(export.join("sub/file").canonicalize().unwrap())
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Create and remove many files, checking that the cache stays small. The number of files can
+    /// be set with NFS_HANDLE_STRESS_FILES.
+    #[test]
+    #[ignore = "creates a million files; run with --ignored"]
+    fn collection_stress() {
+        let files: usize = std::env::var("NFS_HANDLE_STRESS_FILES")
+            .map(|n| n.parse().unwrap())
+            .unwrap_or(1_000_000);
+        let dir = scratch_dir("stress");
+        let export = dir.join("export");
+
+        let mut map = FileHandleMap::new(&dir.join("key")).unwrap();
+        map.add_export(&export).unwrap();
+        map.set_retention(Duration::ZERO);
+
+        // Keep a batch of files at a time, so that collections find some current entries. Removed
+        // files are kept open until the end of the next batch, so that their entries are not just
+        // replaced by new files that reuse their inode numbers:
+        const BATCH: usize = 1000;
+        let mut open = Vec::new();
+        let mut max_entries = 0;
+        for batch in 0..files.div_ceil(BATCH) {
+            let paths: Vec<_> = (0..BATCH)
+                .map(|i| export.join(format!("file_{}", batch * BATCH + i)))
+                .collect();
+            let mut batch_files = Vec::new();
+            for path in paths.iter() {
+                fs::write(path, b"").unwrap();
+                map.handle_for(path).unwrap();
+                batch_files.push(fs::File::open(path).unwrap());
+            }
+            for path in paths.iter() {
+                fs::remove_file(path).unwrap();
+            }
+            open = batch_files;
+            max_entries = max_entries.max(map.stats().entries);
+        }
+
+        let stats = map.stats();
+        eprintln!("{stats:?}, at most {max_entries} entries");
+        assert!(
+            max_entries <= 4 * BATCH + MIN_COLLECTION_SIZE,
+            "{max_entries}"
+        );
+        drop(open);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn removed_exports_go_stale() {
+        let dir = scratch_dir("removed");
+        let export = dir.join("export");
+        let sub = export.join("sub");
+        let file = sub.join("file").canonicalize().unwrap();
+
+        let mut map = FileHandleMap::new(&dir.join("key")).unwrap();
+        map.add_export(&export).unwrap();
+        map.add_export(&sub).unwrap();
+        assert_eq!(
+            map.export_of(&file),
+            Some(sub.canonicalize().unwrap().as_path())
+        );
+
+        let handle = map.handle_for(&file).unwrap();
+        map.remove_export(&sub).unwrap();
+        assert_eq!(map.lookup(&handle), Err(NfsResult::Stale));
+        assert_eq!(
+            map.export_of(&file),
+            Some(export.canonicalize().unwrap().as_path())
+        );
+        assert_eq!(
+            map.remove_export(&sub).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        // Exporting the directory again makes its handles valid again:
+        map.add_export(&sub).unwrap();
+        assert_eq!(map.lookup(&handle), Ok(file.clone()));
+        assert_eq!(map.exports().count(), 2);
+
+        // Rescanning the outer export leaves the files of the nested one to it:
+        let root_handle = map.handle_for(&export).unwrap();
+        let mut map = FileHandleMap::new(&dir.join("key")).unwrap();
+        map.add_export(&export).unwrap();
+        map.add_export(&sub).unwrap();
+        assert!(map.lookup(&root_handle).is_ok());
+        assert_eq!(map.stats().entries, 1);
+        assert_eq!(map.lookup(&handle), Ok(file));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn aliased_exports_are_rejected() {
+        let dir = scratch_dir("aliased");
+        let export = dir.join("export");
+        std::os::unix::fs::symlink(&export, dir.join("link")).unwrap();
+
+        let mut map = FileHandleMap::new(&dir.join("key")).unwrap();
+        map.add_export(&export).unwrap();
+        map.add_export(&export.join("sub")).unwrap();
+
+        let e = map.add_export(&dir.join("link")).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert!(e.to_string().contains("more than once"), "{e}");
+
+        let e = map.add_export(&export.join("sub/file")).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::NotADirectory);
+
+        // A bind mount of an export is only made if the test has the privileges for it:
+        let bind = dir.join("bind");
+        fs::create_dir(&bind).unwrap();
+        let mounted = std::process::Command::new("mount")
+            .arg("--bind")
+            .arg(&export)
+            .arg(&bind)
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success());
+        if mounted {
+            let e = map.add_export(&bind).unwrap_err();
+            std::process::Command::new("umount")
+                .arg(&bind)
+                .status()
+                .unwrap();
+            assert!(e.to_string().contains("same directory"), "{e}");
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,30 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
+include!(concat!(env!("OUT_DIR"), "/control_proto.rs"));
+
 include!(concat!(env!("OUT_DIR"), "/mount_proto.rs"));
 
 include!(concat!(env!("OUT_DIR"), "/nfs3_xdr.rs"));
+
+include!(concat!(env!("OUT_DIR"), "/nsm_proto.rs"));
+
+include!(concat!(env!("OUT_DIR"), "/suite_proto.rs"));
+
+pub mod capabilities;
+#[cfg(feature = "nfs3-server")]
+pub mod clients;
+#[cfg(feature = "nfs3-server")]
+pub mod config;
+#[cfg(feature = "nfs3-server")]
+pub mod control;
+#[cfg(feature = "nfs3-server")]
+pub mod file_handle;
+pub mod mount;
+#[cfg(feature = "nfs3-server")]
+pub mod netgroup;
+pub mod read_digest;
+#[cfg(feature = "nfs3-server")]
+pub mod resolver;
+pub mod status;
+pub mod write_digest;
@@ -0,0 +1,428 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Calls to a remote mount service. The usual way to get the root file handle of an export is:
+//
+//     let export = mount::client::mount("server", Path::new("/export"), Duration::from_secs(10))?;
+//
+// which finds the mount service through the RPCBIND server on the host, and calls MNT. The MNT
+// result lists the auth flavors the server accepts for the export, from which the client picks the
+// one to send NFS calls with:
+//
+//     let flavor = export.select_flavor(CLIENT_FLAVORS)?;
+//     let cred = credential(&flavor).unwrap();
+
+use std::{
+    fmt, io,
+    io::{Read, Write},
+    net::TcpStream,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use log::*;
+
+use crate::{
+    mount_proto::{
+        procedures::*, ExportNode, Exports, MountArgs, MountBody, MountList, MountResult,
+        MountStatus,
+    },
+    nfs3_xdr::FileHandle,
+};
+use rpc_protocol::{client::do_rpc_call, AuthFlavor, AuthSysParms, OpaqueAuth};
+
+/// The auth flavors this client can make credentials for (see [`credential`]), weakest first.
+pub const CLIENT_FLAVORS: &[AuthFlavor] = &[AuthFlavor::None, AuthFlavor::Sys];
+
+/// The flavors a server may list in a MNT result, weakest first. Servers list the Kerberos
+/// pseudo-flavors of RPCSEC_GSS (RFC 2623, section 2.2) rather than RPCSEC_GSS itself.
+const FLAVOR_STRENGTH: [i32; 6] = [
+    0,      // AUTH_NONE
+    1,      // AUTH_SYS
+    3,      // AUTH_DH
+    390003, // RPCSEC_GSS with Kerberos 5
+    390004, // and integrity protection
+    390005, // and privacy
+];
+
+/// The most groups an AUTH_SYS credential can carry.
+const MAX_SYS_GROUPS: usize = 16;
+
+/// An export mounted by [`mnt`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct MountedExport {
+    /// The file handle of the root of the export.
+    pub root: FileHandle,
+
+    /// The auth flavors the server accepts for NFS calls to the export, in order of preference.
+    pub auth_flavors: Vec<i32>,
+}
+
+impl MountedExport {
+    /// Choose the flavor to send NFS calls to the export with: the strongest flavor in
+    /// `candidates` that the server accepts. A server that lists no flavors is taken to accept
+    /// AUTH_SYS, as Linux clients do.
+    ///
+    /// To insist on one flavor, pass just that one.
+    pub fn select_flavor(&self, candidates: &[AuthFlavor]) -> Result<AuthFlavor, Error> {
+        let accepted = match self.auth_flavors.as_slice() {
+            [] => &[AuthFlavor::Sys.number()][..],
+            flavors => flavors,
+        };
+
+        candidates
+            .iter()
+            .filter(|flavor| accepted.contains(&flavor.number()))
+            .max_by_key(|flavor| FLAVOR_STRENGTH.iter().position(|&f| f == flavor.number()))
+            .cloned()
+            .ok_or_else(|| Error::NoCommonFlavor(accepted.to_vec()))
+    }
+}
+
+/// A credential of `flavor` for this process, or None if this client cannot make credentials of
+/// that flavor. An AUTH_SYS credential carries the process's user and group ids, and the first
+/// groups of its supplementary groups, as many as fit.
+pub fn credential(flavor: &AuthFlavor) -> Option<OpaqueAuth> {
+    use nix::unistd;
+
+    match flavor {
+        AuthFlavor::None => Some(OpaqueAuth::none()),
+        AuthFlavor::Sys => {
+            let stamp = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |t| t.as_secs() as u32);
+            let mut gids: Vec<u32> = unistd::getgroups()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|gid| gid.as_raw())
+                .collect();
+            gids.truncate(MAX_SYS_GROUPS);
+
+            Some(OpaqueAuth::sys(&AuthSysParms {
+                stamp,
+                machinename: unistd::gethostname().unwrap_or_default(),
+                uid: unistd::getuid().as_raw(),
+                gid: unistd::getgid().as_raw(),
+                gids,
+            }))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The call itself failed, or the mount service could not be found.
+    Rpc(rpc_protocol::Error),
+
+    /// The result of a call could not be decoded.
+    Decode,
+
+    /// The server refused to mount the export.
+    Mount(MountStatus),
+
+    /// The server accepts none of the flavors the client offered: the flavors it does accept.
+    NoCommonFlavor(Vec<i32>),
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Rpc(e) => write!(f, "{e}"),
+            Self::Decode => write!(f, "Could not decode the result"),
+            Self::Mount(status) => write!(f, "MNT failed: {status}"),
+            Self::NoCommonFlavor(accepted) => write!(
+                f,
+                "The server accepts none of the auth flavors offered; it accepts {accepted:?}"
+            ),
+        }
+    }
+}
+
+impl From<rpc_protocol::Error> for Error {
+    fn from(e: rpc_protocol::Error) -> Self {
+        Self::Rpc(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Rpc(e.into())
+    }
+}
+
+/// Connect to the mount service on `host`, whose port is looked up with the RPCBIND server on
+/// `host`. Connecting, and each call made on the returned stream, time out after `timeout`.
+pub fn connect(host: &str, timeout: Duration) -> Result<TcpStream, rpc_protocol::Error> {
+    let addr = rpcbind::client::lookup(host, MOUNT_PROGRAM, MOUNT_V3::VERSION, timeout)?;
+    debug!("mount service on {host} is at {addr}");
+
+    let stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    Ok(stream)
+}
+
+/// Mount the export at `dirpath` on `host`. See [`connect`] for how the mount service is found.
+pub fn mount(host: &str, dirpath: &Path, timeout: Duration) -> Result<MountedExport, Error> {
+    let mut stream = connect(host, timeout)?;
+
+    mnt(&mut stream, dirpath)
+}
+
+/// Call MNT for the export at `dirpath`.
+pub fn mnt<S: Read + Write>(stream: &mut S, dirpath: &Path) -> Result<MountedExport, Error> {
+    let args = MountArgs {
+        dirpath: dirpath.into(),
+    };
+
+    let res = do_rpc_call(
+        stream,
+        MOUNT_PROGRAM,
+        MOUNT_V3::VERSION,
+        MOUNT_V3::MOUNTPROC3_MNT,
+        &args.serialize_alloc(),
+    )?;
+
+    let mut result = MountResult::default();
+    if result.deserialize(&mut res.as_slice()).is_err() {
+        return Err(Error::Decode);
+    }
+
+    match result {
+        MountResult::Ok(ok) => Ok(MountedExport {
+            root: FileHandle { data: ok.fhandle },
+            auth_flavors: ok.auth_flavors,
+        }),
+        // The failure arm is void, so the status is only found in the encoded result:
+        MountResult::Default => {
+            let mut status = MountStatus::default();
+            match status.deserialize(&mut res.as_slice()) {
+                Ok(()) => Err(Error::Mount(status)),
+                Err(_) => Err(Error::Decode),
+            }
+        }
+    }
+}
+
+/// Call UMNT for the export at `dirpath`, to tell the server it is no longer mounted by this
+/// client.
+pub fn umnt<S: Read + Write>(stream: &mut S, dirpath: &Path) -> Result<(), Error> {
+    // UMNT takes the same argument as MNT, a single path:
+    let args = MountArgs {
+        dirpath: dirpath.into(),
+    };
+
+    do_rpc_call(
+        stream,
+        MOUNT_PROGRAM,
+        MOUNT_V3::VERSION,
+        MOUNT_V3::MOUNTPROC3_UMNT,
+        &args.serialize_alloc(),
+    )?;
+
+    Ok(())
+}
+
+/// Call DUMP for the mounts that the server has recorded: the client host and the directory of
+/// each, in the server's order.
+pub fn dump<S: Read + Write>(stream: &mut S) -> Result<Vec<MountBody>, Error> {
+    let res = do_rpc_call(
+        stream,
+        MOUNT_PROGRAM,
+        MOUNT_V3::VERSION,
+        MOUNT_V3::MOUNTPROC3_DUMP,
+        &[],
+    )?;
+
+    let mut list = MountList::default();
+    if list.deserialize(&mut res.as_slice()).is_err() {
+        return Err(Error::Decode);
+    }
+
+    Ok(list.inner)
+}
+
+/// Call EXPORT for the server's exports: the directory of each, and the clients that may mount it
+/// (any, if none are listed).
+pub fn export<S: Read + Write>(stream: &mut S) -> Result<Vec<ExportNode>, Error> {
+    let res = do_rpc_call(
+        stream,
+        MOUNT_PROGRAM,
+        MOUNT_V3::VERSION,
+        MOUNT_V3::MOUNTPROC3_EXPORT,
+        &[],
+    )?;
+
+    let mut list = Exports::default();
+    if list.deserialize(&mut res.as_slice()).is_err() {
+        return Err(Error::Decode);
+    }
+
+    Ok(list.inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mount_proto::{GroupNode, Groups, MountResultOk};
+    use rpc_protocol::{server::*, Call};
+    use test_harness::{spawn_program, Fault};
+
+    /// A mount service that exports only "/export".
+    fn fake_mnt(call: &Call, _state: &mut ()) -> RpcResult {
+        let mut args = MountArgs::default();
+        let mut arg = call.arg;
+        if args.deserialize(&mut arg).is_err() {
+            return RpcResult::GarbageArgs;
+        }
+
+        if args.dirpath != "/export" {
+            return RpcResult::Success(MountStatus::NoEnt.serialize_alloc());
+        }
+
+        let result = MountResult::Ok(MountResultOk {
+            fhandle: vec![1, 2, 3, 4],
+            auth_flavors: vec![1],
+        });
+        RpcResult::Success(result.serialize_alloc())
+    }
+
+    #[test]
+    fn mount_and_unmount() {
+        let procedures: Vec<Option<RpcProcedure<()>>> =
+            vec![None, Some(fake_mnt), None, Some(null_procedure)];
+        let mut client = spawn_program(RpcProgram::new(
+            MOUNT_PROGRAM,
+            MOUNT_V3::VERSION,
+            MOUNT_V3::VERSION,
+            procedures,
+            (),
+        ));
+
+        let export = mnt(&mut client, Path::new("/export")).unwrap();
+        assert_eq!(
+            export,
+            MountedExport {
+                root: FileHandle {
+                    data: vec![1, 2, 3, 4]
+                },
+                auth_flavors: vec![1],
+            }
+        );
+
+        assert!(matches!(
+            mnt(&mut client, Path::new("/elsewhere")),
+            Err(Error::Mount(MountStatus::NoEnt))
+        ));
+
+        umnt(&mut client, Path::new("/export")).unwrap();
+
+        // A reply cut short fails the call, rather than leaving the client waiting:
+        client.inject(Fault::Truncate(12));
+        assert!(matches!(
+            mnt(&mut client, Path::new("/export")),
+            Err(Error::Rpc(rpc_protocol::Error::Io(_)))
+        ));
+    }
+
+    /// A mount service with one client's mount of "/export", and two exports.
+    fn fake_dump(_call: &Call, _state: &mut ()) -> RpcResult {
+        let list = MountList {
+            inner: vec![MountBody {
+                hostname: "client1".into(),
+                directory: "/export".into(),
+            }],
+        };
+        RpcResult::Success(list.serialize_alloc())
+    }
+
+    fn fake_export(_call: &Call, _state: &mut ()) -> RpcResult {
+        let group = |name: &str| GroupNode { name: name.into() };
+        let list = Exports {
+            inner: vec![
+                ExportNode {
+                    dir: "/export".into(),
+                    groups: Groups {
+                        inner: vec![group("10.0.0.0/8"), group("client1")],
+                    },
+                },
+                ExportNode {
+                    dir: "/public".into(),
+                    groups: Groups::default(),
+                },
+            ],
+        };
+        RpcResult::Success(list.serialize_alloc())
+    }
+
+    #[test]
+    fn dump_and_export() {
+        let mut procedures: Vec<Option<RpcProcedure<()>>> = vec![None; 6];
+        procedures[MOUNT_V3::MOUNTPROC3_DUMP as usize] = Some(fake_dump);
+        procedures[MOUNT_V3::MOUNTPROC3_EXPORT as usize] = Some(fake_export);
+        let mut client = spawn_program(RpcProgram::new(
+            MOUNT_PROGRAM,
+            MOUNT_V3::VERSION,
+            MOUNT_V3::VERSION,
+            procedures,
+            (),
+        ));
+
+        let mounts = dump(&mut client).unwrap();
+        assert_eq!(mounts.len(), 1);
+        assert_eq!(mounts[0].hostname, "client1");
+        assert_eq!(mounts[0].directory, "/export");
+
+        let exports = export(&mut client).unwrap();
+        let dirs: Vec<_> = exports.iter().map(|e| e.dir.clone()).collect();
+        assert_eq!(dirs, ["/export", "/public"]);
+        assert_eq!(exports[0].groups.inner.len(), 2);
+        assert!(exports[1].groups.inner.is_empty());
+    }
+
+    #[test]
+    fn select_flavor() {
+        let export = |auth_flavors: &[i32]| MountedExport {
+            root: FileHandle::default(),
+            auth_flavors: auth_flavors.to_vec(),
+        };
+
+        // The strongest flavor both sides support, whatever order the server lists them in:
+        let flavor = export(&[390003, 0, 1]).select_flavor(CLIENT_FLAVORS);
+        assert_eq!(flavor.unwrap(), AuthFlavor::Sys);
+        let flavor = export(&[0]).select_flavor(CLIENT_FLAVORS);
+        assert_eq!(flavor.unwrap(), AuthFlavor::None);
+
+        // No list means AUTH_SYS:
+        let flavor = export(&[]).select_flavor(CLIENT_FLAVORS);
+        assert_eq!(flavor.unwrap(), AuthFlavor::Sys);
+
+        // An override the server does not accept fails:
+        let flavor = export(&[1, 0]).select_flavor(&[AuthFlavor::None]);
+        assert_eq!(flavor.unwrap(), AuthFlavor::None);
+        assert!(matches!(
+            export(&[390005]).select_flavor(CLIENT_FLAVORS),
+            Err(Error::NoCommonFlavor(accepted)) if accepted == [390005]
+        ));
+    }
+
+    #[test]
+    fn sys_credential() {
+        let cred = credential(&AuthFlavor::Sys).unwrap();
+        assert_eq!(cred.flavor, AuthFlavor::Sys);
+
+        let mut params = AuthSysParms::default();
+        params.deserialize(&mut cred.body.as_slice()).unwrap();
+        assert_eq!(params.uid, nix::unistd::getuid().as_raw());
+        assert_eq!(params.gid, nix::unistd::getgid().as_raw());
+        assert!(params.gids.len() <= MAX_SYS_GROUPS);
+
+        assert_eq!(credential(&AuthFlavor::None), Some(OpaqueAuth::none()));
+        assert_eq!(credential(&AuthFlavor::DH), None);
+    }
+}
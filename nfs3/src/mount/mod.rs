@@ -0,0 +1,8 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The MOUNT protocol (RFC 1813, appendix I), which NFS v3 clients use to get the file handle of the
+// root of an export. The protocol types are in the generated `mount_proto` module.
+
+#[cfg(feature = "nfs3-client")]
+pub mod client;
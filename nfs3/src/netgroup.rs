@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Netgroups, which name sets of hosts that an export's clients may refer to as "@group".
+//
+// A netgroup is looked up in a `NetgroupBackend`: a netgroup(5) file such as /etc/netgroup, which
+// `NetgroupFile` reads, or the system's name service switch, which `SystemNetgroups` asks through
+// innetgr(3), and which may in turn consult NIS or LDAP as nsswitch.conf(5) says. Each line of a
+// netgroup file names a group and lists its members, which are triples of a host, a user, and a
+// domain, or the names of other groups:
+//
+//     # name     members
+//     login      (login1,,) (login2,,)
+//     compute    (node1.cluster.example,-,) (node2.cluster.example,-,)
+//     cluster    login compute
+//
+// Only the host of a triple matters for exports. An empty host stands for any host, and "-" for
+// none. A line ending in a backslash continues on the next.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs, io,
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// Where netgroups are looked up.
+pub trait NetgroupBackend: fmt::Debug + Send + Sync {
+    /// Whether `host`, a host name or an address as text, is a member of `netgroup`.
+    fn contains(&self, netgroup: &str, host: &str) -> io::Result<bool>;
+}
+
+/// The netgroups of a netgroup(5) file, which is read again for each lookup, so that it may be
+/// edited while the servers run.
+#[derive(Debug)]
+pub struct NetgroupFile {
+    path: PathBuf,
+}
+
+impl NetgroupFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl NetgroupBackend for NetgroupFile {
+    fn contains(&self, netgroup: &str, host: &str) -> io::Result<bool> {
+        let netgroups: Netgroups = fs::read_to_string(&self.path)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(netgroups.contains(netgroup, host))
+    }
+}
+
+/// The netgroups of the system's name service switch, looked up with innetgr(3).
+#[derive(Debug)]
+pub struct SystemNetgroups;
+
+#[cfg(target_env = "gnu")]
+extern "C" {
+    fn innetgr(
+        netgroup: *const libc::c_char,
+        host: *const libc::c_char,
+        user: *const libc::c_char,
+        domain: *const libc::c_char,
+    ) -> libc::c_int;
+}
+
+impl NetgroupBackend for SystemNetgroups {
+    #[cfg(target_env = "gnu")]
+    fn contains(&self, netgroup: &str, host: &str) -> io::Result<bool> {
+        let netgroup = std::ffi::CString::new(netgroup)?;
+        let host = std::ffi::CString::new(host)?;
+        let res = unsafe {
+            innetgr(
+                netgroup.as_ptr(),
+                host.as_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+
+        Ok(res == 1)
+    }
+
+    #[cfg(not(target_env = "gnu"))]
+    fn contains(&self, _netgroup: &str, _host: &str) -> io::Result<bool> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the C library cannot look up netgroups",
+        ))
+    }
+}
+
+/// The netgroups of a netgroup file, each with its members.
+#[derive(Debug, Default, PartialEq)]
+pub struct Netgroups {
+    groups: HashMap<String, Vec<Member>>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Member {
+    /// The host of a triple, or None for any host. A host of "-" stands for none.
+    Host(Option<String>),
+
+    /// Another netgroup, whose members are members of this one.
+    Group(String),
+}
+
+impl Netgroups {
+    /// Whether `host` is a member of `netgroup`, or of a netgroup that it includes. Host names are
+    /// compared without regard to case. A netgroup that is not defined has no members.
+    pub fn contains(&self, netgroup: &str, host: &str) -> bool {
+        let mut seen = HashSet::new();
+        let mut pending = vec![netgroup];
+        while let Some(group) = pending.pop() {
+            // Groups that include each other would otherwise be searched forever:
+            if !seen.insert(group) {
+                continue;
+            }
+            for member in self.groups.get(group).into_iter().flatten() {
+                match member {
+                    Member::Host(None) => return true,
+                    Member::Host(Some(name)) if name.eq_ignore_ascii_case(host) => return true,
+                    Member::Host(Some(_)) => {}
+                    Member::Group(group) => pending.push(group),
+                }
+            }
+        }
+
+        false
+    }
+}
+
+impl FromStr for Netgroups {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let mut groups = HashMap::new();
+        let text = text.replace("\\\n", " ");
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line);
+            let mut tokens = tokens(line).map_err(|e| format!("line {}: {e}", i + 1))?;
+            let Some(name) = tokens.next() else {
+                continue;
+            };
+            if name.starts_with('(') {
+                return Err(format!("line {}: a netgroup needs a name", i + 1));
+            }
+
+            let members = tokens
+                .map(|token| match token.strip_prefix('(') {
+                    Some(triple) => {
+                        let host = triple.split(',').next().unwrap_or("").trim();
+                        Member::Host((!host.is_empty()).then(|| host.to_string()))
+                    }
+                    None => Member::Group(token.to_string()),
+                })
+                .collect();
+            groups.insert(name.to_string(), members);
+        }
+
+        Ok(Self { groups })
+    }
+}
+
+/// The words of a line of a netgroup file, with triples given as "(host,user,domain" since the
+/// spaces they may hold are kept.
+fn tokens(line: &str) -> Result<impl Iterator<Item = &str>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = line.trim_start();
+    while !rest.is_empty() {
+        let end = match rest.starts_with('(') {
+            true => rest.find(')').ok_or("a triple is missing its \")\"")?,
+            false => rest.find(char::is_whitespace).unwrap_or(rest.len()),
+        };
+        tokens.push(&rest[..end]);
+        rest = rest[end..].trim_start_matches(')').trim_start();
+    }
+
+    Ok(tokens.into_iter())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETGROUPS: &str = "\
+# Hosts that may mount the scratch file system:
+login      (login1,,) ( login2 , alice , example )
+compute    (node1.cluster.example,-,) \\
+           (node2.cluster.example,-,)
+cluster    login compute
+anyone     (,-,)
+nobody     (-,alice,)
+loop       loop cluster
+";
+
+    #[test]
+    fn membership() {
+        let netgroups: Netgroups = NETGROUPS.parse().unwrap();
+        assert!(netgroups.contains("login", "login1"));
+        assert!(netgroups.contains("login", "LOGIN2"));
+        assert!(netgroups.contains("compute", "node2.cluster.example"));
+        assert!(!netgroups.contains("compute", "login1"));
+        assert!(netgroups.contains("cluster", "node1.cluster.example"));
+        assert!(netgroups.contains("loop", "login1"));
+        assert!(!netgroups.contains("loop", "node3.cluster.example"));
+        assert!(netgroups.contains("anyone", "node3.cluster.example"));
+        assert!(!netgroups.contains("nobody", "alice"));
+        assert!(!netgroups.contains("no-such-group", "login1"));
+
+        assert!("login (login1,,".parse::<Netgroups>().is_err());
+        assert!("(login1,,)".parse::<Netgroups>().is_err());
+        assert_eq!("# nothing\n\n".parse(), Ok(Netgroups::default()));
+    }
+
+    #[test]
+    fn file() {
+        let path = std::env::temp_dir().join(format!("netgroup_{}", std::process::id()));
+        fs::write(&path, NETGROUPS).unwrap();
+
+        let netgroups = NetgroupFile::new(&path);
+        assert!(netgroups.contains("cluster", "login2").unwrap());
+        assert!(!netgroups.contains("login", "node1").unwrap());
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            netgroups.contains("cluster", "login2").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
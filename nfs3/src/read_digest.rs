@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// An extension to READ that lets the client detect corruption of file data in transit, for example
+// by a misbehaving middlebox on a WAN mount where the TCP checksum is too weak to rely on.
+//
+// A client asks for a digest by sending a READ call whose verifier has the private flavor
+// `AuthFlavor::ReadDigest` and an empty body. A server that supports the extension replies with a
+// verifier of the same flavor, whose body is the CRC32C of the data in the READ result, encoded as a
+// big-endian 4-byte integer. The READ arguments and results themselves are unchanged.
+//
+// Other NFS clients never send this verifier, so the server's behavior towards them is unchanged.
+// Other servers may reject a call with this verifier (the Linux server replies AUTH_BADVERF), so the
+// client only asks for a digest when told to.
+
+use std::fmt;
+#[cfg(feature = "nfs3-client")]
+use std::io::{Read, Write};
+
+use rpc_protocol::{AuthFlavor, Call, OpaqueAuth};
+#[cfg(feature = "nfs3-client")]
+use {
+    crate::nfs3_xdr::{procedures::*, ReadArgs, ReadResult},
+    rpc_protocol::client::do_rpc_call_with_auth,
+};
+
+/// The verifier a client sends with a READ call to ask for a digest of the data.
+pub fn request_verifier() -> OpaqueAuth {
+    OpaqueAuth {
+        flavor: AuthFlavor::ReadDigest,
+        body: Vec::new(),
+    }
+}
+
+/// Whether the client asked for a digest of the data in its reply to `call`.
+pub fn requested(call: &Call) -> bool {
+    call.get_verifier().flavor == AuthFlavor::ReadDigest
+}
+
+/// The verifier a server sends with a READ reply that carries `data`.
+pub fn reply_verifier(data: &[u8]) -> OpaqueAuth {
+    OpaqueAuth {
+        flavor: AuthFlavor::ReadDigest,
+        body: crc32c::crc32c(data).to_be_bytes().to_vec(),
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The READ call itself failed.
+    Rpc(rpc_protocol::Error),
+
+    /// The READ result could not be decoded.
+    Decode,
+
+    /// The server did not return a digest, so it probably does not support the extension.
+    MissingDigest,
+
+    /// The data does not match the digest.
+    Mismatch { expected: u32, actual: u32 },
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Rpc(e) => write!(f, "{e}"),
+            Self::Decode => write!(f, "Could not decode READ result"),
+            Self::MissingDigest => write!(f, "Server did not return a digest of the READ data"),
+            Self::Mismatch { expected, actual } => write!(
+                f,
+                "READ data has checksum {actual:#010x}, but the server sent {expected:#010x}"
+            ),
+        }
+    }
+}
+
+impl From<rpc_protocol::Error> for Error {
+    fn from(e: rpc_protocol::Error) -> Self {
+        Self::Rpc(e)
+    }
+}
+
+/// Check the data of a READ result against the verifier the server replied with.
+pub fn check(verf: &OpaqueAuth, data: &[u8]) -> Result<(), Error> {
+    if verf.flavor != AuthFlavor::ReadDigest {
+        return Err(Error::MissingDigest);
+    }
+
+    let Ok(expected) = <[u8; 4]>::try_from(verf.body.as_slice()) else {
+        return Err(Error::MissingDigest);
+    };
+
+    let expected = u32::from_be_bytes(expected);
+    let actual = crc32c::crc32c(data);
+    if expected != actual {
+        return Err(Error::Mismatch { expected, actual });
+    }
+
+    Ok(())
+}
+
+/// Do a READ call with the credential `cred`, and if `digest` is true, ask for a digest of the data
+/// and validate it.
+///
+/// Returns the decoded result along with its XDR encoding.
+#[cfg(feature = "nfs3-client")]
+pub fn read<S: Read + Write>(
+    stream: &mut S,
+    args: &ReadArgs,
+    cred: OpaqueAuth,
+    digest: bool,
+) -> Result<(ReadResult, Vec<u8>), Error> {
+    let verf = if digest {
+        request_verifier()
+    } else {
+        OpaqueAuth::none()
+    };
+
+    let (reply_verf, bytes) = do_rpc_call_with_auth(
+        stream,
+        NFS_PROGRAM,
+        NFS_V3::VERSION,
+        NFS_V3::READ,
+        cred,
+        verf,
+        &args.serialize_alloc(),
+    )?;
+
+    let mut res = ReadResult::default();
+    if res.deserialize(&mut bytes.as_slice()).is_err() {
+        return Err(Error::Decode);
+    }
+
+    // Failed READs carry no data, so there is nothing to check:
+    if let (true, ReadResult::Ok(ok)) = (digest, &res) {
+        check(&reply_verf, &ok.data)?;
+    }
+
+    Ok((res, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_digest() {
+        let data = b"some file data";
+        let verf = reply_verifier(data);
+
+        assert!(check(&verf, data).is_ok());
+        assert!(matches!(
+            check(&verf, b"some file dat4"),
+            Err(Error::Mismatch { .. })
+        ));
+        assert!(matches!(
+            check(&OpaqueAuth::none(), data),
+            Err(Error::MissingDigest)
+        ));
+    }
+}
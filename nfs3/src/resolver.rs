@@ -0,0 +1,304 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Host name lookups for matching clients to exports, cached for a time.
+//
+// An export's clients may be given by host name (node17.example), which stands for the addresses
+// it resolves to, or by a wildcard pattern (*.example), which matches the clients whose address
+// reverse resolves to a name that fits it. Each answer, including a failure to resolve, is kept
+// for the TTL of the resolver (`export.name_ttl` in the configuration file), so that a name whose
+// addresses change is followed without a reload, but the name service is not asked on every call.
+//
+// A lookup blocks the caller, which is the thread serving the call that needed it, so a slow name
+// service delays the first call from each client after its entry expires. A reverse lookup is only
+// believed if the name it gives resolves back to the address, so that whoever controls the
+// reverse zone of a network cannot claim an arbitrary name for it.
+//
+// A client is in a netgroup (@group) if its name, or failing that its address, is a member of the
+// group in the resolver's `NetgroupBackend`, which is /etc/netgroup unless configured otherwise.
+
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    hash::Hash,
+    io,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    ptr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use log::*;
+use nix::sys::socket::{SockaddrLike, SockaddrStorage};
+
+use crate::netgroup::{NetgroupBackend, NetgroupFile};
+
+/// The netgroup file read unless configured otherwise.
+pub const DEFAULT_NETGROUP_FILE: &str = "/etc/netgroup";
+
+/// How long a lookup is kept, unless configured otherwise.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+/// Looks up the addresses of a host name.
+pub type ForwardLookup = fn(&str) -> io::Result<Vec<IpAddr>>;
+
+/// Looks up the host name of an address, if it has one.
+pub type ReverseLookup = fn(IpAddr) -> io::Result<Option<String>>;
+
+/// Resolves host names to addresses and addresses to host names, keeping each answer for a time.
+#[derive(Debug)]
+pub struct Resolver {
+    ttl: Duration,
+    forward: Mutex<HashMap<String, Cached<Vec<IpAddr>>>>,
+    reverse: Mutex<HashMap<IpAddr, Cached<Option<String>>>>,
+
+    /// Whether each host, by name or address, is in each netgroup.
+    membership: Mutex<HashMap<(String, String), Cached<bool>>>,
+
+    forward_lookup: ForwardLookup,
+    reverse_lookup: ReverseLookup,
+    netgroups: Box<dyn NetgroupBackend>,
+}
+
+#[derive(Debug)]
+struct Cached<T> {
+    value: T,
+    expires: Instant,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL)
+    }
+}
+
+impl Resolver {
+    /// A resolver that asks the system's name service, and keeps each answer for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self::with_lookups(ttl, system_forward_lookup, system_reverse_lookup)
+    }
+
+    /// A resolver that asks `forward_lookup` and `reverse_lookup` in place of the system's name
+    /// service, as tests do.
+    pub fn with_lookups(
+        ttl: Duration,
+        forward_lookup: ForwardLookup,
+        reverse_lookup: ReverseLookup,
+    ) -> Self {
+        Self {
+            ttl,
+            forward: Mutex::default(),
+            reverse: Mutex::default(),
+            membership: Mutex::default(),
+            forward_lookup,
+            reverse_lookup,
+            netgroups: Box::new(NetgroupFile::new(DEFAULT_NETGROUP_FILE)),
+        }
+    }
+
+    /// Look up netgroups in `netgroups`, in place of the default netgroup file.
+    pub fn with_netgroups(self, netgroups: impl NetgroupBackend + 'static) -> Self {
+        Self {
+            netgroups: Box::new(netgroups),
+            ..self
+        }
+    }
+
+    /// The addresses that `name` resolves to, with IPv4 addresses mapped into IPv6 made plain. A
+    /// name that does not resolve has none, and the failure is logged.
+    pub fn addresses(&self, name: &str) -> Vec<IpAddr> {
+        let name = name.to_ascii_lowercase();
+        cached(&self.forward, name.clone(), self.ttl, || {
+            match (self.forward_lookup)(&name) {
+                Ok(addresses) => {
+                    let mut addresses: Vec<_> =
+                        addresses.into_iter().map(|a| a.to_canonical()).collect();
+                    addresses.sort();
+                    addresses.dedup();
+                    debug!("Client {name} resolved to {addresses:?}");
+                    addresses
+                }
+                Err(e) => {
+                    warn!("Could not resolve client {name}, which is left out: {e}");
+                    Vec::new()
+                }
+            }
+        })
+    }
+
+    /// The host name of `address`, in lower case, if it has one that resolves back to it.
+    pub fn name(&self, address: IpAddr) -> Option<String> {
+        let address = address.to_canonical();
+        cached(&self.reverse, address, self.ttl, || {
+            let name = match (self.reverse_lookup)(address) {
+                Ok(name) => name?.to_ascii_lowercase(),
+                Err(e) => {
+                    warn!("Could not look up the name of client {address}: {e}");
+                    return None;
+                }
+            };
+            if !self.addresses(&name).contains(&address) {
+                warn!("Client {address} is named {name}, which does not resolve to it");
+                return None;
+            }
+            debug!("Client {address} is named {name}");
+
+            Some(name)
+        })
+    }
+
+    /// Whether a client at `address` is in `netgroup`, by its name if it has one, or else by its
+    /// address. A netgroup that cannot be looked up has no members, and the failure is logged.
+    pub fn in_netgroup(&self, netgroup: &str, address: IpAddr) -> bool {
+        let host = self
+            .name(address)
+            .unwrap_or_else(|| address.to_canonical().to_string());
+        let key = (netgroup.to_string(), host.clone());
+        cached(&self.membership, key, self.ttl, || {
+            match self.netgroups.contains(netgroup, &host) {
+                Ok(member) => {
+                    debug!(
+                        "Client {host} is {}in netgroup {netgroup}",
+                        if member { "" } else { "not " }
+                    );
+                    member
+                }
+                Err(e) => {
+                    warn!("Could not look up netgroup {netgroup}: {e}");
+                    false
+                }
+            }
+        })
+    }
+}
+
+/// The value for `key` in `cache`, or what `lookup` returns, which is kept for `ttl`. The cache is
+/// not locked during the lookup, so that other lookups are not held up by a slow one.
+fn cached<K: Eq + Hash, T: Clone>(
+    cache: &Mutex<HashMap<K, Cached<T>>>,
+    key: K,
+    ttl: Duration,
+    lookup: impl FnOnce() -> T,
+) -> T {
+    let now = Instant::now();
+    if let Some(entry) = cache.lock().unwrap().get(&key) {
+        if entry.expires > now {
+            return entry.value.clone();
+        }
+    }
+
+    let value = lookup();
+    let mut cache = cache.lock().unwrap();
+    cache.retain(|_, entry| entry.expires > now);
+    cache.insert(
+        key,
+        Cached {
+            value: value.clone(),
+            expires: now + ttl,
+        },
+    );
+
+    value
+}
+
+fn system_forward_lookup(name: &str) -> io::Result<Vec<IpAddr>> {
+    Ok((name, 0).to_socket_addrs()?.map(|a| a.ip()).collect())
+}
+
+/// Look up the name of `address` with getnameinfo(3), which fails with EAI_NONAME if it has none.
+fn system_reverse_lookup(address: IpAddr) -> io::Result<Option<String>> {
+    let address = SockaddrStorage::from(SocketAddr::new(address, 0));
+    let mut host = [0; libc::NI_MAXHOST as usize];
+
+    let res = unsafe {
+        libc::getnameinfo(
+            address.as_ptr(),
+            address.len(),
+            host.as_mut_ptr(),
+            host.len() as libc::socklen_t,
+            ptr::null_mut(),
+            0,
+            libc::NI_NAMEREQD,
+        )
+    };
+    match res {
+        0 => {
+            let host = unsafe { CStr::from_ptr(host.as_ptr()) };
+            Ok(Some(host.to_string_lossy().into_owned()))
+        }
+        libc::EAI_NONAME => Ok(None),
+        libc::EAI_SYSTEM => Err(io::Error::last_os_error()),
+        e => {
+            let message = unsafe { CStr::from_ptr(libc::gai_strerror(e)) };
+            Err(io::Error::other(message.to_string_lossy()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// The lookups of "counted.example", which only one test makes.
+    static COUNTED_LOOKUPS: AtomicUsize = AtomicUsize::new(0);
+
+    fn forward(name: &str) -> io::Result<Vec<IpAddr>> {
+        match name {
+            "counted.example" => {
+                COUNTED_LOOKUPS.fetch_add(1, Ordering::Relaxed);
+                Ok(vec!["10.0.0.5".parse().unwrap()])
+            }
+            "node1.example" => Ok(vec![
+                "10.0.0.1".parse().unwrap(),
+                "10.0.0.1".parse().unwrap(),
+            ]),
+            "liar.example" => Ok(vec!["10.0.0.9".parse().unwrap()]),
+            _ => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn reverse(address: IpAddr) -> io::Result<Option<String>> {
+        Ok(match address.to_string().as_str() {
+            "10.0.0.1" => Some("Node1.Example".into()),
+            "10.0.0.2" => Some("liar.example".into()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn lookups() {
+        let resolver = Resolver::with_lookups(Duration::from_secs(60), forward, reverse);
+        assert_eq!(
+            resolver.addresses("NODE1.example"),
+            ["10.0.0.1".parse::<IpAddr>().unwrap()]
+        );
+        assert!(resolver.addresses("no-such-host.invalid").is_empty());
+
+        assert_eq!(
+            resolver.name("::ffff:10.0.0.1".parse().unwrap()),
+            Some("node1.example".into())
+        );
+        // A name that does not resolve back to the address is not believed:
+        assert_eq!(resolver.name("10.0.0.2".parse().unwrap()), None);
+        assert_eq!(resolver.name("10.0.0.3".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn expiry() {
+        let lookups = || COUNTED_LOOKUPS.load(Ordering::Relaxed);
+
+        let resolver = Resolver::with_lookups(Duration::from_secs(60), forward, reverse);
+        let before = lookups();
+        resolver.addresses("counted.example");
+        resolver.addresses("counted.example");
+        assert_eq!(lookups() - before, 1);
+
+        let resolver = Resolver::with_lookups(Duration::ZERO, forward, reverse);
+        let before = lookups();
+        resolver.addresses("counted.example");
+        resolver.addresses("counted.example");
+        assert_eq!(lookups() - before, 2);
+    }
+}
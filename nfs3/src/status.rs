@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Human-readable descriptions of the status codes returned by the NFS and mount procedures.
+
+use std::fmt;
+
+use crate::{mount_proto::MountStatus, nfs3_xdr::NfsResult};
+
+impl NfsResult {
+    /// The name of the status in RFC 1813, such as "NFS3ERR_NOENT".
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ok => "NFS3_OK",
+            Self::Perm => "NFS3ERR_PERM",
+            Self::NoEnt => "NFS3ERR_NOENT",
+            Self::Io => "NFS3ERR_IO",
+            Self::Nxio => "NFS3ERR_NXIO",
+            Self::Acces => "NFS3ERR_ACCES",
+            Self::Exist => "NFS3ERR_EXIST",
+            Self::XDev => "NFS3ERR_XDEV",
+            Self::NoDev => "NFS3ERR_NODEV",
+            Self::NotDir => "NFS3ERR_NOTDIR",
+            Self::IsDir => "NFS3ERR_ISDIR",
+            Self::Inval => "NFS3ERR_INVAL",
+            Self::FBig => "NFS3ERR_FBIG",
+            Self::NoSpc => "NFS3ERR_NOSPC",
+            Self::RoFs => "NFS3ERR_ROFS",
+            Self::MLink => "NFS3ERR_MLINK",
+            Self::NameTooLong => "NFS3ERR_NAMETOOLONG",
+            Self::NotEmpty => "NFS3ERR_NOTEMPTY",
+            Self::Dquot => "NFS3ERR_DQUOT",
+            Self::Stale => "NFS3ERR_STALE",
+            Self::Remote => "NFS3ERR_REMOTE",
+            Self::BadHandle => "NFS3ERR_BADHANDLE",
+            Self::NotSync => "NFS3ERR_NOT_SYNC",
+            Self::BadCookie => "NFS3ERR_BAD_COOKIE",
+            Self::NotSupp => "NFS3ERR_NOTSUPP",
+            Self::TooSmall => "NFS3ERR_TOOSMALL",
+            Self::ServerFault => "NFS3ERR_SERVERFAULT",
+            Self::Badtype => "NFS3ERR_BADTYPE",
+            Self::Jukebox => "NFS3ERR_JUKEBOX",
+        }
+    }
+
+    /// A short description of the status.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Ok => "Success",
+            Self::Perm => "Operation not permitted",
+            Self::NoEnt => "No such file or directory",
+            Self::Io => "I/O error",
+            Self::Nxio => "No such device or address",
+            Self::Acces => "Permission denied",
+            Self::Exist => "File exists",
+            Self::XDev => "Cross-device link",
+            Self::NoDev => "No such device",
+            Self::NotDir => "Not a directory",
+            Self::IsDir => "Is a directory",
+            Self::Inval => "Invalid argument",
+            Self::FBig => "File too large",
+            Self::NoSpc => "No space left on device",
+            Self::RoFs => "Read-only file system",
+            Self::MLink => "Too many links",
+            Self::NameTooLong => "File name too long",
+            Self::NotEmpty => "Directory not empty",
+            Self::Dquot => "Disk quota exceeded",
+            Self::Stale => "Stale file handle",
+            Self::Remote => "Too many levels of remote in path",
+            Self::BadHandle => "Illegal file handle",
+            Self::NotSync => "Update synchronization mismatch",
+            Self::BadCookie => "Directory cookie is stale",
+            Self::NotSupp => "Operation not supported",
+            Self::TooSmall => "Buffer or request is too small",
+            Self::ServerFault => "Server fault",
+            Self::Badtype => "Type not supported by the server",
+            Self::Jukebox => "Request could not be completed in time; try again later",
+        }
+    }
+}
+
+impl fmt::Display for NfsResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.description(), self.name())
+    }
+}
+
+impl MountStatus {
+    /// The name of the status in RFC 1813, such as "MNT3ERR_NOENT".
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ok => "MNT3_OK",
+            Self::Perm => "MNT3ERR_PERM",
+            Self::NoEnt => "MNT3ERR_NOENT",
+            Self::Io => "MNT3ERR_IO",
+            Self::Access => "MNT3ERR_ACCES",
+            Self::NotDir => "MNT3ERR_NOTDIR",
+            Self::Inval => "MNT3ERR_INVAL",
+            Self::NameTooLong => "MNT3ERR_NAMETOOLONG",
+            Self::NotSupp => "MNT3ERR_NOTSUPP",
+            Self::ServerFault => "MNT3ERR_SERVERFAULT",
+        }
+    }
+
+    /// A short description of the status.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Ok => "Success",
+            Self::Perm => "Not owner",
+            Self::NoEnt => "No such file or directory",
+            Self::Io => "I/O error",
+            Self::Access => "Permission denied",
+            Self::NotDir => "Not a directory",
+            Self::Inval => "Invalid argument",
+            Self::NameTooLong => "File name too long",
+            Self::NotSupp => "Operation not supported",
+            Self::ServerFault => "Server fault",
+        }
+    }
+}
+
+impl fmt::Display for MountStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.description(), self.name())
+    }
+}
@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// An extension to WRITE that lets the server, and the client, detect corruption of file data in
+// transit: the counterpart of read_digest.rs for the data that clients send.
+//
+// A client asks for its data to be checked by sending a WRITE call whose verifier has the private
+// flavor `AuthFlavor::WriteDigest`, and whose body is the CRC32C of the data in the WRITE arguments,
+// encoded as a big-endian 4-byte integer. A server that checks it replies with a verifier of the same
+// flavor, whose body is the CRC32C of the data as the server received it, so that the client can
+// tell both that the data was checked and whether it arrived intact. The WRITE arguments and results
+// themselves are unchanged.
+//
+// What the server does with data that does not match is up to its integrity mode (see
+// `config::Integrity`): it may log the WRITE and carry it out anyway, or refuse it with GARBAGE_ARGS
+// before writing anything, so that the client can send it again. Servers that do not check the data,
+// including this crate's server unless told to, reply with another verifier.
+
+use std::fmt;
+#[cfg(feature = "nfs3-client")]
+use std::io::{Read, Write};
+
+use rpc_protocol::{AuthFlavor, Call, OpaqueAuth};
+#[cfg(feature = "nfs3-client")]
+use {
+    crate::nfs3_xdr::{procedures::*, WriteArgs, WriteResult},
+    rpc_protocol::client::do_rpc_call_with_auth,
+};
+
+/// The verifier a client sends with a WRITE call of `data`, to have it checked.
+pub fn request_verifier(data: &[u8]) -> OpaqueAuth {
+    OpaqueAuth {
+        flavor: AuthFlavor::WriteDigest,
+        body: crc32c::crc32c(data).to_be_bytes().to_vec(),
+    }
+}
+
+/// The digest that the client sent with `call`, if it asked for its data to be checked.
+pub fn requested(call: &Call) -> Option<u32> {
+    let verf = call.get_verifier();
+    if verf.flavor != AuthFlavor::WriteDigest {
+        return None;
+    }
+
+    <[u8; 4]>::try_from(verf.body.as_slice())
+        .ok()
+        .map(u32::from_be_bytes)
+}
+
+/// The verifier a server sends with a WRITE reply, once it has checked that `data` is what the
+/// client sent.
+pub fn reply_verifier(data: &[u8]) -> OpaqueAuth {
+    OpaqueAuth {
+        flavor: AuthFlavor::WriteDigest,
+        body: crc32c::crc32c(data).to_be_bytes().to_vec(),
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The WRITE call itself failed, which it does with GARBAGE_ARGS if the server refused data
+    /// that did not match its digest.
+    Rpc(rpc_protocol::Error),
+
+    /// The WRITE result could not be decoded.
+    Decode,
+
+    /// The server did not return a digest, so it did not check the data.
+    MissingDigest,
+
+    /// The data that the server received, and wrote, does not match what was sent.
+    Mismatch { expected: u32, actual: u32 },
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Rpc(e) => write!(f, "{e}"),
+            Self::Decode => write!(f, "Could not decode WRITE result"),
+            Self::MissingDigest => write!(f, "Server did not check the WRITE data"),
+            Self::Mismatch { expected, actual } => write!(
+                f,
+                "WRITE data arrived with checksum {actual:#010x}, but was sent with {expected:#010x}"
+            ),
+        }
+    }
+}
+
+impl From<rpc_protocol::Error> for Error {
+    fn from(e: rpc_protocol::Error) -> Self {
+        Self::Rpc(e)
+    }
+}
+
+/// Check the verifier the server replied to a WRITE of `data` with.
+pub fn check(verf: &OpaqueAuth, data: &[u8]) -> Result<(), Error> {
+    if verf.flavor != AuthFlavor::WriteDigest {
+        return Err(Error::MissingDigest);
+    }
+
+    let Ok(actual) = <[u8; 4]>::try_from(verf.body.as_slice()) else {
+        return Err(Error::MissingDigest);
+    };
+
+    let expected = crc32c::crc32c(data);
+    let actual = u32::from_be_bytes(actual);
+    if expected != actual {
+        return Err(Error::Mismatch { expected, actual });
+    }
+
+    Ok(())
+}
+
+/// Do a WRITE call with the credential `cred`, and if `digest` is true, have the server check the
+/// data, and check that it did.
+///
+/// Returns the decoded result along with its XDR encoding.
+#[cfg(feature = "nfs3-client")]
+pub fn write<S: Read + Write>(
+    stream: &mut S,
+    args: &WriteArgs,
+    cred: OpaqueAuth,
+    digest: bool,
+) -> Result<(WriteResult, Vec<u8>), Error> {
+    let verf = if digest {
+        request_verifier(&args.data)
+    } else {
+        OpaqueAuth::none()
+    };
+
+    let (reply_verf, bytes) = do_rpc_call_with_auth(
+        stream,
+        NFS_PROGRAM,
+        NFS_V3::VERSION,
+        NFS_V3::WRITE,
+        cred,
+        verf,
+        &args.serialize_alloc(),
+    )?;
+
+    let mut res = WriteResult::default();
+    if res.deserialize(&mut bytes.as_slice()).is_err() {
+        return Err(Error::Decode);
+    }
+
+    if digest {
+        check(&reply_verf, &args.data)?;
+    }
+
+    Ok((res, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_digest() {
+        let data = b"some file data";
+        let verf = request_verifier(data);
+        assert_eq!(verf, reply_verifier(data));
+
+        assert!(check(&verf, data).is_ok());
+        assert!(matches!(
+            check(&reply_verifier(b"some file dat4"), data),
+            Err(Error::Mismatch { .. })
+        ));
+        assert!(matches!(
+            check(&OpaqueAuth::none(), data),
+            Err(Error::MissingDigest)
+        ));
+    }
+}
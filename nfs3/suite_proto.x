@@ -0,0 +1,30 @@
+/*
+ * A private protocol through which the daemons in this suite report what they support: the
+ * version of the suite they were built from, the optional features that are enabled, and their
+ * limits. It is not part of any standard, and uses a program number from the range that RFC 5531
+ * leaves to be defined by users.
+ */
+
+const SUITE_MAXNAMELEN = 255;
+
+typedef string SuiteName<SUITE_MAXNAMELEN>;
+
+/* A named limit, such as the most bytes a single READ returns: */
+struct Limit {
+    SuiteName      name;
+    unsigned hyper value;
+};
+
+struct Capabilities {
+    SuiteName daemon;      /* The name of the daemon, such as "nfs_server" */
+    SuiteName release;     /* The version of the suite it was built from */
+    SuiteName features<>;  /* The optional features that are enabled */
+    Limit     limits<>;
+};
+
+program SUITE_PROG {
+    version SUITE_VERS {
+        void         SUITEPROC_NULL(void)         = 0;
+        Capabilities SUITEPROC_CAPABILITIES(void) = 1;
+    } = 1;
+} = 0x20059100;
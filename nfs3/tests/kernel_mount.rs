@@ -0,0 +1,312 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// An interop smoke test that mounts an export of `mountd` and `nfs_server` with the Linux kernel's
+// NFS client, and works with files through the mount. It is mounted once for each way that
+// `nfs_server` can send the data of READ replies, since each puts the replies on the wire
+// differently, and once more with the files kept in memory. The servers check the replies that they
+// can, and the test fails if any is malformed.
+//
+// It needs root, the kernel NFS client, and mount.nfs, so it only runs when NFS_KERNEL_INTEROP is
+// set. The test re-runs itself under unshare(1) in new mount and network namespaces, so that the
+// servers (including rpcbind, on the usual port) and the mount are not visible to the rest of the
+// system, and disappear with the test even if it fails.
+
+#![cfg(target_os = "linux")]
+
+use std::{
+    env,
+    fs::{self, File},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rpcbind::RpcbindServerAddress;
+
+/// Set to run the test.
+const INTEROP_VAR: &str = "NFS_KERNEL_INTEROP";
+
+/// Set in the copy of the test that runs inside the namespaces.
+const INNER_VAR: &str = "NFS_KERNEL_INTEROP_INNER";
+
+const MOUNT_PORT: u16 = 20048;
+
+/// The values of `--read-replies` to mount with, and the port of the NFS server for each. Only
+/// copied replies can be checked, since checking a reply copies it.
+const READ_REPLIES: [(&str, u16); 3] = [("copy", 2049), ("splice", 2050), ("send-zc", 2051)];
+
+/// The port of the NFS server that keeps its files in memory.
+const MEMORY_PORT: u16 = 2052;
+
+/// The size of the file that is read through the mount, which is not a multiple of 4 so that the
+/// last READ reply is padded.
+const LARGE_SIZE: usize = 1024 * 1024 + 3;
+
+#[test]
+fn kernel_mount() {
+    if env::var_os(INTEROP_VAR).is_none() {
+        eprintln!("skipping kernel_mount: set {INTEROP_VAR}=1 to run it (requires root)");
+        return;
+    }
+
+    if env::var_os(INNER_VAR).is_some() {
+        mount_and_use_export();
+        return;
+    }
+
+    let status = Command::new("unshare")
+        .args(["--mount", "--net", "--fork", "--kill-child"])
+        .arg(env::current_exe().unwrap())
+        .args(["kernel_mount", "--exact", "--nocapture"])
+        .env(INNER_VAR, "1")
+        .status()
+        .expect("unshare(1) should be installed");
+
+    assert!(status.success(), "kernel_mount failed in its namespaces");
+}
+
+/// Child processes that are killed when the test ends.
+struct Servers(Vec<Child>);
+
+impl Drop for Servers {
+    fn drop(&mut self) {
+        for child in self.0.iter_mut() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// The mount point of the export, which is forcibly unmounted if the test fails.
+struct Mount(PathBuf);
+
+impl Mount {
+    fn unmount(self) {
+        run("umount", &[self.0.to_str().unwrap()]);
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for Mount {
+    fn drop(&mut self) {
+        let _ = Command::new("umount").arg("-f").arg(&self.0).status();
+    }
+}
+
+fn mount_and_use_export() {
+    run("ip", &["link", "set", "lo", "up"]);
+    // Keep the mounts made by the test out of the parent namespace:
+    run("mount", &["--make-rprivate", "/"]);
+
+    let dir = env::temp_dir().join(format!("nfs_kernel_mount_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    run("mount", &["-t", "tmpfs", "tmpfs", dir.to_str().unwrap()]);
+
+    let export = dir.join("export");
+    let mnt = dir.join("mnt");
+    let handle_key = dir.join("handle_key");
+    fs::create_dir(&export).unwrap();
+    fs::create_dir(&mnt).unwrap();
+
+    // mountd registers itself with rpcbind as it starts:
+    thread::spawn(|| {
+        rpcbind::server::main(RpcbindServerAddress::Tcp("0.0.0.0:111".to_string()), None);
+    });
+    wait_for_port(111);
+
+    let mut servers = Servers(Vec::new());
+    let mountd_log = dir.join("mountd.log");
+    servers.0.push(
+        Command::new(env!("CARGO_BIN_EXE_mountd"))
+            .arg("--export")
+            .arg(&export)
+            .arg("--handle-key")
+            .arg(&handle_key)
+            .arg("--check-replies")
+            .stderr(log_file(&mountd_log))
+            .spawn()
+            .unwrap(),
+    );
+    wait_for_port(MOUNT_PORT);
+
+    for (read_replies, port) in READ_REPLIES {
+        eprintln!("mounting with --read-replies {read_replies}");
+        let log = dir.join(format!("nfs_server_{read_replies}.log"));
+        let mut server = nfs_server(port, &export, &handle_key, &log);
+        server.args(["--read-replies", read_replies]);
+        if read_replies == "copy" {
+            server.arg("--check-replies");
+        }
+        let nfs_server = Servers(vec![server.spawn().unwrap()]);
+        wait_for_port(port);
+
+        let mount = mount(port, &export, &mnt);
+        use_files(&mnt.join(read_replies), &export.join(read_replies));
+
+        mount.unmount();
+        drop(nfs_server);
+        assert_no_malformed_replies(&log);
+    }
+
+    eprintln!("mounting with --backend memory");
+    let log = dir.join("nfs_server_memory.log");
+    let mut server = nfs_server(MEMORY_PORT, &export, &handle_key, &log);
+    server.args(["--backend", "memory", "--check-replies"]);
+    let nfs_server = Servers(vec![server.spawn().unwrap()]);
+    wait_for_port(MEMORY_PORT);
+
+    let mount = mount(MEMORY_PORT, &export, &mnt);
+    use_memory_files(&mnt, &export);
+
+    mount.unmount();
+    drop(nfs_server);
+    assert_no_malformed_replies(&log);
+
+    assert_no_malformed_replies(&mountd_log);
+}
+
+/// An `nfs_server` command for `export`, listening on `port` and logging to `log`.
+fn nfs_server(port: u16, export: &Path, handle_key: &Path, log: &Path) -> Command {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_nfs_server"));
+    server
+        .args(["--port", &port.to_string()])
+        .arg("--export")
+        .arg(export)
+        .arg("--handle-key")
+        .arg(handle_key)
+        .stderr(log_file(log));
+
+    server
+}
+
+/// Mount `export` from the NFS server on `port` at `mnt`.
+fn mount(port: u16, export: &Path, mnt: &Path) -> Mount {
+    // There is no lock manager, so locking is left to the client:
+    let options = format!("vers=3,tcp,nolock,port={port},mountport={MOUNT_PORT},mountproto=tcp");
+    run(
+        "mount",
+        &[
+            "-t",
+            "nfs",
+            "-o",
+            &options,
+            &format!("127.0.0.1:{}", export.display()),
+            mnt.to_str().unwrap(),
+        ],
+    );
+
+    Mount(mnt.to_path_buf())
+}
+
+/// Work with files through the mount at `mnt`, checking the results in `export`. Both are the same
+/// directory, which does not exist yet, seen from the client and from the server.
+fn use_files(mnt: &Path, export: &Path) {
+    fs::create_dir(export).unwrap();
+    fs::write(export.join("existing"), b"written by the server side\n").unwrap();
+    let large: Vec<u8> = (0..LARGE_SIZE).map(|i| (i % 253) as u8).collect();
+    fs::write(export.join("large"), &large).unwrap();
+
+    // The client has none of these cached, so they are read from the server:
+    assert_eq!(
+        fs::read(mnt.join("existing")).unwrap(),
+        b"written by the server side\n"
+    );
+    assert!(
+        fs::read(mnt.join("large")).unwrap() == large,
+        "large was not read back whole"
+    );
+
+    let data: Vec<u8> = (0..256 * 1024).map(|i| (i % 251) as u8).collect();
+    fs::write(mnt.join("new"), &data).unwrap();
+    assert_eq!(fs::read(export.join("new")).unwrap(), data);
+    assert_eq!(fs::read(mnt.join("new")).unwrap(), data);
+
+    fs::create_dir(mnt.join("dir")).unwrap();
+    fs::write(mnt.join("dir/inner"), b"inner").unwrap();
+    assert!(export.join("dir").is_dir());
+
+    let mut names: Vec<_> = fs::read_dir(mnt)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["dir", "existing", "large", "new"]);
+
+    let e = fs::create_dir(mnt.join("dir")).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::AlreadyExists);
+
+    fs::remove_file(mnt.join("dir/inner")).unwrap();
+    fs::remove_file(mnt.join("new")).unwrap();
+    assert!(!export.join("new").exists());
+}
+
+/// Work with files through the mount at `mnt` of a server that keeps them in memory, checking that
+/// nothing reaches the export directory `export`.
+fn use_memory_files(mnt: &Path, export: &Path) {
+    let before: Vec<_> = fs::read_dir(export).unwrap().collect();
+    assert!(fs::read_dir(mnt).unwrap().next().is_none());
+
+    let large: Vec<u8> = (0..LARGE_SIZE).map(|i| (i % 253) as u8).collect();
+    fs::create_dir(mnt.join("dir")).unwrap();
+    fs::write(mnt.join("dir/large"), &large).unwrap();
+    fs::write(mnt.join("small"), b"small").unwrap();
+
+    // Drop the client's cache, so that the data is read from the server:
+    run("sh", &["-c", "echo 3 > /proc/sys/vm/drop_caches"]);
+    assert!(
+        fs::read(mnt.join("dir/large")).unwrap() == large,
+        "large was not read back whole"
+    );
+    assert_eq!(fs::read(mnt.join("small")).unwrap(), b"small");
+
+    let mut names: Vec<_> = fs::read_dir(mnt)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["dir", "small"]);
+
+    fs::rename(mnt.join("small"), mnt.join("dir/moved")).unwrap();
+    fs::remove_file(mnt.join("dir/moved")).unwrap();
+    fs::remove_file(mnt.join("dir/large")).unwrap();
+    assert_eq!(fs::read_dir(export).unwrap().count(), before.len());
+}
+
+/// Where a server's log is written, which is read again by `assert_no_malformed_replies()`.
+fn log_file(path: &Path) -> Stdio {
+    File::create(path)
+        .unwrap_or_else(|e| panic!("could not create {}: {e}", path.display()))
+        .into()
+}
+
+/// Fail if the server whose log is at `path` logged a reply that did not pass its check.
+fn assert_no_malformed_replies(path: &Path) {
+    let log = fs::read_to_string(path).unwrap();
+    let malformed: Vec<_> = log
+        .lines()
+        .filter(|line| line.contains("Malformed reply"))
+        .collect();
+    assert!(malformed.is_empty(), "{}: {malformed:#?}", path.display());
+}
+
+fn run(program: &str, args: &[&str]) {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .unwrap_or_else(|e| panic!("could not run {program}: {e}"));
+    assert!(status.success(), "{program} {args:?} failed: {status}");
+}
+
+fn wait_for_port(port: u16) {
+    let start = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "nothing is listening on port {port}"
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,9 @@
+fn main() {
+    xdr_codegen::build_helper()
+        .spec_with("nfs4_prot.x", |c| {
+            c.enable_display()
+                .display_timestamp("nfstime4")
+                .display_size("length4")
+        })
+        .run();
+}
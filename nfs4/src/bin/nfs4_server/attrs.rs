@@ -0,0 +1,317 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The attributes of files (RFC 7530, section 5), which are exchanged as a bitmap of the attributes
+// present and their values, encoded one after another in the order of their numbers.
+//
+// GETATTR supports the mandatory attributes, and the recommended ones that describe a file as the
+// GETATTR of NFSv3 does; an attribute that is asked for but not supported is left out of the result.
+// Owners and groups are given as their numeric ids, which the Linux client accepts when id mapping is
+// off. OPEN can set the mode and the size of the file that it creates.
+
+use std::{fs::Metadata, os::unix::fs::MetadataExt, time::Duration};
+
+use nfs4::nfs4_prot::*;
+
+/// The attributes that GETATTR returns, in order.
+const SUPPORTED: &[u32] = &[
+    FATTR4_SUPPORTED_ATTRS,
+    FATTR4_TYPE,
+    FATTR4_FH_EXPIRE_TYPE,
+    FATTR4_CHANGE,
+    FATTR4_SIZE,
+    FATTR4_LINK_SUPPORT,
+    FATTR4_SYMLINK_SUPPORT,
+    FATTR4_NAMED_ATTR,
+    FATTR4_FSID,
+    FATTR4_UNIQUE_HANDLES,
+    FATTR4_LEASE_TIME,
+    FATTR4_RDATTR_ERROR,
+    FATTR4_FILEHANDLE,
+    FATTR4_FILEID,
+    FATTR4_MODE,
+    FATTR4_NUMLINKS,
+    FATTR4_OWNER,
+    FATTR4_OWNER_GROUP,
+    FATTR4_SPACE_USED,
+    FATTR4_TIME_ACCESS,
+    FATTR4_TIME_METADATA,
+    FATTR4_TIME_MODIFY,
+];
+
+fn is_set(bitmap: &[u32], attr: u32) -> bool {
+    bitmap
+        .get(attr as usize / 32)
+        .is_some_and(|word| word & (1 << (attr % 32)) != 0)
+}
+
+fn set(bitmap: &mut Vec<u32>, attr: u32) {
+    let word = attr as usize / 32;
+    if bitmap.len() <= word {
+        bitmap.resize(word + 1, 0);
+    }
+    bitmap[word] |= 1 << (attr % 32);
+}
+
+fn put_opaque(vals: &mut Vec<u8>, bytes: &[u8]) {
+    vals.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    vals.extend_from_slice(bytes);
+    vals.resize(
+        vals.len() + xdr_lib::padded_4byte(bytes.len()) - bytes.len(),
+        0,
+    );
+}
+
+fn file_type(metadata: &Metadata) -> nfs_ftype4 {
+    use std::os::unix::fs::FileTypeExt;
+
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        nfs_ftype4::NF4DIR
+    } else if file_type.is_symlink() {
+        nfs_ftype4::NF4LNK
+    } else if file_type.is_block_device() {
+        nfs_ftype4::NF4BLK
+    } else if file_type.is_char_device() {
+        nfs_ftype4::NF4CHR
+    } else if file_type.is_socket() {
+        nfs_ftype4::NF4SOCK
+    } else if file_type.is_fifo() {
+        nfs_ftype4::NF4FIFO
+    } else {
+        nfs_ftype4::NF4REG
+    }
+}
+
+fn nfs_time(seconds: i64, nseconds: i64) -> nfstime4 {
+    nfstime4 {
+        seconds,
+        nseconds: nseconds as u32,
+    }
+}
+
+/// The change attribute of a file, which changes whenever its data or its attributes do.
+pub fn change(metadata: &Metadata) -> u64 {
+    (metadata.ctime() as u64)
+        .wrapping_mul(1_000_000_000)
+        .wrapping_add(metadata.ctime_nsec() as u64)
+}
+
+/// The attributes in `request` of the file with `metadata`, whose handle is `fh`, on a server whose
+/// clients hold leases of `lease`.
+pub fn attributes(request: &[u32], metadata: &Metadata, fh: &[u8], lease: Duration) -> fattr4 {
+    let mut attrmask = Vec::new();
+    let mut vals = Vec::new();
+
+    for &attr in SUPPORTED.iter().filter(|&&attr| is_set(request, attr)) {
+        set(&mut attrmask, attr);
+        match attr {
+            FATTR4_SUPPORTED_ATTRS => {
+                let mut supported = Vec::new();
+                for &attr in SUPPORTED {
+                    set(&mut supported, attr);
+                }
+                vals.extend_from_slice(&(supported.len() as u32).to_be_bytes());
+                for word in supported {
+                    vals.extend_from_slice(&word.to_be_bytes());
+                }
+            }
+            FATTR4_TYPE => vals.extend_from_slice(&file_type(metadata).serialize_alloc()),
+            FATTR4_FH_EXPIRE_TYPE => vals.extend_from_slice(&FH4_PERSISTENT.to_be_bytes()),
+            FATTR4_CHANGE => vals.extend_from_slice(&change(metadata).to_be_bytes()),
+            FATTR4_SIZE => vals.extend_from_slice(&metadata.size().to_be_bytes()),
+            FATTR4_LINK_SUPPORT | FATTR4_SYMLINK_SUPPORT | FATTR4_UNIQUE_HANDLES => {
+                vals.extend_from_slice(&xdr_lib::serialize_bool(&true))
+            }
+            FATTR4_NAMED_ATTR => vals.extend_from_slice(&xdr_lib::serialize_bool(&false)),
+            FATTR4_FSID => vals.extend_from_slice(
+                &fsid4 {
+                    major: metadata.dev(),
+                    minor: 0,
+                }
+                .serialize_alloc(),
+            ),
+            FATTR4_LEASE_TIME => vals.extend_from_slice(&(lease.as_secs() as u32).to_be_bytes()),
+            FATTR4_RDATTR_ERROR => vals.extend_from_slice(&nfsstat4::NFS4_OK.serialize_alloc()),
+            FATTR4_FILEHANDLE => put_opaque(&mut vals, fh),
+            FATTR4_FILEID => vals.extend_from_slice(&metadata.ino().to_be_bytes()),
+            FATTR4_MODE => vals.extend_from_slice(&(metadata.mode() & 0o7777).to_be_bytes()),
+            FATTR4_NUMLINKS => vals.extend_from_slice(&(metadata.nlink() as u32).to_be_bytes()),
+            FATTR4_OWNER => put_opaque(&mut vals, metadata.uid().to_string().as_bytes()),
+            FATTR4_OWNER_GROUP => put_opaque(&mut vals, metadata.gid().to_string().as_bytes()),
+            FATTR4_SPACE_USED => vals.extend_from_slice(&(metadata.blocks() * 512).to_be_bytes()),
+            FATTR4_TIME_ACCESS => vals.extend_from_slice(
+                &nfs_time(metadata.atime(), metadata.atime_nsec()).serialize_alloc(),
+            ),
+            FATTR4_TIME_METADATA => vals.extend_from_slice(
+                &nfs_time(metadata.ctime(), metadata.ctime_nsec()).serialize_alloc(),
+            ),
+            FATTR4_TIME_MODIFY => vals.extend_from_slice(
+                &nfs_time(metadata.mtime(), metadata.mtime_nsec()).serialize_alloc(),
+            ),
+            _ => unreachable!("attribute {attr} is not supported"),
+        }
+    }
+
+    fattr4 {
+        attrmask,
+        attr_vals: vals,
+    }
+}
+
+/// The attributes with which OPEN creates a file.
+#[derive(Debug, Default, PartialEq)]
+pub struct CreateAttributes {
+    pub mode: Option<u32>,
+    pub size: Option<u64>,
+
+    /// The bitmap of the attributes that were given, which OPEN returns as those it set.
+    pub attrset: Vec<u32>,
+}
+
+impl CreateAttributes {
+    /// Decode the attributes of `attrs`. Fails with NFS4ERR_ATTRNOTSUPP if any but the mode and size
+    /// are given, and with NFS4ERR_BADXDR if their values cannot be decoded.
+    pub fn decode(attrs: &fattr4) -> Result<Self, nfsstat4> {
+        let mut created = Self::default();
+        let mut vals = &attrs.attr_vals[..];
+
+        for attr in 0..attrs.attrmask.len() as u32 * 32 {
+            if !is_set(&attrs.attrmask, attr) {
+                continue;
+            }
+            match attr {
+                FATTR4_SIZE => {
+                    let mut size = 0;
+                    xdr_lib::get_u64(&mut size, &mut vals).map_err(|_| nfsstat4::NFS4ERR_BADXDR)?;
+                    created.size = Some(size);
+                }
+                FATTR4_MODE => {
+                    let mut mode = 0;
+                    xdr_lib::get_u32(&mut mode, &mut vals).map_err(|_| nfsstat4::NFS4ERR_BADXDR)?;
+                    created.mode = Some(mode & 0o7777);
+                }
+                _ => return Err(nfsstat4::NFS4ERR_ATTRNOTSUPP),
+            }
+            set(&mut created.attrset, attr);
+        }
+        if !vals.is_empty() {
+            return Err(nfsstat4::NFS4ERR_BADXDR);
+        }
+
+        Ok(created)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requested_attributes() {
+        let path = std::env::temp_dir().join(format!("attrs_{}", std::process::id()));
+        std::fs::write(&path, b"abc").unwrap();
+        let metadata = std::fs::symlink_metadata(&path).unwrap();
+
+        // ACL is asked for, but not supported:
+        let mut request = Vec::new();
+        for attr in [
+            FATTR4_SIZE,
+            FATTR4_TYPE,
+            FATTR4_ACL,
+            FATTR4_FILEHANDLE,
+            FATTR4_OWNER,
+        ] {
+            set(&mut request, attr);
+        }
+        let attrs = attributes(&request, &metadata, b"handle", Duration::from_secs(90));
+        std::fs::remove_file(&path).unwrap();
+
+        let mut expected = Vec::new();
+        for attr in [FATTR4_SIZE, FATTR4_TYPE, FATTR4_FILEHANDLE, FATTR4_OWNER] {
+            set(&mut expected, attr);
+        }
+        assert_eq!(attrs.attrmask, expected);
+
+        let mut vals = &attrs.attr_vals[..];
+        let mut file_type = nfs_ftype4::NF4DIR;
+        file_type.deserialize(&mut vals).unwrap();
+        assert_eq!(file_type, nfs_ftype4::NF4REG);
+        let mut size = 0;
+        xdr_lib::get_u64(&mut size, &mut vals).unwrap();
+        assert_eq!(size, 3);
+        let mut len = 0;
+        xdr_lib::get_u32(&mut len, &mut vals).unwrap();
+        assert_eq!(
+            xdr_lib::get_opaque(len as usize, &mut vals).unwrap(),
+            b"handle"
+        );
+        let owner = metadata.uid().to_string();
+        xdr_lib::get_u32(&mut len, &mut vals).unwrap();
+        assert_eq!(
+            xdr_lib::get_opaque(len as usize, &mut vals).unwrap(),
+            owner.as_bytes()
+        );
+        assert!(vals.is_empty());
+    }
+
+    #[test]
+    fn supported_attributes() {
+        let metadata = std::fs::symlink_metadata("/").unwrap();
+        let mut request = Vec::new();
+        set(&mut request, FATTR4_SUPPORTED_ATTRS);
+        let attrs = attributes(&request, &metadata, b"root", Duration::from_secs(90));
+
+        let mut vals = &attrs.attr_vals[..];
+        let mut len = 0;
+        xdr_lib::get_u32(&mut len, &mut vals).unwrap();
+        assert_eq!(len, 2);
+        let mut supported = vec![0; 2];
+        for word in &mut supported {
+            xdr_lib::get_u32(word, &mut vals).unwrap();
+        }
+        assert!(SUPPORTED.iter().all(|&attr| is_set(&supported, attr)));
+        assert!(!is_set(&supported, FATTR4_ACL));
+    }
+
+    #[test]
+    fn create_attributes() {
+        let mut attrmask = Vec::new();
+        set(&mut attrmask, FATTR4_MODE);
+        set(&mut attrmask, FATTR4_SIZE);
+        let mut attr_vals = 0_u64.to_be_bytes().to_vec();
+        attr_vals.extend_from_slice(&0o100640_u32.to_be_bytes());
+        let attrs = fattr4 {
+            attrmask: attrmask.clone(),
+            attr_vals,
+        };
+
+        assert_eq!(
+            CreateAttributes::decode(&attrs),
+            Ok(CreateAttributes {
+                mode: Some(0o640),
+                size: Some(0),
+                attrset: attrmask,
+            })
+        );
+
+        let mut short = attrs.clone();
+        short.attr_vals.truncate(10);
+        assert_eq!(
+            CreateAttributes::decode(&short),
+            Err(nfsstat4::NFS4ERR_BADXDR)
+        );
+
+        let mut owner = attrs.clone();
+        set(&mut owner.attrmask, FATTR4_OWNER);
+        assert_eq!(
+            CreateAttributes::decode(&owner),
+            Err(nfsstat4::NFS4ERR_ATTRNOTSUPP)
+        );
+
+        assert_eq!(
+            CreateAttributes::decode(&fattr4::default()),
+            Ok(CreateAttributes::default())
+        );
+    }
+}
@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// A minimal NFSv4.0 server: COMPOUND, with the operations that open, read, and write the files of
+// one exported directory, and the client ids, stateids, and leases that those need. It runs on the
+// io_uring server of `rpc_protocol`, as `nfs_server` does, and shares the file handles of nfs3.
+
+#[cfg(target_os = "linux")]
+use {
+    clap::Parser,
+    log::*,
+    nfs3::{config::LogConfig, file_handle::FileHandleMap},
+    nfs4::{
+        compound::{CompoundState, OpResult, Operations},
+        nfs4_prot::{procedures::*, *},
+    },
+    rpc_protocol::{ring::*, server::RpcResult, Principal},
+    std::{
+        net::SocketAddr,
+        path::PathBuf,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    },
+};
+
+#[cfg(target_os = "linux")]
+mod attrs;
+#[cfg(target_os = "linux")]
+mod ops;
+#[cfg(target_os = "linux")]
+mod state;
+
+#[cfg(target_os = "linux")]
+#[derive(Parser)]
+struct Cli {
+    /// The IPv4 or IPv6 address to listen on.
+    #[arg(long, default_value = "0.0.0.0:2049")]
+    listen: SocketAddr,
+
+    /// The directory to export.
+    #[arg(long, default_value = "/test/nfs/export")]
+    export: PathBuf,
+
+    /// The file holding the key that protects file handles. It is created if it does not exist.
+    #[arg(long, default_value = "/test/nfs/handle_key")]
+    handle_key: PathBuf,
+
+    /// How many seconds a client keeps its state without renewing its lease. A client whose lease
+    /// runs out loses the files that it opened.
+    #[arg(long, default_value_t = 90)]
+    lease_time: u64,
+
+    /// The level to log at, such as "info" or "debug". The RUST_LOG environment variable, if set,
+    /// takes precedence.
+    #[arg(long, default_value = "error")]
+    log_level: String,
+}
+
+#[cfg(target_os = "linux")]
+struct ServerState {
+    /// Maps file handles to the files in the export.
+    handles: FileHandleMap,
+
+    /// The canonical path of the exported directory, which PUTROOTFH and PUTPUBFH lead to.
+    root: PathBuf,
+
+    /// The client ids, open files, and leases of the clients.
+    clients: state::StateTable,
+
+    /// Who made the COMPOUND that is being performed.
+    principal: Principal,
+
+    /// Returned in WRITE and COMMIT results so that clients can detect a server restart.
+    write_verifier: [u8; NFS4_VERIFIER_SIZE as usize],
+}
+
+#[cfg(target_os = "linux")]
+impl Operations for ServerState {
+    fn perform(&mut self, state: &mut CompoundState, op: nfs_argop4) -> OpResult {
+        match op {
+            nfs_argop4::OP_PUTROOTFH | nfs_argop4::OP_PUTPUBFH => ops::putrootfh(self, state),
+            nfs_argop4::OP_PUTFH(args) => ops::putfh(self, state, args),
+            nfs_argop4::OP_LOOKUP(args) => ops::lookup(self, state, args),
+            nfs_argop4::OP_GETATTR(args) => ops::getattr(self, state, args),
+            nfs_argop4::OP_OPEN(args) => ops::open(self, state, args),
+            nfs_argop4::OP_OPEN_CONFIRM(args) => ops::open_confirm(self, state, args),
+            nfs_argop4::OP_CLOSE(args) => ops::close(self, state, args),
+            nfs_argop4::OP_READ(args) => ops::read(self, state, args),
+            nfs_argop4::OP_WRITE(args) => ops::write(self, state, args),
+            nfs_argop4::OP_COMMIT(_) => ops::commit(self, state),
+            nfs_argop4::OP_SETCLIENTID(args) => ops::setclientid(self, args),
+            nfs_argop4::OP_SETCLIENTID_CONFIRM(args) => ops::setclientid_confirm(self, args),
+            nfs_argop4::OP_RENEW(args) => ops::renew(self, args),
+            _ => Err(nfsstat4::NFS4ERR_NOTSUPP.into()),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn main() {
+    let args = Cli::parse();
+    LogConfig {
+        level: args.log_level.clone(),
+    }
+    .init();
+
+    let root = args
+        .export
+        .canonicalize()
+        .unwrap_or_else(|e| panic!("Could not export {}: {e}", args.export.display()));
+    let mut handles = FileHandleMap::new(&args.handle_key)
+        .unwrap_or_else(|e| panic!("Could not load {}: {e}", args.handle_key.display()));
+    handles
+        .add_export(&root)
+        .unwrap_or_else(|e| panic!("Could not export {}: {e}", root.display()));
+
+    let state = ServerState {
+        handles,
+        root,
+        clients: state::StateTable::new(Duration::from_secs(args.lease_time)),
+        principal: Principal::default(),
+        write_verifier: new_write_verifier(),
+    };
+
+    let procedures: Vec<Option<RingProcedure<ServerState>>> = vec![None, Some(compound)];
+    let mut procedure_map =
+        ProcedureMap::new(NFS4_PROGRAM, NFS_V4::VERSION, NFS_V4::VERSION, procedures);
+    procedure_map.set_procedure_names(proc_name);
+
+    let sizes = RingSizes {
+        entries: 1024,
+        max_call_size: ops::MAX_COMPOUND_CALL,
+        receive_memory: 32 * 1024 * 1024,
+    };
+    let mut server = RpcServer::new(args.listen, procedure_map, state, sizes)
+        .unwrap_or_else(|e| panic!("Could not listen on {}: {e}", args.listen));
+    info!(
+        "Exporting {} on {}, with leases of {} seconds",
+        args.export.display(),
+        args.listen,
+        args.lease_time
+    );
+
+    server.main_loop().unwrap();
+}
+
+#[cfg(target_os = "linux")]
+fn new_write_verifier() -> [u8; NFS4_VERIFIER_SIZE as usize] {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time should be after the epoch");
+
+    (now.as_nanos() as u64).to_be_bytes()
+}
+
+/// NFSPROC4_COMPOUND: perform the operations of the call, once the leases that have run out since
+/// the last call are taken away.
+#[cfg(target_os = "linux")]
+fn compound(call: &RingCall, state: &mut ServerState) -> RingResult<ServerState> {
+    state.clients.expire(Instant::now());
+    state.principal = call.get_principal().clone();
+
+    match nfs4::compound::compound(state, call.arg) {
+        Ok(res) => RingResult::Done(RpcResult::Success(res)),
+        Err(_) => RingResult::Done(RpcResult::GarbageArgs),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    eprintln!("nfs4 server only supported on linux.");
+}
@@ -0,0 +1,780 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The operations that the server performs for COMPOUND: those that set the current filehandle,
+// GETATTR, READ, WRITE, and COMMIT, and those that act on client state, which is kept in the
+// `StateTable`.
+//
+// Files are looked up, read, written, and created as the user that the server runs as. The
+// credential of a call only tells the principals of clients apart, so the server should only export
+// directories that every client may change.
+
+use std::{
+    ffi::OsStr,
+    fs::{self, File, FileTimes, Metadata, OpenOptions},
+    io,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{FileExt, OpenOptionsExt},
+    },
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use nfs3::{file_handle::FileHandleMap, nfs3_xdr::NfsResult};
+use nfs4::{compound::*, nfs4_prot::*};
+use rpc_protocol::ring::MAX_CALL_HEADER;
+
+use crate::{attrs, ServerState};
+
+/// The most that a READ returns.
+pub const MAX_READ: u32 = 1024 * 1024;
+
+/// The most that a WRITE writes.
+pub const MAX_WRITE: u32 = 64 * 1024;
+
+/// The size of the largest COMPOUND call: a WRITE of `MAX_WRITE` bytes, with room for the other
+/// operations of the COMPOUND, and the call header.
+pub const MAX_COMPOUND_CALL: u32 = MAX_WRITE + 4096 + MAX_CALL_HEADER;
+
+/// The mode of files created without one.
+const DEFAULT_FILE_MODE: u32 = 0o644;
+
+/// Map an I/O error from the local file system to the closest NFSv4 status.
+pub fn io_status(e: io::Error) -> nfsstat4 {
+    let Some(errno) = e.raw_os_error() else {
+        return nfsstat4::NFS4ERR_IO;
+    };
+
+    match nix::errno::Errno::from_raw(errno) {
+        nix::Error::EPERM => nfsstat4::NFS4ERR_PERM,
+        nix::Error::ENOENT => nfsstat4::NFS4ERR_NOENT,
+        nix::Error::ENXIO => nfsstat4::NFS4ERR_NXIO,
+        nix::Error::EACCES => nfsstat4::NFS4ERR_ACCESS,
+        nix::Error::EEXIST => nfsstat4::NFS4ERR_EXIST,
+        nix::Error::EXDEV => nfsstat4::NFS4ERR_XDEV,
+        nix::Error::ENOTDIR => nfsstat4::NFS4ERR_NOTDIR,
+        nix::Error::EISDIR => nfsstat4::NFS4ERR_ISDIR,
+        nix::Error::EINVAL => nfsstat4::NFS4ERR_INVAL,
+        nix::Error::EFBIG => nfsstat4::NFS4ERR_FBIG,
+        nix::Error::ENOSPC => nfsstat4::NFS4ERR_NOSPC,
+        nix::Error::EROFS => nfsstat4::NFS4ERR_ROFS,
+        nix::Error::EMLINK => nfsstat4::NFS4ERR_MLINK,
+        nix::Error::ENAMETOOLONG => nfsstat4::NFS4ERR_NAMETOOLONG,
+        nix::Error::ENOTEMPTY => nfsstat4::NFS4ERR_NOTEMPTY,
+        nix::Error::EDQUOT => nfsstat4::NFS4ERR_DQUOT,
+        nix::Error::ESTALE => nfsstat4::NFS4ERR_STALE,
+        nix::Error::ELOOP => nfsstat4::NFS4ERR_SYMLINK,
+        _ => nfsstat4::NFS4ERR_IO,
+    }
+}
+
+/// The path of the file whose handle is `fh`.
+fn file_path(handles: &mut FileHandleMap, fh: &[u8]) -> Result<PathBuf, nfsstat4> {
+    handles.lookup(fh).map_err(|e| match e {
+        NfsResult::Stale => nfsstat4::NFS4ERR_STALE,
+        _ => nfsstat4::NFS4ERR_BADHANDLE,
+    })
+}
+
+fn file_handle(handles: &mut FileHandleMap, path: &Path) -> Result<Vec<u8>, nfsstat4> {
+    handles.handle_for(path).map_err(io_status)
+}
+
+/// A name of a directory entry, as LOOKUP and OPEN take it: UTF-8 (RFC 7530, section 12.7), and
+/// neither ".", "..", nor holding a slash.
+fn component(name: &[u8]) -> Result<&OsStr, nfsstat4> {
+    let name = std::str::from_utf8(name).map_err(|_| nfsstat4::NFS4ERR_INVAL)?;
+    if name.is_empty() {
+        return Err(nfsstat4::NFS4ERR_INVAL);
+    }
+    if name == "." || name == ".." || name.contains(['/', '\0']) {
+        return Err(nfsstat4::NFS4ERR_BADNAME);
+    }
+    if name.len() > 255 {
+        return Err(nfsstat4::NFS4ERR_NAMETOOLONG);
+    }
+
+    Ok(OsStr::from_bytes(name.as_bytes()))
+}
+
+/// Fail unless `metadata` is that of a directory, with NFS4ERR_SYMLINK for a symbolic link, which
+/// clients follow themselves.
+fn check_dir(metadata: &Metadata) -> Result<(), nfsstat4> {
+    if metadata.is_symlink() {
+        return Err(nfsstat4::NFS4ERR_SYMLINK);
+    }
+    if !metadata.is_dir() {
+        return Err(nfsstat4::NFS4ERR_NOTDIR);
+    }
+
+    Ok(())
+}
+
+/// Fail unless `metadata` is that of a regular file, which is all that OPEN, READ, WRITE, and
+/// COMMIT act on.
+fn check_regular(metadata: &Metadata) -> Result<(), nfsstat4> {
+    if metadata.is_dir() {
+        return Err(nfsstat4::NFS4ERR_ISDIR);
+    }
+    if metadata.is_symlink() {
+        return Err(nfsstat4::NFS4ERR_SYMLINK);
+    }
+    if !metadata.is_file() {
+        return Err(nfsstat4::NFS4ERR_INVAL);
+    }
+
+    Ok(())
+}
+
+/// The path of the current filehandle's file, which must be a regular file, and its metadata.
+fn regular_file(server: &mut ServerState, fh: &[u8]) -> Result<(PathBuf, Metadata), nfsstat4> {
+    let path = file_path(&mut server.handles, fh)?;
+    let metadata = fs::symlink_metadata(&path).map_err(io_status)?;
+    check_regular(&metadata)?;
+
+    Ok((path, metadata))
+}
+
+pub fn putrootfh(server: &mut ServerState, state: &mut CompoundState) -> OpResult {
+    state.current_fh = Some(file_handle(&mut server.handles, &server.root)?);
+
+    Ok(Vec::new())
+}
+
+pub fn putfh(server: &mut ServerState, state: &mut CompoundState, args: PUTFH4args) -> OpResult {
+    file_path(&mut server.handles, &args.object)?;
+    state.current_fh = Some(args.object);
+
+    Ok(Vec::new())
+}
+
+pub fn lookup(server: &mut ServerState, state: &mut CompoundState, args: LOOKUP4args) -> OpResult {
+    let dir = file_path(&mut server.handles, state.current()?)?;
+    check_dir(&fs::symlink_metadata(&dir).map_err(io_status)?)?;
+    let path = dir.join(component(&args.objname)?);
+    fs::symlink_metadata(&path).map_err(io_status)?;
+
+    state.current_fh = Some(file_handle(&mut server.handles, &path)?);
+
+    Ok(Vec::new())
+}
+
+pub fn getattr(
+    server: &mut ServerState,
+    state: &mut CompoundState,
+    args: GETATTR4args,
+) -> OpResult {
+    let fh = state.current()?;
+    let path = file_path(&mut server.handles, fh)?;
+    let metadata = fs::symlink_metadata(path).map_err(io_status)?;
+    let lease = server.clients.lease();
+
+    Ok(GETATTR4resok {
+        obj_attributes: attrs::attributes(&args.attr_request, &metadata, fh, lease),
+    }
+    .serialize_alloc())
+}
+
+/// Read from `file` at `offset` until `buf` is full or the end of the file is reached. Returns the
+/// number of bytes read.
+fn read_fully(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match file.read_at(&mut buf[len..], offset + len as u64) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(len)
+}
+
+pub fn read(server: &mut ServerState, state: &mut CompoundState, args: READ4args) -> OpResult {
+    let fh = state.current()?;
+    let (path, metadata) = regular_file(server, fh)?;
+
+    let mut data = vec![0; args.count.min(MAX_READ) as usize];
+    let now = Instant::now();
+    let read = match server
+        .clients
+        .file_for(&args.stateid, fh, OPEN4_SHARE_ACCESS_READ, now)?
+    {
+        Some(file) => read_fully(file, &mut data, args.offset),
+        None => File::open(&path).and_then(|file| read_fully(&file, &mut data, args.offset)),
+    };
+    data.truncate(read.map_err(io_status)?);
+
+    Ok(READ4resok {
+        eof: args.offset.saturating_add(data.len() as u64) >= metadata.len(),
+        data,
+    }
+    .serialize_alloc())
+}
+
+/// Write `data` to `file` at `offset`, and flush it to storage as `stable` asks.
+fn write_stable(file: &File, data: &[u8], offset: u64, stable: stable_how4) -> io::Result<()> {
+    file.write_all_at(data, offset)?;
+    match stable {
+        stable_how4::UNSTABLE4 => Ok(()),
+        stable_how4::DATA_SYNC4 => file.sync_data(),
+        stable_how4::FILE_SYNC4 => file.sync_all(),
+    }
+}
+
+pub fn write(server: &mut ServerState, state: &mut CompoundState, args: WRITE4args) -> OpResult {
+    let fh = state.current()?;
+    let (path, _) = regular_file(server, fh)?;
+    if args.data.len() > MAX_WRITE as usize {
+        return Err(nfsstat4::NFS4ERR_INVAL.into());
+    }
+
+    let now = Instant::now();
+    let written = match server
+        .clients
+        .file_for(&args.stateid, fh, OPEN4_SHARE_ACCESS_WRITE, now)?
+    {
+        Some(file) => write_stable(file, &args.data, args.offset, args.stable),
+        None => OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .and_then(|file| write_stable(&file, &args.data, args.offset, args.stable)),
+    };
+    written.map_err(io_status)?;
+
+    Ok(WRITE4resok {
+        count: args.data.len() as u32,
+        committed: args.stable,
+        writeverf: server.write_verifier,
+    }
+    .serialize_alloc())
+}
+
+pub fn commit(server: &mut ServerState, state: &mut CompoundState) -> OpResult {
+    let (path, _) = regular_file(server, state.current()?)?;
+    File::open(path)
+        .and_then(|file| file.sync_all())
+        .map_err(io_status)?;
+
+    Ok(COMMIT4resok {
+        writeverf: server.write_verifier,
+    }
+    .serialize_alloc())
+}
+
+pub fn setclientid(server: &mut ServerState, args: SETCLIENTID4args) -> OpResult {
+    let now = Instant::now();
+    match server
+        .clients
+        .set_client_id(args.client, &server.principal, now)
+    {
+        Ok(resok) => Ok(resok.serialize_alloc()),
+        // The failure names the address of the client that uses the id, which is not known:
+        Err(nfsstat4::NFS4ERR_CLID_INUSE) => Err(OpFailure {
+            status: nfsstat4::NFS4ERR_CLID_INUSE,
+            body: clientaddr4::default().serialize_alloc(),
+        }),
+        Err(status) => Err(status.into()),
+    }
+}
+
+pub fn setclientid_confirm(server: &mut ServerState, args: SETCLIENTID_CONFIRM4args) -> OpResult {
+    let now = Instant::now();
+    server
+        .clients
+        .confirm_client_id(args.clientid, args.setclientid_confirm, now)?;
+
+    Ok(Vec::new())
+}
+
+pub fn renew(server: &mut ServerState, args: RENEW4args) -> OpResult {
+    server.clients.renew(args.clientid, Instant::now())?;
+
+    Ok(Vec::new())
+}
+
+/// The times that record the verifier of an exclusive create: the atime and the mtime each hold
+/// four of its bytes as a number of seconds, as with the Linux server.
+fn verifier_times(verifier: &[u8; 8]) -> FileTimes {
+    let seconds = |bytes: &[u8]| {
+        let seconds = u32::from_be_bytes(bytes.try_into().unwrap());
+        SystemTime::UNIX_EPOCH + Duration::from_secs(seconds.into())
+    };
+
+    FileTimes::new()
+        .set_accessed(seconds(&verifier[..4]))
+        .set_modified(seconds(&verifier[4..]))
+}
+
+/// Open the file at `path` for the share `access`, creating it as `how` says. Returns the file, and
+/// the bitmap of the attributes that were set on it.
+fn open_file(path: &Path, access: u32, how: &openflag4) -> Result<(File, Vec<u32>), nfsstat4> {
+    let mut options = OpenOptions::new();
+    options
+        .read(access & OPEN4_SHARE_ACCESS_READ != 0)
+        .write(access & OPEN4_SHARE_ACCESS_WRITE != 0);
+
+    let createhow = match how {
+        openflag4::OPEN4_CREATE(createhow) => createhow,
+        _ => return Ok((options.open(path).map_err(io_status)?, Vec::new())),
+    };
+
+    // The file may be created even if it is only opened for reading, which OpenOptions::create()
+    // does not allow:
+    let (attrs, exclusive) = match createhow {
+        createhow4::UNCHECKED4(attrs) => (attrs::CreateAttributes::decode(attrs)?, 0),
+        createhow4::GUARDED4(attrs) => (attrs::CreateAttributes::decode(attrs)?, nix::libc::O_EXCL),
+        createhow4::EXCLUSIVE4(verifier) => {
+            let times = verifier_times(verifier);
+            let file = match options
+                .clone()
+                .custom_flags(nix::libc::O_CREAT | nix::libc::O_EXCL)
+                .mode(DEFAULT_FILE_MODE)
+                .open(path)
+            {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    // A retransmission of the OPEN that created the file finds its verifier:
+                    let file = options.open(path).map_err(io_status)?;
+                    if !file.metadata().is_ok_and(|m| has_verifier(&m, verifier)) {
+                        return Err(nfsstat4::NFS4ERR_EXIST);
+                    }
+                    return Ok((file, Vec::new()));
+                }
+                Err(e) => return Err(io_status(e)),
+            };
+            file.set_times(times).map_err(io_status)?;
+            return Ok((file, Vec::new()));
+        }
+    };
+
+    let file = options
+        .custom_flags(nix::libc::O_CREAT | exclusive)
+        .mode(attrs.mode.unwrap_or(DEFAULT_FILE_MODE))
+        .open(path)
+        .map_err(io_status)?;
+    if let Some(size) = attrs.size {
+        OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|truncated| truncated.set_len(size))
+            .map_err(io_status)?;
+    }
+
+    Ok((file, attrs.attrset))
+}
+
+/// Whether the file with `metadata` has the times that record `verifier`.
+fn has_verifier(metadata: &Metadata, verifier: &[u8; 8]) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.atime() == i64::from(u32::from_be_bytes(verifier[..4].try_into().unwrap()))
+        && metadata.mtime() == i64::from(u32::from_be_bytes(verifier[4..].try_into().unwrap()))
+        && metadata.atime_nsec() == 0
+        && metadata.mtime_nsec() == 0
+}
+
+pub fn open(server: &mut ServerState, state: &mut CompoundState, args: OPEN4args) -> OpResult {
+    let OPEN4args {
+        seqid,
+        share_access,
+        share_deny,
+        owner,
+        openhow,
+        claim,
+    } = args;
+    if share_access == 0
+        || share_access & !OPEN4_SHARE_ACCESS_BOTH != 0
+        || share_deny & !OPEN4_SHARE_DENY_BOTH != 0
+    {
+        return Err(nfsstat4::NFS4ERR_INVAL.into());
+    }
+    let name = match claim {
+        open_claim4::CLAIM_NULL(name) => name,
+        // No state survives a restart of the server, so there is none to reclaim:
+        open_claim4::CLAIM_PREVIOUS(_) => return Err(nfsstat4::NFS4ERR_NO_GRACE.into()),
+        // Nor does the server hand out delegations:
+        _ => return Err(nfsstat4::NFS4ERR_NOTSUPP.into()),
+    };
+
+    let now = Instant::now();
+    server.clients.renew(owner.clientid, now)?;
+    let dir = file_path(&mut server.handles, state.current()?)?;
+    let owner = (owner.clientid, owner.owner);
+    let ServerState {
+        handles, clients, ..
+    } = server;
+
+    let resok = clients.sequenced(&owner, seqid, |clients| {
+        let dir_metadata = fs::symlink_metadata(&dir).map_err(io_status)?;
+        check_dir(&dir_metadata)?;
+        let path = dir.join(component(&name)?);
+
+        // The file is checked before it is opened, so that opening a FIFO does not block:
+        let mut access = share_access;
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) => {
+                check_regular(&metadata)?;
+                let fh = file_handle(handles, &path)?;
+                clients.check_share(&fh, share_access, share_deny, &owner)?;
+                access |= clients.held_access(&fh, &owner);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(io_status(e)),
+        }
+
+        let (file, attrset) = open_file(&path, access, &openhow)?;
+        let fh = file_handle(handles, &path)?;
+        let after = fs::symlink_metadata(&dir).map_or(0, |m| attrs::change(&m));
+        let (stateid, confirm) =
+            clients.add_open(&owner, seqid, &fh, file, share_access, share_deny);
+        state.current_fh = Some(fh);
+
+        let mut rflags = OPEN4_RESULT_LOCKTYPE_POSIX;
+        if confirm {
+            rflags |= OPEN4_RESULT_CONFIRM;
+        }
+        Ok(OPEN4resok {
+            stateid,
+            cinfo: change_info4 {
+                atomic: false,
+                before: attrs::change(&dir_metadata),
+                after,
+            },
+            rflags,
+            attrset,
+            delegation: open_delegation4::OPEN_DELEGATE_NONE,
+        })
+    })?;
+
+    Ok(resok.serialize_alloc())
+}
+
+pub fn open_confirm(
+    server: &mut ServerState,
+    state: &mut CompoundState,
+    args: OPEN_CONFIRM4args,
+) -> OpResult {
+    let open_stateid = server.clients.confirm_open(
+        &args.open_stateid,
+        args.seqid,
+        state.current()?,
+        Instant::now(),
+    )?;
+
+    Ok(OPEN_CONFIRM4resok { open_stateid }.serialize_alloc())
+}
+
+pub fn close(server: &mut ServerState, state: &mut CompoundState, args: CLOSE4args) -> OpResult {
+    let stateid = server.clients.close(
+        &args.open_stateid,
+        args.seqid,
+        state.current()?,
+        Instant::now(),
+    )?;
+
+    Ok(stateid.serialize_alloc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::StateTable;
+    use rpc_protocol::Principal;
+
+    fn server(name: &str) -> (ServerState, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("nfs4_ops_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("export")).unwrap();
+        let root = dir.join("export").canonicalize().unwrap();
+
+        let mut handles = FileHandleMap::new(&dir.join("handle_key")).unwrap();
+        handles.add_export(&root).unwrap();
+        let server = ServerState {
+            handles,
+            root,
+            clients: StateTable::new(Duration::from_secs(90)),
+            principal: Principal::default(),
+            write_verifier: *b"verifier",
+        };
+
+        (server, dir)
+    }
+
+    /// Perform a COMPOUND of `ops`, and return the status and the results of the operations.
+    fn call(server: &mut ServerState, ops: Vec<nfs_argop4>) -> (nfsstat4, Vec<nfs_resop4>) {
+        let args = COMPOUND4args {
+            tag: Vec::new(),
+            minorversion: 0,
+            argarray: ops,
+        };
+        let res = nfs4::compound::compound(server, &args.serialize_alloc()).unwrap();
+        let mut decoded = COMPOUND4res::default();
+        decoded.deserialize(&mut &res[..]).unwrap();
+
+        (decoded.status, decoded.resarray)
+    }
+
+    fn client_id(server: &mut ServerState) -> u64 {
+        let (_, res) = call(
+            server,
+            vec![nfs_argop4::OP_SETCLIENTID(SETCLIENTID4args {
+                client: nfs_client_id4 {
+                    verifier: [1; 8],
+                    id: b"client".to_vec(),
+                },
+                ..Default::default()
+            })],
+        );
+        let nfs_resop4::OP_SETCLIENTID(SETCLIENTID4res::NFS4_OK(resok)) = &res[0] else {
+            panic!("SETCLIENTID failed: {res:?}");
+        };
+
+        let confirm = SETCLIENTID_CONFIRM4args {
+            clientid: resok.clientid,
+            setclientid_confirm: resok.setclientid_confirm,
+        };
+        let (status, _) = call(server, vec![nfs_argop4::OP_SETCLIENTID_CONFIRM(confirm)]);
+        assert_eq!(status, nfsstat4::NFS4_OK);
+
+        resok.clientid
+    }
+
+    fn open(clientid: u64, seqid: u32, name: &str, openhow: openflag4) -> nfs_argop4 {
+        nfs_argop4::OP_OPEN(OPEN4args {
+            seqid,
+            share_access: OPEN4_SHARE_ACCESS_BOTH,
+            share_deny: OPEN4_SHARE_DENY_NONE,
+            owner: open_owner4 {
+                clientid,
+                owner: b"owner".to_vec(),
+            },
+            openhow,
+            claim: open_claim4::CLAIM_NULL(name.as_bytes().to_vec()),
+        })
+    }
+
+    fn unchecked() -> openflag4 {
+        openflag4::OPEN4_CREATE(createhow4::UNCHECKED4(fattr4::default()))
+    }
+
+    #[test]
+    fn open_write_read_close() {
+        let (mut server, dir) = server("io");
+        let clientid = client_id(&mut server);
+
+        let (status, res) = call(
+            &mut server,
+            vec![
+                nfs_argop4::OP_PUTROOTFH,
+                open(clientid, 1, "file", unchecked()),
+                nfs_argop4::OP_GETFH,
+            ],
+        );
+        assert_eq!(status, nfsstat4::NFS4_OK);
+        let nfs_resop4::OP_OPEN(OPEN4res::NFS4_OK(opened)) = &res[1] else {
+            panic!("OPEN failed: {res:?}");
+        };
+        assert_ne!(opened.rflags & OPEN4_RESULT_CONFIRM, 0);
+        let nfs_resop4::OP_GETFH(GETFH4res::NFS4_OK(GETFH4resok { object: fh })) = &res[2] else {
+            panic!("GETFH failed: {res:?}");
+        };
+
+        let (status, res) = call(
+            &mut server,
+            vec![
+                nfs_argop4::OP_PUTFH(PUTFH4args { object: fh.clone() }),
+                nfs_argop4::OP_OPEN_CONFIRM(OPEN_CONFIRM4args {
+                    open_stateid: opened.stateid,
+                    seqid: 2,
+                }),
+            ],
+        );
+        assert_eq!(status, nfsstat4::NFS4_OK);
+        let nfs_resop4::OP_OPEN_CONFIRM(OPEN_CONFIRM4res::NFS4_OK(confirmed)) = &res[1] else {
+            panic!("OPEN_CONFIRM failed: {res:?}");
+        };
+        let stateid = confirmed.open_stateid;
+
+        let (status, res) = call(
+            &mut server,
+            vec![
+                nfs_argop4::OP_PUTFH(PUTFH4args { object: fh.clone() }),
+                nfs_argop4::OP_WRITE(WRITE4args {
+                    stateid,
+                    offset: 2,
+                    stable: stable_how4::FILE_SYNC4,
+                    data: b"data".to_vec(),
+                }),
+                nfs_argop4::OP_READ(READ4args {
+                    stateid,
+                    offset: 0,
+                    count: 100,
+                }),
+                nfs_argop4::OP_CLOSE(CLOSE4args {
+                    seqid: 3,
+                    open_stateid: stateid,
+                }),
+            ],
+        );
+        assert_eq!(status, nfsstat4::NFS4_OK);
+        assert_eq!(
+            res[1],
+            nfs_resop4::OP_WRITE(WRITE4res::NFS4_OK(WRITE4resok {
+                count: 4,
+                committed: stable_how4::FILE_SYNC4,
+                writeverf: *b"verifier",
+            }))
+        );
+        assert_eq!(
+            res[2],
+            nfs_resop4::OP_READ(READ4res::NFS4_OK(READ4resok {
+                eof: true,
+                data: b"\0\0data".to_vec(),
+            }))
+        );
+
+        // The stateid is gone once the file is closed:
+        let (status, _) = call(
+            &mut server,
+            vec![
+                nfs_argop4::OP_PUTFH(PUTFH4args { object: fh.clone() }),
+                nfs_argop4::OP_READ(READ4args {
+                    stateid,
+                    offset: 0,
+                    count: 100,
+                }),
+            ],
+        );
+        assert_eq!(status, nfsstat4::NFS4ERR_BAD_STATEID);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lookups_and_failed_opens() {
+        let (mut server, dir) = server("lookup");
+        fs::create_dir(server.root.join("subdir")).unwrap();
+        fs::write(server.root.join("subdir/file"), b"abc").unwrap();
+        fs::write(server.root.join("existing"), b"").unwrap();
+        let clientid = client_id(&mut server);
+
+        let (status, res) = call(
+            &mut server,
+            vec![
+                nfs_argop4::OP_PUTROOTFH,
+                nfs_argop4::OP_LOOKUP(LOOKUP4args {
+                    objname: b"subdir".to_vec(),
+                }),
+                nfs_argop4::OP_LOOKUP(LOOKUP4args {
+                    objname: b"file".to_vec(),
+                }),
+                nfs_argop4::OP_GETATTR(GETATTR4args {
+                    attr_request: vec![1 << FATTR4_SIZE],
+                }),
+            ],
+        );
+        assert_eq!(status, nfsstat4::NFS4_OK);
+        assert_eq!(
+            res[3],
+            nfs_resop4::OP_GETATTR(GETATTR4res::NFS4_OK(GETATTR4resok {
+                obj_attributes: fattr4 {
+                    attrmask: vec![1 << FATTR4_SIZE],
+                    attr_vals: 3_u64.to_be_bytes().to_vec(),
+                },
+            }))
+        );
+
+        for (name, expected) in [
+            ("..", nfsstat4::NFS4ERR_BADNAME),
+            ("a/b", nfsstat4::NFS4ERR_BADNAME),
+            ("missing", nfsstat4::NFS4ERR_NOENT),
+        ] {
+            let lookup = nfs_argop4::OP_LOOKUP(LOOKUP4args {
+                objname: name.as_bytes().to_vec(),
+            });
+            let (status, _) = call(&mut server, vec![nfs_argop4::OP_PUTROOTFH, lookup]);
+            assert_eq!(status, expected, "LOOKUP of {name}");
+        }
+
+        // A failed OPEN still uses its seqid:
+        let (status, _) = call(
+            &mut server,
+            vec![
+                nfs_argop4::OP_PUTROOTFH,
+                open(clientid, 1, "subdir", openflag4::Default),
+            ],
+        );
+        assert_eq!(status, nfsstat4::NFS4ERR_ISDIR);
+        let (status, _) = call(
+            &mut server,
+            vec![
+                nfs_argop4::OP_PUTROOTFH,
+                open(clientid, 2, "missing", openflag4::Default),
+            ],
+        );
+        assert_eq!(status, nfsstat4::NFS4ERR_NOENT);
+
+        let guarded = openflag4::OPEN4_CREATE(createhow4::GUARDED4(fattr4::default()));
+        let (status, _) = call(
+            &mut server,
+            vec![
+                nfs_argop4::OP_PUTROOTFH,
+                open(clientid, 3, "existing", guarded),
+            ],
+        );
+        assert_eq!(status, nfsstat4::NFS4ERR_EXIST);
+
+        // Opens need a client id that is confirmed:
+        let (status, _) = call(
+            &mut server,
+            vec![
+                nfs_argop4::OP_PUTROOTFH,
+                open(clientid + 1, 1, "new", unchecked()),
+            ],
+        );
+        assert_eq!(status, nfsstat4::NFS4ERR_STALE_CLIENTID);
+        assert!(!server.root.join("new").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn exclusive_creates() {
+        let (mut server, dir) = server("exclusive");
+        let clientid = client_id(&mut server);
+        let exclusive =
+            |verifier: &[u8; 8]| openflag4::OPEN4_CREATE(createhow4::EXCLUSIVE4(*verifier));
+
+        let (status, _) = call(
+            &mut server,
+            vec![
+                nfs_argop4::OP_PUTROOTFH,
+                open(clientid, 1, "file", exclusive(b"verifier")),
+            ],
+        );
+        assert_eq!(status, nfsstat4::NFS4_OK);
+
+        // A retransmission finds the verifier, and another create does not:
+        let (status, _) = call(
+            &mut server,
+            vec![
+                nfs_argop4::OP_PUTROOTFH,
+                open(clientid, 2, "file", exclusive(b"verifier")),
+            ],
+        );
+        assert_eq!(status, nfsstat4::NFS4_OK);
+        let (status, _) = call(
+            &mut server,
+            vec![
+                nfs_argop4::OP_PUTROOTFH,
+                open(clientid, 3, "file", exclusive(b"verifie2")),
+            ],
+        );
+        assert_eq!(status, nfsstat4::NFS4ERR_EXIST);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
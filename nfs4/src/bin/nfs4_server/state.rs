@@ -0,0 +1,887 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The state that NFSv4.0 clients hold on the server (RFC 7530, section 9): the client ids that they
+// set up with SETCLIENTID and SETCLIENTID_CONFIRM, and the files that they open, each identified by
+// a stateid.
+//
+// Each client holds a lease, which RENEW renews, as does any operation that uses the client's id or
+// one of its stateids. A client whose lease runs out loses its state: its files are closed, which
+// releases their share reservations, and its client id and stateids fail with NFS4ERR_EXPIRED until
+// it sets up a new client id. Leases are checked at the start of each COMPOUND, rather than by a
+// timer, since nothing but a call can notice that a client's state is gone.
+//
+// Client ids and stateids begin with the time that the server started, so that those handed out by
+// an earlier run fail with NFS4ERR_STALE_CLIENTID and NFS4ERR_STALE_STATEID. Since no state survives
+// a restart, there is no grace period in which to reclaim it.
+//
+// An open owner numbers its OPEN, OPEN_CONFIRM, and CLOSE operations with seqids. The reply to the
+// last of them is not kept, so a retransmission of it fails with NFS4ERR_BAD_SEQID rather than being
+// answered again.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::*;
+use nfs4::nfs4_prot::*;
+use rpc_protocol::Principal;
+
+/// The special stateid with which READ and WRITE act on a file that the caller has not opened,
+/// subject to the share reservations of those who have (RFC 7530, section 9.1.4.3).
+const ANONYMOUS: stateid4 = stateid4 {
+    seqid: 0,
+    other: [0; 12],
+};
+
+/// The special stateid with which READ reads a file regardless of share reservations.
+const READ_BYPASS: stateid4 = stateid4 {
+    seqid: u32::MAX,
+    other: [0xff; 12],
+};
+
+/// An open owner: the client id of a client, and the name that it gives the owner.
+pub type OwnerKey = (u64, Vec<u8>);
+
+/// The clients of the server, and the files that they have open.
+pub struct StateTable {
+    lease: Duration,
+
+    /// When the server started, in seconds since the epoch, which begins every client id and stateid
+    /// that it hands out.
+    boot: u32,
+
+    /// The last number given to a client id, a stateid, or a confirm verifier.
+    last_id: u64,
+
+    clients: HashMap<u64, Client>,
+    owners: HashMap<OwnerKey, Owner>,
+
+    /// The open files, by the `other` part of their stateids.
+    opens: HashMap<[u8; 12], Open>,
+}
+
+struct Client {
+    /// The name that the client gives itself, which is the same across its restarts.
+    id: Vec<u8>,
+
+    /// Changes each time that the client restarts.
+    verifier: [u8; 8],
+
+    /// Who set up the client id, who alone may set it up again while its lease lasts.
+    principal: Principal,
+
+    /// The verifier that SETCLIENTID_CONFIRM must give.
+    confirm: [u8; 8],
+    confirmed: bool,
+
+    renewed: Instant,
+
+    /// Set once the lease has run out, to the stateids that the client lost.
+    expired: Option<Vec<[u8; 12]>>,
+}
+
+struct Owner {
+    /// The seqid of the owner's last OPEN, OPEN_CONFIRM, or CLOSE.
+    seqid: u32,
+
+    /// Whether the owner's first open has been confirmed with OPEN_CONFIRM.
+    confirmed: bool,
+}
+
+/// A file opened by an open owner.
+struct Open {
+    clientid: u64,
+    owner: Vec<u8>,
+    fh: Vec<u8>,
+    file: File,
+
+    /// The OPEN4_SHARE_ACCESS and OPEN4_SHARE_DENY bits of the opens of the file by the owner.
+    access: u32,
+    deny: u32,
+
+    /// The seqid of the current stateid, which each upgrade, confirmation, and close advances.
+    seqid: u32,
+    confirmed: bool,
+}
+
+impl Open {
+    fn owner_key(&self) -> OwnerKey {
+        (self.clientid, self.owner.clone())
+    }
+}
+
+impl StateTable {
+    /// An empty table, for a server whose clients hold leases of `lease`.
+    pub fn new(lease: Duration) -> Self {
+        let boot = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time should be after the epoch");
+
+        Self {
+            lease,
+            boot: boot.as_secs() as u32,
+            last_id: 0,
+            clients: HashMap::new(),
+            owners: HashMap::new(),
+            opens: HashMap::new(),
+        }
+    }
+
+    pub fn lease(&self) -> Duration {
+        self.lease
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn new_clientid(&mut self) -> u64 {
+        (u64::from(self.boot) << 32) | (self.next_id() & u64::from(u32::MAX))
+    }
+
+    fn new_stateid(&mut self) -> stateid4 {
+        let mut other = [0; 12];
+        other[..4].copy_from_slice(&self.boot.to_be_bytes());
+        other[4..].copy_from_slice(&self.next_id().to_be_bytes());
+
+        stateid4 { seqid: 1, other }
+    }
+
+    /// Take away the state of the clients whose leases have run out by `now`, and forget the client
+    /// ids that were not confirmed within a lease.
+    pub fn expire(&mut self, now: Instant) {
+        let lease = self.lease;
+        let mut lapsed = Vec::new();
+        self.clients.retain(|&clientid, client| {
+            if client.expired.is_some() || now.saturating_duration_since(client.renewed) < lease {
+                return true;
+            }
+            if client.confirmed {
+                lapsed.push(clientid);
+            }
+            client.confirmed
+        });
+
+        for clientid in lapsed {
+            let lost = self.forget_state(clientid);
+            info!(
+                "The lease of client {clientid:x} expired, closing its {} open files",
+                lost.len()
+            );
+            if let Some(client) = self.clients.get_mut(&clientid) {
+                client.expired = Some(lost);
+            }
+        }
+    }
+
+    /// Close the files of the client with `clientid`, and forget its open owners. Returns the
+    /// stateids of the files.
+    fn forget_state(&mut self, clientid: u64) -> Vec<[u8; 12]> {
+        let lost: Vec<_> = self
+            .opens
+            .iter()
+            .filter(|(_, open)| open.clientid == clientid)
+            .map(|(other, _)| *other)
+            .collect();
+        for other in &lost {
+            self.opens.remove(other);
+        }
+        self.owners
+            .retain(|(owner_clientid, _), _| *owner_clientid != clientid);
+
+        lost
+    }
+
+    /// SETCLIENTID: a client id for `client`, which it must confirm before using it. A client that
+    /// sets up its id again with the same verifier keeps its client id; with a new verifier, as after
+    /// it restarts, it gets a new one, and loses the state of the old one once it confirms it.
+    pub fn set_client_id(
+        &mut self,
+        client: nfs_client_id4,
+        principal: &Principal,
+        now: Instant,
+    ) -> Result<SETCLIENTID4resok, nfsstat4> {
+        let confirmed = self
+            .clients
+            .iter()
+            .find(|(_, c)| c.id == client.id && c.confirmed && c.expired.is_none())
+            .map(|(&clientid, c)| (clientid, c.verifier, c.principal.clone()));
+        if let Some((_, _, owner)) = &confirmed {
+            if owner != principal {
+                return Err(nfsstat4::NFS4ERR_CLID_INUSE);
+            }
+        }
+
+        // Whatever the client set up before and did not confirm is replaced:
+        self.clients.retain(|_, c| c.id != client.id || c.confirmed);
+
+        let setclientid_confirm = self.next_id().to_be_bytes();
+        let clientid = match confirmed {
+            Some((clientid, verifier, _)) if verifier == client.verifier => {
+                let existing = self.clients.get_mut(&clientid).unwrap();
+                existing.confirm = setclientid_confirm;
+                existing.renewed = now;
+                clientid
+            }
+            _ => {
+                let clientid = self.new_clientid();
+                self.clients.insert(
+                    clientid,
+                    Client {
+                        id: client.id,
+                        verifier: client.verifier,
+                        principal: principal.clone(),
+                        confirm: setclientid_confirm,
+                        confirmed: false,
+                        renewed: now,
+                        expired: None,
+                    },
+                );
+                clientid
+            }
+        };
+
+        Ok(SETCLIENTID4resok {
+            clientid,
+            setclientid_confirm,
+        })
+    }
+
+    /// SETCLIENTID_CONFIRM: confirm the client id that SETCLIENTID handed out with `confirm`.
+    pub fn confirm_client_id(
+        &mut self,
+        clientid: u64,
+        confirm: [u8; 8],
+        now: Instant,
+    ) -> Result<(), nfsstat4> {
+        let client = self
+            .clients
+            .get_mut(&clientid)
+            .filter(|c| c.expired.is_none() && c.confirm == confirm)
+            .ok_or(nfsstat4::NFS4ERR_STALE_CLIENTID)?;
+        client.confirmed = true;
+        client.renewed = now;
+
+        // The client has restarted, if it had another client id:
+        let id = client.id.clone();
+        let replaced: Vec<_> = self
+            .clients
+            .iter()
+            .filter(|&(&other, c)| other != clientid && c.id == id)
+            .map(|(&other, _)| other)
+            .collect();
+        for other in replaced {
+            debug!("client id {other:x} is replaced by {clientid:x}");
+            self.forget_state(other);
+            self.clients.remove(&other);
+        }
+
+        Ok(())
+    }
+
+    /// Renew the lease of the client with `clientid`, as RENEW does, and as any operation that uses
+    /// the client id does before it proceeds.
+    pub fn renew(&mut self, clientid: u64, now: Instant) -> Result<(), nfsstat4> {
+        if clientid >> 32 != u64::from(self.boot) {
+            return Err(nfsstat4::NFS4ERR_STALE_CLIENTID);
+        }
+
+        match self.clients.get_mut(&clientid) {
+            Some(client) if client.expired.is_some() => Err(nfsstat4::NFS4ERR_EXPIRED),
+            Some(client) if client.confirmed => {
+                client.renewed = now;
+                Ok(())
+            }
+            _ => Err(nfsstat4::NFS4ERR_STALE_CLIENTID),
+        }
+    }
+
+    /// Check that `seqid` is the next of the open owner `owner`, then perform `op`. The owner's seqid
+    /// advances unless `op` fails with one of the errors that leave it alone (RFC 7530, section
+    /// 9.1.7). An owner that has not been seen yet may start with any seqid.
+    pub fn sequenced<T>(
+        &mut self,
+        owner: &OwnerKey,
+        seqid: u32,
+        op: impl FnOnce(&mut Self) -> Result<T, nfsstat4>,
+    ) -> Result<T, nfsstat4> {
+        if let Some(last) = self.owners.get(owner) {
+            if seqid != last.seqid.wrapping_add(1) {
+                return Err(nfsstat4::NFS4ERR_BAD_SEQID);
+            }
+        }
+
+        let result = op(self);
+        let advances = !matches!(
+            result,
+            Err(nfsstat4::NFS4ERR_STALE_CLIENTID
+                | nfsstat4::NFS4ERR_STALE_STATEID
+                | nfsstat4::NFS4ERR_BAD_STATEID
+                | nfsstat4::NFS4ERR_BAD_SEQID
+                | nfsstat4::NFS4ERR_BADXDR
+                | nfsstat4::NFS4ERR_RESOURCE
+                | nfsstat4::NFS4ERR_NOFILEHANDLE)
+        );
+        if advances {
+            if let Some(last) = self.owners.get_mut(owner) {
+                last.seqid = seqid;
+            }
+        }
+
+        result
+    }
+
+    /// Fail with NFS4ERR_SHARE_DENIED if opening the file `fh` for `access`, denying others `deny`,
+    /// conflicts with the opens of the file by owners other than `owner`.
+    pub fn check_share(
+        &self,
+        fh: &[u8],
+        access: u32,
+        deny: u32,
+        owner: &OwnerKey,
+    ) -> Result<(), nfsstat4> {
+        let conflicts = self.opens.values().any(|open| {
+            open.fh == fh
+                && (open.clientid, &open.owner) != (owner.0, &owner.1)
+                && (open.deny & access != 0 || deny & open.access != 0)
+        });
+        if conflicts {
+            return Err(nfsstat4::NFS4ERR_SHARE_DENIED);
+        }
+
+        Ok(())
+    }
+
+    /// The share access that `owner` already has the file `fh` open for, which a new open of it
+    /// must keep.
+    pub fn held_access(&self, fh: &[u8], owner: &OwnerKey) -> u32 {
+        self.opens
+            .values()
+            .filter(|open| open.fh == fh && (open.clientid, &open.owner) == (owner.0, &owner.1))
+            .fold(0, |access, open| access | open.access)
+    }
+
+    /// Record that `owner` opened `file`, whose handle is `fh`, once `check_share()` allows it. If
+    /// the owner had the file open already, that open is upgraded, keeping its stateid. Returns the
+    /// stateid, and whether the owner must confirm it with OPEN_CONFIRM, as an owner must confirm
+    /// its first open.
+    pub fn add_open(
+        &mut self,
+        owner: &OwnerKey,
+        seqid: u32,
+        fh: &[u8],
+        file: File,
+        access: u32,
+        deny: u32,
+    ) -> (stateid4, bool) {
+        let confirmed = self
+            .owners
+            .entry(owner.clone())
+            .or_insert(Owner {
+                seqid,
+                confirmed: false,
+            })
+            .confirmed;
+
+        let existing = self
+            .opens
+            .iter_mut()
+            .find(|(_, open)| open.fh == fh && (open.clientid, &open.owner) == (owner.0, &owner.1));
+        if let Some((&other, open)) = existing {
+            open.file = file;
+            open.access |= access;
+            open.deny |= deny;
+            open.seqid = open.seqid.wrapping_add(1);
+            let stateid = stateid4 {
+                seqid: open.seqid,
+                other,
+            };
+            return (stateid, !confirmed);
+        }
+
+        let stateid = self.new_stateid();
+        self.opens.insert(
+            stateid.other,
+            Open {
+                clientid: owner.0,
+                owner: owner.1.clone(),
+                fh: fh.to_vec(),
+                file,
+                access,
+                deny,
+                seqid: stateid.seqid,
+                confirmed,
+            },
+        );
+
+        (stateid, !confirmed)
+    }
+
+    /// The open of the file `fh` that `stateid` identifies, renewing its client's lease.
+    fn find(&mut self, stateid: &stateid4, fh: &[u8], now: Instant) -> Result<&mut Open, nfsstat4> {
+        if stateid.other[..4] != self.boot.to_be_bytes() {
+            return Err(nfsstat4::NFS4ERR_STALE_STATEID);
+        }
+
+        let Some(open) = self.opens.get(&stateid.other) else {
+            let expired = self.clients.values().any(|client| {
+                client
+                    .expired
+                    .as_ref()
+                    .is_some_and(|lost| lost.contains(&stateid.other))
+            });
+            return Err(if expired {
+                nfsstat4::NFS4ERR_EXPIRED
+            } else {
+                nfsstat4::NFS4ERR_BAD_STATEID
+            });
+        };
+        if stateid.seqid < open.seqid {
+            return Err(nfsstat4::NFS4ERR_OLD_STATEID);
+        }
+        if stateid.seqid > open.seqid || open.fh != fh {
+            return Err(nfsstat4::NFS4ERR_BAD_STATEID);
+        }
+
+        if let Some(client) = self.clients.get_mut(&open.clientid) {
+            client.renewed = now;
+        }
+
+        Ok(self.opens.get_mut(&stateid.other).unwrap())
+    }
+
+    /// The open file with which READ or WRITE acts on the file `fh`, for the share `access` of the
+    /// operation: that of `stateid`, or None for the special stateids, with which the caller opens
+    /// the file itself, once the share reservations of others allow it.
+    pub fn file_for(
+        &mut self,
+        stateid: &stateid4,
+        fh: &[u8],
+        access: u32,
+        now: Instant,
+    ) -> Result<Option<&File>, nfsstat4> {
+        if *stateid == READ_BYPASS && access == OPEN4_SHARE_ACCESS_READ {
+            return Ok(None);
+        }
+        if *stateid == ANONYMOUS {
+            let denied = self
+                .opens
+                .values()
+                .any(|open| open.fh == fh && open.deny & access != 0);
+            if denied {
+                return Err(nfsstat4::NFS4ERR_LOCKED);
+            }
+            return Ok(None);
+        }
+
+        let open = self.find(stateid, fh, now)?;
+        if !open.confirmed {
+            return Err(nfsstat4::NFS4ERR_BAD_STATEID);
+        }
+        if open.access & access == 0 {
+            return Err(nfsstat4::NFS4ERR_OPENMODE);
+        }
+
+        Ok(Some(&open.file))
+    }
+
+    /// OPEN_CONFIRM: confirm the first open of an open owner. Returns the stateid of the open.
+    pub fn confirm_open(
+        &mut self,
+        stateid: &stateid4,
+        seqid: u32,
+        fh: &[u8],
+        now: Instant,
+    ) -> Result<stateid4, nfsstat4> {
+        let open = self.find(stateid, fh, now)?;
+        if open.confirmed {
+            return Err(nfsstat4::NFS4ERR_BAD_STATEID);
+        }
+        let owner = open.owner_key();
+
+        self.sequenced(&owner, seqid, |table| {
+            if let Some(confirmed) = table.owners.get_mut(&owner) {
+                confirmed.confirmed = true;
+            }
+            let open = table.opens.get_mut(&stateid.other).unwrap();
+            open.confirmed = true;
+            open.seqid = open.seqid.wrapping_add(1);
+
+            Ok(stateid4 {
+                seqid: open.seqid,
+                other: stateid.other,
+            })
+        })
+    }
+
+    /// CLOSE: close the open that `stateid` identifies. Returns the stateid that it had, advanced
+    /// once more.
+    pub fn close(
+        &mut self,
+        stateid: &stateid4,
+        seqid: u32,
+        fh: &[u8],
+        now: Instant,
+    ) -> Result<stateid4, nfsstat4> {
+        let open = self.find(stateid, fh, now)?;
+        if !open.confirmed {
+            return Err(nfsstat4::NFS4ERR_BAD_STATEID);
+        }
+        let owner = open.owner_key();
+
+        self.sequenced(&owner, seqid, |table| {
+            let open = table.opens.remove(&stateid.other).unwrap();
+
+            Ok(stateid4 {
+                seqid: open.seqid.wrapping_add(1),
+                other: stateid.other,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEASE: Duration = Duration::from_secs(90);
+
+    fn principal(uid: u32) -> Principal {
+        Principal::Unix {
+            uid,
+            gid: uid,
+            gids: Vec::new(),
+        }
+    }
+
+    fn client(verifier: u8) -> nfs_client_id4 {
+        nfs_client_id4 {
+            verifier: [verifier; 8],
+            id: b"client.example.com".to_vec(),
+        }
+    }
+
+    /// Set up and confirm a client id.
+    fn confirmed(table: &mut StateTable, verifier: u8, now: Instant) -> u64 {
+        let res = table
+            .set_client_id(client(verifier), &principal(0), now)
+            .unwrap();
+        table
+            .confirm_client_id(res.clientid, res.setclientid_confirm, now)
+            .unwrap();
+
+        res.clientid
+    }
+
+    fn file() -> File {
+        File::open("/dev/null").unwrap()
+    }
+
+    /// Open `fh` for `owner` and confirm the open.
+    fn open(table: &mut StateTable, owner: &OwnerKey, seqid: u32, fh: &[u8]) -> stateid4 {
+        let now = Instant::now();
+        let (stateid, confirm) = table
+            .sequenced(owner, seqid, |table| {
+                Ok(table.add_open(owner, seqid, fh, file(), OPEN4_SHARE_ACCESS_BOTH, 0))
+            })
+            .unwrap();
+        assert!(confirm);
+
+        table.confirm_open(&stateid, seqid + 1, fh, now).unwrap()
+    }
+
+    #[test]
+    fn client_ids() {
+        let now = Instant::now();
+        let mut table = StateTable::new(LEASE);
+
+        let res = table.set_client_id(client(1), &principal(0), now).unwrap();
+        assert_eq!(
+            table.renew(res.clientid, now),
+            Err(nfsstat4::NFS4ERR_STALE_CLIENTID)
+        );
+        assert_eq!(
+            table.confirm_client_id(res.clientid, [0; 8], now),
+            Err(nfsstat4::NFS4ERR_STALE_CLIENTID)
+        );
+        table
+            .confirm_client_id(res.clientid, res.setclientid_confirm, now)
+            .unwrap();
+        table.renew(res.clientid, now).unwrap();
+
+        // Setting up the same client again keeps its client id, but someone else may not:
+        let again = table.set_client_id(client(1), &principal(0), now).unwrap();
+        assert_eq!(again.clientid, res.clientid);
+        assert_ne!(again.setclientid_confirm, res.setclientid_confirm);
+        assert_eq!(
+            table.set_client_id(client(1), &principal(1000), now),
+            Err(nfsstat4::NFS4ERR_CLID_INUSE)
+        );
+
+        // A client id of an earlier run of the server is stale:
+        assert_eq!(
+            table.renew(res.clientid ^ (1 << 32), now),
+            Err(nfsstat4::NFS4ERR_STALE_CLIENTID)
+        );
+    }
+
+    #[test]
+    fn restarted_clients_lose_their_state() {
+        let now = Instant::now();
+        let mut table = StateTable::new(LEASE);
+        let old = confirmed(&mut table, 1, now);
+        let stateid = open(&mut table, &(old, b"owner".to_vec()), 1, b"fh");
+
+        let new = confirmed(&mut table, 2, now);
+        assert_ne!(new, old);
+        assert_eq!(table.renew(old, now), Err(nfsstat4::NFS4ERR_STALE_CLIENTID));
+        assert_eq!(
+            table
+                .file_for(&stateid, b"fh", OPEN4_SHARE_ACCESS_READ, now)
+                .err(),
+            Some(nfsstat4::NFS4ERR_BAD_STATEID)
+        );
+    }
+
+    #[test]
+    fn leases_expire() {
+        let start = Instant::now();
+        let mut table = StateTable::new(LEASE);
+        let renewed = confirmed(&mut table, 1, start);
+        let lapsed = table
+            .set_client_id(
+                nfs_client_id4 {
+                    verifier: [1; 8],
+                    id: b"other".to_vec(),
+                },
+                &principal(0),
+                start,
+            )
+            .unwrap();
+        table
+            .confirm_client_id(lapsed.clientid, lapsed.setclientid_confirm, start)
+            .unwrap();
+        let lapsed = lapsed.clientid;
+        let unconfirmed = table
+            .set_client_id(
+                nfs_client_id4 {
+                    verifier: [1; 8],
+                    id: b"unconfirmed".to_vec(),
+                },
+                &principal(0),
+                start,
+            )
+            .unwrap();
+        let stateid = open(&mut table, &(lapsed, b"owner".to_vec()), 7, b"fh");
+
+        // Using a stateid renews the lease of its client, as RENEW does:
+        let later = start + LEASE / 2;
+        table.renew(renewed, later).unwrap();
+        assert!(table
+            .file_for(&stateid, b"fh", OPEN4_SHARE_ACCESS_READ, later)
+            .is_ok());
+        table.expire(start + LEASE + Duration::from_secs(1));
+        table.renew(renewed, start + LEASE).unwrap();
+
+        let expired = later + LEASE + Duration::from_secs(1);
+        table.expire(expired);
+        table.renew(renewed, expired).unwrap();
+        assert_eq!(table.renew(lapsed, expired), Err(nfsstat4::NFS4ERR_EXPIRED));
+        assert_eq!(
+            table
+                .file_for(&stateid, b"fh", OPEN4_SHARE_ACCESS_READ, expired)
+                .err(),
+            Some(nfsstat4::NFS4ERR_EXPIRED)
+        );
+        assert_eq!(
+            table.confirm_client_id(
+                unconfirmed.clientid,
+                unconfirmed.setclientid_confirm,
+                expired
+            ),
+            Err(nfsstat4::NFS4ERR_STALE_CLIENTID)
+        );
+
+        // The expired client's files were closed, so others may open them:
+        let other = (renewed, b"owner".to_vec());
+        table
+            .check_share(
+                b"fh",
+                OPEN4_SHARE_ACCESS_BOTH,
+                OPEN4_SHARE_DENY_BOTH,
+                &other,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn opens_and_closes() {
+        let now = Instant::now();
+        let mut table = StateTable::new(LEASE);
+        let clientid = confirmed(&mut table, 1, now);
+        let owner = (clientid, b"owner".to_vec());
+
+        // The open must be confirmed before it is used:
+        let (unconfirmed, confirm) = table
+            .sequenced(&owner, 1, |table| {
+                Ok(table.add_open(&owner, 1, b"fh", file(), OPEN4_SHARE_ACCESS_READ, 0))
+            })
+            .unwrap();
+        assert!(confirm);
+        assert_eq!(
+            table
+                .file_for(&unconfirmed, b"fh", OPEN4_SHARE_ACCESS_READ, now)
+                .err(),
+            Some(nfsstat4::NFS4ERR_BAD_STATEID)
+        );
+        assert_eq!(
+            table.confirm_open(&unconfirmed, 1, b"fh", now),
+            Err(nfsstat4::NFS4ERR_BAD_SEQID)
+        );
+        let stateid = table.confirm_open(&unconfirmed, 2, b"fh", now).unwrap();
+        assert_eq!(stateid.seqid, unconfirmed.seqid + 1);
+
+        assert_eq!(
+            table
+                .file_for(&unconfirmed, b"fh", OPEN4_SHARE_ACCESS_READ, now)
+                .err(),
+            Some(nfsstat4::NFS4ERR_OLD_STATEID)
+        );
+        assert_eq!(
+            table
+                .file_for(&stateid, b"other fh", OPEN4_SHARE_ACCESS_READ, now)
+                .err(),
+            Some(nfsstat4::NFS4ERR_BAD_STATEID)
+        );
+        assert_eq!(
+            table
+                .file_for(&stateid, b"fh", OPEN4_SHARE_ACCESS_WRITE, now)
+                .err(),
+            Some(nfsstat4::NFS4ERR_OPENMODE)
+        );
+        assert!(table
+            .file_for(&stateid, b"fh", OPEN4_SHARE_ACCESS_READ, now)
+            .unwrap()
+            .is_some());
+
+        // A second open by the owner upgrades the first, whose stateid it keeps, and a confirmed
+        // owner need not confirm its later opens:
+        assert_eq!(table.held_access(b"fh", &owner), OPEN4_SHARE_ACCESS_READ);
+        let (upgraded, confirm) = table
+            .sequenced(&owner, 3, |table| {
+                Ok(table.add_open(&owner, 3, b"fh", file(), OPEN4_SHARE_ACCESS_WRITE, 0))
+            })
+            .unwrap();
+        assert!(!confirm);
+        assert_eq!(upgraded.other, stateid.other);
+        assert_eq!(upgraded.seqid, stateid.seqid + 1);
+        assert!(table
+            .file_for(&upgraded, b"fh", OPEN4_SHARE_ACCESS_WRITE, now)
+            .is_ok());
+
+        let closed = table.close(&upgraded, 4, b"fh", now).unwrap();
+        assert_eq!(closed.seqid, upgraded.seqid + 1);
+        assert_eq!(
+            table.close(&upgraded, 5, b"fh", now),
+            Err(nfsstat4::NFS4ERR_BAD_STATEID)
+        );
+
+        // A stateid of an earlier run of the server is stale:
+        let mut stale = upgraded;
+        stale.other[0] ^= 1;
+        assert_eq!(
+            table
+                .file_for(&stale, b"fh", OPEN4_SHARE_ACCESS_READ, now)
+                .err(),
+            Some(nfsstat4::NFS4ERR_STALE_STATEID)
+        );
+    }
+
+    #[test]
+    fn failures_advance_seqids() {
+        let now = Instant::now();
+        let mut table = StateTable::new(LEASE);
+        let clientid = confirmed(&mut table, 1, now);
+        let owner = (clientid, b"owner".to_vec());
+        open(&mut table, &owner, 1, b"fh");
+
+        let failed: Result<(), _> = table.sequenced(&owner, 3, |_| Err(nfsstat4::NFS4ERR_NOENT));
+        assert_eq!(failed, Err(nfsstat4::NFS4ERR_NOENT));
+        let failed: Result<(), _> =
+            table.sequenced(&owner, 4, |_| Err(nfsstat4::NFS4ERR_BAD_STATEID));
+        assert_eq!(failed, Err(nfsstat4::NFS4ERR_BAD_STATEID));
+
+        // The NOENT used seqid 3, and the BAD_STATEID did not use seqid 4:
+        assert_eq!(
+            table.sequenced(&owner, 3, |_| Ok(())),
+            Err(nfsstat4::NFS4ERR_BAD_SEQID)
+        );
+        assert_eq!(table.sequenced(&owner, 4, |_| Ok(())), Ok(()));
+    }
+
+    #[test]
+    fn share_reservations() {
+        let now = Instant::now();
+        let mut table = StateTable::new(LEASE);
+        let clientid = confirmed(&mut table, 1, now);
+        let reader = (clientid, b"reader".to_vec());
+        let writer = (clientid, b"writer".to_vec());
+
+        table
+            .sequenced(&reader, 1, |table| {
+                Ok(table.add_open(
+                    &reader,
+                    1,
+                    b"fh",
+                    file(),
+                    OPEN4_SHARE_ACCESS_READ,
+                    OPEN4_SHARE_DENY_WRITE,
+                ))
+            })
+            .unwrap();
+
+        assert_eq!(
+            table.check_share(b"fh", OPEN4_SHARE_ACCESS_WRITE, 0, &writer),
+            Err(nfsstat4::NFS4ERR_SHARE_DENIED)
+        );
+        assert_eq!(
+            table.check_share(
+                b"fh",
+                OPEN4_SHARE_ACCESS_READ,
+                OPEN4_SHARE_DENY_READ,
+                &writer
+            ),
+            Err(nfsstat4::NFS4ERR_SHARE_DENIED)
+        );
+        table
+            .check_share(b"fh", OPEN4_SHARE_ACCESS_READ, 0, &writer)
+            .unwrap();
+        table
+            .check_share(b"other fh", OPEN4_SHARE_ACCESS_WRITE, 0, &writer)
+            .unwrap();
+        // An owner does not conflict with itself:
+        table
+            .check_share(b"fh", OPEN4_SHARE_ACCESS_WRITE, 0, &reader)
+            .unwrap();
+
+        // The special stateids are subject to the reservations, but for READ bypassing them:
+        assert_eq!(
+            table
+                .file_for(&ANONYMOUS, b"fh", OPEN4_SHARE_ACCESS_WRITE, now)
+                .err(),
+            Some(nfsstat4::NFS4ERR_LOCKED)
+        );
+        assert!(matches!(
+            table.file_for(&ANONYMOUS, b"fh", OPEN4_SHARE_ACCESS_READ, now),
+            Ok(None)
+        ));
+        assert!(matches!(
+            table.file_for(&READ_BYPASS, b"fh", OPEN4_SHARE_ACCESS_READ, now),
+            Ok(None)
+        ));
+    }
+}
@@ -0,0 +1,446 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// COMPOUND, the one NFSv4.0 procedure besides NULL (RFC 7530, section 16.2).
+//
+// The arguments of a COMPOUND are a sequence of operations, performed in order against a current
+// filehandle and a saved one, which operations such as PUTFH, SAVEFH, and RESTOREFH set and which
+// the others act on. The sequence stops at the first operation that fails, and the result holds the
+// result of each operation performed, up to and including that one, and the status of the last.
+//
+// Operations are decoded one at a time, just before they are performed, so that an operation this
+// server does not know of is answered with OP_ILLEGAL, and one whose arguments cannot be decoded with
+// NFS4ERR_BADXDR, rather than failing the whole call with GARBAGE_ARGS. The results are encoded by
+// hand, since the generated `Default` variants of the result unions do not record which status they
+// were created for: each is the operation number, the status, and the body of the arm for that
+// status.
+//
+// A server implements `Operations` for the operations that act on its file systems or on client
+// state. GETFH, SAVEFH, and RESTOREFH only act on the filehandles, and are performed here:
+//
+//     let result = compound(&mut server, call.arg).map_err(|_| RpcResult::GarbageArgs)?;
+
+use crate::nfs4_prot::*;
+
+/// The filehandles shared by the operations of one COMPOUND.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CompoundState {
+    pub current_fh: Option<Vec<u8>>,
+    pub saved_fh: Option<Vec<u8>>,
+}
+
+impl CompoundState {
+    /// The current filehandle, or NFS4ERR_NOFILEHANDLE if no operation has set it.
+    pub fn current(&self) -> Result<&[u8], OpFailure> {
+        self.current_fh
+            .as_deref()
+            .ok_or(nfsstat4::NFS4ERR_NOFILEHANDLE.into())
+    }
+
+    /// The saved filehandle, or NFS4ERR_NOFILEHANDLE if no operation has set it.
+    pub fn saved(&self) -> Result<&[u8], OpFailure> {
+        self.saved_fh
+            .as_deref()
+            .ok_or(nfsstat4::NFS4ERR_NOFILEHANDLE.into())
+    }
+}
+
+/// A failed operation: its status, and the encoded body of the arm of the operation's result for
+/// that status, which is empty for most.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpFailure {
+    pub status: nfsstat4,
+    pub body: Vec<u8>,
+}
+
+impl From<nfsstat4> for OpFailure {
+    fn from(status: nfsstat4) -> Self {
+        Self {
+            status,
+            body: Vec::new(),
+        }
+    }
+}
+
+/// The result of an operation: the encoded body of the NFS4_OK arm of its result, or a failure.
+pub type OpResult = Result<Vec<u8>, OpFailure>;
+
+/// The operations that a server performs itself.
+pub trait Operations {
+    /// Perform `op`, which is neither GETFH, SAVEFH, RESTOREFH, nor ILLEGAL. An operation that acts
+    /// on the current filehandle, or on the saved one, is only passed here once it is set.
+    fn perform(&mut self, state: &mut CompoundState, op: nfs_argop4) -> OpResult;
+}
+
+/// Perform the COMPOUND whose arguments are encoded in `args`, and return its encoded result. Fails
+/// only if the tag, the minor version, or the number of operations cannot be decoded, which should
+/// be answered with GARBAGE_ARGS.
+pub fn compound(ops: &mut impl Operations, mut args: &[u8]) -> xdr_lib::Result<Vec<u8>> {
+    let mut len = 0;
+    xdr_lib::get_u32(&mut len, &mut args)?;
+    let tag = xdr_lib::get_opaque(len as usize, &mut args)?;
+    let mut minorversion = 0;
+    xdr_lib::get_u32(&mut minorversion, &mut args)?;
+    let mut count = 0;
+    xdr_lib::get_u32(&mut count, &mut args)?;
+
+    let mut status = nfsstat4::NFS4_OK;
+    let mut performed: u32 = 0;
+    let mut results = Vec::new();
+    if minorversion != 0 {
+        status = nfsstat4::NFS4ERR_MINOR_VERS_MISMATCH;
+    } else {
+        let mut state = CompoundState::default();
+        for _ in 0..count {
+            let (opnum, result) = perform_next(ops, &mut state, &mut args);
+            performed += 1;
+            results.extend_from_slice(&opnum.serialize_alloc());
+            match result {
+                Ok(body) => {
+                    results.extend_from_slice(&nfsstat4::NFS4_OK.serialize_alloc());
+                    results.extend_from_slice(&body);
+                }
+                Err(failure) => {
+                    log::debug!("{opnum:?} failed: {:?}", failure.status);
+                    results.extend_from_slice(&failure.status.serialize_alloc());
+                    results.extend_from_slice(&failure.body);
+                    status = failure.status;
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut buf = status.serialize_alloc();
+    buf.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+    buf.extend_from_slice(tag);
+    buf.resize(buf.len() + xdr_lib::padded_4byte(tag.len()) - tag.len(), 0);
+    buf.extend_from_slice(&performed.to_be_bytes());
+    buf.extend_from_slice(&results);
+
+    Ok(buf)
+}
+
+/// Decode the next operation from `args` and perform it. Returns the number of the operation for its
+/// result, which is OP_ILLEGAL for an operation that is not known, or that is missing.
+fn perform_next(
+    ops: &mut impl Operations,
+    state: &mut CompoundState,
+    args: &mut &[u8],
+) -> (nfs_opnum4, OpResult) {
+    let mut opnum = nfs_opnum4::OP_ILLEGAL;
+    if opnum.deserialize(&mut &args[..]).is_err() {
+        let status = if args.len() < 4 {
+            nfsstat4::NFS4ERR_BADXDR
+        } else {
+            nfsstat4::NFS4ERR_OP_ILLEGAL
+        };
+        return (nfs_opnum4::OP_ILLEGAL, Err(status.into()));
+    }
+    let mut op = nfs_argop4::default();
+    if op.deserialize(args).is_err() {
+        return (opnum, Err(nfsstat4::NFS4ERR_BADXDR.into()));
+    }
+
+    (opnum, perform(ops, state, op))
+}
+
+fn perform(ops: &mut impl Operations, state: &mut CompoundState, op: nfs_argop4) -> OpResult {
+    match op {
+        nfs_argop4::OP_ILLEGAL => Err(nfsstat4::NFS4ERR_OP_ILLEGAL.into()),
+        nfs_argop4::OP_GETFH => {
+            let object = state.current()?.to_vec();
+            Ok(GETFH4resok { object }.serialize_alloc())
+        }
+        nfs_argop4::OP_SAVEFH => {
+            state.saved_fh = Some(state.current()?.to_vec());
+            Ok(Vec::new())
+        }
+        nfs_argop4::OP_RESTOREFH => {
+            let saved = state.saved_fh.clone().ok_or(nfsstat4::NFS4ERR_RESTOREFH)?;
+            state.current_fh = Some(saved);
+            Ok(Vec::new())
+        }
+        op => {
+            if needs_current_fh(&op) {
+                state.current()?;
+            }
+            if needs_saved_fh(&op) {
+                state.saved()?;
+            }
+            ops.perform(state, op)
+        }
+    }
+}
+
+/// Whether `op` acts on the current filehandle, and so fails with NFS4ERR_NOFILEHANDLE if no earlier
+/// operation has set it.
+pub fn needs_current_fh(op: &nfs_argop4) -> bool {
+    !matches!(
+        op,
+        nfs_argop4::OP_PUTFH(_)
+            | nfs_argop4::OP_PUTPUBFH
+            | nfs_argop4::OP_PUTROOTFH
+            | nfs_argop4::OP_RESTOREFH
+            | nfs_argop4::OP_RENEW(_)
+            | nfs_argop4::OP_SETCLIENTID(_)
+            | nfs_argop4::OP_SETCLIENTID_CONFIRM(_)
+            | nfs_argop4::OP_DELEGPURGE(_)
+            | nfs_argop4::OP_RELEASE_LOCKOWNER(_)
+            | nfs_argop4::OP_ILLEGAL
+    )
+}
+
+/// Whether `op` also acts on the saved filehandle: the source of LINK and of RENAME.
+pub fn needs_saved_fh(op: &nfs_argop4) -> bool {
+    matches!(op, nfs_argop4::OP_LINK(_) | nfs_argop4::OP_RENAME(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT: &[u8] = &[1; 8];
+    const CHILD: &[u8] = &[2; 8];
+
+    /// A server with a root directory that holds one entry, `child`.
+    struct Server {
+        performed: Vec<nfs_argop4>,
+    }
+
+    impl Operations for Server {
+        fn perform(&mut self, state: &mut CompoundState, op: nfs_argop4) -> OpResult {
+            self.performed.push(op.clone());
+            match op {
+                nfs_argop4::OP_PUTROOTFH => {
+                    state.current_fh = Some(ROOT.to_vec());
+                    Ok(Vec::new())
+                }
+                nfs_argop4::OP_PUTFH(args) => {
+                    state.current_fh = Some(args.object);
+                    Ok(Vec::new())
+                }
+                nfs_argop4::OP_LOOKUP(args) => {
+                    if state.current()? != ROOT {
+                        return Err(nfsstat4::NFS4ERR_NOTDIR.into());
+                    }
+                    if args.objname != b"child" {
+                        return Err(nfsstat4::NFS4ERR_NOENT.into());
+                    }
+                    state.current_fh = Some(CHILD.to_vec());
+                    Ok(Vec::new())
+                }
+                nfs_argop4::OP_ACCESS(args) => Ok(ACCESS4resok {
+                    supported: args.access,
+                    access: args.access & ACCESS4_READ,
+                }
+                .serialize_alloc()),
+                _ => Err(nfsstat4::NFS4ERR_NOTSUPP.into()),
+            }
+        }
+    }
+
+    fn run(args: &[u8]) -> (Server, COMPOUND4res) {
+        let mut server = Server {
+            performed: Vec::new(),
+        };
+        let result = compound(&mut server, args).unwrap();
+        let mut res = COMPOUND4res::default();
+        let mut input = &result[..];
+        res.deserialize(&mut input).unwrap();
+        assert!(input.is_empty());
+
+        (server, res)
+    }
+
+    fn args(argarray: Vec<nfs_argop4>) -> Vec<u8> {
+        COMPOUND4args {
+            tag: b"test".to_vec(),
+            minorversion: 0,
+            argarray,
+        }
+        .serialize_alloc()
+    }
+
+    fn lookup(name: &str) -> nfs_argop4 {
+        nfs_argop4::OP_LOOKUP(LOOKUP4args {
+            objname: name.as_bytes().to_vec(),
+        })
+    }
+
+    fn getfh(fh: &[u8]) -> nfs_resop4 {
+        nfs_resop4::OP_GETFH(GETFH4res::NFS4_OK(GETFH4resok {
+            object: fh.to_vec(),
+        }))
+    }
+
+    fn ok() -> LOOKUP4res {
+        LOOKUP4res {
+            status: nfsstat4::NFS4_OK,
+        }
+    }
+
+    #[test]
+    fn operations_share_filehandles() {
+        let (_, res) = run(&args(vec![
+            nfs_argop4::OP_PUTROOTFH,
+            nfs_argop4::OP_SAVEFH,
+            lookup("child"),
+            nfs_argop4::OP_GETFH,
+            nfs_argop4::OP_RESTOREFH,
+            nfs_argop4::OP_GETFH,
+        ]));
+
+        assert_eq!(res.status, nfsstat4::NFS4_OK);
+        assert_eq!(res.tag, b"test");
+        assert_eq!(
+            res.resarray,
+            vec![
+                nfs_resop4::OP_PUTROOTFH(PUTROOTFH4res {
+                    status: nfsstat4::NFS4_OK
+                }),
+                nfs_resop4::OP_SAVEFH(SAVEFH4res {
+                    status: nfsstat4::NFS4_OK
+                }),
+                nfs_resop4::OP_LOOKUP(ok()),
+                getfh(CHILD),
+                nfs_resop4::OP_RESTOREFH(RESTOREFH4res {
+                    status: nfsstat4::NFS4_OK
+                }),
+                getfh(ROOT),
+            ]
+        );
+    }
+
+    #[test]
+    fn success_bodies() {
+        let (_, res) = run(&args(vec![
+            nfs_argop4::OP_PUTROOTFH,
+            nfs_argop4::OP_ACCESS(ACCESS4args {
+                access: ACCESS4_READ | ACCESS4_MODIFY,
+            }),
+        ]));
+
+        assert_eq!(res.status, nfsstat4::NFS4_OK);
+        assert_eq!(
+            res.resarray[1],
+            nfs_resop4::OP_ACCESS(ACCESS4res::NFS4_OK(ACCESS4resok {
+                supported: ACCESS4_READ | ACCESS4_MODIFY,
+                access: ACCESS4_READ,
+            }))
+        );
+    }
+
+    #[test]
+    fn stops_at_first_failure() {
+        let (server, res) = run(&args(vec![
+            nfs_argop4::OP_PUTROOTFH,
+            lookup("missing"),
+            nfs_argop4::OP_GETFH,
+        ]));
+
+        assert_eq!(res.status, nfsstat4::NFS4ERR_NOENT);
+        assert_eq!(res.resarray.len(), 2);
+        assert_eq!(
+            res.resarray[1],
+            nfs_resop4::OP_LOOKUP(LOOKUP4res {
+                status: nfsstat4::NFS4ERR_NOENT
+            })
+        );
+        assert_eq!(server.performed.len(), 2);
+    }
+
+    #[test]
+    fn missing_filehandles() {
+        let (server, res) = run(&args(vec![lookup("child")]));
+        assert_eq!(res.status, nfsstat4::NFS4ERR_NOFILEHANDLE);
+        assert!(server.performed.is_empty());
+
+        let (_, res) = run(&args(vec![nfs_argop4::OP_GETFH]));
+        assert_eq!(res.status, nfsstat4::NFS4ERR_NOFILEHANDLE);
+
+        let (_, res) = run(&args(vec![
+            nfs_argop4::OP_PUTROOTFH,
+            nfs_argop4::OP_RESTOREFH,
+        ]));
+        assert_eq!(res.status, nfsstat4::NFS4ERR_RESTOREFH);
+
+        let (server, res) = run(&args(vec![
+            nfs_argop4::OP_PUTROOTFH,
+            nfs_argop4::OP_LINK(LINK4args {
+                newname: b"link".to_vec(),
+            }),
+        ]));
+        assert_eq!(res.status, nfsstat4::NFS4ERR_NOFILEHANDLE);
+        assert_eq!(server.performed.len(), 1);
+    }
+
+    #[test]
+    fn illegal_operations() {
+        // An operation number that is not defined, after a PUTROOTFH:
+        let mut call = args(vec![nfs_argop4::OP_PUTROOTFH]);
+        call[15] = 2;
+        call.extend_from_slice(&2_u32.to_be_bytes());
+
+        let (_, res) = run(&call);
+        assert_eq!(res.status, nfsstat4::NFS4ERR_OP_ILLEGAL);
+        assert_eq!(
+            res.resarray,
+            vec![
+                nfs_resop4::OP_PUTROOTFH(PUTROOTFH4res {
+                    status: nfsstat4::NFS4_OK
+                }),
+                nfs_resop4::OP_ILLEGAL(ILLEGAL4res {
+                    status: nfsstat4::NFS4ERR_OP_ILLEGAL
+                }),
+            ]
+        );
+
+        let (_, res) = run(&args(vec![nfs_argop4::OP_ILLEGAL]));
+        assert_eq!(res.status, nfsstat4::NFS4ERR_OP_ILLEGAL);
+    }
+
+    #[test]
+    fn undecodable_operations() {
+        // The arguments of the LOOKUP are cut short:
+        let mut call = args(vec![nfs_argop4::OP_PUTROOTFH, lookup("child")]);
+        call.truncate(call.len() - 4);
+        let (_, res) = run(&call);
+        assert_eq!(res.status, nfsstat4::NFS4ERR_BADXDR);
+        assert_eq!(
+            res.resarray[1],
+            nfs_resop4::OP_LOOKUP(LOOKUP4res {
+                status: nfsstat4::NFS4ERR_BADXDR
+            })
+        );
+
+        // There are fewer operations than the call claims:
+        let mut call = args(vec![nfs_argop4::OP_PUTROOTFH]);
+        call[15] = 2;
+        let (_, res) = run(&call);
+        assert_eq!(res.status, nfsstat4::NFS4ERR_BADXDR);
+        assert_eq!(res.resarray.len(), 2);
+
+        // The header itself cannot be decoded:
+        let mut server = Server {
+            performed: Vec::new(),
+        };
+        assert!(compound(&mut server, &[0, 0, 0, 8, 1]).is_err());
+    }
+
+    #[test]
+    fn minor_version_mismatch() {
+        let call = COMPOUND4args {
+            tag: b"minor".to_vec(),
+            minorversion: 1,
+            argarray: vec![nfs_argop4::OP_PUTROOTFH],
+        }
+        .serialize_alloc();
+
+        let (server, res) = run(&call);
+        assert_eq!(res.status, nfsstat4::NFS4ERR_MINOR_VERS_MISMATCH);
+        assert_eq!(res.tag, b"minor");
+        assert!(res.resarray.is_empty());
+        assert!(server.performed.is_empty());
+    }
+}
@@ -0,0 +1,6 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+include!(concat!(env!("OUT_DIR"), "/nfs4_prot.rs"));
+
+pub mod compound;
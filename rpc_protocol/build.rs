@@ -1,6 +1,5 @@
 fn main() {
-    xdr_codegen::Compiler::new()
-        .file("rpc_prot.x")
-        .run()
-        .expect("That should have worked. :(");
+    xdr_codegen::build_helper()
+        .spec_with("rpc_prot.x", |c| c.enable_deserialize_from())
+        .run();
 }
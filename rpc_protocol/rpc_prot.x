@@ -3,7 +3,16 @@ enum AuthFlavor {
     Sys        = 1,
     Short      = 2,
     DH         = 3,
-    RpcsecGss  = 6
+    RpcsecGss  = 6,
+    Tls        = 7,  /* only the credential of the probe that starts RPC-with-TLS (RFC 9289) */
+
+    /* Flavors in the range 400000-409999 are reserved for private use. This one is only sent as a
+     * call or reply verifier between this crate's NFS client and server, to carry a checksum of the
+     * data in READ replies. */
+    ReadDigest = 400100,
+    /* Likewise, to carry a checksum of the data in WRITE calls, and of the data the server
+     * received in its reply. */
+    WriteDigest = 400101
 };
 
 struct OpaqueAuth {
@@ -11,6 +20,15 @@ struct OpaqueAuth {
     opaque body<400>;
 };
 
+/* The body of an AUTH_SYS credential. */
+struct AuthSysParms {
+    unsigned int stamp;
+    string machinename<255>;
+    unsigned int uid;
+    unsigned int gid;
+    unsigned int gids<16>;
+};
+
 enum MessageType {
     Call  = 0,
     Reply = 1
@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Calls that a server makes to its clients, such as the CB_RECALL that takes back an NFSv4
+// delegation, or the NLM_GRANTED that tells a client that a lock it waited for is now held.
+//
+// A callback is made either on a connection that the client made to the server (bidirectional RPC;
+// see `ring::Backchannel`), or on a connection that the server makes to an address the client gave
+// it, with a `CallbackClient`. Either way, the server chooses the XIDs of its callbacks from an
+// `XidSpace` of its own, apart from the XIDs of the calls it receives, which the clients choose. On
+// a connection that carries both, a reply is only matched against the callbacks sent on it, and a
+// call is dispatched as any other, so the same XID going each way is never confused.
+//
+//     let mut client = CallbackClient::connect(address, Duration::from_secs(10))?;
+//     let result = client.call(&Callback {
+//         prog: NFS4_CALLBACK,
+//         vers: 1,
+//         proc: CB_COMPOUND,
+//         cred: OpaqueAuth::none(),
+//         arg,
+//     })?;
+
+use std::{
+    io,
+    net::{SocketAddr, TcpStream},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::*;
+
+/// A call that a server makes to a client.
+#[derive(Clone, Debug)]
+pub struct Callback {
+    pub prog: u32,
+    pub vers: u32,
+    pub proc: u32,
+
+    /// The credential of the call, which the client may check to tell that it comes from the
+    /// server.
+    pub cred: OpaqueAuth,
+
+    /// The encoded argument.
+    pub arg: Vec<u8>,
+}
+
+impl Callback {
+    /// Encode the call with the given XID, with its record mark.
+    pub fn encode(&self, xid: u32) -> Vec<u8> {
+        let message = RpcMessage {
+            xid,
+            body: RpcMessageBody::Call(CallBody {
+                rpcvers: RPC_VERSION,
+                prog: self.prog,
+                vers: self.vers,
+                proc: self.proc,
+                cred: self.cred.clone(),
+                verf: OpaqueAuth::none(),
+            }),
+        };
+
+        let mut buf = buf_with_dummy_record_mark();
+        buf.append(&mut message.serialize_alloc());
+        buf.extend_from_slice(&self.arg);
+        update_record_mark(&mut buf);
+
+        buf
+    }
+}
+
+/// The XIDs of a server's callbacks.
+#[derive(Debug)]
+pub struct XidSpace {
+    next: u32,
+}
+
+impl XidSpace {
+    /// XIDs from one derived from the time, so that a restarted server does not give its callbacks
+    /// the XIDs of those it made before, whose replies may still arrive.
+    pub fn new() -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Self {
+            next: (now.as_secs() as u32).rotate_left(16) ^ now.subsec_nanos(),
+        }
+    }
+
+    /// The XID for the next callback.
+    pub fn allocate(&mut self) -> u32 {
+        let xid = self.next;
+        self.next = self.next.wrapping_add(1);
+
+        xid
+    }
+}
+
+impl Default for XidSpace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A connection that a server made to a client, to make callbacks to an address that the client
+/// gave it, such as the callback address of an NFSv4.0 SETCLIENTID.
+///
+/// Calls block until their reply arrives or the timeout passes, so they should be made on a thread
+/// of their own rather than that of a server, which can pass the results back to the server with a
+/// `ring::StateUpdater`.
+pub struct CallbackClient {
+    stream: TcpStream,
+    xids: XidSpace,
+
+    /// Whether a call failed in a way that leaves the connection in an unknown state, such as in
+    /// the middle of a reply that timed out.
+    broken: bool,
+}
+
+impl CallbackClient {
+    /// Connect to a client at `address`, waiting up to `timeout` for the connection, and for each
+    /// reply.
+    pub fn connect(address: SocketAddr, timeout: Duration) -> io::Result<Self> {
+        let stream = TcpStream::connect_timeout(&address, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            stream,
+            xids: XidSpace::new(),
+            broken: false,
+        })
+    }
+
+    /// The address of the client.
+    pub fn peer_address(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /// Make `callback`, and return the result of the client's reply. A reply that is not
+    /// successful is returned as an error, as `client::do_rpc_call()` returns it. Any other error,
+    /// such as a timeout, leaves the connection unusable, and every call after it fails too: the
+    /// caller should connect again.
+    pub fn call(&mut self, callback: &Callback) -> Result<Vec<u8>, Error> {
+        if self.broken {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "an earlier callback on the connection failed",
+            )));
+        }
+
+        let xid = self.xids.allocate();
+        let res = self.exchange(xid, callback);
+        if matches!(res, Err(Error::Io(_) | Error::Protocol(_))) {
+            self.broken = true;
+        }
+
+        res
+    }
+
+    fn exchange(&mut self, xid: u32, callback: &Callback) -> Result<Vec<u8>, Error> {
+        self.stream.write_all(&callback.encode(xid))?;
+
+        let len = stream_record_mark(&mut self.stream)?;
+        let mut record = vec![0; len as usize];
+        self.stream.read_exact(&mut record)?;
+
+        decode_reply(xid, &record).map(|(_, result)| result)
+    }
+}
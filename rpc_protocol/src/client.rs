@@ -1,6 +1,8 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
+use std::time::{Duration, Instant};
+
 use crate::*;
 
 /// Do an RPC call indicated by the `prog`, `vers`, and `proc`, arguments, using the given
@@ -18,13 +20,28 @@ pub fn do_rpc_call<S: Read + Write>(
     proc: u32,
     arg: &[u8],
 ) -> Result<Vec<u8>, Error> {
+    do_rpc_call_with_verifier(stream, prog, vers, proc, OpaqueAuth::none(), arg)
+        .map(|(_, result)| result)
+}
+
+/// Like `do_rpc_call_with_verifier()`, but also sends the given credential with the call, rather
+/// than an AUTH_NONE one.
+pub fn do_rpc_call_with_auth<S: Read + Write>(
+    stream: &mut S,
+    prog: u32,
+    vers: u32,
+    proc: u32,
+    cred: OpaqueAuth,
+    verf: OpaqueAuth,
+    arg: &[u8],
+) -> Result<(OpaqueAuth, Vec<u8>), Error> {
     let body = RpcMessageBody::Call(CallBody {
         rpcvers: RPC_VERSION,
         prog,
         vers,
         proc,
-        cred: OpaqueAuth::none(),
-        verf: OpaqueAuth::none(),
+        cred,
+        verf,
     });
 
     let xid = get_xid();
@@ -45,10 +62,23 @@ pub fn do_rpc_call<S: Read + Write>(
     read_reply_from_stream(xid, stream)
 }
 
+/// Like `do_rpc_call()`, but sends the given verifier with the call, and returns the verifier from
+/// the server's reply along with the result.
+pub fn do_rpc_call_with_verifier<S: Read + Write>(
+    stream: &mut S,
+    prog: u32,
+    vers: u32,
+    proc: u32,
+    verf: OpaqueAuth,
+    arg: &[u8],
+) -> Result<(OpaqueAuth, Vec<u8>), Error> {
+    do_rpc_call_with_auth(stream, prog, vers, proc, OpaqueAuth::none(), verf, arg)
+}
+
 fn read_reply_from_stream<S: Read + Write>(
     xid: u32,
     stream: &mut S,
-) -> Result<Vec<u8>, crate::Error> {
+) -> Result<(OpaqueAuth, Vec<u8>), crate::Error> {
     let message_length = stream_record_mark(stream)?;
 
     let mut buf = vec![0; message_length as usize];
@@ -56,32 +86,212 @@ fn read_reply_from_stream<S: Read + Write>(
         return Err(Error::Io(e));
     }
 
-    let mut message = RpcMessage::default();
-    let mut rest = buf.as_slice();
-    if RpcMessage::deserialize(&mut message, &mut rest).is_err() {
-        return Err(Error::Protocol(ProtocolError::Decode));
+    decode_reply(xid, &buf)
+}
+
+/// A connection on which each call may be given a deadline.
+///
+/// A call that is not answered before its deadline returns `Error::TimedOut`. Its reply may still
+/// arrive later, where it would be taken for the reply to the next call, so the connection is then
+/// poisoned: every later call on it fails without being sent, and the caller should open a new one.
+/// A reply that could not be read in whole, for any other reason, poisons it too.
+///
+/// The connection sets the read timeout of its stream for each call, so the stream's own read
+/// timeout is not kept.
+pub struct Connection<S> {
+    stream: S,
+    poisoned: bool,
+}
+
+impl<S: Read + Write + ReadTimeout> Connection<S> {
+    pub fn new(stream: S) -> Self {
+        Connection {
+            stream,
+            poisoned: false,
+        }
+    }
+
+    /// Like `do_rpc_call()`, but gives up waiting for the reply after `timeout`, if given.
+    pub fn call(
+        &mut self,
+        prog: u32,
+        vers: u32,
+        proc: u32,
+        arg: &[u8],
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, Error> {
+        let (cred, verf) = (OpaqueAuth::none(), OpaqueAuth::none());
+        self.call_with_auth(prog, vers, proc, cred, verf, arg, timeout)
+            .map(|(_, result)| result)
     }
 
-    // Assuming that the stream was just used for sending the message indicated by the arg `xid`, it
-    // is unexpected to get a different XID back in the reply:
-    if message.xid != xid {
-        return Err(Error::Protocol(ProtocolError::Decode));
-    };
+    /// Like `do_rpc_call_with_auth()`, but gives up waiting for the reply after `timeout`, if
+    /// given.
+    #[allow(clippy::too_many_arguments)]
+    pub fn call_with_auth(
+        &mut self,
+        prog: u32,
+        vers: u32,
+        proc: u32,
+        cred: OpaqueAuth,
+        verf: OpaqueAuth,
+        arg: &[u8],
+        timeout: Option<Duration>,
+    ) -> Result<(OpaqueAuth, Vec<u8>), Error> {
+        if self.poisoned {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "An earlier call on this connection was not answered",
+            )));
+        }
 
-    // It is unexpected to receive a Call message after sending a Call message:
-    let RpcMessageBody::Reply(reply) = message.body else {
-        return Err(Error::Protocol(ProtocolError::Decode));
-    };
+        let mut stream = DeadlineStream {
+            stream: &mut self.stream,
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+        };
+        let res = do_rpc_call_with_auth(&mut stream, prog, vers, proc, cred, verf, arg);
+        let res = match res {
+            Err(Error::Io(e)) if is_timeout(&e) => Err(Error::TimedOut),
+            res => res,
+        };
+        if matches!(
+            res,
+            Err(Error::TimedOut | Error::Io(_) | Error::Protocol(_))
+        ) {
+            debug!("Poisoning the connection after a call that failed: {res:?}");
+            self.poisoned = true;
+        }
 
-    // Only continue for accepted succesful replies: anything else is returned as an error:
-    let ReplyBody::Accepted(ref arep) = reply else {
-        return Err(Error::Rpc(reply));
-    };
-    let AcceptedReplyBody::Success(_) = arep.reply_data else {
-        return Err(Error::Rpc(reply));
-    };
+        res
+    }
+
+    /// Whether a call has failed in a way that leaves the connection unusable.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// The stream of the connection.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+/// A stream whose reads can be made to give up after a timeout.
+pub trait ReadTimeout {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>;
+}
+
+impl ReadTimeout for std::net::TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        std::net::TcpStream::set_read_timeout(self, timeout)
+    }
+}
 
-    // The entire header was already been decoded, so the rest of the message is the return value
-    // of the RPC Call:
-    Ok(rest.to_vec())
+impl ReadTimeout for std::os::unix::net::UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        std::os::unix::net::UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl ReadTimeout for pipe::Endpoint {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        pipe::Endpoint::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(feature = "tls")]
+impl<S: Read + Write + ReadTimeout> ReadTimeout for TlsStream<S> {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.sock.set_read_timeout(timeout)
+    }
+}
+
+/// A stream whose reads fail with `TimedOut` once `deadline` has passed.
+struct DeadlineStream<'a, S> {
+    stream: &'a mut S,
+    deadline: Option<Instant>,
+}
+
+impl<S: Read + ReadTimeout> Read for DeadlineStream<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Each read waits only as long as is left until the deadline:
+        let timeout = match self.deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    return Err(std::io::ErrorKind::TimedOut.into());
+                }
+                Some(left)
+            }
+            None => None,
+        };
+        self.stream.set_read_timeout(timeout)?;
+
+        self.stream.read(buf)
+    }
+}
+
+impl<S: Write> Write for DeadlineStream<'_, S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Whether `e` is a read that timed out, which is `WouldBlock` on Unix.
+fn is_timeout(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+    )
+}
+
+/// A connection on which `start_tls()` has started TLS.
+#[cfg(feature = "tls")]
+pub type TlsStream<S> = rustls::StreamOwned<rustls::ClientConnection, S>;
+
+/// Ask the server at the other end of `stream` to start TLS, with a probe to the NULL procedure of
+/// program `prog`, version `vers` (see `rpc_protocol::tls`), and once it agrees, complete a
+/// handshake with `config`, checking that the server's certificate is for `server_name`.
+///
+/// A server that does not offer TLS rejects the probe, which is returned as an error, as is a reply
+/// that accepts the probe without agreeing to start TLS.
+#[cfg(feature = "tls")]
+pub fn start_tls<S: Read + Write>(
+    mut stream: S,
+    prog: u32,
+    vers: u32,
+    config: std::sync::Arc<rustls::ClientConfig>,
+    server_name: rustls::pki_types::ServerName<'static>,
+) -> Result<TlsStream<S>, Error> {
+    let probe = crate::tls::probe_credential();
+    let (verf, _) =
+        do_rpc_call_with_auth(&mut stream, prog, vers, 0, probe, OpaqueAuth::none(), &[])?;
+    if verf != crate::tls::starttls_verifier() {
+        debug!("The server accepted the probe to start TLS, but did not agree to it");
+        return Err(Error::Protocol(ProtocolError::UnsupportedAuth));
+    }
+
+    let mut connection = rustls::ClientConnection::new(config, server_name)
+        .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+    while connection.is_handshaking() {
+        connection.complete_io(&mut stream)?;
+    }
+
+    Ok(TlsStream::new(connection, stream))
+}
+
+/// Like `do_rpc_call()`, over a connection on which `start_tls()` has started TLS.
+#[cfg(feature = "tls")]
+pub fn do_rpc_call_tls<S: Read + Write>(
+    stream: &mut TlsStream<S>,
+    prog: u32,
+    vers: u32,
+    proc: u32,
+    arg: &[u8],
+) -> Result<Vec<u8>, Error> {
+    do_rpc_call(stream, prog, vers, proc, arg)
 }
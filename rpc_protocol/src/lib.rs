@@ -1,21 +1,37 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
+#[cfg(feature = "server")]
+pub mod callback;
+#[cfg(feature = "client")]
 pub mod client;
+#[cfg(feature = "server")]
+pub mod metrics;
+#[cfg(feature = "server")]
+pub mod record;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub mod ring;
+#[cfg(feature = "server")]
 pub mod server;
+#[cfg(feature = "server")]
+pub mod throttle;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "server")]
+pub mod trace;
 
 use log::*;
 
-use std::{
-    fmt,
-    io::{Read, Write},
-};
+use std::fmt;
+#[cfg(any(feature = "client", feature = "server"))]
+use std::io::{Read, Write};
+use std::net::SocketAddr;
 
 include!(concat!(env!("OUT_DIR"), "/rpc_prot.rs"));
 
 pub use rpc_prot::{
-    AcceptedReply, AcceptedReplyBody, AuthFlavor, AuthStat, CallBody, OpaqueAuth, ProgMismatchBody,
-    RejectedReply, ReplyBody, RpcMessage, RpcMessageBody,
+    AcceptedReply, AcceptedReplyBody, AuthFlavor, AuthStat, AuthSysParms, CallBody, OpaqueAuth,
+    ProgMismatchBody, RejectedReply, ReplyBody, RpcMessage, RpcMessageBody, RpcMismatchBody,
 };
 
 /// Only supported version of the RPC Protocol
@@ -30,14 +46,20 @@ pub enum Error {
 
     /// Some RPC errors are returned by the server implementation (for example, unknown procedure),
     /// and some are returned by the procedure implementation (for example garbage args, or
-    /// internal error like ENOMEM).
+    /// internal error like ENOMEM). This is the reply that the server sends for them.
     ///
     // XXX: would it make sense to separate out the library-generated and user-generated errors
     // into separate variants?
     Rpc(ReplyBody),
 
+    /// A call was not answered succesfully, which the client decodes from the server's reply.
+    Call(RpcCallError),
+
     /// Errors returned by I/O failures.
     Io(std::io::Error),
+
+    /// A call was not answered before its deadline (see `client::Connection`).
+    TimedOut,
 }
 
 impl std::error::Error for Error {}
@@ -47,7 +69,9 @@ impl fmt::Display for Error {
         match self {
             Self::Protocol(e) => write!(f, "Protocol error: {e}"),
             Self::Rpc(e) => write!(f, "RPC error: {e:?}"),
+            Self::Call(e) => write!(f, "Call failed: {e}"),
             Self::Io(e) => write!(f, "IO error: {e}"),
+            Self::TimedOut => write!(f, "The call was not answered in time"),
         }
     }
 }
@@ -89,11 +113,299 @@ impl fmt::Display for ProtocolError {
     }
 }
 
+/// Why a server rejected a call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// The server does not support the version of the RPC protocol used by the call; it supports
+    /// versions `low` to `high`.
+    RpcMismatch { low: u32, high: u32 },
+
+    /// The server did not accept the credential or verifier of the call.
+    Auth(AuthError),
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RpcMismatch { low, high } => {
+                write!(f, "RPC versions {low} to {high} are supported")
+            }
+            Self::Auth(e) => write!(f, "Authentication error: {e}"),
+        }
+    }
+}
+
+impl TryFrom<RejectedReply> for Rejection {
+    type Error = ProtocolError;
+
+    /// Fails for an AUTH_ERROR reply with the status AUTH_OK, which is not an error.
+    fn try_from(reply: RejectedReply) -> Result<Self, ProtocolError> {
+        match reply {
+            RejectedReply::RpcMismatch(RpcMismatchBody { low, high }) => {
+                Ok(Self::RpcMismatch { low, high })
+            }
+            RejectedReply::AuthError(stat) => AuthError::try_from(stat).map(Self::Auth),
+        }
+    }
+}
+
+impl From<Rejection> for RejectedReply {
+    fn from(rejection: Rejection) -> Self {
+        match rejection {
+            Rejection::RpcMismatch { low, high } => {
+                RejectedReply::RpcMismatch(RpcMismatchBody { low, high })
+            }
+            Rejection::Auth(e) => RejectedReply::AuthError(e.into()),
+        }
+    }
+}
+
+/// Why a server did not answer a call succesfully: each reply other than a succesful one, as the
+/// client decodes it from the `ReplyBody`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcCallError {
+    /// The server does not offer the program that was called.
+    ProgUnavail,
+
+    /// The server offers versions `low` to `high` of the program, but not the one that was called.
+    ProgMismatch { low: u32, high: u32 },
+
+    /// The program does not have the procedure that was called.
+    ProcUnavail,
+
+    /// The procedure could not decode its arguments.
+    GarbageArgs,
+
+    /// The server had an error of its own, such as running out of memory.
+    SystemErr,
+
+    /// The server did not accept the credential or verifier of the call.
+    AuthError(AuthError),
+
+    /// The server does not support the version of the RPC protocol used by the call; it supports
+    /// versions `low` to `high`.
+    RpcMismatch { low: u32, high: u32 },
+}
+
+impl fmt::Display for RpcCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ProgUnavail => write!(f, "program unavailable"),
+            Self::ProgMismatch { low, high } => {
+                write!(f, "program versions {low} to {high} are supported")
+            }
+            Self::ProcUnavail => write!(f, "procedure unavailable"),
+            Self::GarbageArgs => write!(f, "the server could not decode the arguments"),
+            Self::SystemErr => write!(f, "system error on the server"),
+            Self::AuthError(e) => write!(f, "authentication error: {e}"),
+            Self::RpcMismatch { low, high } => {
+                write!(f, "RPC versions {low} to {high} are supported")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RpcCallError {}
+
+impl From<Rejection> for RpcCallError {
+    fn from(rejection: Rejection) -> Self {
+        match rejection {
+            Rejection::RpcMismatch { low, high } => Self::RpcMismatch { low, high },
+            Rejection::Auth(e) => Self::AuthError(e),
+        }
+    }
+}
+
+impl TryFrom<ReplyBody> for RpcCallError {
+    type Error = ProtocolError;
+
+    /// Fails for a succesful reply, and for an AUTH_ERROR reply with the status AUTH_OK, neither of
+    /// which is an error.
+    fn try_from(reply: ReplyBody) -> Result<Self, ProtocolError> {
+        let accepted = match reply {
+            ReplyBody::Accepted(accepted) => accepted,
+            ReplyBody::Denied(rejected) => return Rejection::try_from(rejected).map(Self::from),
+        };
+
+        Ok(match accepted.reply_data {
+            AcceptedReplyBody::Success(_) => return Err(ProtocolError::Decode),
+            AcceptedReplyBody::ProgUnavail => Self::ProgUnavail,
+            AcceptedReplyBody::ProgMismatch(ProgMismatchBody { low, high }) => {
+                Self::ProgMismatch { low, high }
+            }
+            AcceptedReplyBody::ProcUnavail => Self::ProcUnavail,
+            AcceptedReplyBody::GarbageArgs => Self::GarbageArgs,
+            AcceptedReplyBody::SystemErr => Self::SystemErr,
+        })
+    }
+}
+
+impl From<RpcCallError> for ReplyBody {
+    fn from(e: RpcCallError) -> Self {
+        let reply_data = match e {
+            RpcCallError::ProgUnavail => AcceptedReplyBody::ProgUnavail,
+            RpcCallError::ProgMismatch { low, high } => {
+                AcceptedReplyBody::ProgMismatch(ProgMismatchBody { low, high })
+            }
+            RpcCallError::ProcUnavail => AcceptedReplyBody::ProcUnavail,
+            RpcCallError::GarbageArgs => AcceptedReplyBody::GarbageArgs,
+            RpcCallError::SystemErr => AcceptedReplyBody::SystemErr,
+            RpcCallError::AuthError(e) => return ReplyBody::Denied(Rejection::Auth(e).into()),
+            RpcCallError::RpcMismatch { low, high } => {
+                return ReplyBody::Denied(Rejection::RpcMismatch { low, high }.into());
+            }
+        };
+
+        ReplyBody::Accepted(AcceptedReply {
+            verf: OpaqueAuth::none(),
+            reply_data,
+        })
+    }
+}
+
+/// The reasons a server may give for rejecting the credential or verifier of a call: each status of
+/// `AuthStat` other than AUTH_OK.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    /// The credential is malformed, or its seal is broken.
+    BadCred,
+    /// The client must begin a new session.
+    RejectedCred,
+    /// The verifier is malformed, or its seal is broken.
+    BadVerf,
+    /// The verifier has expired or was replayed.
+    RejectedVerf,
+    /// The server requires a stronger form of authentication.
+    TooWeak,
+    /// The response verifier is invalid.
+    InvalidResp,
+    /// Authentication failed for an unknown reason.
+    Failed,
+    /// A Kerberos generic error.
+    KerbGeneric,
+    /// The Kerberos credential has expired.
+    TimeExpire,
+    /// A problem with the Kerberos ticket file.
+    TktFile,
+    /// The Kerberos authenticator could not be decoded.
+    Decode,
+    /// The network address in the Kerberos ticket is wrong.
+    NetAddr,
+    /// RPCSEC_GSS: the credential has no security context.
+    RpcsecGssCredProblem,
+    /// RPCSEC_GSS: the security context is invalid, or has expired.
+    RpcsecGssCtxProblem,
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::BadCred => "bad credential",
+                Self::RejectedCred => "credential rejected, begin a new session",
+                Self::BadVerf => "bad verifier",
+                Self::RejectedVerf => "verifier expired or replayed",
+                Self::TooWeak => "authentication too weak",
+                Self::InvalidResp => "invalid response verifier",
+                Self::Failed => "authentication failed",
+                Self::KerbGeneric => "Kerberos error",
+                Self::TimeExpire => "Kerberos credential expired",
+                Self::TktFile => "problem with the Kerberos ticket file",
+                Self::Decode => "could not decode the Kerberos authenticator",
+                Self::NetAddr => "wrong network address in the Kerberos ticket",
+                Self::RpcsecGssCredProblem => "no RPCSEC_GSS context for the credential",
+                Self::RpcsecGssCtxProblem => "RPCSEC_GSS context invalid or expired",
+            }
+        )
+    }
+}
+
+impl TryFrom<AuthStat> for AuthError {
+    type Error = ProtocolError;
+
+    /// Fails for AUTH_OK.
+    fn try_from(stat: AuthStat) -> Result<Self, ProtocolError> {
+        Ok(match stat {
+            AuthStat::Ok => return Err(ProtocolError::Decode),
+            AuthStat::BadCred => Self::BadCred,
+            AuthStat::RejectedCred => Self::RejectedCred,
+            AuthStat::BadVerf => Self::BadVerf,
+            AuthStat::RejectedVerf => Self::RejectedVerf,
+            AuthStat::TooWeak => Self::TooWeak,
+            AuthStat::InvalidResp => Self::InvalidResp,
+            AuthStat::Failed => Self::Failed,
+            AuthStat::KerbGeneric => Self::KerbGeneric,
+            AuthStat::TimeExpire => Self::TimeExpire,
+            AuthStat::TktFile => Self::TktFile,
+            AuthStat::Decode => Self::Decode,
+            AuthStat::NetAddr => Self::NetAddr,
+            AuthStat::RpcsecGssCredProblem => Self::RpcsecGssCredProblem,
+            AuthStat::RpcsecGssCtxProblem => Self::RpcsecGssCtxProblem,
+        })
+    }
+}
+
+impl From<AuthError> for AuthStat {
+    fn from(e: AuthError) -> Self {
+        match e {
+            AuthError::BadCred => Self::BadCred,
+            AuthError::RejectedCred => Self::RejectedCred,
+            AuthError::BadVerf => Self::BadVerf,
+            AuthError::RejectedVerf => Self::RejectedVerf,
+            AuthError::TooWeak => Self::TooWeak,
+            AuthError::InvalidResp => Self::InvalidResp,
+            AuthError::Failed => Self::Failed,
+            AuthError::KerbGeneric => Self::KerbGeneric,
+            AuthError::TimeExpire => Self::TimeExpire,
+            AuthError::TktFile => Self::TktFile,
+            AuthError::Decode => Self::Decode,
+            AuthError::NetAddr => Self::NetAddr,
+            AuthError::RpcsecGssCredProblem => Self::RpcsecGssCredProblem,
+            AuthError::RpcsecGssCtxProblem => Self::RpcsecGssCtxProblem,
+        }
+    }
+}
+
+/// Who made a call, as established by the server's `Authenticator`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Principal {
+    /// The caller did not identify itself (AUTH_NONE), or the call has not been authenticated.
+    #[default]
+    Anonymous,
+
+    /// A user identified by its UNIX user and group ids, as with AUTH_SYS.
+    Unix { uid: u32, gid: u32, gids: Vec<u32> },
+
+    /// A user identified by name, as by a security mechanism such as Kerberos.
+    Named(String),
+}
+
+/// The process at the other end of a UNIX domain socket, as the kernel reports it (SO_PEERCRED).
+/// Unlike the ids in an AUTH_SYS credential, these cannot be forged by the caller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerCredentials {
+    pub pid: i32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl fmt::Display for PeerCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "pid {} uid {} gid {}", self.pid, self.uid, self.gid)
+    }
+}
+
 /// A `call` holds the data needed to respond to an RPC call.
 #[derive(Debug)]
 pub struct Call<'a> {
     xid: u32,
     inner: CallBody,
+    principal: Principal,
+    peer: Option<PeerCredentials>,
+    address: Option<SocketAddr>,
+    tls: bool,
 
     /// The call's encoded argument.
     pub arg: &'a [u8],
@@ -124,6 +436,92 @@ impl Call<'_> {
     pub fn get_credential(&self) -> &OpaqueAuth {
         &self.inner.cred
     }
+
+    /// Verifier
+    pub fn get_verifier(&self) -> &OpaqueAuth {
+        &self.inner.verf
+    }
+
+    /// The caller, once the server has authenticated the call.
+    pub fn get_principal(&self) -> &Principal {
+        &self.principal
+    }
+
+    /// The process that made the call, if it was made over a UNIX domain socket.
+    pub fn get_peer(&self) -> Option<&PeerCredentials> {
+        self.peer.as_ref()
+    }
+
+    /// The address that the call came from, if it was made over an IP network.
+    pub fn get_peer_address(&self) -> Option<SocketAddr> {
+        self.address
+    }
+
+    /// Record the address that the call came from, for servers that decode calls themselves with
+    /// `decode_call()`.
+    pub fn set_peer_address(&mut self, address: Option<SocketAddr>) {
+        self.address = address;
+    }
+
+    /// Whether the call was received through TLS (see `rpc_protocol::tls`).
+    pub fn is_tls(&self) -> bool {
+        self.tls
+    }
+}
+
+/// The size of the fixed part of a call header: the XID, the message type, and the RPC version,
+/// program, version, and procedure numbers.
+const FIXED_CALL_HEADER: usize = 24;
+
+/// The size of the smallest encoded call: the fixed part of the header, an empty credential and
+/// verifier, and no argument.
+pub const MIN_CALL_SIZE: usize = FIXED_CALL_HEADER + 16;
+
+/// The fixed part of a call header, read from its fixed offsets without decoding the message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CallPeek {
+    pub xid: u32,
+    pub prog: u32,
+    pub vers: u32,
+    pub proc: u32,
+}
+
+/// Check the fixed part of the header of the call in `data` (without its record mark): that the
+/// message is long enough to be a call, that it is a call rather than a reply, and that it is for
+/// RPC version 2. Returns the header's XID and program, version, and procedure numbers, without
+/// decoding the rest of the message, so that a server can refuse bogus records, and calls for
+/// programs or procedures it does not serve, before decoding their credentials or holding on to
+/// their buffers.
+pub fn peek_call(data: &[u8]) -> Result<CallPeek, ProtocolError> {
+    if data.len() < MIN_CALL_SIZE {
+        debug!("{} bytes is too short for a call", data.len());
+        return Err(ProtocolError::Decode);
+    }
+
+    peek_fixed(data[..FIXED_CALL_HEADER].try_into().unwrap())
+}
+
+/// Check the fixed part of a call header, as `peek_call()` does.
+fn peek_fixed(fixed: &[u8; FIXED_CALL_HEADER]) -> Result<CallPeek, ProtocolError> {
+    let word = |i: usize| u32::from_be_bytes(fixed[4 * i..][..4].try_into().unwrap());
+
+    // The message type, which is 0 for a call:
+    if word(1) != 0 {
+        debug!("Message of type {} where a call was expected", word(1));
+        return Err(ProtocolError::Decode);
+    }
+
+    if word(2) != RPC_VERSION {
+        debug!("CALL with unexpected RPC version: {}", word(2));
+        return Err(ProtocolError::WrongRpcVersion);
+    }
+
+    Ok(CallPeek {
+        xid: word(0),
+        prog: word(3),
+        vers: word(4),
+        proc: word(5),
+    })
 }
 
 /// Given an encoded RPC call in `data` (including both the call header and the encoded arguments),
@@ -135,26 +533,57 @@ impl Call<'_> {
 /// The caller is expected to provide a complete RPC call record without the record mark prefix (if
 /// present). If the caller is using a transport layer that uses record marking, like TCP, the
 /// caller must handle decoding the record mark and reading a cmplete record. Passing a record that
-/// is too short is returned as a decoding error.
+/// is too short is returned as a decoding error. The fixed part of the header is checked with
+/// `peek_call()` before the rest is decoded.
 pub fn decode_call(data: &[u8]) -> Result<Call<'_>, ProtocolError> {
+    peek_call(data)?;
+
     let mut message = RpcMessage::default();
     let mut rest = data;
 
     if let Err(e) = message.deserialize(&mut rest) {
+        debug!("Error deserializing call: {e}");
+        return Err(ProtocolError::Decode);
+    }
+
+    let mut call = call_from_message(message)?;
+    debug!("argument length {} bytes", rest.len());
+    call.arg = rest;
+
+    Ok(call)
+}
+
+/// Like `decode_call()`, but reads only the call header from `input`, leaving the argument to be
+/// read from it by the caller. The returned call's `arg` is empty. The fixed part of the header is
+/// read and checked first.
+#[cfg(feature = "server")]
+fn read_call_header(input: &mut impl Read) -> Result<Call<'static>, ProtocolError> {
+    let mut fixed = [0; FIXED_CALL_HEADER];
+    if let Err(e) = input.read_exact(&mut fixed) {
+        debug!("Could not read a call header: {e}");
+        return Err(ProtocolError::Decode);
+    }
+    peek_fixed(&fixed)?;
+
+    let mut message = RpcMessage::default();
+    if let Err(e) = message.deserialize_from(&mut fixed.as_slice().chain(input)) {
         warn!("Error deserializing message: {e}");
-        todo!();
+        return Err(ProtocolError::Decode);
     }
 
+    call_from_message(message)
+}
+
+/// Check that `message` is a call for a supported RPC version, and return it with an empty
+/// argument.
+fn call_from_message(message: RpcMessage) -> Result<Call<'static>, ProtocolError> {
     let RpcMessageBody::Call(call) = message.body else {
         return Err(ProtocolError::Decode);
     };
 
     debug!(
-        "recieved CALL for program {}, version {}, procedure {}, argument length {} bytes",
-        call.prog,
-        call.vers,
-        call.proc,
-        rest.len(),
+        "recieved CALL for program {}, version {}, procedure {}",
+        call.prog, call.vers, call.proc,
     );
 
     if call.rpcvers != RPC_VERSION {
@@ -165,12 +594,68 @@ pub fn decode_call(data: &[u8]) -> Result<Call<'_>, ProtocolError> {
     Ok(Call {
         xid: message.xid,
         inner: call,
-        arg: rest,
+        principal: Principal::Anonymous,
+        peer: None,
+        address: None,
+        tls: false,
+        arg: &[],
     })
 }
 
+/// The XID of the message in `data` (without its record mark), if it is a reply rather than a call.
+/// A connection that callbacks are made on (see `rpc_protocol::callback`) carries both.
+pub fn peek_reply(data: &[u8]) -> Option<u32> {
+    let word = |i: usize| {
+        Some(u32::from_be_bytes(
+            data.get(4 * i..)?.get(..4)?.try_into().ok()?,
+        ))
+    };
+
+    (word(1)? == 1).then_some(word(0)?)
+}
+
+/// Decode the reply in `data` (without its record mark) to the call with the given `xid`, and return
+/// the verifier and the result of a successful reply. A reply to a different call, and one that
+/// is not accepted or not successful, is returned as an error.
+#[cfg(any(feature = "client", feature = "server"))]
+fn decode_reply(xid: u32, data: &[u8]) -> Result<(OpaqueAuth, Vec<u8>), Error> {
+    let mut message = RpcMessage::default();
+    let mut rest = data;
+    if RpcMessage::deserialize(&mut message, &mut rest).is_err() {
+        return Err(Error::Protocol(ProtocolError::Decode));
+    }
+
+    // Assuming that the stream was just used for sending the message indicated by the arg `xid`, it
+    // is unexpected to get a different XID back in the reply:
+    if message.xid != xid {
+        return Err(Error::Protocol(ProtocolError::Decode));
+    };
+
+    // It is unexpected to receive a Call message after sending a Call message:
+    let RpcMessageBody::Reply(reply) = message.body else {
+        return Err(Error::Protocol(ProtocolError::Decode));
+    };
+
+    // Only continue for accepted succesful replies: anything else is returned as an error:
+    let verf = match reply {
+        ReplyBody::Accepted(AcceptedReply {
+            verf,
+            reply_data: AcceptedReplyBody::Success(_),
+        }) => verf,
+        reply => {
+            let e = RpcCallError::try_from(reply).map_err(Error::Protocol)?;
+            return Err(Error::Call(e));
+        }
+    };
+
+    // The entire header was already been decoded, so the rest of the message is the return value
+    // of the RPC Call:
+    Ok((verf, rest.to_vec()))
+}
+
 /// Given a buffer that contains an encoded message, prefaced by a dummy record mark, update that
 /// record mark based on the actual length of the message.
+#[cfg(any(feature = "client", feature = "server"))]
 fn update_record_mark(buf: &mut [u8]) {
     // size of message, not including the 4 bytes for the record mark itself:
     let message_size = u32::try_from(buf.len() - 4).unwrap();
@@ -179,12 +664,13 @@ fn update_record_mark(buf: &mut [u8]) {
 }
 
 /// Reads 4 bytes from the given stream, and interprets them as a record mark.
+#[cfg(any(feature = "client", feature = "server"))]
 fn stream_record_mark<S: Read>(stream: &mut S) -> Result<u32, crate::Error> {
     let mut record_mark_bytes: [u8; 4] = [0; 4];
 
     stream.read_exact(&mut record_mark_bytes).inspect_err(|e| {
         if e.kind() != std::io::ErrorKind::UnexpectedEof {
-            eprintln!("Error getting record mark: error reading from stream: {e}");
+            debug!("Error getting record mark: error reading from stream: {e}");
         }
     })?;
 
@@ -208,21 +694,47 @@ pub fn decode_record_mark(mark: &[u8; 4]) -> Result<u32, crate::Error> {
 }
 
 impl OpaqueAuth {
-    fn none() -> Self {
+    pub fn none() -> Self {
         OpaqueAuth {
             flavor: AuthFlavor::None,
             body: Vec::new(),
         }
     }
+
+    /// An AUTH_SYS credential carrying `params`.
+    pub fn sys(params: &AuthSysParms) -> Self {
+        OpaqueAuth {
+            flavor: AuthFlavor::Sys,
+            body: params.serialize_alloc(),
+        }
+    }
+}
+
+impl AuthFlavor {
+    /// The number of the flavor, as it is encoded.
+    pub fn number(&self) -> i32 {
+        match self {
+            Self::None => 0,
+            Self::Sys => 1,
+            Self::Short => 2,
+            Self::DH => 3,
+            Self::RpcsecGss => 6,
+            Self::Tls => 7,
+            Self::ReadDigest => 400100,
+            Self::WriteDigest => 400101,
+        }
+    }
 }
 
 /// Get a "unique" XID. TODO: make a real implementation for this function...
+#[cfg(feature = "client")]
 fn get_xid() -> u32 {
     17
 }
 
 /// Returns a buffer with space for a record mark already allocated, but a dummy value (0) encoded
 /// since the length of the message isn't known yet.
+#[cfg(any(feature = "client", feature = "server"))]
 fn buf_with_dummy_record_mark() -> Vec<u8> {
     vec![0, 0, 0, 0]
 }
@@ -230,32 +742,50 @@ fn buf_with_dummy_record_mark() -> Vec<u8> {
 /// An "pipe", constructed using socketpair(2), that can be used for testing client and
 /// server behavior.
 pub mod pipe {
-    use nix::sys::socket::{socketpair, AddressFamily, SockFlag, SockType};
+    use std::os::unix::net::UnixStream;
 
     pub struct Endpoint {
-        fd: std::os::fd::OwnedFd,
+        stream: UnixStream,
     }
 
     pub fn pipe() -> std::io::Result<(Endpoint, Endpoint)> {
-        let (a, b) = socketpair(
-            AddressFamily::Unix,
-            SockType::Stream,
-            None,
-            SockFlag::empty(),
-        )?;
+        let (a, b) = UnixStream::pair()?;
+
+        Ok((Endpoint { stream: a }, Endpoint { stream: b }))
+    }
 
-        Ok((Endpoint { fd: a }, Endpoint { fd: b }))
+    impl Endpoint {
+        /// Another handle to the same endpoint, to read from in one thread while writing in
+        /// another.
+        pub fn try_clone(&self) -> std::io::Result<Self> {
+            Ok(Endpoint {
+                stream: self.stream.try_clone()?,
+            })
+        }
+
+        /// Shut down the reading or writing half, or both, as the other end sees an orderly close.
+        pub fn shutdown(&self, how: std::net::Shutdown) -> std::io::Result<()> {
+            self.stream.shutdown(how)
+        }
+
+        /// Make reads fail with `WouldBlock` after `timeout`, rather than block forever.
+        pub fn set_read_timeout(
+            &self,
+            timeout: Option<std::time::Duration>,
+        ) -> std::io::Result<()> {
+            self.stream.set_read_timeout(timeout)
+        }
     }
 
     impl std::io::Read for Endpoint {
         fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-            Ok(nix::unistd::read(&self.fd, buf)?)
+            self.stream.read(buf)
         }
     }
 
     impl std::io::Write for Endpoint {
         fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-            Ok(nix::unistd::write(&self.fd, buf)?)
+            self.stream.write(buf)
         }
 
         fn flush(&mut self) -> std::io::Result<()> {
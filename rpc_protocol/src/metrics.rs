@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Counts of the calls that a server answers, per procedure, for monitoring.
+//
+// A `Metrics` is a handle to one set of counts, which any number of programs and connections can
+// share. `RpcProgram::set_metrics()` makes a program record each call it answers: whether it
+// failed, how many bytes the call and its reply took, and how long the reply took, in a histogram.
+// The fragments that calls are received in are counted too, in `RecordStats` (see
+// `rpc_protocol::record`). The counts can be read with `snapshot()` and `records()`, or served to
+// Prometheus in its text format:
+//
+//     let metrics = Metrics::default();
+//     program.set_metrics(metrics.clone());
+//     metrics.serve_prometheus(TcpListener::bind("[::]:9100")?);
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use log::*;
+
+use crate::{record::RecordStats, server::ProcedureNames, Call};
+
+/// The upper bounds of the buckets of the latency histogram.
+pub const LATENCY_BUCKETS: [Duration; 13] = [
+    Duration::from_micros(100),
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_micros(2500),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+];
+
+/// A procedure of a version of a program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProcedureKey {
+    pub program: u32,
+    pub version: u32,
+    pub procedure: u32,
+}
+
+/// What has been counted of the calls to a procedure.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProcedureStats {
+    /// The procedure's name, if the program that answered it knows it.
+    pub name: Option<&'static str>,
+
+    pub calls: u64,
+
+    /// Calls that were not answered with a succesful reply: rejected ones, ones for a procedure
+    /// that is not available, and ones whose procedure returned an error.
+    pub errors: u64,
+
+    /// The bytes of the calls and of the replies, including their record marks.
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+
+    /// The number of calls in each bucket of `LATENCY_BUCKETS`: those that took longer than the
+    /// previous bound, and no longer than this one. Calls that took longer than every bound are
+    /// only counted in `calls`.
+    pub latency_buckets: [u64; LATENCY_BUCKETS.len()],
+
+    /// The total time taken by the calls.
+    pub latency_sum: Duration,
+}
+
+/// What a server records of one call.
+pub struct Sample {
+    pub bytes_in: usize,
+    pub bytes_out: usize,
+    pub error: bool,
+
+    /// From when the call was received to when its reply was handed to the transport.
+    pub latency: Duration,
+}
+
+/// A handle to a set of counts, which clones share.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    procedures: Arc<Mutex<BTreeMap<ProcedureKey, ProcedureStats>>>,
+    records: Arc<Mutex<RecordStats>>,
+}
+
+impl Metrics {
+    /// Count `call`, whose procedure `names` may name.
+    pub fn record(&self, call: &Call, names: Option<ProcedureNames>, sample: Sample) {
+        let key = ProcedureKey {
+            program: call.get_program(),
+            version: call.get_version(),
+            procedure: call.get_procedure(),
+        };
+
+        let mut procedures = self.procedures.lock().unwrap();
+        let stats = procedures.entry(key).or_insert_with(|| ProcedureStats {
+            name: names.and_then(|names| names(key.version, key.procedure)),
+            ..Default::default()
+        });
+
+        stats.calls += 1;
+        stats.errors += u64::from(sample.error);
+        stats.bytes_in += sample.bytes_in as u64;
+        stats.bytes_out += sample.bytes_out as u64;
+        stats.latency_sum += sample.latency;
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|b| sample.latency <= *b) {
+            stats.latency_buckets[bucket] += 1;
+        }
+    }
+
+    /// Add `stats`, counted of the records of a connection, to the counts of all connections.
+    pub fn count_records(&self, stats: &RecordStats) {
+        self.records.lock().unwrap().add(stats);
+    }
+
+    /// What has been counted of the records of all connections.
+    pub fn records(&self) -> RecordStats {
+        *self.records.lock().unwrap()
+    }
+
+    /// The counts of every procedure that has been called, in order.
+    pub fn snapshot(&self) -> Vec<(ProcedureKey, ProcedureStats)> {
+        let procedures = self.procedures.lock().unwrap();
+
+        procedures
+            .iter()
+            .map(|(key, stats)| (*key, stats.clone()))
+            .collect()
+    }
+
+    /// The counts in the Prometheus text exposition format.
+    pub fn prometheus(&self) -> String {
+        let snapshot = self.snapshot();
+        let mut out = String::new();
+
+        let counter = |out: &mut String, metric, help, value: fn(&ProcedureStats) -> u64| {
+            let _ = writeln!(out, "# HELP {metric} {help}\n# TYPE {metric} counter");
+            for (key, stats) in &snapshot {
+                let _ = writeln!(out, "{metric}{{{}}} {}", labels(key, stats), value(stats));
+            }
+        };
+        counter(&mut out, "rpc_calls_total", "Calls answered.", |s| s.calls);
+        counter(
+            &mut out,
+            "rpc_errors_total",
+            "Calls not answered succesfully.",
+            |s| s.errors,
+        );
+        counter(
+            &mut out,
+            "rpc_received_bytes_total",
+            "Bytes of calls received.",
+            |s| s.bytes_in,
+        );
+        counter(
+            &mut out,
+            "rpc_sent_bytes_total",
+            "Bytes of replies sent.",
+            |s| s.bytes_out,
+        );
+
+        let metric = "rpc_call_duration_seconds";
+        let _ = writeln!(out, "# HELP {metric} Time taken to answer calls.");
+        let _ = writeln!(out, "# TYPE {metric} histogram");
+        for (key, stats) in &snapshot {
+            let labels = labels(key, stats);
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(stats.latency_buckets) {
+                cumulative += count;
+                let le = bound.as_secs_f64();
+                let _ = writeln!(out, "{metric}_bucket{{{labels},le=\"{le}\"}} {cumulative}");
+            }
+            let _ = writeln!(
+                out,
+                "{metric}_bucket{{{labels},le=\"+Inf\"}} {}",
+                stats.calls
+            );
+            let sum = stats.latency_sum.as_secs_f64();
+            let _ = writeln!(out, "{metric}_sum{{{labels}}} {sum}");
+            let _ = writeln!(out, "{metric}_count{{{labels}}} {}", stats.calls);
+        }
+
+        // Only servers that receive calls in records count them:
+        let records = self.records();
+        if records != RecordStats::default() {
+            let mut metric = |metric, kind, help, value| {
+                let _ = writeln!(out, "# HELP {metric} {help}\n# TYPE {metric} {kind}");
+                let _ = writeln!(out, "{metric} {value}");
+            };
+            metric(
+                "rpc_records_total",
+                "counter",
+                "Records received.",
+                records.records,
+            );
+            metric(
+                "rpc_record_fragments_total",
+                "counter",
+                "Fragments of records received.",
+                records.fragments,
+            );
+            metric(
+                "rpc_fragmented_records_total",
+                "counter",
+                "Records received in more than one fragment.",
+                records.fragmented,
+            );
+            metric(
+                "rpc_tiny_record_fragments_total",
+                "counter",
+                "Fragments much smaller than their record, other than the last.",
+                records.tiny_fragments,
+            );
+            metric(
+                "rpc_incomplete_records_total",
+                "counter",
+                "Records whose connection ended partway through them.",
+                records.incomplete,
+            );
+            metric(
+                "rpc_refused_records_total",
+                "counter",
+                "Records that exceeded a limit, which ended their connection.",
+                records.refused,
+            );
+            metric(
+                "rpc_largest_record_bytes",
+                "gauge",
+                "The size of the largest record received.",
+                records.largest,
+            );
+        }
+
+        out
+    }
+
+    /// Answer each HTTP request on `listener` with the counts in the Prometheus text format, from a
+    /// new thread.
+    pub fn serve_prometheus(&self, listener: TcpListener) -> thread::JoinHandle<()> {
+        let metrics = self.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let res = stream.and_then(|stream| metrics.answer_http(stream));
+                if let Err(e) = res {
+                    debug!("Error answering metrics request: {e}");
+                }
+            }
+        })
+    }
+
+    fn answer_http(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+        // Whatever was asked for, the answer is the same, so the request is read only to its end:
+        let mut reader = BufReader::new(&stream);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+        }
+
+        let body = self.prometheus();
+        write!(
+            stream,
+            "HTTP/1.0 200 OK\r\n\
+             Content-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\n\
+             \r\n\
+             {body}",
+            body.len()
+        )
+    }
+}
+
+fn labels(key: &ProcedureKey, stats: &ProcedureStats) -> String {
+    let procedure = match stats.name {
+        Some(name) => name.to_string(),
+        None => key.procedure.to_string(),
+    };
+
+    format!(
+        "program=\"{}\",version=\"{}\",procedure=\"{procedure}\"",
+        key.program, key.version
+    )
+}
@@ -0,0 +1,410 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Reading the records of a stream connection, which a client may send in several fragments (the
+// record marking standard of RFC 5531, section 11).
+//
+// Each fragment has a header giving its length, and whether it is the last of its record. A
+// `Record` reads the fragments of one record as a single stream of bytes, without holding them, so
+// what reassembling a record costs is what the procedure reads it into. Servers that are handed the
+// bytes received on a connection, rather than reading them, reassemble its records with a
+// `Reassembly` instead, which only copies those that arrive in pieces. Limits keep a client from
+// sending records larger than any call of the program, or in a great many fragments, and a record
+// that exceeds one fails with an `InvalidData` error, which ends its connection.
+//
+// What is seen of the fragments is counted in `RecordStats`, per connection, and in a server's
+// `Metrics`, so that clients that fragment their calls strangely (in tiny fragments, or leaving
+// records unfinished) can be found.
+
+use std::io::{self, Read};
+use std::ops::Range;
+
+/// Limits on the records of a connection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecordLimits {
+    /// The most bytes a record may hold, without the headers of its fragments.
+    pub max_record: u32,
+
+    /// The most fragments a record may be sent in.
+    pub max_fragments: u32,
+
+    /// Fragments before the last of their record that hold fewer bytes than this are counted as
+    /// tiny. Clients have no reason to send them, but they are allowed, within `max_fragments`.
+    pub tiny_fragment: u32,
+}
+
+impl Default for RecordLimits {
+    fn default() -> Self {
+        Self {
+            max_record: 4 * 1024 * 1024,
+            max_fragments: 256,
+            tiny_fragment: 64,
+        }
+    }
+}
+
+/// What has been counted of the records received on a connection, or on all the connections of a
+/// server.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RecordStats {
+    /// Records received in full.
+    pub records: u64,
+
+    /// Fragments received, in full or not.
+    pub fragments: u64,
+
+    /// Records received in more than one fragment.
+    pub fragmented: u64,
+
+    /// Fragments smaller than `RecordLimits::tiny_fragment` that were not the last of their record.
+    pub tiny_fragments: u64,
+
+    /// Records that their connection ended partway through.
+    pub incomplete: u64,
+
+    /// Records that exceeded a limit, which ended their connection.
+    pub refused: u64,
+
+    /// The size of the largest record received, with the headers of its fragments.
+    pub largest: u64,
+}
+
+impl RecordStats {
+    /// Add the counts of `other` to these.
+    pub fn add(&mut self, other: &Self) {
+        self.records += other.records;
+        self.fragments += other.fragments;
+        self.fragmented += other.fragmented;
+        self.tiny_fragments += other.tiny_fragments;
+        self.incomplete += other.incomplete;
+        self.refused += other.refused;
+        self.largest = self.largest.max(other.largest);
+    }
+
+    /// Count a record received in full, in `fragments` fragments of `size` bytes in all, with their
+    /// headers.
+    fn count_record(&mut self, fragments: u32, size: usize) {
+        self.records += 1;
+        self.fragmented += u64::from(fragments > 1);
+        self.largest = self.largest.max(size as u64);
+    }
+
+    /// Whether anything was counted that a well-behaved client does not cause.
+    pub fn is_abnormal(&self) -> bool {
+        self.tiny_fragments > 0 || self.incomplete > 0 || self.refused > 0
+    }
+}
+
+/// The body of one record of `stream`, read across its fragments. It reads as ending where the
+/// record does.
+pub struct Record<'a, S> {
+    stream: S,
+    limits: &'a RecordLimits,
+    stats: &'a mut RecordStats,
+
+    /// The bytes left in the current fragment.
+    left: u32,
+
+    /// Whether the current fragment is the last of the record.
+    last: bool,
+
+    /// The fragments of the record so far, and the bytes they hold.
+    fragments: u32,
+    len: u64,
+
+    /// Set once the record was refused or found incomplete, after which reading it fails the same
+    /// way again.
+    failed: Option<io::ErrorKind>,
+}
+
+impl<'a, S: Read> Record<'a, S> {
+    /// Start reading the next record of `stream`, by reading the header of its first fragment.
+    /// Fails with `UnexpectedEof` if the stream ends before it.
+    pub fn begin(
+        stream: S,
+        limits: &'a RecordLimits,
+        stats: &'a mut RecordStats,
+    ) -> io::Result<Self> {
+        let mut record = Self {
+            stream,
+            limits,
+            stats,
+            left: 0,
+            last: false,
+            fragments: 0,
+            len: 0,
+            failed: None,
+        };
+
+        let mut header = [0; 4];
+        record.stream.read_exact(&mut header)?;
+        record.begin_fragment(header)?;
+
+        Ok(record)
+    }
+
+    /// The size of the record so far, counting all of the fragments whose headers have been read,
+    /// and their headers.
+    pub fn size(&self) -> usize {
+        (self.len + 4 * u64::from(self.fragments)) as usize
+    }
+
+    /// The stream that the record is read from.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Read and discard whatever is left of the record, and count it. Returns its size, as
+    /// `size()` does.
+    pub fn finish(mut self) -> io::Result<usize> {
+        io::copy(&mut self, &mut io::sink())?;
+        self.stats.count_record(self.fragments, self.size());
+
+        Ok(self.size())
+    }
+
+    fn begin_fragment(&mut self, header: [u8; 4]) -> io::Result<()> {
+        let (left, last) = decode_header(header);
+        self.last = last;
+        self.left = left;
+        self.fragments += 1;
+        self.len += u64::from(left);
+
+        count_fragment(
+            self.limits,
+            self.stats,
+            self.fragments,
+            self.len,
+            left,
+            last,
+        )
+        .inspect_err(|e| self.failed = Some(e.kind()))
+    }
+
+    fn incomplete(&mut self) -> io::Error {
+        self.stats.incomplete += 1;
+        self.failed = Some(io::ErrorKind::UnexpectedEof);
+        io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "connection closed partway through a record",
+        )
+    }
+}
+
+impl<S: Read> Read for Record<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(kind) = self.failed {
+            return Err(io::Error::new(kind, "the record was not received"));
+        }
+
+        while self.left == 0 {
+            if self.last {
+                return Ok(0);
+            }
+
+            let mut header = [0; 4];
+            match self.stream.read_exact(&mut header) {
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(self.incomplete()),
+                res => res?,
+            }
+            self.begin_fragment(header)?;
+        }
+
+        let len = buf.len().min(self.left as usize);
+        match self.stream.read(&mut buf[..len])? {
+            0 if len > 0 => Err(self.incomplete()),
+            n => {
+                self.left -= n as u32;
+                Ok(n)
+            }
+        }
+    }
+}
+
+/// The records of a stream connection, reassembled from the bytes received on it, in whatever
+/// pieces they arrive. A record that arrives whole, in one fragment, is found where it lies in the
+/// received bytes; any other is copied together as its pieces arrive.
+#[derive(Debug, Default)]
+pub struct Reassembly {
+    /// The bytes of a fragment header that was split between receives.
+    header: Vec<u8>,
+
+    /// The record being copied together, beginning with room for its record mark.
+    record: Vec<u8>,
+
+    /// The bytes left in the current fragment, and whether it is the last of its record.
+    left: u32,
+    last: bool,
+
+    /// The fragments of the record so far.
+    fragments: u32,
+
+    /// What has been counted since `take_stats()` was last called.
+    stats: RecordStats,
+}
+
+/// A record completed by `Reassembly::receive()`, with a record mark that makes it a single
+/// fragment.
+#[derive(Debug, PartialEq)]
+pub enum Reassembled {
+    /// A record that lies whole within the received bytes, in this range.
+    Whole(Range<usize>),
+
+    /// A record that was copied together from several receives or fragments.
+    Copied(Vec<u8>),
+}
+
+impl Reassembly {
+    /// Reassemble the records of `bytes`, the next received on the connection, adding those that
+    /// they complete to `records`. Fails with `InvalidData` if a record exceeds `limits`, after
+    /// which the connection should be ended.
+    pub fn receive(
+        &mut self,
+        bytes: &[u8],
+        limits: &RecordLimits,
+        records: &mut Vec<Reassembled>,
+    ) -> io::Result<()> {
+        // A refused record is counted as such, not as incomplete once the connection ends:
+        self.reassemble(bytes, limits, records)
+            .inspect_err(|_| self.discard())
+    }
+
+    /// Note that the connection has ended, counting any record that it ended partway through.
+    pub fn end(&mut self) {
+        if self.fragments > 0 || !self.header.is_empty() {
+            self.stats.incomplete += 1;
+        }
+        self.discard();
+    }
+
+    /// What has been counted of the records since this was last called.
+    pub fn take_stats(&mut self) -> RecordStats {
+        std::mem::take(&mut self.stats)
+    }
+
+    fn reassemble(
+        &mut self,
+        bytes: &[u8],
+        limits: &RecordLimits,
+        records: &mut Vec<Reassembled>,
+    ) -> io::Result<()> {
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let rest = &bytes[pos..];
+
+            if self.left > 0 {
+                let len = rest.len().min(self.left as usize);
+                self.record.extend_from_slice(&rest[..len]);
+                self.left -= len as u32;
+                pos += len;
+            } else if let Some(len) = self.whole_record(rest, limits)? {
+                records.push(Reassembled::Whole(pos..pos + len));
+                pos += len;
+                continue;
+            } else {
+                let len = rest.len().min(4 - self.header.len());
+                self.header.extend_from_slice(&rest[..len]);
+                pos += len;
+                if self.header.len() < 4 {
+                    break;
+                }
+
+                let header = std::mem::take(&mut self.header);
+                self.begin_fragment(header.try_into().unwrap(), limits)?;
+            }
+
+            if self.fragments > 0 && self.left == 0 && self.last {
+                records.push(Reassembled::Copied(self.finish()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Discard the record being reassembled, if any.
+    fn discard(&mut self) {
+        self.header.clear();
+        self.record = Vec::new();
+        self.left = 0;
+        self.fragments = 0;
+    }
+
+    /// The size of the record at the start of `bytes`, with its record mark, if it lies there
+    /// whole, in one fragment, and no other record is being copied together.
+    fn whole_record(&mut self, bytes: &[u8], limits: &RecordLimits) -> io::Result<Option<usize>> {
+        if self.fragments > 0 || !self.header.is_empty() || bytes.len() < 4 {
+            return Ok(None);
+        }
+
+        let (len, last) = decode_header(bytes[..4].try_into().unwrap());
+        let size = 4 + len as usize;
+        if !last || bytes.len() < size {
+            return Ok(None);
+        }
+
+        count_fragment(limits, &mut self.stats, 1, len.into(), len, last)?;
+        self.stats.count_record(1, size);
+
+        Ok(Some(size))
+    }
+
+    fn begin_fragment(&mut self, header: [u8; 4], limits: &RecordLimits) -> io::Result<()> {
+        let (left, last) = decode_header(header);
+        if self.fragments == 0 {
+            self.record.extend_from_slice(&[0; 4]);
+        }
+        self.left = left;
+        self.last = last;
+        self.fragments += 1;
+
+        let len = (self.record.len() - 4) as u64 + u64::from(left);
+        count_fragment(limits, &mut self.stats, self.fragments, len, left, last)
+    }
+
+    /// Give the copied record its record mark, and count it.
+    fn finish(&mut self) -> Vec<u8> {
+        let mut record = std::mem::take(&mut self.record);
+        let mark = (record.len() as u32 - 4) | (1 << 31);
+        record[..4].copy_from_slice(&mark.to_be_bytes());
+
+        let headers = 4 * (self.fragments as usize - 1);
+        self.stats
+            .count_record(self.fragments, record.len() + headers);
+        self.fragments = 0;
+
+        record
+    }
+}
+
+/// The length of a fragment, and whether it is the last of its record, from its header.
+fn decode_header(header: [u8; 4]) -> (u32, bool) {
+    let header = u32::from_be_bytes(header);
+    (header & !(1 << 31), header & (1 << 31) != 0)
+}
+
+/// Count a fragment of `left` bytes in `stats`, which brings its record to `fragments` fragments
+/// holding `len` bytes, and check the record against `limits`.
+fn count_fragment(
+    limits: &RecordLimits,
+    stats: &mut RecordStats,
+    fragments: u32,
+    len: u64,
+    left: u32,
+    last: bool,
+) -> io::Result<()> {
+    stats.fragments += 1;
+    if !last && left < limits.tiny_fragment {
+        stats.tiny_fragments += 1;
+    }
+
+    let refused = if len > limits.max_record.into() {
+        format!("record of more than {} bytes", limits.max_record)
+    } else if fragments > limits.max_fragments {
+        format!("record in more than {} fragments", limits.max_fragments)
+    } else {
+        return Ok(());
+    };
+
+    stats.refused += 1;
+    Err(io::Error::new(io::ErrorKind::InvalidData, refused))
+}
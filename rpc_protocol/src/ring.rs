@@ -0,0 +1,3250 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::ops::{Deref, Range};
+use std::os::fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::FileExt;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use io_uring::{cqueue, opcode, squeue, types, IoUring};
+use log::*;
+use nix::sys::eventfd::{EfdFlags, EventFd};
+
+use crate::callback::{Callback, XidSpace};
+use crate::record::{Reassembled, Reassembly, RecordLimits, RecordStats};
+use crate::throttle::{Class, Classifier, Throttle};
+use crate::{metrics::*, server::*, trace::*, *};
+
+const GROUP_ID: u16 = 42;
+
+/// The most that an RPC call adds to the arguments of a procedure: the record mark, the fixed fields
+/// of the call header, and a credential and verifier of up to 400 bytes each.
+pub const MAX_CALL_HEADER: u32 = 4 + 6 * 4 + 2 * (2 * 4 + 400);
+
+/// The most that an RPC reply adds to the result of a procedure: the record mark, the fixed fields
+/// of the reply header, and a verifier of up to 400 bytes.
+pub const MAX_REPLY_HEADER: u32 = 4 + 4 * 4 + 2 * 4 + 400;
+
+/// The bounds on the number of receive buffers.
+const MIN_BUFFERS: u16 = 16;
+const MAX_BUFFERS: u16 = 1024;
+
+/// The number of registered buffers that file data is sent from with `FileSends::SendZc`.
+const SEND_BUFFERS: u16 = 8;
+
+/// The size asked for the pipes that file data is spliced through with `FileSends::Splice`, which
+/// is the most an unprivileged process may ask for by default. A smaller pipe is spliced through
+/// in several steps.
+const PIPE_SIZE: i32 = 1024 * 1024;
+
+/// The io_uring implementation has a custom procedure type that takes a RingCall rather than a
+/// Call, and returns a RingResult rather than the RpcResult.
+pub type RingProcedure<T> = fn(&RingCall, &mut T) -> RingResult<T>;
+pub type RingProcedureList<T> = Vec<Option<RingProcedure<T>>>;
+
+/// The NULL Procedure is defined for every service and does nothing, succesfully.
+fn ring_null_procedure<T>(_call: &RingCall, _state: &mut T) -> RingResult<T> {
+    RingResult::Done(RpcResult::Success(vec![]))
+}
+
+pub enum RingResult<T> {
+    /// A procedure implementation can either complete synchronously, in which case it returns the
+    /// immediate result as an RpcResult...
+    Done(RpcResult),
+
+    /// A successful result can also end with data from a file, which the RpcServer sends without
+    /// first copying it into the result. Procedures may only return this when
+    /// `RingCall::sends_files()` allows it.
+    File(FileReply),
+
+    /// ...or it may need to do I/O, which will use this thread's io_uring instance. The RpcServer
+    /// submits the entry on behalf of the procedure implementation, and calls its continuation
+    /// when the completion comes in, which finishes the call with another RingResult, or does
+    /// more I/O. Other calls are answered meanwhile. See `RingResult::more_io()`.
+    MoreIo(MoreIo<T>),
+}
+
+impl<T> RingResult<T> {
+    /// Have the server submit `entry`, and call `then` with its result and the server's state once
+    /// it completes. The user data of the entry is the server's.
+    ///
+    /// # Safety
+    ///
+    /// Whatever the entry refers to, such as a file descriptor, or a buffer that it reads into,
+    /// must stay valid until it completes. Moving it into `then`, which is not dropped before
+    /// then, ensures this. If the connection closes first, `then` is still called, but what it
+    /// returns is dropped, I/O and all, without being submitted.
+    pub unsafe fn more_io(
+        entry: squeue::Entry,
+        then: impl FnOnce(io::Result<u32>, &mut T) -> RingResult<T> + 'static,
+    ) -> Self {
+        Self::MoreIo(MoreIo {
+            entry,
+            then: Box::new(then),
+        })
+    }
+}
+
+/// I/O that a procedure needs done to finish a call; see `RingResult::more_io()`.
+pub struct MoreIo<T> {
+    entry: squeue::Entry,
+    then: Continuation<T>,
+}
+
+/// How a procedure finishes a call once the I/O it needs is done.
+type Continuation<T> = Box<dyn FnOnce(io::Result<u32>, &mut T) -> RingResult<T>>;
+
+/// A successful result that ends with `len` bytes of `file`, from `offset`: the variable-length
+/// opaque data of the result, which is padded by the server.
+///
+/// The data is read when the reply is sent, which may be after the procedure returns. If the file
+/// no longer holds all of it by then, the connection is shut down, since the reply cannot be
+/// finished; the client then retransmits the call on a new connection.
+pub struct FileReply {
+    /// The encoded result up to the data, ending with its length.
+    pub head: Vec<u8>,
+
+    pub file: File,
+    pub offset: u64,
+    pub len: u32,
+}
+
+impl FileReply {
+    /// The length of the padding after the data.
+    fn padding(&self) -> usize {
+        (self.len.next_multiple_of(4) - self.len) as usize
+    }
+
+    /// Read the data into a buffer after `reply`, the encoded reply up to the data, and pad it.
+    fn read_after(&self, reply: &mut [u8], head_len: usize) -> io::Result<usize> {
+        let end = head_len + self.len as usize;
+        self.file
+            .read_exact_at(&mut reply[head_len..end], self.offset)?;
+        reply[end..end + self.padding()].fill(0);
+
+        Ok(end + self.padding())
+    }
+}
+
+/// How an `RpcServer` sends the file data of `RingResult::File` replies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileSends {
+    /// Spliced from the file to the socket, through a pipe of each connection.
+    Splice,
+
+    /// Read into buffers registered with the ring, and sent from them with zero-copy sends.
+    SendZc,
+}
+
+/// An RPC call, along with the receive buffer that it was decoded from. It dereferences to the
+/// Call, so procedures can use it just like one.
+pub struct RingCall<'a> {
+    call: Call<'a>,
+    buffer: &'a Rc<RecvBuffer>,
+
+    /// The connection that the call was received on.
+    connection: ConnectionId,
+
+    /// Whether the procedure may return `RingResult::File`.
+    sends_files: bool,
+}
+
+impl<'a> Deref for RingCall<'a> {
+    type Target = Call<'a>;
+
+    fn deref(&self) -> &Call<'a> {
+        &self.call
+    }
+}
+
+impl RingCall<'_> {
+    /// Take shared ownership of `bytes`, which must lie within the call's receive buffer (for
+    /// example, part of the argument), so that a procedure can keep using them after it returns
+    /// without copying them.
+    ///
+    /// The receive buffer is only given back to the kernel once every ArgBuffer that refers to it
+    /// has been dropped. While it is held, the kernel has one fewer buffer to receive into, so
+    /// ArgBuffers should be dropped as soon as the data is no longer needed.
+    ///
+    /// Panics if `bytes` is not part of the receive buffer.
+    pub fn retain(&self, bytes: &[u8]) -> ArgBuffer {
+        let buf = self.buffer.as_ptr_range();
+        let wanted = bytes.as_ptr_range();
+        assert!(
+            buf.start <= wanted.start && wanted.end <= buf.end,
+            "retained bytes must be part of the receive buffer"
+        );
+
+        let start = wanted.start as usize - buf.start as usize;
+
+        ArgBuffer {
+            buffer: Rc::clone(self.buffer),
+            range: start..start + bytes.len(),
+        }
+    }
+
+    /// Whether the procedure may reply with `RingResult::File`. It may not unless the server was
+    /// set up to send files, and while replies are traced or checked, since those need every reply
+    /// whole. Otherwise, the procedure must read the data into its result.
+    pub fn sends_files(&self) -> bool {
+        self.sends_files
+    }
+
+    /// The connection that the call was received on, which callbacks can be made on with a
+    /// `Backchannel` for as long as it stays open.
+    pub fn connection(&self) -> ConnectionId {
+        self.connection
+    }
+}
+
+/// A connection accepted by an `RpcServer`. A file descriptor is reused once its connection closes,
+/// so connections are told apart by the order they were accepted in, too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ConnectionId {
+    fd: i32,
+    serial: u64,
+}
+
+/// What is done with the result of a callback's reply, or with the error that ended it, with the
+/// server's state.
+pub type CallbackHandler<T> = Box<dyn FnOnce(Result<Vec<u8>, Error>, &mut T)>;
+
+/// Makes callbacks on the connections that clients made to an `RpcServer` (bidirectional RPC), for
+/// clients that take calls from the server on the same connection as they make their own.
+///
+/// Procedures, and updates from a `StateUpdater`, queue callbacks through a `Backchannel` kept in
+/// the server's state; each is sent once the completion being handled is done with, after the
+/// replies already waiting on its connection. Its XID is chosen by the server (see
+/// `rpc_protocol::callback`), and the reply that comes back on the connection is handed to its
+/// handler, instead of being taken for a call. A callback whose connection closes before the reply
+/// comes is handed an error; the server does not retransmit callbacks.
+pub struct Backchannel<T> {
+    queued: Rc<RefCell<VecDeque<QueuedCallback<T>>>>,
+}
+
+struct QueuedCallback<T> {
+    connection: ConnectionId,
+    callback: Callback,
+    then: CallbackHandler<T>,
+}
+
+impl<T> Clone for Backchannel<T> {
+    fn clone(&self) -> Self {
+        Self {
+            queued: Rc::clone(&self.queued),
+        }
+    }
+}
+
+impl<T> Backchannel<T> {
+    /// Make `callback` on `connection`, and call `then` with the result of the reply.
+    pub fn call(
+        &self,
+        connection: ConnectionId,
+        callback: Callback,
+        then: impl FnOnce(Result<Vec<u8>, Error>, &mut T) + 'static,
+    ) {
+        self.queued.borrow_mut().push_back(QueuedCallback {
+            connection,
+            callback,
+            then: Box::new(then),
+        });
+    }
+}
+
+/// Bytes of a receive buffer that are retained by a procedure; see `RingCall::retain()`.
+pub struct ArgBuffer {
+    buffer: Rc<RecvBuffer>,
+    range: Range<usize>,
+}
+
+impl Deref for ArgBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer[self.range.clone()]
+    }
+}
+
+/// A mapping between RPC procedures (identified by program, version, and procedure numbers), and
+/// the Rust code that implements them.
+pub struct ProcedureMap<T> {
+    /// The program number of this RPC service.
+    program: u32,
+
+    /// The min version number of this RPC service.
+    version_min: u32,
+
+    /// The max version number of this RPC service.
+    version_max: u32,
+
+    /// The mapping of procedure numbers to functions that implement the procedures.
+    /// If the 0th element of this array is None, the NULL procedure is answered without calling
+    /// into the service; a service that wants to see NULL calls can implement it here instead.
+    /// This structure assumes that al the versions between version_min and version_max share the
+    /// same procedures. If that assumption should turn false in the future, this structure will
+    /// have to be modified.
+    procedures: RingProcedureList<T>,
+
+    /// Used to name procedures in log messages, if set.
+    procedure_names: Option<ProcedureNames>,
+
+    /// Checks the credential of each call.
+    authenticator: Box<dyn Authenticator>,
+
+    /// Where each call is counted, if anywhere.
+    metrics: Option<Metrics>,
+
+    /// Where each call and reply is traced, while it is started.
+    tracer: Option<Tracer>,
+
+    /// If set, each reply is checked with `check_reply()` before it is sent.
+    reply_check: Option<ResultCheck>,
+
+    /// Limits on the calls of each client, and of each class of calls if there is a classifier.
+    throttle: Option<(Throttle, Option<Classifier<T>>)>,
+}
+
+impl<T> ProcedureMap<T> {
+    pub fn new(
+        program: u32,
+        version_min: u32,
+        version_max: u32,
+        procedures: RingProcedureList<T>,
+    ) -> Self {
+        Self {
+            program,
+            version_min,
+            version_max,
+            procedures,
+            procedure_names: None,
+            authenticator: Box::new(SysAuthenticator),
+            metrics: None,
+            tracer: None,
+            reply_check: None,
+            throttle: None,
+        }
+    }
+
+    /// Use `names` to refer to procedures by name, rather than by number, in log messages.
+    pub fn set_procedure_names(&mut self, names: ProcedureNames) {
+        self.procedure_names = Some(names);
+    }
+
+    /// Authenticate calls with `authenticator`, instead of the default `SysAuthenticator`.
+    pub fn set_authenticator(&mut self, authenticator: impl Authenticator + 'static) {
+        self.authenticator = Box::new(authenticator);
+    }
+
+    /// Count each call in `metrics`.
+    pub fn set_metrics(&mut self, metrics: Metrics) {
+        self.metrics = Some(metrics);
+    }
+
+    /// Write each call and reply to `tracer`, while it is started. Connections are told apart in
+    /// the trace by their file descriptors.
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Check each reply with `check_reply()` before sending it, logging an error for any that is
+    /// malformed.
+    pub fn set_reply_check(&mut self, check: ResultCheck) {
+        self.reply_check = Some(check);
+    }
+
+    /// Answer calls no faster than `throttle` allows, for each client, and for each class of calls
+    /// that `classify` finds, if given (see `rpc_protocol::throttle`). A call over the limits is
+    /// held back, with the calls after it on its connection, until it may be answered.
+    pub fn set_throttle(&mut self, throttle: Throttle, classify: Option<Classifier<T>>) {
+        self.throttle = Some((throttle, classify));
+    }
+
+    /// Take `call`, of `bytes` bytes, from the throttle's allowances, if there is a throttle, given
+    /// the server's state. Returns the class of the call, or how long until it may be answered.
+    fn admit(&self, call: &Call, bytes: usize, state: &T) -> Result<Option<Class>, Duration> {
+        let Some((throttle, classify)) = &self.throttle else {
+            return Ok(None);
+        };
+
+        let class = classify.and_then(|classify| classify(call, state));
+        let client = call.get_peer_address().map(|address| address.ip());
+        throttle.admit(client, class.as_ref(), bytes, Instant::now())?;
+
+        Ok(class)
+    }
+
+    /// Take the `bytes` of the reply to `call`, of `class`, from the throttle's allowances.
+    fn charge(&self, call: &Call, class: Option<&Class>, bytes: usize) {
+        if let Some((throttle, _)) = &self.throttle {
+            let client = call.get_peer_address().map(|address| address.ip());
+            throttle.charge(client, class, bytes, Instant::now());
+        }
+    }
+
+    fn check(&self, call: &Call, reply: &[u8]) {
+        if let Some(check) = self.reply_check {
+            if let Err(e) = check_reply(call, reply, Some(check)) {
+                let label = procedure_label(self.procedure_names, call);
+                error!(
+                    "Malformed reply to {label} (xid {:#x}): {e}",
+                    call.get_xid()
+                );
+            }
+        }
+    }
+
+    fn trace(&self, conn_fd: i32, call: &[u8], reply: &[u8]) {
+        if let Some(tracer) = &self.tracer {
+            tracer.trace(conn_fd as u64, Direction::Received, call);
+            tracer.trace(conn_fd as u64, Direction::Sent, reply);
+        }
+    }
+
+    fn record(
+        &self,
+        call: &Call,
+        bytes_in: usize,
+        bytes_out: usize,
+        error: bool,
+        received: Instant,
+    ) {
+        log_reply(bytes_in, bytes_out, error, received);
+        if let Some(metrics) = &self.metrics {
+            let sample = Sample {
+                bytes_in,
+                bytes_out,
+                error,
+                latency: received.elapsed(),
+            };
+            metrics.record(call, self.procedure_names, sample);
+        }
+    }
+}
+
+/// A change to the state of an `RpcServer`, made from another thread.
+pub type StateUpdate<T> = Box<dyn FnOnce(&mut T) + std::marker::Send>;
+
+/// Sends updates to the state of an `RpcServer` from other threads, and wakes the server to apply
+/// them.
+pub struct StateUpdater<T> {
+    updates: Sender<StateUpdate<T>>,
+    wake: Arc<EventFd>,
+}
+
+impl<T> Clone for StateUpdater<T> {
+    fn clone(&self) -> Self {
+        Self {
+            updates: self.updates.clone(),
+            wake: self.wake.clone(),
+        }
+    }
+}
+
+impl<T> StateUpdater<T> {
+    /// Apply `update` to the server's state. The update takes effect before the next call received
+    /// is handled, and calls in progress are not affected.
+    pub fn update(&self, update: impl FnOnce(&mut T) + std::marker::Send + 'static) {
+        if self.updates.send(Box::new(update)).is_ok() {
+            let _ = self.wake.write(1);
+        }
+    }
+
+    /// Call `query` with the server's state, and wait for what it returns.
+    pub fn query<R: std::marker::Send + 'static>(
+        &self,
+        query: impl FnOnce(&mut T) -> R + std::marker::Send + 'static,
+    ) -> R {
+        let (result, result_rx) = mpsc::channel();
+        self.update(move |state| {
+            let _ = result.send(query(state));
+        });
+
+        result_rx
+            .recv()
+            .expect("the server applies every update sent to it")
+    }
+}
+
+pub struct RpcServer<T> {
+    ring: IoUring,
+    listener: TcpListener,
+    buffer_map: Rc<RefCell<BufferMap>>,
+    procedure_map: ProcedureMap<T>,
+
+    /// The RPC service implementation uses this field to store state that must be maintained
+    /// across RPC calls.
+    user_state: T,
+
+    /// Updates to `user_state`, which are applied before the next completion is handled, and the
+    /// updater given out for them.
+    updates: Option<(Receiver<StateUpdate<T>>, StateUpdater<T>)>,
+
+    /// The accepted connections that calls have been received on.
+    connections: HashMap<i32, Connection>,
+
+    /// How file data is sent, if procedures may reply with files.
+    file_sends: Option<FileSends>,
+
+    /// The buffers registered for `FileSends::SendZc`. They are declared after `ring`, so that they
+    /// outlive it.
+    send_buffers: SendBuffers,
+
+    /// Limits on the records that calls are received in.
+    record_limits: RecordLimits,
+
+    /// The calls waiting on I/O submitted for them, by the IDs that their completions carry, and
+    /// the ID for the next.
+    waiting: HashMap<u64, WaitingCall<T>>,
+    next_waiting: u64,
+
+    /// Connections whose receives ended because every receive buffer was in use, in the order they
+    /// did. They are received on again once buffers are given back, and until then, connections
+    /// that are accepted join them rather than being received on.
+    starved: Vec<i32>,
+
+    /// Limits on the calls in flight, and the connections whose calls are held back by them, in the
+    /// order they were first held back.
+    limits: ConnectionLimits,
+    held: Vec<i32>,
+
+    /// The bytes of the replies waiting to be sent, on every connection.
+    reply_memory: usize,
+
+    /// The callbacks queued by procedures, and those sent and waiting for their replies, by their
+    /// connections and XIDs, which are chosen from `callback_xids`.
+    backchannel: Backchannel<T>,
+    callbacks: HashMap<(i32, u32), CallbackHandler<T>>,
+    callback_xids: XidSpace,
+
+    /// The serial number of the next connection accepted.
+    next_serial: u64,
+
+    /// How long connections may go without progress, and the same timeouts as io_uring takes them:
+    /// the interval at which idle connections are looked for, and the timeout linked to each
+    /// operation sending a reply. These are boxed, and only ever changed in place, since the kernel
+    /// reads them when the operations pointing to them are submitted, which may be after the server
+    /// has moved.
+    timeouts: ConnectionTimeouts,
+    idle_check: Box<types::Timespec>,
+    send_timeout: Box<types::Timespec>,
+
+    /// Whether a check for idle connections has been submitted.
+    checking_idle: bool,
+}
+
+/// The sizes of the io_uring of an `RpcServer` and of its receive buffers.
+pub struct RingSizes {
+    /// The number of submission queue entries, which must be a power of two.
+    pub entries: u32,
+
+    /// The size of each receive buffer, which holds a call of up to this many bytes, including its
+    /// record mark. This should be the size of the largest call that clients are told they may
+    /// send, such as a WRITE of the largest size that the service advertises; a larger call ends
+    /// its connection.
+    pub max_call_size: u32,
+
+    /// About how much memory to give the receive buffers together, which decides their number.
+    pub receive_memory: usize,
+}
+
+/// Limits on the calls that an `RpcServer` answers at once. A call received while they are reached
+/// is held back until replies have been sent, and the connection it came on is not received on
+/// until then, so that the client is held back by TCP flow control rather than the server queueing
+/// its calls without bound.
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionLimits {
+    /// The most calls that may be in flight on one connection: being answered, waiting on I/O, or
+    /// with replies waiting to be sent.
+    pub max_in_flight: usize,
+
+    /// The most bytes that the replies waiting to be sent may hold, on every connection together.
+    /// Replies with file data count the data too, however it is sent.
+    pub reply_memory: usize,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 128,
+            reply_memory: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl<T> RpcServer<T> {
+    /// Create a server listening on `address`.
+    pub fn new(
+        address: SocketAddr,
+        procedure_map: ProcedureMap<T>,
+        user_state: T,
+        sizes: RingSizes,
+    ) -> io::Result<Self> {
+        let mut ring = IoUring::new(sizes.entries)?;
+        let buffers = buffer_count(sizes.max_call_size, sizes.receive_memory);
+        let buffer_map = Rc::new(RefCell::new(BufferMap::new(
+            &mut ring,
+            sizes.max_call_size,
+            buffers,
+        )));
+
+        let mut ring = Self {
+            ring,
+            listener: bind_tcp(address)?,
+            buffer_map,
+            procedure_map,
+            user_state,
+            updates: None,
+            connections: HashMap::new(),
+            file_sends: None,
+            send_buffers: SendBuffers::default(),
+            record_limits: RecordLimits {
+                max_record: sizes.max_call_size.saturating_sub(4),
+                ..Default::default()
+            },
+            starved: Vec::new(),
+            limits: ConnectionLimits::default(),
+            held: Vec::new(),
+            reply_memory: 0,
+            waiting: HashMap::new(),
+            next_waiting: 0,
+            backchannel: Backchannel {
+                queued: Rc::default(),
+            },
+            callbacks: HashMap::new(),
+            callback_xids: XidSpace::new(),
+            next_serial: 1,
+            timeouts: ConnectionTimeouts::default(),
+            idle_check: Box::new(types::Timespec::new()),
+            send_timeout: Box::new(types::Timespec::new()),
+            checking_idle: false,
+        };
+
+        ring.submit_multishot_accept();
+
+        Ok(ring)
+    }
+
+    /// A handle through which other threads can update the server's state.
+    pub fn state_updater(&mut self) -> io::Result<StateUpdater<T>> {
+        if let Some((_, updater)) = &self.updates {
+            return Ok(updater.clone());
+        }
+
+        let wake = EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK)?;
+        let (updates, receiver) = mpsc::channel();
+        let updater = StateUpdater {
+            updates,
+            wake: Arc::new(wake),
+        };
+
+        let wake_fd = updater.wake.as_raw_fd();
+        let user_data = Box::new(Operation::Wake(Wake::new(wake_fd)));
+        submit_wake(&mut self.ring, types::Fd(wake_fd), user_data.to_u64());
+
+        self.updates = Some((receiver, updater.clone()));
+
+        Ok(updater)
+    }
+
+    /// Let procedures reply with `RingResult::File`, sending the file data as `how` says. With
+    /// `FileSends::SendZc`, buffers of `max_reply` bytes, the size of the largest such reply with its
+    /// header, are registered with the ring; this fails if they would exceed RLIMIT_MEMLOCK.
+    pub fn send_files(&mut self, how: FileSends, max_reply: u32) -> io::Result<()> {
+        if how == FileSends::SendZc {
+            self.send_buffers = SendBuffers::new(&self.ring, max_reply)?;
+        }
+        self.file_sends = Some(how);
+
+        Ok(())
+    }
+
+    /// A handle through which procedures make callbacks on the connections that calls came on.
+    pub fn backchannel(&self) -> Backchannel<T> {
+        self.backchannel.clone()
+    }
+
+    /// The address that the server listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Hold back calls as `limits` say, in place of the default `ConnectionLimits`.
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.limits = limits;
+    }
+
+    /// Close connections that go without progress as `timeouts` say, and keep them alive with TCP
+    /// keepalive if it says to. A connection with no call being answered and no reply being sent
+    /// is closed once nothing has been received on it for the read timeout, which is checked for
+    /// every quarter of it; a send that takes longer than the write timeout is cancelled, which
+    /// shuts its connection down.
+    pub fn set_connection_timeouts(&mut self, timeouts: ConnectionTimeouts) {
+        self.timeouts = timeouts;
+        if let Some(read) = timeouts.read {
+            *self.idle_check = (read / 4).into();
+        }
+        if let Some(write) = timeouts.write {
+            *self.send_timeout = write.into();
+        }
+
+        if !self.checking_idle {
+            self.submit_idle_check();
+        }
+    }
+
+    pub fn main_loop(&mut self) -> io::Result<()> {
+        loop {
+            self.try_submit_and_wait();
+
+            if let Some((updates, _)) = &self.updates {
+                for update in updates.try_iter() {
+                    update(&mut self.user_state);
+                }
+            }
+
+            let cqe = self
+                .ring
+                .completion()
+                .next()
+                .expect("failed to get completion");
+
+            // SAFETY: user data was derived from a Box<Operation>::into_raw().
+            let op = unsafe { Operation::from_u64(cqe.user_data()) };
+
+            check_completion_error(&cqe, &op);
+
+            trace!("{op}: {cqe:?}");
+
+            match *op {
+                Operation::Accept(ref a) => {
+                    let listen_fd = a.fd;
+                    op.handle_accept(self, cqe, listen_fd);
+                }
+                Operation::Recv(ref r) => {
+                    let conn_fd = r.fd;
+                    op.handle_receive(self, cqe, conn_fd);
+                }
+                Operation::Send(ref s) => {
+                    let send = *s;
+                    op.handle_send(self, cqe, send);
+                }
+                Operation::SendZc(ref s) => {
+                    let send = *s;
+                    op.handle_send_zc(self, cqe, send);
+                }
+                Operation::Wake(ref w) => {
+                    let wake_fd = w.fd;
+                    op.handle_wake(&mut self.ring, cqe, wake_fd);
+                }
+                Operation::Resume(ref r) => {
+                    let resume = *r;
+                    op.handle_resume(self, cqe, resume);
+                }
+                Operation::Cancel(ref c) => {
+                    let conn_fd = c.fd;
+                    op.handle_cancel(cqe, conn_fd);
+                }
+                Operation::SendTimeout(ref t) => {
+                    let conn_fd = t.fd;
+                    op.handle_send_timeout(cqe, conn_fd);
+                }
+                // The calls held back are answered below, if they may be now:
+                Operation::Throttled(_) => drop(op),
+                Operation::IdleCheck => {
+                    drop(op);
+                    self.close_idle();
+                    self.submit_idle_check();
+                }
+            }
+
+            self.release_held();
+            self.resume_starved();
+            self.send_callbacks();
+        }
+    }
+
+    fn submit_multishot_accept(&mut self) {
+        let listen_fd = self.listener.as_raw_fd();
+        let user_data = Box::new(Operation::Accept(Accept::new(listen_fd)));
+        let listen_fd = types::Fd(self.listener.as_raw_fd());
+
+        submit_accept(&mut self.ring, listen_fd, user_data.to_u64());
+    }
+
+    /// Look for idle connections once the interval between checks has passed, if there is a read
+    /// timeout.
+    fn submit_idle_check(&mut self) {
+        self.checking_idle = self.timeouts.read.is_some();
+        if !self.checking_idle {
+            return;
+        }
+
+        let entry = opcode::Timeout::new(&*self.idle_check)
+            .build()
+            .user_data(Box::new(Operation::IdleCheck).to_u64());
+        push_entries(&mut self.ring, &[entry]);
+    }
+
+    /// Shut down the connections that nothing has been received on for the read timeout, and that
+    /// have no call being answered nor reply being sent. Shutting one down ends the receive on it,
+    /// which closes it.
+    fn close_idle(&mut self) {
+        let Some(read) = self.timeouts.read else {
+            return;
+        };
+
+        let idle: Vec<_> = self
+            .connections
+            .iter()
+            .filter(|(_, c)| {
+                !c.abandoned
+                    && !c.closed
+                    && !c.sending
+                    && c.waiting == 0
+                    && c.replies.is_empty()
+                    && c.held.is_empty()
+                    && c.active.is_some_and(|active| active.elapsed() >= read)
+            })
+            .map(|(&fd, _)| fd)
+            .collect();
+        for conn_fd in idle {
+            debug!("Closing FD {conn_fd}, which has been idle for {read:?}");
+            self.abandon(conn_fd);
+        }
+    }
+
+    fn try_submit_and_wait(&mut self) {
+        let Err(e) = self.ring.submit_and_wait(1) else {
+            return;
+        };
+
+        match nix::errno::Errno::from_raw(e.raw_os_error().unwrap()) {
+            // EAGAIN means try again later, so just return now:
+            nix::Error::EAGAIN => {}
+            other => {
+                panic!("Unexpected error result from io_uring_enter() (submit_and_wait()): {other}")
+            }
+        };
+    }
+
+    /// Receive on a connection, starting a multishot receive, or waiting until receive buffers are
+    /// given back if other connections already are. Nothing is done if a receive is live on the
+    /// connection already, or if calls received on it are held back.
+    fn receive(&mut self, conn_fd: i32) {
+        let Some(connection) = self.connections.get_mut(&conn_fd) else {
+            return;
+        };
+        if connection.receiving || !connection.held.is_empty() {
+            return;
+        }
+
+        if !self.starved.is_empty() {
+            self.starve(conn_fd);
+            return;
+        }
+
+        connection.receiving = true;
+        let user_data = Box::new(Operation::Recv(Receive::new(conn_fd)));
+        submit_receive(&mut self.ring, types::Fd(conn_fd), user_data.to_u64());
+    }
+
+    /// Note that the receive on a connection has ended, with a completion without the MORE flag.
+    fn receive_ended(&mut self, conn_fd: i32) {
+        if let Some(connection) = self.connections.get_mut(&conn_fd) {
+            connection.receiving = false;
+            connection.cancelling = false;
+        }
+    }
+
+    /// Cancel the receive on a connection whose calls are held back, so that what the client sends
+    /// next waits in the socket. `receive` is the user data of the receive, which must be live.
+    fn stop_receiving_if_held(&mut self, conn_fd: i32, receive: u64) {
+        let Some(connection) = self.connections.get_mut(&conn_fd) else {
+            return;
+        };
+        if connection.held.is_empty() || connection.cancelling {
+            return;
+        }
+        connection.cancelling = true;
+
+        let user_data = Box::new(Operation::Cancel(Cancel { fd: conn_fd }));
+        let entry = opcode::AsyncCancel::new(receive)
+            .build()
+            .user_data(user_data.to_u64());
+        push_entries(&mut self.ring, &[entry]);
+    }
+
+    /// Wait to receive on a connection until receive buffers are given back. Buffers are taken from
+    /// a ring registered with the kernel, which cannot grow, so a connection that runs out waits
+    /// for those that procedures are done with, and holds back the connections after it.
+    fn starve(&mut self, conn_fd: i32) {
+        if self.starved.is_empty() {
+            warn!("Out of receive buffers; waiting for some to be given back");
+        }
+        debug!("Waiting for receive buffers to receive on FD {conn_fd}");
+        self.starved.push(conn_fd);
+    }
+
+    /// Receive again on the starved connections, if receive buffers have been given back since
+    /// they were starved. Any that find none again are starved again, in the same order.
+    fn resume_starved(&mut self) {
+        if self.starved.is_empty() || !self.buffer_map.borrow_mut().take_given_back() {
+            return;
+        }
+
+        for conn_fd in std::mem::take(&mut self.starved) {
+            self.receive(conn_fd);
+        }
+    }
+
+    /// Whether a connection has as many calls in flight as it may, or the replies waiting to be sent
+    /// hold as many bytes as they may, or the connection's next call is over the throttle's limits,
+    /// so that the next call received on it must wait.
+    fn at_limits(&self, conn_fd: i32) -> bool {
+        let Some(connection) = self.connections.get(&conn_fd) else {
+            return false;
+        };
+
+        connection.replies.len() + connection.waiting >= self.limits.max_in_flight
+            || self.reply_memory >= self.limits.reply_memory
+            || connection
+                .throttled
+                .is_some_and(|until| until > Instant::now())
+    }
+
+    /// Hold back a call received on a connection, until `release_held()` finds it below the limits.
+    fn hold(&mut self, conn_fd: i32, buffer: Rc<RecvBuffer>, range: Range<usize>) {
+        let Some(connection) = self.connections.get_mut(&conn_fd) else {
+            return;
+        };
+
+        if connection.held.is_empty() {
+            debug!(
+                "Holding back calls on FD {conn_fd}, with {} in flight and {} bytes of replies \
+                 waiting on every connection",
+                connection.replies.len() + connection.waiting,
+                self.reply_memory
+            );
+            self.held.push(conn_fd);
+        }
+        connection.held.push_back((buffer, range));
+    }
+
+    /// Hold back a call that is over the throttle's limits, ahead of the calls held back after it,
+    /// until `wait` has passed, when the server is woken to answer it.
+    fn throttle(
+        &mut self,
+        conn_fd: i32,
+        buffer: Rc<RecvBuffer>,
+        range: Range<usize>,
+        wait: Duration,
+    ) {
+        let Some(connection) = self.connections.get_mut(&conn_fd) else {
+            return;
+        };
+
+        debug!("Holding back calls on FD {conn_fd} for {wait:?}, over the throttle's limits");
+        connection.throttled = Some(Instant::now() + wait);
+        connection.held.push_front((buffer, range));
+        if !self.held.contains(&conn_fd) {
+            self.held.push(conn_fd);
+        }
+
+        // The kernel reads the timeout when the operation is submitted, so it is kept with the
+        // operation, which outlives it:
+        let throttled = Box::new(Operation::Throttled(Throttled {
+            fd: conn_fd,
+            timeout: wait.into(),
+        }));
+        let Operation::Throttled(Throttled { timeout, .. }) = &*throttled else {
+            unreachable!();
+        };
+        let entry = opcode::Timeout::new(timeout).build();
+        push_entries(&mut self.ring, &[entry.user_data(throttled.to_u64())]);
+    }
+
+    /// Answer the calls held back on connections that are now below the limits, in the order they
+    /// were received, and receive on each connection again once none of its calls are left.
+    fn release_held(&mut self) {
+        if self.held.is_empty() {
+            return;
+        }
+
+        for conn_fd in std::mem::take(&mut self.held) {
+            while !self.at_limits(conn_fd) {
+                let Some((buffer, range)) = self
+                    .connections
+                    .get_mut(&conn_fd)
+                    .and_then(|c| c.held.pop_front())
+                else {
+                    break;
+                };
+                self.handle_call(&buffer, range, conn_fd);
+            }
+
+            match self.connections.get(&conn_fd) {
+                Some(connection) if connection.held.is_empty() => self.receive(conn_fd),
+                // A call that was over the throttle's limits may have been held back again already:
+                Some(_) if !self.held.contains(&conn_fd) => self.held.push(conn_fd),
+                _ => {}
+            }
+        }
+    }
+
+    /// Reassemble the records in `recv_buf`, the next bytes received on a connection, and answer
+    /// the calls that they complete, or hold them back if the connection is at the limits on calls
+    /// in flight. A record that exceeds the record limits ends the connection.
+    fn handle_received_bytes(&mut self, recv_buf: Rc<RecvBuffer>, conn_fd: i32) {
+        assert!(!recv_buf.is_empty());
+
+        let connection = self.connections.entry(conn_fd).or_default();
+        if connection.abandoned {
+            return;
+        }
+        connection.active = Some(Instant::now());
+
+        let mut records = Vec::new();
+        let res = connection
+            .reassembly
+            .receive(&recv_buf, &self.record_limits, &mut records);
+        self.count_records(conn_fd);
+
+        for record in records {
+            // A call that could not be decoded ends the connection, and the calls after it:
+            if self.connections.get(&conn_fd).is_none_or(|c| c.abandoned) {
+                return;
+            }
+
+            let (buffer, range) = match record {
+                Reassembled::Whole(range) => (Rc::clone(&recv_buf), range),
+                Reassembled::Copied(record) => {
+                    let len = record.len();
+                    (Rc::new(RecvBuffer::copied(record)), 0..len)
+                }
+            };
+
+            // Replies to callbacks are not calls, and are never held back:
+            if let Some(xid) = peek_reply(&buffer[range.start + 4..range.end]) {
+                self.handle_callback_reply(conn_fd, xid, &buffer[range]);
+                continue;
+            }
+
+            // Calls are answered in order, so once one is held back, so are those after it:
+            let held = self
+                .connections
+                .get(&conn_fd)
+                .is_some_and(|c| !c.held.is_empty());
+            if held || self.at_limits(conn_fd) {
+                self.hold(conn_fd, buffer, range);
+            } else {
+                self.handle_call(&buffer, range, conn_fd);
+            }
+        }
+
+        if let Err(e) = res {
+            warn!("Refused a {e} on FD {conn_fd}; shutting the connection down");
+            self.abandon(conn_fd);
+        }
+    }
+
+    /// Send the callbacks that have been queued on the backchannel. One whose connection has closed
+    /// is handed an error right away.
+    fn send_callbacks(&mut self) {
+        let queued = std::mem::take(&mut *self.backchannel.queued.borrow_mut());
+        for queued in queued {
+            let ConnectionId { fd, serial } = queued.connection;
+            let open = self
+                .connections
+                .get(&fd)
+                .is_some_and(|c| c.serial == serial && !c.abandoned && !c.closed);
+            if !open {
+                let e = io::Error::new(io::ErrorKind::NotConnected, "the connection has closed");
+                (queued.then)(Err(Error::Io(e)), &mut self.user_state);
+                continue;
+            }
+
+            let xid = self.callback_xids.allocate();
+            debug!(
+                "Calling back program {}, version {}, procedure {} on FD {fd}, with XID {xid:#x}",
+                queued.callback.prog, queued.callback.vers, queued.callback.proc
+            );
+            self.callbacks.insert((fd, xid), queued.then);
+            self.send(fd, Reply::Bytes(queued.callback.encode(xid)));
+        }
+    }
+
+    /// Hand the reply in `record`, which starts with its record mark, to the handler of the
+    /// callback with the XID `xid` on a connection. A reply to no callback is dropped.
+    fn handle_callback_reply(&mut self, conn_fd: i32, xid: u32, record: &[u8]) {
+        let Some(then) = self.callbacks.remove(&(conn_fd, xid)) else {
+            warn!("Dropping a reply on FD {conn_fd} to XID {xid:#x}, which is not a callback");
+            return;
+        };
+
+        let res = decode_reply(xid, &record[4..]).map(|(_, result)| result);
+        then(res, &mut self.user_state);
+    }
+
+    /// Add what has been counted of a connection's records since this was last called to its
+    /// counts, and to the metrics.
+    fn count_records(&mut self, conn_fd: i32) {
+        let Some(connection) = self.connections.get_mut(&conn_fd) else {
+            return;
+        };
+
+        let uncounted = connection.reassembly.take_stats();
+        if let Some(metrics) = &self.procedure_map.metrics {
+            metrics.count_records(&uncounted);
+        }
+        connection.records.add(&uncounted);
+    }
+
+    /// Answer the call in the record at `range` of `recv_buf`, which starts with its record mark.
+    ///
+    /// If the RPC message is valid and for a procedure implemented by this service, then calls the
+    /// procedure implementation. The fixed part of the call header is checked first, so that a
+    /// record that is not a call ends the connection, and a call for a program, version, or
+    /// procedure that this service does not have is refused, without the call being decoded. A call
+    /// that cannot be decoded ends the connection.
+    fn handle_call(&mut self, recv_buf: &Rc<RecvBuffer>, range: Range<usize>, conn_fd: i32) {
+        let record = &recv_buf[range.clone()];
+        let received = Instant::now();
+        let bytes_in = record.len();
+        let map = &self.procedure_map;
+        let peek = match peek_call(&record[4..]) {
+            Ok(peek) => peek,
+            Err(e) => {
+                warn!(
+                    "Protocol error in a record of {bytes_in} bytes on FD {conn_fd}: {e}; \
+                     shutting the connection down"
+                );
+                self.abandon(conn_fd);
+                return;
+            }
+        };
+
+        let known = map
+            .procedures
+            .get(peek.proc as usize)
+            .is_some_and(Option::is_some)
+            || peek.proc == 0;
+        let valid = peek
+            .validate(map.program, map.version_min, map.version_max)
+            .and_then(|()| match known {
+                true => Ok(()),
+                false => {
+                    debug!("CALL for unknown procedure {}", peek.proc);
+                    let reply = ReplyBody::accepted_reply(AcceptedReplyBody::ProcUnavail);
+                    Err(Error::Rpc(reply))
+                }
+            });
+        if let Err(e) = valid {
+            if let Error::Rpc(reply) = e {
+                let reply = encode_reply_no_arg(peek.xid, reply);
+                map.trace(conn_fd, record, &reply);
+                self.send(conn_fd, Reply::Bytes(reply));
+            }
+            return;
+        }
+
+        let mut call = match decode_call(&record[4..]) {
+            Ok(call) => call,
+            Err(e) => {
+                warn!(
+                    "Protocol error in decoding a call on FD {conn_fd}: {e}; shutting the \
+                     connection down"
+                );
+                self.abandon(conn_fd);
+                return;
+            }
+        };
+
+        call.set_peer_address(self.connections.get(&conn_fd).and_then(|c| c.address));
+        let class = match map.admit(&call, bytes_in, &self.user_state) {
+            Ok(class) => class,
+            Err(wait) => {
+                self.throttle(conn_fd, Rc::clone(recv_buf), range, wait);
+                return;
+            }
+        };
+        let span = call_span(map.procedure_names, &call);
+        let _entered = span.enter();
+
+        let map = &mut self.procedure_map;
+        let verifier = match authenticate(&mut call, map.authenticator.as_mut()) {
+            Ok(verifier) => verifier,
+            Err(e) => {
+                if let Error::Rpc(reply) = e {
+                    let reply = encode_reply_no_arg(call.get_xid(), reply);
+                    map.check(&call, &reply);
+                    map.trace(conn_fd, record, &reply);
+                    map.record(&call, bytes_in, reply.len(), true, received);
+                    self.send(conn_fd, Reply::Bytes(reply));
+                }
+                return;
+            }
+        };
+
+        let label = procedure_label(map.procedure_names, &call);
+        // The procedure was found to exist above:
+        let procedure = match map.procedures.get(peek.proc as usize) {
+            Some(Some(procedure)) => *procedure,
+            _ => ring_null_procedure,
+        };
+
+        debug!("calling {label}");
+        let tracing = map.tracer.as_ref().is_some_and(Tracer::is_started);
+        let serial = self.connections.get(&conn_fd).map_or(0, |c| c.serial);
+        let call = RingCall {
+            call,
+            buffer: recv_buf,
+            connection: ConnectionId {
+                fd: conn_fd,
+                serial,
+            },
+            sends_files: self.file_sends.is_some() && map.reply_check.is_none() && !tracing,
+        };
+        let res = procedure(&call, &mut self.user_state);
+        let record = Received {
+            buffer: Rc::clone(recv_buf),
+            range,
+            received,
+        };
+        self.finish_call(conn_fd, &call, record, verifier, class, res);
+    }
+
+    /// Reply to a call with the result of its procedure, or submit the I/O that the procedure needs
+    /// done first.
+    fn finish_call(
+        &mut self,
+        conn_fd: i32,
+        call: &Call,
+        record: Received,
+        verifier: OpaqueAuth,
+        class: Option<Class>,
+        res: RingResult<T>,
+    ) {
+        let xid = call.get_xid();
+        let bytes_in = record.range.len();
+        let received = record.received;
+
+        // A succesful reply carries `verifier`, from the authenticator, unless the procedure gave
+        // its own:
+        let map = &self.procedure_map;
+        let reply = match res {
+            RingResult::Done(res) => {
+                let error = !res.is_success();
+                let mut reply = res.encode_reply_parts(xid, verifier);
+                let tracing = map.tracer.as_ref().is_some_and(Tracer::is_started);
+                if map.reply_check.is_some() || tracing {
+                    // Checking and tracing need the reply whole, so it is sent whole too:
+                    let bytes = reply.whole();
+                    map.check(call, bytes);
+                    map.trace(conn_fd, record.bytes(), bytes);
+                }
+                map.record(call, bytes_in, reply.size(), error, received);
+                map.charge(call, class.as_ref(), reply.size());
+                Reply::from(reply)
+            }
+            RingResult::File(mut file) => {
+                let mut head =
+                    RpcResult::Success(std::mem::take(&mut file.head)).encode_reply(xid, verifier);
+                let len = head.len() + file.len as usize + file.padding();
+                head[..4].copy_from_slice(&((len as u32 - 4) | (1 << 31)).to_be_bytes());
+                map.record(call, bytes_in, len, false, received);
+                map.charge(call, class.as_ref(), len);
+                Reply::File(head, file)
+            }
+            RingResult::MoreIo(more) => {
+                let id = self.next_waiting;
+                self.next_waiting += 1;
+
+                let resume = Box::new(Operation::Resume(Resume { fd: conn_fd, id }));
+                let entry = more.entry.user_data(resume.to_u64());
+                let waiting = WaitingCall {
+                    record,
+                    verifier,
+                    class,
+                    then: more.then,
+                    span: tracing::Span::current(),
+                };
+                self.waiting.insert(id, waiting);
+                self.connections.entry(conn_fd).or_default().waiting += 1;
+
+                push_entries(&mut self.ring, &[entry]);
+                return;
+            }
+        };
+        self.send(conn_fd, reply);
+    }
+
+    /// Continue a call whose I/O has completed with `res`. If its connection has closed, the result
+    /// is dropped.
+    fn resume(&mut self, resume: Resume, res: io::Result<u32>) {
+        let waiting = self
+            .waiting
+            .remove(&resume.id)
+            .expect("a call waits on each resumed operation");
+        let _entered = waiting.span.enter();
+        let res = (waiting.then)(res, &mut self.user_state);
+
+        let Some(connection) = self.connections.get_mut(&resume.fd) else {
+            return;
+        };
+        connection.waiting -= 1;
+        if connection.closed {
+            self.close(resume.fd);
+            return;
+        }
+        if connection.abandoned {
+            return;
+        }
+
+        let buffer = Rc::clone(&waiting.record.buffer);
+        let record = &buffer[waiting.record.range.clone()];
+        let call = decode_call(&record[4..]).expect("the call was decoded before");
+        self.finish_call(
+            resume.fd,
+            &call,
+            waiting.record,
+            waiting.verifier,
+            waiting.class,
+            res,
+        );
+    }
+
+    /// Send a reply on a connection, once the replies before it have been sent.
+    fn send(&mut self, conn_fd: i32, reply: Reply) {
+        assert!(conn_fd > 2);
+
+        let connection = self.connections.entry(conn_fd).or_default();
+        if connection.abandoned {
+            return;
+        }
+
+        self.reply_memory += reply.size();
+        connection.replies.push_back(reply);
+        if !connection.sending {
+            self.send_next(conn_fd);
+        }
+    }
+
+    /// Submit the operations that send the first reply waiting on a connection.
+    fn send_next(&mut self, conn_fd: i32) {
+        let Some(connection) = self.connections.get_mut(&conn_fd) else {
+            return;
+        };
+        let Some(reply) = connection.replies.front_mut() else {
+            return;
+        };
+        connection.sending = true;
+
+        if let Reply::File(head, file) = reply {
+            let sent = match self.file_sends {
+                Some(FileSends::Splice) => match Pipe::of(&mut connection.pipe) {
+                    Ok(pipe) => {
+                        let sends = splice_file(conn_fd, head, file, pipe);
+                        submit_sends(
+                            &mut self.ring,
+                            sends,
+                            self.timeouts.write.and(Some(&*self.send_timeout)),
+                        );
+                        true
+                    }
+                    Err(e) => {
+                        warn!("Could not create a pipe to splice through: {e}");
+                        false
+                    }
+                },
+                _ => send_zc(
+                    &mut self.ring,
+                    &mut self.send_buffers,
+                    self.timeouts.write.and(Some(&*self.send_timeout)),
+                    conn_fd,
+                    head,
+                    file,
+                ),
+            };
+            if sent {
+                return;
+            }
+
+            // Otherwise, the data is copied into the reply after all:
+            match read_whole(head, file) {
+                Ok(bytes) => *reply = Reply::Bytes(bytes),
+                Err(e) => {
+                    warn!("Could not read a file to send on FD {conn_fd}: {e}");
+                    self.abandon(conn_fd);
+                    return;
+                }
+            }
+        }
+
+        let send = match reply {
+            Reply::Bytes(bytes) => Send::bytes(conn_fd, bytes, 0),
+            Reply::Parts(parts) => parts.send(conn_fd),
+            Reply::File(..) => unreachable!("file data was read into the reply"),
+        };
+        submit_sends(
+            &mut self.ring,
+            vec![send],
+            self.timeouts.write.and(Some(&*self.send_timeout)),
+        );
+    }
+
+    /// Move on to the next reply of a connection, once the last operation sending a reply has
+    /// completed.
+    fn reply_sent(&mut self, conn_fd: i32) {
+        let Some(connection) = self.connections.get_mut(&conn_fd) else {
+            return;
+        };
+        if let Some(reply) = connection.replies.pop_front() {
+            self.reply_memory -= reply.size();
+        }
+        connection.sending = false;
+        connection.active = Some(Instant::now());
+
+        if connection.closed {
+            self.close(conn_fd);
+        } else if connection.failed {
+            warn!("Could not send a whole reply on FD {conn_fd}; shutting the connection down");
+            self.abandon(conn_fd);
+        } else {
+            self.send_next(conn_fd);
+        }
+    }
+
+    /// Note that an operation sending the current reply of a connection fell short.
+    fn connection_failed(&mut self, conn_fd: i32) {
+        if let Some(connection) = self.connections.get_mut(&conn_fd) {
+            connection.failed = true;
+        }
+    }
+
+    /// Give up on a connection that a reply could not be sent on in full, since the client cannot
+    /// tell where the next one would start. Shutting it down ends the receive on it, which closes
+    /// it.
+    fn abandon(&mut self, conn_fd: i32) {
+        if let Some(connection) = self.connections.get_mut(&conn_fd) {
+            self.reply_memory -= connection
+                .replies
+                .drain(..)
+                .map(|r| r.size())
+                .sum::<usize>();
+            connection.held.clear();
+            connection.sending = false;
+            connection.abandoned = true;
+        }
+
+        // SAFETY: the connection is not closed while replies are being sent on it.
+        let _ = unsafe { libc::shutdown(conn_fd, libc::SHUT_RDWR) };
+    }
+
+    /// Close a connection that the client closed, or that was abandoned, once no reply is being
+    /// sent on it, and no call on it is waiting on I/O.
+    fn close(&mut self, conn_fd: i32) {
+        if let Some(connection) = self.connections.get_mut(&conn_fd) {
+            // The client will not see replies to the calls held back:
+            connection.held.clear();
+            connection.reassembly.end();
+            self.count_records(conn_fd);
+        }
+
+        if let Some(connection) = self.connections.get_mut(&conn_fd) {
+            if connection.sending || connection.waiting > 0 {
+                connection.closed = true;
+                return;
+            }
+        }
+
+        trace!("Closing connection with fd {conn_fd}");
+        let unanswered: Vec<_> = self
+            .callbacks
+            .keys()
+            .filter(|(fd, _)| *fd == conn_fd)
+            .copied()
+            .collect();
+        for key in unanswered {
+            let then = self.callbacks.remove(&key).unwrap();
+            let e = io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "the connection closed before the reply to the callback",
+            );
+            then(Err(Error::Io(e)), &mut self.user_state);
+        }
+
+        if let Some(connection) = self.connections.remove(&conn_fd) {
+            self.reply_memory -= connection.replies.iter().map(Reply::size).sum::<usize>();
+            let records = connection.records;
+            if records.is_abnormal() {
+                warn!(
+                    "Connection on FD {conn_fd} ended after {} records in {} fragments, with {} \
+                     tiny fragments, {} incomplete records, and {} refused",
+                    records.records,
+                    records.fragments,
+                    records.tiny_fragments,
+                    records.incomplete,
+                    records.refused
+                );
+            } else {
+                debug!("Connection on FD {conn_fd} ended after {records:?}");
+            }
+        }
+        let _ = unsafe { libc::close(conn_fd) };
+    }
+}
+
+/// The operations that splice a reply with file data to a connection: the encoded head, then the
+/// file data through the pipe, in steps of up to its size, then the padding.
+fn splice_file(
+    conn_fd: i32,
+    head: &[u8],
+    file: &FileReply,
+    pipe: &Pipe,
+) -> Vec<(squeue::Entry, Send)> {
+    let fd = types::Fd(conn_fd);
+    let more = if file.len > 0 { libc::MSG_MORE } else { 0 };
+    let mut sends = vec![Send::bytes(conn_fd, head, more)];
+
+    // SAFETY: sysconf() has no preconditions.
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+
+    let (mut offset, mut left) = (file.offset, file.len);
+    while left > 0 {
+        // The pipe holds whole pages of the file, so a step that starts part way into a page ends
+        // that much sooner, or it would not fit:
+        let len = left.min(pipe.size - (offset % page) as u32);
+        left -= len;
+        let more = if left > 0 || file.padding() > 0 {
+            libc::SPLICE_F_MORE
+        } else {
+            0
+        };
+
+        let entry = opcode::Splice::new(
+            types::Fd(file.file.as_raw_fd()),
+            offset as i64,
+            types::Fd(pipe.write.as_raw_fd()),
+            -1,
+            len,
+        )
+        .build();
+        sends.push((entry, Send::new(conn_fd, len)));
+
+        let entry = opcode::Splice::new(types::Fd(pipe.read.as_raw_fd()), -1, fd, -1, len)
+            .flags(more)
+            .build();
+        sends.push((entry, Send::new(conn_fd, len)));
+
+        offset += u64::from(len);
+    }
+
+    if file.padding() > 0 {
+        sends.push(Send::bytes(conn_fd, &PADDING[..file.padding()], 0));
+    }
+
+    sends
+}
+
+/// Send a reply with file data from one of the registered buffers, if one is free and large enough.
+/// The data is read into the buffer right away. Returns whether it was sent; if it was not, it must
+/// be copied into the reply instead.
+fn send_zc(
+    ring: &mut IoUring,
+    buffers: &mut SendBuffers,
+    timeout: Option<&types::Timespec>,
+    conn_fd: i32,
+    head: &[u8],
+    file: &FileReply,
+) -> bool {
+    let len = head.len() + file.len as usize + file.padding();
+    let Some(index) = buffers.take(len) else {
+        return false;
+    };
+
+    let buffer = &mut buffers.buffers[index as usize];
+    buffer[..head.len()].copy_from_slice(head);
+    if let Err(e) = file.read_after(buffer, head.len()) {
+        // The copy will fail the same way, and deal with it:
+        debug!("Could not read a file to send on FD {conn_fd}: {e}");
+        buffers.free.push(index);
+        return false;
+    }
+
+    let send = SendZc {
+        fd: conn_fd,
+        len: len as u32,
+        buffer: index,
+    };
+    let entry = opcode::SendZc::new(types::Fd(conn_fd), buffer.as_ptr(), send.len)
+        .buf_index(Some(index))
+        .flags(libc::MSG_WAITALL)
+        .build()
+        .user_data(Box::new(Operation::SendZc(send)).to_u64());
+    match timeout {
+        Some(timeout) => {
+            let entries = [
+                entry.flags(squeue::Flags::IO_LINK),
+                send_timeout(conn_fd, timeout),
+            ];
+            push_entries(ring, &entries);
+        }
+        None => push_entries(ring, &[entry]),
+    }
+
+    true
+}
+
+/// The whole encoded reply, with the file data read into it after `head`.
+fn read_whole(head: &[u8], file: &FileReply) -> io::Result<Vec<u8>> {
+    let mut reply = head.to_vec();
+    reply.resize(head.len() + file.len as usize + file.padding(), 0);
+    file.read_after(&mut reply, head.len())?;
+
+    Ok(reply)
+}
+
+/// Submit operations that send (part of) a reply, linked so that each only starts once the one
+/// before it has finished, and the rest are cancelled if one falls short. The last one completes
+/// the reply. With a `timeout`, each is followed by a linked timeout, which cancels it if it takes
+/// longer.
+fn submit_sends(
+    ring: &mut IoUring,
+    sends: Vec<(squeue::Entry, Send)>,
+    timeout: Option<&types::Timespec>,
+) {
+    let last = sends.len() - 1;
+    let mut entries = Vec::new();
+    for (i, (entry, mut send)) in sends.into_iter().enumerate() {
+        send.last = i == last;
+        let fd = send.fd;
+        let entry = if send.last && timeout.is_none() {
+            entry
+        } else {
+            entry.flags(squeue::Flags::IO_LINK)
+        };
+        entries.push(entry.user_data(Box::new(Operation::Send(send)).to_u64()));
+
+        if let Some(timeout) = timeout {
+            let entry = send_timeout(fd, timeout);
+            entries.push(if send.last {
+                entry
+            } else {
+                entry.flags(squeue::Flags::IO_LINK)
+            });
+        }
+    }
+
+    push_entries(ring, &entries);
+}
+
+/// A timeout linked to the operation before it, sending (part of) a reply on `conn_fd`.
+fn send_timeout(conn_fd: i32, timeout: &types::Timespec) -> squeue::Entry {
+    opcode::LinkTimeout::new(timeout)
+        .build()
+        .user_data(Box::new(Operation::SendTimeout(SendTimeout { fd: conn_fd })).to_u64())
+}
+
+/// Push `entries` to the submission queue together, submitting what it holds first if they do not
+/// fit.
+fn push_entries(ring: &mut IoUring, entries: &[squeue::Entry]) {
+    // SAFETY: the buffers and file descriptors of the entries are kept by the connection until the
+    // last of them completes, and the user data of each is a leaked Operation.
+    unsafe {
+        if ring.submission().push_multiple(entries).is_err() {
+            ring.submit().expect("failed to submit to the ring");
+            ring.submission()
+                .push_multiple(entries)
+                .expect("queue is full");
+        }
+    }
+}
+
+/// Check for fatal errors in completions. These errors always indicate a BUG in this program.
+fn check_completion_error(cqe: &cqueue::Entry, op: &Operation) {
+    let res = cqe.result();
+
+    // Not an error:
+    if res >= 0 {
+        return;
+    }
+
+    match nix::errno::Errno::from_raw(-res) {
+        nix::Error::EBADF => panic!("Completion returned EBADF: {op}, {cqe:?}"),
+        nix::Error::EFAULT => panic!("Completion returned EFAULT: {op}, {cqe:?}"),
+        _ => {}
+    };
+}
+
+fn submit_accept(ring: &mut IoUring, listen_fd: types::Fd, user_data: u64) {
+    let submission = opcode::AcceptMulti::new(listen_fd)
+        .build()
+        .user_data(user_data);
+
+    // SAFETY: the parameter listen_fd will be valid for the lifetime of the operation because it
+    // is owned by the user_data, which has been "leaked" (passing ownership to the kernel) before
+    // calling this function.
+    unsafe {
+        ring.submission().push(&submission).expect("queue is full");
+    }
+}
+
+/// Receive on a connection into the receive buffers, until it ends or they run out.
+fn submit_receive(ring: &mut IoUring, conn_fd: types::Fd, user_data: u64) {
+    let submission = opcode::RecvMulti::new(conn_fd, GROUP_ID)
+        .build()
+        .user_data(user_data);
+
+    // Many may be submitted at once, when starved connections are resumed:
+    push_entries(ring, &[submission]);
+}
+
+/// Wait for the event file descriptor of a `StateUpdater` to be written to.
+fn submit_wake(ring: &mut IoUring, wake_fd: types::Fd, user_data: u64) {
+    let submission = opcode::PollAdd::new(wake_fd, libc::POLLIN as u32)
+        .multi(true)
+        .build()
+        .user_data(user_data);
+
+    // SAFETY: the event file descriptor is kept open by the server's own `StateUpdater`, which
+    // lives as long as the ring.
+    unsafe {
+        ring.submission().push(&submission).expect("queue is full");
+    }
+}
+
+#[derive(Debug)]
+enum Operation {
+    Accept(Accept),
+    Recv(Receive),
+    Send(Send),
+    SendZc(SendZc),
+    Wake(Wake),
+    Resume(Resume),
+    Cancel(Cancel),
+    SendTimeout(SendTimeout),
+    Throttled(Throttled),
+    IdleCheck,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Accept(a) => write!(f, "Accept on FD {}", a.fd),
+            Self::Recv(r) => write!(f, "Receive on FD {}", r.fd),
+            Self::Send(s) => write!(f, "Send on FD {}", s.fd),
+            Self::SendZc(s) => write!(f, "Zero-copy send on FD {}", s.fd),
+            Self::Wake(w) => write!(f, "Wake on FD {}", w.fd),
+            Self::Resume(r) => write!(f, "I/O for a call on FD {}", r.fd),
+            Self::Cancel(c) => write!(f, "Cancel of the receive on FD {}", c.fd),
+            Self::SendTimeout(t) => write!(f, "Timeout of a send on FD {}", t.fd),
+            Self::Throttled(t) => write!(f, "Wait for the throttle on FD {}", t.fd),
+            Self::IdleCheck => write!(f, "Check for idle connections"),
+        }
+    }
+}
+
+impl Operation {
+    fn handle_accept<T>(
+        self: Box<Self>,
+        server: &mut RpcServer<T>,
+        cqe: cqueue::Entry,
+        listen_fd: i32,
+    ) {
+        let fd = cqe.result();
+
+        if fd < 0 {
+            warn!("accept: error: {fd}: {}", io::Error::from_raw_os_error(fd))
+        } else {
+            let connection = server.connections.entry(fd).or_default();
+            connection.address = peer_address(fd);
+            connection.serial = server.next_serial;
+            connection.active = Some(Instant::now());
+            server.next_serial += 1;
+
+            // The read and write timeouts are kept by the server, since the socket's own do not
+            // apply to io_uring operations:
+            let keepalive = ConnectionTimeouts {
+                keepalive: server.timeouts.keepalive,
+                ..Default::default()
+            };
+            // SAFETY: the connection was just accepted, and is only closed by the server.
+            if let Err(e) = keepalive.apply(&unsafe { BorrowedFd::borrow_raw(fd) }) {
+                warn!("Could not set keepalive on FD {fd}: {e}");
+            }
+            server.receive(fd);
+        }
+
+        // Keep submission alive:
+        if !cqueue::more(cqe.flags()) {
+            warn!("Multishot accept did not set MORE flag; resubmitting");
+            submit_accept(
+                &mut server.ring,
+                types::Fd(listen_fd),
+                self.to_u64_noexpose(),
+            );
+        } else {
+            // Leak self again since this submission stays live with self as its user data
+            let _ = self.to_u64_noexpose();
+        }
+    }
+
+    fn handle_receive<T>(
+        self: Box<Self>,
+        server: &mut RpcServer<T>,
+        cqe: cqueue::Entry,
+        conn_fd: i32,
+    ) {
+        match cqe.result() {
+            // Every receive buffer is in use, which ends the receive:
+            res if res == -libc::ENOBUFS => {
+                server.receive_ended(conn_fd);
+                server.starve(conn_fd);
+                return;
+            }
+            // The receive was cancelled since the connection's calls are held back. They may have
+            // been released since, in which case it is received on again:
+            res if res == -libc::ECANCELED => {
+                server.receive_ended(conn_fd);
+                server.receive(conn_fd);
+                return;
+            }
+            res if res < 0 => {
+                warn!("Error in Receive completion: {cqe:?}");
+
+                if !cqueue::more(cqe.flags()) {
+                    server.close(conn_fd);
+                    return;
+                }
+            }
+            // Connection is done:
+            0 => {
+                server.close(conn_fd);
+
+                // Return early because there is no need to keep this submission alive anymore:
+                return;
+            }
+            // Got data:
+            amount => {
+                let buffer_id: u16 = cqueue::buffer_select(cqe.flags())
+                    .expect("Buffer ID should be set on a multishot receive");
+
+                // SAFETY: the buffer_id was just received from a completion.
+                let buf =
+                    unsafe { BufferMap::take_buf(&server.buffer_map, buffer_id, amount as usize) };
+
+                // The buffer goes back to the kernel once the procedure is done with it:
+                server.handle_received_bytes(Rc::new(buf), conn_fd);
+            }
+        }
+
+        // Keep submission alive, unless the connection's calls are held back:
+        if !cqueue::more(cqe.flags()) {
+            debug!("Multishot receive on FD {conn_fd} did not set MORE flag; resubmitting");
+            drop(self);
+            server.receive_ended(conn_fd);
+            server.receive(conn_fd);
+        } else {
+            // Leak self again since this submission stays live with self as its user data
+            let receive = self.to_u64_noexpose();
+            server.stop_receiving_if_held(conn_fd, receive);
+        }
+    }
+
+    /// A receive has been cancelled, or was found to have ended already, which its own completion
+    /// reports.
+    fn handle_cancel(&self, cqe: cqueue::Entry, conn_fd: i32) {
+        match cqe.result() {
+            0 => {}
+            res if res == -libc::ENOENT || res == -libc::EALREADY => {}
+            res => warn!(
+                "Could not cancel the receive on FD {conn_fd}: {}",
+                io::Error::from_raw_os_error(-res)
+            ),
+        }
+    }
+
+    /// The timeout linked to an operation sending a reply has passed, which cancelled the operation,
+    /// or was itself cancelled when the operation completed in time.
+    fn handle_send_timeout(&self, cqe: cqueue::Entry, conn_fd: i32) {
+        if cqe.result() == -libc::ETIME {
+            debug!("Timed out sending a reply on FD {conn_fd}");
+        }
+    }
+
+    /// One of the operations sending a reply has completed. If it fell short, the connection is
+    /// abandoned once the last of them completes, which it does with ECANCELED.
+    fn handle_send<T>(self: Box<Self>, server: &mut RpcServer<T>, cqe: cqueue::Entry, send: Send) {
+        match cqe.result() {
+            res if res == send.len as i32 => {}
+            res if res == -libc::ECANCELED => {
+                server.connection_failed(send.fd);
+            }
+            res => {
+                debug!("{self} sent {res} of {} bytes", send.len);
+                server.connection_failed(send.fd);
+            }
+        }
+
+        if send.last {
+            server.reply_sent(send.fd);
+        }
+    }
+
+    /// A zero-copy send completes twice: once when it has been sent, after which the next reply can
+    /// be, and again, with the NOTIF flag, when the kernel no longer uses its buffer.
+    fn handle_send_zc<T>(
+        self: Box<Self>,
+        server: &mut RpcServer<T>,
+        cqe: cqueue::Entry,
+        send: SendZc,
+    ) {
+        if cqueue::notif(cqe.flags()) {
+            server.send_buffers.free.push(send.buffer);
+            return;
+        }
+
+        if cqe.result() != send.len as i32 {
+            debug!("{self} sent {} of {} bytes", cqe.result(), send.len);
+            server.connection_failed(send.fd);
+        }
+        server.reply_sent(send.fd);
+
+        if cqueue::more(cqe.flags()) {
+            // Leak self again, for the notification:
+            let _ = self.to_u64_noexpose();
+        } else {
+            server.send_buffers.free.push(send.buffer);
+        }
+    }
+
+    /// The updates that woke the server have already been applied, so this only resets the event
+    /// file descriptor's count.
+    fn handle_wake(self: Box<Self>, ring: &mut IoUring, cqe: cqueue::Entry, wake_fd: i32) {
+        let mut count = [0_u8; 8];
+        // SAFETY: `count` is valid for writes of its length.
+        let _ = unsafe { libc::read(wake_fd, count.as_mut_ptr().cast(), count.len()) };
+
+        // Keep submission alive:
+        if !cqueue::more(cqe.flags()) {
+            submit_wake(ring, types::Fd(wake_fd), self.to_u64_noexpose());
+        } else {
+            // Leak self again since this submission stays live with self as its user data
+            let _ = self.to_u64_noexpose();
+        }
+    }
+
+    /// I/O that a procedure needed done has completed, so the call continues.
+    fn handle_resume<T>(
+        self: Box<Self>,
+        server: &mut RpcServer<T>,
+        cqe: cqueue::Entry,
+        resume: Resume,
+    ) {
+        let res = match cqe.result() {
+            res if res < 0 => Err(io::Error::from_raw_os_error(-res)),
+            res => Ok(res as u32),
+        };
+
+        // The operation is not resubmitted, whatever the call does next:
+        drop(self);
+        server.resume(resume, res);
+    }
+
+    /// Temporarily "leak" the Operation so that the kernel side can take ownership of it until the
+    /// completion is processed.
+    ///
+    /// Exposes provenance so that a pointer to the Operation can be acquired with the proper
+    /// provenance when processing the completion that holds this data.
+    fn to_u64(self: Box<Self>) -> u64 {
+        Box::into_raw(self).expose_provenance() as u64
+    }
+
+    /// Leak an operation without the need to expose its provenance, because it was already exposed.
+    /// Useful when re-submitting multishot requests with the same user_data from the original
+    /// submission.
+    fn to_u64_noexpose(self: Box<Self>) -> u64 {
+        Box::into_raw(self) as u64
+    }
+
+    /// Given a u64 which is expected to be a pointer to an Operation, turn it into a
+    /// Box<Operation> with some previously exposed provenance.
+    ///
+    /// # Safety
+    ///
+    /// Uses Box::from_raw() and has the same safety requirements as that function.
+    unsafe fn from_u64(p: u64) -> Box<Self> {
+        Box::from_raw(std::ptr::with_exposed_provenance::<Operation>(p as usize) as *mut Self)
+    }
+}
+
+#[derive(Debug)]
+struct Accept {
+    /// fd for the listener, needed in order to resubmit the accept
+    fd: i32,
+}
+
+impl Accept {
+    fn new(fd: i32) -> Self {
+        Self { fd }
+    }
+}
+
+#[derive(Debug)]
+struct Receive {
+    fd: i32,
+}
+
+impl Receive {
+    fn new(fd: i32) -> Self {
+        Self { fd }
+    }
+}
+
+#[derive(Debug)]
+struct Wake {
+    /// The event file descriptor of the server's `StateUpdater`.
+    fd: i32,
+}
+
+impl Wake {
+    fn new(fd: i32) -> Self {
+        Self { fd }
+    }
+}
+
+/// I/O submitted for a call by its procedure; see `RingResult::MoreIo`.
+#[derive(Clone, Copy, Debug)]
+struct Resume {
+    /// The connection that the call was received on.
+    fd: i32,
+
+    /// The call's ID in `RpcServer::waiting`.
+    id: u64,
+}
+
+/// The cancellation of the receive on a connection whose calls are held back.
+#[derive(Debug)]
+struct Cancel {
+    fd: i32,
+}
+
+/// The timeout linked to an operation sending a reply on a connection.
+#[derive(Debug)]
+struct SendTimeout {
+    fd: i32,
+}
+
+/// The wait until the calls held back on a connection, over the throttle's limits, may be answered.
+#[derive(Debug)]
+struct Throttled {
+    fd: i32,
+    timeout: types::Timespec,
+}
+
+/// A call waiting on I/O that its procedure needs done.
+struct WaitingCall<T> {
+    record: Received,
+
+    /// The verifier that a succesful reply carries.
+    verifier: OpaqueAuth,
+
+    /// The class of the call, which its reply is charged to, if there is a throttle.
+    class: Option<Class>,
+
+    then: Continuation<T>,
+
+    /// The span of the call, in which it continues.
+    span: tracing::Span,
+}
+
+/// The record of a call, which starts with its record mark, where it was received.
+struct Received {
+    buffer: Rc<RecvBuffer>,
+    range: Range<usize>,
+
+    /// When the call was received.
+    received: Instant,
+}
+
+impl Received {
+    fn bytes(&self) -> &[u8] {
+        &self.buffer[self.range.clone()]
+    }
+}
+
+/// One of the operations that send a reply: a send of bytes that the connection keeps, or a splice
+/// of file data.
+#[derive(Clone, Copy, Debug)]
+struct Send {
+    fd: i32,
+
+    /// The number of bytes that the operation should transfer.
+    len: u32,
+
+    /// Whether this is the last operation sending the reply.
+    last: bool,
+}
+
+impl Send {
+    fn new(fd: i32, len: u32) -> Self {
+        Self {
+            fd,
+            len,
+            last: false,
+        }
+    }
+
+    /// Send `bytes`, which the connection keeps until the reply has been sent, with send(2) `flags`.
+    /// The send waits for all of them to go, however slowly the client reads, rather than falling
+    /// short: a reply still being sent counts against the connection's limits, which is what holds
+    /// back a client that does not read its replies.
+    fn bytes(fd: i32, bytes: &[u8], flags: i32) -> (squeue::Entry, Self) {
+        let send = Self::new(fd, bytes.len() as u32);
+        let entry = opcode::Send::new(types::Fd(fd), bytes.as_ptr(), send.len)
+            .flags(flags | libc::MSG_WAITALL)
+            .build();
+
+        (entry, send)
+    }
+}
+
+/// A zero-copy send from one of the registered buffers.
+#[derive(Clone, Copy, Debug)]
+struct SendZc {
+    fd: i32,
+    len: u32,
+
+    /// The index of the buffer, which is freed once the kernel is done with it.
+    buffer: u16,
+}
+
+/// The padding sent after file data.
+static PADDING: [u8; 3] = [0; 3];
+
+/// A reply waiting to be sent on a connection, or being sent.
+enum Reply {
+    /// The encoded reply, with its record mark.
+    Bytes(Vec<u8>),
+
+    /// A reply with file data: the encoded reply up to the data, with a record mark that counts the
+    /// data and its padding too, and the file.
+    File(Vec<u8>, FileReply),
+
+    /// The encoded reply in parts, from an `RpcResult::SuccessParts`, sent with one sendmsg(2).
+    Parts(Parts),
+}
+
+impl Reply {
+    /// The bytes of the reply, with its file data.
+    fn size(&self) -> usize {
+        match self {
+            Self::Bytes(bytes) => bytes.len(),
+            Self::File(head, file) => head.len() + file.len as usize + file.padding(),
+            Self::Parts(parts) => parts.parts.iter().map(Vec::len).sum(),
+        }
+    }
+}
+
+impl From<ReplyParts> for Reply {
+    fn from(reply: ReplyParts) -> Self {
+        let mut parts = reply.into_parts();
+        if parts.len() == 1 {
+            return Self::Bytes(parts.pop().unwrap());
+        }
+
+        let iovecs: Vec<_> = parts
+            .iter()
+            .map(|part| libc::iovec {
+                iov_base: part.as_ptr() as *mut libc::c_void,
+                iov_len: part.len(),
+            })
+            .collect();
+        // SAFETY: a msghdr of zeros is an empty message to no particular address.
+        let mut message: Box<libc::msghdr> = Box::new(unsafe { std::mem::zeroed() });
+        message.msg_iov = iovecs.as_ptr() as *mut libc::iovec;
+        message.msg_iovlen = iovecs.len() as _;
+
+        Self::Parts(Parts {
+            parts,
+            iovecs,
+            message,
+        })
+    }
+}
+
+/// The parts of a reply, with the io vectors and message header that sendmsg(2) is given for them.
+/// The vectors point into the parts and the header to the vectors, all of which stay where they are
+/// on the heap while the reply is moved about.
+struct Parts {
+    parts: Vec<Vec<u8>>,
+
+    /// Only kept for `message`, which points to them.
+    #[allow(dead_code)]
+    iovecs: Vec<libc::iovec>,
+
+    message: Box<libc::msghdr>,
+}
+
+impl Parts {
+    /// Send the parts with sendmsg(2) rather than writev(2), which waits for all of them to go like
+    /// `Send::bytes()` does.
+    fn send(&self, fd: i32) -> (squeue::Entry, Send) {
+        let send = Send::new(fd, self.parts.iter().map(Vec::len).sum::<usize>() as u32);
+        let entry = opcode::SendMsg::new(types::Fd(fd), &*self.message)
+            .flags(libc::MSG_WAITALL as u32)
+            .build();
+
+        (entry, send)
+    }
+}
+
+/// The state of an accepted connection that calls have been received on.
+#[derive(Default)]
+struct Connection {
+    /// The replies to send, in order. Only one is sent at a time, since the kernel does not keep
+    /// separate submissions in order, and a reply may take several operations.
+    replies: VecDeque<Reply>,
+
+    /// Whether the first reply is being sent.
+    sending: bool,
+
+    /// The number of calls waiting on I/O.
+    waiting: usize,
+
+    /// Whether one of the operations sending the current reply fell short.
+    failed: bool,
+
+    /// Whether the connection was shut down, after which replies to it are dropped.
+    abandoned: bool,
+
+    /// Whether the connection is to be closed once the current reply has been sent.
+    closed: bool,
+
+    /// The pipe that file data is spliced through, once one has been.
+    pipe: Option<Pipe>,
+
+    /// The records being received on the connection, and what has been counted of them.
+    reassembly: Reassembly,
+    records: RecordStats,
+
+    /// The address of the client, which procedures find with `Call::get_peer_address()`.
+    address: Option<SocketAddr>,
+
+    /// The order in which the connection was accepted; see `ConnectionId`.
+    serial: u64,
+
+    /// The calls held back until the connection is below the limits on calls in flight: their
+    /// records, in the order they were received.
+    held: VecDeque<(Rc<RecvBuffer>, Range<usize>)>,
+
+    /// Until when the first call held back is over the throttle's limits, if it was.
+    throttled: Option<Instant>,
+
+    /// Whether a multishot receive is live on the connection, and whether it is being cancelled.
+    receiving: bool,
+    cancelling: bool,
+
+    /// When the connection was accepted, or last received on or sent a reply, after which it is
+    /// idle.
+    active: Option<Instant>,
+}
+
+/// The address of the peer of the connected socket `fd`, if it can be found.
+fn peer_address(fd: i32) -> Option<SocketAddr> {
+    // SAFETY: the stream is never dropped, so the socket is left open for the connection.
+    let stream = std::mem::ManuallyDrop::new(unsafe { std::net::TcpStream::from_raw_fd(fd) });
+
+    stream
+        .peer_addr()
+        .inspect_err(|e| warn!("Could not find the address of the peer on FD {fd}: {e}"))
+        .ok()
+}
+
+/// A pipe that file data is spliced from a file into, and from there into a socket.
+struct Pipe {
+    read: OwnedFd,
+    write: OwnedFd,
+
+    /// The most that the pipe holds.
+    size: u32,
+}
+
+impl Pipe {
+    /// The pipe in `pipe`, which is created if there is none yet.
+    fn of(pipe: &mut Option<Pipe>) -> io::Result<&Pipe> {
+        if let Some(pipe) = pipe {
+            return Ok(pipe);
+        }
+
+        let mut fds = [0; 2];
+        // SAFETY: `fds` has room for the two file descriptors.
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: pipe2() just opened them.
+        let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+        // Ask for a larger pipe, but make do with the size it has if that is not allowed:
+        let fd = write.as_raw_fd();
+        let mut size = unsafe { libc::fcntl(fd, libc::F_SETPIPE_SZ, PIPE_SIZE) };
+        if size < 0 {
+            size = unsafe { libc::fcntl(fd, libc::F_GETPIPE_SZ) };
+        }
+        if size <= 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(pipe.insert(Pipe {
+            read,
+            write,
+            size: size as u32,
+        }))
+    }
+}
+
+/// The buffers registered with the ring for zero-copy sends, and which of them are free.
+#[derive(Default)]
+struct SendBuffers {
+    buffers: Vec<Box<[u8]>>,
+    free: Vec<u16>,
+}
+
+impl SendBuffers {
+    fn new(ring: &IoUring, size: u32) -> io::Result<Self> {
+        let buffers: Vec<Box<[u8]>> = (0..SEND_BUFFERS)
+            .map(|_| vec![0; size as usize].into_boxed_slice())
+            .collect();
+        let iovecs: Vec<libc::iovec> = buffers
+            .iter()
+            .map(|buffer| libc::iovec {
+                iov_base: buffer.as_ptr() as *mut libc::c_void,
+                iov_len: buffer.len(),
+            })
+            .collect();
+
+        // SAFETY: the buffers live as long as the ring, and are only written to while they are
+        // free.
+        unsafe { ring.submitter().register_buffers(&iovecs)? };
+        debug!("Sending file data from {SEND_BUFFERS} buffers of {size} bytes");
+
+        Ok(Self {
+            buffers,
+            free: (0..SEND_BUFFERS).rev().collect(),
+        })
+    }
+
+    /// A free buffer that holds at least `len` bytes, if there is one.
+    fn take(&mut self, len: usize) -> Option<u16> {
+        if self.buffers.first().is_none_or(|buffer| buffer.len() < len) {
+            return None;
+        }
+
+        self.free.pop()
+    }
+}
+
+/// A memory map of a ring of buffer descriptors shared with the kernel, along with the buffers
+/// themselves.
+struct BufferMap {
+    /// Pointer to the memory shared with the kernel which holds the `struct io_uring_buf`s. Its
+    /// size is `sizeof(struct io_uring_buf) * num_entries`.
+    addr: *mut libc::c_void,
+
+    /// The number of entries in the shared buffer ring.
+    num_entries: u16,
+
+    /// The size of each buffer.
+    _buf_size: u32,
+
+    /// The tail of the ring, including unpublished buffers. This is the index of the next unused
+    /// slot.
+    private_tail: u16,
+
+    group_id: u16,
+
+    buffers: Vec<Box<[u8]>>,
+
+    /// Whether any buffer has been given back to the kernel since `take_given_back()` was called.
+    given_back: bool,
+}
+
+impl BufferMap {
+    pub fn new(ring: &mut IoUring, buf_size: u32, num_entries: u16) -> Self {
+        debug!("Receiving into {num_entries} buffers of {buf_size} bytes");
+
+        assert!(num_entries < u16::MAX); // top bit must not be set
+        assert!(num_entries & (num_entries - 1) == 0); // must be a power of 2
+
+        let len = (num_entries as usize) * std::mem::size_of::<types::BufRingEntry>();
+        let addr = unsafe {
+            match libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_ANONYMOUS | libc::MAP_SHARED | libc::MAP_POPULATE,
+                -1,
+                0,
+            ) {
+                libc::MAP_FAILED => panic!("mmap: {:?}", io::Error::last_os_error()),
+                addr => addr,
+            }
+        };
+
+        let mut buffer_map = Self {
+            addr,
+            num_entries,
+            _buf_size: buf_size,
+            private_tail: 0,
+            group_id: GROUP_ID,
+            buffers: Vec::new(),
+            given_back: false,
+        };
+
+        unsafe {
+            ring.submitter()
+                .register_buf_ring(buffer_map.addr as u64, num_entries, buffer_map.group_id)
+                .unwrap();
+        };
+
+        for i in 0..num_entries {
+            buffer_map
+                .buffers
+                .push(vec![0; buf_size as usize].into_boxed_slice());
+            let addr: *mut u8 = buffer_map.buffers[i as usize].as_ptr() as *mut u8;
+            buffer_map.push_buf(addr, buf_size, i);
+        }
+
+        buffer_map.publish_bufs();
+
+        buffer_map
+    }
+
+    /// Add a buffer described by `addr`, `len`, and `bid` to the buffer map.
+    fn push_buf(&mut self, addr: *mut u8, len: u32, bid: u16) {
+        let entries = self.addr as *mut types::BufRingEntry;
+        let index: u16 = self.private_tail & self.mask();
+
+        // SAFETY: ...
+        let entry = unsafe { entries.add(index as usize) };
+        // SAFETY: ...
+        let entry = unsafe { &mut *entry };
+
+        entry.set_addr(addr as u64);
+        entry.set_len(len);
+        entry.set_bid(bid);
+
+        self.private_tail = self.private_tail.wrapping_add(1);
+    }
+
+    /// Advance the shared tail to publish new buffers to the kernel.
+    fn publish_bufs(&mut self) {
+        let base_entry = self.addr as *const types::BufRingEntry;
+
+        // SAFETY: ...
+        let shared_tail = unsafe { types::BufRingEntry::tail(base_entry) };
+        let shared_tail = shared_tail as *const AtomicU16;
+
+        // SAFETY: ...
+        unsafe { (*shared_tail).store(self.private_tail, Ordering::Release) };
+    }
+
+    fn mask(&self) -> u16 {
+        self.num_entries - 1
+    }
+
+    /// Take the buffer with the given ID, which holds `len` bytes of received data, out of the map.
+    /// It is given back to the kernel when the returned RecvBuffer is dropped.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the buffer ID is one returned by the kernel in a completion
+    /// event, and which has not been re-submitted to the kernel. Otherwise, reading the buffer can
+    /// result in a data race with the kernel writing to that buffer.
+    pub unsafe fn take_buf(map: &Rc<RefCell<Self>>, id: u16, len: usize) -> RecvBuffer {
+        let buf = std::mem::take(&mut map.borrow_mut().buffers[id as usize]);
+        assert!(len <= buf.len());
+
+        RecvBuffer {
+            buf,
+            len,
+            ring: Some((id, Rc::clone(map))),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Has the same requirements as take_buf()
+    unsafe fn resubmit_buf(&mut self, mut buf: Box<[u8]>, id: u16) {
+        self.push_buf(buf.as_mut_ptr(), self._buf_size, id);
+        self.buffers[id as usize] = buf;
+        self.publish_bufs();
+        self.given_back = true;
+    }
+
+    /// Whether any buffer has been given back to the kernel since this was last called.
+    fn take_given_back(&mut self) -> bool {
+        std::mem::take(&mut self.given_back)
+    }
+}
+
+/// The number of receive buffers of `buf_size` bytes that fit in about `memory` bytes, which must be
+/// a power of two.
+fn buffer_count(buf_size: u32, memory: usize) -> u16 {
+    let count = (memory / buf_size.max(1) as usize).clamp(1, MAX_BUFFERS.into()) as u16;
+
+    // Round down to a power of two:
+    (1 << count.ilog2()).max(MIN_BUFFERS)
+}
+
+/// A buffer that the kernel received data into, taken out of the BufferMap. The buffer is given
+/// back to the kernel, to receive into again, when this is dropped.
+///
+/// A record that arrived in pieces is copied together into a buffer of its own, which is not part
+/// of the BufferMap.
+pub struct RecvBuffer {
+    buf: Box<[u8]>,
+
+    /// The number of bytes received into the buffer.
+    len: usize,
+
+    /// The ID of the buffer, and the map that it is given back to, unless it holds a copied record.
+    ring: Option<(u16, Rc<RefCell<BufferMap>>)>,
+}
+
+impl RecvBuffer {
+    /// A buffer holding a record that was copied together.
+    fn copied(record: Vec<u8>) -> Self {
+        Self {
+            len: record.len(),
+            buf: record.into_boxed_slice(),
+            ring: None,
+        }
+    }
+}
+
+impl Deref for RecvBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl Drop for RecvBuffer {
+    fn drop(&mut self) {
+        let Some((id, map)) = self.ring.take() else {
+            return;
+        };
+        let buf = std::mem::take(&mut self.buf);
+
+        // SAFETY: the buffer was taken out of the map by take_buf(), and is only given back here.
+        unsafe { map.borrow_mut().resubmit_buf(buf, id) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffer_counts() {
+        const MEMORY: usize = 32 * 1024 * 1024;
+
+        assert_eq!(buffer_count(4096, MEMORY), MAX_BUFFERS);
+        assert_eq!(buffer_count(0, MEMORY), MAX_BUFFERS);
+
+        // 64 KiB writes, with their headers, fit 504 times in 32 MiB:
+        assert_eq!(buffer_count(64 * 1024 + 2048, MEMORY), 256);
+
+        assert_eq!(buffer_count(16 * 1024 * 1024, MEMORY), MIN_BUFFERS);
+        assert_eq!(buffer_count(u32::MAX, MEMORY), MIN_BUFFERS);
+        assert_eq!(buffer_count(4096, 0), MIN_BUFFERS);
+    }
+
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct CallbackState {
+        backchannel: Option<Backchannel<CallbackState>>,
+        reply: Option<Result<Vec<u8>, Error>>,
+    }
+
+    /// Calls the client back with its argument, on the connection the call came on.
+    fn call_back(call: &RingCall, state: &mut CallbackState) -> RingResult<CallbackState> {
+        let callback = Callback {
+            prog: 40,
+            vers: 1,
+            proc: 1,
+            cred: OpaqueAuth::none(),
+            arg: call.arg.to_vec(),
+        };
+        let backchannel = state.backchannel.as_ref().unwrap();
+        backchannel.call(call.connection(), callback, |res, state| {
+            state.reply = Some(res)
+        });
+
+        RingResult::Done(RpcResult::Success(Vec::new()))
+    }
+
+    /// Answers with the result of the reply to the callback.
+    fn callback_result(_call: &RingCall, state: &mut CallbackState) -> RingResult<CallbackState> {
+        match state.reply.take() {
+            Some(Ok(result)) => RingResult::Done(RpcResult::Success(result)),
+            Some(Err(e)) => RingResult::Done(RpcResult::Success(e.to_string().into_bytes())),
+            None => RingResult::Done(RpcResult::SystemErr),
+        }
+    }
+
+    fn read_record(stream: &mut std::net::TcpStream) -> Vec<u8> {
+        let mut mark = [0; 4];
+        stream.read_exact(&mut mark).unwrap();
+        let mut record = vec![0; decode_record_mark(&mark).unwrap() as usize];
+        stream.read_exact(&mut record).unwrap();
+
+        record
+    }
+
+    #[test]
+    fn backchannel() {
+        let (address_tx, address_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let procedures: RingProcedureList<CallbackState> =
+                vec![None, Some(call_back), Some(callback_result)];
+            let sizes = RingSizes {
+                entries: 64,
+                max_call_size: 4096,
+                receive_memory: 0,
+            };
+            let address = "127.0.0.1:0".parse().unwrap();
+            let map = ProcedureMap::new(7, 1, 1, procedures);
+            let mut server = RpcServer::new(address, map, CallbackState::default(), sizes).unwrap();
+            server.user_state.backchannel = Some(server.backchannel());
+            address_tx.send(server.local_addr().unwrap()).unwrap();
+            server.main_loop().unwrap();
+        });
+
+        let address = address_rx.recv().unwrap();
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        let call = |proc: u32, arg: &[u8]| Callback {
+            prog: 7,
+            vers: 1,
+            proc,
+            cred: OpaqueAuth::none(),
+            arg: arg.to_vec(),
+        };
+
+        // The reply comes first, then the callback:
+        stream.write_all(&call(1, b"ping").encode(5)).unwrap();
+        assert_eq!(peek_reply(&read_record(&mut stream)), Some(5));
+        let record = read_record(&mut stream);
+        assert_eq!(peek_reply(&record), None);
+        let callback = decode_call(&record).unwrap();
+        assert_eq!((callback.get_program(), callback.arg), (40, &b"ping"[..]));
+
+        // The client answers the callback, and a second reply to it is dropped. Its next call has
+        // the same XID as the callback, which is no matter, since the server chose that one:
+        let xid = callback.get_xid();
+        stream
+            .write_all(&encode_succesful_reply(xid, b"pong"))
+            .unwrap();
+        stream
+            .write_all(&encode_succesful_reply(xid, b"pong"))
+            .unwrap();
+        stream.write_all(&call(2, &[]).encode(xid)).unwrap();
+        let (_, result) = decode_reply(xid, &read_record(&mut stream)).unwrap();
+        assert_eq!(result, b"pong");
+
+        // A callback whose connection closes before it is answered fails:
+        stream.write_all(&call(1, b"ping").encode(6)).unwrap();
+        assert_eq!(peek_reply(&read_record(&mut stream)), Some(6));
+        assert_eq!(peek_reply(&read_record(&mut stream)), None);
+        drop(stream);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        stream.write_all(&call(2, &[]).encode(7)).unwrap();
+        let (_, result) = decode_reply(7, &read_record(&mut stream)).unwrap();
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            "IO error: the connection closed before the reply to the callback"
+        );
+    }
+
+    /// Waits `ms` milliseconds on the ring, and then finishes the call with `then`.
+    fn sleep<T: 'static>(
+        ms: u64,
+        then: impl FnOnce(&mut T) -> RingResult<T> + 'static,
+    ) -> RingResult<T> {
+        let timespec = Box::new(types::Timespec::from(Duration::from_millis(ms)));
+        let entry = opcode::Timeout::new(&*timespec).build();
+
+        // SAFETY: the timespec is kept by the continuation until the timeout completes.
+        unsafe {
+            RingResult::more_io(entry, move |_, state| {
+                drop(timespec);
+                then(state)
+            })
+        }
+    }
+
+    /// Replies once a timeout has passed.
+    fn resumed(_call: &RingCall, _state: &mut ()) -> RingResult<()> {
+        sleep(100, |_| {
+            RingResult::Done(RpcResult::Success(b"resumed!".to_vec()))
+        })
+    }
+
+    /// Replies once two timeouts have passed, one after the other.
+    fn chained(_call: &RingCall, _state: &mut ()) -> RingResult<()> {
+        sleep(20, |_| {
+            sleep(20, |_| {
+                RingResult::Done(RpcResult::Success(b"chained!".to_vec()))
+            })
+        })
+    }
+
+    #[test]
+    fn continuations() {
+        let (address_tx, address_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let procedures: RingProcedureList<()> = vec![None, Some(resumed), Some(chained)];
+            let sizes = RingSizes {
+                entries: 64,
+                max_call_size: 4096,
+                receive_memory: 0,
+            };
+            let address = "127.0.0.1:0".parse().unwrap();
+            let map = ProcedureMap::new(7, 1, 1, procedures);
+            let mut server = RpcServer::new(address, map, (), sizes).unwrap();
+            address_tx.send(server.local_addr().unwrap()).unwrap();
+            server.main_loop().unwrap();
+        });
+
+        let address = address_rx.recv().unwrap();
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        let call = |proc: u32| Callback {
+            prog: 7,
+            vers: 1,
+            proc,
+            cred: OpaqueAuth::none(),
+            arg: Vec::new(),
+        };
+
+        // A call that waits on I/O does not hold up the calls after it, and its continuation
+        // replies once the I/O completes:
+        stream.write_all(&call(1).encode(1)).unwrap();
+        stream.write_all(&call(0).encode(2)).unwrap();
+        assert_eq!(peek_reply(&read_record(&mut stream)), Some(2));
+        let (_, result) = decode_reply(1, &read_record(&mut stream)).unwrap();
+        assert_eq!(result, b"resumed!");
+
+        // A continuation may do more I/O in turn:
+        stream.write_all(&call(2).encode(3)).unwrap();
+        let (_, result) = decode_reply(3, &read_record(&mut stream)).unwrap();
+        assert_eq!(result, b"chained!");
+    }
+
+    /// Makes a callback on the connection the call came on, which is not answered, and replies once
+    /// a timeout has passed.
+    fn call_back_and_wait(call: &RingCall, state: &mut CallbackState) -> RingResult<CallbackState> {
+        let _ = call_back(call, state);
+        sleep(300, |_| RingResult::Done(RpcResult::Success(Vec::new())))
+    }
+
+    #[test]
+    fn closed_while_waiting() {
+        let (address_tx, address_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let procedures: RingProcedureList<CallbackState> =
+                vec![None, Some(call_back_and_wait), Some(callback_result)];
+            let sizes = RingSizes {
+                entries: 64,
+                max_call_size: 4096,
+                receive_memory: 0,
+            };
+            let address = "127.0.0.1:0".parse().unwrap();
+            let map = ProcedureMap::new(7, 1, 1, procedures);
+            let mut server = RpcServer::new(address, map, CallbackState::default(), sizes).unwrap();
+            server.user_state.backchannel = Some(server.backchannel());
+            address_tx.send(server.local_addr().unwrap()).unwrap();
+            server.main_loop().unwrap();
+        });
+
+        let address = address_rx.recv().unwrap();
+        let call = |proc: u32| Callback {
+            prog: 7,
+            vers: 1,
+            proc,
+            cred: OpaqueAuth::none(),
+            arg: Vec::new(),
+        };
+
+        // The client closes its connection while the call is waiting on I/O:
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        stream.write_all(&call(1).encode(1)).unwrap();
+        assert_eq!(peek_reply(&read_record(&mut stream)), None);
+        drop(stream);
+        std::thread::sleep(Duration::from_millis(100));
+
+        // The connection is not closed until the call is done with, so the callback on it has not
+        // failed yet:
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        stream.write_all(&call(2).encode(2)).unwrap();
+        assert!(decode_reply(2, &read_record(&mut stream)).is_err());
+
+        // Once the I/O completes, the connection is closed, which fails the callback:
+        std::thread::sleep(Duration::from_millis(400));
+        stream.write_all(&call(2).encode(3)).unwrap();
+        let (_, result) = decode_reply(3, &read_record(&mut stream)).unwrap();
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            "IO error: the connection closed before the reply to the callback"
+        );
+    }
+
+    /// Counts the calls answered, and replies with the count once a timeout has passed.
+    fn counted(_call: &RingCall, count: &mut u32) -> RingResult<u32> {
+        *count += 1;
+        let n = *count;
+        sleep(200, move |_| {
+            RingResult::Done(RpcResult::Success(n.to_be_bytes().to_vec()))
+        })
+    }
+
+    /// Replies with the count of the calls answered so far.
+    fn count(_call: &RingCall, count: &mut u32) -> RingResult<u32> {
+        RingResult::Done(RpcResult::Success(count.to_be_bytes().to_vec()))
+    }
+
+    #[test]
+    fn held_calls() {
+        let (address_tx, address_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let procedures: RingProcedureList<u32> = vec![None, Some(counted), Some(count)];
+            let sizes = RingSizes {
+                entries: 64,
+                max_call_size: 4096,
+                receive_memory: 0,
+            };
+            let address = "127.0.0.1:0".parse().unwrap();
+            let map = ProcedureMap::new(7, 1, 1, procedures);
+            let mut server = RpcServer::new(address, map, 0, sizes).unwrap();
+            server.set_connection_limits(ConnectionLimits {
+                max_in_flight: 2,
+                ..Default::default()
+            });
+            address_tx.send(server.local_addr().unwrap()).unwrap();
+            server.main_loop().unwrap();
+        });
+
+        let address = address_rx.recv().unwrap();
+        let call = |proc: u32| Callback {
+            prog: 7,
+            vers: 1,
+            proc,
+            cred: OpaqueAuth::none(),
+            arg: Vec::new(),
+        };
+
+        // Five calls are sent without reading their replies, and only two are answered at once:
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        for xid in 1..=5 {
+            stream.write_all(&call(1).encode(xid)).unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(100));
+        let mut other = std::net::TcpStream::connect(address).unwrap();
+        other.write_all(&call(2).encode(6)).unwrap();
+        let (_, result) = decode_reply(6, &read_record(&mut other)).unwrap();
+        assert_eq!(result, 2u32.to_be_bytes());
+
+        // The others are answered as replies are sent, in the order they were received:
+        for xid in 1..=5 {
+            let (_, result) = decode_reply(xid, &read_record(&mut stream)).unwrap();
+            assert_eq!(result, xid.to_be_bytes());
+        }
+    }
+
+    /// The arguments kept in their receive buffers.
+    #[derive(Default)]
+    struct Kept {
+        args: Vec<ArgBuffer>,
+    }
+
+    /// Keeps its argument in its receive buffer.
+    fn keep(call: &RingCall, kept: &mut Kept) -> RingResult<Kept> {
+        kept.args.push(call.retain(call.arg));
+        RingResult::Done(RpcResult::Success(Vec::new()))
+    }
+
+    /// Replies with the arguments kept so far.
+    fn kept(_call: &RingCall, kept: &mut Kept) -> RingResult<Kept> {
+        let args: Vec<&[u8]> = kept.args.iter().map(|arg| &arg[..]).collect();
+        RingResult::Done(RpcResult::Success(args.concat()))
+    }
+
+    #[test]
+    fn retained_buffers() {
+        let (server_tx, server_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let procedures: RingProcedureList<Kept> = vec![None, Some(keep), Some(kept)];
+            let sizes = RingSizes {
+                entries: 64,
+                max_call_size: 4096,
+                receive_memory: 0,
+            };
+            let address = "127.0.0.1:0".parse().unwrap();
+            let map = ProcedureMap::new(7, 1, 1, procedures);
+            let mut server = RpcServer::new(address, map, Kept::default(), sizes).unwrap();
+            let updater = server.state_updater().unwrap();
+            server_tx
+                .send((server.local_addr().unwrap(), updater))
+                .unwrap();
+            server.main_loop().unwrap();
+        });
+
+        let (address, updater) = server_rx.recv().unwrap();
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        let call = |proc: u32, arg: Vec<u8>| Callback {
+            prog: 7,
+            vers: 1,
+            proc,
+            cred: OpaqueAuth::none(),
+            arg,
+        };
+
+        // Each call is received into a buffer of its own, and all but one of the buffers are kept:
+        let mut expected = Vec::new();
+        for i in 1..MIN_BUFFERS as u32 {
+            stream
+                .write_all(&call(1, vec![i as u8; 4]).encode(i))
+                .unwrap();
+            decode_reply(i, &read_record(&mut stream)).unwrap();
+            expected.extend_from_slice(&[i as u8; 4]);
+        }
+
+        // The arguments kept are unchanged, though the buffer left is received into meanwhile:
+        for xid in [100, 101] {
+            stream.write_all(&call(2, Vec::new()).encode(xid)).unwrap();
+            let (_, result) = decode_reply(xid, &read_record(&mut stream)).unwrap();
+            assert_eq!(result, expected);
+        }
+
+        // Once every buffer is kept, nothing more is received:
+        stream.write_all(&call(1, vec![0; 4]).encode(102)).unwrap();
+        decode_reply(102, &read_record(&mut stream)).unwrap();
+        stream.write_all(&call(2, Vec::new()).encode(103)).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let e = stream.read(&mut [0; 4]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+
+        // Until the buffers are dropped, which gives them back to the ring:
+        updater.update(|kept| kept.args.clear());
+        stream.set_read_timeout(None).unwrap();
+        let (_, result) = decode_reply(103, &read_record(&mut stream)).unwrap();
+        assert!(result.is_empty());
+    }
+
+    /// Replies with 1 MiB of data, after its length, in parts.
+    fn parts<T>(_call: &RingCall, _state: &mut T) -> RingResult<T> {
+        let data: Vec<u8> = (0..1 << 20).map(|i| (i % 251) as u8).collect();
+        let len = (data.len() as u32).to_be_bytes().to_vec();
+        RingResult::Done(RpcResult::SuccessParts(vec![len, data]))
+    }
+
+    #[test]
+    fn reply_parts() {
+        let (address_tx, address_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let procedures: RingProcedureList<()> = vec![None, Some(parts)];
+            let sizes = RingSizes {
+                entries: 64,
+                max_call_size: 4096,
+                receive_memory: 0,
+            };
+            let address = "127.0.0.1:0".parse().unwrap();
+            let map = ProcedureMap::new(7, 1, 1, procedures);
+            let mut server = RpcServer::new(address, map, (), sizes).unwrap();
+            address_tx.send(server.local_addr().unwrap()).unwrap();
+            server.main_loop().unwrap();
+        });
+
+        let address = address_rx.recv().unwrap();
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        let call = Callback {
+            prog: 7,
+            vers: 1,
+            proc: 1,
+            cred: OpaqueAuth::none(),
+            arg: Vec::new(),
+        };
+
+        // Twice, to see that the next reply follows the whole of the first:
+        for xid in [8, 9] {
+            stream.write_all(&call.encode(xid)).unwrap();
+            let (_, result) = decode_reply(xid, &read_record(&mut stream)).unwrap();
+            assert_eq!(result[..4], (1u32 << 20).to_be_bytes());
+            assert!(result[4..]
+                .iter()
+                .enumerate()
+                .all(|(i, &b)| b == (i % 251) as u8));
+        }
+    }
+
+    #[test]
+    fn throttled_calls() {
+        use crate::throttle::Limits;
+
+        let (address_tx, address_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let procedures: RingProcedureList<()> = vec![None, Some(parts)];
+            let sizes = RingSizes {
+                entries: 64,
+                max_call_size: 4096,
+                receive_memory: 0,
+            };
+            let address = "127.0.0.1:0".parse().unwrap();
+            let mut map = ProcedureMap::new(7, 1, 1, procedures);
+            let limits = Limits {
+                ops_per_sec: Some(2),
+                bytes_per_sec: None,
+            };
+            map.set_throttle(Throttle::new(limits), None);
+            let mut server = RpcServer::new(address, map, (), sizes).unwrap();
+            address_tx.send(server.local_addr().unwrap()).unwrap();
+            server.main_loop().unwrap();
+        });
+
+        let address = address_rx.recv().unwrap();
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        let call = |proc: u32| Callback {
+            prog: 7,
+            vers: 1,
+            proc,
+            cred: OpaqueAuth::none(),
+            arg: Vec::new(),
+        };
+
+        // The client's first two calls are answered at once, and the rest, in order, as its
+        // allowance is refilled:
+        let start = Instant::now();
+        for xid in 1..=4 {
+            stream.write_all(&call(0).encode(xid)).unwrap();
+        }
+        for xid in 1..=4 {
+            assert_eq!(peek_reply(&read_record(&mut stream)), Some(xid));
+            let elapsed = start.elapsed();
+            match xid {
+                1 | 2 => assert!(elapsed < Duration::from_millis(400), "{elapsed:?}"),
+                _ => assert!(
+                    elapsed >= Duration::from_millis(500 * (xid as u64 - 2) - 100),
+                    "{elapsed:?}"
+                ),
+            }
+        }
+    }
+
+    /// Replies with the file data of `FILE_DATA`, spliced from a file after its length.
+    fn file_reply(_call: &RingCall, file: &mut Option<File>) -> RingResult<Option<File>> {
+        let file = file.as_ref().unwrap().try_clone().unwrap();
+        RingResult::File(FileReply {
+            head: (FILE_DATA.len() as u32).to_be_bytes().to_vec(),
+            file,
+            offset: 0,
+            len: FILE_DATA.len() as u32,
+        })
+    }
+
+    const FILE_DATA: &[u8] = b"spliced through a pipe";
+
+    /// Replies with the data of the file at the offset and of the length in the argument, after
+    /// its length.
+    fn file_range(call: &RingCall, file: &mut Option<File>) -> RingResult<Option<File>> {
+        let offset = u64::from_be_bytes(call.arg[..8].try_into().unwrap());
+        let len = u32::from_be_bytes(call.arg[8..12].try_into().unwrap());
+        RingResult::File(FileReply {
+            head: len.to_be_bytes().to_vec(),
+            file: file.as_ref().unwrap().try_clone().unwrap(),
+            offset,
+            len,
+        })
+    }
+
+    /// Read ranges of a file larger than a pipe through a server that sends file data as `how`
+    /// says, and check them against the file.
+    fn check_file_sends(how: FileSends) {
+        let path = std::env::temp_dir().join(format!("ring-{how:?}-{}", std::process::id()));
+        let data: Vec<u8> = (0..PIPE_SIZE as usize * 3 / 2 + 3)
+            .map(|i| (i % 253) as u8)
+            .collect();
+        std::fs::write(&path, &data).unwrap();
+        let file = File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let expected = file.try_clone().unwrap();
+
+        let (address_tx, address_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let procedures: RingProcedureList<Option<File>> = vec![None, Some(file_range)];
+            let sizes = RingSizes {
+                entries: 64,
+                max_call_size: 4096,
+                receive_memory: 0,
+            };
+            let address = "127.0.0.1:0".parse().unwrap();
+            let map = ProcedureMap::new(7, 1, 1, procedures);
+            let mut server = RpcServer::new(address, map, Some(file), sizes).unwrap();
+            server.send_files(how, 2 * PIPE_SIZE as u32).unwrap();
+            address_tx.send(server.local_addr().unwrap()).unwrap();
+            server.main_loop().unwrap();
+        });
+
+        let address = address_rx.recv().unwrap();
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+
+        // The whole file, which needs padding, a range larger than the pipe that does not, from
+        // part way into a page, and a short one:
+        let ranges = [
+            (0, data.len() as u32),
+            (1, PIPE_SIZE as u32 + 4),
+            (PIPE_SIZE as u64 - 2, 5),
+        ];
+        for (xid, (offset, len)) in (1..).zip(ranges) {
+            let mut arg = offset.to_be_bytes().to_vec();
+            arg.extend_from_slice(&len.to_be_bytes());
+            let call = Callback {
+                prog: 7,
+                vers: 1,
+                proc: 1,
+                cred: OpaqueAuth::none(),
+                arg,
+            };
+            stream.write_all(&call.encode(xid)).unwrap();
+            let (_, result) = decode_reply(xid, &read_record(&mut stream)).unwrap();
+
+            let mut read = vec![0; len as usize];
+            expected.read_exact_at(&mut read, offset).unwrap();
+            let padded = (len as usize).next_multiple_of(4);
+            assert_eq!(
+                result.len(),
+                4 + padded,
+                "{how:?} of {len} bytes at {offset}"
+            );
+            assert_eq!(result[..4], len.to_be_bytes());
+            assert!(
+                result[4..4 + len as usize] == read,
+                "{how:?} of {len} bytes at {offset}"
+            );
+            assert!(result[4 + len as usize..].iter().all(|&b| b == 0));
+        }
+    }
+
+    #[test]
+    fn spliced_files() {
+        check_file_sends(FileSends::Splice);
+    }
+
+    #[test]
+    fn zero_copy_files() {
+        check_file_sends(FileSends::SendZc);
+    }
+
+    #[test]
+    fn connection_timeouts() {
+        let path = std::env::temp_dir().join(format!("ring-timeouts-{}", std::process::id()));
+        std::fs::write(&path, FILE_DATA).unwrap();
+        let file = File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let (address_tx, address_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let procedures: RingProcedureList<Option<File>> =
+                vec![None, Some(parts), Some(file_reply)];
+            let sizes = RingSizes {
+                entries: 64,
+                max_call_size: 4096,
+                receive_memory: 0,
+            };
+            let address = "127.0.0.1:0".parse().unwrap();
+            let map = ProcedureMap::new(7, 1, 1, procedures);
+            let mut server = RpcServer::new(address, map, Some(file), sizes).unwrap();
+            server.send_files(FileSends::Splice, 4096).unwrap();
+            server.set_connection_timeouts(ConnectionTimeouts {
+                read: Some(Duration::from_millis(200)),
+                write: Some(Duration::from_millis(200)),
+                keepalive: Some(Duration::from_secs(60)),
+            });
+            address_tx.send(server.local_addr().unwrap()).unwrap();
+            server.main_loop().unwrap();
+        });
+
+        let address = address_rx.recv().unwrap();
+        let call = |proc: u32| Callback {
+            prog: 7,
+            vers: 1,
+            proc,
+            cred: OpaqueAuth::none(),
+            arg: Vec::new(),
+        };
+
+        // Replies sent in several linked operations, each with its timeout, arrive whole:
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        for xid in [1, 2] {
+            stream.write_all(&call(2).encode(xid)).unwrap();
+            let (_, result) = decode_reply(xid, &read_record(&mut stream)).unwrap();
+            assert_eq!(result[4..4 + FILE_DATA.len()], *FILE_DATA);
+        }
+
+        // Then the connection is idle, and is closed:
+        std::thread::sleep(Duration::from_millis(500));
+        assert_eq!(stream.read(&mut [0; 4]).unwrap(), 0);
+
+        // A client that does not read its replies has its connection closed once a send of one
+        // takes too long, rather than once every reply has been sent. Its receive buffer is kept
+        // small, so that the replies cannot all wait in the socket buffers:
+        let mut stream = {
+            use nix::sys::socket::*;
+
+            let fd = socket(
+                AddressFamily::Inet,
+                SockType::Stream,
+                SockFlag::SOCK_CLOEXEC,
+                None,
+            )
+            .unwrap();
+            setsockopt(&fd, sockopt::RcvBuf, &(64 * 1024)).unwrap();
+            let SocketAddr::V4(v4) = address else {
+                unreachable!("the server listens on 127.0.0.1");
+            };
+            connect(fd.as_raw_fd(), &SockaddrIn::from(v4)).unwrap();
+            std::net::TcpStream::from(fd)
+        };
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        for xid in 0..32 {
+            stream.write_all(&call(1).encode(xid)).unwrap();
+        }
+        let mut received = vec![0; 4];
+        stream.read_exact(&mut received).unwrap();
+        std::thread::sleep(Duration::from_millis(1000));
+        let _ = stream.read_to_end(&mut received);
+        assert!(received.len() < 32 << 20);
+    }
+}
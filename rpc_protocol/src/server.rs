@@ -1,9 +1,21 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
+use std::{
+    io::{self, IoSlice},
+    os::fd::AsFd,
+    time::{Duration, Instant},
+};
+
 use log::*;
 
-use crate::*;
+use crate::{
+    metrics::*,
+    record::{Record, RecordLimits, RecordStats},
+    throttle::{Class, Classifier, Throttle},
+    trace::*,
+    *,
+};
 
 /// An RPC Procedure implementation takes a reference to the RPC call information for the request
 /// which allows it to inspect the credential, and also contains the encoded argument to the
@@ -11,16 +23,94 @@ use crate::*;
 /// or unsuccesful.
 pub type RpcProcedure<T> = fn(&Call, &mut T) -> RpcResult;
 
+/// A streaming procedure reads its argument from the connection as it arrives, rather than from a
+/// buffer holding the whole argument, so that a large argument (like the data of a write) can be
+/// decoded incrementally, for example with the `deserialize_from()` methods of generated types.
+/// The call's `arg` is empty: `arg` reads the rest of the call's record instead. Whatever the
+/// procedure leaves unread is discarded before the reply is sent.
+pub type StreamingProcedure<T> = fn(&Call, arg: &mut dyn Read, &mut T) -> RpcResult;
+
+/// How a procedure is given its argument.
+enum Procedure<T> {
+    Buffered(RpcProcedure<T>),
+    Streaming(StreamingProcedure<T>),
+}
+
+/// Looks up the name of a procedure given its version and procedure numbers, for logging. The code
+/// generated for an RPC program definition includes such a function, `procedures::proc_name()`.
+pub type ProcedureNames = fn(u32, u32) -> Option<&'static str>;
+
+/// Checks the encoded result of a procedure, given its version and procedure numbers, against the
+/// procedure's result type; it returns None for a procedure it does not check. The code generated
+/// for an RPC program definition includes such a function, `procedures::check_result()`.
+pub type ResultCheck = fn(u32, u32, &[u8]) -> Option<Result<(), xdr_lib::EncodingError>>;
+
 /// The NULL Procedure is defined for every service and does nothing, succesfully.
 pub fn null_procedure<T>(_call: &Call, _state: &mut T) -> RpcResult {
     RpcResult::Success(vec![])
 }
 
+/// Implement a procedure with `f`, which takes its decoded argument and returns its result, rather
+/// than the encoded argument and result, such as a procedure of the code that `xdr_codegen`
+/// generates:
+///
+/// ```ignore
+/// fn add(call: &Call, args: ExportEntry, state: &mut State) -> Result<ControlResult, RpcResult>
+/// ```
+///
+/// An argument that does not decode is answered with GARBAGE_ARGS, and an `Err` is the result of
+/// the call instead, such as `RpcResult::SystemErr`. `f` is a function, or a closure that captures
+/// nothing, which is checked when the program is built, so that it can be a `RpcProcedure`.
+pub fn typed_procedure<Args, Res, T, F>(f: F) -> RpcProcedure<T>
+where
+    Args: xdr_lib::Decode,
+    Res: xdr_lib::Encode,
+    F: Fn(&Call, Args, &mut T) -> Result<Res, RpcResult> + Copy,
+{
+    fn procedure<Args, Res, T, F>(call: &Call, state: &mut T) -> RpcResult
+    where
+        Args: xdr_lib::Decode,
+        Res: xdr_lib::Encode,
+        F: Fn(&Call, Args, &mut T) -> Result<Res, RpcResult> + Copy,
+    {
+        // SAFETY: `F` has no size, as `typed_procedure()` checks, and a value of it was given to
+        // `typed_procedure()`, so making one is the same as copying that value.
+        let f: F = unsafe { std::mem::zeroed() };
+
+        let mut arg = call.arg;
+        let Ok(args) = Args::decode(&mut arg) else {
+            return RpcResult::GarbageArgs;
+        };
+        match f(call, args, state) {
+            Ok(res) => RpcResult::Success(res.encode()),
+            Err(res) => res,
+        }
+    }
+
+    const {
+        assert!(
+            std::mem::size_of::<F>() == 0,
+            "typed_procedure() takes a function, or a closure that captures nothing"
+        )
+    };
+    let _ = f;
+    procedure::<Args, Res, T, F>
+}
+
 /// An RPC procedure implementation is permitted to return these results.
+#[derive(Debug)]
 pub enum RpcResult {
     /// A succesful result includes the encoded value of the reply.
     Success(Vec<u8>),
 
+    /// A succesful result whose reply carries the given verifier instead of an AUTH_NONE one.
+    SuccessWithVerifier(OpaqueAuth, Vec<u8>),
+
+    /// A succesful result in parts, such as the head of a READ result and its data, that are sent
+    /// one after another with a vectored write rather than copied into the reply. Their total length
+    /// must be a multiple of 4.
+    SuccessParts(Vec<Vec<u8>>),
+
     /// The procedure implementation determined that the arguments were invalid.
     GarbageArgs,
 
@@ -28,6 +118,209 @@ pub enum RpcResult {
     SystemErr,
 }
 
+impl RpcResult {
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self,
+            Self::Success(_) | Self::SuccessWithVerifier(..) | Self::SuccessParts(_)
+        )
+    }
+
+    /// Encode the reply to call `xid`, with its record mark. A succesful reply carries `verifier`,
+    /// unless the procedure gave its own.
+    pub fn encode_reply(self, xid: u32, verifier: OpaqueAuth) -> Vec<u8> {
+        match self {
+            Self::Success(data) => encode_succesful_reply_with_verifier(xid, verifier, &data),
+            Self::SuccessWithVerifier(verf, data) => {
+                encode_succesful_reply_with_verifier(xid, verf, &data)
+            }
+            res @ Self::SuccessParts(_) => res.encode_reply_parts(xid, verifier).into_bytes(),
+            Self::GarbageArgs => encode_reply_no_arg(
+                xid,
+                ReplyBody::accepted_reply(AcceptedReplyBody::GarbageArgs),
+            ),
+            Self::SystemErr => {
+                encode_reply_no_arg(xid, ReplyBody::accepted_reply(AcceptedReplyBody::SystemErr))
+            }
+        }
+    }
+
+    /// Encode the reply to call `xid` like `encode_reply()`, but keep the parts of a
+    /// `SuccessParts` result apart from the reply header, so that they are not copied.
+    pub fn encode_reply_parts(self, xid: u32, verifier: OpaqueAuth) -> ReplyParts {
+        match self {
+            Self::SuccessParts(parts) => {
+                // It is illegal to pass a result that is not padded to a multiple of 4 bytes:
+                assert_eq!(0, parts.iter().map(Vec::len).sum::<usize>() % 4);
+
+                let header = encode_succesful_reply_with_verifier(xid, verifier, &[]);
+                ReplyParts::new(header, parts)
+            }
+            res => res.encode_reply(xid, verifier).into(),
+        }
+    }
+}
+
+/// An encoded reply, with its record mark, kept in the parts it was built from, so that it can be
+/// sent with a vectored write instead of being copied into one buffer.
+#[derive(Debug)]
+pub struct ReplyParts {
+    /// The parts, none of them empty but the first, which begins with the record mark.
+    parts: Vec<Vec<u8>>,
+}
+
+impl ReplyParts {
+    /// The reply `header`, which begins with a record mark, followed by `results`. The record mark
+    /// is set to count all of them.
+    fn new(header: Vec<u8>, results: Vec<Vec<u8>>) -> Self {
+        let mut parts = vec![header];
+        parts.extend(results.into_iter().filter(|part| !part.is_empty()));
+
+        let size = u32::try_from(parts.iter().map(Vec::len).sum::<usize>() - 4).unwrap();
+        parts[0][..4].copy_from_slice(&(size | (1 << 31)).to_be_bytes());
+
+        Self { parts }
+    }
+
+    /// The number of bytes in the reply, with its record mark.
+    pub fn size(&self) -> usize {
+        self.parts.iter().map(Vec::len).sum()
+    }
+
+    /// The parts of the reply, in order, to write with `Write::write_vectored()`.
+    pub fn io_slices(&self) -> Vec<IoSlice<'_>> {
+        self.parts.iter().map(|part| IoSlice::new(part)).collect()
+    }
+
+    /// The whole reply, in one buffer. The parts are copied together the first time it is asked
+    /// for, so this is best left to when it is needed, such as for tracing.
+    pub fn whole(&mut self) -> &[u8] {
+        if self.parts.len() > 1 {
+            self.parts = vec![self.parts.concat()];
+        }
+
+        &self.parts[0]
+    }
+
+    /// The whole reply, in one buffer.
+    pub fn into_bytes(mut self) -> Vec<u8> {
+        self.whole();
+        self.parts.swap_remove(0)
+    }
+
+    /// The parts of the reply, for the io_uring server to send.
+    #[cfg(feature = "uring")]
+    pub(crate) fn into_parts(self) -> Vec<Vec<u8>> {
+        self.parts
+    }
+
+    /// Write the whole reply to `stream`, with as few vectored writes as it takes.
+    pub fn write_to<W: Write + ?Sized>(&self, stream: &mut W) -> io::Result<()> {
+        let mut slices = self.io_slices();
+        let mut slices = &mut slices[..];
+        while !slices.is_empty() {
+            match stream.write_vectored(slices) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write whole reply",
+                    ));
+                }
+                Ok(n) => IoSlice::advance_slices(&mut slices, n),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<Vec<u8>> for ReplyParts {
+    /// A reply that was encoded whole, with its record mark.
+    fn from(bytes: Vec<u8>) -> Self {
+        Self { parts: vec![bytes] }
+    }
+}
+
+/// An authenticator checks the credential and verifier of each call before it is dispatched, and
+/// decides who made the call and which verifier to send back in the reply. The server's default
+/// is `SysAuthenticator`; a service can install its own with `RpcProgram::set_authenticator()`.
+pub trait Authenticator {
+    /// Authenticate `call`, or return the reason it is rejected, which is sent to the caller in an
+    /// AUTH_ERROR reply.
+    fn authenticate(&mut self, call: &Call) -> Result<Authenticated, AuthError>;
+}
+
+/// A call that was succesfully authenticated.
+pub struct Authenticated {
+    /// Who made the call. Procedures find it with `Call::get_principal()`.
+    pub principal: Principal,
+
+    /// The verifier sent with a succesful reply to the call, unless the procedure gives one of its
+    /// own with `RpcResult::SuccessWithVerifier`.
+    pub verifier: OpaqueAuth,
+}
+
+/// Accepts calls with AUTH_NONE and AUTH_SYS credentials, and rejects all other flavors. The user
+/// and group ids of an AUTH_SYS credential are trusted as they are. Call verifiers are not checked,
+/// and replies carry an AUTH_NONE verifier.
+#[derive(Default)]
+pub struct SysAuthenticator;
+
+impl Authenticator for SysAuthenticator {
+    fn authenticate(&mut self, call: &Call) -> Result<Authenticated, AuthError> {
+        let credential = call.get_credential();
+        let principal = match credential.flavor {
+            AuthFlavor::None => Principal::Anonymous,
+            AuthFlavor::Sys => {
+                let mut params = AuthSysParms::default();
+                let mut body = credential.body.as_slice();
+                if params.deserialize(&mut body).is_err() || !body.is_empty() {
+                    debug!("CALL with malformed AUTH_SYS credential");
+                    return Err(AuthError::BadCred);
+                }
+
+                Principal::Unix {
+                    uid: params.uid,
+                    gid: params.gid,
+                    gids: params.gids,
+                }
+            }
+            _ => {
+                debug!("CALL with unsupported auth: {:?}", credential);
+                return Err(AuthError::RejectedCred);
+            }
+        };
+
+        Ok(Authenticated {
+            principal,
+            verifier: OpaqueAuth::none(),
+        })
+    }
+}
+
+/// Authenticate `call` with `authenticator`, recording the caller in the call. Returns the verifier
+/// for the reply, or the AUTH_ERROR reply to send if the call is rejected.
+pub fn authenticate(
+    call: &mut Call,
+    authenticator: &mut dyn Authenticator,
+) -> Result<OpaqueAuth, Error> {
+    match authenticator.authenticate(call) {
+        Ok(Authenticated {
+            principal,
+            verifier,
+        }) => {
+            call.principal = principal;
+            Ok(verifier)
+        }
+        Err(e) => {
+            debug!("CALL rejected by authenticator: {e}");
+            Err(crate::Error::Rpc(ReplyBody::auth_error(e)))
+        }
+    }
+}
+
 /// An RPC Service is defined by its program and version numbers, and a map from procedure numbers
 /// to the actual procedures which implement them. The private state is shared by each procedure
 /// implementation in the service.
@@ -42,32 +335,267 @@ pub struct RpcProgram<T> {
     version_max: u32,
 
     /// The mapping of procedure numbers to functions that implement the procedures.
-    /// The 0th element of this array is ignored because it is always mapped to the NULL procedure.
+    /// If the 0th element of this array is None, the NULL procedure is answered without calling
+    /// into the service; a service that wants to see NULL calls can implement it here instead.
     /// This structure assumes that al the versions between version_min and version_max share the
     /// same procedures. If that assumption should turn false in the future, this structure will
     /// have to be modified.
     procedures: Vec<Option<RpcProcedure<T>>>,
 
+    /// Procedures that read their arguments from the connection, indexed like `procedures`. These
+    /// take precedence over `procedures`.
+    streaming_procedures: Vec<Option<StreamingProcedure<T>>>,
+
+    /// Used to name procedures in log messages, if set.
+    procedure_names: Option<ProcedureNames>,
+
+    /// Checks the credential of each call.
+    authenticator: Box<dyn Authenticator + Send>,
+
+    /// Where each call is counted, if anywhere.
+    metrics: Option<Metrics>,
+
+    /// Where each call and reply is traced, while it is started.
+    tracer: Option<Tracer>,
+
+    /// If set, each reply is checked with `check_reply()` before it is sent.
+    reply_check: Option<ResultCheck>,
+
+    /// Limits on the records that calls are received in.
+    record_limits: RecordLimits,
+
+    /// The timeouts applied to the connections that `run_blocking_tcp_server()` accepts.
+    timeouts: ConnectionTimeouts,
+
+    /// Limits on the calls of each client, and of each class of calls if there is a classifier.
+    throttle: Option<(Throttle, Option<Classifier<T>>)>,
+
+    /// Whether clients may start TLS on their connections, and must.
+    #[cfg(feature = "tls")]
+    tls: Option<crate::tls::TlsPolicy>,
+
     /// The RPC service implementation can use this field to store state that must be maintained
     /// across RPC calls.
     private_state: T,
 }
 
+/// Listen for TCP connections on `address`.
+///
+/// A listener on the IPv6 wildcard address ("[::]") is made dual-stack, accepting IPv4 connections
+/// as well, whatever the system default (net.ipv6.bindv6only) is. Any other address is bound as
+/// with `TcpListener::bind()`.
+pub fn bind_tcp(address: std::net::SocketAddr) -> std::io::Result<std::net::TcpListener> {
+    use nix::sys::socket::*;
+    use std::{net::SocketAddr, os::fd::AsRawFd};
+
+    let SocketAddr::V6(v6) = address else {
+        return std::net::TcpListener::bind(address);
+    };
+    if !v6.ip().is_unspecified() {
+        return std::net::TcpListener::bind(address);
+    }
+
+    let fd = socket(
+        AddressFamily::Inet6,
+        SockType::Stream,
+        SockFlag::SOCK_CLOEXEC,
+        None,
+    )?;
+    setsockopt(&fd, sockopt::ReuseAddr, &true)?;
+    setsockopt(&fd, sockopt::Ipv6V6Only, &false)?;
+    bind(fd.as_raw_fd(), &SockaddrIn6::from(v6))?;
+    listen(&fd, Backlog::MAXCONN)?;
+
+    Ok(fd.into())
+}
+
 /// A trait that allows functions to be generic over both TcpListener and UnixListener.
 pub trait Listener<S> {
     fn accept(&self) -> std::io::Result<S>;
+
+    /// The credentials of the process that connected with `stream`, if the transport has a way of
+    /// knowing them.
+    fn peer_credentials(&self, _stream: &S) -> Option<PeerCredentials> {
+        None
+    }
+
+    /// The address of the peer at the other end of `stream`, if it is on an IP network.
+    fn peer_address(&self, _stream: &S) -> Option<std::net::SocketAddr> {
+        None
+    }
+
+    /// Apply `timeouts` to `stream`, as far as the transport has them.
+    fn set_timeouts(&self, _stream: &S, _timeouts: &ConnectionTimeouts) -> std::io::Result<()> {
+        Ok(())
+    }
 }
 
 impl Listener<std::net::TcpStream> for std::net::TcpListener {
     fn accept(&self) -> std::io::Result<std::net::TcpStream> {
         Ok(self.accept()?.0)
     }
+
+    fn peer_address(&self, stream: &std::net::TcpStream) -> Option<std::net::SocketAddr> {
+        stream.peer_addr().ok()
+    }
+
+    fn set_timeouts(
+        &self,
+        stream: &std::net::TcpStream,
+        timeouts: &ConnectionTimeouts,
+    ) -> std::io::Result<()> {
+        timeouts.apply(stream)
+    }
 }
 
 impl Listener<std::os::unix::net::UnixStream> for std::os::unix::net::UnixListener {
     fn accept(&self) -> std::io::Result<std::os::unix::net::UnixStream> {
         Ok(self.accept()?.0)
     }
+
+    fn peer_credentials(&self, stream: &std::os::unix::net::UnixStream) -> Option<PeerCredentials> {
+        use nix::sys::socket::{getsockopt, sockopt};
+
+        match getsockopt(stream, sockopt::PeerCredentials) {
+            Ok(creds) => Some(PeerCredentials {
+                pid: creds.pid(),
+                uid: creds.uid(),
+                gid: creds.gid(),
+            }),
+            Err(e) => {
+                warn!("Could not get the credentials of a UNIX socket peer: {e}");
+                None
+            }
+        }
+    }
+
+    fn set_timeouts(
+        &self,
+        stream: &std::os::unix::net::UnixStream,
+        timeouts: &ConnectionTimeouts,
+    ) -> std::io::Result<()> {
+        // There is no keepalive on UNIX domain sockets, whose peers cannot vanish unnoticed:
+        let timeouts = ConnectionTimeouts {
+            keepalive: None,
+            ..*timeouts
+        };
+        timeouts.apply(stream)
+    }
+}
+
+/// How long a connection may go without progress before the server closes it, and whether TCP
+/// keepalive probes are sent on it, so that a client that goes silent, or away, does not hold on to
+/// the server's resources for good. None of them is set by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConnectionTimeouts {
+    /// How long the server waits for the next bytes of a call, after which the connection is closed
+    /// as idle. This is also the grace period for the first call on a new connection.
+    pub read: Option<Duration>,
+
+    /// How long a reply may take to be sent, after which the connection is closed, since the
+    /// client is not reading its replies.
+    pub write: Option<Duration>,
+
+    /// How long a connection may be idle before TCP keepalive probes are sent on it, which end
+    /// the connection if the client's host is gone.
+    pub keepalive: Option<Duration>,
+}
+
+impl ConnectionTimeouts {
+    /// Set the timeouts of the connected socket `socket`: its receive and send timeouts, which make
+    /// blocking reads and writes on it fail once they pass, and its keepalive.
+    pub fn apply(&self, socket: &impl AsFd) -> io::Result<()> {
+        use nix::sys::{
+            socket::{setsockopt, sockopt},
+            time::TimeVal,
+        };
+
+        let timeval =
+            |timeout: Duration| TimeVal::new(timeout.as_secs() as _, timeout.subsec_micros() as _);
+        if let Some(read) = self.read {
+            setsockopt(socket, sockopt::ReceiveTimeout, &timeval(read))?;
+        }
+        if let Some(write) = self.write {
+            setsockopt(socket, sockopt::SendTimeout, &timeval(write))?;
+        }
+        if let Some(keepalive) = self.keepalive {
+            setsockopt(socket, sockopt::KeepAlive, &true)?;
+            let idle = keepalive.as_secs().clamp(1, i32::MAX as u64) as u32;
+            setsockopt(socket, sockopt::TcpKeepIdle, &idle)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Who may make the calls that a program restricts, when they are made over a UNIX domain socket:
+/// root, the user that the server runs as, and the members of `group`, if there is one.
+#[derive(Clone, Debug, Default)]
+pub struct PeerPolicy {
+    pub group: Option<u32>,
+}
+
+impl PeerPolicy {
+    /// A policy that also admits the members of the group called `name`.
+    pub fn for_group(name: &str) -> std::io::Result<Self> {
+        match nix::unistd::Group::from_name(name)? {
+            Some(group) => Ok(Self {
+                group: Some(group.gid.as_raw()),
+            }),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no group called \"{name}\""),
+            )),
+        }
+    }
+
+    /// Whether `peer` is admitted: by its user id, its primary group, or the supplementary groups
+    /// of its user.
+    pub fn allows(&self, peer: &PeerCredentials) -> bool {
+        use nix::unistd::{geteuid, getgrouplist, Gid, Uid, User};
+
+        if peer.uid == 0 || peer.uid == geteuid().as_raw() {
+            return true;
+        }
+        let Some(group) = self.group else {
+            return false;
+        };
+        if peer.gid == group {
+            return true;
+        }
+
+        let Ok(Some(user)) = User::from_uid(Uid::from_raw(peer.uid)) else {
+            return false;
+        };
+        let Ok(name) = std::ffi::CString::new(user.name) else {
+            return false;
+        };
+        getgrouplist(&name, Gid::from_raw(peer.gid))
+            .is_ok_and(|groups| groups.contains(&Gid::from_raw(group)))
+    }
+
+    /// Decide whether `call` may do `action`, and record the decision in the audit log (the
+    /// "audit" log target) along with who made the call.
+    ///
+    /// Only calls made over a UNIX domain socket carry the credentials of the caller. Any other
+    /// call is allowed, and it is up to the program to decide which transports to offer a
+    /// restricted procedure on.
+    pub fn check(&self, call: &Call, action: fmt::Arguments) -> bool {
+        match call.get_peer() {
+            Some(peer) if self.allows(peer) => {
+                info!(target: "audit", "{action}: allowed for {peer}");
+                true
+            }
+            Some(peer) => {
+                warn!(target: "audit", "{action}: refused for {peer}");
+                false
+            }
+            None => {
+                info!(target: "audit", "{action}: allowed for remote caller {:?}", call.get_principal());
+                true
+            }
+        }
+    }
 }
 
 impl<T> RpcProgram<T> {
@@ -83,16 +611,96 @@ impl<T> RpcProgram<T> {
             version_min,
             version_max,
             procedures,
+            streaming_procedures: Vec::new(),
+            procedure_names: None,
+            authenticator: Box::new(SysAuthenticator),
+            metrics: None,
+            tracer: None,
+            reply_check: None,
+            record_limits: RecordLimits::default(),
+            timeouts: ConnectionTimeouts::default(),
+            throttle: None,
+            #[cfg(feature = "tls")]
+            tls: None,
             private_state,
         }
     }
 
+    /// Use `names` to refer to procedures by name, rather than by number, in log messages.
+    pub fn set_procedure_names(&mut self, names: ProcedureNames) {
+        self.procedure_names = Some(names);
+    }
+
+    /// Authenticate calls with `authenticator`, instead of the default `SysAuthenticator`.
+    pub fn set_authenticator(&mut self, authenticator: impl Authenticator + Send + 'static) {
+        self.authenticator = Box::new(authenticator);
+    }
+
+    /// Count each call in `metrics`.
+    pub fn set_metrics(&mut self, metrics: Metrics) {
+        self.metrics = Some(metrics);
+    }
+
+    /// Write each call and reply to `tracer`, while it is started.
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Check each reply with `check_reply()` before sending it, logging an error for any that is
+    /// malformed. This is for debugging, since it decodes and encodes every reply again.
+    pub fn set_reply_check(&mut self, check: ResultCheck) {
+        self.reply_check = Some(check);
+    }
+
+    /// Refuse calls that exceed `limits`, in place of the default `RecordLimits`, by ending their
+    /// connections.
+    pub fn set_record_limits(&mut self, limits: RecordLimits) {
+        self.record_limits = limits;
+    }
+
+    /// Close connections that go without progress as `timeouts` say, and keep them alive with TCP
+    /// keepalive if it says to. These apply to the connections that `run_blocking_tcp_server()`
+    /// accepts; a stream handed to `handle_connection()` is expected to have them applied already,
+    /// such as with `ConnectionTimeouts::apply()`.
+    pub fn set_connection_timeouts(&mut self, timeouts: ConnectionTimeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Answer calls no faster than `throttle` allows, for each client, and for each class of calls
+    /// that `classify` finds, if given (see `rpc_protocol::throttle`). A call over the limits is
+    /// answered once it may be, which holds up the connection it came on, and since calls are
+    /// answered on one connection at a time, every other connection too.
+    pub fn set_throttle(&mut self, throttle: Throttle, classify: Option<Classifier<T>>) {
+        self.throttle = Some((throttle, classify));
+    }
+
+    /// Let clients start TLS on their connections, as `policy` says (see `rpc_protocol::tls`).
+    #[cfg(feature = "tls")]
+    pub fn set_tls(&mut self, policy: crate::tls::TlsPolicy) {
+        self.tls = Some(policy);
+    }
+
+    /// Implement procedure number `proc` with a streaming procedure, in place of any procedure
+    /// given for it to `new()`.
+    pub fn set_streaming_procedure(&mut self, proc: u32, procedure: StreamingProcedure<T>) {
+        let proc = proc as usize;
+        if self.streaming_procedures.len() <= proc {
+            self.streaming_procedures.resize(proc + 1, None);
+        }
+        self.streaming_procedures[proc] = Some(procedure);
+    }
+
     /// Run a blocking TCP server for this RPC service using the given Listener.
     pub fn run_blocking_tcp_server<S: Read + Write>(&mut self, listener: impl Listener<S>) {
         loop {
             match listener.accept() {
                 Ok(stream) => {
-                    let _ = self.handle_connection(stream);
+                    if let Err(e) = listener.set_timeouts(&stream, &self.timeouts) {
+                        warn!("Could not set the timeouts of a connection: {e}");
+                    }
+                    let peer = listener.peer_credentials(&stream);
+                    let address = listener.peer_address(&stream);
+                    let _ = self.handle_connection_from(stream, peer, address);
                 }
                 Err(e) => warn!("Error accepting connection: {e}"),
             }
@@ -102,42 +710,348 @@ impl<T> RpcProgram<T> {
     /// Tries to handle a given stream by reading a series of RPC Call messages from it, and
     /// passing those calls off to the appropriate implementation function to handle. If any errors
     /// are encountered, the function returns and the stream is dropped.
-    pub fn handle_connection<S: Read + Write>(
+    ///
+    /// The call header is decoded as it is read from the stream. The argument is then either read
+    /// into a buffer for the procedure, or for a streaming procedure, read by the procedure itself.
+    pub fn handle_connection<S: Read + Write>(&mut self, stream: S) -> Result<(), crate::Error> {
+        self.handle_connection_from(stream, None, None)
+    }
+
+    /// Like `handle_connection()`, for a connection from the process described by `peer`, or from
+    /// `address`, which procedures find with `Call::get_peer()` and `Call::get_peer_address()`.
+    pub fn handle_connection_from<S: Read + Write>(
         &mut self,
         mut stream: S,
+        peer: Option<PeerCredentials>,
+        address: Option<std::net::SocketAddr>,
     ) -> Result<(), crate::Error> {
+        // What has been counted of the connection's records, in all, and since the counts were
+        // last added to the metrics:
+        let mut records = RecordStats::default();
+        let mut uncounted = RecordStats::default();
+
+        let res = self.serve_connection(
+            &mut stream,
+            peer,
+            address,
+            false,
+            &mut records,
+            &mut uncounted,
+        );
+        #[cfg(feature = "tls")]
+        let res = match res {
+            Ok(Ended::StartTls) => {
+                self.serve_tls(stream, peer, address, &mut records, &mut uncounted)
+            }
+            res => res,
+        };
+        let res = res.map(|_| ());
+        self.count_records(&mut records, &mut uncounted);
+
+        // A connection that goes without progress past the timeouts fails to read or write:
+        if let Err(Error::Io(e)) = &res {
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) {
+                debug!("Closing a connection that timed out: {e}");
+            }
+        }
+
+        if records.is_abnormal() {
+            let ending = match &res {
+                Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::InvalidData => {
+                    format!("refused a {e}")
+                }
+                _ => "ended".to_string(),
+            };
+            warn!(
+                "Connection {}{ending} after {} records in {} fragments, with {} tiny fragments \
+                 and {} incomplete records",
+                peer.map(|peer| format!("from {peer} "))
+                    .or(address.map(|address| format!("from {address} ")))
+                    .unwrap_or_default(),
+                records.records,
+                records.fragments,
+                records.tiny_fragments,
+                records.incomplete
+            );
+        } else {
+            debug!("Connection ended after {records:?}");
+        }
+
+        res
+    }
+
+    /// Complete the TLS handshake that a client began on `stream` after its probe was answered,
+    /// and answer the calls it sends through TLS.
+    #[cfg(feature = "tls")]
+    fn serve_tls<S: Read + Write>(
+        &mut self,
+        mut stream: S,
+        peer: Option<PeerCredentials>,
+        address: Option<std::net::SocketAddr>,
+        records: &mut RecordStats,
+        uncounted: &mut RecordStats,
+    ) -> Result<Ended, crate::Error> {
+        use rustls::{ServerConnection, StreamOwned};
+
+        let Some(policy) = &self.tls else {
+            return Ok(Ended::Closed);
+        };
+        let mut connection = ServerConnection::new(policy.config.clone())
+            .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+        while connection.is_handshaking() {
+            connection.complete_io(&mut stream).inspect_err(|e| {
+                debug!("TLS handshake failed: {e}");
+            })?;
+        }
+        debug!("Started TLS");
+
+        let mut stream = StreamOwned::new(connection, stream);
+        self.serve_connection(&mut stream, peer, address, true, records, uncounted)
+    }
+
+    /// Answer the calls of a connection, which is through TLS if `tls` is set, until it ends or a
+    /// call cannot be answered. `uncounted` counts its records, and is added to `records` after each
+    /// call. Returns early, with `Ended::StartTls`, once a client's probe to start TLS has been
+    /// answered.
+    fn serve_connection<S: Read + Write>(
+        &mut self,
+        stream: &mut S,
+        peer: Option<PeerCredentials>,
+        address: Option<std::net::SocketAddr>,
+        tls: bool,
+        records: &mut RecordStats,
+        uncounted: &mut RecordStats,
+    ) -> Result<Ended, crate::Error> {
+        // Holds the argument of a call to a procedure that is not streaming. It is reused for every
+        // call on the connection, so it is only as large as the largest argument sent on it:
+        let mut arg = Vec::new();
+
+        // The connection's number in traces, and a copy of each call while tracing is started:
+        let connection = self.tracer.as_ref().map(Tracer::new_connection);
+        let mut received = Vec::new();
+
         loop {
-            let message_length = stream_record_mark(&mut stream)?;
-            trace!("got message with record mark: {message_length}");
+            // The header of the call's first fragment is always copied, since tracing may be
+            // started while waiting for it:
+            received.clear();
+            let limits = self.record_limits;
+            let recorder = Recorder::new(&mut *stream, Some(&mut received));
+            let mut record = Record::begin(recorder, &limits, uncounted)?;
+            trace!("got record of {} bytes", record.size());
+            if !self.tracer.as_ref().is_some_and(Tracer::is_started) {
+                record.get_mut().stop_copying();
+            }
 
-            let mut buf = vec![0; message_length as usize];
-            stream
-                .read_exact(&mut buf)
-                .inspect_err(|e| warn!("Error reading message from stream: {e}"))?;
+            arg.clear();
+            let mut call = read_call_header(&mut record).map_err(Error::Protocol)?;
+            call.peer = peer;
+            call.address = address;
+            call.tls = tls;
+            let start = Instant::now();
+            let span = call_span(self.procedure_names, &call);
+            let _entered = span.enter();
 
-            let call = match decode_call(&buf) {
-                Ok(call) => call,
-                Err(e) => return Err(Error::Protocol(e)),
+            // A probe to start TLS is answered without being authenticated, since its credential
+            // only asks for TLS:
+            let probe = self.tls_probe(&call);
+            let starts_tls = probe.is_some();
+            let authenticated = match probe {
+                Some(verifier) => Ok(verifier),
+                None => self
+                    .check_tls(&call)
+                    .and_then(|()| authenticate(&mut call, self.authenticator.as_mut())),
+            };
+            let verifier = match authenticated {
+                Ok(verifier) => verifier,
+                Err(e) => {
+                    if let Error::Rpc(reply) = e {
+                        let bytes_in = record.size();
+                        let mut reply = ReplyParts::from(encode_reply_no_arg(call.xid, reply));
+                        self.check(&call, &mut reply);
+                        self.trace(connection, &received, &mut reply);
+                        reply.write_to(stream)?;
+                        self.record(&call, bytes_in, reply.size(), true, start);
+                    }
+
+                    return Ok(Ended::Closed);
+                }
             };
 
             let procedure = match self.validate_call(&call) {
                 Ok(proc) => proc,
                 Err(e) => {
                     if let Error::Rpc(reply) = e {
-                        send_reply_no_arg(&mut stream, call.xid, reply)?;
+                        let bytes_in = record.size();
+                        let mut reply = ReplyParts::from(encode_reply_no_arg(call.xid, reply));
+                        self.check(&call, &mut reply);
+                        self.trace(connection, &received, &mut reply);
+                        reply.write_to(stream)?;
+                        self.record(&call, bytes_in, reply.size(), true, start);
                     }
 
-                    return Ok(());
+                    return Ok(Ended::Closed);
+                }
+            };
+
+            debug!("calling {}", procedure_label(self.procedure_names, &call));
+            let (res, bytes_in, class, admitted) = match procedure {
+                Procedure::Buffered(procedure) => {
+                    record.read_to_end(&mut arg)?;
+                    let bytes_in = record.finish()?;
+
+                    call.arg = &arg;
+                    let class = self.admit(&call, bytes_in);
+                    let res = procedure(&call, &mut self.private_state);
+
+                    (res, bytes_in, class, bytes_in)
+                }
+                Procedure::Streaming(procedure) => {
+                    // The argument is only read by the procedure, so the call is admitted by what
+                    // has been read of it, and the rest is charged along with the reply:
+                    let admitted = record.size();
+                    let class = self.admit(&call, admitted);
+                    let res = procedure(&call, &mut record, &mut self.private_state);
+                    let bytes_in = record.finish()?;
+
+                    (res, bytes_in, class, admitted)
                 }
             };
 
-            let res = procedure(&call, &mut self.private_state);
+            let error = !res.is_success();
+            let mut reply = res.encode_reply_parts(call.xid, verifier);
+            self.check(&call, &mut reply);
+            self.trace(connection, &received, &mut reply);
+            let sent = reply.write_to(stream);
+            self.record(&call, bytes_in, reply.size(), error, start);
+            self.charge(&call, class.as_ref(), bytes_in - admitted + reply.size());
+            self.count_records(records, uncounted);
+
+            // The client cannot tell where the next reply would start after one that was cut short,
+            // such as by the write timeout:
+            sent?;
+
+            if starts_tls && !error {
+                return Ok(Ended::StartTls);
+            }
+        }
+    }
+
+    /// If `call` is a probe to start TLS (see `rpc_protocol::tls`), and this program offers TLS on
+    /// the call's connection, the verifier with which to agree to it.
+    #[cfg(feature = "tls")]
+    fn tls_probe(&self, call: &Call) -> Option<OpaqueAuth> {
+        let probe = self.tls.is_some()
+            && !call.tls
+            && call.get_credential().flavor == AuthFlavor::Tls
+            && call.get_procedure() == 0;
+
+        probe.then(crate::tls::starttls_verifier)
+    }
+
+    #[cfg(not(feature = "tls"))]
+    fn tls_probe(&self, _call: &Call) -> Option<OpaqueAuth> {
+        None
+    }
+
+    /// Refuse `call` with AUTH_TOOWEAK if this program requires TLS and the call was not received
+    /// through it. NULL calls are answered either way, so that clients can still find the server.
+    #[cfg(feature = "tls")]
+    fn check_tls(&self, call: &Call) -> Result<(), Error> {
+        if self.tls.as_ref().is_some_and(|tls| tls.required)
+            && !call.tls
+            && call.get_procedure() != 0
+        {
+            debug!(
+                "Refusing {}, which was not sent through TLS",
+                procedure_label(self.procedure_names, call)
+            );
+            return Err(Error::Rpc(ReplyBody::auth_error(AuthError::TooWeak)));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "tls"))]
+    fn check_tls(&self, _call: &Call) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Wait until the throttle, if there is one, allows `call`, of `bytes` bytes, and take it from
+    /// the throttle's allowances. Returns the class of the call.
+    fn admit(&self, call: &Call, bytes: usize) -> Option<Class> {
+        let (throttle, classify) = self.throttle.as_ref()?;
+
+        let class = classify.and_then(|classify| classify(call, &self.private_state));
+        let client = call.get_peer_address().map(|address| address.ip());
+        while let Err(wait) = throttle.admit(client, class.as_ref(), bytes, Instant::now()) {
+            debug!("Delaying the call for {wait:?}, over the throttle's limits");
+            std::thread::sleep(wait);
+        }
+
+        class
+    }
+
+    /// Take `bytes`, such as those of the reply to `call`, of `class`, from the throttle's
+    /// allowances.
+    fn charge(&self, call: &Call, class: Option<&Class>, bytes: usize) {
+        if let Some((throttle, _)) = &self.throttle {
+            let client = call.get_peer_address().map(|address| address.ip());
+            throttle.charge(client, class, bytes, Instant::now());
+        }
+    }
+
+    /// Add the counts of records in `uncounted` to those of the connection, and to the metrics.
+    fn count_records(&self, records: &mut RecordStats, uncounted: &mut RecordStats) {
+        if let Some(metrics) = &self.metrics {
+            metrics.count_records(uncounted);
+        }
+        records.add(uncounted);
+        *uncounted = RecordStats::default();
+    }
+
+    fn check(&self, call: &Call, reply: &mut ReplyParts) {
+        if let Some(check) = self.reply_check {
+            if let Err(e) = check_reply(call, reply.whole(), Some(check)) {
+                let label = procedure_label(self.procedure_names, call);
+                error!(
+                    "Malformed reply to {label} (xid {:#x}): {e}",
+                    call.get_xid()
+                );
+            }
+        }
+    }
+
+    /// Trace a call, as much of it as was read, and its reply, if tracing is started.
+    fn trace(&self, connection: Option<u64>, call: &[u8], reply: &mut ReplyParts) {
+        if let (Some(tracer), Some(connection)) = (&self.tracer, connection) {
+            // Tracing may have been started partway through the call, which was not copied then:
+            if !call.is_empty() {
+                tracer.trace(connection, Direction::Received, call);
+                tracer.trace(connection, Direction::Sent, reply.whole());
+            }
+        }
+    }
 
-            let _ = match res {
-                RpcResult::Success(data) => send_succesful_reply(&mut stream, call.xid, &data),
-                // can reply with either GARBAGE_ARGS, SYSTEM_ERR, or SUCCESS
-                _ => todo!(),
+    fn record(
+        &self,
+        call: &Call,
+        bytes_in: usize,
+        bytes_out: usize,
+        error: bool,
+        received: Instant,
+    ) {
+        log_reply(bytes_in, bytes_out, error, received);
+        if let Some(metrics) = &self.metrics {
+            let sample = Sample {
+                bytes_in,
+                bytes_out,
+                error,
+                latency: received.elapsed(),
             };
+            metrics.record(call, self.procedure_names, sample);
         }
     }
 
@@ -145,29 +1059,170 @@ impl<T> RpcProgram<T> {
     /// procedure which implements that call.
     ///
     /// Otherwise, returns the appropiate kind of error.
-    fn validate_call(&self, call: &Call) -> Result<RpcProcedure<T>, Error> {
+    fn validate_call(&self, call: &Call) -> Result<Procedure<T>, Error> {
         validate_program_and_version(call, self.program, self.version_min, self.version_max)?;
 
-        let procedure_number = call.get_procedure();
+        let procedure_number = call.get_procedure() as usize;
 
-        if procedure_number == 0 {
-            return Ok(null_procedure);
+        if let Some(Some(procedure)) = self.streaming_procedures.get(procedure_number) {
+            return Ok(Procedure::Streaming(*procedure));
         }
 
-        if procedure_number as usize > self.procedures.len() - 1 {
-            debug!("CALL for unknown procedure {}", procedure_number);
-            let reply = ReplyBody::accepted_reply(AcceptedReplyBody::ProcUnavail);
-            return Err(crate::Error::Rpc(reply));
+        match self.procedures.get(procedure_number) {
+            Some(Some(procedure)) => Ok(Procedure::Buffered(*procedure)),
+            // Unless the service implements it, the NULL procedure does nothing:
+            _ if procedure_number == 0 => Ok(Procedure::Buffered(null_procedure)),
+            None => {
+                debug!(
+                    "CALL for unknown {}",
+                    procedure_label(self.procedure_names, call)
+                );
+                let reply = ReplyBody::accepted_reply(AcceptedReplyBody::ProcUnavail);
+                Err(crate::Error::Rpc(reply))
+            }
+            Some(None) => {
+                debug!(
+                    "CALL for unimplemented {}",
+                    procedure_label(self.procedure_names, call)
+                );
+                let reply = ReplyBody::accepted_reply(AcceptedReplyBody::ProcUnavail);
+                Err(crate::Error::Rpc(reply))
+            }
         }
+    }
+}
 
-        let Some(procedure) = self.procedures[procedure_number as usize] else {
-            debug!("CALL for unimplemented procedure {}", procedure_number);
-            let reply = ReplyBody::accepted_reply(AcceptedReplyBody::ProcUnavail);
-            return Err(crate::Error::Rpc(reply));
+/// How `serve_connection()` stopped answering the calls of a connection.
+enum Ended {
+    /// The connection ended, or was ended.
+    Closed,
+
+    /// The client's probe to start TLS was answered, and the handshake comes next.
+    StartTls,
+}
+
+/// Reads from a stream, copying what it reads into a buffer, if given one.
+struct Recorder<'a, S> {
+    stream: S,
+    copy: Option<&'a mut Vec<u8>>,
+}
+
+impl<'a, S> Recorder<'a, S> {
+    fn new(stream: S, copy: Option<&'a mut Vec<u8>>) -> Self {
+        Self { stream, copy }
+    }
+
+    /// Stop copying, and forget what was copied.
+    fn stop_copying(&mut self) {
+        if let Some(copy) = self.copy.take() {
+            copy.clear();
+        }
+    }
+}
+
+impl<S: Read> Read for Recorder<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.stream.read(buf)?;
+        if let Some(copy) = &mut self.copy {
+            copy.extend_from_slice(&buf[..n]);
+        }
+
+        Ok(n)
+    }
+}
+
+/// Check that `reply`, an encoded reply to `call` with its record mark, is what a client expects:
+/// that the record mark gives the length of the message, and that the reply header, and the result
+/// of a succesful call, are canonically encoded, which means that they are a multiple of 4 bytes long
+/// and that their padding is zero. The result is decoded as the procedure's result type if
+/// `check_result` knows it, and otherwise only its length is checked.
+pub fn check_reply(
+    call: &Call,
+    reply: &[u8],
+    check_result: Option<ResultCheck>,
+) -> Result<(), String> {
+    let Some((mark, message)) = reply.split_first_chunk::<4>() else {
+        return Err(format!(
+            "{} bytes is too short for a record mark",
+            reply.len()
+        ));
+    };
+    let len = decode_record_mark(mark).map_err(|e| e.to_string())?;
+    if len as usize != message.len() {
+        return Err(format!(
+            "the record mark gives {len} bytes, but the message is {} bytes",
+            message.len()
+        ));
+    }
+
+    let mut header = RpcMessage::default();
+    let mut result = message;
+    if header.deserialize(&mut result).is_err() {
+        return Err("the header does not decode".to_string());
+    }
+    xdr_lib::check_encoding(&message[..message.len() - result.len()], |input| {
+        let mut header = RpcMessage::default();
+        header.deserialize(input)?;
+        Ok(header.serialize_alloc())
+    })
+    .map_err(|e| format!("the header {e}"))?;
+
+    let success = matches!(
+        header.body,
+        RpcMessageBody::Reply(ReplyBody::Accepted(AcceptedReply {
+            reply_data: AcceptedReplyBody::Success(_),
+            ..
+        }))
+    );
+    if !success {
+        return match result.len() {
+            0 => Ok(()),
+            n => Err(format!("{n} bytes follow a reply that carries no result")),
         };
+    }
 
-        Ok(procedure)
+    let checked =
+        check_result.and_then(|check| check(call.get_version(), call.get_procedure(), result));
+    match checked {
+        Some(res) => res,
+        None if !result.len().is_multiple_of(4) => {
+            Err(xdr_lib::EncodingError::Unaligned(result.len()))
+        }
+        None => Ok(()),
     }
+    .map_err(|e| format!("the result {e}"))
+}
+
+/// Describe the procedure that `call` is for, by name if `names` knows it.
+pub fn procedure_label(names: Option<ProcedureNames>, call: &Call) -> String {
+    match names.and_then(|names| names(call.get_version(), call.get_procedure())) {
+        Some(name) => name.to_string(),
+        None => format!("procedure {}", call.get_procedure()),
+    }
+}
+
+/// A span of the handling of `call`, in which the server, and the procedure it calls, log what
+/// happens, along with the call's XID, program, version, procedure, and client.
+pub fn call_span(names: Option<ProcedureNames>, call: &Call) -> tracing::Span {
+    tracing::info_span!(
+        "call",
+        xid = call.get_xid(),
+        prog = call.get_program(),
+        vers = call.get_version(),
+        proc = %procedure_label(names, call),
+        client = call.get_peer_address().map(tracing::field::display),
+    )
+}
+
+/// Log that the reply to a call, received at `received`, was sent, in the call's span.
+pub fn log_reply(bytes_in: usize, bytes_out: usize, error: bool, received: Instant) {
+    tracing::debug!(
+        latency_us = received.elapsed().as_micros() as u64,
+        bytes_in,
+        bytes_out,
+        error,
+        "replied"
+    );
 }
 
 pub fn validate_program_and_version(
@@ -176,44 +1231,43 @@ pub fn validate_program_and_version(
     version_min: u32,
     version_max: u32,
 ) -> Result<(), Error> {
-    // This implementation currently only supports auth styles "None" and "Sys":
-    let credential = call.get_credential();
-
-    match credential.flavor {
-        AuthFlavor::None => {}
-        AuthFlavor::Sys => {}
-        _ => {
-            debug!("CALL with unsupported auth: {:?}", credential);
-            let reply = ReplyBody::Denied(RejectedReply::AuthError(AuthStat::RejectedCred));
+    let peek = CallPeek {
+        xid: call.get_xid(),
+        prog: call.get_program(),
+        vers: call.get_version(),
+        proc: call.get_procedure(),
+    };
+
+    peek.validate(program, version_min, version_max)
+}
+
+impl CallPeek {
+    /// Check that the call is for `program`, in a version from `version_min` to `version_max`, as
+    /// `validate_program_and_version()` does, before the call is decoded.
+    pub fn validate(&self, program: u32, version_min: u32, version_max: u32) -> Result<(), Error> {
+        if self.prog != program {
+            debug!("CALL for unknown program {}", self.prog);
+            let reply = ReplyBody::accepted_reply(AcceptedReplyBody::ProgUnavail);
             return Err(crate::Error::Rpc(reply));
         }
-    };
 
-    let call_prog = call.get_program();
-    if call_prog != program {
-        debug!("CALL for unknown program {}", call_prog);
-        let reply = ReplyBody::accepted_reply(AcceptedReplyBody::ProgUnavail);
-        return Err(crate::Error::Rpc(reply));
-    }
+        if self.vers < version_min || self.vers > version_max {
+            debug!("CALL for unknown version {}", self.vers);
+            let reply =
+                ReplyBody::accepted_reply(AcceptedReplyBody::ProgMismatch(ProgMismatchBody {
+                    low: version_min,
+                    high: version_max,
+                }));
+            return Err(crate::Error::Rpc(reply));
+        }
 
-    let version = call.get_version();
-    if version < version_min || version > version_max {
-        debug!("CALL for unknown version {}", version);
-        let reply = ReplyBody::accepted_reply(AcceptedReplyBody::ProgMismatch(ProgMismatchBody {
-            low: version_min,
-            high: version_max,
-        }));
-        return Err(crate::Error::Rpc(reply));
+        Ok(())
     }
-    Ok(())
 }
 
-/// Write a reply to the stream without encoding any procedure result (for example, an error reply).
-fn send_reply_no_arg<S: Read + Write>(
-    stream: &mut S,
-    xid: u32,
-    reply_data: ReplyBody,
-) -> Result<(), crate::Error> {
+/// Encode a reply that carries no procedure result (for example, an error reply), with its record
+/// mark.
+pub fn encode_reply_no_arg(xid: u32, reply_data: ReplyBody) -> Vec<u8> {
     let message = RpcMessage {
         xid,
         body: RpcMessageBody::Reply(reply_data),
@@ -223,9 +1277,7 @@ fn send_reply_no_arg<S: Read + Write>(
     buf.append(&mut message.serialize_alloc());
     crate::update_record_mark(&mut buf);
 
-    stream.write_all(&buf)?;
-
-    Ok(())
+    buf
 }
 
 impl ReplyBody {
@@ -235,30 +1287,26 @@ impl ReplyBody {
             reply_data,
         })
     }
-}
 
-/// Given the reply body, encode it and send it on the given TcpStream.
-///
-/// XXX: can the protocol definition be adjusted so that AcceptedReplyBody::Success(_) holds
-/// arg instead of needing to split out arg into a separate Option?
-///
-/// TODO: currently hard-coded to use auth "None"--this will have to be updated to use the
-/// correct kind of auth based on the call.
-fn send_succesful_reply<S: Read + Write>(
-    stream: &mut S,
-    xid: u32,
-    arg: &[u8],
-) -> Result<(), crate::Error> {
-    let buf = encode_succesful_reply(xid, arg);
-    stream.write_all(&buf)?;
+    pub fn rejected_reply(rejection: Rejection) -> Self {
+        ReplyBody::Denied(rejection.into())
+    }
 
-    Ok(())
+    /// Reject a call whose credential or verifier is not accepted.
+    pub fn auth_error(e: AuthError) -> Self {
+        Self::rejected_reply(Rejection::Auth(e))
+    }
 }
 
 pub fn encode_succesful_reply(xid: u32, arg: &[u8]) -> Vec<u8> {
-    let body = RpcMessageBody::Reply(ReplyBody::accepted_reply(AcceptedReplyBody::Success(
-        [0u8; 0],
-    )));
+    encode_succesful_reply_with_verifier(xid, OpaqueAuth::none(), arg)
+}
+
+pub fn encode_succesful_reply_with_verifier(xid: u32, verf: OpaqueAuth, arg: &[u8]) -> Vec<u8> {
+    let body = RpcMessageBody::Reply(ReplyBody::Accepted(AcceptedReply {
+        verf,
+        reply_data: AcceptedReplyBody::Success([0u8; 0]),
+    }));
 
     let message = RpcMessage { xid, body };
 
@@ -0,0 +1,309 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Limits on the calls that a server answers for each client, and for each class of calls, so that
+// one client that sends many calls, or moves much data, does not crowd out the others.
+//
+// A `Throttle` is a handle to one set of allowances, which any number of programs and connections
+// can share, like `Metrics`. Each client address, and each class, has an allowance of calls and of
+// bytes, which is refilled at the rate that its `Limits` give, and holds up to a second's worth. A
+// call is answered once every allowance it draws on has a call left and is not overdrawn in bytes:
+// the call's bytes are taken then, and its reply's bytes once it is sent, so that a single large
+// call or reply is never held back for good, but delays the calls after it instead.
+//
+// Classes are found by the program, with a `Classifier` given to `RpcProgram::set_throttle()` or
+// `ProcedureMap::set_throttle()`, which names the class of a call and its limits from the call and
+// the program's state, such as the export that an NFS call's file handle is in. Calls with no class
+// are limited only by client.
+//
+// A call over the limits is answered late, rather than refused. The blocking server sleeps until
+// it may be answered, which holds up the other connections that it would answer meanwhile; the
+// io_uring server holds the call back, along with the calls after it on its connection, and stops
+// receiving on the connection until the call may be answered, while it answers other connections.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::Call;
+
+/// How often allowances that have been refilled to the full are forgotten.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The rates at which the calls of a client or class are answered. Neither is limited by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The most calls answered per second, on average.
+    pub ops_per_sec: Option<u32>,
+
+    /// The most bytes of calls and replies per second, on average, including their record marks.
+    pub bytes_per_sec: Option<u64>,
+}
+
+impl Limits {
+    pub fn is_unlimited(&self) -> bool {
+        self.ops_per_sec.is_none() && self.bytes_per_sec.is_none()
+    }
+}
+
+/// A class of calls that are limited together, and its limits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Class {
+    pub name: String,
+    pub limits: Limits,
+}
+
+/// Finds the class of a call, given the state of the program that answers it, if it has one.
+pub type Classifier<T> = fn(&Call, &T) -> Option<Class>;
+
+/// A handle to a set of allowances, which clones share.
+#[derive(Clone, Default)]
+pub struct Throttle {
+    inner: Arc<Mutex<Allowances>>,
+}
+
+#[derive(Default)]
+struct Allowances {
+    /// The limits of each client, which apply to each client address separately.
+    client_limits: Limits,
+
+    clients: HashMap<IpAddr, Allowance>,
+    classes: HashMap<String, Allowance>,
+
+    /// When the allowances were last pruned.
+    pruned: Option<Instant>,
+}
+
+/// The calls and bytes left to a client or class, as of `updated`. Bytes may be overdrawn.
+#[derive(Clone, Copy, Debug)]
+struct Allowance {
+    ops: f64,
+    bytes: f64,
+    updated: Instant,
+}
+
+impl Throttle {
+    /// Limit each client as `client_limits` say.
+    pub fn new(client_limits: Limits) -> Self {
+        let throttle = Self::default();
+        throttle.set_client_limits(client_limits);
+
+        throttle
+    }
+
+    /// Limit each client as `limits` say, from now on.
+    pub fn set_client_limits(&self, limits: Limits) {
+        self.inner.lock().unwrap().client_limits = limits;
+    }
+
+    /// Take a call of `bytes` bytes from the allowances of the client at `client` and of `class`,
+    /// as of `now`, if they allow it. Otherwise, returns how long until they might.
+    pub fn admit(
+        &self,
+        client: Option<IpAddr>,
+        class: Option<&Class>,
+        bytes: usize,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.prune(now);
+
+        let client_limits = inner.client_limits;
+        let Allowances {
+            clients, classes, ..
+        } = &mut *inner;
+        let mut drawn = Vec::with_capacity(2);
+        if let Some(client) = client.filter(|_| !client_limits.is_unlimited()) {
+            let allowance = clients
+                .entry(client)
+                .or_insert_with(|| Allowance::full(&client_limits, now));
+            drawn.push((allowance, client_limits));
+        }
+        if let Some(class) = class.filter(|class| !class.limits.is_unlimited()) {
+            let allowance = classes
+                .entry(class.name.clone())
+                .or_insert_with(|| Allowance::full(&class.limits, now));
+            drawn.push((allowance, class.limits));
+        }
+
+        let wait = drawn
+            .iter_mut()
+            .map(|(allowance, limits)| {
+                allowance.refill(limits, now);
+                allowance.wait(limits)
+            })
+            .max()
+            .unwrap_or_default();
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+
+        for (allowance, _) in drawn {
+            allowance.ops -= 1.0;
+            allowance.bytes -= bytes as f64;
+        }
+
+        Ok(())
+    }
+
+    /// Take `bytes` more, such as those of the reply to a call that was admitted, from the
+    /// allowances of the client at `client` and of `class`, as of `now`.
+    pub fn charge(
+        &self,
+        client: Option<IpAddr>,
+        class: Option<&Class>,
+        bytes: usize,
+        now: Instant,
+    ) {
+        let mut inner = self.inner.lock().unwrap();
+
+        let client_limits = inner.client_limits;
+        if let Some(allowance) = client.and_then(|client| inner.clients.get_mut(&client)) {
+            allowance.refill(&client_limits, now);
+            allowance.bytes -= bytes as f64;
+        }
+        if let Some(class) = class {
+            if let Some(allowance) = inner.classes.get_mut(&class.name) {
+                allowance.refill(&class.limits, now);
+                allowance.bytes -= bytes as f64;
+            }
+        }
+    }
+}
+
+impl Allowances {
+    /// Forget the allowances that have not been drawn on for long enough to be full again, if they
+    /// were last pruned long enough ago, since a full allowance is as good as none.
+    fn prune(&mut self, now: Instant) {
+        if self
+            .pruned
+            .is_some_and(|pruned| now.duration_since(pruned) < PRUNE_INTERVAL)
+        {
+            return;
+        }
+        self.pruned = Some(now);
+
+        let client_limits = self.client_limits;
+        self.clients.retain(|_, allowance| {
+            allowance.refill(&client_limits, now);
+            !allowance.is_full(&client_limits)
+        });
+
+        // The limits of a class are only known when one of its calls arrives, so classes are
+        // forgotten once none of their calls has arrived for as long:
+        self.classes
+            .retain(|_, allowance| now.duration_since(allowance.updated) < PRUNE_INTERVAL);
+    }
+}
+
+impl Allowance {
+    fn full(limits: &Limits, now: Instant) -> Self {
+        Self {
+            ops: limits.ops_per_sec.unwrap_or_default() as f64,
+            bytes: limits.bytes_per_sec.unwrap_or_default() as f64,
+            updated: now,
+        }
+    }
+
+    /// Add what `limits` allow since the allowance was last updated, up to a second's worth.
+    fn refill(&mut self, limits: &Limits, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.updated = self.updated.max(now);
+
+        if let Some(ops) = limits.ops_per_sec {
+            self.ops = (self.ops + ops as f64 * elapsed).min(ops as f64);
+        }
+        if let Some(bytes) = limits.bytes_per_sec {
+            self.bytes = (self.bytes + bytes as f64 * elapsed).min(bytes as f64);
+        }
+    }
+
+    fn is_full(&self, limits: &Limits) -> bool {
+        limits.ops_per_sec.is_none_or(|ops| self.ops >= ops as f64)
+            && limits
+                .bytes_per_sec
+                .is_none_or(|bytes| self.bytes >= bytes as f64)
+    }
+
+    /// How long until the allowance has a call left, and is not overdrawn in bytes.
+    fn wait(&self, limits: &Limits) -> Duration {
+        let ops = match limits.ops_per_sec {
+            Some(rate) if self.ops < 1.0 => (1.0 - self.ops) / rate.max(1) as f64,
+            _ => 0.0,
+        };
+        let bytes = match limits.bytes_per_sec {
+            Some(rate) if self.bytes < 0.0 => -self.bytes / rate.max(1) as f64,
+            _ => 0.0,
+        };
+
+        Duration::from_secs_f64(ops.max(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowances() {
+        let client: Option<IpAddr> = "192.0.2.1".parse().ok();
+        let other: Option<IpAddr> = "192.0.2.2".parse().ok();
+        let throttle = Throttle::new(Limits {
+            ops_per_sec: Some(4),
+            bytes_per_sec: None,
+        });
+        let start = Instant::now();
+
+        // A second's worth of calls is answered at once, and then one every quarter second:
+        for _ in 0..4 {
+            assert_eq!(throttle.admit(client, None, 100, start), Ok(()));
+        }
+        assert_eq!(
+            throttle.admit(client, None, 100, start),
+            Err(Duration::from_millis(250))
+        );
+        assert_eq!(throttle.admit(other, None, 100, start), Ok(()));
+        let later = start + Duration::from_millis(250);
+        assert_eq!(throttle.admit(client, None, 100, later), Ok(()));
+        assert!(throttle.admit(client, None, 100, later).is_err());
+
+        // Calls with no known client are limited only by their class, which a large reply
+        // overdraws, holding back the next call until it is made up:
+        let class = Class {
+            name: "/export".into(),
+            limits: Limits {
+                ops_per_sec: None,
+                bytes_per_sec: Some(1000),
+            },
+        };
+        assert_eq!(throttle.admit(None, Some(&class), 100, start), Ok(()));
+        throttle.charge(None, Some(&class), 2900, start);
+        assert_eq!(
+            throttle.admit(None, Some(&class), 100, start),
+            Err(Duration::from_secs(2))
+        );
+        let later = start + Duration::from_secs(2);
+        assert_eq!(throttle.admit(None, Some(&class), 100, later), Ok(()));
+
+        // A call draws on both, and is only answered once both allow it:
+        let class = Class {
+            name: "/other".into(),
+            limits: Limits {
+                ops_per_sec: Some(1),
+                bytes_per_sec: None,
+            },
+        };
+        assert_eq!(throttle.admit(other, Some(&class), 100, later), Ok(()));
+        assert!(throttle.admit(client, Some(&class), 100, later).is_err());
+        assert!(throttle.admit(client, None, 100, later).is_ok());
+
+        // Unlimited calls are always answered:
+        throttle.set_client_limits(Limits::default());
+        for _ in 0..10 {
+            assert_eq!(throttle.admit(client, None, 100, later), Ok(()));
+        }
+    }
+}
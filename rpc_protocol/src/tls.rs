@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// RPC-with-TLS (RFC 9289), which encrypts the calls and replies of a TCP connection without
+// changing the RPC protocol that runs over it.
+//
+// A client asks to start TLS with a probe: a NULL call whose credential has the flavor AUTH_TLS and
+// an empty body. A server that offers TLS answers it with an AUTH_NONE verifier that holds the
+// string "STARTTLS", after which the client begins a TLS handshake on the same connection, and the
+// calls that follow are sent through TLS. A server that does not offer it rejects the probe with an
+// AUTH_ERROR, like any other credential it does not accept.
+//
+// The server side is opt-in for each program:
+//
+//     program.set_tls(TlsPolicy {
+//         config: tls::server_config(Path::new("server.pem"), Path::new("server.key"))?,
+//         required: false,
+//     });
+//
+// and the client upgrades a connection before making its calls:
+//
+//     let config = tls::client_config(Path::new("ca.pem"))?;
+//     let name = ServerName::try_from("server.example")?;
+//     let mut stream = client::start_tls(TcpStream::connect(address)?, PROG, VERS, config, name)?;
+//     let res = client::do_rpc_call_tls(&mut stream, PROG, VERS, proc, &args)?;
+//
+// Only TLS 1.3 is offered, with the "sunrpc" ALPN protocol, as the RFC requires.
+
+use std::{io, path::Path, sync::Arc};
+
+pub use rustls;
+use rustls::{
+    crypto::CryptoProvider,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    ClientConfig, RootCertStore, ServerConfig,
+};
+
+use crate::{AuthFlavor, OpaqueAuth};
+
+/// The body of the verifier with which a server agrees to start TLS.
+pub const STARTTLS: &[u8] = b"STARTTLS";
+
+/// The ALPN protocol of RPC-with-TLS.
+pub const ALPN: &[u8] = b"sunrpc";
+
+/// Whether, and how, a server offers TLS. See `server::RpcProgram::set_tls()`.
+#[derive(Clone)]
+pub struct TlsPolicy {
+    /// The server's certificates and key.
+    pub config: Arc<ServerConfig>,
+
+    /// Whether calls other than to the NULL procedure are refused, with AUTH_ERROR and the status
+    /// AUTH_TOOWEAK, until TLS has been started on their connection.
+    pub required: bool,
+}
+
+/// The credential of the probe with which a client asks to start TLS.
+pub fn probe_credential() -> OpaqueAuth {
+    OpaqueAuth {
+        flavor: AuthFlavor::Tls,
+        body: Vec::new(),
+    }
+}
+
+/// The verifier of the reply with which a server agrees to start TLS.
+pub fn starttls_verifier() -> OpaqueAuth {
+    OpaqueAuth {
+        flavor: AuthFlavor::None,
+        body: STARTTLS.to_vec(),
+    }
+}
+
+/// A server configuration presenting the certificate chain in the PEM file `certificates`, server
+/// certificate first, with the private key in the PEM file `key`.
+pub fn server_config(certificates: &Path, key: &Path) -> io::Result<Arc<ServerConfig>> {
+    let chain = read_certificates(certificates)?;
+    let key = PrivateKeyDer::from_pem_file(key).map_err(|e| pem_error(key, e))?;
+
+    let mut config = ServerConfig::builder_with_provider(provider())
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .map_err(io::Error::other)?
+        .with_no_client_auth()
+        .with_single_cert(chain, key)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    config.alpn_protocols = vec![ALPN.to_vec()];
+
+    Ok(Arc::new(config))
+}
+
+/// A client configuration that trusts the certificate authorities in the PEM file `roots`.
+pub fn client_config(roots: &Path) -> io::Result<Arc<ClientConfig>> {
+    let mut store = RootCertStore::empty();
+    for certificate in read_certificates(roots)? {
+        store
+            .add(certificate)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+
+    let mut config = ClientConfig::builder_with_provider(provider())
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .map_err(io::Error::other)?
+        .with_root_certificates(store)
+        .with_no_client_auth();
+    config.alpn_protocols = vec![ALPN.to_vec()];
+
+    Ok(Arc::new(config))
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+/// Every certificate in the PEM file at `path`, of which there must be at least one.
+fn read_certificates(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let certificates = CertificateDer::pem_file_iter(path)
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+        .map_err(|e| pem_error(path, e))?;
+    if certificates.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} holds no certificates", path.display()),
+        ));
+    }
+
+    Ok(certificates)
+}
+
+fn pem_error(path: &Path, e: rustls::pki_types::pem::Error) -> io::Error {
+    match e {
+        rustls::pki_types::pem::Error::Io(e) => {
+            io::Error::new(e.kind(), format!("{}: {e}", path.display()))
+        }
+        e => io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {e}", path.display()),
+        ),
+    }
+}
@@ -0,0 +1,330 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Tracing of the calls a server receives and the replies it sends, to debug interoperability
+// problems with real clients.
+//
+// A `Tracer` is a handle that a server keeps, and that can be started and stopped at any time, for
+// example from a signal handler. While it is started, every call and reply is written to its file,
+// as received or sent, record mark included:
+//
+//     let tracer = Tracer::new(2049);
+//     program.set_tracer(tracer.clone());
+//     tracer.start(Path::new("/tmp/nfs.pcap"), TraceFormat::Pcap)?;
+//
+// A pcap file can be opened in Wireshark, which dissects the RPC messages in it. Since the server
+// does not see the network packets that carried them, each message is wrapped in made up IPv4 and
+// TCP headers: the server is 127.0.0.1 at the port given to `Tracer::new()`, and each connection is
+// a client at 127.0.0.2, on a port numbered after the connection. The text format has a line with
+// the decoded header of each message, and a hex dump of its bytes.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::*;
+
+use crate::*;
+
+/// The formats that traces can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// A pcap file of IPv4 packets (LINKTYPE_RAW).
+    Pcap,
+
+    /// A line of text per message, followed by a hex dump of it.
+    Text,
+}
+
+impl TraceFormat {
+    /// The format of a trace file named `path`: pcap if it ends in ".pcap", text otherwise.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension == "pcap" => Self::Pcap,
+            _ => Self::Text,
+        }
+    }
+}
+
+/// Which way a message went.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// A call received by the server.
+    Received,
+
+    /// A reply sent by the server.
+    Sent,
+}
+
+/// A handle to a trace, which clones share.
+#[derive(Clone)]
+pub struct Tracer {
+    port: u16,
+    trace: Arc<Mutex<Option<Trace>>>,
+
+    /// The number of connections handed out by `new_connection()`.
+    connections: Arc<AtomicU64>,
+}
+
+/// A trace in progress.
+struct Trace {
+    file: BufWriter<File>,
+    format: TraceFormat,
+
+    /// The next TCP sequence numbers of the client and server sides of each connection.
+    sequences: HashMap<u64, (u32, u32)>,
+}
+
+impl Tracer {
+    /// A tracer for a server listening on `port`, which is not started.
+    pub fn new(port: u16) -> Self {
+        Self {
+            port,
+            trace: Arc::new(Mutex::new(None)),
+            connections: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// A number for a new connection, to pass to `trace()`, which no other connection of the
+    /// servers sharing this tracer is given.
+    pub fn new_connection(&self) -> u64 {
+        self.connections.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Start writing a trace to a new file at `path`, in place of any trace already started.
+    pub fn start(&self, path: &Path, format: TraceFormat) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        if format == TraceFormat::Pcap {
+            write_pcap_header(&mut file)?;
+            file.flush()?;
+        }
+
+        *self.trace.lock().unwrap() = Some(Trace {
+            file,
+            format,
+            sequences: HashMap::new(),
+        });
+
+        Ok(())
+    }
+
+    /// Stop tracing, if a trace was started.
+    pub fn stop(&self) {
+        if let Some(mut trace) = self.trace.lock().unwrap().take() {
+            if let Err(e) = trace.file.flush() {
+                warn!("Could not finish trace: {e}");
+            }
+        }
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.trace.lock().unwrap().is_some()
+    }
+
+    /// Write `message`, an RPC message with its record mark, to the trace if it is started.
+    /// `connection` tells the connections of the server apart.
+    ///
+    /// A trace that cannot be written to is stopped.
+    pub fn trace(&self, connection: u64, direction: Direction, message: &[u8]) {
+        let mut guard = self.trace.lock().unwrap();
+        let Some(trace) = guard.as_mut() else {
+            return;
+        };
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let res = match trace.format {
+            TraceFormat::Pcap => {
+                trace.write_packets(self.port, connection, direction, time, message)
+            }
+            TraceFormat::Text => trace.write_text(connection, direction, time, message),
+        };
+
+        if let Err(e) = res.and_then(|()| trace.file.flush()) {
+            warn!("Stopping trace, since it could not be written: {e}");
+            *guard = None;
+        }
+    }
+}
+
+impl Trace {
+    fn write_text(
+        &mut self,
+        connection: u64,
+        direction: Direction,
+        time: Duration,
+        message: &[u8],
+    ) -> io::Result<()> {
+        let what = match direction {
+            Direction::Received => "received",
+            Direction::Sent => "sent",
+        };
+        writeln!(
+            self.file,
+            "{}.{:06} connection {connection} {what} {} bytes: {}",
+            time.as_secs(),
+            time.subsec_micros(),
+            message.len(),
+            Summary(message)
+        )?;
+
+        for (i, line) in message.chunks(16).enumerate() {
+            write!(self.file, "    {:08x} ", i * 16)?;
+            for byte in line {
+                write!(self.file, " {byte:02x}")?;
+            }
+            writeln!(self.file)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write `message` as TCP segments of the connection, each in a packet of its own.
+    fn write_packets(
+        &mut self,
+        port: u16,
+        connection: u64,
+        direction: Direction,
+        time: Duration,
+        message: &[u8],
+    ) -> io::Result<()> {
+        let client = ([127, 0, 0, 2], 1024 + (connection % 64000) as u16);
+        let server = ([127, 0, 0, 1], port);
+        let (client_seq, server_seq) = self.sequences.entry(connection).or_insert((1, 1));
+        let (from, to, seq, ack) = match direction {
+            Direction::Received => (client, server, client_seq, *server_seq),
+            Direction::Sent => (server, client, server_seq, *client_seq),
+        };
+
+        for segment in message.chunks(MAX_SEGMENT) {
+            let packet = tcp_packet(from, to, *seq, ack, segment);
+            *seq = seq.wrapping_add(segment.len() as u32);
+
+            let len = packet.len() as u32;
+            self.file
+                .write_all(&(time.as_secs() as u32).to_le_bytes())?;
+            self.file.write_all(&time.subsec_micros().to_le_bytes())?;
+            self.file.write_all(&len.to_le_bytes())?;
+            self.file.write_all(&len.to_le_bytes())?;
+            self.file.write_all(&packet)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The most bytes of a message put in one packet, which keeps the packet within the largest size
+/// that an IPv4 header can describe.
+const MAX_SEGMENT: usize = 65000;
+
+/// LINKTYPE_RAW: each packet starts with an IPv4 or IPv6 header.
+const LINKTYPE_RAW: u32 = 101;
+
+fn write_pcap_header(file: &mut impl Write) -> io::Result<()> {
+    file.write_all(&0xa1b2c3d4_u32.to_le_bytes())?;
+    file.write_all(&2_u16.to_le_bytes())?;
+    file.write_all(&4_u16.to_le_bytes())?;
+    file.write_all(&0_i32.to_le_bytes())?; // the time zone, which is UTC
+    file.write_all(&0_u32.to_le_bytes())?; // the accuracy of the timestamps
+    file.write_all(&(MAX_SEGMENT as u32 + 40).to_le_bytes())?;
+    file.write_all(&LINKTYPE_RAW.to_le_bytes())
+}
+
+/// An IPv4 packet holding a TCP segment with `payload`, from one address and port to another. The
+/// TCP checksum is left as zero, which Wireshark does not check by default.
+fn tcp_packet(
+    (from, from_port): ([u8; 4], u16),
+    (to, to_port): ([u8; 4], u16),
+    seq: u32,
+    ack: u32,
+    payload: &[u8],
+) -> Vec<u8> {
+    let total = (20 + 20 + payload.len()) as u16;
+
+    let mut packet = Vec::with_capacity(total.into());
+    packet.extend_from_slice(&[0x45, 0]); // IPv4, a 20 byte header
+    packet.extend_from_slice(&total.to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0x40, 0]); // no identification, don't fragment
+    packet.extend_from_slice(&[64, 6, 0, 0]); // TTL, TCP, and the checksum, filled in below
+    packet.extend_from_slice(&from);
+    packet.extend_from_slice(&to);
+    let checksum = ipv4_checksum(&packet);
+    packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    packet.extend_from_slice(&from_port.to_be_bytes());
+    packet.extend_from_slice(&to_port.to_be_bytes());
+    packet.extend_from_slice(&seq.to_be_bytes());
+    packet.extend_from_slice(&ack.to_be_bytes());
+    packet.extend_from_slice(&[0x50, 0x18]); // a 20 byte header; PSH and ACK
+    packet.extend_from_slice(&u16::MAX.to_be_bytes()); // window
+    packet.extend_from_slice(&[0, 0, 0, 0]); // checksum and urgent pointer
+    packet.extend_from_slice(payload);
+
+    packet
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header
+        .chunks(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+/// Describes the header of an RPC message, with its record mark.
+struct Summary<'a>(&'a [u8]);
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut message = RpcMessage::default();
+        let decoded = self
+            .0
+            .get(4..)
+            .is_some_and(|mut body| message.deserialize(&mut body).is_ok());
+        if !decoded {
+            return write!(f, "not an RPC message");
+        }
+
+        let xid = message.xid;
+        match message.body {
+            RpcMessageBody::Call(call) => write!(
+                f,
+                "CALL xid {xid:#010x} program {} version {} procedure {}, {:?} credential",
+                call.prog, call.vers, call.proc, call.cred.flavor
+            ),
+            RpcMessageBody::Reply(ReplyBody::Accepted(reply)) => {
+                let status = match reply.reply_data {
+                    AcceptedReplyBody::Success(_) => "SUCCESS".to_string(),
+                    AcceptedReplyBody::ProgMismatch(mismatch) => {
+                        format!("PROG_MISMATCH ({}-{})", mismatch.low, mismatch.high)
+                    }
+                    AcceptedReplyBody::ProgUnavail => "PROG_UNAVAIL".to_string(),
+                    AcceptedReplyBody::ProcUnavail => "PROC_UNAVAIL".to_string(),
+                    AcceptedReplyBody::GarbageArgs => "GARBAGE_ARGS".to_string(),
+                    AcceptedReplyBody::SystemErr => "SYSTEM_ERR".to_string(),
+                };
+                write!(f, "REPLY xid {xid:#010x} accepted: {status}")
+            }
+            RpcMessageBody::Reply(ReplyBody::Denied(rejected)) => {
+                match Rejection::try_from(rejected) {
+                    Ok(rejection) => write!(f, "REPLY xid {xid:#010x} denied: {rejection}"),
+                    Err(_) => write!(f, "REPLY xid {xid:#010x} denied with AUTH_OK"),
+                }
+            }
+        }
+    }
+}
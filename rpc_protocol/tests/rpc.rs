@@ -59,7 +59,7 @@ fn call_invalid_program() {
     // invalid CALL: wrong program number
     let res = client::do_rpc_call(&mut client_endpoint, 8, 4, 1, &[0; 0]);
 
-    expected_error(res, AcceptedReplyBody::ProgUnavail);
+    expected_error(res, RpcCallError::ProgUnavail);
 }
 
 #[test]
@@ -69,20 +69,14 @@ fn call_invalid_version() {
     // invalid CALL: too low version number
     let res = client::do_rpc_call(&mut client_endpoint, 7, 1, 1, &[0; 0]);
 
-    expected_error(
-        res,
-        AcceptedReplyBody::ProgMismatch(ProgMismatchBody { low: 2, high: 4 }),
-    );
+    expected_error(res, RpcCallError::ProgMismatch { low: 2, high: 4 });
 
     let mut client_endpoint = launch_example_server();
 
     // invalid CALL: too high version number
     let res = client::do_rpc_call(&mut client_endpoint, 7, 5, 1, &[0; 0]);
 
-    expected_error(
-        res,
-        AcceptedReplyBody::ProgMismatch(ProgMismatchBody { low: 2, high: 4 }),
-    );
+    expected_error(res, RpcCallError::ProgMismatch { low: 2, high: 4 });
 }
 
 #[test]
@@ -92,7 +86,7 @@ fn call_invalid_procedure() {
     // invalid CALL: wrong procedure number
     let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 2, &[0; 0]);
 
-    expected_error(res, AcceptedReplyBody::ProcUnavail);
+    expected_error(res, RpcCallError::ProcUnavail);
 }
 
 #[test]
@@ -112,8 +106,499 @@ fn call_invalid_rpc_version() {
     let mut output = vec![0u8; 4];
     let res = client_endpoint.read_exact(&mut output).unwrap_err();
     // It is expected that the server simply drops the connection, which leads to being unable to
-    // read from the pipe endpoint:
-    assert_eq!(res.kind(), std::io::ErrorKind::UnexpectedEof);
+    // read from the pipe endpoint. The server stops reading once it has seen the RPC version, so
+    // the rest of the call may be left unread, which resets the connection:
+    assert!(
+        matches!(
+            res.kind(),
+            std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::ConnectionReset
+        ),
+        "{res:?}"
+    );
+}
+
+#[test]
+fn call_header_peek() {
+    let call = RpcMessage {
+        xid: 0x1234,
+        body: RpcMessageBody::Call(CallBody {
+            rpcvers: 2,
+            prog: 100003,
+            vers: 3,
+            proc: 6,
+            cred: OpaqueAuth::none(),
+            verf: OpaqueAuth::none(),
+        }),
+    }
+    .serialize_alloc();
+
+    let peek = peek_call(&call).unwrap();
+    assert_eq!(
+        peek,
+        CallPeek {
+            xid: 0x1234,
+            prog: 100003,
+            vers: 3,
+            proc: 6
+        }
+    );
+    assert!(peek.validate(100003, 3, 3).is_ok());
+    let Err(Error::Rpc(reply)) = peek.validate(100005, 3, 3) else {
+        panic!("Expected RPC error reply");
+    };
+    assert_eq!(
+        RpcCallError::try_from(reply).ok(),
+        Some(RpcCallError::ProgUnavail)
+    );
+
+    // Too short, a reply, and for another version of RPC:
+    assert!(peek_call(&call[..MIN_CALL_SIZE - 1]).is_err());
+    let mut reply = call.clone();
+    reply[7] = 1;
+    assert!(matches!(peek_call(&reply), Err(ProtocolError::Decode)));
+    let mut rpc_v3 = call.clone();
+    rpc_v3[11] = 3;
+    assert!(matches!(
+        peek_call(&rpc_v3),
+        Err(ProtocolError::WrongRpcVersion)
+    ));
+
+    // A header whose fixed part is fine, but whose credential runs past the end, is only found out
+    // by decoding it:
+    let mut truncated = call.clone();
+    truncated[28..32].copy_from_slice(&100_u32.to_be_bytes());
+    assert!(peek_call(&truncated).is_ok());
+    assert!(decode_call(&truncated).is_err());
+    assert!(decode_call(&[0xff; 64]).is_err());
+}
+
+#[test]
+fn streaming_procedures() {
+    let (mut client_endpoint, mut server_endpoint) = pipe::pipe().unwrap();
+
+    let mut server = server::RpcProgram::new(7, 2, 4, vec![None, Some(server::null_procedure)], ());
+    // Checks an opaque argument as it arrives, a chunk at a time, and replies with its length:
+    server.set_streaming_procedure(2, |_call, arg, _state| {
+        let mut len = 0;
+        if xdr_lib::read_u32(&mut len, arg).is_err() {
+            return server::RpcResult::GarbageArgs;
+        }
+
+        let mut chunk = [0; 4096];
+        let mut seen = 0;
+        let mut data = arg.take(len.into());
+        while let Ok(n @ 1..) = data.read(&mut chunk) {
+            assert!(chunk[..n]
+                .iter()
+                .zip(seen..)
+                .all(|(&b, i)| b == (i % 251) as u8));
+            seen += n;
+        }
+
+        server::RpcResult::Success((seen as u32).to_be_bytes().to_vec())
+    });
+    // Ignores its argument, which the server has to skip to get to the next call:
+    server.set_streaming_procedure(3, |_, _, _| server::RpcResult::Success(Vec::new()));
+
+    std::thread::spawn(move || {
+        server.handle_connection(&mut server_endpoint).unwrap();
+    });
+
+    let data: Vec<u8> = (0..1 << 20).map(|i| (i % 251) as u8).collect();
+    let mut arg = (data.len() as u32).to_be_bytes().to_vec();
+    arg.extend_from_slice(&data);
+
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 2, &arg).unwrap();
+    assert_eq!(res, (data.len() as u32).to_be_bytes());
+
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 3, &arg).unwrap();
+    assert!(res.is_empty());
+
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 1, &[]).unwrap();
+    assert!(res.is_empty());
+}
+
+#[test]
+fn null_procedure_override() {
+    // Without an override, NULL does nothing:
+    let mut client_endpoint = launch_example_server();
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 0, &[]).unwrap();
+    assert!(res.is_empty());
+
+    let (mut client_endpoint, mut server_endpoint) = pipe::pipe().unwrap();
+
+    // Counts the NULL calls it has seen, and replies with the count:
+    fn null(_call: &Call, calls: &mut u32) -> server::RpcResult {
+        *calls += 1;
+        server::RpcResult::Success(calls.to_be_bytes().to_vec())
+    }
+    let mut server = server::RpcProgram::new(7, 2, 4, vec![Some(null)], 0);
+
+    std::thread::spawn(move || {
+        server.handle_connection(&mut server_endpoint).unwrap();
+    });
+
+    for i in 1..=3u32 {
+        let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 0, &[]).unwrap();
+        assert_eq!(res, i.to_be_bytes());
+    }
+
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 1, &[]);
+    expected_error(res, RpcCallError::ProcUnavail);
+}
+
+#[test]
+fn sys_credentials() {
+    let (mut client_endpoint, mut server_endpoint) = pipe::pipe().unwrap();
+
+    // Replies with the uid, gid, and number of other groups of the caller:
+    fn whoami(call: &Call, _: &mut ()) -> server::RpcResult {
+        let Principal::Unix { uid, gid, gids } = call.get_principal() else {
+            return server::RpcResult::Success(Vec::new());
+        };
+        let reply = [*uid, *gid, gids.len() as u32];
+        server::RpcResult::Success(reply.iter().flat_map(|x| x.to_be_bytes()).collect())
+    }
+    let mut server = server::RpcProgram::new(7, 2, 4, vec![None, Some(whoami)], ());
+
+    std::thread::spawn(move || {
+        server.handle_connection(&mut server_endpoint).unwrap();
+    });
+
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 1, &[]).unwrap();
+    assert!(res.is_empty());
+
+    let params = AuthSysParms {
+        stamp: 0,
+        machinename: "client".into(),
+        uid: 1000,
+        gid: 100,
+        gids: vec![100, 10],
+    };
+    let cred = OpaqueAuth {
+        flavor: AuthFlavor::Sys,
+        body: params.serialize_alloc(),
+    };
+    let (_, res) =
+        client::do_rpc_call_with_auth(&mut client_endpoint, 7, 4, 1, cred, OpaqueAuth::none(), &[])
+            .unwrap();
+    assert_eq!(res, [0, 0, 3, 232, 0, 0, 0, 100, 0, 0, 0, 2]);
+
+    // A credential whose body is not an AUTH_SYS one:
+    let cred = OpaqueAuth {
+        flavor: AuthFlavor::Sys,
+        body: vec![0; 6],
+    };
+    let res =
+        client::do_rpc_call_with_auth(&mut client_endpoint, 7, 4, 1, cred, OpaqueAuth::none(), &[]);
+    expected_auth_error(res, AuthError::BadCred);
+}
+
+#[test]
+fn typed_procedures() {
+    let (mut client_endpoint, mut server_endpoint) = pipe::pipe().unwrap();
+
+    // Counts the groups of the AUTH_SYS parameters it is given, and its calls, in its state:
+    fn count_groups(
+        _call: &Call,
+        args: AuthSysParms,
+        calls: &mut u32,
+    ) -> Result<u32, server::RpcResult> {
+        *calls += 1;
+        if args.uid == 0 {
+            return Err(server::RpcResult::SystemErr);
+        }
+        Ok(args.gids.len() as u32)
+    }
+    let procedures: Vec<Option<server::RpcProcedure<u32>>> = vec![
+        None,
+        Some(server::typed_procedure(count_groups)),
+        Some(server::typed_procedure(|_, (), calls: &mut u32| Ok(*calls))),
+    ];
+    let mut server = server::RpcProgram::new(7, 2, 4, procedures, 0);
+    std::thread::spawn(move || {
+        server.handle_connection(&mut server_endpoint).unwrap();
+    });
+
+    let mut params = AuthSysParms {
+        stamp: 0,
+        machinename: "client".into(),
+        uid: 1000,
+        gid: 100,
+        gids: vec![100, 10],
+    };
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 1, &params.serialize_alloc());
+    assert_eq!(res.unwrap(), [0, 0, 0, 2]);
+
+    params.uid = 0;
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 1, &params.serialize_alloc());
+    expected_error(res, RpcCallError::SystemErr);
+
+    // An argument that does not decode never reaches the procedure:
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 1, &[0; 6]);
+    expected_error(res, RpcCallError::GarbageArgs);
+
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 2, &[]);
+    assert_eq!(res.unwrap(), [0, 0, 0, 2]);
+}
+
+/// Accepts calls whose credential names a known user, and replies with a verifier that echoes the
+/// name.
+struct NameAuthenticator {
+    users: Vec<&'static str>,
+}
+
+impl server::Authenticator for NameAuthenticator {
+    fn authenticate(&mut self, call: &Call) -> Result<server::Authenticated, AuthError> {
+        let cred = call.get_credential();
+        let name = std::str::from_utf8(&cred.body).map_err(|_| AuthError::BadCred)?;
+        if cred.flavor != AuthFlavor::Short || !self.users.contains(&name) {
+            return Err(AuthError::RejectedCred);
+        }
+
+        Ok(server::Authenticated {
+            principal: Principal::Named(name.to_string()),
+            verifier: OpaqueAuth {
+                flavor: AuthFlavor::Short,
+                body: name.as_bytes().to_vec(),
+            },
+        })
+    }
+}
+
+#[test]
+fn custom_authenticator() {
+    let (mut client_endpoint, mut server_endpoint) = pipe::pipe().unwrap();
+
+    // Replies with the name of the caller:
+    fn whoami(call: &Call, _: &mut ()) -> server::RpcResult {
+        let Principal::Named(name) = call.get_principal() else {
+            return server::RpcResult::SystemErr;
+        };
+        let mut reply = name.as_bytes().to_vec();
+        reply.resize(reply.len().next_multiple_of(4), 0);
+        server::RpcResult::Success(reply)
+    }
+    let mut server = server::RpcProgram::new(7, 2, 4, vec![None, Some(whoami)], ());
+    server.set_authenticator(NameAuthenticator {
+        users: vec!["alice", "bob"],
+    });
+
+    std::thread::spawn(move || {
+        server.handle_connection(&mut server_endpoint).unwrap();
+    });
+
+    let cred = OpaqueAuth {
+        flavor: AuthFlavor::Short,
+        body: b"alice".to_vec(),
+    };
+    let (verf, res) = client::do_rpc_call_with_auth(
+        &mut client_endpoint,
+        7,
+        4,
+        1,
+        cred.clone(),
+        OpaqueAuth::none(),
+        &[],
+    )
+    .unwrap();
+    assert_eq!(verf, cred);
+    assert_eq!(res, b"alice\0\0\0");
+
+    // The NULL procedure is authenticated too:
+    let (verf, _) = client::do_rpc_call_with_auth(
+        &mut client_endpoint,
+        7,
+        4,
+        0,
+        cred.clone(),
+        OpaqueAuth::none(),
+        &[],
+    )
+    .unwrap();
+    assert_eq!(verf, cred);
+
+    let cred = OpaqueAuth {
+        flavor: AuthFlavor::Short,
+        body: b"mallory".to_vec(),
+    };
+    let res =
+        client::do_rpc_call_with_auth(&mut client_endpoint, 7, 4, 1, cred, OpaqueAuth::none(), &[]);
+    expected_auth_error(res, AuthError::RejectedCred);
+}
+
+/// Every reason a server can give for rejecting a credential or verifier.
+const AUTH_ERRORS: [AuthError; 14] = [
+    AuthError::BadCred,
+    AuthError::RejectedCred,
+    AuthError::BadVerf,
+    AuthError::RejectedVerf,
+    AuthError::TooWeak,
+    AuthError::InvalidResp,
+    AuthError::Failed,
+    AuthError::KerbGeneric,
+    AuthError::TimeExpire,
+    AuthError::TktFile,
+    AuthError::Decode,
+    AuthError::NetAddr,
+    AuthError::RpcsecGssCredProblem,
+    AuthError::RpcsecGssCtxProblem,
+];
+
+/// Rejects calls to procedure `n` with the `n`th of `AUTH_ERRORS`.
+struct Rejecter;
+
+impl server::Authenticator for Rejecter {
+    fn authenticate(&mut self, call: &Call) -> Result<server::Authenticated, AuthError> {
+        Err(AUTH_ERRORS[call.get_procedure() as usize])
+    }
+}
+
+#[test]
+fn auth_errors() {
+    for (proc, &e) in AUTH_ERRORS.iter().enumerate() {
+        let (mut client_endpoint, mut server_endpoint) = pipe::pipe().unwrap();
+        let mut server = server::RpcProgram::new(7, 2, 4, vec![None], ());
+        server.set_authenticator(Rejecter);
+        std::thread::spawn(move || {
+            server.handle_connection(&mut server_endpoint).unwrap();
+        });
+
+        let res = client::do_rpc_call(&mut client_endpoint, 7, 4, proc as u32, &[]);
+        expected_auth_error(res, e);
+
+        // Each status survives encoding as an AuthStat:
+        assert_eq!(AuthError::try_from(AuthStat::from(e)).unwrap(), e);
+    }
+
+    assert!(AuthError::try_from(AuthStat::Ok).is_err());
+}
+
+#[test]
+fn rejected_replies() {
+    // Replies to one call with `reply`, and returns the client's view of the reply:
+    fn reply_with(reply: ReplyBody) -> Result<Vec<u8>, Error> {
+        let (mut client_endpoint, mut server_endpoint) = pipe::pipe().unwrap();
+        std::thread::spawn(move || {
+            let mut mark = [0; 4];
+            server_endpoint.read_exact(&mut mark).unwrap();
+            let len = decode_record_mark(&mark).unwrap();
+            let mut call = vec![0; len as usize];
+            server_endpoint.read_exact(&mut call).unwrap();
+            let xid = decode_call(&call).unwrap().get_xid();
+
+            let message = RpcMessage {
+                xid,
+                body: RpcMessageBody::Reply(reply),
+            };
+            let mut buf = message.serialize_alloc();
+            let mark = (buf.len() as u32 | 1 << 31).to_be_bytes();
+            buf.splice(0..0, mark);
+            server_endpoint.write_all(&buf).unwrap();
+        });
+
+        client::do_rpc_call(&mut client_endpoint, 7, 4, 1, &[])
+    }
+
+    let res = reply_with(ReplyBody::rejected_reply(Rejection::RpcMismatch {
+        low: 2,
+        high: 2,
+    }));
+    expected_error(res, RpcCallError::RpcMismatch { low: 2, high: 2 });
+
+    let res = reply_with(ReplyBody::auth_error(AuthError::TooWeak));
+    expected_auth_error(res, AuthError::TooWeak);
+
+    // AUTH_OK is not a reason to reject a call:
+    let res = reply_with(ReplyBody::Denied(RejectedReply::AuthError(AuthStat::Ok)));
+    let Err(Error::Protocol(ProtocolError::Decode)) = res else {
+        panic!("Expected a decoding error, got {res:?}");
+    };
+}
+
+#[test]
+fn reply_parts() {
+    let (mut client_endpoint, mut server_endpoint) = pipe::pipe().unwrap();
+
+    // Replies with a length, then that much data, in parts that are sent without being copied
+    // together:
+    fn parts(_call: &Call, _state: &mut ()) -> server::RpcResult {
+        let data: Vec<u8> = (0..1 << 20).map(|i| (i % 251) as u8).collect();
+        let len = (data.len() as u32).to_be_bytes().to_vec();
+        server::RpcResult::SuccessParts(vec![len, Vec::new(), data])
+    }
+    let mut server = server::RpcProgram::new(7, 2, 4, vec![None, Some(parts)], ());
+
+    std::thread::spawn(move || {
+        server.handle_connection(&mut server_endpoint).unwrap();
+    });
+
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 1, &[]).unwrap();
+    assert_eq!(res[..4], (1u32 << 20).to_be_bytes());
+    assert!(res[4..]
+        .iter()
+        .enumerate()
+        .all(|(i, &b)| b == (i % 251) as u8));
+
+    // The parts make the same reply as the result encoded whole, however it is written:
+    let result = || vec![vec![1, 2, 3, 4], vec![5, 6, 7], vec![8]];
+    let whole = server::RpcResult::Success(result().concat()).encode_reply(9, OpaqueAuth::none());
+    let mut reply =
+        server::RpcResult::SuccessParts(result()).encode_reply_parts(9, OpaqueAuth::none());
+    assert_eq!(reply.size(), whole.len());
+    assert_eq!(reply.io_slices().len(), 4);
+
+    // Writes at most 5 bytes at a time, of the first slice only:
+    struct Trickle(Vec<u8>);
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(5);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let mut written = Trickle(Vec::new());
+    reply.write_to(&mut written).unwrap();
+    assert_eq!(written.0, whole);
+
+    assert_eq!(reply.whole(), whole);
+    assert_eq!(reply.into_bytes(), whole);
+}
+
+#[test]
+fn call_errors() {
+    let errors = [
+        RpcCallError::ProgUnavail,
+        RpcCallError::ProgMismatch { low: 2, high: 4 },
+        RpcCallError::ProcUnavail,
+        RpcCallError::GarbageArgs,
+        RpcCallError::SystemErr,
+        RpcCallError::AuthError(AuthError::TooWeak),
+        RpcCallError::RpcMismatch { low: 2, high: 2 },
+    ];
+    for e in errors {
+        assert_eq!(RpcCallError::try_from(ReplyBody::from(e)).ok(), Some(e));
+    }
+
+    assert_eq!(
+        Error::Call(RpcCallError::ProgMismatch { low: 2, high: 4 }).to_string(),
+        "Call failed: program versions 2 to 4 are supported"
+    );
+    assert_eq!(
+        RpcCallError::AuthError(AuthError::BadCred).to_string(),
+        "authentication error: bad credential"
+    );
+
+    // A succesful reply is not an error:
+    let success = ReplyBody::Accepted(AcceptedReply {
+        verf: OpaqueAuth::none(),
+        reply_data: AcceptedReplyBody::Success([0; 0]),
+    });
+    assert!(RpcCallError::try_from(success).is_err());
 }
 
 /// Launches an RpcProgram with program number 7, version range 2-4, and one procedure defined (in
@@ -132,16 +617,761 @@ fn launch_example_server() -> pipe::Endpoint {
     client_endpoint
 }
 
-fn expected_error(res: Result<Vec<u8>, Error>, expected: AcceptedReplyBody) {
-    let Err(Error::Rpc(reply)) = res else {
-        panic!("Expected RPC error reply, got {res:?}");
+fn expected_error<T: std::fmt::Debug>(res: Result<T, Error>, expected: RpcCallError) {
+    let Err(Error::Call(e)) = res else {
+        panic!("Expected an unsuccesful reply, got {res:?}");
     };
 
-    let ReplyBody::Accepted(arep) = reply else {
-        panic!("Expected Accepted reply, got {reply:?}");
+    assert_eq!(e, expected);
+}
+
+fn expected_auth_error<T: std::fmt::Debug>(res: Result<T, Error>, expected: AuthError) {
+    expected_error(res, RpcCallError::AuthError(expected));
+}
+
+#[test]
+fn call_deadlines() {
+    use std::time::Duration;
+
+    // Calls answered in time return their results, and leave the connection usable:
+    let mut connection = client::Connection::new(launch_example_server());
+    let timeout = Some(Duration::from_secs(5));
+    assert!(connection.call(7, 2, 1, &[], timeout).unwrap().is_empty());
+    assert!(connection.call(7, 2, 0, &[], None).unwrap().is_empty());
+    expected_error(
+        connection.call(7, 2, 2, &[], timeout),
+        RpcCallError::ProcUnavail,
+    );
+    assert!(!connection.is_poisoned());
+
+    // A server that never answers:
+    let (client_endpoint, mut server_endpoint) = pipe::pipe().unwrap();
+    let mut connection = client::Connection::new(client_endpoint);
+    let timeout = Some(Duration::from_millis(100));
+    let res = connection.call(7, 2, 1, &[], timeout);
+    assert!(matches!(res, Err(Error::TimedOut)), "{res:?}");
+    assert!(connection.is_poisoned());
+
+    // Later calls are not sent, so that its late reply is never taken for that of one of them:
+    let mut call = [0; 44];
+    server_endpoint.read_exact(&mut call).unwrap();
+    let res = connection.call(7, 2, 1, &[], timeout);
+    assert!(matches!(&res, Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotConnected));
+    server_endpoint
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    assert!(server_endpoint.read(&mut [0; 4]).is_err());
+}
+
+#[test]
+fn throttled_calls() {
+    use rpc_protocol::throttle::{Class, Limits, Throttle};
+    use std::time::{Duration, Instant};
+
+    /// Procedure 2 is limited to 2 calls per second, as a class.
+    fn classify(call: &Call, _state: &()) -> Option<Class> {
+        (call.get_procedure() == 2).then(|| Class {
+            name: "two".into(),
+            limits: Limits {
+                ops_per_sec: Some(2),
+                bytes_per_sec: None,
+            },
+        })
+    }
+
+    // Two programs share the allowances, each answering one connection:
+    let throttle = Throttle::new(Limits {
+        ops_per_sec: Some(4),
+        bytes_per_sec: None,
+    });
+    let launch = |address: Option<std::net::SocketAddr>| {
+        let (client_endpoint, server_endpoint) = pipe::pipe().unwrap();
+        let null: server::RpcProcedure<()> = server::null_procedure;
+        let mut server = server::RpcProgram::new(7, 1, 1, vec![None, Some(null), Some(null)], ());
+        server.set_throttle(throttle.clone(), Some(classify));
+        std::thread::spawn(move || server.handle_connection_from(server_endpoint, None, address));
+
+        client_endpoint
+    };
+
+    // A client may make a second's worth of calls at once, and then one every quarter second:
+    let mut client = launch(Some("192.0.2.1:700".parse().unwrap()));
+    let start = Instant::now();
+    for _ in 0..4 {
+        client::do_rpc_call(&mut client, 7, 1, 1, &[]).unwrap();
+    }
+    assert!(start.elapsed() < Duration::from_millis(200));
+    client::do_rpc_call(&mut client, 7, 1, 1, &[]).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // Calls of a class are limited together, and others not at all, without a client address:
+    let mut client = launch(None);
+    let start = Instant::now();
+    for proc in [2, 1, 1, 1, 1, 1, 2] {
+        client::do_rpc_call(&mut client, 7, 1, proc, &[]).unwrap();
+    }
+    assert!(start.elapsed() < Duration::from_millis(200));
+    client::do_rpc_call(&mut client, 7, 1, 2, &[]).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(400));
+}
+
+#[test]
+fn dual_stack_listener() {
+    let listener = server::bind_tcp("[::]:0".parse().unwrap()).unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    for host in ["127.0.0.1", "::1"] {
+        std::net::TcpStream::connect((host, port)).unwrap();
+        let (_, peer) = listener.accept().unwrap();
+        assert!(peer.is_ipv6(), "{host}: {peer}");
+    }
+}
+
+#[test]
+fn connection_timeouts() {
+    use std::time::Duration;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let mut server = server::RpcProgram::new(7, 2, 4, vec![None, Some(server::null_procedure)], ());
+    server.set_connection_timeouts(server::ConnectionTimeouts {
+        read: Some(Duration::from_millis(200)),
+        write: Some(Duration::from_secs(5)),
+        keepalive: Some(Duration::from_secs(60)),
+    });
+    std::thread::spawn(move || server.run_blocking_tcp_server(listener));
+
+    // Calls are answered until the client goes quiet, which ends the connection:
+    let mut stream = std::net::TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let res = client::do_rpc_call(&mut stream, 7, 4, 1, &[]).unwrap();
+    assert!(res.is_empty());
+    std::thread::sleep(Duration::from_millis(500));
+    assert_eq!(stream.read(&mut [0; 4]).unwrap(), 0);
+
+    // So does stopping partway through a call, which would otherwise hold the server's thread:
+    let mut stream = std::net::TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(&[0x80, 0, 0, 40, 0, 0]).unwrap();
+    assert_eq!(stream.read(&mut [0; 4]).unwrap(), 0);
+
+    // The server, which serves one connection at a time, is free for the next:
+    let mut stream = std::net::TcpStream::connect(address).unwrap();
+    assert!(client::do_rpc_call(&mut stream, 7, 4, 1, &[]).is_ok());
+}
+
+#[test]
+fn callback_connections() {
+    use callback::*;
+
+    // The client's callback service, which echoes its argument back reversed:
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let echo: server::RpcProcedure<()> =
+        |call, _| server::RpcResult::Success(call.arg.iter().rev().copied().collect());
+    let mut service = server::RpcProgram::new(40, 1, 1, vec![None, Some(echo)], ());
+    std::thread::spawn(move || service.run_blocking_tcp_server(listener));
+
+    let timeout = std::time::Duration::from_secs(5);
+    let mut client = CallbackClient::connect(address, timeout).unwrap();
+    assert_eq!(client.peer_address().unwrap(), address);
+
+    let mut callback = Callback {
+        prog: 40,
+        vers: 1,
+        proc: 1,
+        cred: OpaqueAuth::none(),
+        arg: vec![1, 2, 3, 4],
+    };
+    assert_eq!(client.call(&callback).unwrap(), [4, 3, 2, 1]);
+
+    // A reply that is not successful is an error. This service then closes the connection, which
+    // fails the next call, and every one after it:
+    callback.proc = 2;
+    expected_error(client.call(&callback), RpcCallError::ProcUnavail);
+    callback.proc = 1;
+    assert!(matches!(client.call(&callback), Err(Error::Io(_))));
+    let Err(Error::Io(e)) = client.call(&callback) else {
+        panic!("Expected a failed connection");
+    };
+    assert_eq!(e.kind(), std::io::ErrorKind::NotConnected);
+
+    let mut client = CallbackClient::connect(address, timeout).unwrap();
+    callback.arg = vec![5, 6, 7, 8];
+    assert_eq!(client.call(&callback).unwrap(), [8, 7, 6, 5]);
+
+    // Each callback gets an XID of its own:
+    let mut xids = XidSpace::new();
+    let first = xids.allocate();
+    assert_eq!(xids.allocate(), first.wrapping_add(1));
+}
+
+#[test]
+fn unix_peer_credentials() {
+    use std::os::unix::{fs::MetadataExt, net::*};
+
+    let path = std::env::temp_dir().join(format!("rpc_peer_{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    let uid = std::fs::metadata(&path).unwrap().uid();
+
+    // Replies with the pid and uid of the caller, or nothing if they are not known:
+    fn whoami(call: &Call, _: &mut ()) -> server::RpcResult {
+        let Some(peer) = call.get_peer() else {
+            return server::RpcResult::Success(Vec::new());
+        };
+        let reply = [peer.pid as u32, peer.uid];
+        server::RpcResult::Success(reply.iter().flat_map(|x| x.to_be_bytes()).collect())
+    }
+    let mut server = server::RpcProgram::new(7, 2, 4, vec![None, Some(whoami)], ());
+    std::thread::spawn(move || server.run_blocking_tcp_server(listener));
+
+    let mut client = UnixStream::connect(&path).unwrap();
+    let res = client::do_rpc_call(&mut client, 7, 4, 1, &[]).unwrap();
+    let expected: Vec<u8> = [std::process::id(), uid]
+        .iter()
+        .flat_map(|x| x.to_be_bytes())
+        .collect();
+    assert_eq!(res, expected);
+    std::fs::remove_file(&path).unwrap();
+
+    // Over a connection with no peer credentials:
+    let (mut client_endpoint, server_endpoint) = pipe::pipe().unwrap();
+    let mut server = server::RpcProgram::new(7, 2, 4, vec![None, Some(whoami)], ());
+    std::thread::spawn(move || server.handle_connection(server_endpoint));
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 4, 1, &[]).unwrap();
+    assert!(res.is_empty());
+
+    // Root is always admitted; a user in no configured group is not:
+    let policy = server::PeerPolicy { group: Some(54321) };
+    let stranger = PeerCredentials {
+        pid: 1,
+        uid: 12345,
+        gid: 12345,
+    };
+    assert!(policy.allows(&PeerCredentials { uid: 0, ..stranger }));
+    assert!(policy.allows(&PeerCredentials {
+        gid: 54321,
+        ..stranger
+    }));
+    assert!(!policy.allows(&stranger));
+    assert!(!server::PeerPolicy::default().allows(&stranger));
+}
+
+#[test]
+fn procedure_metrics() {
+    let (mut client_endpoint, server_endpoint) = pipe::pipe().unwrap();
+
+    // Procedure 2 always finds its argument garbled:
+    fn garbled(_call: &Call, _: &mut ()) -> server::RpcResult {
+        server::RpcResult::GarbageArgs
+    }
+    let procedures: Vec<Option<server::RpcProcedure<()>>> =
+        vec![None, Some(server::null_procedure), Some(garbled)];
+    let mut server = server::RpcProgram::new(7, 2, 4, procedures, ());
+    server.set_procedure_names(|_, proc| (proc == 1).then_some("ONE"));
+    let metrics = metrics::Metrics::default();
+    server.set_metrics(metrics.clone());
+    std::thread::spawn(move || server.handle_connection(server_endpoint));
+
+    for _ in 0..3 {
+        client::do_rpc_call(&mut client_endpoint, 7, 3, 1, &[0; 8]).unwrap();
+    }
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 3, 2, &[]);
+    expected_error(res, RpcCallError::GarbageArgs);
+
+    // A call is counted after its reply is sent, so by the time the reply to this one arrives, the
+    // ones before it have been counted:
+    client::do_rpc_call(&mut client_endpoint, 7, 3, 0, &[]).unwrap();
+
+    let snapshot = metrics.snapshot();
+    let stats = |proc| {
+        let found = snapshot.iter().find(|(key, _)| key.procedure == proc);
+        let (key, stats) = found.unwrap();
+        assert_eq!((key.program, key.version), (7, 3));
+        stats
+    };
+    let one = stats(1);
+    assert_eq!(one.name, Some("ONE"));
+    assert_eq!((one.calls, one.errors), (3, 0));
+    // Each call is a record mark, 40 bytes of header, and the argument:
+    assert_eq!(one.bytes_in, 3 * (4 + 40 + 8));
+    assert_eq!(one.bytes_out, 3 * (4 + 24));
+    assert!(one.latency_buckets.iter().sum::<u64>() <= 3);
+    let two = stats(2);
+    assert_eq!((two.name, two.calls, two.errors), (None, 1, 1));
+
+    // The same counts, over HTTP:
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    metrics.serve_prometheus(listener);
+
+    let mut stream = std::net::TcpStream::connect(address).unwrap();
+    stream.write_all(b"GET /metrics HTTP/1.0\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.0 200 OK\r\n"), "{response}");
+    let labels = r#"program="7",version="3",procedure="ONE""#;
+    assert!(response.contains(&format!("rpc_calls_total{{{labels}}} 3\n")));
+    assert!(response.contains(&format!("rpc_call_duration_seconds_count{{{labels}}} 3\n")));
+    assert!(response.contains(r#"rpc_errors_total{program="7",version="3",procedure="2"} 1"#));
+}
+
+#[test]
+fn call_spans() {
+    use std::sync::{Arc, Mutex};
+
+    /// A log that the test can read back.
+    #[derive(Clone, Default)]
+    struct Log(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Log {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn logging(_call: &Call, _state: &mut ()) -> server::RpcResult {
+        tracing::info!("in the procedure");
+        server::RpcResult::Success(Vec::new())
+    }
+
+    let (mut client_endpoint, server_endpoint) = pipe::pipe().unwrap();
+    let mut server = server::RpcProgram::new(7, 3, 3, vec![None, Some(logging)], ());
+    server.set_procedure_names(|_, proc| (proc == 1).then_some("ONE"));
+    let log = Log::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_ansi(false)
+        .with_writer({
+            let log = log.clone();
+            move || log.clone()
+        })
+        .finish();
+    let server = std::thread::spawn(move || {
+        tracing::subscriber::with_default(subscriber, || server.handle_connection(server_endpoint))
+    });
+
+    client::do_rpc_call(&mut client_endpoint, 7, 3, 1, &[]).unwrap();
+    // The server returns once the client closes the connection:
+    drop(client_endpoint);
+    let _ = server.join().unwrap();
+
+    // What the procedure logs, and the reply, are logged in the span of the call:
+    let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+    let span = "call{xid=17 prog=7 vers=3 proc=ONE}";
+    assert!(
+        log.contains(&format!("{span}: rpc: in the procedure")),
+        "{log}"
+    );
+    let replied = log.lines().find(|line| line.contains("replied")).unwrap();
+    assert!(replied.contains(&format!("{span}: ")), "{replied}");
+    assert!(replied.contains("latency_us="), "{replied}");
+    assert!(
+        replied.contains("bytes_in=44 bytes_out=28 error=false"),
+        "{replied}"
+    );
+}
+
+#[test]
+fn tracing() {
+    let (mut client_endpoint, server_endpoint) = pipe::pipe().unwrap();
+
+    let procedures: Vec<Option<server::RpcProcedure<()>>> =
+        vec![None, Some(server::null_procedure)];
+    let mut server = server::RpcProgram::new(7, 3, 3, procedures, ());
+    let tracer = trace::Tracer::new(2049);
+    server.set_tracer(tracer.clone());
+    std::thread::spawn(move || server.handle_connection(server_endpoint));
+
+    // Nothing is traced before the tracer is started:
+    client::do_rpc_call(&mut client_endpoint, 7, 3, 1, &[0; 8]).unwrap();
+
+    let dir = std::env::temp_dir().join(format!("rpc_tracing_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let pcap = dir.join("trace.pcap");
+    let format = trace::TraceFormat::for_path(&pcap);
+    assert_eq!(format, trace::TraceFormat::Pcap);
+    tracer.start(&pcap, format).unwrap();
+    assert!(tracer.is_started());
+    client::do_rpc_call(&mut client_endpoint, 7, 3, 1, &[1; 8]).unwrap();
+    tracer.stop();
+    client::do_rpc_call(&mut client_endpoint, 7, 3, 1, &[2; 8]).unwrap();
+
+    // The global header, then the call and the reply, each in an IPv4 packet of its own:
+    let bytes = std::fs::read(&pcap).unwrap();
+    assert_eq!(bytes[..4], 0xa1b2c3d4_u32.to_le_bytes());
+    assert_eq!(bytes[20..24], 101_u32.to_le_bytes());
+    let mut rest = &bytes[24..];
+    let mut packets = Vec::new();
+    while !rest.is_empty() {
+        let len = u32::from_le_bytes(rest[8..12].try_into().unwrap()) as usize;
+        packets.push(&rest[16..16 + len]);
+        rest = &rest[16 + len..];
+    }
+    assert_eq!(packets.len(), 2);
+    let (call, reply) = (packets[0], packets[1]);
+    assert_eq!(call[0], 0x45);
+    // The call goes to the server's port, and holds its record mark, header, and argument:
+    assert_eq!(call[22..24], 2049_u16.to_be_bytes());
+    assert_eq!(call.len(), 40 + 4 + 40 + 8);
+    assert_eq!(call[40..44], (0x8000_0000_u32 | 48).to_be_bytes());
+    assert_eq!(call[call.len() - 8..], [1; 8]);
+    assert_eq!(reply[20..22], 2049_u16.to_be_bytes());
+    assert_eq!(reply.len(), 40 + 4 + 24);
+    // The reply acknowledges the call, which starts the connection's sequence numbers:
+    assert_eq!(call[24..28], 1_u32.to_be_bytes());
+    assert_eq!(reply[28..32], (1 + 52_u32).to_be_bytes());
+
+    let text = dir.join("trace.txt");
+    tracer
+        .start(&text, trace::TraceFormat::for_path(&text))
+        .unwrap();
+    client::do_rpc_call(&mut client_endpoint, 7, 3, 1, &[3; 8]).unwrap();
+    tracer.stop();
+
+    let text = std::fs::read_to_string(&text).unwrap();
+    let summaries: Vec<&str> = text.lines().filter(|l| !l.starts_with(' ')).collect();
+    assert_eq!(summaries.len(), 2, "{text}");
+    assert!(
+        summaries[0].contains("connection 0 received 52 bytes: CALL xid")
+            && summaries[0].contains("program 7 version 3 procedure 1"),
+        "{text}"
+    );
+    assert!(
+        summaries[1].contains("connection 0 sent 28 bytes: REPLY xid")
+            && summaries[1].ends_with("accepted: SUCCESS"),
+        "{text}"
+    );
+    // The last line of the call's dump is the end of its argument:
+    assert!(text.contains("    00000030  03 03 03 03\n"), "{text}");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn checking_replies() {
+    let message = RpcMessage {
+        xid: 5,
+        body: RpcMessageBody::Call(CallBody {
+            rpcvers: 2,
+            prog: 7,
+            vers: 1,
+            proc: 1,
+            cred: OpaqueAuth::none(),
+            verf: OpaqueAuth::none(),
+        }),
+    };
+    let bytes = message.serialize_alloc();
+    let call = decode_call(&bytes).unwrap();
+
+    // Procedure 1 returns an opaque value, and the others are not checked:
+    fn check_result(
+        _version: u32,
+        proc: u32,
+        result: &[u8],
+    ) -> Option<Result<(), xdr_lib::EncodingError>> {
+        (proc == 1).then(|| {
+            xdr_lib::check_encoding(result, |input| {
+                let mut len = 0;
+                xdr_lib::get_u32(&mut len, input)?;
+                let opaque = xdr_lib::get_opaque(len as usize, input)?;
+                let mut encoded = len.to_be_bytes().to_vec();
+                encoded.extend_from_slice(opaque);
+                encoded.resize(xdr_lib::padded_4byte(encoded.len()), 0);
+                Ok(encoded)
+            })
+        })
+    }
+    // A succesful reply with `result`, which the encoder would refuse if it were not aligned:
+    let reply = |result: &[u8]| {
+        let mut reply = server::RpcResult::Success(Vec::new()).encode_reply(5, OpaqueAuth::none());
+        reply.extend_from_slice(result);
+        let len = reply.len() as u32 - 4;
+        reply[..4].copy_from_slice(&(len | 1 << 31).to_be_bytes());
+        reply
+    };
+    let check = |reply: &[u8]| server::check_reply(&call, reply, Some(check_result));
+
+    assert_eq!(check(&reply(&[0, 0, 0, 2, b'h', b'i', 0, 0])), Ok(()));
+    let err = check(&reply(&[0, 0, 0, 2, b'h', b'i', b'!', 0])).unwrap_err();
+    assert_eq!(
+        err,
+        "the result differs from its canonical encoding at byte 6"
+    );
+    let err = check(&reply(&[0, 0, 0, 2, b'h', b'i'])).unwrap_err();
+    assert_eq!(
+        err,
+        "the result is 6 bytes long, which is not a multiple of 4"
+    );
+
+    // Without a check for the result, only its length is checked:
+    let unchecked = |reply: &[u8]| server::check_reply(&call, reply, None);
+    assert_eq!(
+        unchecked(&reply(&[0, 0, 0, 2, b'h', b'i', b'!', 0])),
+        Ok(())
+    );
+    assert!(unchecked(&reply(&[0, 0, 0, 2, b'h', b'i'])).is_err());
+
+    // The header is checked in any case:
+    let mut bad = server::RpcResult::GarbageArgs.encode_reply(5, OpaqueAuth::none());
+    assert_eq!(check(&bad), Ok(()));
+    bad.extend_from_slice(&[0; 4]);
+    let err = check(&bad).unwrap_err();
+    assert!(err.starts_with("the record mark gives"), "{err}");
+    let len = bad.len() as u32 - 4;
+    bad[..4].copy_from_slice(&(len | 1 << 31).to_be_bytes());
+    assert_eq!(
+        check(&bad).unwrap_err(),
+        "4 bytes follow a reply that carries no result"
+    );
+}
+
+#[test]
+fn fragmented_records() {
+    let (mut client_endpoint, server_endpoint) = pipe::pipe().unwrap();
+    let (mut refused_endpoint, refused_server_endpoint) = pipe::pipe().unwrap();
+
+    // Procedure 1 replies with the length of its argument:
+    fn length(call: &Call, _: &mut ()) -> server::RpcResult {
+        server::RpcResult::Success((call.arg.len() as u32).to_be_bytes().to_vec())
+    }
+    let procedures: Vec<Option<server::RpcProcedure<()>>> = vec![None, Some(length)];
+    let mut server = server::RpcProgram::new(7, 3, 3, procedures, ());
+    server.set_record_limits(record::RecordLimits {
+        max_record: 1024,
+        max_fragments: 4,
+        tiny_fragment: 64,
+    });
+    let metrics = metrics::Metrics::default();
+    server.set_metrics(metrics.clone());
+    let server = std::thread::spawn(move || {
+        let res = server.handle_connection(server_endpoint);
+        (res, server.handle_connection(refused_server_endpoint))
+    });
+
+    let message = RpcMessage {
+        xid: 9,
+        body: RpcMessageBody::Call(CallBody {
+            rpcvers: 2,
+            prog: 7,
+            vers: 3,
+            proc: 1,
+            cred: OpaqueAuth::none(),
+            verf: OpaqueAuth::none(),
+        }),
+    };
+    let mut call = message.serialize_alloc();
+    call.extend_from_slice(&[7; 200]);
+
+    // Send `call` in fragments of the given sizes, followed by whatever is left:
+    let send = |stream: &mut pipe::Endpoint, sizes: &[usize]| {
+        let mut rest = &call[..];
+        for size in sizes {
+            let (fragment, after) = rest.split_at(*size);
+            stream.write_all(&(*size as u32).to_be_bytes()).unwrap();
+            stream.write_all(fragment).unwrap();
+            rest = after;
+        }
+        stream
+            .write_all(&(rest.len() as u32 | 1 << 31).to_be_bytes())
+            .unwrap();
+        stream.write_all(rest).unwrap();
+    };
+
+    // A call in three fragments, the first two tiny, and its header split between them:
+    send(&mut client_endpoint, &[20, 8]);
+    let mut reply = [0; 4 + 24 + 4];
+    client_endpoint.read_exact(&mut reply).unwrap();
+    assert_eq!(reply[..4], (0x8000_0000_u32 | 28).to_be_bytes());
+    assert_eq!(reply[28..], 200_u32.to_be_bytes());
+
+    // Calls in one fragment work as ever:
+    let res = client::do_rpc_call(&mut client_endpoint, 7, 3, 1, &[1; 8]).unwrap();
+    assert_eq!(res, 8_u32.to_be_bytes());
+    drop(client_endpoint);
+
+    // A call in five fragments is refused, and ends its connection:
+    send(&mut refused_endpoint, &[40, 40, 40, 40]);
+    let mut byte = [0];
+    assert!(matches!(refused_endpoint.read(&mut byte), Ok(0) | Err(_)));
+
+    let (first, refused) = server.join().unwrap();
+    assert!(matches!(first, Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof));
+    assert!(
+        matches!(refused, Err(Error::Io(e)) if e.to_string() == "record in more than 4 fragments")
+    );
+
+    let records = metrics.records();
+    assert_eq!(records.records, 2);
+    assert_eq!(records.fragments, 3 + 1 + 5);
+    assert_eq!(records.fragmented, 1);
+    // Those two, and the first four of the refused call:
+    assert_eq!(records.tiny_fragments, 2 + 4);
+    assert_eq!((records.incomplete, records.refused), (0, 1));
+    assert_eq!(records.largest, call.len() as u64 + 3 * 4);
+    assert!(metrics
+        .prometheus()
+        .contains("rpc_refused_records_total 1\n"));
+}
+
+#[test]
+fn reassembly() {
+    use record::{Reassembled, Reassembly, RecordLimits};
+
+    let limits = RecordLimits {
+        max_record: 16,
+        max_fragments: 2,
+        tiny_fragment: 4,
+    };
+    let whole = |body: &[u8]| {
+        let mut record = (body.len() as u32 | 1 << 31).to_be_bytes().to_vec();
+        record.extend_from_slice(body);
+        record
     };
 
-    if arep.reply_data != expected {
-        panic!("Expected {expected:?}, got {:?}", arep.reply_data);
+    // A record in one fragment, one in two, and another in one:
+    let mut bytes = whole(b"first");
+    bytes.extend_from_slice(&3_u32.to_be_bytes());
+    bytes.extend_from_slice(b"sec");
+    bytes.extend_from_slice(&whole(b"ond"));
+    bytes.extend_from_slice(&whole(b"third"));
+
+    // Received at once, only the fragmented record is copied:
+    let mut reassembly = Reassembly::default();
+    let mut records = Vec::new();
+    reassembly.receive(&bytes, &limits, &mut records).unwrap();
+    assert_eq!(
+        records,
+        [
+            Reassembled::Whole(0..9),
+            Reassembled::Copied(whole(b"second")),
+            Reassembled::Whole(23..32),
+        ]
+    );
+
+    // Received a byte at a time, each is:
+    records.clear();
+    for byte in bytes.chunks(1) {
+        reassembly.receive(byte, &limits, &mut records).unwrap();
+    }
+    assert_eq!(
+        records,
+        [
+            Reassembled::Copied(whole(b"first")),
+            Reassembled::Copied(whole(b"second")),
+            Reassembled::Copied(whole(b"third")),
+        ]
+    );
+
+    // A connection that ends partway through a record leaves it incomplete:
+    records.clear();
+    reassembly
+        .receive(&bytes[..12], &limits, &mut records)
+        .unwrap();
+    assert_eq!(records, [Reassembled::Whole(0..9)]);
+    reassembly.end();
+
+    let stats = reassembly.take_stats();
+    assert_eq!(stats.records, 3 + 3 + 1);
+    assert_eq!(stats.fragments, 4 + 4 + 1);
+    assert_eq!(stats.fragmented, 2);
+    assert_eq!(stats.tiny_fragments, 2);
+    assert_eq!((stats.incomplete, stats.refused), (1, 0));
+    assert_eq!(stats.largest, 6 + 2 * 4);
+
+    // Records are refused once they are known to exceed the limits:
+    let mut reassembly = Reassembly::default();
+    let err = reassembly
+        .receive(&whole(&[0; 17])[..8], &limits, &mut records)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "record of more than 16 bytes");
+    reassembly.end();
+
+    let mut fragments = Vec::new();
+    for _ in 0..3 {
+        fragments.extend_from_slice(&1_u32.to_be_bytes());
+        fragments.push(0);
+    }
+    let err = Reassembly::default()
+        .receive(&fragments, &limits, &mut records)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "record in more than 2 fragments");
+    let stats = reassembly.take_stats();
+    assert_eq!((stats.refused, stats.incomplete), (1, 0));
+}
+
+/// Launches the example program of `launch_example_server()` with TLS, whose procedure 1 returns
+/// whether it was called through TLS, in one word.
+#[cfg(feature = "tls")]
+fn launch_tls_server(required: bool) -> pipe::Endpoint {
+    use std::path::Path;
+
+    fn through_tls(call: &Call, _state: &mut ()) -> server::RpcResult {
+        server::RpcResult::Success(u32::from(call.is_tls()).to_be_bytes().to_vec())
     }
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/tls");
+    let config = tls::server_config(&dir.join("server.pem"), &dir.join("server.key")).unwrap();
+
+    let (client_endpoint, mut server_endpoint) = pipe::pipe().unwrap();
+    let mut server = server::RpcProgram::new(7, 2, 4, vec![None, Some(through_tls)], ());
+    server.set_tls(tls::TlsPolicy { config, required });
+
+    std::thread::spawn(move || {
+        let _ = server.handle_connection(&mut server_endpoint);
+    });
+
+    client_endpoint
+}
+
+#[cfg(feature = "tls")]
+fn tls_client_config() -> std::sync::Arc<tls::rustls::ClientConfig> {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/tls");
+    tls::client_config(&dir.join("ca.pem")).unwrap()
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn tls() {
+    use tls::rustls::pki_types::ServerName;
+
+    let localhost = || ServerName::try_from("localhost").unwrap();
+
+    // Calls are answered through TLS once it has been started:
+    let endpoint = launch_tls_server(false);
+    let mut stream = client::start_tls(endpoint, 7, 4, tls_client_config(), localhost()).unwrap();
+    for _ in 0..3 {
+        let res = client::do_rpc_call_tls(&mut stream, 7, 4, 1, &[]).unwrap();
+        assert_eq!(res, 1_u32.to_be_bytes());
+    }
+
+    // And without it, unless it is required:
+    let mut endpoint = launch_tls_server(false);
+    let res = client::do_rpc_call(&mut endpoint, 7, 4, 1, &[]).unwrap();
+    assert_eq!(res, 0_u32.to_be_bytes());
+
+    let mut endpoint = launch_tls_server(true);
+    client::do_rpc_call(&mut endpoint, 7, 4, 0, &[]).unwrap();
+    let res = client::do_rpc_call(&mut endpoint, 7, 4, 1, &[]);
+    expected_auth_error(res, AuthError::TooWeak);
+
+    // A server that does not offer TLS rejects the probe:
+    let endpoint = launch_example_server();
+    let res = client::start_tls(endpoint, 7, 4, tls_client_config(), localhost());
+    expected_auth_error(res.map(|_| ()), AuthError::RejectedCred);
+
+    // The server's certificate must be for the name the client expects:
+    let endpoint = launch_tls_server(false);
+    let name = ServerName::try_from("nfs.example").unwrap();
+    let res = client::start_tls(endpoint, 7, 4, tls_client_config(), name);
+    assert!(matches!(res, Err(Error::Io(_))));
 }
@@ -1,6 +1,3 @@
 fn main() {
-    xdr_codegen::Compiler::new()
-        .file("rpcbind.x")
-        .run()
-        .expect("That should have worked. :(");
+    xdr_codegen::build_helper().spec("rpcbind.x").run();
 }
@@ -19,6 +19,41 @@ struct RpcbindList {
     RpcbindItem *items;
 };
 
+/* The arguments of an indirect call through CALLIT, and its result. */
+struct RpcbRmtcallArgs {
+    unsigned long prog;
+    unsigned long vers;
+    unsigned long proc;
+    opaque args<>;
+};
+
+struct RpcbRmtcallRes {
+    string addr<>;    /* universal address of the service that was called */
+    opaque results<>;
+};
+
+/*
+ * Not part of RFC 1833: the health of each registered service, as last determined by the sweeper
+ * that probes registered services.
+ */
+enum ServiceHealth {
+    Unprobed    = 0,
+    Reachable   = 1,
+    Unreachable = 2,
+    Dead        = 3
+};
+
+struct RpcServiceHealth {
+    RpcService     service;
+    ServiceHealth  health;
+    unsigned long  failures;   /* consecutive failed probes */
+    unsigned hyper last_seen;  /* seconds since the epoch of the last successful probe, or 0 */
+};
+
+struct HealthList {
+    RpcServiceHealth services<>;
+};
+
 program RPCBPROG {
  version RPCBVERS {
      bool RPCBPROC_SET(RpcService) = 1;
@@ -28,5 +63,11 @@ program RPCBPROG {
      RpcbString RPCBPROC_GETADDR(RpcService) = 3;
 
      RpcbindList RPCBPROC_DUMP(void) = 4;
+
+     /* Called by broadcast, as BCAST is in version 4. */
+     RpcbRmtcallRes RPCBPROC_CALLIT(RpcbRmtcallArgs) = 5;
+
+     /* Extension: DUMP along with the health of each service. */
+     HealthList RPCBPROC_DUMP_HEALTH(void) = 16;
  } = 3;
 } = 100000;
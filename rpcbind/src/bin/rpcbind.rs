@@ -3,12 +3,76 @@
 
 #![allow(non_camel_case_types)]
 
-use rpcbind::{self, RpcbindServerAddress};
+use std::time::Duration;
+
+use clap::Parser;
+
+use rpc_protocol::server::PeerPolicy;
+use rpcbind::{self, health::HealthCheck, RpcbindServerAddress};
+
+#[derive(Parser)]
+struct Cli {
+    /// The address to listen on. The IPv6 wildcard address, the default, accepts IPv4 connections
+    /// too.
+    #[arg(long, default_value = "[::]:111")]
+    listen: String,
+
+    /// Also listen on a UNIX domain socket at this path, such as /run/rpcbind.sock. Anyone may look
+    /// up services through it, but only root, rpcbind's own user, and the members of
+    /// --admin-group may register and unregister them.
+    #[arg(long)]
+    unix_socket: Option<String>,
+
+    /// Also listen for UDP datagrams at this address, such as [::]:111, to answer the CALLIT calls
+    /// that clients broadcast to find the hosts that run a service. Only NULL and CALLIT are
+    /// answered over UDP.
+    #[arg(long)]
+    udp: Option<String>,
+
+    /// A group whose members may register and unregister services through --unix-socket.
+    #[arg(long, requires = "unix_socket")]
+    admin_group: Option<String>,
+
+    /// Seconds between probes of the NULL procedure of each registered TCP service, or 0 to never
+    /// probe services.
+    #[arg(long, default_value_t = 30)]
+    probe_interval: u64,
+
+    /// Seconds to wait for a service to answer a probe.
+    #[arg(long, default_value_t = 5)]
+    probe_timeout: u64,
+
+    /// Seconds a service must have been failing probes before it is considered dead.
+    #[arg(long, default_value_t = 120)]
+    dead_after: u64,
+
+    /// Keep dead services registered and flag them, rather than removing them. Flagged services
+    /// are hidden from GETADDR and DUMP until they answer a probe again.
+    #[arg(long)]
+    keep_dead: bool,
+}
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
+    let args = Cli::parse();
+
+    let health_check = (args.probe_interval > 0).then(|| HealthCheck {
+        interval: Duration::from_secs(args.probe_interval),
+        timeout: Duration::from_secs(args.probe_timeout),
+        dead_after: Duration::from_secs(args.dead_after),
+        remove_dead: !args.keep_dead,
+    });
+
+    let policy = match &args.admin_group {
+        Some(group) => PeerPolicy::for_group(group)?,
+        None => PeerPolicy::default(),
+    };
+
+    let mut addrs = vec![RpcbindServerAddress::Tcp(args.listen)];
+    addrs.extend(args.unix_socket.map(RpcbindServerAddress::Unix));
+    addrs.extend(args.udp.map(RpcbindServerAddress::Udp));
 
-    rpcbind::server::main(RpcbindServerAddress::Tcp("0.0.0.0:111".to_string()));
+    rpcbind::server::serve(&addrs, health_check, policy);
 
     Ok(())
 }
@@ -1,50 +1,350 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
-use std::net::TcpStream;
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    os::unix::net::UnixStream,
+    process::ExitCode,
+    time::Duration,
+};
 
-use clap::Parser;
+use clap::{error::ErrorKind, CommandFactory, Parser};
 
-include!(concat!(env!("OUT_DIR"), "/rpcbind.rs"));
-
-use rpc_protocol::client::*;
+use rpc_protocol::client::do_rpc_call;
+use rpcbind::{client::*, parse_universal_address, RpcService};
 
+/// Report on the services registered with an rpcbind server, like the standard rpcinfo(8):
+///
+///   rpcinfo [host]                       list the registered services
+///   rpcinfo -p [host]                    list them as the portmapper (version 2) would
+///   rpcinfo -T netid host prog [vers]    call the NULL procedure of a service
+///   rpcinfo -b prog vers                 broadcast a call to the NULL procedure of a service
+///   rpcinfo -d [-T netid] prog vers      unregister a service from the local rpcbind
+///
+/// Programs may be given by number or by name, such as "mountd" or "nfs".
 #[derive(Parser)]
+#[command(name = "rpcinfo", verbatim_doc_comment)]
 struct Cli {
-    #[arg(long, default_value = "localhost")]
-    hostname: String,
+    /// List the services as the portmapper would: the program, version, protocol, and port of each
+    /// service registered for the "tcp" or "udp" netid.
+    #[arg(short = 'p', conflicts_with_all = ["delete", "transport"])]
+    portmap: bool,
+
+    /// Call the NULL procedure of a service registered for this netid on the host, which only
+    /// supports "tcp" and "tcp6". With -d, unregister the service for this netid only.
+    #[arg(short = 'T', value_name = "NETID")]
+    transport: Option<String>,
+
+    /// Unregister a service from the rpcbind server on this host, under every netid unless -T is
+    /// given.
+    #[arg(short = 'd', conflicts_with = "health")]
+    delete: bool,
+
+    /// Broadcast a call to the NULL procedure of a service through the CALLIT procedure of every
+    /// rpcbind server on the local network, or only of --hostname, and list the hosts that answer.
+    /// The servers must listen for UDP (see rpcbind's --udp).
+    #[arg(short = 'b', conflicts_with_all = ["delete", "transport", "portmap", "health"])]
+    broadcast: bool,
+
+    /// Seconds to wait for answers to -b.
+    #[arg(long, default_value_t = 3, requires = "broadcast")]
+    wait: u64,
 
+    /// The port that the rpcbind server listens on.
     #[arg(long, default_value_t = 111)]
     port: u16,
+
+    /// Reach the rpcbind server on this host through its UNIX domain socket (see rpcbind's
+    /// --unix-socket) rather than TCP, as it only lets some callers unregister services through it.
+    #[arg(long)]
+    unix_socket: Option<String>,
+
+    /// The host to ask, in place of the host argument.
+    #[arg(long, hide = true)]
+    hostname: Option<String>,
+
+    /// Also list services that have been flagged as dead, along with the health of each service as
+    /// last probed by the server. Only supported by this crate's rpcbind server.
+    #[arg(long)]
+    health: bool,
+
+    /// The host, program, and version, as each mode takes them.
+    args: Vec<String>,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// The names of the programs that rpcinfo knows, as in /etc/rpc.
+const PROGRAMS: &[(u32, &str)] = &[
+    (100000, "portmapper"),
+    (100003, "nfs"),
+    (100005, "mountd"),
+    (100021, "nlockmgr"),
+    (100024, "status"),
+    (100227, "nfs_acl"),
+];
+
+/// A connection to the rpcbind server.
+trait Stream: Read + Write {}
+
+impl<S: Read + Write> Stream for S {}
+
+fn main() -> ExitCode {
     let args = Cli::parse();
-    let server_address = format!("{}:{}", args.hostname, args.port);
-    let mut stream = TcpStream::connect(&server_address)?;
 
-    let res = do_rpc_call(
-        &mut stream,
-        rpcbind::procedures::RPCBPROG,
-        rpcbind::procedures::RPCBVERS::VERSION,
-        rpcbind::procedures::RPCBVERS::RPCBPROC_DUMP,
-        &[0u8; 0],
-    )?;
+    let res = if args.delete {
+        delete(&args)
+    } else if args.broadcast {
+        broadcast(&args)
+    } else if let Some(netid) = &args.transport {
+        ping(&args, netid)
+    } else {
+        let [host] = positional(&args, 0, "[host]");
+        let host = host.unwrap_or(args.hostname.as_deref().unwrap_or("localhost"));
+        connect(&args, host).and_then(|mut stream| {
+            if args.health {
+                print_health_list(dump_health_using_stream(&mut stream)?);
+            } else if args.portmap {
+                print_portmap_list(dump_using_stream(&mut stream)?);
+            } else {
+                print_rpcblist(dump_using_stream(&mut stream)?);
+            }
+
+            Ok(())
+        })
+    };
+
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("rpcinfo: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+type Error = Box<dyn std::error::Error>;
+
+/// The positional arguments, of which there must be from `min` to `N`, as described by `usage`.
+fn positional<'a, const N: usize>(args: &'a Cli, min: usize, usage: &str) -> [Option<&'a str>; N] {
+    if args.args.len() < min || args.args.len() > N {
+        Cli::command()
+            .error(
+                ErrorKind::WrongNumberOfValues,
+                format!("expected the arguments {usage}"),
+            )
+            .exit();
+    }
+
+    std::array::from_fn(|i| args.args.get(i).map(String::as_str))
+}
+
+/// Connect to the rpcbind server on `host`, or through --unix-socket.
+fn connect(args: &Cli, host: &str) -> Result<Box<dyn Stream>, Error> {
+    if let Some(path) = &args.unix_socket {
+        return Ok(Box::new(UnixStream::connect(path)?));
+    }
+
+    Ok(Box::new(TcpStream::connect((host, args.port))?))
+}
+
+/// Call the NULL procedure of program `prog` on `host`, at the address that the host's rpcbind
+/// server gives for each of its versions, or just for `vers`.
+fn ping(args: &Cli, netid: &str) -> Result<(), Error> {
+    if netid != "tcp" && netid != "tcp6" {
+        return Err(format!("the netid {netid} is not supported").into());
+    }
+    let [host, prog, vers] = positional(args, 2, "-T netid host prog [vers]");
+    let host = host.unwrap();
+    let prog = program_number(prog.unwrap())?;
+
+    let rpcbind_address = resolve(host, args.port)?;
+    // The connection is closed before the services are called, since rpcbind may be one of them,
+    // and it answers one connection at a time:
+    let mut stream = connect(args, host)?;
+    let services: Vec<RpcService> = match vers {
+        Some(vers) => {
+            let vers = vers.parse()?;
+            let service = service(prog, vers, netid);
+            let addr = getaddr_using_stream(service.clone(), &mut stream)?;
+            vec![RpcService { addr, ..service }]
+        }
+        None => dump_using_stream(&mut stream)?
+            .into_iter()
+            .filter(|s| s.prog == prog && s.netid == netid)
+            .collect(),
+    };
+    drop(stream);
+
+    let mut failed = services.is_empty();
+    for service in services {
+        let vers = service.vers;
+        let res = service_address(&service, rpcbind_address).and_then(|address| {
+            let mut stream = TcpStream::connect(address)?;
+            do_rpc_call(&mut stream, prog, vers, 0, &[])?;
+
+            Ok(())
+        });
+        match res {
+            Ok(()) => println!("program {prog} version {vers} ready and waiting"),
+            Err(e) => {
+                failed = true;
+                println!("program {prog} version {vers} is not available: {e}");
+            }
+        }
+    }
+
+    if failed {
+        return Err(format!("program {prog} is not available on {host} over {netid}").into());
+    }
+
+    Ok(())
+}
+
+/// Call the NULL procedure of version `vers` of program `prog` on every host that answers a
+/// broadcast.
+fn broadcast(args: &Cli) -> Result<(), Error> {
+    let [prog, vers] = positional(args, 2, "-b prog vers");
+    let prog = program_number(prog.unwrap())?;
+    let vers = vers.unwrap().parse()?;
+    let destination = match &args.hostname {
+        Some(host) => resolve(host, args.port)?,
+        None => SocketAddr::from(([255, 255, 255, 255], args.port)),
+    };
 
-    let mut list = rpcbind::RpcbindList::default();
-    rpcbind::RpcbindList::deserialize(&mut list, &mut res.as_slice())?;
+    let wait = Duration::from_secs(args.wait);
+    let responders = rpcbind::client::broadcast(destination, prog, vers, 0, &[], wait)?;
+    if responders.is_empty() {
+        return Err(format!("no host answered for program {prog} version {vers}").into());
+    }
+    for responder in responders {
+        println!("{}", responder.address);
+    }
 
-    print_rpcblist(list);
+    Ok(())
+}
+
+/// Unregister version `vers` of program `prog` from the local rpcbind server.
+fn delete(args: &Cli) -> Result<(), Error> {
+    let [prog, vers] = positional(args, 2, "-d prog vers");
+    let prog = program_number(prog.unwrap())?;
+    let vers = vers.unwrap().parse()?;
+    let netid = args.transport.as_deref().unwrap_or("");
+
+    let mut stream = connect(args, "localhost")?;
+    if !unset_using_stream(service(prog, vers, netid), &mut stream)? {
+        return Err(format!("could not delete registration for prog {prog} version {vers}").into());
+    }
 
     Ok(())
 }
 
-fn print_rpcblist(list: rpcbind::RpcbindList) {
-    for map in list.items.iter() {
-        let map = &map.rpcb_map;
+fn service(prog: u32, vers: u32, netid: &str) -> RpcService {
+    RpcService {
+        prog,
+        vers,
+        netid: netid.into(),
+        addr: "".into(),
+        owner: "".into(),
+    }
+}
+
+fn resolve(host: &str, port: u16) -> Result<SocketAddr, Error> {
+    (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| format!("no address for {host}").into())
+}
+
+/// The address at which to reach `service`, which is registered with the rpcbind server at
+/// `rpcbind_address`, which stands in for a wildcard address.
+fn service_address(service: &RpcService, rpcbind_address: SocketAddr) -> Result<SocketAddr, Error> {
+    let addr = service
+        .addr
+        .to_str()
+        .and_then(parse_universal_address)
+        .ok_or("it is not registered")?;
+
+    if addr.ip().is_unspecified() {
+        return Ok(SocketAddr::new(rpcbind_address.ip(), addr.port()));
+    }
+
+    Ok(addr)
+}
+
+/// Parse a program number, or the name of a program in `PROGRAMS`.
+fn program_number(prog: &str) -> Result<u32, Error> {
+    if let Ok(number) = prog.parse() {
+        return Ok(number);
+    }
+
+    PROGRAMS
+        .iter()
+        .find(|(_, name)| *name == prog)
+        .map(|(number, _)| *number)
+        .ok_or_else(|| format!("{prog} is unknown").into())
+}
+
+fn program_name(prog: u32) -> &'static str {
+    PROGRAMS
+        .iter()
+        .find(|(number, _)| *number == prog)
+        .map_or("-", |(_, name)| name)
+}
+
+fn print_rpcblist(list: Vec<RpcService>) {
+    println!(
+        "{:>10} {:>7} {:<9} {:<24} {:<10} owner",
+        "program", "version", "netid", "address", "service"
+    );
+    for map in list {
+        println!(
+            "{:>10} {:>7} {:<9} {:<24} {:<10} {}",
+            map.prog,
+            map.vers,
+            map.netid.to_string_lossy(),
+            map.addr.to_string_lossy(),
+            program_name(map.prog),
+            map.owner.to_string_lossy()
+        );
+    }
+}
+
+fn print_portmap_list(list: Vec<RpcService>) {
+    println!(
+        "{:>10} {:>4} {:>5} {:>6}  service",
+        "program", "vers", "proto", "port"
+    );
+    for map in list {
+        if map.netid != "tcp" && map.netid != "udp" {
+            continue;
+        }
+        let Some(addr) = map.addr.to_str().and_then(parse_universal_address) else {
+            continue;
+        };
+        println!(
+            "{:>10} {:>4} {:>5} {:>6}  {}",
+            map.prog,
+            map.vers,
+            map.netid.to_string_lossy(),
+            addr.port(),
+            program_name(map.prog)
+        );
+    }
+}
+
+fn print_health_list(list: rpcbind::HealthList) {
+    for entry in list.services.iter() {
+        let map = &entry.service;
         println!(
-            "{} {} {:?} {:?} {:?}",
-            map.prog, map.vers, map.netid, map.addr, map.owner
+            "{} {} {:?} {:?} {:?} {:?} failures={} last_seen={}",
+            map.prog,
+            map.vers,
+            map.netid,
+            map.addr,
+            map.owner,
+            entry.health,
+            entry.failures,
+            entry.last_seen
         );
     }
 }
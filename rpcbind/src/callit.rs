@@ -0,0 +1,190 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Indirect calls through CALLIT, which clients broadcast to find the hosts that run a service.
+//
+// The call that CALLIT carries is forwarded to the service registered for its program and version
+// over TCP, on the loopback address if the service listens on a wildcard address, with the caller's
+// credential, and the service's result is returned along with its universal address. A call that
+// cannot be forwarded, or that fails, is not answered over UDP, as RFC 1833 has it, so that a
+// client that broadcasts it only hears from the hosts where it succeeded. Over a connection, where
+// silence would only hold the caller until it gives up, it is answered with SYSTEM_ERR instead.
+//
+// Over UDP, each CALLIT is forwarded on a thread of its own, with shorter timeouts than over a
+// connection, so that a service that does not answer does not hold up the datagrams after it. Only
+// so many are forwarded at once, and CALLIT datagrams that arrive while that many are in flight are
+// dropped, which a client that broadcasts takes as silence.
+//
+// Calls to rpcbind itself are never forwarded, so that CALLIT cannot be used to change the
+// registry from afar, or to make rpcbind call itself. Nor are calls to the NFS services, which may
+// trust calls from the loopback address more than calls from elsewhere: a forwarded call would get
+// a remote host past an export rule that admits the local host, or reach the procedures of statd
+// that only the local lock manager may call. This is the list of upstream rpcbind's check_callit().
+
+use log::*;
+
+use std::{
+    ffi::OsStr,
+    net::{SocketAddr, TcpStream, UdpSocket},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{health, procedures::*, server::Registry, *};
+use rpc_protocol::{
+    client::do_rpc_call_with_auth, decode_call, peek_call, server::RpcResult, OpaqueAuth,
+};
+
+/// How long a service has to accept a call forwarded from a connection, and to answer it.
+pub(crate) const FORWARD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a service has to accept a call forwarded from a datagram, and to answer it.
+const UDP_FORWARD_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The most calls from datagrams that are forwarded at once.
+const MAX_UDP_FORWARDS: usize = 16;
+
+/// The programs whose calls are never forwarded, besides rpcbind's own.
+const UNFORWARDED: [(u32, &str); 4] = [
+    (100003, "NFS"),
+    (100005, "MOUNT"),
+    (100021, "NLM"),
+    (100024, "NSM"),
+];
+
+/// Forward the call that `args` describes, with the credential `cred`, to the service in `registry`
+/// registered for its program and version over TCP, which has `timeout` to accept it and as long to
+/// answer it. Returns the service's result, or None if there is no such service, or the call failed.
+pub(crate) fn forward(
+    registry: &Mutex<Registry>,
+    args: &RpcbRmtcallArgs,
+    cred: OpaqueAuth,
+    timeout: Duration,
+) -> Option<RpcbRmtcallRes> {
+    if let Some((_, name)) = UNFORWARDED.iter().find(|(prog, _)| *prog == args.prog) {
+        debug!("CALLIT: refusing to forward a call to the {name} program");
+        return None;
+    }
+
+    let (service, address) = {
+        let registry = registry.lock().unwrap();
+        let service = ["tcp", "tcp6"]
+            .into_iter()
+            .find_map(|netid| registry.get_service(args.prog, args.vers, OsStr::new(netid)))?;
+        (service.clone(), health::probe_address(service)?)
+    };
+
+    debug!(
+        "CALLIT: forwarding procedure {} of program {} version {} to {address}",
+        args.proc, args.prog, args.vers
+    );
+    match call(address, args, cred, timeout) {
+        Ok(results) => Some(RpcbRmtcallRes {
+            addr: service.addr,
+            results,
+        }),
+        Err(e) => {
+            debug!("CALLIT: the call to {address} failed: {e}");
+            None
+        }
+    }
+}
+
+fn call(
+    address: SocketAddr,
+    args: &RpcbRmtcallArgs,
+    cred: OpaqueAuth,
+    timeout: Duration,
+) -> Result<Vec<u8>, rpc_protocol::Error> {
+    let mut stream = TcpStream::connect_timeout(&address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let (_, results) = do_rpc_call_with_auth(
+        &mut stream,
+        args.prog,
+        args.vers,
+        args.proc,
+        cred,
+        OpaqueAuth::none(),
+        &args.args,
+    )?;
+
+    Ok(results)
+}
+
+/// Answer the NULL and CALLIT calls that arrive on `socket`, broadcast or not, forever. Other calls
+/// are ignored.
+pub(crate) fn serve_udp(socket: UdpSocket, registry: Arc<Mutex<Registry>>) {
+    let socket = Arc::new(socket);
+    let forwarding = Arc::new(AtomicUsize::new(0));
+    let mut buf = vec![0; MAX_DATAGRAM];
+    loop {
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) => {
+                warn!("Error receiving a datagram: {e}");
+                continue;
+            }
+        };
+        let datagram = &buf[..len];
+
+        let is_callit = peek_call(datagram)
+            .is_ok_and(|call| call.prog == RPCBPROG && call.proc == RPCBVERS::RPCBPROC_CALLIT);
+        if !is_callit {
+            reply(&socket, answer(datagram, &registry), from);
+            continue;
+        }
+
+        if forwarding.fetch_add(1, Ordering::Relaxed) >= MAX_UDP_FORWARDS {
+            forwarding.fetch_sub(1, Ordering::Relaxed);
+            debug!("Dropped a CALLIT from {from}: {MAX_UDP_FORWARDS} calls are being forwarded");
+            continue;
+        }
+
+        let (socket, registry, forwarding) = (socket.clone(), registry.clone(), forwarding.clone());
+        let datagram = datagram.to_vec();
+        thread::spawn(move || {
+            reply(&socket, answer(&datagram, &registry), from);
+            forwarding.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+}
+
+fn reply(socket: &UdpSocket, reply: Option<Vec<u8>>, to: SocketAddr) {
+    let Some(reply) = reply else {
+        return;
+    };
+
+    if let Err(e) = socket.send_to(&reply, to) {
+        debug!("Could not reply to {to}: {e}");
+    }
+}
+
+/// The reply to the call in `datagram`, if it is answered.
+fn answer(datagram: &[u8], registry: &Mutex<Registry>) -> Option<Vec<u8>> {
+    let call = decode_call(datagram).ok()?;
+    if call.get_program() != RPCBPROG || !(RPCBVERS::VERSION..=4).contains(&call.get_version()) {
+        return None;
+    }
+
+    let result = match call.get_procedure() {
+        0 => Vec::new(),
+        RPCBVERS::RPCBPROC_CALLIT => {
+            let mut args = RpcbRmtcallArgs::default();
+            let mut arg = call.arg;
+            args.deserialize(&mut arg).ok()?;
+            let cred = call.get_credential().clone();
+            forward(registry, &args, cred, UDP_FORWARD_TIMEOUT)?.serialize_alloc()
+        }
+        _ => return None,
+    };
+
+    // Datagrams carry no record mark:
+    let reply = RpcResult::Success(result).encode_reply(call.get_xid(), OpaqueAuth::none());
+    Some(reply[4..].to_vec())
+}
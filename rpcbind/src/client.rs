@@ -4,9 +4,10 @@
 use log::*;
 
 use std::{
-    io::{Read, Write},
-    net::TcpStream,
+    io::{self, Read, Write},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
     os::unix::net::UnixStream,
+    time::{Duration, Instant, SystemTime},
 };
 
 use crate::{procedures::*, RpcbindServerAddress, *};
@@ -29,9 +30,39 @@ pub fn set(
             let mut stream = TcpStream::connect(addr)?;
             set_using_stream(new_service, &mut stream)
         }
+        RpcbindServerAddress::Udp(addr) => Err(Error::Io(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Services cannot be registered over UDP, at {addr}"),
+        ))),
     }
 }
 
+/// Register version `vers` of program `prog`, listening for TCP connections at `address`, with the
+/// RPCBIND server at `server_address`, under each netid that reaches it (see `tcp_registrations()`).
+/// Returns whether every registration was accepted.
+pub fn set_tcp(
+    prog: u32,
+    vers: u32,
+    address: SocketAddr,
+    server_address: RpcbindServerAddress,
+) -> Result<bool, rpc_protocol::Error> {
+    let mut all_set = true;
+
+    for (netid, addr) in tcp_registrations(address) {
+        let service = rpcbind::RpcService {
+            prog,
+            vers,
+            netid: netid.into(),
+            addr: addr.into(),
+            owner: "superuser".into(),
+        };
+
+        all_set &= set(service, server_address.clone())?;
+    }
+
+    Ok(all_set)
+}
+
 pub fn set_using_stream<S: Read + Write>(
     new_service: rpcbind::RpcService,
     stream: &mut S,
@@ -52,6 +83,28 @@ pub fn set_using_stream<S: Read + Write>(
     }
 }
 
+/// Call the UNSET RPC to remove the registrations of `service`'s program and version under its
+/// netid, or under every netid if it is empty. Returns whether there were any.
+pub fn unset_using_stream<S: Read + Write>(
+    service: rpcbind::RpcService,
+    stream: &mut S,
+) -> Result<bool, rpc_protocol::Error> {
+    let arg = service.serialize_alloc();
+
+    let res = do_rpc_call(
+        stream,
+        RPCBPROG,
+        RPCBVERS::VERSION,
+        RPCBVERS::RPCBPROC_UNSET,
+        arg.as_slice(),
+    )?;
+
+    match res.as_slice() {
+        &[0, 0, 0, 0] => Ok(false),
+        _ => Ok(true),
+    }
+}
+
 pub fn getaddr_using_stream<S: Read + Write>(
     service: rpcbind::RpcService,
     stream: &mut S,
@@ -72,3 +125,212 @@ pub fn getaddr_using_stream<S: Read + Write>(
         Err(_) => Err(Error::Protocol(ProtocolError::Decode)),
     }
 }
+
+/// Call the DUMP RPC for the list of every registered service.
+pub fn dump_using_stream<S: Read + Write>(
+    stream: &mut S,
+) -> Result<Vec<rpcbind::RpcService>, rpc_protocol::Error> {
+    let res = do_rpc_call(
+        stream,
+        RPCBPROG,
+        RPCBVERS::VERSION,
+        RPCBVERS::RPCBPROC_DUMP,
+        &[],
+    )?;
+
+    let mut list = rpcbind::RpcbindList::default();
+    match list.deserialize(&mut res.as_slice()) {
+        Ok(_) => Ok(list.items.into_iter().map(|item| item.rpcb_map).collect()),
+        Err(_) => Err(Error::Protocol(ProtocolError::Decode)),
+    }
+}
+
+/// Call the DUMP_HEALTH RPC, which only this crate's server has, for the list of every registered
+/// service with its health, including those flagged as dead.
+pub fn dump_health_using_stream<S: Read + Write>(
+    stream: &mut S,
+) -> Result<rpcbind::HealthList, rpc_protocol::Error> {
+    let res = do_rpc_call(
+        stream,
+        RPCBPROG,
+        RPCBVERS::VERSION,
+        RPCBVERS::RPCBPROC_DUMP_HEALTH,
+        &[],
+    )?;
+
+    let mut list = rpcbind::HealthList::default();
+    match list.deserialize(&mut res.as_slice()) {
+        Ok(_) => Ok(list),
+        Err(_) => Err(Error::Protocol(ProtocolError::Decode)),
+    }
+}
+
+/// Ask the RPCBIND server on `host` for the TCP address of version `vers` of program `prog`, in the
+/// address family ("tcp" or "tcp6") that the server was reached over.
+///
+/// If the server registered a wildcard address, the address of `host` is returned with the
+/// registered port. Connecting to the RPCBIND server times out after `timeout`.
+pub fn lookup(
+    host: &str,
+    prog: u32,
+    vers: u32,
+    timeout: Duration,
+) -> Result<SocketAddr, rpc_protocol::Error> {
+    debug!("performing RPCBIND GetAddr call to {host} for program {prog} version {vers}");
+
+    let rpcbind_address = (host, 111)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No address for {host}")))?;
+
+    let mut stream = TcpStream::connect_timeout(&rpcbind_address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let service = rpcbind::RpcService {
+        prog,
+        vers,
+        netid: tcp_netid(rpcbind_address).into(),
+        addr: "".into(),
+        owner: "".into(),
+    };
+
+    let addr = getaddr_using_stream(service, &mut stream)?;
+    let Some(addr) = addr.to_str().and_then(parse_universal_address) else {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Program {prog} version {vers} is not registered on {host}"),
+        )));
+    };
+
+    let ip = if addr.ip().is_unspecified() {
+        rpcbind_address.ip()
+    } else {
+        addr.ip()
+    };
+
+    Ok(SocketAddr::new(ip, addr.port()))
+}
+
+/// A host that answered a broadcast call, and the result of the call there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Responder {
+    /// The address of the service that was called, on the host that answered.
+    pub address: SocketAddr,
+
+    /// The result of the call.
+    pub result: Vec<u8>,
+}
+
+/// Call procedure `proc` of version `vers` of program `prog` with `arg` through the CALLIT RPC of
+/// each RPCBIND server that receives a UDP datagram sent to `destination`, such as the broadcast
+/// address 255.255.255.255 on port 111, and collect the answers that arrive within `wait`.
+///
+/// Servers only answer if the call succeeded, so hosts that do not run the service stay silent, and
+/// no answer at all is not an error.
+pub fn broadcast(
+    destination: SocketAddr,
+    prog: u32,
+    vers: u32,
+    proc: u32,
+    arg: &[u8],
+    wait: Duration,
+) -> io::Result<Vec<Responder>> {
+    debug!("broadcasting CALLIT to {destination} for procedure {proc} of program {prog}");
+
+    let socket = match destination {
+        SocketAddr::V4(_) => {
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+            socket.set_broadcast(true)?;
+            socket
+        }
+        SocketAddr::V6(_) => UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?,
+    };
+
+    // Datagrams are not matched to a connection, so the XID tells the replies to this call apart
+    // from any others:
+    let xid = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |t| t.subsec_nanos())
+        ^ std::process::id();
+    let message = RpcMessage {
+        xid,
+        body: RpcMessageBody::Call(CallBody {
+            rpcvers: 2,
+            prog: RPCBPROG,
+            vers: RPCBVERS::VERSION,
+            proc: RPCBVERS::RPCBPROC_CALLIT,
+            cred: OpaqueAuth::none(),
+            verf: OpaqueAuth::none(),
+        }),
+    };
+    let args = rpcbind::RpcbRmtcallArgs {
+        prog,
+        vers,
+        proc,
+        args: arg.to_vec(),
+    };
+    let mut call = message.serialize_alloc();
+    call.append(&mut args.serialize_alloc());
+    socket.send_to(&call, destination)?;
+
+    let deadline = Instant::now() + wait;
+    let mut responders: Vec<Responder> = Vec::new();
+    let mut buf = vec![0; MAX_DATAGRAM];
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        socket.set_read_timeout(Some(deadline - now))?;
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                break
+            }
+            Err(e) => return Err(e),
+        };
+
+        let Some(responder) = decode_callit_reply(xid, &buf[..len], from) else {
+            debug!("ignoring a datagram from {from}");
+            continue;
+        };
+        if !responders.iter().any(|r| r.address == responder.address) {
+            responders.push(responder);
+        }
+    }
+
+    Ok(responders)
+}
+
+/// The responder in the reply `data` from `from` to the CALLIT call `xid`, if it is one.
+fn decode_callit_reply(xid: u32, data: &[u8], from: SocketAddr) -> Option<Responder> {
+    let mut message = RpcMessage::default();
+    let mut rest = data;
+    message.deserialize(&mut rest).ok()?;
+    if message.xid != xid {
+        return None;
+    }
+    let RpcMessageBody::Reply(ReplyBody::Accepted(AcceptedReply {
+        reply_data: AcceptedReplyBody::Success(_),
+        ..
+    })) = message.body
+    else {
+        return None;
+    };
+
+    let mut res = rpcbind::RpcbRmtcallRes::default();
+    res.deserialize(&mut rest).ok()?;
+    // The service may listen on a wildcard address, so only its port is taken from its address:
+    let port = res.addr.to_str().and_then(parse_universal_address)?.port();
+
+    Some(Responder {
+        address: SocketAddr::new(from.ip(), port),
+        result: res.results,
+    })
+}
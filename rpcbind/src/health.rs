@@ -0,0 +1,208 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Detection of registered services that have stopped running.
+//
+// A service that exits without calling UNSET leaves its registration behind, and clients that ask
+// for its address are sent to a port that nobody listens on. The sweeper periodically calls the
+// NULL procedure of every registered service, and once a service has failed to answer for long
+// enough, it is either removed from the registry or flagged as dead. Flagged services are hidden
+// from GETADDR and DUMP, but are still listed by DUMP_HEALTH, and become visible again if they
+// start answering.
+
+use log::*;
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{procedures::*, server::Registry, *};
+use rpc_protocol::client::do_rpc_call;
+
+/// How the sweeper probes registered services.
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    /// How often every registered service is probed.
+    pub interval: Duration,
+
+    /// How long to wait for a service to accept a connection and answer its NULL procedure.
+    pub timeout: Duration,
+
+    /// How long a service must have been failing probes before it is considered dead.
+    pub dead_after: Duration,
+
+    /// Whether dead services are removed from the registry, rather than kept and flagged.
+    pub remove_dead: bool,
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(5),
+            dead_after: Duration::from_secs(120),
+            remove_dead: true,
+        }
+    }
+}
+
+/// What the sweeper knows about a registered service.
+#[derive(Debug, Default)]
+pub(crate) struct Health {
+    pub(crate) state: ServiceHealth,
+
+    /// The number of consecutive probes that failed.
+    failures: u32,
+
+    /// When the current run of failed probes started.
+    unreachable_since: Option<Instant>,
+
+    /// When the service last answered a probe.
+    last_seen: Option<SystemTime>,
+}
+
+impl Health {
+    pub(crate) fn is_dead(&self) -> bool {
+        self.state == ServiceHealth::Dead
+    }
+
+    pub(crate) fn encode(&self, service: &RpcService) -> RpcServiceHealth {
+        let last_seen = self
+            .last_seen
+            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map_or(0, |t| t.as_secs());
+
+        RpcServiceHealth {
+            service: service.clone(),
+            health: self.state,
+            failures: self.failures,
+            last_seen,
+        }
+    }
+}
+
+/// Start a thread that probes the services in `registry` forever.
+pub(crate) fn spawn_sweeper(registry: Arc<Mutex<Registry>>, check: HealthCheck) {
+    thread::spawn(move || loop {
+        thread::sleep(check.interval);
+        sweep(&registry, &check);
+    });
+}
+
+/// Probe every service in `registry` once. The registry is not locked while a probe is in
+/// progress, so a slow service does not hold up calls to the rpcbind server.
+fn sweep(registry: &Mutex<Registry>, check: &HealthCheck) {
+    let targets: Vec<_> = registry
+        .lock()
+        .unwrap()
+        .services()
+        .filter_map(|service| Some((service.clone(), probe_address(service)?)))
+        .collect();
+
+    for (service, addr) in targets {
+        let res = probe(&service, addr, check.timeout);
+        record_probe(&mut registry.lock().unwrap(), &service, res, check);
+    }
+}
+
+/// The address at which `service` can be probed, or called, if it is a TCP service other than
+/// rpcbind itself. Services registered with a wildcard address are reached on the loopback address.
+pub(crate) fn probe_address(service: &RpcService) -> Option<SocketAddr> {
+    if service.prog == RPCBPROG || (service.netid != "tcp" && service.netid != "tcp6") {
+        return None;
+    }
+
+    let addr = parse_universal_address(service.addr.to_str()?)?;
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => Ipv4Addr::LOCALHOST.into(),
+        IpAddr::V6(ip) if ip.is_unspecified() => Ipv6Addr::LOCALHOST.into(),
+        ip => ip,
+    };
+
+    Some(SocketAddr::new(ip, addr.port()))
+}
+
+fn probe(
+    service: &RpcService,
+    addr: SocketAddr,
+    timeout: Duration,
+) -> Result<(), rpc_protocol::Error> {
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    do_rpc_call(&mut stream, service.prog, service.vers, 0, &[])?;
+
+    Ok(())
+}
+
+fn record_probe(
+    registry: &mut Registry,
+    service: &RpcService,
+    res: Result<(), rpc_protocol::Error>,
+    check: &HealthCheck,
+) {
+    // The service may have been replaced while it was being probed:
+    let Some(health) = registry.health_mut(service) else {
+        return;
+    };
+
+    let name = format!(
+        "program {} version {} at {:?}",
+        service.prog, service.vers, service.addr
+    );
+
+    let e = match res {
+        Ok(()) => {
+            if matches!(
+                health.state,
+                ServiceHealth::Unreachable | ServiceHealth::Dead
+            ) {
+                info!(
+                    "{name} is reachable again after {} failed probes",
+                    health.failures
+                );
+            }
+
+            *health = Health {
+                state: ServiceHealth::Reachable,
+                failures: 0,
+                unreachable_since: None,
+                last_seen: Some(SystemTime::now()),
+            };
+            return;
+        }
+        Err(e) => e,
+    };
+
+    health.failures += 1;
+    let since = *health.unreachable_since.get_or_insert_with(Instant::now);
+
+    if health.is_dead() || since.elapsed() < check.dead_after {
+        if matches!(
+            health.state,
+            ServiceHealth::Unprobed | ServiceHealth::Reachable
+        ) {
+            info!("{name} is unreachable: {e}");
+            health.state = ServiceHealth::Unreachable;
+        }
+        return;
+    }
+
+    if check.remove_dead {
+        warn!(
+            "removing {name}, which has been unreachable for {:?}",
+            since.elapsed()
+        );
+        registry.remove(service);
+    } else {
+        warn!(
+            "flagging {name} as dead, since it has been unreachable for {:?}",
+            since.elapsed()
+        );
+        health.state = ServiceHealth::Dead;
+    }
+}
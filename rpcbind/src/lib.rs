@@ -1,14 +1,65 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+mod callit;
 pub mod client;
+pub mod health;
 pub mod server;
 
 include!(concat!(env!("OUT_DIR"), "/rpcbind.rs"));
 pub use self::rpcbind::*;
 
-/// An RPCBIND Server tends to listen both on a Unix socket and a TCP socket.
+/// An RPCBIND Server tends to listen both on a Unix socket and a TCP socket. On a UDP socket, it
+/// only answers NULL and CALLIT calls, such as those that clients broadcast.
+#[derive(Clone)]
 pub enum RpcbindServerAddress {
     Unix(String),
     Tcp(String),
+    Udp(String),
+}
+
+/// The size of the largest UDP datagram, and so of the largest call or reply sent in one.
+const MAX_DATAGRAM: usize = 65535;
+
+/// Parse a universal address of the "tcp", "udp", "tcp6", or "udp6" netid, as returned by GETADDR.
+/// It has the form "h.p1.p2", where h is the IPv4 or IPv6 address in its usual text form, and the
+/// port is p1 * 256 + p2 (RFC 5665, section 5.2.3).
+pub fn parse_universal_address(addr: &str) -> Option<SocketAddr> {
+    let mut fields = addr.rsplitn(3, '.');
+    let p2 = fields.next()?.parse::<u8>().ok()?;
+    let p1 = fields.next()?.parse::<u8>().ok()?;
+    let ip = fields.next()?.parse::<IpAddr>().ok()?;
+
+    Some(SocketAddr::new(ip, u16::from_be_bytes([p1, p2])))
+}
+
+/// The universal address of `addr`: the inverse of `parse_universal_address()`.
+pub fn universal_address(addr: SocketAddr) -> String {
+    let [p1, p2] = addr.port().to_be_bytes();
+
+    format!("{}.{p1}.{p2}", addr.ip())
+}
+
+/// The netid of TCP over the address family of `addr`.
+pub fn tcp_netid(addr: SocketAddr) -> &'static str {
+    match addr {
+        SocketAddr::V4(_) => "tcp",
+        SocketAddr::V6(_) => "tcp6",
+    }
+}
+
+/// The netids and universal addresses under which to register a TCP service listening on `addr`.
+/// A service listening on the IPv6 wildcard address also accepts IPv4 connections (see
+/// `rpc_protocol::server::bind_tcp()`), so it is registered for both "tcp6" and "tcp".
+pub fn tcp_registrations(addr: SocketAddr) -> Vec<(&'static str, String)> {
+    let mut registrations = vec![(tcp_netid(addr), universal_address(addr))];
+
+    if addr.is_ipv6() && addr.ip().is_unspecified() {
+        let v4 = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), addr.port());
+        registrations.push(("tcp", universal_address(v4)));
+    }
+
+    registrations
 }
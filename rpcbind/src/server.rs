@@ -5,47 +5,185 @@
 
 use log::*;
 
-use std::ffi::OsString;
-use std::net::TcpListener;
-use std::os::unix::net::UnixListener;
+use std::ffi::{OsStr, OsString};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::os::unix::{fs::PermissionsExt, net::UnixListener};
+use std::sync::{Arc, Mutex};
 
+use crate::callit;
+use crate::health::{self, Health, HealthCheck};
 use crate::*;
 use crate::{procedures::*, RpcbindServerAddress};
 use rpc_protocol::{server::*, Call};
 
-pub fn main(addr: RpcbindServerAddress) {
-    let service_list = default_service_list();
-
-    let procedures: Vec<Option<RpcProcedure<rpcbind::RpcbindList>>> =
-        vec![None, Some(set), None, Some(getaddr), Some(dump)];
-    let mut server = RpcProgram::new(RPCBPROG, RPCBVERS::VERSION, 4, procedures, service_list);
-
-    match addr {
-        RpcbindServerAddress::Tcp(addr) => {
-            let listener = TcpListener::bind(addr).unwrap();
-            server.run_blocking_tcp_server(listener);
-        }
-        RpcbindServerAddress::Unix(addr) => {
-            // Not necessary to check for errors in remove_file() because ENOENT is expected, and
-            // a failure to remove the file (while it already exists) will result in an error in
-            // bind().
-            let _ = std::fs::remove_file(&addr);
-            let listener = UnixListener::bind(addr).unwrap();
-            server.run_blocking_tcp_server(listener);
-        }
+/// The services registered with the server, along with what the sweeper knows of their health.
+pub(crate) struct Registry {
+    entries: Vec<(rpcbind::RpcService, Health)>,
+}
+
+impl Registry {
+    /// A registry holding only rpcbind itself, listening for TCP connections at `address`.
+    fn new(address: SocketAddr) -> Self {
+        let entries = tcp_registrations(address)
+            .into_iter()
+            .map(|(netid, addr)| {
+                let service = rpcbind::RpcService {
+                    prog: RPCBPROG,
+                    vers: RPCBVERS::VERSION,
+                    netid: netid.into(),
+                    addr: addr.into(),
+                    owner: OsString::from("superuser"),
+                };
+                (service, Health::default())
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    /// All registered services, including dead ones.
+    pub(crate) fn services(&self) -> impl Iterator<Item = &rpcbind::RpcService> {
+        self.entries.iter().map(|(service, _)| service)
+    }
+
+    /// Returns the service specified by `program`, `version`, and `netid`, or none if there is no
+    /// match or the service is dead. An empty `netid` matches any.
+    pub(crate) fn get_service(
+        &self,
+        program: u32,
+        version: u32,
+        netid: &OsStr,
+    ) -> Option<&rpcbind::RpcService> {
+        self.entries
+            .iter()
+            .find(|(service, health)| {
+                service.prog == program
+                    && service.vers == version
+                    && (netid.is_empty() || service.netid == netid)
+                    && !health.is_dead()
+            })
+            .map(|(service, _)| service)
     }
+
+    pub(crate) fn health_mut(&mut self, service: &rpcbind::RpcService) -> Option<&mut Health> {
+        self.entries
+            .iter_mut()
+            .find(|(s, _)| s == service)
+            .map(|(_, health)| health)
+    }
+
+    pub(crate) fn remove(&mut self, service: &rpcbind::RpcService) {
+        self.entries.retain(|(s, _)| s != service);
+    }
+}
+
+type SharedRegistry = Arc<Mutex<Registry>>;
+
+/// What the procedures share: the registry, and who may change it over a UNIX domain socket.
+#[derive(Clone)]
+struct State {
+    registry: SharedRegistry,
+    policy: PeerPolicy,
+}
+
+/// Run an rpcbind server at `addr`. If `health_check` is given, registered services are probed in
+/// the background, and those that stop answering are removed or flagged.
+pub fn main(addr: RpcbindServerAddress, health_check: Option<HealthCheck>) {
+    serve(&[addr], health_check, PeerPolicy::default());
+}
+
+/// Like `main()`, listening at each of `addrs`, one thread each. rpcbind registers itself at the
+/// first TCP address.
+///
+/// Over a UNIX domain socket, only the callers that `policy` admits may SET and UNSET services, and
+/// each of those calls is recorded in the audit log. Anyone may connect to the socket to look up
+/// services. Over TCP, the callers cannot be told apart, so any may register. Over UDP, only NULL
+/// and CALLIT calls are answered, for clients that broadcast them.
+pub fn serve(
+    addrs: &[RpcbindServerAddress],
+    health_check: Option<HealthCheck>,
+    policy: PeerPolicy,
+) {
+    let tcp_address = addrs
+        .iter()
+        .find_map(|addr| match addr {
+            RpcbindServerAddress::Tcp(addr) => Some(
+                addr.to_socket_addrs()
+                    .ok()
+                    .and_then(|mut addrs| addrs.next())
+                    .unwrap_or_else(|| panic!("Could not resolve {addr}")),
+            ),
+            RpcbindServerAddress::Unix(_) | RpcbindServerAddress::Udp(_) => None,
+        })
+        .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 111)));
+
+    let registry = Arc::new(Mutex::new(Registry::new(tcp_address)));
+    if let Some(check) = health_check {
+        health::spawn_sweeper(registry.clone(), check);
+    }
+    let state = State { registry, policy };
+
+    let threads: Vec<_> = addrs
+        .iter()
+        .map(|addr| {
+            match addr {
+                RpcbindServerAddress::Tcp(_) => {
+                    let mut server = program(state.clone());
+                    let listener = bind_tcp(tcp_address).unwrap();
+                    std::thread::spawn(move || server.run_blocking_tcp_server(listener))
+                }
+                RpcbindServerAddress::Udp(addr) => {
+                    let socket = UdpSocket::bind(addr).unwrap();
+                    socket.set_broadcast(true).unwrap();
+                    let registry = state.registry.clone();
+                    std::thread::spawn(move || callit::serve_udp(socket, registry))
+                }
+                RpcbindServerAddress::Unix(addr) => {
+                    // Not necessary to check for errors in remove_file() because ENOENT is
+                    // expected, and a failure to remove the file (while it already exists) will
+                    // result in an error in bind().
+                    let mut server = program(state.clone());
+                    let _ = std::fs::remove_file(addr);
+                    let listener = UnixListener::bind(addr).unwrap();
+                    std::fs::set_permissions(addr, std::fs::Permissions::from_mode(0o666)).unwrap();
+                    std::thread::spawn(move || server.run_blocking_tcp_server(listener))
+                }
+            }
+        })
+        .collect();
+
+    for thread in threads {
+        let _ = thread.join();
+    }
+}
+
+fn program(state: State) -> RpcProgram<State> {
+    let mut procedures: Vec<Option<RpcProcedure<State>>> =
+        vec![None; RPCBVERS::RPCBPROC_DUMP_HEALTH as usize + 1];
+    procedures[RPCBVERS::RPCBPROC_SET as usize] = Some(set);
+    procedures[RPCBVERS::RPCBPROC_UNSET as usize] = Some(unset);
+    procedures[RPCBVERS::RPCBPROC_GETADDR as usize] = Some(getaddr);
+    procedures[RPCBVERS::RPCBPROC_DUMP as usize] = Some(dump);
+    procedures[RPCBVERS::RPCBPROC_CALLIT as usize] = Some(typed_procedure(callit));
+    procedures[RPCBVERS::RPCBPROC_DUMP_HEALTH as usize] = Some(dump_health);
+
+    let mut server = RpcProgram::new(RPCBPROG, RPCBVERS::VERSION, 4, procedures, state);
+    server.set_procedure_names(proc_name);
+
+    server
 }
 
-/// Implementation of the getaddr RPC. This loops over the `service_list` to see if the service
-/// requested in the `arg` is in the list, and returns its address if so. Otherwise, it returns an
-/// empty string.
-fn getaddr(call: &Call, service_list: &mut rpcbind::RpcbindList) -> RpcResult {
+/// Implementation of the getaddr RPC. This looks up the service requested in the `arg` in the
+/// registry, and returns its address if it is found and not dead. Otherwise, it returns an empty
+/// string.
+fn getaddr(call: &Call, state: &mut State) -> RpcResult {
     let mut requested = rpcbind::RpcService::default();
     let mut arg = call.arg;
     rpcbind::RpcService::deserialize(&mut requested, &mut arg).unwrap();
     debug!("GETADDR Call: {requested:?}");
 
-    if let Some(service) = get_service(requested.prog, requested.vers, service_list) {
+    let registry = state.registry.lock().unwrap();
+    if let Some(service) = registry.get_service(requested.prog, requested.vers, &requested.netid) {
         let address = rpcbind::RpcbString {
             contents: service.addr.clone(),
         };
@@ -61,8 +199,9 @@ fn getaddr(call: &Call, service_list: &mut rpcbind::RpcbindList) -> RpcResult {
     RpcResult::Success(empty.serialize_alloc())
 }
 
-/// Implementation of the set RPC. This adds a service to the list.
-fn set(call: &Call, service_list: &mut rpcbind::RpcbindList) -> RpcResult {
+/// Implementation of the set RPC. This adds a service to the registry, replacing a dead
+/// registration of the same program, version, and netid.
+fn set(call: &Call, state: &mut State) -> RpcResult {
     let mut new_service = rpcbind::RpcService::default();
     let mut arg = call.arg;
     if new_service.deserialize(&mut arg).is_err() {
@@ -71,8 +210,20 @@ fn set(call: &Call, service_list: &mut rpcbind::RpcbindList) -> RpcResult {
 
     debug!("SET call: {new_service:?}");
 
+    if !state
+        .policy
+        .check(call, format_args!("SET {}", describe(&new_service)))
+    {
+        return RpcResult::Success(vec![0, 0, 0, 0]);
+    }
+
+    let mut registry = state.registry.lock().unwrap();
+
     // Make sure that this service is not already registered:
-    if get_service(new_service.prog, new_service.vers, service_list).is_some() {
+    if registry
+        .get_service(new_service.prog, new_service.vers, &new_service.netid)
+        .is_some()
+    {
         // If it is, return False to the caller:
         return RpcResult::Success(vec![0, 0, 0, 0]);
     }
@@ -82,54 +233,94 @@ fn set(call: &Call, service_list: &mut rpcbind::RpcbindList) -> RpcResult {
         return RpcResult::Success(vec![0, 0, 0, 0]);
     }
 
-    service_list.items.push(rpcbind::RpcbindItem {
-        rpcb_map: new_service,
+    registry.entries.retain(|(service, _)| {
+        service.prog != new_service.prog
+            || service.vers != new_service.vers
+            || service.netid != new_service.netid
     });
+    registry.entries.push((new_service, Health::default()));
 
     RpcResult::Success(vec![0, 0, 0, 1])
 }
 
-/// Implementation of the dump RPC. This returns the entire known `service_list`.
-fn dump(_call: &Call, service_list: &mut rpcbind::RpcbindList) -> RpcResult {
-    let data = service_list.serialize_alloc();
+/// Implementation of the unset RPC. This removes the registrations of a program and version under a
+/// netid, or under every netid if the netid is empty, and returns whether there were any.
+fn unset(call: &Call, state: &mut State) -> RpcResult {
+    let mut old_service = rpcbind::RpcService::default();
+    let mut arg = call.arg;
+    if old_service.deserialize(&mut arg).is_err() {
+        return RpcResult::GarbageArgs;
+    }
+
+    debug!("UNSET call: {old_service:?}");
+
+    if !state
+        .policy
+        .check(call, format_args!("UNSET {}", describe(&old_service)))
+    {
+        return RpcResult::Success(vec![0, 0, 0, 0]);
+    }
 
-    RpcResult::Success(data)
+    let mut registry = state.registry.lock().unwrap();
+    let before = registry.entries.len();
+    registry.entries.retain(|(service, _)| {
+        service.prog != old_service.prog
+            || service.vers != old_service.vers
+            || !(old_service.netid.is_empty() || service.netid == old_service.netid)
+    });
+
+    if registry.entries.len() == before {
+        return RpcResult::Success(vec![0, 0, 0, 0]);
+    }
+
+    RpcResult::Success(vec![0, 0, 0, 1])
 }
 
-fn default_service_list() -> rpcbind::RpcbindList {
-    let item = rpcbind::RpcbindItem {
-        rpcb_map: rpcbind::RpcService {
-            prog: 100000,
-            vers: 3,
-            netid: OsString::from("tcp"),
-            addr: OsString::from("0.0.0.0.111"),
-            owner: OsString::from("superuser"),
-        },
-    };
+/// A service, as named in the audit log.
+fn describe(service: &rpcbind::RpcService) -> String {
+    format!(
+        "program {} version {} netid {:?} address {:?}",
+        service.prog, service.vers, service.netid, service.addr
+    )
+}
+
+/// Implementation of the dump RPC. This returns every registered service that is not dead.
+fn dump(_call: &Call, state: &mut State) -> RpcResult {
+    let registry = state.registry.lock().unwrap();
+    let items = registry
+        .entries
+        .iter()
+        .filter(|(_, health)| !health.is_dead())
+        .map(|(service, _)| rpcbind::RpcbindItem {
+            rpcb_map: service.clone(),
+        })
+        .collect();
 
-    rpcbind::RpcbindList { items: vec![item] }
+    RpcResult::Success(rpcbind::RpcbindList { items }.serialize_alloc())
 }
 
-/// Returns the service specified by `program` and `version` from the `service_list`, or none if
-/// there is no match.
-fn get_service(
-    program: u32,
-    version: u32,
-    service_list: &rpcbind::RpcbindList,
-) -> Option<&rpcbind::RpcService> {
-    for service in &service_list.items {
-        let service = &service.rpcb_map;
-
-        if program != service.prog {
-            continue;
-        }
-
-        if version != service.vers {
-            continue;
-        }
-
-        return Some(service);
-    }
+/// Implementation of the CALLIT RPC, which forwards the call it carries to a registered service
+/// (see `callit`). Over a connection, a call that cannot be forwarded, or that fails, is answered
+/// with SYSTEM_ERR.
+fn callit(
+    call: &Call,
+    args: rpcbind::RpcbRmtcallArgs,
+    state: &mut State,
+) -> Result<rpcbind::RpcbRmtcallRes, RpcResult> {
+    let cred = call.get_credential().clone();
+    callit::forward(&state.registry, &args, cred, callit::FORWARD_TIMEOUT)
+        .ok_or(RpcResult::SystemErr)
+}
+
+/// Implementation of the DUMP_HEALTH extension. This returns every registered service, including
+/// dead ones, along with its health.
+fn dump_health(_call: &Call, state: &mut State) -> RpcResult {
+    let registry = state.registry.lock().unwrap();
+    let services = registry
+        .entries
+        .iter()
+        .map(|(service, health)| health.encode(service))
+        .collect();
 
-    None
+    RpcResult::Success(rpcbind::HealthList { services }.serialize_alloc())
 }
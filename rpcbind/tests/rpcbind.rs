@@ -1,17 +1,31 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
-use std::os::unix::net::UnixStream;
+use std::{
+    net::{SocketAddr, TcpListener, TcpStream, UdpSocket},
+    os::unix::net::UnixStream,
+    process::Command,
+    time::{Duration, Instant},
+};
 
-use rpcbind::RpcbindServerAddress;
+use rpc_protocol::{
+    client::do_rpc_call,
+    server::{PeerPolicy, RpcProgram, RpcResult},
+    Call, RpcCallError,
+};
+use rpcbind::{client::Responder, health::HealthCheck, RpcbindServerAddress, ServiceHealth};
+use test_harness::{assert_call_error, connect_unix, spawn_tcp};
 
 #[test]
 fn set_and_getaddr() {
     std::thread::spawn(|| {
-        rpcbind::server::main(RpcbindServerAddress::Unix("rpcbind.socket".to_string()));
+        rpcbind::server::main(
+            RpcbindServerAddress::Unix("rpcbind.socket".to_string()),
+            None,
+        );
     });
 
-    let mut stream = wait_for_server("rpcbind.socket");
+    let mut stream = connect_unix("rpcbind.socket");
 
     let new_service = rpcbind::RpcService {
         prog: 12345,
@@ -37,15 +51,396 @@ fn set_and_getaddr() {
     assert_eq!(res, std::ffi::OsString::from("example_addr"));
 }
 
-fn wait_for_server(addr: &str) -> UnixStream {
-    let mut counter = 20;
-    while counter > 0 {
-        match UnixStream::connect(addr) {
-            Ok(stream) => return stream,
-            Err(_) => std::thread::sleep(std::time::Duration::from_millis(10)),
+#[test]
+fn dead_services_are_flagged() {
+    std::thread::spawn(|| {
+        let health_check = HealthCheck {
+            interval: Duration::from_millis(20),
+            timeout: Duration::from_millis(500),
+            dead_after: Duration::from_millis(100),
+            remove_dead: false,
         };
-        counter -= 1;
+        rpcbind::server::main(
+            RpcbindServerAddress::Unix("rpcbind_health.socket".to_string()),
+            Some(health_check),
+        );
+    });
+
+    let mut stream = connect_unix("rpcbind_health.socket");
+
+    // A service that answers its NULL procedure:
+    let live_port = spawn_tcp(RpcProgram::new(400200, 1, 1, vec![None], ())).port();
+
+    // A service whose port nobody listens on:
+    let dead_port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    for (prog, port) in [(400200, live_port), (400201, dead_port)] {
+        let service = tcp_service(prog, port);
+        assert!(rpcbind::client::set_using_stream(service, &mut stream).unwrap());
     }
 
-    panic!("Timeout trying to connect to unix domain socket at {addr}");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let list = dump_health(&mut stream);
+        let health = |prog| {
+            list.services
+                .iter()
+                .find(|s| s.service.prog == prog)
+                .map(|s| s.health)
+        };
+
+        if health(400200) == Some(ServiceHealth::Reachable)
+            && health(400201) == Some(ServiceHealth::Dead)
+        {
+            break;
+        }
+
+        assert!(Instant::now() < deadline, "services not probed: {list:?}");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    let res = rpcbind::client::getaddr_using_stream(tcp_service(400201, 0), &mut stream).unwrap();
+    assert_eq!(res, "");
+
+    let res = rpcbind::client::getaddr_using_stream(tcp_service(400200, 0), &mut stream).unwrap();
+    assert_eq!(res, tcp_service(400200, live_port).addr);
+
+    // A restarted service can register again in place of its dead registration:
+    let restarted = tcp_service(400201, live_port);
+    assert!(rpcbind::client::set_using_stream(restarted, &mut stream).unwrap());
+}
+
+#[test]
+fn universal_addresses() {
+    let addr = rpcbind::parse_universal_address("127.0.0.1.78.80").unwrap();
+    assert_eq!(addr, "127.0.0.1:20048".parse().unwrap());
+
+    assert_eq!(rpcbind::parse_universal_address(""), None);
+    assert_eq!(rpcbind::parse_universal_address("127.0.0.1.78"), None);
+    assert_eq!(rpcbind::parse_universal_address("127.0.0.1.78.800"), None);
+    assert_eq!(rpcbind::parse_universal_address("/run/rpcbind.sock"), None);
+
+    let addr = rpcbind::parse_universal_address("fe80::1:2.78.80").unwrap();
+    assert_eq!(addr, "[fe80::1:2]:20048".parse().unwrap());
+    assert_eq!(rpcbind::universal_address(addr), "fe80::1:2.78.80");
+    assert_eq!(
+        rpcbind::parse_universal_address("::ffff:127.0.0.1.0.111").unwrap(),
+        "[::ffff:127.0.0.1]:111".parse().unwrap()
+    );
+    assert_eq!(rpcbind::parse_universal_address("fe80::1:2.78"), None);
+
+    // A service on the IPv6 wildcard address is reachable over IPv4 too:
+    assert_eq!(
+        rpcbind::tcp_registrations("[::]:20048".parse().unwrap()),
+        [("tcp6", "::.78.80".into()), ("tcp", "0.0.0.0.78.80".into())]
+    );
+    assert_eq!(
+        rpcbind::tcp_registrations("[::1]:20048".parse().unwrap()),
+        [("tcp6", "::1.78.80".into())]
+    );
+    assert_eq!(
+        rpcbind::tcp_registrations("0.0.0.0:20048".parse().unwrap()),
+        [("tcp", "0.0.0.0.78.80".into())]
+    );
+}
+
+#[test]
+fn getaddr_by_netid() {
+    std::thread::spawn(|| {
+        rpcbind::server::main(
+            RpcbindServerAddress::Unix("rpcbind_netid.socket".to_string()),
+            None,
+        );
+    });
+
+    let mut stream = connect_unix("rpcbind_netid.socket");
+
+    let service = |netid: &str, addr: &str| rpcbind::RpcService {
+        prog: 400300,
+        vers: 1,
+        netid: netid.into(),
+        addr: addr.into(),
+        owner: "".into(),
+    };
+
+    // The same program may be registered once for each netid:
+    for (netid, addr) in [("tcp", "0.0.0.0.78.80"), ("tcp6", "::.78.80")] {
+        assert!(rpcbind::client::set_using_stream(service(netid, addr), &mut stream).unwrap());
+    }
+    let again = service("tcp6", "::1.78.81");
+    assert!(!rpcbind::client::set_using_stream(again, &mut stream).unwrap());
+
+    for (netid, addr) in [("tcp", "0.0.0.0.78.80"), ("tcp6", "::.78.80"), ("udp6", "")] {
+        let res = rpcbind::client::getaddr_using_stream(service(netid, ""), &mut stream).unwrap();
+        assert_eq!(res, addr, "{netid}");
+    }
+
+    // An empty netid matches any:
+    let res = rpcbind::client::getaddr_using_stream(service("", ""), &mut stream).unwrap();
+    assert_eq!(res, "0.0.0.0.78.80");
+
+    // UNSET removes the registration under one netid, or under every netid if it is empty:
+    assert!(rpcbind::client::unset_using_stream(service("tcp", ""), &mut stream).unwrap());
+    assert!(!rpcbind::client::unset_using_stream(service("tcp", ""), &mut stream).unwrap());
+    let res = rpcbind::client::getaddr_using_stream(service("", ""), &mut stream).unwrap();
+    assert_eq!(res, "::.78.80");
+
+    assert!(rpcbind::client::unset_using_stream(service("", ""), &mut stream).unwrap());
+    let res = rpcbind::client::getaddr_using_stream(service("", ""), &mut stream).unwrap();
+    assert_eq!(res, "");
+}
+
+#[test]
+fn rpcinfo() {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    std::thread::spawn(move || {
+        rpcbind::server::main(RpcbindServerAddress::Tcp(format!("127.0.0.1:{port}")), None);
+    });
+    let mut stream = connect_tcp(port);
+
+    let live_port = spawn_tcp(RpcProgram::new(400500, 1, 1, vec![None], ())).port();
+    assert!(
+        rpcbind::client::set_using_stream(tcp_service(400500, live_port), &mut stream).unwrap()
+    );
+    let dead_port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    assert!(
+        rpcbind::client::set_using_stream(tcp_service(400501, dead_port), &mut stream).unwrap()
+    );
+    // The server answers one connection at a time:
+    drop(stream);
+
+    let rpcinfo = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_rpcinfo"))
+            .args(["--port", &port.to_string()])
+            .args(args)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        (output.status.success(), stdout)
+    };
+
+    let (ok, listing) = rpcinfo(&["127.0.0.1"]);
+    assert!(ok);
+    let address = tcp_service(400500, live_port).addr;
+    assert!(listing.lines().any(|line| {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        fields[..4] == ["400500", "1", "tcp", address.to_str().unwrap()]
+    }));
+
+    let (ok, listing) = rpcinfo(&["-p", "127.0.0.1"]);
+    assert!(ok);
+    let line = format!("400500 1 tcp {live_port} -");
+    assert!(listing
+        .lines()
+        .any(|l| l.split_whitespace().collect::<Vec<_>>().join(" ") == line));
+
+    let (ok, out) = rpcinfo(&["-T", "tcp", "127.0.0.1", "400500"]);
+    assert!(ok);
+    assert_eq!(out, "program 400500 version 1 ready and waiting\n");
+    let (ok, out) = rpcinfo(&["-T", "tcp", "127.0.0.1", "400501", "1"]);
+    assert!(!ok);
+    assert!(out.starts_with("program 400501 version 1 is not available"));
+
+    assert!(rpcinfo(&["-d", "400500", "1"]).0);
+    assert!(!rpcinfo(&["-d", "400500", "1"]).0);
+    assert!(!rpcinfo(&["-T", "tcp", "127.0.0.1", "400500", "1"]).0);
+}
+
+#[test]
+fn callit() {
+    let udp_port = UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    std::thread::spawn(move || {
+        let addrs = [
+            RpcbindServerAddress::Unix("rpcbind_callit.socket".to_string()),
+            RpcbindServerAddress::Udp(format!("127.0.0.1:{udp_port}")),
+        ];
+        rpcbind::server::serve(&addrs, None, PeerPolicy::default());
+    });
+    let mut stream = connect_unix("rpcbind_callit.socket");
+
+    // A service whose procedure 1 returns its argument reversed:
+    fn reverse(call: &Call, _: &mut ()) -> RpcResult {
+        RpcResult::Success(call.arg.iter().rev().copied().collect())
+    }
+    let program = RpcProgram::new(400600, 1, 1, vec![None, Some(reverse)], ());
+    let service_port = spawn_tcp(program).port();
+    assert!(
+        rpcbind::client::set_using_stream(tcp_service(400600, service_port), &mut stream).unwrap()
+    );
+
+    let rpcbind_address = SocketAddr::from(([127, 0, 0, 1], udp_port));
+    let wait = Duration::from_millis(500);
+    let responders =
+        rpcbind::client::broadcast(rpcbind_address, 400600, 1, 1, &[1, 2, 3, 4], wait).unwrap();
+    assert_eq!(
+        responders,
+        [Responder {
+            address: SocketAddr::from(([127, 0, 0, 1], service_port)),
+            result: vec![4, 3, 2, 1],
+        }]
+    );
+
+    // Hosts that do not run the service stay silent:
+    let responders = rpcbind::client::broadcast(rpcbind_address, 400601, 1, 0, &[], wait).unwrap();
+    assert!(responders.is_empty());
+
+    // Over a connection, a call that cannot be forwarded is answered with an error:
+    let args = rpcbind::RpcbRmtcallArgs {
+        prog: 400601,
+        vers: 1,
+        proc: 0,
+        args: Vec::new(),
+    };
+    let res = do_rpc_call(
+        &mut stream,
+        rpcbind::procedures::RPCBPROG,
+        rpcbind::procedures::RPCBVERS::VERSION,
+        rpcbind::procedures::RPCBVERS::RPCBPROC_CALLIT,
+        &args.serialize_alloc(),
+    );
+    assert_call_error(res, RpcCallError::SystemErr);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rpcinfo"))
+        .args(["--port", &udp_port.to_string(), "--hostname", "127.0.0.1"])
+        .args(["-b", "400600", "1", "--wait", "1"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, format!("127.0.0.1:{service_port}\n"));
+}
+
+#[test]
+fn callit_is_not_held_up_by_stalled_services() {
+    let udp_port = UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    std::thread::spawn(move || {
+        let addrs = [
+            RpcbindServerAddress::Unix("rpcbind_callit_stalled.socket".to_string()),
+            RpcbindServerAddress::Udp(format!("127.0.0.1:{udp_port}")),
+        ];
+        rpcbind::server::serve(&addrs, None, PeerPolicy::default());
+    });
+    let mut stream = connect_unix("rpcbind_callit_stalled.socket");
+    let rpcbind_address = SocketAddr::from(([127, 0, 0, 1], udp_port));
+
+    // A service whose connections are accepted by the kernel, but never answered:
+    let stalled = TcpListener::bind("127.0.0.1:0").unwrap();
+    let stalled_port = stalled.local_addr().unwrap().port();
+    assert!(
+        rpcbind::client::set_using_stream(tcp_service(400700, stalled_port), &mut stream).unwrap()
+    );
+
+    fn echo(call: &Call, _: &mut ()) -> RpcResult {
+        RpcResult::Success(call.arg.to_vec())
+    }
+    let program = RpcProgram::new(400701, 1, 1, vec![None, Some(echo)], ());
+    let port = spawn_tcp(program).port();
+    assert!(rpcbind::client::set_using_stream(tcp_service(400701, port), &mut stream).unwrap());
+
+    // The calls to the stalled service are still being forwarded when the next one arrives:
+    let wait = Duration::from_millis(100);
+    for _ in 0..3 {
+        let responders =
+            rpcbind::client::broadcast(rpcbind_address, 400700, 1, 1, &[], wait).unwrap();
+        assert!(responders.is_empty());
+    }
+    let responders =
+        rpcbind::client::broadcast(rpcbind_address, 400701, 1, 1, &[5, 6, 7, 8], wait).unwrap();
+    assert_eq!(responders.len(), 1);
+    assert_eq!(responders[0].result, [5, 6, 7, 8]);
+}
+
+#[test]
+fn callit_refuses_nfs_programs() {
+    let udp_port = UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    std::thread::spawn(move || {
+        let addrs = [
+            RpcbindServerAddress::Unix("rpcbind_callit_refused.socket".to_string()),
+            RpcbindServerAddress::Udp(format!("127.0.0.1:{udp_port}")),
+        ];
+        rpcbind::server::serve(&addrs, None, PeerPolicy::default());
+    });
+    let mut stream = connect_unix("rpcbind_callit_refused.socket");
+    let rpcbind_address = SocketAddr::from(([127, 0, 0, 1], udp_port));
+
+    // Services that would answer the call, were it forwarded to them:
+    fn echo(call: &Call, _: &mut ()) -> RpcResult {
+        RpcResult::Success(call.arg.to_vec())
+    }
+    // NFS, MOUNT, NLM, and NSM:
+    for prog in [100003, 100005, 100021, 100024] {
+        let program = RpcProgram::new(prog, 1, 1, vec![None, Some(echo)], ());
+        let port = spawn_tcp(program).port();
+        assert!(rpcbind::client::set_using_stream(tcp_service(prog, port), &mut stream).unwrap());
+
+        let wait = Duration::from_millis(200);
+        let responders =
+            rpcbind::client::broadcast(rpcbind_address, prog, 1, 1, &[1, 2, 3, 4], wait).unwrap();
+        assert!(responders.is_empty(), "program {prog}");
+
+        let args = rpcbind::RpcbRmtcallArgs {
+            prog,
+            vers: 1,
+            proc: 1,
+            args: vec![1, 2, 3, 4],
+        };
+        let res = do_rpc_call(
+            &mut stream,
+            rpcbind::procedures::RPCBPROG,
+            rpcbind::procedures::RPCBVERS::VERSION,
+            rpcbind::procedures::RPCBVERS::RPCBPROC_CALLIT,
+            &args.serialize_alloc(),
+        );
+        assert_call_error(res, RpcCallError::SystemErr);
+    }
+}
+
+fn tcp_service(prog: u32, port: u16) -> rpcbind::RpcService {
+    rpcbind::RpcService {
+        prog,
+        vers: 1,
+        netid: "tcp".into(),
+        addr: format!("127.0.0.1.{}.{}", port >> 8, port & 0xff).into(),
+        owner: "".into(),
+    }
+}
+
+fn dump_health(stream: &mut UnixStream) -> rpcbind::HealthList {
+    rpcbind::client::dump_health_using_stream(stream).unwrap()
+}
+
+fn connect_tcp(port: u16) -> TcpStream {
+    let deadline = Instant::now() + Duration::from_secs(2);
+    loop {
+        match TcpStream::connect(("127.0.0.1", port)) {
+            Ok(stream) => return stream,
+            Err(e) => assert!(Instant::now() < deadline, "{e}"),
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
 }
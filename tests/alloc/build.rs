@@ -1,3 +1,24 @@
+/// Turns `%#derive TRAITS` lines into derives, and `%#pragma timestamp FIELD` lines into a method
+/// which reads the field as seconds since the epoch.
+fn annotate(name: &str, lines: &[String]) -> Option<String> {
+    let mut code = String::new();
+    for line in lines.iter() {
+        if let Some(traits) = line.strip_prefix("#derive ") {
+            code += &format!("#[derive({traits})]\n");
+        } else if let Some(field) = line.strip_prefix("#pragma timestamp ") {
+            code += &format!(
+                "impl {name} {{\n\
+                 pub fn {field}_time(&self) -> std::time::SystemTime {{\n\
+                 std::time::UNIX_EPOCH + std::time::Duration::from_secs(self.{field})\n\
+                 }}\n\
+                 }}\n"
+            );
+        }
+    }
+
+    (!code.is_empty()).then_some(code)
+}
+
 fn main() {
     xdr_codegen::Compiler::new()
         .file("../input/arrays.x")
@@ -6,6 +27,44 @@ fn main() {
         .file("../input/unions.x")
         .file("../input/structs.x")
         .file("../input/optional.x")
+        .file("../input/program.x")
+        .file("../input/nested.x")
+        .file("../input/include/shared_ops.x")
+        .enable_deserialize_from()
+        .enable_display()
+        .run()
+        .expect("That should have worked. :(");
+
+    xdr_codegen::Compiler::new()
+        .file("../input/include/shared_ops.x")
+        .merge("shared")
+        .run()
+        .expect("That should have worked. :(");
+
+    xdr_codegen::Compiler::new()
+        .file("../input/serde_types.x")
+        .derive_serde()
+        .run()
+        .expect("That should have worked. :(");
+
+    xdr_codegen::Compiler::new()
+        .file("../input/literals.x")
+        .literal_comments()
+        .annotate(annotate)
+        .run()
+        .expect("That should have worked. :(");
+
+    xdr_codegen::Compiler::new()
+        .file("../input/display.x")
+        .enable_display()
+        .display_timestamp("Time")
+        .display_size("Size")
+        .run()
+        .expect("That should have worked. :(");
+
+    xdr_codegen::Compiler::new()
+        .file("../input/derives.x")
+        .derive("PartialOrd")
         .run()
         .expect("That should have worked. :(");
 }
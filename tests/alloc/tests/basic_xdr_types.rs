@@ -122,6 +122,59 @@ fn test_hello() {
     assert_eq!(ANOTHER, 15);
 }
 
+#[test]
+fn constants() {
+    use hello::*;
+
+    assert_eq!(HEX_CONSTANT, 32);
+    assert_eq!(OCTAL_CONSTANT, 8);
+    assert_eq!(SAME_AS_ANOTHER, ANOTHER);
+    assert_eq!(ConstSized::default().bytes.len(), 15);
+
+    // Constants that fit are u32, so they can be compared with unsigned ints without a cast:
+    let hello = Hello {
+        abc: 15,
+        ..Default::default()
+    };
+    assert!(matches!(hello.abc, ANOTHER..=A_CONSTANT));
+
+    let big: u64 = BIG_CONSTANT;
+    assert_eq!(big, 1 << 32);
+}
+
+#[test]
+fn negative_values() {
+    use hello::*;
+
+    let min: i32 = INT_MIN;
+    assert_eq!(min, i32::MIN);
+    assert_eq!(NEGATIVE_HEX, -16);
+    let hyper_min: i64 = HYPER_MIN;
+    assert_eq!(hyper_min, i64::MIN);
+
+    for (offset, val) in [
+        (Offset::Bottom, i32::MIN),
+        (Offset::Behind, -1),
+        (Offset::Level, 0),
+        (Offset::Ahead, 1),
+    ] {
+        let buf = offset.serialize_alloc();
+        assert_eq!(buf, val.to_be_bytes());
+
+        let mut decoded = Offset::default();
+        decoded.deserialize(&mut buf.as_slice()).unwrap();
+        assert_eq!(decoded, offset);
+    }
+
+    let measured = Measured::Behind(7);
+    let buf = measured.serialize_alloc();
+    assert_eq!(buf, [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 7]);
+
+    let mut decoded = Measured::default();
+    decoded.deserialize(&mut buf.as_slice()).unwrap();
+    assert_eq!(decoded, measured);
+}
+
 include!(concat!(env!("OUT_DIR"), "/optional.rs"));
 use optional::*;
 
@@ -165,6 +218,23 @@ fn test_struct() {
     assert_eq!(before, after);
 }
 
+#[test]
+fn keyword_field_names() {
+    let before = structs::Keywords {
+        r#where: "here".into(),
+        r#type: 3,
+        data: vec![1, 2, 3],
+    };
+
+    let bytes = before.serialize_alloc();
+    assert_eq!(bytes.len(), 20);
+
+    let mut after = structs::Keywords::default();
+    after.deserialize(&mut bytes.as_slice()).unwrap();
+
+    assert_eq!(before, after);
+}
+
 include!(concat!(env!("OUT_DIR"), "/typedef.rs"));
 use typedef::*;
 
@@ -205,6 +275,34 @@ fn test_simple_union() {
     assert_eq!(plant, plant_after);
 }
 
+#[test]
+fn test_integer_discriminated_unions() {
+    let gss = SecInfo::FLAVOR_GSS(Stuff { a: 1, b: 2 });
+    let bytes = gss.serialize_alloc();
+    assert_eq!(bytes[..4], 6_i32.to_be_bytes());
+    let mut after = SecInfo::Default;
+    after.deserialize(&mut bytes.as_slice()).unwrap();
+    assert_eq!(gss, after);
+
+    let mut after = SecInfo::Default;
+    after.deserialize(&mut [0, 0, 0, 2].as_slice()).unwrap();
+    assert_eq!(after, SecInfo::Var2);
+    after.deserialize(&mut [0, 0, 0, 9].as_slice()).unwrap();
+    assert_eq!(after, SecInfo::Default);
+
+    // A bool union whose FALSE arm is not void:
+    for before in [NewOrOld::TRUE(Stuff { a: 3, b: 4 }), NewOrOld::FALSE(17)] {
+        let bytes = before.serialize_alloc();
+        let mut after = NewOrOld::default();
+        after.deserialize(&mut bytes.as_slice()).unwrap();
+        assert_eq!(before, after);
+    }
+    assert_eq!(
+        NewOrOld::FALSE(17).serialize_alloc(),
+        [0, 0, 0, 0, 0, 0, 0, 17]
+    );
+}
+
 #[test]
 fn test_bool_union_contains_int() {
     let not_a_plant: NumLeaves = NumLeaves { inner: None };
@@ -320,6 +418,40 @@ fn test_enum_union_with_compound_arms() {
     });
 }
 
+#[test]
+fn result_union_helpers() {
+    let stuff = Stuff { a: 1, b: 2 };
+    let ok = Outcome::Okay(stuff);
+    assert!(ok.is_ok());
+    assert_eq!(ok.ok(), Some(stuff));
+    assert_eq!(ok.err(), None);
+    assert_eq!(ok.into_result(), Ok(stuff));
+
+    // A failure is decoded into the default arm, whatever its status:
+    let mut bytes = 2_i32.to_be_bytes().to_vec();
+    bytes.extend_from_slice(&17_i32.to_be_bytes());
+    let mut failed = Outcome::default();
+    failed.deserialize(&mut bytes.as_slice()).unwrap();
+    assert!(!failed.is_ok());
+    assert_eq!(failed.ok(), None);
+    assert_eq!(failed.err(), Some(17));
+    assert_eq!(failed.into_result(), Err(17));
+
+    assert_eq!(VoidOutcome::Okay.into_result(), Ok(()));
+    assert_eq!(VoidOutcome::Default.err(), Some(()));
+}
+
+#[test]
+fn serialized_sizes() {
+    let ok = Outcome::Okay(Stuff { a: 1, b: 2 });
+    assert_eq!(ok.serialized_size(), ok.serialize_alloc().len());
+    assert_eq!(Status::Worse.serialized_size(), 4);
+    assert_eq!(
+        VoidOutcome::Default.serialized_size(),
+        VoidOutcome::Default.serialize_alloc().len()
+    );
+}
+
 #[test]
 fn mount_proto_multiple_optionals() {
     use std::ffi::OsString;
@@ -344,3 +476,81 @@ fn mount_proto_multiple_optionals() {
     after.deserialize(&mut bytes.as_slice()).unwrap();
     assert_eq!(exports, after);
 }
+
+include!(concat!(env!("OUT_DIR"), "/program.rs"));
+
+#[test]
+fn procedure_names() {
+    use program::procedures::*;
+
+    assert_eq!(GREETER_PROGRAM, 400111);
+    assert_eq!(
+        proc_name(GREETER_V1::VERSION, GREETER_V1::HELLO),
+        Some("HELLO")
+    );
+    assert_eq!(
+        proc_name(GREETER_V2::VERSION, GREETER_V2::GOODBYE),
+        Some("GOODBYE")
+    );
+    assert_eq!(proc_name(GREETER_V1::VERSION, GREETER_V2::GOODBYE), None);
+    assert_eq!(proc_name(3, 0), None);
+}
+
+#[test]
+fn checking_results() {
+    use program::procedures::*;
+    use xdr_lib::EncodingError;
+
+    let greeting = program::Greeting {
+        message: "hello".into(),
+    };
+    let mut bytes = greeting.serialize_alloc();
+    assert_eq!(bytes.len(), 12);
+    let check = |version, proc, bytes: &[u8]| check_result(version, proc, bytes).unwrap();
+    assert_eq!(
+        check(GREETER_V2::VERSION, GREETER_V2::GOODBYE, &bytes),
+        Ok(())
+    );
+
+    // Padding that is not zero decodes, but does not encode the same:
+    bytes[11] = 1;
+    assert_eq!(
+        check(GREETER_V2::VERSION, GREETER_V2::GOODBYE, &bytes),
+        Err(EncodingError::Mismatch(11))
+    );
+    bytes[11] = 0;
+
+    assert_eq!(
+        check(GREETER_V2::VERSION, GREETER_V2::GOODBYE, &bytes[..10]),
+        Err(EncodingError::Unaligned(10))
+    );
+    assert_eq!(
+        check(GREETER_V2::VERSION, GREETER_V2::GOODBYE, &bytes[..8]),
+        Err(EncodingError::Undecodable)
+    );
+    bytes.extend_from_slice(&[0; 4]);
+    assert_eq!(
+        check(GREETER_V2::VERSION, GREETER_V2::GOODBYE, &bytes),
+        Err(EncodingError::TrailingBytes(4))
+    );
+
+    // A bool other than 0 or 1, and a result for a void procedure:
+    assert_eq!(
+        check(GREETER_V2::VERSION, GREETER_V2::FRIENDLY, &[0, 0, 0, 1]),
+        Ok(())
+    );
+    assert_eq!(
+        check(GREETER_V2::VERSION, GREETER_V2::FRIENDLY, &[0, 0, 0, 2]),
+        Err(EncodingError::Mismatch(3))
+    );
+    assert_eq!(check(GREETER_V1::VERSION, GREETER_V1::NULL, &[]), Ok(()));
+    assert_eq!(
+        check(GREETER_V1::VERSION, GREETER_V1::NULL, &[0; 4]),
+        Err(EncodingError::TrailingBytes(4))
+    );
+
+    assert_eq!(
+        check_result(GREETER_V1::VERSION, GREETER_V2::FRIENDLY, &[]),
+        None
+    );
+}
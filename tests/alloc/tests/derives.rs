@@ -0,0 +1,56 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+use std::collections::{HashMap, HashSet};
+
+include!(concat!(env!("OUT_DIR"), "/derives.rs"));
+use crate::derives::*;
+
+fn handle(kind: Kind) -> Handle {
+    Handle {
+        data: [1, 2, 3, 4, 5, 6, 7, 8],
+        kind,
+    }
+}
+
+#[test]
+fn plain_types_are_copy() {
+    let file = handle(Kind::Regular);
+    let copy = file;
+    assert_eq!(file, copy);
+
+    let attributes = Attributes {
+        inner: Some(Times {
+            seconds: 1,
+            nanoseconds: 2,
+        }),
+    };
+    let copy = attributes;
+    assert_eq!(
+        attributes.inner.unwrap().seconds,
+        copy.inner.unwrap().seconds
+    );
+}
+
+#[test]
+fn types_are_hashable() {
+    let mut attributes = HashMap::new();
+    attributes.insert(handle(Kind::Regular), Attributes::default());
+    attributes.insert(handle(Kind::Directory), Attributes::default());
+    attributes.insert(handle(Kind::Regular), Attributes::default());
+    assert_eq!(attributes.len(), 2);
+
+    // Types that hold strings are not `Copy`, but are still `Eq` and `Hash`:
+    let named = NamedHandle {
+        name: "file".into(),
+        handle: handle(Kind::Regular),
+    };
+    let names: HashSet<_> = [named.clone(), named].into();
+    assert_eq!(names.len(), 1);
+}
+
+#[test]
+fn extra_derives() {
+    assert!(Kind::Regular < Kind::Directory);
+    assert!(handle(Kind::Regular) < handle(Kind::Directory));
+}
@@ -0,0 +1,66 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+include!(concat!(env!("OUT_DIR"), "/display.rs"));
+use crate::display::*;
+
+fn attributes() -> Attributes {
+    Attributes {
+        kind: Kind::Regular,
+        size: 1536,
+        mtime: Time {
+            seconds: 1_717_245_000,
+            nseconds: 500_000_000,
+        },
+        handle: Handle {
+            data: vec![0x01, 0xab, 0xff],
+        },
+        used: None,
+        name: "file".into(),
+        ids: vec![1, 2],
+    }
+}
+
+#[test]
+fn domain_rendering() {
+    assert_eq!(
+        attributes().to_string(),
+        "Attributes { kind: Regular, size: 1536 (1.5 KiB), mtime: 2024-06-01T12:30:00.5Z, \
+         handle: Handle { data: 01abff }, used: none, name: \"file\", ids: [1, 2] }"
+    );
+}
+
+#[test]
+fn union_arms() {
+    let result = Result::Regular(Attributes {
+        used: Some(4 << 30),
+        ..attributes()
+    });
+    assert!(result.to_string().starts_with("Regular(Attributes { "));
+    assert!(result.to_string().contains("used: 4294967296 (4.0 GiB)"));
+    assert_eq!(Result::Default.to_string(), "Default");
+}
+
+#[test]
+fn timestamps() {
+    let time = |seconds, nseconds| Time { seconds, nseconds }.to_string();
+    assert_eq!(time(0, 0), "1970-01-01T00:00:00Z");
+    assert_eq!(time(951_782_400, 1), "2000-02-29T00:00:00.000000001Z");
+    assert_eq!(time(-1, 0), "1969-12-31T23:59:59Z");
+    assert_eq!(time(253_402_300_799, 0), "9999-12-31T23:59:59Z");
+}
+
+#[test]
+fn sizes() {
+    let size = |size| {
+        Attributes {
+            size,
+            ..attributes()
+        }
+        .to_string()
+    };
+    assert!(size(0).contains("size: 0,"));
+    assert!(size(1023).contains("size: 1023,"));
+    assert!(size(1024).contains("size: 1024 (1.0 KiB),"));
+    assert!(size(u64::MAX).contains("size: 18446744073709551615 (16.0 EiB),"));
+}
@@ -0,0 +1,45 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// shared_ops.x includes shared_types.x. Compiled on their own, each file's code is in a module of
+// its own, and shared_ops imports the types of shared_types from it:
+include!(concat!(env!("OUT_DIR"), "/shared_types.rs"));
+include!(concat!(env!("OUT_DIR"), "/shared_ops.rs"));
+
+// Merged, both files' code is in one module:
+include!(concat!(env!("OUT_DIR"), "/shared.rs"));
+
+#[test]
+fn included_types() {
+    use shared_ops::*;
+    use shared_types::*;
+
+    let args = LookupArgs {
+        dir: Handle {
+            data: [1; HANDLE_SIZE as usize],
+        },
+        name: "file".into(),
+    };
+    let bytes = args.serialize_alloc();
+    let mut after = LookupArgs::default();
+    after.deserialize(&mut bytes.as_slice()).unwrap();
+    assert_eq!(args, after);
+
+    let handle = after.dir;
+    assert_eq!(LookupResult::Okay(handle).ok(), Some(handle));
+    assert_eq!(procedures::SHARED_PROGRAM, 400100);
+}
+
+#[test]
+fn merged_files() {
+    use shared::*;
+
+    let result = LookupResult::Okay(Handle { data: [7; 8] });
+    let bytes = result.serialize_alloc();
+    assert_eq!(bytes[..4], (Status::Okay as i32).to_be_bytes());
+
+    let mut after = LookupResult::default();
+    after.deserialize(&mut bytes.as_slice()).unwrap();
+    assert_eq!(result, after);
+    assert_eq!(procedures::SHARED_PROGRAM, 400100);
+}
@@ -32,3 +32,73 @@ fn too_short_64bit() {
     let mut uhyper = Uhyper::default();
     assert!(uhyper.deserialize(&mut msg.as_slice()).is_err());
 }
+
+include!(concat!(env!("OUT_DIR"), "/arrays.rs"));
+
+#[test]
+fn length_past_end_of_input() {
+    use arrays::*;
+
+    // A string claiming to be longer than the rest of the input:
+    let msg = [0u8, 0, 0, 5, b'a', b'b', b'c', 0];
+    let mut strings = Strings::default();
+    assert!(strings.deserialize(&mut msg.as_slice()).is_err());
+
+    // Opaque data whose padding is missing:
+    let msg = [1u8, 2, 3, 0, 0, 0, 0, 2, 9, 9];
+    let mut opaque = OpaqueArrays::default();
+    assert!(opaque.deserialize(&mut msg.as_slice()).is_err());
+}
+
+#[test]
+fn every_truncation_fails() {
+    use arrays::*;
+
+    let before = ManyStrings {
+        first: Strings {
+            str: "abc".into(),
+            str_2: "de".into(),
+        },
+        ..Default::default()
+    };
+    let bytes = before.serialize_alloc();
+
+    for len in 0..bytes.len() {
+        let mut after = ManyStrings::default();
+        assert!(
+            after.deserialize(&mut &bytes[..len]).is_err(),
+            "decoding {len} of {} bytes succeeded",
+            bytes.len()
+        );
+    }
+}
+
+#[test]
+fn over_limit_arrays() {
+    use arrays::*;
+
+    // `string data<4>` with 5 bytes:
+    let msg = [0u8, 0, 0, 5, b'a', b'b', b'c', b'd', b'e', 0, 0, 0];
+    let mut string = LimitedOpaqueArray::default();
+    assert!(string.deserialize(&mut msg.as_slice()).is_err());
+
+    // At the limit is fine:
+    let msg = [0u8, 0, 0, 4, b'a', b'b', b'c', b'd'];
+    let mut string = LimitedOpaqueArray::default();
+    assert!(string.deserialize(&mut msg.as_slice()).is_ok());
+    assert_eq!(string.data, "abcd");
+
+    // `opaque a<1>` with 2 bytes:
+    let mut opaque = LimitedOpaqueArrays::default();
+    let mut msg = opaque.serialize_alloc();
+    msg.splice(..8, [0, 0, 0, 2, 1, 2, 0, 0]);
+    assert!(opaque.deserialize(&mut msg.as_slice()).is_err());
+
+    // `AnInt limited<7>` with 8 elements, after `AnInt fixed[4]`:
+    let mut msg = vec![0u8; 4 * 4];
+    msg.extend_from_slice(&8u32.to_be_bytes());
+    msg.extend_from_slice(&[0u8; 8 * 4]);
+    msg.extend_from_slice(&0u32.to_be_bytes());
+    let mut ints = IntArrays::default();
+    assert!(ints.deserialize(&mut msg.as_slice()).is_err());
+}
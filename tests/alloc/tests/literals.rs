@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+include!(concat!(env!("OUT_DIR"), "/literals.rs"));
+use crate::literals::*;
+
+#[test]
+fn annotated_derives() {
+    let old = Release { major: 1, minor: 9 };
+    let new = Release { major: 2, minor: 0 };
+    assert!(old < new);
+    assert_eq!([new, old].iter().max(), Some(&new));
+}
+
+#[test]
+fn annotated_methods() {
+    let event = Event {
+        when: 86_400,
+        release: Release { major: 1, minor: 0 },
+    };
+    assert_eq!(event.when_time(), UNIX_EPOCH + Duration::from_secs(86_400));
+}
+
+#[test]
+fn literal_comments() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/literals.rs"));
+    let comment = generated
+        .find("// % /* Seconds since the epoch. */")
+        .unwrap();
+    let pragma = generated.find("// %#pragma timestamp when").unwrap();
+    let event = generated.find("pub struct Event").unwrap();
+    assert!(comment < pragma && pragma < event);
+    assert!(
+        generated.find("// %#derive PartialOrd, Ord").unwrap()
+            < generated.find("pub struct Release").unwrap()
+    );
+}
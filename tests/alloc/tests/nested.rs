@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+include!(concat!(env!("OUT_DIR"), "/nested.rs"));
+use nested::*;
+
+fn round_trip<T: PartialEq + std::fmt::Debug + Default>(
+    before: &T,
+    serialize: fn(&T) -> Vec<u8>,
+    deserialize: fn(&mut T, &mut &[u8]) -> xdr_lib::Result<()>,
+) {
+    let bytes = serialize(before);
+    let mut after = T::default();
+    deserialize(&mut after, &mut bytes.as_slice()).unwrap();
+    assert_eq!(*before, after);
+}
+
+#[test]
+fn anonymous_members() {
+    let entry = Entry {
+        fileid: 17,
+        mtime: Entry_mtime {
+            seconds: 1,
+            nseconds: 2,
+        },
+        r#type: Entry_type::Present(Entry_type_contents {
+            data: vec![1, 2, 3],
+            size: Entry_type_contents_size::Large,
+        }),
+        extra: Some(Entry_extra { level: -4 }),
+        owners: vec![Entry_owners { id: 5 }, Entry_owners { id: 6 }],
+    };
+    round_trip(&entry, Entry::serialize_alloc, Entry::deserialize);
+
+    // The discriminant of the anonymous union is encoded as the inline enum's value:
+    let bytes = entry.serialize_alloc();
+    assert_eq!(bytes[16..20], 1_u32.to_be_bytes());
+
+    let absent = Entry {
+        r#type: Entry_type::Default,
+        ..entry
+    };
+    round_trip(&absent, Entry::serialize_alloc, Entry::deserialize);
+}
+
+#[test]
+fn inline_discriminant() {
+    let found = Lookup::Found(Version { major: 4, minor: 2 });
+    round_trip(&found, Lookup::serialize_alloc, Lookup::deserialize);
+
+    let bytes = Lookup::Missing.serialize_alloc();
+    assert_eq!(bytes, (Lookup_status::Missing as u32).to_be_bytes());
+}
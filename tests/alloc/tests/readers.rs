@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Tests of the deserialize_from() methods, which decode from an std::io::Read.
+
+use std::io::Read;
+
+include!(concat!(env!("OUT_DIR"), "/arrays.rs"));
+include!(concat!(env!("OUT_DIR"), "/optional.rs"));
+include!(concat!(env!("OUT_DIR"), "/unions.rs"));
+
+/// A reader that returns one byte at a time, like a slow connection.
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.0.is_empty() || buf.is_empty() {
+            return Ok(0);
+        }
+        buf[0] = self.0[0];
+        self.0 = &self.0[1..];
+        Ok(1)
+    }
+}
+
+#[test]
+fn arrays_from_reader() {
+    use arrays::*;
+
+    let mut before = IntArrays::default();
+    for i in 0..7 {
+        before.limited.push(AnInt { a: i });
+    }
+    for i in 0..512 {
+        before.unlimited.push(AnInt { a: u32::MAX - i });
+    }
+    let bytes = before.serialize_alloc();
+    let mut after = IntArrays::default();
+    after.deserialize_from(&mut Trickle(&bytes)).unwrap();
+    assert_eq!(before, after);
+
+    let before = OpaqueArrays {
+        bytes: [1, 2, 3],
+        bytes_2: vec![4, 5],
+        bytes_3: (0..=255).collect(),
+    };
+    let bytes = before.serialize_alloc();
+    let mut after = OpaqueArrays::default();
+    after.deserialize_from(&mut Trickle(&bytes)).unwrap();
+    assert_eq!(before, after);
+
+    let before = ManyStrings {
+        first: Strings {
+            str: "abc".into(),
+            str_2: "defgh".into(),
+        },
+        ..Default::default()
+    };
+    let bytes = before.serialize_alloc();
+    let mut after = ManyStrings::default();
+    let mut input = bytes.as_slice();
+    after.deserialize_from(&mut input).unwrap();
+    assert_eq!(before, after);
+    assert!(input.is_empty());
+}
+
+#[test]
+fn optionals_and_unions_from_reader() {
+    use optional::*;
+    use unions::*;
+
+    let before = ListBegin {
+        list: vec![ListNode { data: 1 }, ListNode { data: -2 }],
+    };
+    let bytes = before.serialize_alloc();
+    let mut after = ListBegin::default();
+    after.deserialize_from(&mut Trickle(&bytes)).unwrap();
+    assert_eq!(before, after);
+
+    let before = HasString {
+        inner: Some("a string".into()),
+    };
+    let bytes = before.serialize_alloc();
+    let mut after = HasString::default();
+    after.deserialize_from(&mut Trickle(&bytes)).unwrap();
+    assert_eq!(before, after);
+}
+
+#[test]
+fn invalid_input_from_reader() {
+    use arrays::*;
+
+    let before = Strings {
+        str: "abc".into(),
+        str_2: "de".into(),
+    };
+    let bytes = before.serialize_alloc();
+    for len in 0..bytes.len() {
+        let mut after = Strings::default();
+        assert!(after.deserialize_from(&mut &bytes[..len]).is_err());
+    }
+
+    // `string data<4>` with 5 bytes:
+    let msg = [0u8, 0, 0, 5, b'a', b'b', b'c', b'd', b'e', 0, 0, 0];
+    let mut string = LimitedOpaqueArray::default();
+    assert!(string.deserialize_from(&mut msg.as_slice()).is_err());
+
+    // An opaque array claiming to be 4 GB long, but with no data:
+    let msg = [0u8, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xfc];
+    let mut opaque = OpaqueArrays::default();
+    assert!(opaque.deserialize_from(&mut msg.as_slice()).is_err());
+}
@@ -0,0 +1,57 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+
+include!(concat!(env!("OUT_DIR"), "/serde_types.rs"));
+use crate::serde_types::*;
+
+#[test]
+fn strings_are_encoded_as_strings() {
+    let dir = Directory {
+        name: "home".into(),
+        alias: Some("~".into()),
+        entries: vec!["a".into(), "b".into()],
+        cookie: [1, 2, 3, 4, 5, 6, 7, 8],
+        size: 4096,
+    };
+
+    let json = serde_json::to_value(&dir).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "name": "home",
+            "alias": "~",
+            "entries": ["a", "b"],
+            "cookie": [1, 2, 3, 4, 5, 6, 7, 8],
+            "size": 4096,
+        })
+    );
+    assert_eq!(serde_json::from_value::<Directory>(json).unwrap(), dir);
+
+    let lookup = Lookup::Found("file".into());
+    let json = serde_json::to_value(&lookup).unwrap();
+    assert_eq!(json, serde_json::json!({ "Found": "file" }));
+    assert_eq!(serde_json::from_value::<Lookup>(json).unwrap(), lookup);
+
+    let maybe = MaybeName {
+        inner: Some("file".into()),
+    };
+    let json = serde_json::to_value(&maybe).unwrap();
+    assert_eq!(json, serde_json::json!({ "inner": "file" }));
+    assert_eq!(serde_json::from_value::<MaybeName>(json).unwrap(), maybe);
+}
+
+#[test]
+fn non_utf8_strings_are_encoded_as_bytes() {
+    let dir = Directory {
+        name: OsString::from_vec(vec![0x66, 0xff]),
+        alias: None,
+        ..Default::default()
+    };
+
+    let json = serde_json::to_value(&dir).unwrap();
+    assert_eq!(json["name"], serde_json::json!([0x66, 0xff]));
+    assert_eq!(json["alias"], serde_json::Value::Null);
+    assert_eq!(serde_json::from_value::<Directory>(json).unwrap(), dir);
+}
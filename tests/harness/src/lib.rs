@@ -0,0 +1,269 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+//! An in-process client and server for the integration tests of RPC programs.
+//!
+//! `spawn_program()` runs an `RpcProgram` in a thread, connected to the returned `Harness` through
+//! a pair of UNIX domain sockets (see `rpc_protocol::pipe`). Replies pass through a relay on their
+//! way to the client, which can be told to delay, truncate, or corrupt them with `Harness::inject()`.
+//! A `Harness` reads and writes like any stream, so the client functions of a protocol can be
+//! called on it directly.
+//!
+//! Everything here panics when the fixture itself cannot be set up, as a test would.
+
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener},
+    os::unix::net::UnixStream,
+    path::Path,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use rpc_protocol::{
+    client::do_rpc_call_with_auth,
+    pipe::{pipe, Endpoint},
+    server::RpcProgram,
+    AuthError, Error, OpaqueAuth, ProtocolError, RpcCallError,
+};
+
+/// How long a `Harness` waits to read before a test is failed, rather than hung.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something done to a reply on its way from the server to the client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Hold the reply back for a while before sending it.
+    Delay(Duration),
+
+    /// Send only the first bytes of the reply, counting its record mark, and then close the
+    /// connection.
+    Truncate(usize),
+
+    /// Clear the last-fragment bit of the reply's record mark, which the client takes for a
+    /// fragmented record.
+    CorruptRecordMark,
+}
+
+type Faults = Arc<Mutex<VecDeque<Fault>>>;
+
+/// A client connected to a program served in another thread.
+pub struct Harness {
+    client: Endpoint,
+    credential: OpaqueAuth,
+    faults: Faults,
+    server: JoinHandle<Result<(), Error>>,
+}
+
+/// Serve `program` in a thread, and connect a client to it.
+pub fn spawn_program<T: Send + 'static>(mut program: RpcProgram<T>) -> Harness {
+    let (client, relay_client) = pipe().expect("Could not create a socket pair");
+    let (relay_server, server_end) = pipe().expect("Could not create a socket pair");
+    client.set_read_timeout(Some(READ_TIMEOUT)).unwrap();
+
+    let faults = Faults::default();
+    spawn_relay(relay_client, relay_server, faults.clone());
+    let server = thread::spawn(move || program.handle_connection(server_end));
+
+    Harness {
+        client,
+        credential: OpaqueAuth::none(),
+        faults,
+        server,
+    }
+}
+
+/// Serve `program` over TCP in a thread, on a port of the loopback address, which is returned.
+pub fn spawn_tcp<T: Send + 'static>(mut program: RpcProgram<T>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Could not listen on the loopback");
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || program.run_blocking_tcp_server(listener));
+
+    address
+}
+
+/// Connect to the UNIX domain socket at `path`, waiting for a server that is starting in another
+/// thread to create it.
+pub fn connect_unix(path: impl AsRef<Path>) -> UnixStream {
+    let path = path.as_ref();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    loop {
+        match UnixStream::connect(path) {
+            Ok(stream) => return stream,
+            Err(e) if Instant::now() >= deadline => {
+                panic!("Could not connect to {}: {e}", path.display())
+            }
+            Err(_) => thread::sleep(Duration::from_millis(10)),
+        }
+    }
+}
+
+impl Harness {
+    /// Send `credential` with the calls that follow, rather than an AUTH_NONE one.
+    pub fn set_credential(&mut self, credential: OpaqueAuth) {
+        self.credential = credential;
+    }
+
+    /// Do `fault` to the next reply that is not already due a fault. Faults are done one to each
+    /// reply, in the order they were injected.
+    pub fn inject(&self, fault: Fault) {
+        self.faults.lock().unwrap().push_back(fault);
+    }
+
+    /// Call procedure `proc` of version `vers` of program `prog`, with the encoded `args`, and
+    /// return the encoded result.
+    pub fn call(&mut self, prog: u32, vers: u32, proc: u32, args: &[u8]) -> Result<Vec<u8>, Error> {
+        let credential = self.credential.clone();
+        do_rpc_call_with_auth(
+            &mut self.client,
+            prog,
+            vers,
+            proc,
+            credential,
+            OpaqueAuth::none(),
+            args,
+        )
+        .map(|(_, result)| result)
+    }
+
+    /// Like `call()`, decoding the result with `decode`, which is usually the `deserialize` method
+    /// of a generated type. A result that fails to decode, or that has bytes left over, is a
+    /// decoding error.
+    pub fn call_decode<R: Default, E>(
+        &mut self,
+        prog: u32,
+        vers: u32,
+        proc: u32,
+        args: &[u8],
+        decode: impl FnOnce(&mut R, &mut &[u8]) -> Result<(), E>,
+    ) -> Result<R, Error> {
+        let result = self.call(prog, vers, proc, args)?;
+
+        let mut input = result.as_slice();
+        let mut decoded = R::default();
+        decode(&mut decoded, &mut input).map_err(|_| Error::Protocol(ProtocolError::Decode))?;
+        if !input.is_empty() {
+            return Err(Error::Protocol(ProtocolError::Decode));
+        }
+
+        Ok(decoded)
+    }
+
+    /// Assert that the server has closed the connection, with nothing more to read.
+    #[track_caller]
+    pub fn assert_closed(&mut self) {
+        assert_closed(&mut self.client);
+    }
+
+    /// Close the connection, and return how the server's handling of it ended. A connection that
+    /// the client closes between calls ends with an `UnexpectedEof` I/O error, as the server cannot
+    /// tell it from one closed partway through a record mark.
+    pub fn finish(self) -> Result<(), Error> {
+        let _ = self.client.shutdown(Shutdown::Both);
+
+        self.server.join().expect("The server panicked")
+    }
+}
+
+impl Read for Harness {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.client.read(buf)
+    }
+}
+
+impl Write for Harness {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.client.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.client.flush()
+    }
+}
+
+/// Forward the calls from `client` to `server` as they are, and the replies back, doing the
+/// injected faults to them. A reply is taken to be one record in one fragment, as the servers of
+/// `rpc_protocol` send them.
+fn spawn_relay(client: Endpoint, server: Endpoint, faults: Faults) {
+    let mut calls_from = client.try_clone().unwrap();
+    let mut calls_to = server.try_clone().unwrap();
+    thread::spawn(move || {
+        let _ = io::copy(&mut calls_from, &mut calls_to);
+        let _ = calls_to.shutdown(Shutdown::Write);
+    });
+
+    thread::spawn(move || {
+        let mut client = client;
+        relay_replies(server, &mut client, &faults);
+        // Either end closing closes the other, which also ends the copying of calls:
+        let _ = client.shutdown(Shutdown::Both);
+    });
+}
+
+fn relay_replies(mut server: Endpoint, client: &mut Endpoint, faults: &Faults) {
+    loop {
+        let mut reply = vec![0; 4];
+        if server.read_exact(&mut reply).is_err() {
+            return;
+        }
+        let length = u32::from_be_bytes(reply[..4].try_into().unwrap()) & !(1 << 31);
+        reply.resize(4 + length as usize, 0);
+        if server.read_exact(&mut reply[4..]).is_err() {
+            return;
+        }
+
+        let fault = faults.lock().unwrap().pop_front();
+        match fault {
+            Some(Fault::Delay(delay)) => thread::sleep(delay),
+            Some(Fault::Truncate(length)) => {
+                let _ = client.write_all(&reply[..length.min(reply.len())]);
+                return;
+            }
+            Some(Fault::CorruptRecordMark) => reply[0] &= 0x7f,
+            None => {}
+        }
+
+        if client.write_all(&reply).is_err() {
+            return;
+        }
+    }
+}
+
+/// Assert that `res` is the failure of a call, for the reason `expected`.
+#[track_caller]
+pub fn assert_call_error<T: Debug>(res: Result<T, Error>, expected: RpcCallError) {
+    match res {
+        Err(Error::Call(e)) => assert_eq!(e, expected),
+        res => panic!("Expected the call to fail with {expected:?}, got {res:?}"),
+    }
+}
+
+/// Assert that `res` is the failure of a call whose credential was refused for the reason
+/// `expected`.
+#[track_caller]
+pub fn assert_auth_error<T: Debug>(res: Result<T, Error>, expected: AuthError) {
+    assert_call_error(res, RpcCallError::AuthError(expected));
+}
+
+/// Assert that `res` is an I/O error of the kind `expected`, such as `UnexpectedEof` for a reply
+/// cut short by a closed connection.
+#[track_caller]
+pub fn assert_io_error<T: Debug>(res: Result<T, Error>, expected: io::ErrorKind) {
+    match res {
+        Err(Error::Io(e)) => assert_eq!(e.kind(), expected, "{e}"),
+        res => panic!("Expected an I/O error of kind {expected:?}, got {res:?}"),
+    }
+}
+
+/// Assert that the other end of `stream` has closed it, with nothing more to read.
+#[track_caller]
+pub fn assert_closed(stream: &mut impl Read) {
+    match stream.read(&mut [0; 1]) {
+        Ok(0) => {}
+        Err(e) if e.kind() == io::ErrorKind::ConnectionReset => {}
+        res => panic!("Expected the connection to be closed, got {res:?}"),
+    }
+}
@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+use std::{
+    io::ErrorKind,
+    time::{Duration, Instant},
+};
+
+use rpc_protocol::{
+    server::{null_procedure, RpcProgram, RpcResult},
+    Call, Error, ProtocolError, RpcCallError,
+};
+use test_harness::*;
+
+const PROG: u32 = 400400;
+
+/// Adds its argument to the running total, and returns the total.
+fn add(call: &Call, total: &mut u32) -> RpcResult {
+    let mut arg = 0;
+    if xdr_lib::get_u32(&mut arg, &mut &call.arg[..]).is_err() {
+        return RpcResult::GarbageArgs;
+    }
+    *total += arg;
+
+    RpcResult::Success(total.to_be_bytes().to_vec())
+}
+
+fn adder() -> RpcProgram<u32> {
+    RpcProgram::new(PROG, 1, 1, vec![Some(null_procedure), Some(add)], 0)
+}
+
+#[test]
+fn calls() {
+    let mut harness = spawn_program(adder());
+
+    assert!(harness.call(PROG, 1, 0, &[]).unwrap().is_empty());
+    assert_eq!(
+        harness.call(PROG, 1, 1, &5u32.to_be_bytes()).unwrap(),
+        [0, 0, 0, 5]
+    );
+    let total = harness.call_decode(PROG, 1, 1, &2u32.to_be_bytes(), xdr_lib::get_u32);
+    assert_eq!(total.unwrap(), 7);
+
+    // A result with bytes left over does not decode:
+    let res = harness.call_decode(PROG, 1, 1, &0u32.to_be_bytes(), |_: &mut (), _| {
+        Ok::<_, ()>(())
+    });
+    assert!(matches!(res, Err(Error::Protocol(ProtocolError::Decode))));
+
+    assert_call_error(harness.call(PROG, 1, 1, &[]), RpcCallError::GarbageArgs);
+    assert_io_error(harness.finish(), ErrorKind::UnexpectedEof);
+
+    // A call that the program cannot answer ends the connection:
+    let mut harness = spawn_program(adder());
+    assert_call_error(harness.call(PROG, 1, 2, &[]), RpcCallError::ProcUnavail);
+    harness.assert_closed();
+    harness.finish().unwrap();
+
+    let mut harness = spawn_program(adder());
+    assert_call_error(harness.call(PROG + 1, 1, 0, &[]), RpcCallError::ProgUnavail);
+}
+
+#[test]
+fn faults() {
+    let mut harness = spawn_program(adder());
+
+    harness.inject(Fault::Delay(Duration::from_millis(200)));
+    let start = Instant::now();
+    harness.call(PROG, 1, 0, &[]).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // Once a delay has been done, the next reply is sent at once:
+    let start = Instant::now();
+    harness.call(PROG, 1, 0, &[]).unwrap();
+    assert!(start.elapsed() < Duration::from_millis(200));
+
+    harness.inject(Fault::Truncate(10));
+    assert_io_error(harness.call(PROG, 1, 0, &[]), ErrorKind::UnexpectedEof);
+    harness.assert_closed();
+
+    let mut harness = spawn_program(adder());
+    harness.inject(Fault::CorruptRecordMark);
+    let res = harness.call(PROG, 1, 0, &[]);
+    assert!(matches!(
+        res,
+        Err(Error::Protocol(ProtocolError::MessageFragment))
+    ));
+}
+
+#[test]
+fn tcp() {
+    let address = spawn_tcp(adder());
+    let mut stream = std::net::TcpStream::connect(address).unwrap();
+    let res = rpc_protocol::client::do_rpc_call(&mut stream, PROG, 1, 1, &3u32.to_be_bytes());
+    assert_eq!(res.unwrap(), [0, 0, 0, 3]);
+}
@@ -0,0 +1,26 @@
+enum Kind {
+	Regular = 1,
+	Directory = 2
+};
+
+struct Handle {
+	opaque data[8];
+	Kind kind;
+};
+
+struct Times {
+	unsigned hyper seconds;
+	unsigned int nanoseconds;
+};
+
+union Attributes switch (bool present) {
+case TRUE:
+	Times times;
+case FALSE:
+	void;
+};
+
+struct NamedHandle {
+	string name<>;
+	Handle handle;
+};
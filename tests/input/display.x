@@ -0,0 +1,32 @@
+typedef unsigned hyper Size;
+
+enum Kind {
+	Regular = 1,
+	Directory = 2
+};
+
+struct Time {
+	hyper seconds;
+	unsigned int nseconds;
+};
+
+struct Handle {
+	opaque data<64>;
+};
+
+struct Attributes {
+	Kind kind;
+	Size size;
+	Time mtime;
+	Handle handle;
+	Size *used;
+	string name<>;
+	unsigned int ids<>;
+};
+
+union Result switch (Kind kind) {
+case Regular:
+	Attributes attributes;
+default:
+	void;
+};
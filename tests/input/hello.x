@@ -12,3 +12,29 @@ struct Hello {
 
 const A_CONSTANT = 12345;
 const another = 15;
+const HEX_CONSTANT = 0x20;
+const OCTAL_CONSTANT = 010;
+const SAME_AS_ANOTHER = another;
+const BIG_CONSTANT = 0x100000000;
+
+struct ConstSized {
+	opaque bytes[SAME_AS_ANOTHER];
+};
+
+const INT_MIN = -2147483648;
+const NEGATIVE_HEX = -0x10;
+const HYPER_MIN = -0x8000000000000000;
+
+enum Offset {
+	Bottom = INT_MIN,
+	Behind = -1,
+	Level = 0,
+	Ahead = 1
+};
+
+union Measured switch (Offset which) {
+	case Behind:
+		unsigned int depth;
+	case Level:
+		void;
+};
@@ -0,0 +1,20 @@
+/* Types from another file, which is generated in a module of its own unless the two are merged: */
+%#include "shared_types.x"
+
+struct LookupArgs {
+    Handle dir;
+    Name name;
+};
+
+union LookupResult switch (Status status) {
+case Okay:
+    Handle object;
+default:
+    void;
+};
+
+program SHARED_PROGRAM {
+    version SHARED_V1 {
+        LookupResult LOOKUP(LookupArgs) = 1;
+    } = 1;
+} = 400100;
@@ -0,0 +1,18 @@
+const HANDLE_SIZE = 8;
+
+enum Status {
+    Okay = 0,
+    NotFound = 2,
+    Denied = 13
+};
+
+struct Handle {
+    opaque data[HANDLE_SIZE];
+};
+
+typedef string Name<255>;
+
+struct Entry {
+    Name name;
+    Handle handle;
+};
@@ -0,0 +1,17 @@
+/*
+ * Literal lines, which rpcgen copies into the C it generates, and which tests/alloc/build.rs
+ * turns into code with an annotator.
+ */
+
+%#derive PartialOrd, Ord
+struct Release {
+	unsigned int major;
+	unsigned int minor;
+};
+
+% /* Seconds since the epoch. */
+struct Event {
+%#pragma timestamp when
+	unsigned hyper when;
+	Release release;
+};
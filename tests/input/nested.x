@@ -0,0 +1,30 @@
+struct Entry {
+	unsigned hyper fileid;
+	struct {
+		unsigned int seconds;
+		unsigned int nseconds;
+	} mtime;
+	union switch (enum { Absent = 0, Present = 1 } status) {
+	case Present:
+		struct {
+			opaque data<>;
+			enum { Small = 0, Large = 1 } size;
+		} contents;
+	default:
+		void;
+	} type;
+	struct { int level; } *extra;
+	struct { unsigned int id; } owners<>;
+};
+
+typedef struct {
+	int major;
+	int minor;
+} Version;
+
+union Lookup switch (enum { Found = 0, Missing = 1 } status) {
+case Found:
+	Version release;
+case Missing:
+	void;
+};
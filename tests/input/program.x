@@ -0,0 +1,30 @@
+struct Greeting {
+	string message<>;
+};
+
+enum Mood {
+	HAPPY  = 0,
+	SAD    = 1,
+	GRUMPY = 2
+};
+
+union Mingle switch (Mood mood) {
+case HAPPY:
+	Greeting greeting;
+default:
+	void;
+};
+
+program GREETER_PROGRAM {
+	version GREETER_V1 {
+		void NULL(void)                 = 0;
+		Greeting HELLO(void)            = 1;
+	} = 1;
+	version GREETER_V2 {
+		void NULL(void)                 = 0;
+		Greeting HELLO(void)            = 1;
+		Greeting GOODBYE(Greeting)      = 3;
+		bool     FRIENDLY(Greeting)     = 4;
+		Mingle   MINGLE(void)           = 5;
+	} = 2;
+} = 400111;
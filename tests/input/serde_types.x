@@ -0,0 +1,28 @@
+typedef string dirname<>;
+
+struct Directory {
+	dirname name;
+	dirname *alias;
+	dirname entries<>;
+	opaque cookie[8];
+	unsigned hyper size;
+};
+
+enum LookupStatus {
+	Found = 0,
+	Missing = 1
+};
+
+union Lookup switch (LookupStatus status) {
+case Found:
+	dirname name;
+default:
+	void;
+};
+
+union MaybeName switch (bool present) {
+case TRUE:
+	dirname name;
+case FALSE:
+	void;
+};
@@ -63,3 +63,10 @@ typedef int my_int_type;
 struct HasTypedef {
 	my_int_type blah;
 };
+
+/* Fields named with Rust keywords, where one of them has a variable width: */
+struct Keywords {
+	string where<>;
+	int type;
+	opaque data<>;
+};
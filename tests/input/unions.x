@@ -108,3 +108,43 @@ case one:
 case two:
         void;
 };
+
+const FLAVOR_GSS = 6;
+typedef unsigned int flavor;
+
+union SecInfo switch (flavor which) {
+case FLAVOR_GSS:
+    Stuff gss;
+case 1:
+case 2:
+    void;
+default:
+    void;
+};
+
+union NewOrOld switch (bool new_owner) {
+case TRUE:
+    Stuff new_stuff;
+case FALSE:
+    unsigned int old_id;
+};
+
+enum Status {
+    Okay = 0,
+    Bad = 1,
+    Worse = 2
+};
+
+union Outcome switch (Status status) {
+case Okay:
+    Stuff stuff;
+default:
+    int code;
+};
+
+union VoidOutcome switch (Status status) {
+case Okay:
+    void;
+default:
+    void;
+};
@@ -135,7 +135,7 @@ fn arrays_of_user_defined_type() {
     }
     assert_eq!(before.get_width(), 2100);
 
-    let mut bytes = [1; 2100];
+    let mut bytes = vec![1; before.serialized_size()];
 
     assert_eq!(2100, before.serialize(&mut bytes));
 
@@ -160,3 +160,29 @@ fn too_long_array_of_user_defined_type() {
 
     let _ = before.serialize(&mut bytes);
 }
+
+#[test]
+fn buffer_too_small() {
+    let mut before = IntArrays::default();
+    for i in 0..7 {
+        before.limited.push(AnInt { a: i });
+    }
+    let size = before.serialized_size();
+    assert_eq!(size, before.get_width());
+
+    let mut bytes = vec![1; size - 4];
+    assert_eq!(
+        before.try_serialize(&mut bytes),
+        Err(xdr_lib::BufferTooSmall {
+            needed: size,
+            available: size - 4
+        })
+    );
+    assert!(bytes.iter().all(|b| *b == 1));
+
+    let mut bytes = vec![1; size + 4];
+    assert_eq!(before.try_serialize(&mut bytes), Ok(size));
+    let mut after = IntArrays::default();
+    after.deserialize(&mut bytes.as_slice()).unwrap();
+    assert_eq!(before, after);
+}
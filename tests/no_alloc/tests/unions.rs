@@ -33,8 +33,10 @@ fn bool_union() {
 #[test]
 fn basic_enum() {
     let before = Cases::two;
+    assert_eq!(before.serialized_size(), 4);
     let mut bytes = [1; 4];
     assert_eq!(4, before.serialize(&mut bytes));
+    assert!(before.try_serialize(&mut bytes[..3]).is_err());
     let mut after = Cases::default();
     after.deserialize(&mut bytes.as_slice()).unwrap();
     assert_eq!(before, after);
@@ -0,0 +1,10 @@
+fn main() {
+    xdr_codegen::Compiler::new()
+        .file("../input/arrays.x")
+        .file("../input/optional.x")
+        .file("../input/unions.x")
+        .file("../input/program.x")
+        .no_std()
+        .run()
+        .expect("That should have worked. :(");
+}
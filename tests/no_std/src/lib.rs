@@ -0,0 +1,11 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// The code generated with `Compiler::no_std()`, which must build without `std`.
+
+#![no_std]
+
+include!(concat!(env!("OUT_DIR"), "/arrays.rs"));
+include!(concat!(env!("OUT_DIR"), "/optional.rs"));
+include!(concat!(env!("OUT_DIR"), "/unions.rs"));
+include!(concat!(env!("OUT_DIR"), "/program.rs"));
@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Tests for the code generated with `Compiler::no_std()`, which borrows its variable-length data.
+
+use test_no_std::{arrays::*, optional::*, program::*, unions::*};
+
+#[test]
+fn borrowed_strings_and_opaque_data() {
+    let value = OpaqueArrays {
+        bytes: [1, 2, 3],
+        bytes_2: &[4, 5],
+        bytes_3: b"hello",
+    };
+    let mut buf = [0xff; 32];
+    let len = value.try_serialize(&mut buf).unwrap();
+    assert_eq!(len, value.serialized_size());
+    assert_eq!(
+        buf[..len],
+        [
+            1, 2, 3, 0, // bytes, padded
+            0, 0, 0, 2, 4, 5, 0, 0, // bytes_2
+            0, 0, 0, 5, b'h', b'e', b'l', b'l', b'o', 0, 0, 0, // bytes_3
+        ]
+    );
+
+    let strings = Strings {
+        str: b"hello",
+        str_2: b"world!!!",
+    };
+    assert_eq!(strings.get_width(), 24);
+    let mut buf = [0; 24];
+    assert_eq!(strings.serialize(&mut buf), 24);
+    assert_eq!(&buf[4..9], b"hello");
+    assert_eq!(&buf[16..24], b"world!!!");
+
+    assert!(strings.try_serialize(&mut buf[..20]).is_err());
+}
+
+#[test]
+#[should_panic]
+fn limited_length_string_exceeded() {
+    let value = Strings {
+        str: b"hello, world!",
+        str_2: b"",
+    };
+    let _ = value.serialize(&mut [0; 36]);
+}
+
+#[test]
+fn borrowed_arrays() {
+    let limited: Vec<_> = (0..7).map(|a| AnInt { a }).collect();
+    let unlimited: Vec<_> = (0..512).map(|a| AnInt { a }).collect();
+    let value = IntArrays {
+        fixed: [AnInt { a: 1 }; 4],
+        limited: &limited,
+        unlimited: &unlimited,
+    };
+    assert_eq!(value.get_width(), 2100);
+    let mut buf = [0; 2100];
+    assert_eq!(value.serialize(&mut buf), 2100);
+    assert_eq!(buf[16..20], 7_u32.to_be_bytes());
+    assert_eq!(buf[2096..], 511_u32.to_be_bytes());
+
+    // Every type is Copy, and its default holds empty slices:
+    let copy = value;
+    assert_eq!(copy, value);
+    assert_eq!(IntArrays::default().get_width(), 24);
+    assert_eq!(ManyStrings::default().get_width(), 48);
+}
+
+#[test]
+fn self_referential_optionals_are_slices() {
+    let nodes = [0, 1, 2, 3, 4].map(|data| ListNode { data });
+    let list = ListBegin { list: &nodes };
+    assert_eq!(list.get_width(), 44);
+    let mut buf = [0xff; 44];
+    assert_eq!(list.serialize(&mut buf), 44);
+    assert_eq!(buf[..12], [0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1]);
+    assert_eq!(buf[40..], [0, 0, 0, 0]);
+
+    let groups = [
+        groupnode { gr_name: b"wheel" },
+        groupnode { gr_name: b"staff" },
+    ];
+    let nodes = [
+        exportnode {
+            ex_dir: b"/export",
+            ex_groups: &groups,
+        },
+        exportnode {
+            ex_dir: b"/home",
+            ex_groups: &[],
+        },
+    ];
+    let exports = exports { inner: &nodes };
+    let mut buf = [0; 128];
+    assert_eq!(exports.try_serialize(&mut buf), Ok(exports.get_width()));
+}
+
+#[test]
+fn borrowed_unions() {
+    let value = HasString {
+        inner: Some(b"abc"),
+    };
+    let mut buf = [0xff; 12];
+    assert_eq!(value.serialize(&mut buf), 12);
+    assert_eq!(buf, [0, 0, 0, 1, 0, 0, 0, 3, b'a', b'b', b'c', 0]);
+
+    let greeting = Greeting { message: b"hi" };
+    let mingle = Mingle::HAPPY(greeting);
+    assert_eq!(mingle.serialized_size(), 12);
+    assert_eq!(Mingle::default(), Mingle::HAPPY(Greeting::default()));
+    assert_eq!(
+        procedures::proc_name(2, procedures::GREETER_V2::MINGLE),
+        Some("MINGLE")
+    );
+}
@@ -66,3 +66,20 @@ fn test_hello_one_byte_short() {
 
     assert!(HelloReader::new(data.as_slice()).is_err());
 }
+
+#[test]
+fn test_negative_enum_values() {
+    assert_eq!(
+        Offset::deserialize(&i32::MIN.to_be_bytes()),
+        Ok(Offset::Bottom)
+    );
+    assert_eq!(
+        Offset::deserialize(&(-1_i32).to_be_bytes()),
+        Ok(Offset::Behind)
+    );
+    assert!(Offset::deserialize(&(-2_i32).to_be_bytes()).is_err());
+
+    let buf = [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 7];
+    let reader = MeasuredReader::new(&buf).unwrap();
+    assert_eq!(reader.deserialize(), MeasuredRet::Behind(7));
+}
@@ -119,12 +119,12 @@ fn test_optionals_recursive_varlen_interiors() {
     let mut export_groups: Vec<Exportnode> = Vec::new();
     for i in 0..5 {
         let mut export = Exportnode {
-            dirpath: format!("test_{i}").into(),
+            dirpath: format!("test_{i}"),
             groups: Vec::new(),
         };
         for j in 0..5 {
             let group = Groupnode {
-                name: format!("group_{j}").into(),
+                name: format!("group_{j}"),
             };
             export.groups.push(group);
         }
@@ -151,7 +151,7 @@ fn test_optionals_recursive_varlen_interiors() {
     let reader = exportsReader::new(data.as_slice()).unwrap();
     for (i, en) in reader.get_inner().enumerate() {
         assert_eq!(
-            export_groups.get(i).unwrap().dirpath.as_str().as_bytes(),
+            export_groups.get(i).unwrap().dirpath.as_bytes(),
             en.as_ref().unwrap().get_ex_dir().as_bytes()
         );
 
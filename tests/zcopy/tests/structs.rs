@@ -48,8 +48,8 @@ fn test_structs_basic() {
         assert_eq!(bar_reader.get_width().unwrap(), 28);
     }
 
-    assert_eq!(reader.get_no(), false);
-    assert_eq!(reader.get_yes(), true);
+    assert!(!reader.get_no());
+    assert!(reader.get_yes());
     assert_eq!(reader.get_width().unwrap(), 44);
 }
 
@@ -72,3 +72,21 @@ fn test_structs_one_byte_short() {
 
     assert!(structs::FooReader::new(data.as_slice()).is_err());
 }
+
+#[test]
+fn keyword_field_names() {
+    #[rustfmt::skip]
+    let data: Vec<u8> = vec![
+        0x00, 0x00, 0x00, 0x04, // Keywords.where length
+        b'h', b'e', b'r', b'e', // Keywords.where
+        0x00, 0x00, 0x00, 0x03, // int Keywords.type
+        0x00, 0x00, 0x00, 0x01, // Keywords.data length
+        0xAA, 0x00, 0x00, 0x00, // Keywords.data, padded
+    ];
+
+    let reader = structs::KeywordsReader::new(data.as_slice()).unwrap();
+    assert_eq!(reader.get_where(), "here");
+    assert_eq!(reader.get_type(), 3);
+    assert_eq!(reader.get_data(), &[0xAA]);
+    assert_eq!(reader.get_width().unwrap(), 20);
+}
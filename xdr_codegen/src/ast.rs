@@ -1,12 +1,72 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
+use std::collections::HashMap;
+
 #[derive(Debug)]
 pub struct Schema {
     pub definitions: Vec<Definition>,
+    /// The line each of the `definitions` begins on. An anonymous type is given the line of the
+    /// definition it is declared in.
+    pub lines: Vec<usize>,
     pub programs: Vec<Program>,
     /// If the schema has any string type within it -- need to know during code generation
     pub contains_string: bool,
+    /// The files named by `%#include` lines, relative to the directory of the file that names them.
+    pub includes: Vec<String>,
+    /// The modules generated from the files that this schema includes, which its generated module
+    /// imports.
+    pub imports: Vec<String>,
+    /// How many of the `definitions`, at the start, come from included files, and so are generated
+    /// in the modules of those files rather than in this schema's.
+    pub imported: usize,
+    /// The `%` lines of the source, by the name of the definition or program that they precede or
+    /// are within, as they would be copied into generated C.
+    pub literals: HashMap<String, Vec<String>>,
+}
+
+impl Schema {
+    /// Make the definitions of `included`, each a schema of an included file and the module
+    /// generated from it, visible to this schema, ahead of its own.
+    pub fn import(&mut self, included: Vec<(String, Schema)>) {
+        let mut definitions = Vec::new();
+        let mut lines = Vec::new();
+        for (module, mut schema) in included {
+            definitions.append(&mut schema.definitions);
+            lines.append(&mut schema.lines);
+            self.contains_string |= schema.contains_string;
+            self.literals.extend(schema.literals);
+            self.imports.push(module);
+        }
+        self.imported = definitions.len();
+        definitions.append(&mut self.definitions);
+        lines.append(&mut self.lines);
+        self.definitions = definitions;
+        self.lines = lines;
+    }
+
+    /// Merge `schemas` into one, whose code is generated in a single module.
+    pub fn merge(schemas: Vec<Schema>) -> Schema {
+        let mut merged = Schema {
+            definitions: Vec::new(),
+            lines: Vec::new(),
+            programs: Vec::new(),
+            contains_string: false,
+            includes: Vec::new(),
+            imports: Vec::new(),
+            imported: 0,
+            literals: HashMap::new(),
+        };
+        for mut schema in schemas {
+            merged.definitions.append(&mut schema.definitions);
+            merged.lines.append(&mut schema.lines);
+            merged.programs.append(&mut schema.programs);
+            merged.contains_string |= schema.contains_string;
+            merged.literals.extend(schema.literals);
+        }
+
+        merged
+    }
 }
 
 #[derive(Debug)]
@@ -14,6 +74,7 @@ pub struct Program {
     pub name: String,
     pub versions: Vec<ProgramVersion>,
     pub id: u32,
+    pub line: usize,
 }
 
 #[derive(Debug)]
@@ -27,7 +88,7 @@ pub struct ProgramVersion {
 pub struct Procedure {
     pub name: String,
     pub _arg: ProcedureType,
-    pub _ret: ProcedureType,
+    pub ret: ProcedureType,
     pub id: u32,
 }
 
@@ -158,7 +219,7 @@ pub enum ArraySize {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Value {
-    Int(u64),
+    Int(i128),
     Name(UnresolvedName),
 }
 
@@ -0,0 +1,199 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// A helper for the build scripts of crates that generate code from XDR specifications.
+//
+// Each specification is compiled by a `Compiler` of its own, with the options given for every
+// specification and then those given for it, and its code, and that of the files it includes, is
+// written into OUT_DIR, in files named after their modules. Cargo is told to rerun the build script
+// when a specification, or a file it includes, changes, and failures are reported to cargo as
+// errors, one for each specification that failed, rather than as a panic.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::Compiler;
+
+/// Sets options of the `Compiler` of a specification, such as `|c| c.derive_serde()`.
+type Options = Box<dyn Fn(&mut Compiler) -> &mut Compiler>;
+
+/// Generate code from XDR specifications in a build script:
+///
+/// ```ignore
+/// fn main() {
+///     xdr_codegen::build_helper()
+///         .options(|c| c.enable_deserialize_from())
+///         .spec("mount_proto.x")
+///         .spec_with("nfs3_xdr.x", |c| c.enable_display())
+///         .run();
+/// }
+/// ```
+///
+/// Then include the generated code of `nfs3_xdr.x` with
+/// `include!(concat!(env!("OUT_DIR"), "/nfs3_xdr.rs"));`.
+pub fn build_helper() -> BuildHelper {
+    BuildHelper {
+        options: Vec::new(),
+        specs: Vec::new(),
+    }
+}
+
+/// Generates code from the XDR specifications of a build script. See `build_helper()`.
+pub struct BuildHelper {
+    /// The options of every specification.
+    options: Vec<Options>,
+
+    /// Each specification, with its own options.
+    specs: Vec<(PathBuf, Option<Options>)>,
+}
+
+impl BuildHelper {
+    /// Set options of the `Compiler` of every specification, including those added before. May be
+    /// called more than once.
+    pub fn options<F>(&mut self, options: F) -> &mut Self
+    where
+        F: Fn(&mut Compiler) -> &mut Compiler + 'static,
+    {
+        self.options.push(Box::new(options));
+        self
+    }
+
+    /// Generate code from the specification at `path`, relative to the directory of the crate's
+    /// manifest, into a file named after it, such as `nfs3_xdr.rs` for `nfs3_xdr.x`.
+    pub fn spec<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<Path>,
+    {
+        self.specs.push((path.as_ref().to_path_buf(), None));
+        self
+    }
+
+    /// Like `spec()`, with options of its own, after those of every specification. Calling
+    /// `merge()` names the module and the file that its code is generated in.
+    pub fn spec_with<P, F>(&mut self, path: P, options: F) -> &mut Self
+    where
+        P: AsRef<Path>,
+        F: Fn(&mut Compiler) -> &mut Compiler + 'static,
+    {
+        self.specs
+            .push((path.as_ref().to_path_buf(), Some(Box::new(options))));
+        self
+    }
+
+    /// Generate and write the code of every specification, and print the `cargo::rerun-if-changed`
+    /// lines of the files it is generated from. Returns an error for each specification that
+    /// failed; the others are still generated.
+    pub fn build(&mut self) -> Result<(), Vec<BuildError>> {
+        let mut errors = Vec::new();
+
+        // The code written to each file, which two specifications that include the same file both
+        // generate:
+        let mut written: HashMap<PathBuf, String> = HashMap::new();
+        for (spec, spec_options) in self.specs.iter() {
+            let mut compiler = Compiler::new();
+            for options in self.options.iter().chain(spec_options) {
+                options(&mut compiler);
+            }
+
+            println!("cargo::rerun-if-changed={}", spec.display());
+            let error = |error| BuildError {
+                spec: spec.clone(),
+                error,
+            };
+            let files = match Compiler::load_files(std::slice::from_ref(spec)) {
+                Ok(files) => files,
+                Err(e) => {
+                    // The files it includes are not known, but are most likely beside it:
+                    if let Some(dir) = spec.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                        println!("cargo::rerun-if-changed={}", dir.display());
+                    }
+                    errors.push(error(e));
+                    continue;
+                }
+            };
+            // The specification itself is loaded last, after the files it includes:
+            for file in files[..files.len() - 1].iter() {
+                println!("cargo::rerun-if-changed={}", file.path.display());
+            }
+
+            let generated = match compiler.generate_files(&files) {
+                Ok(generated) => generated,
+                Err(e) => {
+                    errors.push(error(e.into()));
+                    continue;
+                }
+            };
+            for (out_file, code) in generated {
+                if written.get(&out_file).is_some_and(|other| *other != code) {
+                    errors.push(error(
+                        format!(
+                            "{} is also generated from another specification, with different \
+                             code; name the module of one of them with `merge()`",
+                            out_file.display()
+                        )
+                        .into(),
+                    ));
+                    continue;
+                }
+                if let Err(e) = std::fs::write(&out_file, &code) {
+                    errors.push(error(e.into()));
+                    continue;
+                }
+                written.insert(out_file, code);
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(())
+    }
+
+    /// Like `build()`, and report each error to cargo, which fails the build once the build script
+    /// exits, and shows each error with the specification it is of.
+    pub fn run(&mut self) {
+        if let Err(errors) = self.build() {
+            for error in errors {
+                for line in error.to_string().lines() {
+                    println!("cargo::error={line}");
+                }
+            }
+        }
+    }
+}
+
+/// A specification that code could not be generated from, and why.
+#[derive(Debug)]
+pub struct BuildError {
+    spec: PathBuf,
+    error: Box<dyn Error>,
+}
+
+impl BuildError {
+    /// The path to the specification, as it was given.
+    pub fn spec(&self) -> &Path {
+        &self.spec
+    }
+
+    /// Why code could not be generated from it.
+    pub fn error(&self) -> &dyn Error {
+        &*self.error
+    }
+}
+
+impl Error for BuildError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.spec.display(), self.error)
+    }
+}
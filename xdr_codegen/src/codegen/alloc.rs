@@ -116,7 +116,6 @@ impl ValidatedStruct {
         buf.code_block("pub fn serialize_alloc(&self) -> Vec<u8>", |buf| {
             buf.add_line("let mut buf = Vec::new();");
             for (decl, _) in self.members.iter() {
-                buf.add_line(&format!("// {}:", decl.name));
                 decl.serialize_inline(None, Context::NotInUnion, buf, tab);
             }
             buf.add_line("buf");
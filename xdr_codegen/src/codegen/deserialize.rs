@@ -6,8 +6,46 @@
 use super::*;
 use crate::symbol_table::ValidatedSymbolTable;
 
-const DESERIALIZE_SIGNATURE: &str =
-    "pub fn deserialize(&mut self, input: &mut &[u8]) -> xdr_lib::Result<()>";
+/// What a generated deserialization routine decodes from.
+#[derive(Copy, Clone)]
+pub(super) enum Input {
+    /// `deserialize()` decodes from a slice, and advances it past the decoded value.
+    Slice,
+
+    /// `deserialize_from()` decodes from an `std::io::Read`, so that a large value can be decoded
+    /// as it arrives rather than from a buffer that holds all of it.
+    Reader,
+}
+
+impl Input {
+    fn signature(self) -> &'static str {
+        match self {
+            Input::Slice => {
+                "pub fn deserialize(&mut self, input: &mut &[u8]) -> xdr_lib::Result<()>"
+            }
+            Input::Reader => {
+                "pub fn deserialize_from(&mut self, input: &mut (impl std::io::Read + ?Sized)) \
+                 -> xdr_lib::Result<()>"
+            }
+        }
+    }
+
+    /// The name of the method of generated types that decodes from this input.
+    fn method(self) -> &'static str {
+        match self {
+            Input::Slice => "deserialize",
+            Input::Reader => "deserialize_from",
+        }
+    }
+
+    /// The `xdr_lib` function that decodes a primitive type, such as `i32`, from this input.
+    fn get(self, primitive: &str) -> String {
+        match self {
+            Input::Slice => format!("xdr_lib::get_{primitive}"),
+            Input::Reader => format!("xdr_lib::read_{primitive}"),
+        }
+    }
+}
 
 impl Array {
     pub(super) fn deserialize_inline(
@@ -15,46 +53,73 @@ impl Array {
         name: &str,
         buf: &mut CodeBuf,
         tab: &ValidatedSymbolTable,
+        input: Input,
     ) {
+        let get_u32 = input.get("u32");
         match &self.size {
             ArraySize::Fixed(_) => {
                 buf.add_line(&format!("let len = {name}.len();"));
             }
-            _ => {
+            ArraySize::Limited(lim) => {
                 buf.add_line("let mut len = 0;");
-                buf.add_line("xdr_lib::get_u32(&mut len, input)?;");
+                buf.add_line(&format!("{get_u32}(&mut len, input)?;"));
+                // Reject an array longer than its limit before allocating space for it. A limit
+                // that does not fit in the u32 length can never be exceeded.
+                let lim = lim.as_const(tab);
+                if lim < u32::MAX.into() {
+                    buf.code_block(&format!("if len > {lim}"), |buf| {
+                        buf.add_line("return Err(xdr_lib::DeserializeError);");
+                    });
+                }
+            }
+            ArraySize::Unlimited => {
+                buf.add_line("let mut len = 0;");
+                buf.add_line(&format!("{get_u32}(&mut len, input)?;"));
             }
         };
         match &self.kind {
             ArrayKind::UserType(ty) => {
                 buf.block_statement("for _i in 0..len", |buf| {
                     buf.add_line(&format!("let mut new = {};", ty.default_value(tab)));
-                    ty.deserialize_inline("new", buf, tab);
+                    ty.deserialize_inline("new", buf, tab, input);
                     match &self.size {
                         ArraySize::Fixed(_) => buf.add_line(&format!("{name}[_i] = new;")),
                         _ => buf.add_line(&format!("{name}.push(new);")),
                     }
                 });
             }
+            ArrayKind::Byte if !matches!(self.size, ArraySize::Fixed(_)) => match input {
+                Input::Slice => {
+                    buf.add_line("let bytes = xdr_lib::get_opaque(len as usize, input)?;");
+                    buf.add_line(&format!("{name}.extend_from_slice(bytes);"));
+                }
+                // Read straight into the vector, without an intermediate buffer:
+                Input::Reader => buf.add_line(&format!(
+                    "xdr_lib::read_opaque_into(len as usize, &mut {name}, input)?;"
+                )),
+            },
             _ => {
-                buf.add_line("let (bytes, rest) = input.split_at(len as usize);");
-                buf.add_line("*input = rest;");
+                let bytes = match input {
+                    Input::Slice => {
+                        buf.add_line("let bytes = xdr_lib::get_opaque(len as usize, input)?;");
+                        "bytes"
+                    }
+                    Input::Reader => {
+                        buf.add_line("let bytes = xdr_lib::read_opaque(len as usize, input)?;");
+                        "&bytes"
+                    }
+                };
                 match &self.size {
                     ArraySize::Fixed(_) => {
-                        buf.add_line(&format!("{name}.clone_from_slice(bytes);"))
+                        buf.add_line(&format!("{name}.clone_from_slice({bytes});"))
                     }
                     _ => match &self.kind {
-                        ArrayKind::Byte => {
-                            buf.add_line(&format!("{name}.extend_from_slice(bytes);"))
-                        }
-                        ArrayKind::Ascii => buf
-                            .add_line(&format!("{name}.push(std::ffi::OsStr::from_bytes(bytes));")),
-                        ArrayKind::UserType(_) => unreachable!(),
+                        ArrayKind::Ascii => buf.add_line(&format!(
+                            "{name}.push(std::ffi::OsStr::from_bytes({bytes}));"
+                        )),
+                        ArrayKind::Byte | ArrayKind::UserType(_) => unreachable!(),
                     },
                 };
-                buf.add_line("let padding = (4 - len % 4) % 4;");
-                buf.add_line("let (_, rest) = input.split_at(padding as usize);");
-                buf.add_line("*input = rest;");
             }
         }
     }
@@ -72,6 +137,7 @@ impl NamedDeclaration {
         override_name: Option<&str>,
         buf: &mut CodeBuf,
         tab: &ValidatedSymbolTable,
+        input: Input,
     ) {
         let var_name = match override_name {
             Some(over) => over.to_string(),
@@ -79,24 +145,29 @@ impl NamedDeclaration {
         };
         match &self.kind {
             DeclarationKind::Scalar(ty) => {
-                ty.deserialize_inline(&var_name, buf, tab);
+                ty.deserialize_inline(&var_name, buf, tab, input);
             }
             DeclarationKind::Array(a) => {
-                a.deserialize_inline(&var_name, buf, tab);
+                a.deserialize_inline(&var_name, buf, tab, input);
             }
             DeclarationKind::Optional(o) => {
-                o.deserialize_optional_inline(&var_name, buf, tab);
+                o.deserialize_optional_inline(&var_name, buf, tab, input);
             }
         }
     }
 }
 
 impl ValidatedUnion {
-    pub(super) fn deserialize_definition(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
-        buf.code_block(DESERIALIZE_SIGNATURE, |buf| {
+    pub(super) fn deserialize_definition(
+        &self,
+        buf: &mut CodeBuf,
+        tab: &ValidatedSymbolTable,
+        input: Input,
+    ) {
+        buf.code_block(input.signature(), |buf| {
             match &self.body {
-                ValidatedUnionBody::Bool(b) => b.deserialize_bool(buf, tab),
-                ValidatedUnionBody::Enum(e) => e.deserialize_enum(buf, tab),
+                ValidatedUnionBody::Bool(b) => b.deserialize_bool(buf, tab, input),
+                ValidatedUnionBody::Enum(e) => e.deserialize_enum(buf, tab, input),
             };
             buf.add_line("Ok(())");
         });
@@ -104,9 +175,14 @@ impl ValidatedUnion {
 }
 
 impl ValidatedUnionBoolBody {
-    pub(super) fn deserialize_bool(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
+    pub(super) fn deserialize_bool(
+        &self,
+        buf: &mut CodeBuf,
+        tab: &ValidatedSymbolTable,
+        input: Input,
+    ) {
         buf.add_line("let mut discriminant: u32 = 0;");
-        buf.add_line("xdr_lib::get_u32(&mut discriminant, input)?;");
+        buf.add_line(&format!("{}(&mut discriminant, input)?;", input.get("u32")));
         buf.block_statement("match discriminant", |buf| {
             buf.add_line("0 => (*self).inner = None,");
             buf.code_block("_ => ", |buf| {
@@ -114,7 +190,8 @@ impl ValidatedUnionBoolBody {
                     "let mut val = {};",
                     self.true_arm.default_value(tab)
                 ));
-                self.true_arm.deserialize_inline(Some("val"), buf, tab);
+                self.true_arm
+                    .deserialize_inline(Some("val"), buf, tab, input);
                 buf.add_line("(*self).inner = Some(val)");
             });
         });
@@ -122,9 +199,14 @@ impl ValidatedUnionBoolBody {
 }
 
 impl ValidatedUnionEnumBody {
-    pub(super) fn deserialize_enum(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
+    pub(super) fn deserialize_enum(
+        &self,
+        buf: &mut CodeBuf,
+        tab: &ValidatedSymbolTable,
+        input: Input,
+    ) {
         buf.add_line("let mut discriminant = 0;");
-        buf.add_line("xdr_lib::get_i32(&mut discriminant, input)?;");
+        buf.add_line(&format!("{}(&mut discriminant, input)?;", input.get("i32")));
         buf.block_statement("*self = match discriminant", |buf| {
             for arm in self.arms.iter() {
                 let discriminant_value = self.get_discriminant_value(&arm.0, tab);
@@ -136,7 +218,7 @@ impl ValidatedUnionEnumBody {
                         }
                         Declaration::Named(n) => {
                             buf.add_line(&format!("let mut inner = {};", n.default_value(tab)));
-                            n.deserialize_inline(Some("inner"), buf, tab);
+                            n.deserialize_inline(Some("inner"), buf, tab, input);
                             buf.add_line(&format!("Self::{arm_name}(inner) "));
                         }
                     };
@@ -150,7 +232,7 @@ impl ValidatedUnionEnumBody {
                     Declaration::Named(n) => {
                         buf.code_block("_ => ", |buf| {
                             buf.add_line(&format!("let mut inner = {};", n.default_value(tab)));
-                            n.deserialize_inline(Some("inner"), buf, tab);
+                            n.deserialize_inline(Some("inner"), buf, tab, input);
                             buf.add_line("Self::Default(inner) ");
                         });
                     }
@@ -175,7 +257,7 @@ impl ValidatedStruct {
         let code = off
             .deps
             .iter()
-            .map(|v| format!("self.get_{}_width(){}", v, unwrapper))
+            .map(|v| format!("self.get_{}_width(){}", unraw(v), unwrapper))
             .chain(
                 vec![format!("{}", off.known)]
                     .into_iter()
@@ -195,7 +277,7 @@ impl ValidatedStruct {
         let code = off
             .deps
             .iter()
-            .map(|v| format!("{}_width", v))
+            .map(|v| format!("{}_width", unraw(v)))
             .chain(
                 vec![format!("{}", off.known)]
                     .into_iter()
@@ -211,11 +293,15 @@ impl ValidatedStruct {
         }
     }
 
-    pub(super) fn deserialize_definition(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
-        buf.code_block(DESERIALIZE_SIGNATURE, |buf| {
+    pub(super) fn deserialize_definition(
+        &self,
+        buf: &mut CodeBuf,
+        tab: &ValidatedSymbolTable,
+        input: Input,
+    ) {
+        buf.code_block(input.signature(), |buf| {
             for (decl, _) in self.members.iter() {
-                buf.add_line(&format!("// {}:", decl.name));
-                decl.deserialize_inline(None, buf, tab);
+                decl.deserialize_inline(None, buf, tab, input);
             }
             buf.add_line("Ok(())");
         });
@@ -223,10 +309,15 @@ impl ValidatedStruct {
 }
 
 impl ValidatedEnum {
-    pub(super) fn deserialize_definition(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
-        buf.code_block(DESERIALIZE_SIGNATURE, |buf| {
+    pub(super) fn deserialize_definition(
+        &self,
+        buf: &mut CodeBuf,
+        tab: &ValidatedSymbolTable,
+        input: Input,
+    ) {
+        buf.code_block(input.signature(), |buf| {
             buf.add_line("let mut val = 0;");
-            buf.add_line("xdr_lib::get_i32(&mut val, input)?;");
+            buf.add_line(&format!("{}(&mut val, input)?;", input.get("i32")));
             buf.block_statement("*self = match val", |buf| {
                 for variant in self.variants.iter() {
                     let val = variant.1.as_const(tab);
@@ -245,32 +336,34 @@ impl XdrType {
         var_name: &str,
         buf: &mut CodeBuf,
         tab: &ValidatedSymbolTable,
+        input: Input,
     ) {
         // Handle typedefs specially by finding their underlying type:
         if let XdrType::Name(name) = self {
             let definition = tab.lookup_definition(name);
             if let ValidatedDefinition::TypeDef(ref tdef) = *definition {
-                tdef.decl.deserialize_inline(Some(var_name), buf, tab);
+                tdef.decl
+                    .deserialize_inline(Some(var_name), buf, tab, input);
                 return;
             };
         };
 
         // typedef case already handled, non-typedefs follow:
-        let method = self.deserialize_method();
+        let method = self.deserialize_method(input);
         buf.add_line(&format!("{method}(&mut {var_name}, input)?;"));
     }
 
-    fn deserialize_method(&self) -> String {
+    fn deserialize_method(&self, input: Input) -> String {
         match self {
-            XdrType::Int => "xdr_lib::get_i32".to_string(),
-            XdrType::UInt => "xdr_lib::get_u32".to_string(),
-            XdrType::Hyper => "xdr_lib::get_i64".to_string(),
-            XdrType::UHyper => "xdr_lib::get_u64".to_string(),
+            XdrType::Int => input.get("i32"),
+            XdrType::UInt => input.get("u32"),
+            XdrType::Hyper => input.get("i64"),
+            XdrType::UHyper => input.get("u64"),
             XdrType::Float => todo!(),
             XdrType::Double => todo!(),
             XdrType::Quadruple => todo!(),
-            XdrType::Bool => "xdr_lib::get_bool".to_string(),
-            XdrType::Name(n) => format!("{n}::deserialize"),
+            XdrType::Bool => input.get("bool"),
+            XdrType::Name(n) => format!("{n}::{}", input.method()),
         }
     }
 
@@ -279,24 +372,26 @@ impl XdrType {
         name: &str,
         buf: &mut CodeBuf,
         tab: &ValidatedSymbolTable,
+        input: Input,
     ) {
+        let get_i32 = input.get("i32");
         if self.self_referential_optional(tab) {
             buf.code_block("loop", |buf| {
                 buf.add_line("let mut item_follows = 0;");
-                buf.add_line("xdr_lib::get_i32(&mut item_follows, input)?;");
+                buf.add_line(&format!("{get_i32}(&mut item_follows, input)?;"));
                 buf.add_line("if item_follows == 0 { break; }");
                 buf.add_line(&format!("let mut new = {};", self.default_value(tab)));
-                self.deserialize_inline("new", buf, tab);
+                self.deserialize_inline("new", buf, tab, input);
                 buf.add_line(&format!("{name}.push(new)"));
             });
         } else {
             buf.add_line("let mut optional_follows = 0;");
-            buf.add_line("xdr_lib::get_i32(&mut optional_follows, input)?;");
+            buf.add_line(&format!("{get_i32}(&mut optional_follows, input)?;"));
             buf.block_statement(&format!("{name} = match optional_follows"), |buf| {
                 buf.add_line("0 => None,");
                 buf.code_block("_ =>", |buf| {
                     buf.add_line(&format!("let mut new = {};", self.default_value(tab)));
-                    self.deserialize_inline("new", buf, tab);
+                    self.deserialize_inline("new", buf, tab, input);
                     buf.add_line("Some(new)");
                 })
             });
@@ -0,0 +1,211 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// `Display` impls for XDR data types, which render them for people rather than as `Debug` does:
+// opaque data in hexadecimal, enums by name, and the timestamps and sizes named in the `Params` with
+// the helpers of `xdr_lib::display`.
+
+use super::*;
+use crate::symbol_table::ValidatedSymbolTable;
+
+impl ValidatedDefinition {
+    pub(super) fn display(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable, params: &Params) {
+        let name = match self {
+            ValidatedDefinition::Enum(e) => &e.name,
+            ValidatedDefinition::Struct(s) => &s.name,
+            ValidatedDefinition::Union(u) => &u.name,
+            ValidatedDefinition::TypeDef(_) | ValidatedDefinition::Const(_) => return,
+        };
+        if params.skip_display.contains(name) {
+            return;
+        }
+        buf.code_block(&format!("impl std::fmt::Display for {name}"), |buf| {
+            buf.code_block(
+                "fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result",
+                |buf| match self {
+                    ValidatedDefinition::Enum(e) => e.display_body(buf),
+                    ValidatedDefinition::Struct(s) => s.display_body(buf, tab, params),
+                    ValidatedDefinition::Union(u) => u.display_body(buf, tab, params),
+                    _ => unreachable!(),
+                },
+            );
+        });
+    }
+}
+
+impl ValidatedEnum {
+    fn display_body(&self, buf: &mut CodeBuf) {
+        buf.block_with_trailer("f.write_str(match self", ")", |buf| {
+            for (variant, _) in self.variants.iter() {
+                buf.add_line(&format!(
+                    "{}::{variant} => \"{}\",",
+                    self.name,
+                    unraw(variant)
+                ));
+            }
+        });
+    }
+}
+
+impl ValidatedStruct {
+    fn display_body(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable, params: &Params) {
+        if params.timestamps.contains(&self.name) {
+            let [(seconds, _), (nseconds, _), ..] = &self.members[..] else {
+                panic!("Timestamp struct {} needs two members", self.name);
+            };
+            buf.add_line(&format!(
+                "write!(f, \"{{}}\", xdr_lib::display::Timestamp::new(self.{}, self.{}))",
+                seconds.name, nseconds.name
+            ));
+            return;
+        }
+
+        buf.add_line(&format!("write!(f, \"{} {{{{ \")?;", self.name));
+        for (i, (decl, _)) in self.members.iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            buf.add_line(&format!(
+                "write!(f, \"{separator}{}: \")?;",
+                unraw(&decl.name)
+            ));
+            decl.display_inline(&format!("self.{}", decl.name), buf, tab, params);
+        }
+        buf.add_line("write!(f, \" }}\")");
+    }
+}
+
+impl ValidatedUnion {
+    fn display_body(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable, params: &Params) {
+        match &self.body {
+            ValidatedUnionBody::Bool(b) => {
+                buf.code_block("match &self.inner", |buf| {
+                    buf.code_block("Some(inner) =>", |buf| {
+                        b.true_arm.display_inline("inner", buf, tab, params);
+                    });
+                    buf.add_line("None => write!(f, \"none\")?,");
+                });
+            }
+            ValidatedUnionBody::Enum(e) => {
+                let arms = e
+                    .arms
+                    .iter()
+                    .map(|(value, decl)| (ValidatedUnionEnumBody::arm_name(value), decl))
+                    .chain(
+                        e.default_arm
+                            .iter()
+                            .map(|decl| ("Default".to_string(), decl)),
+                    )
+                    .collect::<Vec<_>>();
+                buf.code_block("match self", |buf| {
+                    for (arm, decl) in arms.iter() {
+                        match decl {
+                            Declaration::Void => {
+                                buf.add_line(&format!("Self::{arm} => write!(f, \"{arm}\")?,"))
+                            }
+                            Declaration::Named(n) => {
+                                buf.code_block(&format!("Self::{arm}(inner) =>"), |buf| {
+                                    buf.add_line(&format!("write!(f, \"{arm}(\")?;"));
+                                    n.display_inline("inner", buf, tab, params);
+                                    buf.add_line("write!(f, \")\")?;");
+                                });
+                            }
+                        }
+                    }
+                });
+            }
+        }
+        buf.add_line("Ok(())");
+    }
+}
+
+impl NamedDeclaration {
+    /// Generate code to write `var`, which may be a value or a reference to one, to the formatter
+    /// `f`.
+    fn display_inline(
+        &self,
+        var: &str,
+        buf: &mut CodeBuf,
+        tab: &ValidatedSymbolTable,
+        params: &Params,
+    ) {
+        match &self.kind {
+            DeclarationKind::Scalar(ty) => ty.display_inline(var, buf, tab, params),
+            DeclarationKind::Array(a) => a.display_inline(var, buf, tab, params),
+            // Self-referential optionals are represented as vectors:
+            DeclarationKind::Optional(ty) if ty.self_referential_optional(tab) => {
+                display_list(var, buf, |buf| ty.display_inline("item", buf, tab, params));
+            }
+            DeclarationKind::Optional(ty) => {
+                buf.code_block(&format!("match &{var}"), |buf| {
+                    buf.code_block("Some(value) =>", |buf| {
+                        ty.display_inline("value", buf, tab, params);
+                    });
+                    buf.add_line("None => write!(f, \"none\")?,");
+                });
+            }
+        }
+    }
+}
+
+impl Array {
+    fn display_inline(
+        &self,
+        var: &str,
+        buf: &mut CodeBuf,
+        tab: &ValidatedSymbolTable,
+        params: &Params,
+    ) {
+        match &self.kind {
+            ArrayKind::Byte => buf.add_line(&format!(
+                "write!(f, \"{{}}\", xdr_lib::display::Hex(&{var}[..]))?;"
+            )),
+            ArrayKind::Ascii => buf.add_line(&format!("write!(f, \"{{:?}}\", {var})?;")),
+            ArrayKind::UserType(ty) => {
+                display_list(var, buf, |buf| ty.display_inline("item", buf, tab, params));
+            }
+        }
+    }
+}
+
+impl XdrType {
+    fn display_inline(
+        &self,
+        var: &str,
+        buf: &mut CodeBuf,
+        tab: &ValidatedSymbolTable,
+        params: &Params,
+    ) {
+        // Sizes are named by their typedefs, which are otherwise displayed as their underlying type:
+        if let XdrType::Name(name) = self {
+            if params.sizes.contains(name) {
+                buf.add_line(&format!(
+                    "write!(f, \"{{}}\", xdr_lib::display::Size::of(&{var}))?;"
+                ));
+                return;
+            }
+            let definition = tab.lookup_definition(name);
+            if let ValidatedDefinition::TypeDef(ref tdef) = *definition {
+                tdef.decl.display_inline(var, buf, tab, params);
+                return;
+            }
+        }
+
+        buf.add_line(&format!("write!(f, \"{{}}\", {var})?;"));
+    }
+}
+
+/// Generate code to write the elements of the vector or array `var` as a list, `[a, b]`, each
+/// written by the code that `item` generates for the element `item`.
+fn display_list<F>(var: &str, buf: &mut CodeBuf, mut item: F)
+where
+    F: FnMut(&mut CodeBuf),
+{
+    buf.add_line("write!(f, \"[\")?;");
+    buf.code_block(
+        &format!("for (i, item) in {var}.iter().enumerate()"),
+        |buf| {
+            buf.add_line("if i > 0 { write!(f, \", \")?; }");
+            item(buf);
+        },
+    );
+    buf.add_line("write!(f, \"]\")?;");
+}
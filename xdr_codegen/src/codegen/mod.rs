@@ -10,10 +10,13 @@ use crate::ast::*;
 use crate::ir::*;
 use crate::symbol_table::ValidatedSymbolTable;
 use crate::validate::*;
+use deserialize::Input;
 
 mod alloc;
 mod deserialize;
+mod display;
 mod no_alloc;
+mod no_std;
 mod zcopy_deser;
 
 /// Parameters for code generation.
@@ -26,14 +29,63 @@ pub struct Params {
 
     /// Whether to include zero-copy serdes routines
     pub zcopy: bool,
+
+    /// Whether to generate code for `#![no_std]` crates, with borrowed slices for variable-length
+    /// data, and only the non-allocating serialization routines.
+    pub no_std: bool,
+
+    /// Whether to derive serde's `Serialize` and `Deserialize` traits on generated types.
+    pub serde: bool,
+
+    /// Whether to include `deserialize_from()` methods, which decode from an `std::io::Read`.
+    pub deserialize_from: bool,
+
+    /// Further traits to derive on every generated type, as paths such as `PartialOrd` or
+    /// `my_crate::MyDerive`.
+    pub derives: Vec<String>,
+
+    /// Whether to implement `Display` on generated types.
+    pub display: bool,
+
+    /// The structs which `Display` renders as timestamps, their first two members being seconds and
+    /// nanoseconds since the epoch.
+    pub timestamps: Vec<String>,
+
+    /// The typedefs of integers which `Display` renders as sizes in bytes.
+    pub sizes: Vec<String>,
+
+    /// The types whose `Display` is implemented by hand instead.
+    pub skip_display: Vec<String>,
+
+    /// Whether to copy the `%` lines of the source into the generated code, as doc comments of the
+    /// definitions they belong to.
+    pub literal_comments: bool,
+
+    /// A hook which turns the `%` lines of each definition into code to generate ahead of it.
+    pub annotator: Option<Annotator>,
 }
 
+/// Given the name of a definition or program, and the `%` lines before and within it in the source
+/// (without the `%`), returns code to generate ahead of it, if any: for example, attributes on a
+/// type, or another impl block for it.
+pub type Annotator = fn(name: &str, lines: &[String]) -> Option<String>;
+
 impl Default for Params {
     fn default() -> Self {
         Self {
             no_alloc: false,
             alloc: true,
             zcopy: false,
+            no_std: false,
+            serde: false,
+            deserialize_from: false,
+            derives: Vec::new(),
+            display: false,
+            timestamps: Vec::new(),
+            sizes: Vec::new(),
+            skip_display: Vec::new(),
+            literal_comments: false,
+            annotator: None,
         }
     }
 }
@@ -42,29 +94,75 @@ const USE_FFI_HEADER: &str = r#"
 use std::os::unix::ffi::OsStrExt;
 "#;
 
+const USE_AS_BYTES_HEADER: &str = r#"
+use xdr_lib::AsBytes;
+"#;
+
 enum FunctionKind {
     Function,
     Method,
 }
 
+/// Output `serialized_size()`, the length of the value's encoding, for a struct or union, which
+/// already has `get_width()` to compute it.
+fn serialized_size(buf: &mut CodeBuf) {
+    buf.add_line("pub fn serialized_size(&self) -> usize { self.get_width() }");
+}
+
+/// A `#[doc]` attribute of `text`, which is rendered as a `///` comment.
+fn doc(text: &str) -> String {
+    format!("#[doc = {:?}]", format!(" {text}"))
+}
+
+/// Write a doc comment giving the maximum length of `decl`, if it is a limited-length array or
+/// string, or an optional one.
+fn max_length(buf: &mut CodeBuf, decl: &NamedDeclaration, tab: &ValidatedSymbolTable) {
+    if let Some(lim) = decl.max_length(tab) {
+        buf.add_line(&doc(&format!("Max length: {lim}")));
+    }
+}
+
+/// Strip the raw identifier prefix from a name that was escaped because it is a Rust keyword (see
+/// `Scanner::maybe_escape()`), so that it can be used as part of a longer identifier.
+fn unraw(name: &str) -> &str {
+    name.strip_prefix("r#").unwrap_or(name)
+}
+
 pub fn codegen(schema: &ValidatedSchema, module_name: &str, params: &Params) -> String {
     let mut buf = CodeBuf::new();
 
-    buf.add_line("#[allow(non_camel_case_types, non_snake_case, clippy::all)]");
+    buf.add_line("#[allow(non_camel_case_types, non_snake_case, unused_assignments, clippy::all)]");
     buf.code_block(&format!("pub mod {module_name}"), |buf| {
         if schema.contains_string {
-            buf.add_line(USE_FFI_HEADER);
+            if !schema.imports.is_empty() {
+                // The strings may all be in included types, which are only named here.
+                buf.add_line("#[allow(unused_imports)]");
+            }
+            if params.no_std {
+                buf.add_line(USE_AS_BYTES_HEADER);
+            } else {
+                buf.add_line(USE_FFI_HEADER);
+            }
             buf.add_line("");
         }
 
-        if params.zcopy {
+        if params.zcopy && !params.no_std {
             buf.add_line("#[allow(unused_imports)]");
             buf.add_line("use xdr_lib::Reader;");
             buf.add_line("");
         }
 
-        for def in schema.definition_list.iter() {
-            let def = schema.symbol_table.lookup_definition(def);
+        for module in schema.imports.iter() {
+            buf.add_line("#[allow(unused_imports)]");
+            buf.add_line(&format!("use super::{module}::*;"));
+        }
+        if !schema.imports.is_empty() {
+            buf.add_line("");
+        }
+
+        for name in schema.definition_list.iter() {
+            literals(buf, schema, name, params);
+            let def = schema.symbol_table.lookup_definition(name);
             def.definition(buf, &schema.symbol_table, params);
         }
 
@@ -74,15 +172,41 @@ pub fn codegen(schema: &ValidatedSchema, module_name: &str, params: &Params) ->
         }
 
         for prog in schema.programs.iter() {
-            prog.codegen(buf);
+            literals(buf, schema, &prog.name, params);
+            prog.codegen(buf, &schema.symbol_table, params);
         }
     });
 
     buf.contents
 }
 
+/// Write whatever the `%` lines of the definition or program `name` should become: comments, the
+/// annotator's code, or nothing.
+fn literals(buf: &mut CodeBuf, schema: &ValidatedSchema, name: &str, params: &Params) {
+    let lines = match schema.literals.get(name) {
+        Some(lines) if !lines.is_empty() => lines,
+        _ => return,
+    };
+    // The lines are doc comments of the item they precede, since other comments do not survive
+    // formatting. A typedef generates no item, so its lines are dropped:
+    let typedef = matches!(
+        schema.symbol_table.lookup_definition_fallible(name),
+        Ok(ValidatedDefinition::TypeDef(_))
+    );
+    if params.literal_comments && !typedef {
+        for line in lines.iter() {
+            buf.add_line(&doc(&format!("%{line}")));
+        }
+    }
+    if let Some(annotator) = params.annotator {
+        if let Some(code) = annotator(name, lines) {
+            buf.add_line(&code);
+        }
+    }
+}
+
 impl Program {
-    fn codegen(&self, buf: &mut CodeBuf) {
+    fn codegen(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable, params: &Params) {
         buf.code_block("pub mod procedures", |buf| {
             buf.add_line(&format!("pub const {}: u32 = {};", self.name, self.id));
             for version in self.versions.iter() {
@@ -97,63 +221,217 @@ impl Program {
                     }
                 });
             }
+
+            buf.add_line("/// The name of a procedure of this program, given its version and procedure numbers.");
+            buf.code_block(
+                "pub fn proc_name(version: u32, proc: u32) -> Option<&'static str>",
+                |buf| {
+                    buf.code_block("match (version, proc)", |buf| {
+                        for version in self.versions.iter() {
+                            for procedure in version.procedures.iter() {
+                                buf.add_line(&format!(
+                                    "({}, {}) => Some(\"{}\"),",
+                                    version.id, procedure.id, procedure.name
+                                ));
+                            }
+                        }
+                        buf.add_line("_ => None,");
+                    });
+                },
+            );
+
+            // Checking a result means encoding it again, with the allocating routines:
+            if params.alloc {
+                self.check_result(buf, tab);
+            }
+        });
+    }
+
+    fn check_result(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
+        buf.add_line("/// Check that `result`, the encoded result of a procedure of this program, given its version");
+        buf.add_line("/// and procedure numbers, is the canonical encoding of a value of the procedure's result type");
+        buf.add_line("/// (see `xdr_lib::check_encoding()`). Returns None for a procedure that is not part of the");
+        buf.add_line("/// program, or whose result type is not checked.");
+        buf.code_block(
+            "pub fn check_result(version: u32, proc: u32, result: &[u8]) -> Option<std::result::Result<(), xdr_lib::EncodingError>>",
+            |buf| {
+                buf.code_block("match (version, proc)", |buf| {
+                    for version in self.versions.iter() {
+                        for procedure in version.procedures.iter() {
+                            let recode = match &procedure.ret {
+                                ProcedureType::Void => Some("|_| Ok(Vec::new())".to_string()),
+                                ProcedureType::Ty(ty) => recode_expression(ty, tab)
+                                    .map(|recode| format!("|input| {{ {recode} }}")),
+                            };
+                            let Some(recode) = recode else {
+                                continue;
+                            };
+                            buf.add_line(&format!(
+                                "({}, {}) => Some(xdr_lib::check_encoding(result, {recode})),",
+                                version.id, procedure.id
+                            ));
+                        }
+                    }
+                    buf.add_line("_ => None,");
+                });
+            },
+        );
+    }
+}
+
+/// Implement `xdr_lib::Decode` and `xdr_lib::Encode` on the type `name` with its own routines, for
+/// code that is generic over the generated types. Zero-copy types decode with readers instead.
+fn codec(buf: &mut CodeBuf, name: &str, params: &Params) {
+    if !params.zcopy {
+        buf.code_block(&format!("impl xdr_lib::Decode for {name}"), |buf| {
+            buf.code_block(
+                "fn decode(input: &mut &[u8]) -> xdr_lib::Result<Self>",
+                |buf| {
+                    buf.add_line("let mut value = Self::default();");
+                    buf.add_line("value.deserialize(input)?;");
+                    buf.add_line("Ok(value)");
+                },
+            );
         });
     }
+    if params.alloc {
+        buf.code_block(&format!("impl xdr_lib::Encode for {name}"), |buf| {
+            buf.code_block("fn encode(&self) -> Vec<u8>", |buf| {
+                buf.add_line("self.serialize_alloc()");
+            });
+        });
+    }
+    buf.add_line("");
+}
+
+/// Statements that decode a value of type `ty` from `input`, and evaluate to the value encoded
+/// again, for `Program::check_result()`. Floating point values, arrays, and optional data
+/// defined with typedef are not checked.
+fn recode_expression(ty: &XdrType, tab: &ValidatedSymbolTable) -> Option<String> {
+    let integer = |rust_type: &str, get: &str| {
+        format!(
+            "let mut value: {rust_type} = 0; xdr_lib::{get}(&mut value, input)?; Ok(value.to_be_bytes().to_vec())"
+        )
+    };
+
+    match ty {
+        XdrType::Int => Some(integer("i32", "get_i32")),
+        XdrType::UInt => Some(integer("u32", "get_u32")),
+        XdrType::Hyper => Some(integer("i64", "get_i64")),
+        XdrType::UHyper => Some(integer("u64", "get_u64")),
+        XdrType::Bool => Some(
+            "let mut value = false; xdr_lib::get_bool(&mut value, input)?; Ok(xdr_lib::serialize_bool(&value).to_vec())"
+                .to_string(),
+        ),
+        XdrType::Float | XdrType::Double | XdrType::Quadruple => None,
+        XdrType::Name(name) => match tab.lookup_definition_fallible(name).ok()? {
+            ValidatedDefinition::Union(ValidatedUnion {
+                name,
+                body: ValidatedUnionBody::Enum(body),
+                ..
+            }) if body.default_arm.is_some() => {
+                // The default arm of a union does not keep its discriminant, which is encoded as
+                // one past the largest case. The discriminant of the encoding is put back instead,
+                // once it has been checked to be a value of its type:
+                let check_discriminant = match &body.discriminant {
+                    Some(ty) => format!(
+                        "super::{}::default().deserialize(&mut discriminant.as_slice())?;",
+                        tab.lookup_definition(ty).as_type_name(tab)
+                    ),
+                    None => String::new(),
+                };
+                Some(format!(
+                    "let discriminant = input.get(..4).unwrap_or_default().to_vec(); \
+                     let mut value = super::{name}::default(); value.deserialize(input)?; \
+                     let mut encoded = value.serialize_alloc(); \
+                     if matches!(value, super::{name}::Default {{ .. }}) {{ \
+                     {check_discriminant} encoded[..4].copy_from_slice(&discriminant); }} \
+                     Ok(encoded)"
+                ))
+            }
+            def @ (ValidatedDefinition::Struct(_)
+            | ValidatedDefinition::Union(_)
+            | ValidatedDefinition::Enum(_)) => Some(format!(
+                "let mut value = super::{}::default(); value.deserialize(input)?; Ok(value.serialize_alloc())",
+                def.as_type_name(tab)
+            )),
+            ValidatedDefinition::TypeDef(type_def) => match &type_def.decl.kind {
+                DeclarationKind::Scalar(ty) => recode_expression(ty, tab),
+                _ => None,
+            },
+            ValidatedDefinition::Const(_) => None,
+        },
+    }
 }
 
 impl ValidatedDefinition {
     /// The definition for the type.
     fn definition(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable, params: &Params) {
+        if params.no_std {
+            self.definition_no_std(buf, tab, params);
+            return;
+        }
+
         if params.zcopy {
             self.definition_zcopy(buf, tab);
         }
 
-        self.definition_copy(buf, tab);
+        self.definition_copy(buf, tab, params);
     }
 
-    fn definition_copy(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
+    fn definition_copy(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable, params: &Params) {
         match self {
             ValidatedDefinition::Const(c) => {
-                match &c.value {
-                    Value::Int(n) => {
-                        buf.add_line(&format!(
-                            "pub const {}: u64 = {};",
-                            c.name.to_uppercase(),
-                            n
-                        ));
-                    }
-                    Value::Name(name) => {
-                        todo!("{name}");
-                    }
+                // A constant defined as another constant refers to it by name:
+                let value = match &c.value {
+                    Value::Int(n) => n.to_string(),
+                    Value::Name(name) => name.to_uppercase(),
                 };
+                buf.add_line(&format!(
+                    "pub const {}: {} = {};",
+                    c.name.to_uppercase(),
+                    const_type(c.value.as_const(tab)),
+                    value
+                ));
             }
             ValidatedDefinition::Enum(e) => {
-                e.definition(buf);
+                e.definition(buf, params);
             }
             ValidatedDefinition::Struct(s) => {
-                s.definition(buf, tab);
+                s.definition(buf, tab, params);
             }
             ValidatedDefinition::TypeDef(_) => {}
             ValidatedDefinition::Union(u) => {
-                u.definition(buf, tab);
+                u.definition(buf, tab, params);
             }
         }
     }
 
     /// The impl block for the type, including its serialize and deserialize methods.
     fn implementation(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable, params: &Params) {
+        if params.no_std {
+            self.implementation_no_std(buf, tab);
+            return;
+        }
+
         match self {
             ValidatedDefinition::Enum(e) => {
                 e.codegen(buf, tab, params);
+                codec(buf, &e.name, params);
             }
             ValidatedDefinition::Struct(s) => {
                 s.codegen(buf, tab, params);
+                codec(buf, &s.name, params);
             }
             ValidatedDefinition::Union(u) => {
                 u.codegen(buf, tab, params);
+                codec(buf, &u.name, params);
             }
             ValidatedDefinition::TypeDef(_) | ValidatedDefinition::Const(_) => {}
         }
+        if params.display {
+            self.display(buf, tab, params);
+        }
     }
 
     /// Given a definition, get its type name in a way suitable for a struct member.
@@ -196,7 +474,18 @@ impl ValidatedDefinition {
         }
     }
 
-    fn as_const(&self, tab: &ValidatedSymbolTable) -> u64 {
+    /// Whether the type can derive `Copy`: whether nothing in it is represented as a `Vec` or an
+    /// `OsString`.
+    fn is_copy(&self, tab: &ValidatedSymbolTable) -> bool {
+        match self {
+            ValidatedDefinition::Const(_) | ValidatedDefinition::Enum(_) => true,
+            ValidatedDefinition::TypeDef(t) => t.decl.is_copy(tab),
+            ValidatedDefinition::Struct(s) => s.is_copy(tab),
+            ValidatedDefinition::Union(u) => u.is_copy(tab),
+        }
+    }
+
+    fn as_const(&self, tab: &ValidatedSymbolTable) -> i128 {
         match self {
             ValidatedDefinition::Const(c) => c.value.as_const(tab),
             _ => panic!("not a constant"),
@@ -212,7 +501,7 @@ impl Value {
         }
     }
 
-    fn as_const(&self, tab: &ValidatedSymbolTable) -> u64 {
+    fn as_const(&self, tab: &ValidatedSymbolTable) -> i128 {
         match self {
             Value::Int(i) => *i,
             Value::Name(name) => tab.lookup_definition(name).as_const(tab),
@@ -220,6 +509,43 @@ impl Value {
     }
 }
 
+/// The Rust type of a constant with the value `n`: `u32` if it fits, so that the constant can be
+/// compared with the unsigned ints of generated types without a cast, or `u64` otherwise. Negative
+/// constants are `i32` or `i64` in the same way.
+fn const_type(n: i128) -> &'static str {
+    if u32::try_from(n).is_ok() {
+        "u32"
+    } else if u64::try_from(n).is_ok() {
+        "u64"
+    } else if i32::try_from(n).is_ok() {
+        "i32"
+    } else if i64::try_from(n).is_ok() {
+        "i64"
+    } else {
+        panic!("constant {n} does not fit in 64 bits")
+    }
+}
+
+/// When deriving serde's traits, the attribute that gives a member of type `type_name` the encoding
+/// of `xdr_lib::os_string` if it holds `OsString`s, whose own encoding is awkward to read and write.
+fn serde_with(type_name: &str, params: &Params) -> String {
+    if !params.serde {
+        return String::new();
+    }
+
+    let module = if type_name.starts_with("std::ffi::OsString") {
+        "xdr_lib::os_string"
+    } else if type_name.starts_with("Option<std::ffi::OsString") {
+        "xdr_lib::os_string::option"
+    } else if type_name.starts_with("Vec<std::ffi::OsString") {
+        "xdr_lib::os_string::vec"
+    } else {
+        return String::new();
+    };
+
+    format!("#[serde(with = \"{module}\")] ")
+}
+
 #[derive(Copy, Clone, Debug)]
 enum Context {
     InUnion,
@@ -230,15 +556,7 @@ impl Array {
     // XXX: represent arrays as slices instead of as vectors?
     fn as_type_name(&self, tab: &ValidatedSymbolTable) -> String {
         let inner_type = match &self.kind {
-            ArrayKind::Ascii => {
-                return match &self.size {
-                    ArraySize::Limited(lim) => {
-                        let lim = lim.as_const(tab);
-                        format!("std::ffi::OsString /* max length: {lim} */")
-                    }
-                    _ => "std::ffi::OsString".to_string(),
-                };
-            }
+            ArrayKind::Ascii => return "std::ffi::OsString".to_string(),
             ArrayKind::Byte => "u8".to_string(),
             ArrayKind::UserType(ty) => ty.as_type_name(tab),
         };
@@ -252,11 +570,7 @@ impl Array {
                 format!("[{inner_type}; {len}]")
             }
             // XXX: different representation for upper-bounded array?
-            ArraySize::Limited(lim) => {
-                let lim = lim.as_const(tab);
-                format!("Vec<{inner_type}> /* max length: {lim} */")
-            }
-            ArraySize::Unlimited => format!("Vec<{inner_type}>"),
+            ArraySize::Limited(_) | ArraySize::Unlimited => format!("Vec<{inner_type}>"),
         }
     }
 
@@ -375,6 +689,40 @@ impl NamedDeclaration {
         }
     }
 
+    /// The maximum length of the array or string that this declares, directly or through typedefs.
+    fn max_length(&self, tab: &ValidatedSymbolTable) -> Option<i128> {
+        match &self.kind {
+            DeclarationKind::Array(Array {
+                size: ArraySize::Limited(lim),
+                ..
+            }) => Some(lim.as_const(tab)),
+            DeclarationKind::Array(_) => None,
+            DeclarationKind::Scalar(ty) | DeclarationKind::Optional(ty) => match ty {
+                XdrType::Name(n) if !ty.self_referential_optional(tab) => {
+                    match tab.lookup_definition(n) {
+                        ValidatedDefinition::TypeDef(t) => t.decl.max_length(tab),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+        }
+    }
+
+    fn is_copy(&self, tab: &ValidatedSymbolTable) -> bool {
+        match &self.kind {
+            // Self-referential optionals are represented as vectors:
+            DeclarationKind::Scalar(ty) | DeclarationKind::Optional(ty) => {
+                !ty.self_referential_optional(tab) && ty.is_copy(tab)
+            }
+            DeclarationKind::Array(arr) => match (&arr.size, &arr.kind) {
+                (ArraySize::Fixed(_), ArrayKind::Byte) => true,
+                (ArraySize::Fixed(_), ArrayKind::UserType(ty)) => ty.is_copy(tab),
+                _ => false,
+            },
+        }
+    }
+
     fn is_varlen_reader(&self, tab: &ValidatedSymbolTable) -> bool {
         match &self.kind {
             DeclarationKind::Scalar(ty) | DeclarationKind::Optional(ty) => {
@@ -470,10 +818,17 @@ impl ValidatedUnion {
             }
             buf.add_line("");
             if !params.zcopy {
-                self.deserialize_definition(buf, tab);
+                self.deserialize_definition(buf, tab, Input::Slice);
+                if params.deserialize_from {
+                    self.deserialize_definition(buf, tab, Input::Reader);
+                }
             }
             buf.add_line("");
             self.width_getter(buf, tab);
+            serialized_size(buf);
+            if let ValidatedUnionBody::Enum(e) = &self.body {
+                e.result_helpers(buf, tab);
+            }
         });
 
         if params.zcopy {
@@ -481,11 +836,26 @@ impl ValidatedUnion {
         }
         buf.add_line("");
     }
-    fn definition(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
-        buf.type_header();
+    fn is_copy(&self, tab: &ValidatedSymbolTable) -> bool {
         match &self.body {
-            ValidatedUnionBody::Bool(b) => b.definition_bool(&self.name, buf, tab),
-            ValidatedUnionBody::Enum(e) => e.definition_enum(&self.name, buf, tab),
+            ValidatedUnionBody::Bool(b) => b.true_arm.is_copy(tab),
+            ValidatedUnionBody::Enum(e) => e
+                .arms
+                .iter()
+                .map(|(_, decl)| decl)
+                .chain(&e.default_arm)
+                .all(|decl| match decl {
+                    Declaration::Named(n) => n.is_copy(tab),
+                    Declaration::Void => true,
+                }),
+        }
+    }
+
+    fn definition(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable, params: &Params) {
+        buf.type_header(params, self.is_copy(tab));
+        match &self.body {
+            ValidatedUnionBody::Bool(b) => b.definition_bool(&self.name, buf, tab, params),
+            ValidatedUnionBody::Enum(e) => e.definition_enum(&self.name, buf, tab, params),
         };
     }
 
@@ -538,13 +908,21 @@ impl ValidatedUnion {
 }
 
 impl ValidatedUnionBoolBody {
-    fn definition_bool(&self, name: &str, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
+    fn definition_bool(
+        &self,
+        name: &str,
+        buf: &mut CodeBuf,
+        tab: &ValidatedSymbolTable,
+        params: &Params,
+    ) {
         // XXX: A Bool union nearly always has Void for the false arm.
         // Until I see an example where this is not the case, express it as an Option.
-        let inner_type = self.true_arm.as_type_name(tab);
+        let inner_type = format!("Option<{}>", self.true_arm.as_type_name(tab));
+        let serde = serde_with(&inner_type, params);
 
         buf.code_block(&format!("pub struct {name}"), |buf| {
-            buf.add_line(&format!("pub inner: Option<{inner_type}>,"));
+            max_length(buf, &self.true_arm, tab);
+            buf.add_line(&format!("{serde}pub inner: {inner_type},"));
         });
     }
     fn default_bool(&self, buf: &mut CodeBuf) {
@@ -559,11 +937,18 @@ impl ValidatedUnionEnumBody {
     /// suitable for a variant in a Rust enum.
     fn arm_name(val: &Value) -> String {
         match val {
+            Value::Int(i) if *i < 0 => format!("VarMinus{}", i.unsigned_abs()),
             Value::Int(i) => format!("Var{i}"),
             Value::Name(n) => n.to_string(),
         }
     }
-    fn definition_enum(&self, name: &str, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
+    fn definition_enum(
+        &self,
+        name: &str,
+        buf: &mut CodeBuf,
+        tab: &ValidatedSymbolTable,
+        params: &Params,
+    ) {
         buf.code_block(&format!("pub enum {name}"), |buf| {
             for arm in self.arms.iter() {
                 let name = ValidatedUnionEnumBody::arm_name(&arm.0);
@@ -571,7 +956,9 @@ impl ValidatedUnionEnumBody {
                     Declaration::Void => buf.add_line(&format!("{name},")),
                     Declaration::Named(n) => {
                         let inner_type = n.as_type_name(tab);
-                        buf.add_line(&format!("{name}({inner_type}),"));
+                        let serde = serde_with(&inner_type, params);
+                        max_length(buf, n, tab);
+                        buf.add_line(&format!("{name}({serde}{inner_type}),"));
                     }
                 };
             }
@@ -580,13 +967,94 @@ impl ValidatedUnionEnumBody {
                 Some(Declaration::Void) => buf.add_line("Default,"),
                 Some(Declaration::Named(n)) => {
                     let inner_type = n.as_type_name(tab);
-                    buf.add_line(&format!("Default({inner_type}),"));
+                    let serde = serde_with(&inner_type, params);
+                    max_length(buf, n, tab);
+                    buf.add_line(&format!("Default({serde}{inner_type}),"));
                 }
                 None => {} // Don't generate anything for absent default arm.
             }
         })
     }
 
+    /// Whether this is the union of a procedure result: one arm, for the status enum's value 0
+    /// (such as NFS3_OK), and a default arm for every other status. If so, return the arm.
+    fn result_arm(&self, tab: &ValidatedSymbolTable) -> Option<&UnionArm> {
+        let disc = self.discriminant.as_ref()?;
+        if !matches!(*tab.lookup_definition(disc), ValidatedDefinition::Enum(_)) {
+            return None;
+        }
+        let [arm] = &self.arms[..] else {
+            return None;
+        };
+        if self.default_arm.is_none() || self.get_discriminant_value(&arm.0, tab) != 0 {
+            return None;
+        }
+
+        Some(arm)
+    }
+
+    /// For the union of a procedure result, write `is_ok()`, `ok()`, `err()`, and `into_result()`,
+    /// which give the success arm, or the body of the default arm for any other status.
+    ///
+    /// The default arm does not record which status it was decoded from, so `err()` gives only the
+    /// body of the failure.
+    fn result_helpers(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
+        let Some((value, declaration)) = self.result_arm(tab) else {
+            return;
+        };
+        let name = ValidatedUnionEnumBody::arm_name(value);
+        let (ok_type, ok_pattern, ok_value) = match declaration {
+            Declaration::Void => ("()".to_string(), format!("Self::{name}"), "()"),
+            Declaration::Named(n) => (n.as_type_name(tab), format!("Self::{name}(inner)"), "inner"),
+        };
+        let (err_type, err_pattern, err_value) = match self.default_arm.as_ref().unwrap() {
+            Declaration::Void => ("()".to_string(), "Self::Default", "()"),
+            Declaration::Named(n) => (n.as_type_name(tab), "Self::Default(inner)", "inner"),
+        };
+
+        buf.add_line("");
+        buf.add_line(&format!("/// Whether this is the `{name}` arm."));
+        buf.code_block("pub fn is_ok(&self) -> bool", |buf| {
+            match declaration {
+                Declaration::Void => buf.add_line(&format!("matches!(self, Self::{name})")),
+                Declaration::Named(_) => buf.add_line(&format!("matches!(self, Self::{name}(_))")),
+            };
+        });
+        buf.add_line("");
+        buf.add_line(&format!("/// The body of the `{name}` arm, if this is it."));
+        buf.code_block(&format!("pub fn ok(self) -> Option<{ok_type}>"), |buf| {
+            buf.code_block("match self", |buf| {
+                buf.add_line(&format!("{ok_pattern} => Some({ok_value}),"));
+                buf.add_line("_ => None,");
+            });
+        });
+        buf.add_line("");
+        buf.add_line("/// The body of the default arm, if this is it. The status it was decoded from is not kept.");
+        buf.code_block(&format!("pub fn err(self) -> Option<{err_type}>"), |buf| {
+            buf.code_block("match self", |buf| {
+                buf.add_line(&format!("{err_pattern} => Some({err_value}),"));
+                buf.add_line("_ => None,");
+            });
+        });
+        buf.add_line("");
+        buf.add_line(&format!(
+            "/// The body of the `{name}` arm as `Ok`, or that of the default arm as `Err`."
+        ));
+        buf.code_block(
+            &format!("pub fn into_result(self) -> core::result::Result<{ok_type}, {err_type}>"),
+            |buf| {
+                buf.code_block("match self", |buf| {
+                    buf.add_line(&format!(
+                        "{ok_pattern} => core::result::Result::Ok({ok_value}),"
+                    ));
+                    buf.add_line(&format!(
+                        "{err_pattern} => core::result::Result::Err({err_value}),"
+                    ));
+                });
+            },
+        );
+    }
+
     pub(super) fn get_explicit_lifetime(&self, tab: &ValidatedSymbolTable) -> &str {
         let mut explicit_lifetime: &str = "";
         for arm in self.arms.iter() {
@@ -631,7 +1099,6 @@ impl ValidatedUnionEnumBody {
                         buf.code_block(&format!("Self::{arm_name} => "), |buf| {
                             max_disc = self
                                 .serialize_discriminant_value(&arm.0, max_disc, buf, tab, alloc);
-                            buf.add_line("// void");
                         });
                     }
                     Declaration::Named(n) => {
@@ -658,7 +1125,6 @@ impl ValidatedUnionEnumBody {
                                 tab,
                                 alloc,
                             );
-                            buf.add_line("// void");
                         });
                     }
                     Declaration::Named(n) => {
@@ -685,7 +1151,7 @@ impl ValidatedUnionEnumBody {
         }
     }
 
-    /// Get the value of `val` as a u64, and then write the code to serialize it according to
+    /// Get the value of `val` as an i128, and then write the code to serialize it according to
     /// `alloc`.
     ///
     /// Compare it to `max_disc` and return the larger of the two. This is to serialize default
@@ -696,16 +1162,16 @@ impl ValidatedUnionEnumBody {
     fn serialize_discriminant_value(
         &self,
         val: &Value,
-        max_disc: u64,
+        max_disc: i128,
         buf: &mut CodeBuf,
         tab: &ValidatedSymbolTable,
         alloc: bool,
-    ) -> u64 {
+    ) -> i128 {
         let disc = self.get_discriminant_value(val, tab);
         if alloc {
             let disc: i32 = disc.try_into().unwrap();
             buf.add_line(&format!(
-                "buf.extend_from_slice(&{disc}_i32.to_be_bytes());"
+                "buf.extend_from_slice(&({disc}_i32).to_be_bytes());"
             ));
         } else {
             buf.serialize_int(disc.try_into().unwrap());
@@ -719,10 +1185,7 @@ impl ValidatedUnionEnumBody {
     }
     fn default_enum(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
         let (value, declaration) = &self.arms[0];
-        let name = match &value {
-            Value::Int(i) => format!("Var{i}"),
-            Value::Name(n) => n.to_string(),
-        };
+        let name = ValidatedUnionEnumBody::arm_name(value);
         match declaration {
             Declaration::Void => buf.add_line(&format!("Self::{name}")),
             Declaration::Named(d) => {
@@ -734,12 +1197,17 @@ impl ValidatedUnionEnumBody {
 
     /// Given the value `val`, convert it into its integer value for encoding. If `val` is already
     /// an int, use that, otherwise if it's a string, look it up in the discriminant enum.
-    fn get_discriminant_value(&self, val: &Value, tab: &ValidatedSymbolTable) -> u64 {
+    fn get_discriminant_value(&self, val: &Value, tab: &ValidatedSymbolTable) -> i128 {
         match val {
             Value::Int(i) => *i,
             Value::Name(n) => {
+                // An integer-discriminated union's cases are constants, or TRUE and FALSE:
                 let Some(ref disc) = self.discriminant else {
-                    panic!("BUG: attempt to use enum-style union without a discriminant");
+                    return match n.as_str() {
+                        "TRUE" => 1,
+                        "FALSE" => 0,
+                        _ => tab.lookup_definition(n).as_const(tab),
+                    };
                 };
                 let ValidatedDefinition::Enum(ref e) = *tab.lookup_definition(disc) else {
                     panic!("Using non-enum {n} as union discriminant is not allowed");
@@ -803,10 +1271,14 @@ impl ValidatedStruct {
             }
             buf.add_line("");
             if !params.zcopy {
-                self.deserialize_definition(buf, tab);
+                self.deserialize_definition(buf, tab, Input::Slice);
+                if params.deserialize_from {
+                    self.deserialize_definition(buf, tab, Input::Reader);
+                }
             }
             buf.add_line("");
             self.width_getters(buf, tab);
+            serialized_size(buf);
         });
         if params.zcopy {
             buf.code_block(&format!("impl<'a> {}Reader<'a>", self.name), |buf| {
@@ -824,14 +1296,14 @@ impl ValidatedStruct {
     }
 
     fn width_getters(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
-        let varlen_members = self.get_variable_width_members(tab);
+        // In the order of the members, so that the same definition always generates the same code:
+        for (member, _) in self.get_variable_width_members_ordered(tab) {
+            let name = &member.name;
 
-        for name in varlen_members {
-            let (member, _) = self.members.iter().find(|val| val.0.name == *name).unwrap();
-
-            buf.code_block(&format!("fn get_{}_width(&self) -> usize", name), |buf| {
-                member.get_width(buf, &format!("self.{}", name), tab)
-            });
+            buf.code_block(
+                &format!("fn get_{}_width(&self) -> usize", unraw(name)),
+                |buf| member.get_width(buf, &format!("self.{}", name), tab),
+            );
         }
 
         buf.code_block("pub fn get_width(&self) -> usize", |buf| {
@@ -856,11 +1328,15 @@ impl ValidatedStruct {
         });
     }
 
-    fn definition(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
-        buf.type_header();
+    fn is_copy(&self, tab: &ValidatedSymbolTable) -> bool {
+        self.members.iter().all(|(decl, _)| decl.is_copy(tab))
+    }
+
+    fn definition(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable, params: &Params) {
+        buf.type_header(params, self.is_copy(tab));
         buf.code_block(&format!("pub struct {}", self.name), |buf| {
             for (decl, _) in self.members.iter() {
-                self.member_declaration(decl, buf, tab);
+                self.member_declaration(decl, buf, tab, params);
             }
         });
         buf.add_line("");
@@ -883,9 +1359,12 @@ impl ValidatedStruct {
         decl: &NamedDeclaration,
         buf: &mut CodeBuf,
         tab: &ValidatedSymbolTable,
+        params: &Params,
     ) {
         let type_name = decl.as_type_name(tab);
-        buf.add_line(&format!("pub {}: {},", decl.name, type_name));
+        let serde = serde_with(&type_name, params);
+        max_length(buf, decl, tab);
+        buf.add_line(&format!("{serde}pub {}: {},", decl.name, type_name));
     }
 
     fn default(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
@@ -916,8 +1395,13 @@ impl ValidatedEnum {
             if params.zcopy {
                 self.deserialize_definition_zcopy(buf, tab);
             } else {
-                self.deserialize_definition(buf, tab);
+                self.deserialize_definition(buf, tab, Input::Slice);
+                if params.deserialize_from {
+                    self.deserialize_definition(buf, tab, Input::Reader);
+                }
             }
+            buf.add_line("");
+            buf.add_line("pub fn serialized_size(&self) -> usize { 4 }");
         });
         buf.add_line("");
     }
@@ -931,8 +1415,8 @@ impl ValidatedEnum {
             });
         });
     }
-    fn definition(&self, buf: &mut CodeBuf) {
-        buf.type_header();
+    fn definition(&self, buf: &mut CodeBuf, params: &Params) {
+        buf.type_header(params, true);
         buf.code_block(&format!("pub enum {}", self.name), |buf| {
             for var in self.variants.iter() {
                 buf.add_line(&format!("{},", var.0));
@@ -943,7 +1427,7 @@ impl ValidatedEnum {
     ///
     /// Returns None if `name` does not appear as a variant in this enum, and returns Err(_) if the
     /// value of `name` exists but is unresolvable.
-    fn lookup_value(&self, name: &str, tab: &ValidatedSymbolTable) -> Option<u64> {
+    fn lookup_value(&self, name: &str, tab: &ValidatedSymbolTable) -> Option<i128> {
         for var in self.variants.iter() {
             if name == var.0 {
                 return match &var.1 {
@@ -1092,6 +1576,13 @@ impl XdrType {
         .to_string()
     }
 
+    fn is_copy(&self, tab: &ValidatedSymbolTable) -> bool {
+        match self {
+            XdrType::Name(n) => tab.lookup_definition(n).is_copy(tab),
+            _ => true,
+        }
+    }
+
     fn is_reader(&self, tab: &ValidatedSymbolTable) -> bool {
         match self {
             XdrType::Name(n) => tab.lookup_definition(n).is_reader(tab),
@@ -1197,9 +1688,19 @@ impl CodeBuf {
         }
     }
 
-    /// Write standard "derive"s that each type definition should have.
-    /// TODO: come up with a mechanism to add "Copy" to types for which it's appropriate?
-    pub fn type_header(&mut self) {
-        self.add_line("#[derive(Debug, PartialEq, Clone)]");
+    /// Write the "derive"s of a type definition: the standard ones, which include `Eq` and `Hash`
+    /// since no generated type holds a float, `Copy` if the type is `copy`, and any asked for.
+    pub fn type_header(&mut self, params: &Params, copy: bool) {
+        if copy {
+            self.add_line("#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]");
+        } else {
+            self.add_line("#[derive(Debug, PartialEq, Eq, Hash, Clone)]");
+        }
+        if params.serde && !params.no_std {
+            self.add_line("#[derive(serde::Serialize, serde::Deserialize)]");
+        }
+        if !params.derives.is_empty() {
+            self.add_line(&format!("#[derive({})]", params.derives.join(", ")));
+        }
     }
 }
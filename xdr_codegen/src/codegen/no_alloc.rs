@@ -22,11 +22,11 @@ impl ValidatedStruct {
         buf.code_block("pub fn serialize(&self, buf: &mut [u8]) -> usize", |buf| {
             buf.add_line("let mut offset = 0;");
             for (decl, _) in &self.members {
-                buf.add_line(&format!("// {}:", decl.name));
                 decl.serialize_no_alloc_inline(None, buf, tab);
             }
             buf.add_line("offset");
         });
+        try_serialize(buf);
     }
 }
 
@@ -40,6 +40,7 @@ impl ValidatedUnion {
             };
             buf.add_line("offset");
         });
+        try_serialize(buf);
     }
 }
 
@@ -70,9 +71,28 @@ impl ValidatedEnum {
             buf.add_line("offset += 4;");
             buf.add_line("offset");
         });
+        try_serialize(buf);
     }
 }
 
+/// Output `try_serialize()`, which checks that the buffer can hold the value before serializing it,
+/// so that a buffer that is too small is an error rather than a panic. (`serialize()` itself doesn't
+/// check, since it is also called for each member of a value, which would check them over again.)
+fn try_serialize(buf: &mut CodeBuf) {
+    buf.code_block(
+        "pub fn try_serialize(&self, buf: &mut [u8]) -> core::result::Result<usize, xdr_lib::BufferTooSmall>",
+        |buf| {
+            buf.add_line("let needed = self.serialized_size();");
+            buf.code_block("if buf.len() < needed", |buf| {
+                buf.add_line(
+                    "return Err(xdr_lib::BufferTooSmall { needed, available: buf.len() });",
+                );
+            });
+            buf.add_line("Ok(self.serialize(buf))");
+        },
+    );
+}
+
 impl NamedDeclaration {
     /// Generate code to serialize a named declaration without allocating, inline within the
     /// serialization routine of a container type.
@@ -242,7 +262,7 @@ impl CodeBuf {
     /// Write into `self` the code to serialize a signed integer `val`.
     pub(super) fn serialize_int(&mut self, val: i32) {
         self.add_line(&format!(
-            "buf[offset..offset + 4].copy_from_slice(&{val}_i32.to_be_bytes());"
+            "buf[offset..offset + 4].copy_from_slice(&({val}_i32).to_be_bytes());"
         ));
         self.add_line("offset += 4;");
     }
@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Code generation for `#![no_std]` crates, which have no `Vec` and no `OsString`.
+//
+// Types hold variable-length data as slices that they borrow: strings and variable-length opaque
+// data as `&'a [u8]`, other variable-length arrays as `&'a [T]`, and self-referential optionals,
+// which are otherwise vectors, as `&'a [T]` too. A type that holds any of these, directly or
+// through another type, takes the lifetime `'a`. Every type is `Copy`.
+//
+// Only the non-allocating serialization routines are generated, with `get_width()` and
+// `serialized_size()`, so that values can be encoded into a buffer on the stack.
+
+use super::*;
+use crate::symbol_table::ValidatedSymbolTable;
+
+impl ValidatedDefinition {
+    pub(super) fn definition_no_std(
+        &self,
+        buf: &mut CodeBuf,
+        tab: &ValidatedSymbolTable,
+        params: &Params,
+    ) {
+        match self {
+            ValidatedDefinition::Struct(s) => s.definition_no_std(buf, tab, params),
+            ValidatedDefinition::Union(u) => u.definition_no_std(buf, tab, params),
+            _ => self.definition_copy(buf, tab, params),
+        }
+    }
+
+    pub(super) fn implementation_no_std(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
+        match self {
+            ValidatedDefinition::Enum(e) => {
+                e.default(buf);
+                buf.code_block(&format!("impl {}", e.name), |buf| {
+                    e.serialize_no_alloc(buf, tab);
+                    buf.add_line("pub fn serialized_size(&self) -> usize { 4 }");
+                });
+                buf.add_line("");
+            }
+            ValidatedDefinition::Struct(s) => s.codegen_no_std(buf, tab),
+            ValidatedDefinition::Union(u) => u.codegen_no_std(buf, tab),
+            ValidatedDefinition::TypeDef(_) | ValidatedDefinition::Const(_) => {}
+        }
+    }
+
+    /// Like `as_type_name()`, with the borrowed representation of variable-length data.
+    fn as_borrowed_type_name(&self, tab: &ValidatedSymbolTable) -> String {
+        match self {
+            ValidatedDefinition::Struct(s) => format!("{}{}", s.name, lifetime(self.borrows(tab))),
+            ValidatedDefinition::Union(u) => format!("{}{}", u.name, lifetime(self.borrows(tab))),
+            ValidatedDefinition::Enum(e) => e.name.to_string(),
+            ValidatedDefinition::Const(c) => c.value.as_type_name(tab),
+            ValidatedDefinition::TypeDef(t) => t.decl.as_borrowed_type_name(tab),
+        }
+    }
+
+    /// Whether the type holds a borrowed slice, and so takes a lifetime.
+    fn borrows(&self, tab: &ValidatedSymbolTable) -> bool {
+        match self {
+            ValidatedDefinition::Const(_) | ValidatedDefinition::Enum(_) => false,
+            ValidatedDefinition::TypeDef(t) => t.decl.borrows(tab),
+            ValidatedDefinition::Struct(s) => s.borrows(tab),
+            ValidatedDefinition::Union(u) => u.borrows(tab),
+        }
+    }
+}
+
+/// The lifetime parameter of a type that borrows, or nothing.
+fn lifetime(borrows: bool) -> &'static str {
+    if borrows {
+        "<'a>"
+    } else {
+        ""
+    }
+}
+
+impl ValidatedStruct {
+    fn definition_no_std(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable, params: &Params) {
+        let lifetime = lifetime(self.borrows(tab));
+        buf.type_header(params, true);
+        buf.code_block(&format!("pub struct {}{lifetime}", self.name), |buf| {
+            for (decl, _) in self.members.iter() {
+                let type_name = decl.as_borrowed_type_name(tab);
+                max_length(buf, decl, tab);
+                buf.add_line(&format!("pub {}: {},", decl.name, type_name));
+            }
+        });
+        buf.add_line("");
+    }
+
+    fn codegen_no_std(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
+        let lifetime = lifetime(self.borrows(tab));
+        let name = format!("{}{lifetime}", self.name);
+        buf.code_block(&format!("impl{lifetime} Default for {name}"), |buf| {
+            buf.code_block("fn default() -> Self", |buf| {
+                buf.code_block(&self.name, |buf| {
+                    for (decl, _) in self.members.iter() {
+                        let value = decl.borrowed_default_value(tab);
+                        buf.add_line(&format!("{}: {value},", decl.name));
+                    }
+                });
+            });
+        });
+        buf.code_block(&format!("impl{lifetime} {name}"), |buf| {
+            self.serialize_no_alloc(buf, tab);
+            buf.add_line("");
+            self.width_getters(buf, tab);
+            serialized_size(buf);
+        });
+        buf.add_line("");
+    }
+
+    fn borrows(&self, tab: &ValidatedSymbolTable) -> bool {
+        self.members.iter().any(|(decl, _)| decl.borrows(tab))
+    }
+}
+
+impl ValidatedUnion {
+    fn definition_no_std(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable, params: &Params) {
+        let name = format!("{}{}", self.name, lifetime(self.borrows(tab)));
+        buf.type_header(params, true);
+        match &self.body {
+            ValidatedUnionBody::Bool(b) => {
+                let inner_type = b.true_arm.as_borrowed_type_name(tab);
+                buf.code_block(&format!("pub struct {name}"), |buf| {
+                    max_length(buf, &b.true_arm, tab);
+                    buf.add_line(&format!("pub inner: Option<{inner_type}>,"));
+                });
+            }
+            ValidatedUnionBody::Enum(e) => {
+                buf.code_block(&format!("pub enum {name}"), |buf| {
+                    for (value, decl) in e.arms.iter() {
+                        let arm = ValidatedUnionEnumBody::arm_name(value);
+                        match decl {
+                            Declaration::Void => buf.add_line(&format!("{arm},")),
+                            Declaration::Named(n) => {
+                                max_length(buf, n, tab);
+                                buf.add_line(&format!("{arm}({}),", n.as_borrowed_type_name(tab)));
+                            }
+                        };
+                    }
+                    match &e.default_arm {
+                        Some(Declaration::Void) => buf.add_line("Default,"),
+                        Some(Declaration::Named(n)) => {
+                            max_length(buf, n, tab);
+                            buf.add_line(&format!("Default({}),", n.as_borrowed_type_name(tab)));
+                        }
+                        None => {}
+                    }
+                });
+            }
+        }
+    }
+
+    fn codegen_no_std(&self, buf: &mut CodeBuf, tab: &ValidatedSymbolTable) {
+        let lifetime = lifetime(self.borrows(tab));
+        let name = format!("{}{lifetime}", self.name);
+        buf.code_block(&format!("impl{lifetime} Default for {name}"), |buf| {
+            buf.code_block("fn default() -> Self", |buf| match &self.body {
+                ValidatedUnionBody::Bool(b) => b.default_bool(buf),
+                ValidatedUnionBody::Enum(e) => {
+                    let (value, declaration) = &e.arms[0];
+                    let arm = ValidatedUnionEnumBody::arm_name(value);
+                    match declaration {
+                        Declaration::Void => buf.add_line(&format!("Self::{arm}")),
+                        Declaration::Named(d) => {
+                            buf.add_line(&format!("Self::{arm}({})", d.borrowed_default_value(tab)))
+                        }
+                    };
+                }
+            });
+        });
+        buf.code_block(&format!("impl{lifetime} {name}"), |buf| {
+            self.serialize_no_alloc(buf, tab);
+            buf.add_line("");
+            self.width_getter(buf, tab);
+            serialized_size(buf);
+        });
+        buf.add_line("");
+    }
+
+    fn borrows(&self, tab: &ValidatedSymbolTable) -> bool {
+        match &self.body {
+            ValidatedUnionBody::Bool(b) => b.true_arm.borrows(tab),
+            ValidatedUnionBody::Enum(e) => e
+                .arms
+                .iter()
+                .map(|(_, decl)| decl)
+                .chain(&e.default_arm)
+                .any(|decl| match decl {
+                    Declaration::Named(n) => n.borrows(tab),
+                    Declaration::Void => false,
+                }),
+        }
+    }
+}
+
+impl NamedDeclaration {
+    fn as_borrowed_type_name(&self, tab: &ValidatedSymbolTable) -> String {
+        match &self.kind {
+            DeclarationKind::Scalar(ty) => ty.as_borrowed_type_name(tab),
+            DeclarationKind::Array(arr) => arr.as_borrowed_type_name(tab),
+            DeclarationKind::Optional(ty) => ty.optional_borrowed_type_name(tab),
+        }
+    }
+
+    fn borrowed_default_value(&self, tab: &ValidatedSymbolTable) -> String {
+        match &self.kind {
+            DeclarationKind::Scalar(ty) => ty.borrowed_default_value(tab),
+            DeclarationKind::Array(arr) => arr.borrowed_default_value(tab),
+            DeclarationKind::Optional(ty) if ty.self_referential_optional(tab) => "&[]".to_string(),
+            DeclarationKind::Optional(_) => "None".to_string(),
+        }
+    }
+
+    fn borrows(&self, tab: &ValidatedSymbolTable) -> bool {
+        match &self.kind {
+            // Self-referential optionals are slices:
+            DeclarationKind::Scalar(ty) | DeclarationKind::Optional(ty) => {
+                ty.self_referential_optional(tab) || ty.borrows(tab)
+            }
+            DeclarationKind::Array(arr) => match (&arr.size, &arr.kind) {
+                (ArraySize::Fixed(_), ArrayKind::Byte) => false,
+                (ArraySize::Fixed(_), ArrayKind::UserType(ty)) => ty.borrows(tab),
+                _ => true,
+            },
+        }
+    }
+}
+
+impl Array {
+    fn as_borrowed_type_name(&self, tab: &ValidatedSymbolTable) -> String {
+        let inner_type = match &self.kind {
+            ArrayKind::Ascii => return "&'a [u8]".to_string(),
+            ArrayKind::Byte => "u8".to_string(),
+            ArrayKind::UserType(ty) => ty.as_borrowed_type_name(tab),
+        };
+
+        match &self.size {
+            ArraySize::Fixed(len) => format!("[{inner_type}; {}]", len.as_const(tab)),
+            _ => format!("&'a [{inner_type}]"),
+        }
+    }
+
+    fn borrowed_default_value(&self, tab: &ValidatedSymbolTable) -> String {
+        match (&self.size, &self.kind) {
+            (ArraySize::Fixed(len), ArrayKind::Byte) => format!("[0; {}]", len.as_const(tab)),
+            (ArraySize::Fixed(_), ArrayKind::UserType(ty)) => format!(
+                "::core::array::from_fn(|_| {})",
+                ty.borrowed_default_value(tab)
+            ),
+            _ => "&[]".to_string(),
+        }
+    }
+}
+
+impl XdrType {
+    fn as_borrowed_type_name(&self, tab: &ValidatedSymbolTable) -> String {
+        match self {
+            XdrType::Name(name) => tab.lookup_definition(name).as_borrowed_type_name(tab),
+            _ => self.as_type_name(tab),
+        }
+    }
+
+    fn optional_borrowed_type_name(&self, tab: &ValidatedSymbolTable) -> String {
+        let inner_type = self.as_borrowed_type_name(tab);
+
+        if self.self_referential_optional(tab) {
+            format!("&'a [{inner_type}]")
+        } else {
+            format!("Option<{inner_type}>")
+        }
+    }
+
+    fn borrowed_default_value(&self, tab: &ValidatedSymbolTable) -> String {
+        let XdrType::Name(name) = self else {
+            return self.default_value(tab);
+        };
+        match *tab.lookup_definition(name) {
+            ValidatedDefinition::TypeDef(ref tdef) => tdef.decl.borrowed_default_value(tab),
+            _ => format!("{name}::default()"),
+        }
+    }
+
+    fn borrows(&self, tab: &ValidatedSymbolTable) -> bool {
+        match self {
+            XdrType::Name(name) => tab.lookup_definition(name).borrows(tab),
+            _ => false,
+        }
+    }
+}
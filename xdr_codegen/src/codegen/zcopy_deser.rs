@@ -308,19 +308,22 @@ impl ValidatedStruct {
         buf.add_line("#[derive(Debug, PartialEq, Clone)]");
         buf.code_block(&format!("pub struct {}Reader <'a>", self.name), |buf| {
             buf.add_line("buf: &'a [u8],");
-            for dep in deps.iter() {
-                let (member, _) = self.members.iter().find(|v| v.0.name == **dep).unwrap();
+            for (member, _) in self.members.iter().filter(|v| deps.contains(&v.0.name)) {
+                let dep = &member.name;
                 if member.is_varlen_reader(tab) {
                     let typename = member.as_zcopy_dser_type_name(tab);
 
                     buf.add_line(&format!("{}: {},", dep, typename));
                 } else {
-                    buf.add_line(&format!("{}_width: usize,", dep));
+                    buf.add_line(&format!("{}_width: usize,", unraw(dep)));
                 }
             }
 
             if let Some(last) = self_ref_last {
-                buf.add_line(&format!("{}_width: std::cell::OnceCell<usize>,", last.name));
+                buf.add_line(&format!(
+                    "{}_width: std::cell::OnceCell<usize>,",
+                    unraw(&last.name)
+                ));
             }
         });
 
@@ -334,7 +337,7 @@ impl ValidatedStruct {
                         let deps_in_order = self.get_variable_width_members_ordered(tab);
                         for (i,(nd, off)) in deps_in_order.iter().enumerate() {
                             if self.member_is_self_referential(nd, tab) {
-                                buf.add_line(&format!("let {}_width = std::cell::OnceCell::<usize>::new();", nd.name));
+                                buf.add_line(&format!("let {}_width = std::cell::OnceCell::<usize>::new();", unraw(&nd.name)));
                                 continue;
                             }
 
@@ -353,12 +356,12 @@ impl ValidatedStruct {
                                     if i + 1 != deps.len() {
                                         buf.add_line(&format!(
                                             "let {}_width = {}.get_width()?;",
-                                            nd.name, nd.name
+                                            unraw(&nd.name), nd.name
                                         ));
                                     }
                             } else {
                                 buf.block_with_trailer(
-                                    &format!("let {}_width = ", nd.name),
+                                    &format!("let {}_width = ", unraw(&nd.name)),
                                     "?;",
                                     |buf| {
                                         match &nd.kind {
@@ -394,14 +397,14 @@ impl ValidatedStruct {
                             buf.add_line("buf,");
                             for (nd, _) in deps_in_order.iter() {
                                 if self.member_is_self_referential(nd, tab) {
-                                    buf.add_line(&format!("{}_width,", nd.name));
+                                    buf.add_line(&format!("{}_width,", unraw(&nd.name)));
                                     continue;
                                 }
 
                                 if nd.is_varlen_reader(tab) {
                                     buf.add_line(&format!("{},", nd.name));
                                 } else {
-                                    buf.add_line(&format!("{}_width,", nd.name));
+                                    buf.add_line(&format!("{}_width,", unraw(&nd.name)));
                                 }
                             }
                         });
@@ -487,17 +490,14 @@ impl ValidatedStruct {
             buf.add_line("Ok(self)");
         });
 
-        let deps = self.get_variable_width_members(tab);
-
-        for dep in deps.iter() {
+        for (member, member_off) in self.get_variable_width_members_ordered(tab) {
             buf.code_block(
-                &format!("pub fn get_{}_width(&self) -> xdr_lib::Result<usize>", dep),
+                &format!(
+                    "pub fn get_{}_width(&self) -> xdr_lib::Result<usize>",
+                    unraw(&member.name)
+                ),
                 |buf| {
-                    let (member, member_off) = self
-                        .members
-                        .iter()
-                        .find(|(nd, _)| nd.name == **dep)
-                        .unwrap();
+                    let (member, member_off) = (&member, &member_off);
 
                     if self.member_is_self_referential(member, tab) {
                         match &member.kind {
@@ -516,7 +516,7 @@ impl ValidatedStruct {
                         if member.is_varlen_reader(tab) {
                             buf.add_line(&format!("self.{}.get_width()", member.name));
                         } else {
-                            buf.add_line(&format!("Ok(self.{}_width)", member.name));
+                            buf.add_line(&format!("Ok(self.{}_width)", unraw(&member.name)));
                         }
                     }
                 },
@@ -527,7 +527,7 @@ impl ValidatedStruct {
             buf.code_block(
                 &format!(
                     "pub fn get_{}(&self) -> {}",
-                    member.name,
+                    unraw(&member.name),
                     member.as_zcopy_dser_type_name(tab)
                 ),
                 |buf| {
@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// A line diff in the unified format, for showing how checked-in generated code differs from what
+// the compiler generates now.
+
+/// The number of unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Op {
+    Same,
+    Removed,
+    Added,
+}
+
+impl Op {
+    fn marker(self) -> char {
+        match self {
+            Op::Same => ' ',
+            Op::Removed => '-',
+            Op::Added => '+',
+        }
+    }
+}
+
+/// The lines of `old` and `new`, each marked as unchanged, removed from `old`, or added in `new`.
+fn line_ops<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Op, &'a str)> {
+    // Only the lines between the common prefix and suffix need to be compared, which keeps the
+    // table small for the usual case of a few changes in a large file:
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..]:
+    let mut lcs = vec![vec![0_u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops: Vec<_> = old[..prefix].iter().map(|&line| (Op::Same, line)).collect();
+
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push((Op::Same, a[i]));
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push((Op::Removed, a[i]));
+            i += 1;
+        } else {
+            ops.push((Op::Added, b[j]));
+            j += 1;
+        }
+    }
+
+    ops.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|&line| (Op::Same, line)),
+    );
+
+    ops
+}
+
+/// The differences between `old` and `new` as the hunks of a unified diff, or an empty string if
+/// they have the same lines.
+pub(crate) fn diff(old: &str, new: &str) -> String {
+    let old: Vec<_> = old.lines().collect();
+    let new: Vec<_> = new.lines().collect();
+    let ops = line_ops(&old, &new);
+
+    let changes: Vec<_> = (0..ops.len()).filter(|&i| ops[i].0 != Op::Same).collect();
+
+    let mut out = String::new();
+    let mut k = 0;
+    while k < changes.len() {
+        // Changes separated by no more than twice the context share a hunk:
+        let start = changes[k].saturating_sub(CONTEXT);
+        let mut last = changes[k];
+        k += 1;
+        while k < changes.len() && changes[k] - last <= 2 * CONTEXT {
+            last = changes[k];
+            k += 1;
+        }
+        let end = (last + 1 + CONTEXT).min(ops.len());
+
+        // The range of lines of one side in the hunk, as "start,length". An empty range starts at
+        // the line before it, as in diff(1):
+        let range = |skip: Op| {
+            let count = |ops: &[(Op, &str)]| ops.iter().filter(|(op, _)| *op != skip).count();
+            let (before, len) = (count(&ops[..start]), count(&ops[start..end]));
+            format!("{},{len}", if len == 0 { before } else { before + 1 })
+        };
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(Op::Added),
+            range(Op::Removed)
+        ));
+        for (op, line) in &ops[start..end] {
+            out.push(op.marker());
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff;
+
+    #[test]
+    fn unified_hunks() {
+        let old: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+        let new = old
+            .replace("line 2\n", "line two\n")
+            .replace("line 15\n", "")
+            .replace("line 20\n", "line 20\nline 21\n");
+
+        assert_eq!(
+            diff(&old, &new),
+            "\
+@@ -1,5 +1,5 @@
+ line 1
+-line 2
++line two
+ line 3
+ line 4
+ line 5
+@@ -12,9 +12,9 @@
+ line 12
+ line 13
+ line 14
+-line 15
+ line 16
+ line 17
+ line 18
+ line 19
+ line 20
++line 21
+"
+        );
+
+        assert_eq!(diff(&old, &old), "");
+        assert_eq!(diff("", "a\n"), "@@ -0,0 +1,1 @@\n+a\n");
+    }
+}
@@ -2,7 +2,9 @@
 // Copyright 2025. Triad National Security, LLC.
 
 mod ast;
+mod build_script;
 mod codegen;
+mod diff;
 mod ir;
 mod parser;
 mod scanner;
@@ -16,6 +18,9 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use ast::Schema;
+pub use build_script::{build_helper, BuildError, BuildHelper};
+pub use codegen::Annotator;
 use parser::Parser;
 use scanner::{Scanner, Token};
 
@@ -37,10 +42,43 @@ enum XdrError {
 
     /// For attempting to use a name that should resolve to a constant, when the name isn't a
     /// constant
-    _NotAConstant(String),
+    NotAConstant(String),
 
-    /// For attempting to use a constant that isn't an integer
-    InvalidConstantDefinition(String),
+    /// For defining a name that is already defined, with the lines of both definitions
+    DuplicateDefinition {
+        name: String,
+        line: usize,
+        first: usize,
+    },
+
+    /// For referring to a name that isn't defined anywhere, with the definition or program that
+    /// refers to it, and the line it begins on
+    UndefinedReference {
+        name: String,
+        referrer: String,
+        line: usize,
+    },
+
+    /// For a typedef that is defined in terms of itself, with each typedef of the cycle
+    CyclicTypedef { names: Vec<String>, line: usize },
+
+    /// For checked-in generated files that differ from the code generated now, with a diff of
+    /// each against the new code
+    OutOfDate(Vec<(PathBuf, String)>),
+
+    /// For checking generated code that is written to stdout
+    NothingToCheck,
+
+    /// For a file that includes itself, directly or through other files
+    IncludeCycle(PathBuf),
+
+    /// For generated code that does not parse, which is a bug in the code generator, with the
+    /// parser's error, and the line of the code that it is on
+    InvalidCode {
+        error: String,
+        line: usize,
+        code: String,
+    },
 }
 
 impl std::error::Error for XdrError {}
@@ -56,10 +94,40 @@ impl fmt::Display for XdrError {
             XdrError::_Parse(_) => todo!(),
             XdrError::UnsupportedOptional(n) => write!(f, "Unsupported optional in: {n}"),
             XdrError::UndefinedName(n) => write!(f, "Undefined name: {n}"),
-            XdrError::_NotAConstant(n) => write!(f, "Not a constant: {n}"),
-            XdrError::InvalidConstantDefinition(n) => {
-                write!(f, "Constant definition is invalid, must be an integer: {n}")
+            XdrError::NotAConstant(n) => write!(f, "Not a constant: {n}"),
+            XdrError::DuplicateDefinition { name, line, first } => write!(
+                f,
+                "Duplicate definition of {name} on line {line} (first defined on line {first})"
+            ),
+            XdrError::UndefinedReference {
+                name,
+                referrer,
+                line,
+            } => write!(
+                f,
+                "Undefined name {name}, used by {referrer} on line {line}"
+            ),
+            XdrError::CyclicTypedef { names, line } => write!(
+                f,
+                "Typedef {} on line {line} is defined in terms of itself: {}",
+                names[0],
+                names.join(" -> ")
+            ),
+            XdrError::OutOfDate(files) => {
+                writeln!(f, "Generated code is out of date; regenerate it:")?;
+                for (path, diff) in files {
+                    let path = path.display();
+                    write!(f, "--- {path} (checked in)\n+++ {path} (generated)\n{diff}")?;
+                }
+                Ok(())
             }
+            XdrError::NothingToCheck => write!(f, "Only generated files can be checked"),
+            XdrError::IncludeCycle(path) => write!(f, "{} includes itself", path.display()),
+            XdrError::InvalidCode { error, line, code } => write!(
+                f,
+                "Generated code does not parse: {error}, on line {line}: {}",
+                code.trim()
+            ),
         }
     }
 }
@@ -69,9 +137,30 @@ enum InputSource {
     Files(Vec<PathBuf>),
 }
 
+/// A file to generate code from, with the indices, among the files loaded with it, of those that it
+/// includes.
+struct XdrFile {
+    path: PathBuf,
+    module: String,
+    source: String,
+    includes: Vec<usize>,
+}
+
+impl XdrFile {
+    fn parse(&self) -> Result<Schema> {
+        Parser::new(Scanner::new(&self.source)).parse()
+    }
+}
+
 pub struct Compiler {
     source: InputSource,
     params: codegen::Params,
+
+    /// Where generated files go, if not in OUT_DIR.
+    out_dir: Option<PathBuf>,
+
+    /// The module that the code of every file is generated in, if they are merged into one.
+    merged: Option<String>,
 }
 
 impl Compiler {
@@ -80,6 +169,8 @@ impl Compiler {
         Compiler {
             source: InputSource::StdIo,
             params: codegen::Params::default(),
+            out_dir: None,
+            merged: None,
         }
     }
 
@@ -110,11 +201,287 @@ impl Compiler {
         self
     }
 
+    /// Generate code for `#![no_std]` crates, which depend on `xdr_lib` without its `std` feature.
+    /// Strings and variable-length arrays are borrowed slices, such as `&'a [u8]` for a string or
+    /// opaque data, as are self-referential optionals, so types that hold them take a lifetime.
+    ///
+    /// Only the non-allocating serialization routines are generated, as with `enable_no_alloc()`
+    /// and `disable_alloc()`; deserialization, zero-copy readers, `Display`, and serde's traits are
+    /// not.
+    pub fn no_std(&mut self) -> &mut Self {
+        self.params.no_std = true;
+        self.params.no_alloc = true;
+        self.params.alloc = false;
+        self
+    }
+
     pub fn enable_zcopy(&mut self) -> &mut Self {
         self.params.zcopy = true;
         self
     }
 
+    /// Derive serde's `Serialize` and `Deserialize` traits on the generated types. The crate that
+    /// includes the generated code must depend on `serde` with the `derive` feature.
+    pub fn derive_serde(&mut self) -> &mut Self {
+        self.params.serde = true;
+        self
+    }
+
+    /// Also derive the trait `path`, such as `PartialOrd` or `my_crate::MyDerive`, on every generated
+    /// type, besides `Debug`, `PartialEq`, `Eq`, `Hash`, and `Clone`, which they all derive, and
+    /// `Copy`, which those that hold no vectors or strings derive. May be called more than once.
+    pub fn derive(&mut self, path: &str) -> &mut Self {
+        self.params.derives.push(path.to_string());
+        self
+    }
+
+    /// Also generate a `deserialize_from()` method for each type, which decodes from an
+    /// `std::io::Read` instead of a slice.
+    pub fn enable_deserialize_from(&mut self) -> &mut Self {
+        self.params.deserialize_from = true;
+        self
+    }
+
+    /// Implement `Display` on the generated types, for logs and command line output: structs as
+    /// `Name { member: value }`, enums by name, unions by arm, such as `Ok(value)`, and opaque data in
+    /// hexadecimal.
+    pub fn enable_display(&mut self) -> &mut Self {
+        self.params.display = true;
+        self
+    }
+
+    /// With `enable_display()`, display the struct `name` as a timestamp in RFC 3339 format, taking
+    /// its first two members as seconds and nanoseconds since the epoch, as in NFS v3's `nfstime3`.
+    /// May be called more than once.
+    pub fn display_timestamp(&mut self, name: &str) -> &mut Self {
+        self.params.timestamps.push(name.to_string());
+        self
+    }
+
+    /// With `enable_display()`, display values of the integer typedef `name` as sizes in bytes, with
+    /// binary units, such as `1536 (1.5 KiB)`. May be called more than once.
+    pub fn display_size(&mut self, name: &str) -> &mut Self {
+        self.params.sizes.push(name.to_string());
+        self
+    }
+
+    /// With `enable_display()`, leave `Display` unimplemented on the type `name`, for the crate that
+    /// includes the generated code to implement by hand. May be called more than once.
+    pub fn skip_display(&mut self, name: &str) -> &mut Self {
+        self.params.skip_display.push(name.to_string());
+        self
+    }
+
+    /// Copy the `%` lines of the source, which rpcgen copies into the C it generates, into the
+    /// generated code as doc comments of the definition or program each precedes or is within.
+    /// (Lines after the last definition of a file are dropped, as are those of typedefs, which
+    /// generate no item to document.)
+    pub fn literal_comments(&mut self) -> &mut Self {
+        self.params.literal_comments = true;
+        self
+    }
+
+    /// Call `annotator` with the `%` lines of each definition or program that has any, and generate
+    /// the code it returns ahead of the definition, so that pragmas in a specification, such as
+    /// `%#pragma timestamp`, can add attributes or impls to the types they annotate.
+    pub fn annotate(&mut self, annotator: Annotator) -> &mut Self {
+        self.params.annotator = Some(annotator);
+        self
+    }
+
+    /// Write the code generated from each file into `dir`, instead of into the OUT_DIR of the build
+    /// script, for example to check it in.
+    pub fn out_dir<P>(&mut self, dir: P) -> &mut Self
+    where
+        P: AsRef<Path>,
+    {
+        self.out_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Generate the code of every file, and of the files they include, in one module named
+    /// `module`, written to `module.rs`, so that their definitions share one symbol table.
+    ///
+    /// Otherwise, each file's code is generated in a module of its own, named after the file. A file
+    /// may still use the types of another that it names in an `%#include "other.x"` line, relative
+    /// to its own directory: the included file's code is generated too, if it is not given with
+    /// `file()`, and the including module imports its types from `super::other`, so the generated
+    /// files should be included side by side.
+    pub fn merge(&mut self, module: &str) -> &mut Self {
+        self.merged = Some(module.to_string());
+        self
+    }
+
+    /// Generate the code for each file without writing it, and compare it to the file that `run()`
+    /// would write. Fails with a diff of every file that differs, or is missing, so that checked-in
+    /// generated code can be kept from being edited by hand or going stale.
+    pub fn check(&mut self) -> std::result::Result<(), Box<dyn Error>> {
+        let InputSource::Files(list) = &self.source else {
+            return Err(XdrError::NothingToCheck.into());
+        };
+
+        let mut out_of_date = Vec::new();
+        for (out_file, code) in self.generate(list)? {
+            let checked_in = match std::fs::read_to_string(&out_file) {
+                Ok(checked_in) => checked_in,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(e) => return Err(e.into()),
+            };
+
+            if checked_in != code {
+                let mut diff = diff::diff(&checked_in, &code);
+                if diff.is_empty() {
+                    diff = "(differs only in line endings)\n".into();
+                }
+                out_of_date.push((out_file, diff));
+            }
+        }
+
+        if !out_of_date.is_empty() {
+            return Err(XdrError::OutOfDate(out_of_date).into());
+        }
+
+        Ok(())
+    }
+
+    /// Generate the code of each file in `list`, and of the files they include, or of all of them
+    /// merged into one module. Returns the path to write each module to, and its code.
+    fn generate(
+        &self,
+        list: &[PathBuf],
+    ) -> std::result::Result<Vec<(PathBuf, String)>, Box<dyn Error>> {
+        let files = Self::load_files(list)?;
+        Ok(self.generate_files(&files)?)
+    }
+
+    /// Load each file in `list`, after the files it includes.
+    fn load_files(list: &[PathBuf]) -> std::result::Result<Vec<XdrFile>, Box<dyn Error>> {
+        let mut files = Vec::new();
+        for infile in list.iter() {
+            Self::load_file(infile, &mut files, &mut Vec::new())?;
+        }
+
+        Ok(files)
+    }
+
+    /// Generate the code of each of `files`, or of all of them merged into one module. Returns the
+    /// path to write each module to, and its code.
+    fn generate_files(&self, files: &[XdrFile]) -> Result<Vec<(PathBuf, String)>> {
+        let mut generated = Vec::new();
+        match &self.merged {
+            Some(module) => {
+                let code = self.generate_merged(files, module)?;
+                generated.push((self.out_file(module), code));
+            }
+            None => {
+                for i in 0..files.len() {
+                    eprintln!("Starting file {:?}", files[i].path.display());
+                    let code = self.generate_file(files, i)?;
+                    generated.push((self.out_file(&files[i].module), code));
+                }
+            }
+        }
+
+        Ok(generated)
+    }
+
+    /// Generate the code of `files[i]` in its own module, which imports the types of the files it
+    /// includes, directly or through others.
+    fn generate_file(&self, files: &[XdrFile], i: usize) -> Result<String> {
+        let mut included = vec![false; files.len()];
+        let mut stack = files[i].includes.clone();
+        while let Some(j) = stack.pop() {
+            if !included[j] {
+                included[j] = true;
+                stack.extend(&files[j].includes);
+            }
+        }
+
+        // Files are loaded after those they include, so these are in the order they are defined:
+        let mut imports = Vec::new();
+        for file in files
+            .iter()
+            .zip(included)
+            .filter_map(|(f, inc)| inc.then_some(f))
+        {
+            imports.push((file.module.clone(), file.parse()?));
+        }
+        let mut schema = files[i].parse()?;
+        schema.import(imports);
+
+        Self::codegen(schema, &files[i].module, &self.params)
+    }
+
+    fn generate_merged(&self, files: &[XdrFile], module: &str) -> Result<String> {
+        let schemas = files.iter().map(XdrFile::parse).collect::<Result<_>>()?;
+
+        Self::codegen(Schema::merge(schemas), module, &self.params)
+    }
+
+    fn out_file(&self, module: &str) -> PathBuf {
+        let mut out_file = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => PathBuf::from(std::env::var("OUT_DIR").expect("OUT_DIR should be defined")),
+        };
+        out_file.push(format!("{module}.rs"));
+
+        out_file
+    }
+
+    /// Load the file at `path`, unless it is already in `files`, after the files it includes.
+    /// `including` holds the files that include it, to find those that include themselves. Returns
+    /// the index of the file in `files`.
+    fn load_file(
+        path: &Path,
+        files: &mut Vec<XdrFile>,
+        including: &mut Vec<PathBuf>,
+    ) -> std::result::Result<usize, Box<dyn Error>> {
+        let path = path.canonicalize()?;
+        if including.contains(&path) {
+            return Err(XdrError::IncludeCycle(path).into());
+        }
+        if let Some(i) = files.iter().position(|f| f.path == path) {
+            return Ok(i);
+        }
+
+        let source = std::fs::read_to_string(&path)?;
+        let module = path
+            .file_stem()
+            .unwrap_or(std::ffi::OsStr::new("XdrInterface"))
+            .to_str()
+            .unwrap()
+            .to_string();
+        Self::load(path, module, source, files, including)
+    }
+
+    /// Load `source`, the contents of the file at `path`, after the files it includes.
+    fn load(
+        path: PathBuf,
+        module: String,
+        source: String,
+        files: &mut Vec<XdrFile>,
+        including: &mut Vec<PathBuf>,
+    ) -> std::result::Result<usize, Box<dyn Error>> {
+        let schema = Parser::new(Scanner::new(&source)).parse()?;
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        including.push(path.clone());
+        let mut includes = Vec::new();
+        for include in schema.includes.iter() {
+            includes.push(Self::load_file(&dir.join(include), files, including)?);
+        }
+        including.pop();
+
+        files.push(XdrFile {
+            path,
+            module,
+            source,
+            includes,
+        });
+
+        Ok(files.len() - 1)
+    }
+
     pub fn run(&mut self) -> std::result::Result<(), Box<dyn Error>> {
         match &self.source {
             InputSource::StdIo => {
@@ -122,27 +489,27 @@ impl Compiler {
                 io::stdin().read_to_end(&mut source)?;
                 let source = String::from_utf8(source).expect("Input should be valid UTF-8");
 
-                print!(
-                    "{}",
-                    Compiler::codegen(&source, "XdrInterface", &self.params)?
-                )
+                // Files that the input includes are relative to the current directory, and their
+                // code is not generated:
+                let mut files = Vec::new();
+                let module = self.merged.as_deref().unwrap_or("XdrInterface");
+                let i = Self::load(
+                    PathBuf::from("-"),
+                    module.to_string(),
+                    source,
+                    &mut files,
+                    &mut Vec::new(),
+                )?;
+                let code = match &self.merged {
+                    Some(module) => self.generate_merged(&files, module)?,
+                    None => self.generate_file(&files, i)?,
+                };
+                print!("{code}");
             }
             InputSource::Files(list) => {
-                for infile in list.iter() {
-                    eprintln!("Starting file {:?}", infile.display());
-                    let source = std::fs::read_to_string(infile)?;
-                    let module_name = infile
-                        .file_stem()
-                        .unwrap_or(std::ffi::OsStr::new("XdrInterface"));
-                    let code = Self::codegen(&source, module_name.to_str().unwrap(), &self.params)?;
-
-                    let mut out_name = module_name.to_owned();
-                    out_name.push(".rs");
-                    let out_file = std::env::var("OUT_DIR").expect("OUT_DIR should be defined");
-                    let mut out_file = PathBuf::from(out_file);
-                    out_file.push(out_name);
-                    std::fs::write(out_file, code)?;
-                    eprintln!("Finished file {:?}", infile.display());
+                for (out_file, code) in self.generate(list)? {
+                    std::fs::write(&out_file, code)?;
+                    eprintln!("Finished file {:?}", out_file.display());
                 }
             }
         };
@@ -150,10 +517,219 @@ impl Compiler {
         Ok(())
     }
 
-    fn codegen(source: &str, module_name: &str, params: &codegen::Params) -> Result<String> {
-        let mut parser = Parser::new(Scanner::new(source));
-        let schema = parser.parse()?;
+    fn codegen(schema: Schema, module_name: &str, params: &codegen::Params) -> Result<String> {
         let validated_schema = validate::ValidatedSchema::validate(schema)?;
-        Ok(codegen::codegen(&validated_schema, module_name, params))
+        Self::format(codegen::codegen(&validated_schema, module_name, params))
+    }
+
+    /// Parse `code` as a file of Rust, so that the code generator never writes code that does not
+    /// build, and lay it out as rustfmt would with prettyplease.
+    fn format(code: String) -> Result<String> {
+        match syn::parse_file(&code) {
+            Ok(file) => Ok(prettyplease::unparse(&file)),
+            Err(e) => {
+                let line = e.span().start().line;
+                Err(XdrError::InvalidCode {
+                    error: e.to_string(),
+                    line,
+                    code: code
+                        .lines()
+                        .nth(line.saturating_sub(1))
+                        .unwrap_or("")
+                        .to_string(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{build_helper, Compiler, XdrError};
+
+    /// Write each of `files`, a name and its contents, to a directory of its own for `test`.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("xdr_codegen_{test}_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn includes() {
+        // Both a.x and b.x include c.x, which is generated once, and a.x includes it through b.x
+        // too:
+        let dir = write_files(
+            "includes",
+            &[
+                (
+                    "a.x",
+                    "%#include \"b.x\"\n%#include \"c.x\"\nstruct A { B b; C c; };",
+                ),
+                ("b.x", "%#include \"c.x\"\nstruct B { C c; };"),
+                ("c.x", "struct C { int c; };"),
+            ],
+        );
+        let list = [dir.join("a.x")];
+        let mut compiler = Compiler::new();
+        compiler.out_dir(&dir);
+        let generated = compiler.generate(&list).unwrap();
+        let modules: Vec<_> = generated
+            .iter()
+            .map(|(path, _)| path.file_stem().unwrap())
+            .collect();
+        assert_eq!(modules, ["c", "b", "a"]);
+        assert!(generated[2].1.contains("use super::b::*;"));
+        assert!(generated[2].1.contains("use super::c::*;"));
+        assert!(!generated[2].1.contains("pub struct C"));
+
+        compiler.merge("all");
+        let generated = compiler.generate(&list).unwrap();
+        assert_eq!(generated.len(), 1);
+        assert!(generated[0].0.ends_with("all.rs"));
+        assert!(generated[0].1.contains("pub struct C"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = write_files(
+            "include_cycle",
+            &[
+                ("a.x", "%#include \"b.x\"\nstruct A { int a; };"),
+                ("b.x", "%#include <a.x>\nstruct B { int b; };"),
+            ],
+        );
+        let mut compiler = Compiler::new();
+        compiler.file(dir.join("a.x")).out_dir(&dir);
+        let err = compiler.check().unwrap_err();
+        let err = err.downcast_ref::<XdrError>().unwrap();
+        assert!(matches!(err, XdrError::IncludeCycle(path) if path.ends_with("a.x")));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn literal_lines() {
+        let dir = write_files(
+            "literal_lines",
+            &[(
+                "a.x",
+                "%a\nconst A = 1;\n%b\nprogram P {\n%c\nversion V { void NULL(void) = 0; } = 1;\n} = 2;\n%d",
+            )],
+        );
+        let list = [dir.join("a.x")];
+        let mut compiler = Compiler::new();
+        compiler.out_dir(&dir);
+        let generated = compiler.generate(&list).unwrap();
+        assert!(!generated[0].1.contains("// %"));
+
+        compiler.literal_comments();
+        let generated = &compiler.generate(&list).unwrap()[0].1;
+        let position = |text| generated.find(text).unwrap();
+        assert!(position("// %a") < position("pub const A"));
+        assert!(position("pub const A") < position("// %b"));
+        assert!(position("// %b") < position("// %c"));
+        assert!(position("// %c") < position("pub mod procedures"));
+        assert!(!generated.contains("// %d"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_code() {
+        // An annotator's code is generated as is, and may not parse:
+        let dir = write_files("invalid_code", &[("a.x", "%#broken\nstruct A { int a; };")]);
+        let mut compiler = Compiler::new();
+        compiler
+            .file(dir.join("a.x"))
+            .out_dir(&dir)
+            .annotate(|_, _| Some("pub fn a;".to_string()));
+        let err = compiler.check().unwrap_err();
+        let err = err.downcast_ref::<XdrError>().unwrap();
+        assert!(
+            matches!(err, XdrError::InvalidCode { line: 3, code, .. } if code.trim() == "pub fn a;")
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn build_helper_options() {
+        let dir = write_files(
+            "build_helper_options",
+            &[
+                ("a.x", "%#include \"c.x\"\nstruct A { C c; };"),
+                ("b.x", "struct B { string b<>; };"),
+                ("c.x", "struct C { int c; };"),
+                ("bad.x", "struct Bad { Missing m; };"),
+            ],
+        );
+        let out_dir = dir.clone();
+        build_helper()
+            .options(move |c| c.out_dir(&out_dir))
+            .spec(dir.join("a.x"))
+            .spec_with(dir.join("b.x"), |c| c.derive("PartialOrd").merge("other"))
+            .build()
+            .unwrap();
+        assert!(fs::read_to_string(dir.join("a.rs"))
+            .unwrap()
+            .contains("use super::c::*;"));
+        assert!(dir.join("c.rs").exists());
+        assert!(!dir.join("b.rs").exists());
+        assert!(fs::read_to_string(dir.join("other.rs"))
+            .unwrap()
+            .contains("PartialOrd"));
+
+        // Each specification that fails is reported, and the others are still generated:
+        let out_dir = dir.clone();
+        let errors = build_helper()
+            .options(move |c| c.out_dir(&out_dir))
+            .spec(dir.join("bad.x"))
+            .spec(dir.join("missing.x"))
+            .spec(dir.join("b.x"))
+            .build()
+            .unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].spec(), dir.join("bad.x"));
+        assert!(errors[0].to_string().contains("Missing"));
+        assert_eq!(errors[1].spec(), dir.join("missing.x"));
+        assert!(dir.join("b.rs").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn build_helper_conflicts() {
+        // c.x is generated for both, with different options:
+        let dir = write_files(
+            "build_helper_conflicts",
+            &[
+                ("a.x", "%#include \"c.x\"\nstruct A { C c; };"),
+                ("b.x", "%#include \"c.x\"\nstruct B { C c; };"),
+                ("c.x", "struct C { int c; };"),
+            ],
+        );
+        let out_dir = dir.clone();
+        let mut helper = build_helper();
+        helper
+            .options(move |c| c.out_dir(&out_dir))
+            .spec(dir.join("a.x"))
+            .spec(dir.join("b.x"));
+        helper.build().unwrap();
+
+        helper.spec_with(dir.join("c.x"), |c| c.derive_serde());
+        let errors = helper.build().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].spec(), dir.join("c.x"));
+        assert!(errors[0].to_string().contains("c.rs is also generated"));
+
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -1,30 +1,149 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
+use std::{path::PathBuf, process::ExitCode};
+
 use clap::Parser;
 
 #[derive(Parser, Debug)]
 struct Args {
+    /// The .x files to compile, each into a .rs file of the same name in the output directory.
+    /// Without any, a specification is read from stdin and its code written to stdout.
+    files: Vec<PathBuf>,
+
+    /// The directory to write generated files to. Defaults to OUT_DIR.
+    #[arg(long)]
+    out_dir: Option<PathBuf>,
+
+    /// Generate the code of every file, and of the files they include, in one module named MODULE,
+    /// as `Compiler::merge()` does.
+    #[arg(long, value_name = "MODULE")]
+    merge: Option<String>,
+
+    /// Rather than writing the generated files, check that the files already in the output
+    /// directory are what would be written, and print a diff of each that is not.
+    #[arg(long)]
+    check_generated: bool,
+
     /// Whether to generate non-allocating serialization routines.
     #[arg(short, long)]
     no_alloc: bool,
 
+    /// Generate code for `#![no_std]` crates, as `Compiler::no_std()` does.
+    #[arg(long)]
+    no_std: bool,
+
     /// Whether to generate zero-copy serdes routines
     #[arg(short, long)]
     zero_copy: bool,
+
+    /// Derive serde's traits on the generated types, as `Compiler::derive_serde()` does.
+    #[arg(long)]
+    derive_serde: bool,
+
+    /// Also derive TRAIT on every generated type, as `Compiler::derive()` does. May be repeated.
+    #[arg(long = "derive", value_name = "TRAIT")]
+    derives: Vec<String>,
+
+    /// Generate `deserialize_from()` methods, as `Compiler::enable_deserialize_from()` does.
+    #[arg(long)]
+    deserialize_from: bool,
+
+    /// Implement `Display` on the generated types, as `Compiler::enable_display()` does.
+    #[arg(long)]
+    display: bool,
+
+    /// Display the struct NAME as a timestamp, as `Compiler::display_timestamp()` does. May be
+    /// repeated.
+    #[arg(long = "display-timestamp", value_name = "NAME")]
+    timestamps: Vec<String>,
+
+    /// Display the typedef NAME as a size, as `Compiler::display_size()` does. May be repeated.
+    #[arg(long = "display-size", value_name = "NAME")]
+    sizes: Vec<String>,
+
+    /// Leave `Display` unimplemented on the type NAME, as `Compiler::skip_display()` does. May be
+    /// repeated.
+    #[arg(long = "skip-display", value_name = "NAME")]
+    skip_display: Vec<String>,
+
+    /// Copy the `%` lines of the source into the generated code as comments, as
+    /// `Compiler::literal_comments()` does.
+    #[arg(long)]
+    literal_comments: bool,
 }
 
-pub fn main() -> Result<(), Box<dyn std::error::Error>> {
+pub fn main() -> ExitCode {
     let args = Args::parse();
     let mut compiler = xdr_codegen::Compiler::new();
 
+    for file in args.files.iter() {
+        compiler.file(file);
+    }
+
+    if let Some(dir) = &args.out_dir {
+        compiler.out_dir(dir);
+    }
+
+    if let Some(module) = &args.merge {
+        compiler.merge(module);
+    }
+
     if args.zero_copy {
         compiler.enable_zcopy();
     }
 
+    if args.derive_serde {
+        compiler.derive_serde();
+    }
+
+    for derive in args.derives.iter() {
+        compiler.derive(derive);
+    }
+
+    if args.deserialize_from {
+        compiler.enable_deserialize_from();
+    }
+
+    if args.display {
+        compiler.enable_display();
+    }
+
+    for name in args.timestamps.iter() {
+        compiler.display_timestamp(name);
+    }
+
+    for name in args.sizes.iter() {
+        compiler.display_size(name);
+    }
+
+    for name in args.skip_display.iter() {
+        compiler.skip_display(name);
+    }
+
+    if args.literal_comments {
+        compiler.literal_comments();
+    }
+
     if args.no_alloc {
-        compiler.enable_no_alloc().disable_alloc().run()
+        compiler.enable_no_alloc().disable_alloc();
+    }
+
+    if args.no_std {
+        compiler.no_std();
+    }
+
+    let res = if args.check_generated {
+        compiler.check()
     } else {
         compiler.run()
+    };
+
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("xdr_codegen: {e}");
+            ExitCode::FAILURE
+        }
     }
 }
@@ -1,6 +1,8 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
+use std::collections::HashMap;
+
 use crate::{ast::*, scanner::*};
 
 pub struct Parser<'src> {
@@ -10,6 +12,12 @@ pub struct Parser<'src> {
     /// When the schema contains a string type, the generated code needs to know this in order to
     /// include the right FFI modules.
     schema_contains_string: bool,
+    /// The names of the structs and unions whose bodies are being parsed, innermost last, which
+    /// anonymous types declared in them are named after.
+    scope: Vec<String>,
+    /// The definitions of the anonymous types declared in the definition being parsed, which
+    /// precede it in the schema.
+    nested: Vec<Definition>,
 }
 
 impl<'src> Parser<'src> {
@@ -25,6 +33,8 @@ impl<'src> Parser<'src> {
                 line: 0,
             },
             schema_contains_string: false,
+            scope: Vec::new(),
+            nested: Vec::new(),
         };
 
         parser.next();
@@ -34,23 +44,66 @@ impl<'src> Parser<'src> {
 
     pub fn parse(&mut self) -> crate::Result<Schema> {
         let mut definitions = Vec::new();
+        let mut lines = Vec::new();
         let mut programs = Vec::new();
+        let mut includes = Vec::new();
+        let mut literals = HashMap::new();
         loop {
-            match self.peek().kind {
-                TokenKind::Program => programs.push(self.program()),
+            match &self.peek().kind {
+                TokenKind::Program => {
+                    let program = self.program();
+                    literals.insert(program.name.clone(), self.literals());
+                    programs.push(program);
+                }
+                TokenKind::Include(file) => {
+                    includes.push(file.clone());
+                    self.next();
+                }
                 TokenKind::Eof => break,
-                _ => definitions.push(self.definition()),
+                _ => {
+                    let line = self.peek().line;
+                    let definition = self.definition();
+                    literals.insert(definition.get_name().to_string(), self.literals());
+                    lines.resize(lines.len() + self.nested.len() + 1, line);
+                    definitions.append(&mut self.nested);
+                    definitions.push(definition);
+                }
             }
         }
         Ok(Schema {
             definitions,
+            lines,
             programs,
             contains_string: self.schema_contains_string,
+            includes,
+            imports: Vec::new(),
+            imported: 0,
+            literals,
         })
     }
 
+    /// The `%` lines before the definition whose last token was just parsed, and those within it.
+    /// (Those after it, which the scanner may have passed in peeking at the next token, belong to
+    /// the next definition.)
+    fn literals(&mut self) -> Vec<String> {
+        let end = self.current.line;
+        let count = self
+            .scanner
+            .literals
+            .iter()
+            .take_while(|(line, _)| *line <= end)
+            .count();
+        self.scanner
+            .literals
+            .drain(..count)
+            .map(|(_, text)| text)
+            .collect()
+    }
+
     fn program(&mut self) -> Program {
-        let TokenKind::Program = self.next().kind else {
+        let tok = self.next();
+        let line = tok.line;
+        let TokenKind::Program = tok.kind else {
             panic!("BUG: expected 'program'");
         };
 
@@ -101,14 +154,19 @@ impl<'src> Parser<'src> {
             "Expected ';' after program definition",
         );
 
-        Program { name, versions, id }
+        Program {
+            name,
+            versions,
+            id,
+            line,
+        }
     }
 
     fn procedures(&mut self) -> Vec<Procedure> {
         let mut procs = Vec::new();
 
         loop {
-            let _ret = match self.peek().kind {
+            let ret = match self.peek().kind {
                 TokenKind::RightBrace => break,
                 _ => self.procedure_type(),
             };
@@ -138,7 +196,7 @@ impl<'src> Parser<'src> {
             procs.push(Procedure {
                 name,
                 _arg,
-                _ret,
+                ret,
                 id,
             });
         }
@@ -164,10 +222,12 @@ impl<'src> Parser<'src> {
         let tok = self.next();
         let def = match &tok.kind {
             TokenKind::Const => self.const_definition(),
-            TokenKind::Typedef => Definition::TypeDef(self.type_def()),
+            TokenKind::Typedef => self.type_def(),
             TokenKind::Struct => {
                 let name = self.expect_identifier("Expected identifier in struct definition");
+                self.scope.push(name.clone());
                 let members = self.xdr_struct_body();
+                self.scope.pop();
                 Definition::Struct(XdrStruct { name, members })
             }
             TokenKind::Enum => {
@@ -188,11 +248,18 @@ impl<'src> Parser<'src> {
         def
     }
 
-    fn type_def(&mut self) -> XdrTypeDef {
+    /// A typedef, or, for a typedef of an anonymous type such as `typedef struct { ... } name;`,
+    /// the definition of that type under the typedef's name.
+    fn type_def(&mut self) -> Definition {
         let Declaration::Named(nd) = self.declaration() else {
             panic!("Unexpected void typedef");
         };
-        XdrTypeDef { decl: nd }
+        if let DeclarationKind::Scalar(XdrType::Name(name)) = &nd.kind {
+            if *name == nd.name && self.nested.last().map(Definition::get_name) == Some(name) {
+                return self.nested.pop().unwrap();
+            }
+        }
+        Definition::TypeDef(XdrTypeDef { decl: nd })
     }
 
     fn const_definition(&mut self) -> Definition {
@@ -267,12 +334,20 @@ impl<'src> Parser<'src> {
 
     fn xdr_union(&mut self) -> XdrUnion {
         let name = self.expect_identifier("Expected identifier in union definition");
+        self.scope.push(name.clone());
+        let union = self.xdr_union_body(name);
+        self.scope.pop();
+        union
+    }
+
+    /// The rest of a union called `name`, from 'switch' on.
+    fn xdr_union_body(&mut self, name: String) -> XdrUnion {
         self.expect(TokenKind::Switch, "Expected 'switch' after union name");
         self.expect(TokenKind::LeftParen, "Expected '(' after switch");
         let tok = self.next();
         let body = match &tok.kind {
-            TokenKind::Int => todo!("don't support int unions yet"),
-            TokenKind::Unsigned => {
+            // Integer-discriminated unions, whose cases are numbers or constants:
+            TokenKind::Int | TokenKind::Unsigned => {
                 if self.peek().kind == TokenKind::Int {
                     self.next();
                 };
@@ -296,18 +371,43 @@ impl<'src> Parser<'src> {
             }
             TokenKind::Bool => {
                 self.xdr_union_discriminant_remainder();
-                let (Declaration::Named(true_arm), Declaration::Void) = self.xdr_union_bool_body()
-                else {
-                    panic!(
-                        "xdr_union_bool_body: true arm must be named and false arm must be void!"
-                    );
-                };
-
-                XdrUnionBody::Bool(XdrUnionBoolBody { true_arm })
+                match self.xdr_union_bool_body() {
+                    (Declaration::Named(true_arm), Declaration::Void) => {
+                        XdrUnionBody::Bool(XdrUnionBoolBody { true_arm })
+                    }
+                    // Otherwise, the union is integer-discriminated, with cases called TRUE and
+                    // FALSE, which no constant can be called, since they are keywords:
+                    (true_arm, false_arm) => XdrUnionBody::Enum(XdrUnionEnumBody {
+                        discriminant: None,
+                        arms: vec![
+                            (Value::Name("TRUE".into()), true_arm),
+                            (Value::Name("FALSE".into()), false_arm),
+                        ],
+                        default_arm: None,
+                    }),
+                }
             }
-            // XXX: remove the "Enum" case?
             TokenKind::Enum => {
-                panic!("untested, probably unsupported");
+                // Either "enum identifier", a long form of "identifier", or an enum declared
+                // inline, which is named after the union and the discriminant:
+                let discriminant = if self.peek().kind == TokenKind::LeftBrace {
+                    let enum_name = format!("{name}_{}", self.declared_name());
+                    let variants = self.xdr_enum_body();
+                    self.nested.push(Definition::Enum(XdrEnum {
+                        name: enum_name.clone(),
+                        variants,
+                    }));
+                    enum_name
+                } else {
+                    self.expect_identifier("Expected identifier or '{' after 'enum'")
+                };
+                self.xdr_union_discriminant_remainder();
+                let (arms, default_arm) = self.xdr_union_enum_body();
+                XdrUnionBody::Enum(XdrUnionEnumBody {
+                    discriminant: Some(discriminant),
+                    arms,
+                    default_arm,
+                })
             }
             _ => Parser::error(
                 "Expected one of 'int', 'unsigned', 'enum', or an identifier to begin union",
@@ -383,26 +483,27 @@ impl<'src> Parser<'src> {
                 }
                 _ => {}
             }
-            let mut case_names = Vec::new();
-            loop {
-                let tok = self.peek();
-                match tok.kind {
-                    TokenKind::Case => {
-                        self.next();
-                        case_names.push(
-                            self.expect_identifier("Expected identifier after 'case' in union"),
-                        );
-                        self.expect(TokenKind::Colon, "Expected ':' after identifier in union");
-                    }
-                    _ => break,
-                }
+            let mut case_values = Vec::new();
+            while self.peek().kind == TokenKind::Case {
+                self.next();
+                let tok = self.next();
+                let value = match &tok.kind {
+                    TokenKind::Identifier(name) => Value::Name(name.to_string()),
+                    TokenKind::Number(n) => Value::Int(*n),
+                    _ => Parser::error(
+                        "Expected identifier or number after 'case' in union",
+                        Some(tok),
+                    ),
+                };
+                case_values.push(value);
+                self.expect(TokenKind::Colon, "Expected ':' after case value in union");
             }
-            if case_names.is_empty() {
+            if case_values.is_empty() {
                 Parser::error("union must have at least one case per arm", None);
             }
             let decl = self.declaration();
-            for name in case_names.into_iter() {
-                cases.push((Value::Name(name), decl.clone()));
+            for value in case_values.into_iter() {
+                cases.push((value, decl.clone()));
             }
             self.expect(
                 TokenKind::Semicolon,
@@ -426,6 +527,9 @@ impl<'src> Parser<'src> {
                 } else {
                     let tok = self.next();
                     let val = match &tok.kind {
+                        TokenKind::Number(n) if *n < 0 => {
+                            Parser::error("Array length must not be negative", Some(tok))
+                        }
                         TokenKind::Number(n) => Value::Int(*n),
                         TokenKind::Identifier(name) => Value::Name(name.to_string()),
                         _ => Parser::error("Expected number of identifier after '['", Some(tok)),
@@ -440,6 +544,9 @@ impl<'src> Parser<'src> {
             TokenKind::LessThan => {
                 let tok = self.next();
                 match &tok.kind {
+                    TokenKind::Number(n) if *n < 0 => {
+                        Parser::error("Array length must not be negative", Some(tok))
+                    }
                     TokenKind::Number(n) => {
                         let n = *n;
                         self.expect(
@@ -500,23 +607,55 @@ impl<'src> Parser<'src> {
             TokenKind::Double => XdrType::Double,
             TokenKind::Quadruple => XdrType::Quadruple,
             TokenKind::Bool => XdrType::Bool,
-            TokenKind::Struct => {
-                // Don't allow anonymous structs declared within outer structs, but do allow using
-                // "struct identifier" as a long form of "identifier":
-                let name = self.expect_identifier("Expected identifier after 'struct'");
-                XdrType::Name(name.to_string())
-            }
-            TokenKind::Enum => {
-                // Don't allow anonymous enum declared within outer structs, but do allow using
-                // "enum identifier" as a long form of "identifier":
-                let name = self.expect_identifier("Expected identifier after 'enum'");
-                XdrType::Name(name.to_string())
-            }
+            TokenKind::Struct => self.named_or_anonymous_type(TokenKind::Struct),
+            TokenKind::Union => self.named_or_anonymous_type(TokenKind::Union),
+            TokenKind::Enum => self.named_or_anonymous_type(TokenKind::Enum),
             TokenKind::Identifier(name) => XdrType::Name(name.to_string()),
             _ => Parser::error("Expected type specifier to begin declaration", Some(tok)),
         }
     }
 
+    /// After the keyword `struct`, `union`, or `enum`: either an identifier, since for example
+    /// "struct identifier" is a long form of "identifier", or the body of an anonymous type, which
+    /// is given a name (see `anonymous_name()`) and defined before the definition it is declared
+    /// in.
+    fn named_or_anonymous_type(&mut self, keyword: TokenKind) -> XdrType {
+        let anonymous = match keyword {
+            TokenKind::Union => self.peek().kind == TokenKind::Switch,
+            _ => self.peek().kind == TokenKind::LeftBrace,
+        };
+        if !anonymous {
+            let name = self.expect_identifier("Expected identifier or a body after type keyword");
+            return XdrType::Name(name);
+        }
+
+        let name = self.anonymous_name();
+        let definition = match keyword {
+            TokenKind::Struct => {
+                self.scope.push(name.clone());
+                let members = self.xdr_struct_body();
+                self.scope.pop();
+                Definition::Struct(XdrStruct {
+                    name: name.clone(),
+                    members,
+                })
+            }
+            TokenKind::Union => {
+                self.scope.push(name.clone());
+                let union = self.xdr_union_body(name.clone());
+                self.scope.pop();
+                Definition::Union(union)
+            }
+            _ => Definition::Enum(XdrEnum {
+                name: name.clone(),
+                variants: self.xdr_enum_body(),
+            }),
+        };
+        self.nested.push(definition);
+
+        XdrType::Name(name)
+    }
+
     fn declaration(&mut self) -> Declaration {
         match self.peek().kind {
             TokenKind::Void => {
@@ -563,6 +702,49 @@ impl<'src> Parser<'src> {
         }
     }
 
+    /// The name for an anonymous type whose body is about to be parsed: the name of the enclosing
+    /// type and of the member it is declared as, joined by '_', such as `entry_cookie` for the
+    /// struct in `struct entry { struct { ... } cookie; };`. An anonymous type that a typedef
+    /// declares is given the name of the typedef.
+    fn anonymous_name(&self) -> String {
+        let member = self.declared_name();
+        match self.scope.last() {
+            Some(outer) => format!("{outer}_{member}"),
+            None => member,
+        }
+    }
+
+    /// Look ahead, past the body of the type about to be parsed, for the name that it is declared
+    /// with, without any raw identifier prefix.
+    fn declared_name(&self) -> String {
+        let mut scanner = self.scanner.clone();
+        let mut depth = usize::from(self.next.kind == TokenKind::LeftBrace);
+        loop {
+            let tok = scanner.next();
+            match tok.kind {
+                TokenKind::LeftBrace | TokenKind::LeftParen => depth += 1,
+                TokenKind::RightParen => depth = depth.saturating_sub(1),
+                TokenKind::RightBrace => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                TokenKind::Eof => Parser::error("Expected '}' to end anonymous type", Some(&tok)),
+                _ => {}
+            }
+        }
+
+        let mut tok = scanner.next();
+        if tok.kind == TokenKind::Star {
+            tok = scanner.next();
+        }
+        match tok.kind {
+            TokenKind::Identifier(ref name) => name.strip_prefix("r#").unwrap_or(name).to_string(),
+            _ => Parser::error("Expected identifier after anonymous type", Some(&tok)),
+        }
+    }
+
     fn next(&mut self) -> &Token {
         self.current = std::mem::replace(&mut self.next, self.scanner.next());
         &self.current
@@ -588,7 +770,7 @@ impl<'src> Parser<'src> {
         s.to_string()
     }
 
-    fn expect_number(&mut self, msg: &str) -> u64 {
+    fn expect_number(&mut self, msg: &str) -> i128 {
         let actual = self.next();
         let TokenKind::Number(n) = actual.kind else {
             Parser::error(msg, Some(actual));
@@ -41,8 +41,10 @@ pub enum TokenKind {
     Void,
 
     Identifier(String),
-    // XXX: Not allowing negative constants...
-    Number(u64),
+    // The file named by an `%#include "file.x"` line:
+    Include(String),
+    // Wide enough for both the unsigned and the negative constants that XDR allows:
+    Number(i128),
 
     LeftBrace,
     RightBrace,
@@ -61,12 +63,16 @@ pub enum TokenKind {
     Eof,
 }
 
+#[derive(Clone)]
 pub struct Scanner<'src> {
     source: &'src str,
     chars: std::iter::Peekable<std::str::CharIndices<'src>>,
     start: usize,
     current: usize,
     line: usize,
+    /// The `%` lines passed so far, other than `%#include` lines, each with its line number and
+    /// the text after the `%`.
+    pub literals: Vec<(usize, String)>,
 }
 
 impl<'src> Scanner<'src> {
@@ -77,6 +83,7 @@ impl<'src> Scanner<'src> {
             start: 0,
             current: 0,
             line: 1,
+            literals: Vec::new(),
         }
     }
 
@@ -98,31 +105,14 @@ impl<'src> Scanner<'src> {
                 '*' => TokenKind::Star,
                 '=' => TokenKind::Equal,
                 ',' => TokenKind::Comma,
-                '-' => {
-                    unimplemented!("Negative numbers not currently supported")
-                }
-                // Octal or Hex number:
-                '0' => match self.chars.peek() {
-                    Some((i, 'x')) => {
-                        let i = *i;
-                        self.chars.next();
-                        self.chars.next();
-                        self.start = i + 1;
-                        self.number(16)
-                    }
+                '%' => self.include(i),
+                '-' => match self.chars.next() {
                     Some((i, ch)) if ch.is_numeric() => {
-                        let i = *i;
-                        self.chars.next();
-                        self.start = i;
-                        self.number(8)
+                        TokenKind::Number(-i128::from(self.unsigned_number(i, ch)))
                     }
-                    _ => TokenKind::Number(0),
+                    _ => panic!("Expected a number after '-' on line {}", self.line),
                 },
-                // Positive decimal number:
-                ch if ch.is_numeric() => {
-                    self.start = i;
-                    self.number(10)
-                }
+                ch if ch.is_numeric() => TokenKind::Number(self.unsigned_number(i, ch).into()),
                 ch if ch.is_alphabetic() => {
                     self.start = i;
                     self.keyword_or_identifier()
@@ -138,6 +128,27 @@ impl<'src> Scanner<'src> {
         }
     }
 
+    /// An `%#include` line, starting at index `i`, which names a file in quotes or angle brackets.
+    fn include(&mut self, i: usize) -> TokenKind {
+        let end = self.source[i..]
+            .find('\n')
+            .map_or(self.source.len(), |n| i + n);
+        let line = self.source[i..end].trim_end();
+        while self.chars.next_if(|(j, _)| *j < end).is_some() {}
+
+        let file = line["%#include".len()..].trim();
+        match file.strip_prefix('"').and_then(|f| f.strip_suffix('"')) {
+            Some(file) => TokenKind::Include(file.to_string()),
+            None => match file.strip_prefix('<').and_then(|f| f.strip_suffix('>')) {
+                Some(file) => TokenKind::Include(file.to_string()),
+                None => panic!(
+                    "Expected a file name after '%#include' on line {}",
+                    self.line
+                ),
+            },
+        }
+    }
+
     fn keyword_or_identifier(&mut self) -> TokenKind {
         self.current = self.start;
         while let Some((_, ch)) = self.chars.peek() {
@@ -190,7 +201,35 @@ impl<'src> Scanner<'src> {
         }
     }
 
-    fn number(&mut self, radix: u32) -> TokenKind {
+    /// Scan a decimal, octal, or hex number whose first digit, `ch`, is at index `i`.
+    fn unsigned_number(&mut self, i: usize, ch: char) -> u64 {
+        match ch {
+            // Octal or Hex number:
+            '0' => match self.chars.peek() {
+                Some((i, 'x')) => {
+                    let i = *i;
+                    self.chars.next();
+                    self.chars.next();
+                    self.start = i + 1;
+                    self.number(16)
+                }
+                Some((i, ch)) if ch.is_numeric() => {
+                    let i = *i;
+                    self.chars.next();
+                    self.start = i;
+                    self.number(8)
+                }
+                _ => 0,
+            },
+            // Decimal number:
+            _ => {
+                self.start = i;
+                self.number(10)
+            }
+        }
+    }
+
+    fn number(&mut self, radix: u32) -> u64 {
         self.current = self.start;
         loop {
             match self.chars.peek() {
@@ -205,10 +244,8 @@ impl<'src> Scanner<'src> {
             }
         }
         let num = &self.source[self.start..self.current];
-        let num: u64 = u64::from_str_radix(num, radix)
-            .unwrap_or_else(|_| panic!("Should be able to parse {num} as a number."));
-
-        TokenKind::Number(num)
+        u64::from_str_radix(num, radix)
+            .unwrap_or_else(|_| panic!("Should be able to parse {num} as a number."))
     }
 
     fn skip_whitespace_and_comments(&mut self) {
@@ -225,9 +262,8 @@ impl<'src> Scanner<'src> {
                     self.chars.next();
                     self.multiline_comment();
                 }
-                Some((_, '%')) => {
-                    self.chars.next();
-                    self.singleline_comment();
+                Some(&(i, '%')) if !self.source[i..].starts_with("%#include") => {
+                    self.literal_line(i);
                 }
                 _ => break,
             };
@@ -235,13 +271,15 @@ impl<'src> Scanner<'src> {
     }
 
     /// While I don't see single line comments explained in the XDR spec anywhere, the NFS v4.1 and
-    /// 4.2 specs appear to treat lines starting with '%' as comments.
-    fn singleline_comment(&mut self) {
-        loop {
-            if let Some((_, '\n')) = self.chars.next() {
-                break;
-            }
-        }
+    /// 4.2 specs appear to treat lines starting with '%' as comments. rpcgen copies them into the C
+    /// it generates, so they are kept, in `literals`, for the parser to attach to definitions.
+    fn literal_line(&mut self, i: usize) {
+        let end = self.source[i..]
+            .find('\n')
+            .map_or(self.source.len(), |n| i + n);
+        let text = self.source[i + 1..end].trim_end();
+        self.literals.push((self.line, text.to_string()));
+        while self.chars.next_if(|(j, _)| *j <= end).is_some() {}
         self.line += 1;
     }
 
@@ -307,6 +345,30 @@ mod tests {
         assert_eq!(scanner.next().kind, TokenKind::Eof);
     }
 
+    #[test]
+    fn includes() {
+        let mut scanner = Scanner::new(
+            "%#include \"types.x\"\n% a comment\n%#include <other.x> \n{\n%#include \"last.x\"",
+        );
+        assert_eq!(scanner.next().kind, TokenKind::Include("types.x".into()));
+        assert_eq!(scanner.next().kind, TokenKind::Include("other.x".into()));
+        let brace = scanner.next();
+        assert_eq!((brace.kind, brace.line), (TokenKind::LeftBrace, 4));
+        assert_eq!(scanner.next().kind, TokenKind::Include("last.x".into()));
+        assert_eq!(scanner.next().kind, TokenKind::Eof);
+    }
+
+    #[test]
+    fn literal_lines() {
+        let mut scanner = Scanner::new("%#define A 1\n{\n  %   spaced  \r\n}\n%\n%last");
+        assert_eq!(scanner.next().kind, TokenKind::LeftBrace);
+        assert_eq!(scanner.next().kind, TokenKind::RightBrace);
+        assert_eq!(scanner.next().kind, TokenKind::Eof);
+        let literals = [(1, "#define A 1"), (3, "   spaced"), (5, ""), (6, "last")];
+        let literals = literals.map(|(line, text)| (line, text.to_string()));
+        assert_eq!(scanner.literals, literals);
+    }
+
     #[test]
     fn numbers() {
         let mut scanner = Scanner::new(
@@ -333,6 +395,19 @@ mod tests {
         assert_eq!(scanner.next().kind, TokenKind::Eof);
     }
 
+    #[test]
+    fn negative_numbers() {
+        let mut scanner = Scanner::new("-1 -2147483648 -0x10 -010 -0 =-3");
+        assert_eq!(scanner.next().kind, TokenKind::Number(-1));
+        assert_eq!(scanner.next().kind, TokenKind::Number(-2147483648));
+        assert_eq!(scanner.next().kind, TokenKind::Number(-16));
+        assert_eq!(scanner.next().kind, TokenKind::Number(-8));
+        assert_eq!(scanner.next().kind, TokenKind::Number(0));
+        assert_eq!(scanner.next().kind, TokenKind::Equal);
+        assert_eq!(scanner.next().kind, TokenKind::Number(-3));
+        assert_eq!(scanner.next().kind, TokenKind::Eof);
+    }
+
     #[test]
     fn keywords() {
         let mut scanner = Scanner::new(
@@ -38,6 +38,18 @@ impl ValidatedSymbolTable {
             .unwrap_or_else(|_| panic!("Could not find name \"{name}\""))
     }
 
+    /// Resolves a name to the value of the constant it names, following references from one
+    /// constant to another.
+    pub fn lookup_constant(&self, name: &str) -> Result<i128, XdrError> {
+        match self.lookup_definition_fallible(name)? {
+            ValidatedDefinition::Const(c) => match &c.value {
+                Value::Int(i) => Ok(*i),
+                Value::Name(other) => self.lookup_constant(other),
+            },
+            _ => Err(XdrError::NotAConstant(name.to_string())),
+        }
+    }
+
     pub fn lookup_size(&self, name: &str) -> &DefinitionSize {
         self.size_tab
             .get(name)
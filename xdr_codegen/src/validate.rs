@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: BSD-3-Clause
 // Copyright 2025. Triad National Security, LLC.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use crate::{ast::*, ir::*, symbol_table::*, XdrError};
 
@@ -16,6 +16,13 @@ pub struct ValidatedSchema {
 
     pub programs: Vec<Program>,
     pub contains_string: bool,
+
+    /// The modules generated from included files, whose definitions are in the `symbol_table` but
+    /// not the `definition_list`.
+    pub imports: Vec<String>,
+
+    /// The `%` lines of the source, by the name of the definition or program they belong to.
+    pub literals: HashMap<String, Vec<String>>,
 }
 
 impl ValidatedDefinition {
@@ -74,11 +81,15 @@ impl ValidatedSchema {
     /// Validate a schema, eventually ensuring that it doesn't have any errors that would prevent
     /// succesful code generation.
     ///
-    /// (For now, it only checks some errors, so finding errors during codegen is still possible.)
+    /// The names that the schema defines and refers to are checked first (see
+    /// `Schema::check_names()`), so that later lookups in the symbol table can't fail. (For now,
+    /// only some other errors are checked, so finding errors during codegen is still possible.)
     pub fn validate(mut schema: Schema) -> crate::Result<ValidatedSchema> {
+        schema.check_names()?;
+
         let mut validated_symbol_table = ValidatedSymbolTable::new_empty();
         let mut definition_list = Vec::new();
-        for definition in schema.definitions.drain(..) {
+        for (i, definition) in schema.definitions.drain(..).enumerate() {
             let definition_name = definition.get_name().to_string();
             let validated_definition = definition.validate(&validated_symbol_table)?;
 
@@ -87,7 +98,9 @@ impl ValidatedSchema {
             validated_symbol_table
                 .tab
                 .insert(definition_name.clone(), validated_definition);
-            definition_list.push(definition_name.clone());
+            if i >= schema.imported {
+                definition_list.push(definition_name.clone());
+            }
             validated_symbol_table
                 .size_tab
                 .insert(definition_name, size);
@@ -98,22 +111,152 @@ impl ValidatedSchema {
             definition_list,
             programs: schema.programs,
             contains_string: schema.contains_string,
+            imports: schema.imports,
+            literals: schema.literals,
         })
     }
 }
 
+impl Schema {
+    /// Check, before any definition is validated, that no name is defined twice, that every name
+    /// referred to is defined, and that no typedef is defined in terms of itself.
+    fn check_names(&self) -> crate::Result<()> {
+        // Each name, with the index and line of its definition:
+        let mut defined: HashMap<&str, (usize, usize)> = HashMap::new();
+        for (i, (definition, &line)) in self.definitions.iter().zip(&self.lines).enumerate() {
+            let name = definition.get_name();
+            if let Some(&(_, first)) = defined.get(name) {
+                return Err(XdrError::DuplicateDefinition {
+                    name: name.to_string(),
+                    line,
+                    first,
+                });
+            }
+            defined.insert(name, (i, line));
+        }
+
+        let undefined = |name: &str, referrer: &str, line| XdrError::UndefinedReference {
+            name: name.to_string(),
+            referrer: referrer.to_string(),
+            line,
+        };
+        for (definition, &line) in self.definitions.iter().zip(&self.lines) {
+            for name in definition.references() {
+                if !defined.contains_key(name) {
+                    return Err(undefined(name, definition.get_name(), line));
+                }
+            }
+        }
+        for program in self.programs.iter() {
+            for name in program.references() {
+                if !defined.contains_key(name) {
+                    return Err(undefined(name, &program.name, program.line));
+                }
+            }
+        }
+
+        for (definition, &line) in self.definitions.iter().zip(&self.lines) {
+            if let Some(names) = self.typedef_cycle(definition, &defined) {
+                return Err(XdrError::CyclicTypedef { names, line });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// If `definition` is a typedef that is, through other typedefs, defined in terms of itself,
+    /// return the names of the typedefs, from `definition` back to it.
+    fn typedef_cycle(
+        &self,
+        definition: &Definition,
+        defined: &HashMap<&str, (usize, usize)>,
+    ) -> Option<Vec<String>> {
+        let Definition::TypeDef(start) = definition else {
+            return None;
+        };
+        let mut names = vec![start.decl.name.clone()];
+        let mut typedef = start;
+        while let Some(name) = typedef.decl.aliased_name() {
+            if name == start.decl.name {
+                names.push(name.to_string());
+                return Some(names);
+            }
+            if names.iter().any(|n| n == name) {
+                // A cycle that `definition` leads into, but is not part of.
+                return None;
+            }
+            let Definition::TypeDef(next) = &self.definitions[defined[name].0] else {
+                return None;
+            };
+            names.push(name.to_string());
+            typedef = next;
+        }
+
+        None
+    }
+}
+
+impl Program {
+    fn references(&self) -> Vec<&str> {
+        let mut references = Vec::new();
+        for procedure in self.versions.iter().flat_map(|v| &v.procedures) {
+            for ty in [&procedure._arg, &procedure.ret] {
+                if let ProcedureType::Ty(XdrType::Name(name)) = ty {
+                    references.push(name.as_str());
+                }
+            }
+        }
+
+        references
+    }
+}
+
 impl Definition {
+    /// The names that the definition refers to: types, constants, and enum discriminants. The cases
+    /// of a union are not included, as they are checked when it is validated.
+    fn references(&self) -> Vec<&str> {
+        let mut references = Vec::new();
+        match self {
+            Definition::Const(c) => c.value.references(&mut references),
+            Definition::TypeDef(td) => td.decl.references(&mut references),
+            Definition::Struct(s) => {
+                for member in s.members.iter() {
+                    member.references(&mut references);
+                }
+            }
+            Definition::Enum(e) => {
+                for (_, value) in e.variants.iter() {
+                    value.references(&mut references);
+                }
+            }
+            Definition::Union(u) => match &u.body {
+                XdrUnionBody::Bool(body) => body.true_arm.references(&mut references),
+                XdrUnionBody::Enum(body) => {
+                    if let Some(name) = &body.discriminant {
+                        references.push(name.as_str());
+                    }
+                    let arms = body.arms.iter().map(|(_, decl)| decl);
+                    for decl in arms.chain(&body.default_arm) {
+                        if let Declaration::Named(decl) = decl {
+                            decl.references(&mut references);
+                        }
+                    }
+                }
+            },
+        }
+
+        references
+    }
+
     fn validate(self, tab: &ValidatedSymbolTable) -> crate::Result<ValidatedDefinition> {
         let ret = match self {
-            Definition::Const(cdef) => match cdef.value {
-                Value::Int(_) => ValidatedDefinition::Const(ConstDefinition {
-                    name: cdef.name,
-                    value: cdef.value,
-                }),
-                Value::Name(_) => {
-                    return Err(XdrError::InvalidConstantDefinition(cdef.name));
+            Definition::Const(cdef) => {
+                // A constant may be defined as another constant, which must already be defined:
+                if let Value::Name(name) = &cdef.value {
+                    tab.lookup_constant(name)?;
                 }
-            },
+                ValidatedDefinition::Const(cdef)
+            }
             Definition::TypeDef(td) => ValidatedDefinition::TypeDef(td),
             Definition::Struct(s) => ValidatedDefinition::Struct(s.validate(tab)?),
             Definition::Enum(e) => ValidatedDefinition::Enum(ValidatedEnum {
@@ -161,20 +304,13 @@ impl Array {
         match &self.size {
             ArraySize::Fixed(value) => {
                 let count = match value {
-                    Value::Int(val) => *val as usize,
-                    Value::Name(name) => {
-                        let constval = tab.lookup_definition(name);
-                        if let ValidatedDefinition::Const(constval) = constval {
-                            if let Value::Int(intval) = constval.value {
-                                intval as usize
-                            } else {
-                                panic!("constant \"{name}\" passed to array is not immediately an integer");
-                            }
-                        } else {
-                            panic!("definition for value passed as array length specifier \"{name}\" is not a constant");
-                        }
-                    }
+                    Value::Int(val) => *val,
+                    Value::Name(name) => tab
+                        .lookup_constant(name)
+                        .unwrap_or_else(|_| panic!("array length \"{name}\" is not a constant")),
                 };
+                let count = usize::try_from(count)
+                    .unwrap_or_else(|_| panic!("invalid array length {count}"));
 
                 let single_width = match &self.kind {
                     ArrayKind::Byte | ArrayKind::Ascii => Some(1_usize),
@@ -205,7 +341,43 @@ impl Declaration {
     }
 }
 
+impl Value {
+    fn references<'a>(&'a self, references: &mut Vec<&'a str>) {
+        if let Value::Name(name) = self {
+            references.push(name.as_str());
+        }
+    }
+}
+
 impl NamedDeclaration {
+    fn references<'a>(&'a self, references: &mut Vec<&'a str>) {
+        match &self.kind {
+            DeclarationKind::Scalar(XdrType::Name(name)) => references.push(name.as_str()),
+            DeclarationKind::Optional(XdrType::Name(name)) => references.push(name.as_str()),
+            DeclarationKind::Array(array) => {
+                if let ArrayKind::UserType(XdrType::Name(name)) = &array.kind {
+                    references.push(name.as_str());
+                }
+                if let ArraySize::Fixed(size) | ArraySize::Limited(size) = &array.size {
+                    size.references(references);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The name of the type that a typedef with this declaration is an alias, or an array, of.
+    fn aliased_name(&self) -> Option<&str> {
+        match &self.kind {
+            DeclarationKind::Scalar(XdrType::Name(name)) => Some(name),
+            DeclarationKind::Array(Array {
+                kind: ArrayKind::UserType(XdrType::Name(name)),
+                ..
+            }) => Some(name),
+            _ => None,
+        }
+    }
+
     pub fn size(&self, tab: &ValidatedSymbolTable) -> Option<usize> {
         match &self.kind {
             DeclarationKind::Scalar(xdr_type) => xdr_type.size(tab),
@@ -304,13 +476,14 @@ impl XdrUnionBoolBody {
 }
 
 impl XdrUnionEnumBody {
-    fn validate(self, u_name: String, tab: &ValidatedSymbolTable) -> ValidatedDefinition {
-        let mut arms_iter = self.arms.iter();
-
-        let Some(discriminant_name) = &self.discriminant else {
-            todo!("we do not currently support void discriminant unions")
-        };
-
+    /// Check that each case of a union discriminated by the enum `discriminant_name` is a distinct
+    /// variant of it. Returns the variants without a case.
+    fn check_enum_cases(
+        &self,
+        u_name: &str,
+        discriminant_name: &str,
+        tab: &ValidatedSymbolTable,
+    ) -> HashSet<String> {
         let discriminant = tab.lookup_definition(discriminant_name);
         let all_possible: HashSet<String> = match discriminant {
             ValidatedDefinition::Enum(xdr_enum) => xdr_enum
@@ -350,9 +523,65 @@ impl XdrUnionEnumBody {
             }
         }
 
+        left
+    }
+
+    /// Check that each case of an integer-discriminated union is a number, a constant, or TRUE or
+    /// FALSE (see `Parser::xdr_union_body()`), and that no two of them are equal.
+    fn check_integer_cases(&self, u_name: &str, tab: &ValidatedSymbolTable) {
+        let mut seen = HashSet::new();
+        for (val, _decl) in self.arms.iter() {
+            let value = match val {
+                Value::Int(i) => *i,
+                Value::Name(name) if name == "TRUE" => 1,
+                Value::Name(name) if name == "FALSE" => 0,
+                Value::Name(name) => tab
+                    .lookup_constant(name)
+                    .unwrap_or_else(|_| panic!("{u_name}: case {name} is not a constant")),
+            };
+            // Discriminants are decoded as ints, whatever their type:
+            if i32::try_from(value).is_err() {
+                panic!("{u_name}: case {value} does not fit in an int");
+            }
+            if !seen.insert(value) {
+                panic!("{u_name}: case {value} seems to be a duplicate case");
+            }
+        }
+    }
+}
+
+impl XdrUnionEnumBody {
+    fn validate(mut self, u_name: String, tab: &ValidatedSymbolTable) -> ValidatedDefinition {
+        // A discriminant that is a typedef of an integer type makes an integer-discriminated union:
+        if let Some(name) = &self.discriminant {
+            if let ValidatedDefinition::TypeDef(XdrTypeDef {
+                decl:
+                    NamedDeclaration {
+                        kind: DeclarationKind::Scalar(XdrType::Int | XdrType::UInt),
+                        ..
+                    },
+            }) = tab.lookup_definition(name)
+            {
+                self.discriminant = None;
+            }
+        }
+
+        let mut arms_iter = self.arms.iter();
+
+        let all_covered = match &self.discriminant {
+            Some(discriminant_name) => self
+                .check_enum_cases(&u_name, discriminant_name, tab)
+                .is_empty(),
+            None => {
+                self.check_integer_cases(&u_name, tab);
+                // The cases of an integer-discriminated union never cover every value:
+                false
+            }
+        };
+
         // if all the enum cases are covered by the match arms, we can elide the
         // default case
-        let default_arm = if !left.is_empty() {
+        let default_arm = if !all_covered {
             self.default_arm.as_ref()
         } else {
             None
@@ -474,6 +703,91 @@ mod tests {
         assert!(try_validate("struct foo { int a; foo *next; };").is_ok());
     }
 
+    #[test]
+    fn constant_references() {
+        let schema = try_validate("const A = 0x10; const B = A; const C = B;").unwrap();
+        assert_eq!(schema.symbol_table.lookup_constant("C").unwrap(), 16);
+
+        let res = try_validate("const A = B;").unwrap_err();
+        assert!(matches!(res, XdrError::UndefinedReference { .. }));
+
+        let res = try_validate("struct foo { int a; }; const A = foo;").unwrap_err();
+        assert!(matches!(res, XdrError::NotAConstant(_)));
+    }
+
+    #[test]
+    fn duplicate_definitions() {
+        let xdr = "const A = 1;\nstruct B { int a; };\n\nenum A { X = 0 };";
+        let res = try_validate(xdr).unwrap_err();
+        let XdrError::DuplicateDefinition { name, line, first } = res else {
+            panic!("{res:?}");
+        };
+        assert_eq!((name.as_str(), line, first), ("A", 4, 1));
+
+        // Anonymous types are named after their definitions, and may collide with them too:
+        let xdr = "struct A { int a; };\nstruct B { struct { int b; } a; };\ntypedef int B_a;";
+        let res = try_validate(xdr).unwrap_err();
+        assert!(matches!(
+            res,
+            XdrError::DuplicateDefinition {
+                line: 3,
+                first: 2,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn undefined_references() {
+        let xdr = "struct A { int a; };\n\nunion B switch (bool yes) {\ncase TRUE: C c;\ncase FALSE: void;\n};";
+        let res = try_validate(xdr).unwrap_err();
+        let XdrError::UndefinedReference {
+            name,
+            referrer,
+            line,
+        } = res
+        else {
+            panic!("{res:?}");
+        };
+        assert_eq!((name.as_str(), referrer.as_str(), line), ("C", "B", 3));
+
+        for xdr in [
+            "typedef opaque A[LEN];",
+            "struct A { B *next; };",
+            "union A switch (Kind k) { case one: void; };",
+            "enum A { X = Y };",
+            "struct A { int a; };\nprogram P { version V { A PROC(B) = 1; } = 1; } = 1;",
+        ] {
+            let res = try_validate(xdr).unwrap_err();
+            assert!(
+                matches!(res, XdrError::UndefinedReference { .. }),
+                "{xdr}: {res:?}"
+            );
+        }
+
+        // Types may be referred to before their definitions:
+        assert!(
+            try_validate("typedef struct node *list;\nstruct node { int a; list next; };").is_ok()
+        );
+    }
+
+    #[test]
+    fn cyclic_typedefs() {
+        let res = try_validate("typedef int A;\ntypedef C B;\ntypedef B C[2];").unwrap_err();
+        let XdrError::CyclicTypedef { names, line } = res else {
+            panic!("{res:?}");
+        };
+        assert_eq!(names, ["B", "C", "B"]);
+        assert_eq!(line, 2);
+
+        // A typedef that leads into a cycle is not part of it:
+        let res = try_validate("typedef B A;\ntypedef C B;\ntypedef B C;").unwrap_err();
+        assert!(matches!(res, XdrError::CyclicTypedef { line: 2, .. }));
+
+        // Optionals are not aliases, so may refer back to the typedef:
+        assert!(try_validate("typedef struct node *list;\nstruct node { list next; };").is_ok());
+    }
+
     #[test]
     fn deterministic_struct() {
         let xdr = r#"
@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+// Golden tests of the generated code: each .x file in `tests/golden` is compiled with each set of
+// options below, and the code must be exactly that checked in under `tests/golden/<options>`, so
+// that a change to the code generator shows up as a diff of the code it generates.
+//
+// After a change that is meant to alter the generated code, write the new code with
+// `XDR_CODEGEN_BLESS=1 cargo test -p xdr_codegen --test golden`, and review its diff.
+
+use std::{fs, path::Path};
+
+use xdr_codegen::Compiler;
+
+/// Sets the options of a `Compiler`.
+type Configure = fn(&mut Compiler);
+
+/// The sets of options, each named after the directory its generated code is checked in to.
+const OPTIONS: &[(&str, Configure)] = &[
+    ("alloc", |c| {
+        c.enable_deserialize_from();
+    }),
+    ("no_alloc", |c| {
+        c.enable_no_alloc().disable_alloc();
+    }),
+    ("zcopy", |c| {
+        c.enable_zcopy();
+    }),
+    ("no_std", |c| {
+        c.no_std();
+    }),
+    ("display", |c| {
+        c.enable_display()
+            .display_timestamp("Time")
+            .display_size("Size");
+    }),
+    ("derives", |c| {
+        c.derive_serde().derive("PartialOrd").literal_comments();
+    }),
+];
+
+#[test]
+fn generated_code_is_unchanged() {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let bless = std::env::var_os("XDR_CODEGEN_BLESS").is_some();
+
+    let mut inputs: Vec<_> = fs::read_dir(&golden)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "x"))
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty());
+
+    let mut failures = Vec::new();
+    for (name, configure) in OPTIONS {
+        let out_dir = golden.join(name);
+        fs::create_dir_all(&out_dir).unwrap();
+
+        let mut compiler = Compiler::new();
+        for input in inputs.iter() {
+            compiler.file(input);
+        }
+        compiler.out_dir(&out_dir);
+        configure(&mut compiler);
+
+        let res = if bless {
+            compiler.run()
+        } else {
+            compiler.check()
+        };
+        if let Err(e) = res {
+            failures.push(format!("With the {name} options: {e}"));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{}\nIf the changes are intended, bless them with XDR_CODEGEN_BLESS=1.",
+        failures.join("\n")
+    );
+}
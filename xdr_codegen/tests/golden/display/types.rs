@@ -0,0 +1,727 @@
+#[allow(non_camel_case_types, non_snake_case, unused_assignments, clippy::all)]
+pub mod types {
+    use std::os::unix::ffi::OsStrExt;
+    pub const MAX_NAME: u32 = 255;
+    pub const HANDLE_SIZE: u32 = 8;
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub enum Kind {
+        Regular,
+        Directory,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub enum Status {
+        Ok,
+        NotFound,
+        Denied,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub struct Time {
+        pub seconds: i64,
+        pub nseconds: u32,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+    pub struct Attributes {
+        pub kind: Kind,
+        pub size: u64,
+        pub mtime: Time,
+        pub hidden: bool,
+        pub ids: [i32; 2],
+        /// Max length: 16
+        pub groups: Vec<u32>,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+    pub struct Entry {
+        pub handle: [u8; 8],
+        /// Max length: 255
+        pub name: std::ffi::OsString,
+        pub cookie: Vec<u8>,
+        pub attributes: Option<Attributes>,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+    pub struct Listing {
+        pub entries: Vec<Entry>,
+        pub eof: bool,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub struct MaybeSize {
+        pub inner: Option<u64>,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+    pub enum ListResult {
+        Ok(Listing),
+        Default(MaybeSize),
+    }
+    impl Default for Kind {
+        fn default() -> Self {
+            Kind::Regular
+        }
+    }
+    impl Kind {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let val: i32 = match self {
+                Kind::Regular => 1,
+                Kind::Directory => 2,
+            };
+            val.to_be_bytes().to_vec()
+        }
+        pub fn deserialize(&mut self, input: &mut &[u8]) -> xdr_lib::Result<()> {
+            let mut val = 0;
+            xdr_lib::get_i32(&mut val, input)?;
+            *self = match val {
+                1 => Kind::Regular,
+                2 => Kind::Directory,
+                _ => return Err(xdr_lib::DeserializeError),
+            };
+            Ok(())
+        }
+        pub fn serialized_size(&self) -> usize {
+            4
+        }
+    }
+    impl xdr_lib::Decode for Kind {
+        fn decode(input: &mut &[u8]) -> xdr_lib::Result<Self> {
+            let mut value = Self::default();
+            value.deserialize(input)?;
+            Ok(value)
+        }
+    }
+    impl xdr_lib::Encode for Kind {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl std::fmt::Display for Kind {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str(
+                match self {
+                    Kind::Regular => "Regular",
+                    Kind::Directory => "Directory",
+                },
+            )
+        }
+    }
+    impl Default for Status {
+        fn default() -> Self {
+            Status::Ok
+        }
+    }
+    impl Status {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let val: i32 = match self {
+                Status::Ok => 0,
+                Status::NotFound => 2,
+                Status::Denied => 13,
+            };
+            val.to_be_bytes().to_vec()
+        }
+        pub fn deserialize(&mut self, input: &mut &[u8]) -> xdr_lib::Result<()> {
+            let mut val = 0;
+            xdr_lib::get_i32(&mut val, input)?;
+            *self = match val {
+                0 => Status::Ok,
+                2 => Status::NotFound,
+                13 => Status::Denied,
+                _ => return Err(xdr_lib::DeserializeError),
+            };
+            Ok(())
+        }
+        pub fn serialized_size(&self) -> usize {
+            4
+        }
+    }
+    impl xdr_lib::Decode for Status {
+        fn decode(input: &mut &[u8]) -> xdr_lib::Result<Self> {
+            let mut value = Self::default();
+            value.deserialize(input)?;
+            Ok(value)
+        }
+    }
+    impl xdr_lib::Encode for Status {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl std::fmt::Display for Status {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str(
+                match self {
+                    Status::Ok => "Ok",
+                    Status::NotFound => "NotFound",
+                    Status::Denied => "Denied",
+                },
+            )
+        }
+    }
+    impl Default for Time {
+        fn default() -> Self {
+            Time { seconds: 0, nseconds: 0 }
+        }
+    }
+    impl Time {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let mut buf = Vec::new();
+            let bytes = self.seconds.to_be_bytes();
+            buf.extend_from_slice(&bytes);
+            let bytes = self.nseconds.to_be_bytes();
+            buf.extend_from_slice(&bytes);
+            buf
+        }
+        pub fn deserialize(&mut self, input: &mut &[u8]) -> xdr_lib::Result<()> {
+            xdr_lib::get_i64(&mut self.seconds, input)?;
+            xdr_lib::get_u32(&mut self.nseconds, input)?;
+            Ok(())
+        }
+        pub fn get_width(&self) -> usize {
+            12
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl xdr_lib::Decode for Time {
+        fn decode(input: &mut &[u8]) -> xdr_lib::Result<Self> {
+            let mut value = Self::default();
+            value.deserialize(input)?;
+            Ok(value)
+        }
+    }
+    impl xdr_lib::Encode for Time {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl std::fmt::Display for Time {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(
+                f, "{}", xdr_lib::display::Timestamp::new(self.seconds, self.nseconds)
+            )
+        }
+    }
+    impl Default for Attributes {
+        fn default() -> Self {
+            Attributes {
+                kind: Kind::default(),
+                size: 0,
+                mtime: Time::default(),
+                hidden: false,
+                ids: {
+                    let arr: [i32; 2] = ::core::array::from_fn(|_| { 0 });
+                    arr
+                },
+                groups: Vec::new(),
+            }
+        }
+    }
+    impl Attributes {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let mut buf = Vec::new();
+            let bytes = self.kind.serialize_alloc();
+            buf.extend_from_slice(&bytes);
+            let bytes = self.size.to_be_bytes();
+            buf.extend_from_slice(&bytes);
+            let bytes = self.mtime.serialize_alloc();
+            buf.extend_from_slice(&bytes);
+            let bytes = xdr_lib::serialize_bool(&self.hidden);
+            buf.extend_from_slice(&bytes);
+            for item in self.ids.iter() {
+                let bytes = item.to_be_bytes();
+                buf.extend_from_slice(&bytes);
+            }
+            buf.extend_from_slice(&(self.groups.len() as u32).to_be_bytes());
+            for item in self.groups.iter() {
+                let bytes = item.to_be_bytes();
+                buf.extend_from_slice(&bytes);
+            }
+            buf
+        }
+        pub fn deserialize(&mut self, input: &mut &[u8]) -> xdr_lib::Result<()> {
+            Kind::deserialize(&mut self.kind, input)?;
+            xdr_lib::get_u64(&mut self.size, input)?;
+            Time::deserialize(&mut self.mtime, input)?;
+            xdr_lib::get_bool(&mut self.hidden, input)?;
+            let len = self.ids.len();
+            for _i in 0..len {
+                let mut new = 0;
+                xdr_lib::get_i32(&mut new, input)?;
+                self.ids[_i] = new;
+            }
+            let mut len = 0;
+            xdr_lib::get_u32(&mut len, input)?;
+            if len > 16 {
+                return Err(xdr_lib::DeserializeError);
+            }
+            for _i in 0..len {
+                let mut new = 0;
+                xdr_lib::get_u32(&mut new, input)?;
+                self.groups.push(new);
+            }
+            Ok(())
+        }
+        fn get_groups_width(&self) -> usize {
+            4usize + xdr_lib::padded_4byte(self.groups.len() * 4)
+        }
+        pub fn get_width(&self) -> usize {
+            self.get_groups_width() + 36
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl xdr_lib::Decode for Attributes {
+        fn decode(input: &mut &[u8]) -> xdr_lib::Result<Self> {
+            let mut value = Self::default();
+            value.deserialize(input)?;
+            Ok(value)
+        }
+    }
+    impl xdr_lib::Encode for Attributes {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl std::fmt::Display for Attributes {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "Attributes {{ ")?;
+            write!(f, "kind: ")?;
+            write!(f, "{}", self.kind)?;
+            write!(f, ", size: ")?;
+            write!(f, "{}", xdr_lib::display::Size::of(& self.size))?;
+            write!(f, ", mtime: ")?;
+            write!(f, "{}", self.mtime)?;
+            write!(f, ", hidden: ")?;
+            write!(f, "{}", self.hidden)?;
+            write!(f, ", ids: ")?;
+            write!(f, "[")?;
+            for (i, item) in self.ids.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", item)?;
+            }
+            write!(f, "]")?;
+            write!(f, ", groups: ")?;
+            write!(f, "[")?;
+            for (i, item) in self.groups.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", item)?;
+            }
+            write!(f, "]")?;
+            write!(f, " }}")
+        }
+    }
+    impl Default for Entry {
+        fn default() -> Self {
+            Entry {
+                handle: {
+                    let arr: [u8; 8] = ::core::array::from_fn(|_| { 0_u8 });
+                    arr
+                },
+                name: std::ffi::OsString::new(),
+                cookie: Vec::new(),
+                attributes: None,
+            }
+        }
+    }
+    impl Entry {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let mut buf = Vec::new();
+            buf.extend_from_slice(&self.handle);
+            let padding = (4 - self.handle.len() % 4) % 4;
+            buf.extend_from_slice(&vec![0; padding]);
+            buf.extend_from_slice(&(self.name.len() as u32).to_be_bytes());
+            buf.extend_from_slice(self.name.as_bytes());
+            let padding = (4 - self.name.len() % 4) % 4;
+            buf.extend_from_slice(&vec![0; padding]);
+            buf.extend_from_slice(&(self.cookie.len() as u32).to_be_bytes());
+            buf.extend_from_slice(&self.cookie);
+            let padding = (4 - self.cookie.len() % 4) % 4;
+            buf.extend_from_slice(&vec![0; padding]);
+            match &self.attributes {
+                Some(inner) => {
+                    buf.extend_from_slice(&1_i32.to_be_bytes());
+                    let bytes = inner.serialize_alloc();
+                    buf.extend_from_slice(&bytes);
+                }
+                None => buf.extend_from_slice(&0_i32.to_be_bytes()),
+            };
+            buf
+        }
+        pub fn deserialize(&mut self, input: &mut &[u8]) -> xdr_lib::Result<()> {
+            let len = self.handle.len();
+            let bytes = xdr_lib::get_opaque(len as usize, input)?;
+            self.handle.clone_from_slice(bytes);
+            let mut len = 0;
+            xdr_lib::get_u32(&mut len, input)?;
+            if len > 255 {
+                return Err(xdr_lib::DeserializeError);
+            }
+            let bytes = xdr_lib::get_opaque(len as usize, input)?;
+            self.name.push(std::ffi::OsStr::from_bytes(bytes));
+            let mut len = 0;
+            xdr_lib::get_u32(&mut len, input)?;
+            let bytes = xdr_lib::get_opaque(len as usize, input)?;
+            self.cookie.extend_from_slice(bytes);
+            let mut optional_follows = 0;
+            xdr_lib::get_i32(&mut optional_follows, input)?;
+            self.attributes = match optional_follows {
+                0 => None,
+                _ => {
+                    let mut new = Attributes::default();
+                    Attributes::deserialize(&mut new, input)?;
+                    Some(new)
+                }
+            };
+            Ok(())
+        }
+        fn get_name_width(&self) -> usize {
+            4usize + xdr_lib::padded_4byte(self.name.len())
+        }
+        fn get_cookie_width(&self) -> usize {
+            4usize + xdr_lib::padded_4byte(self.cookie.len())
+        }
+        fn get_attributes_width(&self) -> usize {
+            4usize
+                + match &self.attributes {
+                    None => 0,
+                    Some(_val) => _val.get_width(),
+                }
+        }
+        pub fn get_width(&self) -> usize {
+            self.get_name_width() + self.get_cookie_width() + self.get_attributes_width()
+                + 8
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl xdr_lib::Decode for Entry {
+        fn decode(input: &mut &[u8]) -> xdr_lib::Result<Self> {
+            let mut value = Self::default();
+            value.deserialize(input)?;
+            Ok(value)
+        }
+    }
+    impl xdr_lib::Encode for Entry {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl std::fmt::Display for Entry {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "Entry {{ ")?;
+            write!(f, "handle: ")?;
+            write!(f, "{}", xdr_lib::display::Hex(& self.handle[..]))?;
+            write!(f, ", name: ")?;
+            write!(f, "{:?}", self.name)?;
+            write!(f, ", cookie: ")?;
+            write!(f, "{}", xdr_lib::display::Hex(& self.cookie[..]))?;
+            write!(f, ", attributes: ")?;
+            match &self.attributes {
+                Some(value) => {
+                    write!(f, "{}", value)?;
+                }
+                None => write!(f, "none")?,
+            }
+            write!(f, " }}")
+        }
+    }
+    impl Default for Listing {
+        fn default() -> Self {
+            Listing {
+                entries: Vec::new(),
+                eof: false,
+            }
+        }
+    }
+    impl Listing {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let mut buf = Vec::new();
+            for item in self.entries.iter() {
+                buf.extend_from_slice(&1_i32.to_be_bytes());
+                let bytes = item.serialize_alloc();
+                buf.extend_from_slice(&bytes);
+            }
+            buf.extend_from_slice(&0_i32.to_be_bytes());
+            let bytes = xdr_lib::serialize_bool(&self.eof);
+            buf.extend_from_slice(&bytes);
+            buf
+        }
+        pub fn deserialize(&mut self, input: &mut &[u8]) -> xdr_lib::Result<()> {
+            loop {
+                let mut item_follows = 0;
+                xdr_lib::get_i32(&mut item_follows, input)?;
+                if item_follows == 0 {
+                    break;
+                }
+                let mut new = Entry::default();
+                Entry::deserialize(&mut new, input)?;
+                self.entries.push(new)
+            }
+            xdr_lib::get_bool(&mut self.eof, input)?;
+            Ok(())
+        }
+        fn get_entries_width(&self) -> usize {
+            4usize
+                + xdr_lib::padded_4byte(
+                    self.entries.iter().map(|_v| { 4usize + { _v.get_width() } }).sum(),
+                )
+        }
+        pub fn get_width(&self) -> usize {
+            self.get_entries_width() + 4
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl xdr_lib::Decode for Listing {
+        fn decode(input: &mut &[u8]) -> xdr_lib::Result<Self> {
+            let mut value = Self::default();
+            value.deserialize(input)?;
+            Ok(value)
+        }
+    }
+    impl xdr_lib::Encode for Listing {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl std::fmt::Display for Listing {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "Listing {{ ")?;
+            write!(f, "entries: ")?;
+            write!(f, "[")?;
+            for (i, item) in self.entries.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", item)?;
+            }
+            write!(f, "]")?;
+            write!(f, ", eof: ")?;
+            write!(f, "{}", self.eof)?;
+            write!(f, " }}")
+        }
+    }
+    impl Default for MaybeSize {
+        fn default() -> Self {
+            Self { inner: None }
+        }
+    }
+    impl MaybeSize {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            match &self.inner {
+                Some(val) => {
+                    let mut buf = 1_u32.to_be_bytes().to_vec();
+                    let bytes = val.to_be_bytes();
+                    buf.extend_from_slice(&bytes);
+                    buf
+                }
+                None => 0_u32.to_be_bytes().to_vec(),
+            }
+        }
+        pub fn deserialize(&mut self, input: &mut &[u8]) -> xdr_lib::Result<()> {
+            let mut discriminant: u32 = 0;
+            xdr_lib::get_u32(&mut discriminant, input)?;
+            match discriminant {
+                0 => (*self).inner = None,
+                _ => {
+                    let mut val = 0;
+                    xdr_lib::get_u64(&mut val, input)?;
+                    (*self).inner = Some(val);
+                }
+            };
+            Ok(())
+        }
+        pub fn get_width(&self) -> usize {
+            4usize
+                + match &self.inner {
+                    None => 0,
+                    Some(_val) => 8,
+                }
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl xdr_lib::Decode for MaybeSize {
+        fn decode(input: &mut &[u8]) -> xdr_lib::Result<Self> {
+            let mut value = Self::default();
+            value.deserialize(input)?;
+            Ok(value)
+        }
+    }
+    impl xdr_lib::Encode for MaybeSize {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl std::fmt::Display for MaybeSize {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match &self.inner {
+                Some(inner) => {
+                    write!(f, "{}", xdr_lib::display::Size::of(& inner))?;
+                }
+                None => write!(f, "none")?,
+            }
+            Ok(())
+        }
+    }
+    impl Default for ListResult {
+        fn default() -> Self {
+            Self::Ok(Listing::default())
+        }
+    }
+    impl ListResult {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let mut buf = Vec::new();
+            match self {
+                Self::Ok(inner) => {
+                    buf.extend_from_slice(&(0_i32).to_be_bytes());
+                    let bytes = inner.serialize_alloc();
+                    buf.extend_from_slice(&bytes);
+                }
+                Self::Default(inner) => {
+                    buf.extend_from_slice(&(1_i32).to_be_bytes());
+                    let bytes = inner.serialize_alloc();
+                    buf.extend_from_slice(&bytes);
+                }
+            }
+            buf
+        }
+        pub fn deserialize(&mut self, input: &mut &[u8]) -> xdr_lib::Result<()> {
+            let mut discriminant = 0;
+            xdr_lib::get_i32(&mut discriminant, input)?;
+            *self = match discriminant {
+                0 => {
+                    let mut inner = Listing::default();
+                    Listing::deserialize(&mut inner, input)?;
+                    Self::Ok(inner)
+                }
+                _ => {
+                    let mut inner = MaybeSize::default();
+                    MaybeSize::deserialize(&mut inner, input)?;
+                    Self::Default(inner)
+                }
+            };
+            Ok(())
+        }
+        pub fn get_width(&self) -> usize {
+            4usize
+                + match &self {
+                    Self::Ok(_val) => _val.get_width(),
+                    Self::Default(_val) => _val.get_width(),
+                }
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+        /// Whether this is the `Ok` arm.
+        pub fn is_ok(&self) -> bool {
+            matches!(self, Self::Ok(_))
+        }
+        /// The body of the `Ok` arm, if this is it.
+        pub fn ok(self) -> Option<Listing> {
+            match self {
+                Self::Ok(inner) => Some(inner),
+                _ => None,
+            }
+        }
+        /// The body of the default arm, if this is it. The status it was decoded from is not kept.
+        pub fn err(self) -> Option<MaybeSize> {
+            match self {
+                Self::Default(inner) => Some(inner),
+                _ => None,
+            }
+        }
+        /// The body of the `Ok` arm as `Ok`, or that of the default arm as `Err`.
+        pub fn into_result(self) -> core::result::Result<Listing, MaybeSize> {
+            match self {
+                Self::Ok(inner) => core::result::Result::Ok(inner),
+                Self::Default(inner) => core::result::Result::Err(inner),
+            }
+        }
+    }
+    impl xdr_lib::Decode for ListResult {
+        fn decode(input: &mut &[u8]) -> xdr_lib::Result<Self> {
+            let mut value = Self::default();
+            value.deserialize(input)?;
+            Ok(value)
+        }
+    }
+    impl xdr_lib::Encode for ListResult {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl std::fmt::Display for ListResult {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                Self::Ok(inner) => {
+                    write!(f, "Ok(")?;
+                    write!(f, "{}", inner)?;
+                    write!(f, ")")?;
+                }
+                Self::Default(inner) => {
+                    write!(f, "Default(")?;
+                    write!(f, "{}", inner)?;
+                    write!(f, ")")?;
+                }
+            }
+            Ok(())
+        }
+    }
+    pub mod procedures {
+        pub const LISTER_PROGRAM: u32 = 400200;
+        pub mod LISTER_V1 {
+            pub const VERSION: u32 = 1;
+            pub const NULL: u32 = 0;
+            pub const LIST: u32 = 1;
+        }
+        /// The name of a procedure of this program, given its version and procedure numbers.
+        pub fn proc_name(version: u32, proc: u32) -> Option<&'static str> {
+            match (version, proc) {
+                (1, 0) => Some("NULL"),
+                (1, 1) => Some("LIST"),
+                _ => None,
+            }
+        }
+        /// Check that `result`, the encoded result of a procedure of this program, given its version
+        /// and procedure numbers, is the canonical encoding of a value of the procedure's result type
+        /// (see `xdr_lib::check_encoding()`). Returns None for a procedure that is not part of the
+        /// program, or whose result type is not checked.
+        pub fn check_result(
+            version: u32,
+            proc: u32,
+            result: &[u8],
+        ) -> Option<std::result::Result<(), xdr_lib::EncodingError>> {
+            match (version, proc) {
+                (1, 0) => Some(xdr_lib::check_encoding(result, |_| Ok(Vec::new()))),
+                (1, 1) => {
+                    Some(
+                        xdr_lib::check_encoding(
+                            result,
+                            |input| {
+                                let discriminant = input
+                                    .get(..4)
+                                    .unwrap_or_default()
+                                    .to_vec();
+                                let mut value = super::ListResult::default();
+                                value.deserialize(input)?;
+                                let mut encoded = value.serialize_alloc();
+                                if matches!(value, super::ListResult::Default { .. }) {
+                                    super::Status::default()
+                                        .deserialize(&mut discriminant.as_slice())?;
+                                    encoded[..4].copy_from_slice(&discriminant);
+                                }
+                                Ok(encoded)
+                            },
+                        ),
+                    )
+                }
+                _ => None,
+            }
+        }
+    }
+}
@@ -0,0 +1,446 @@
+#[allow(non_camel_case_types, non_snake_case, unused_assignments, clippy::all)]
+pub mod types {
+    use xdr_lib::AsBytes;
+    pub const MAX_NAME: u32 = 255;
+    pub const HANDLE_SIZE: u32 = 8;
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub enum Kind {
+        Regular,
+        Directory,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub enum Status {
+        Ok,
+        NotFound,
+        Denied,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub struct Time {
+        pub seconds: i64,
+        pub nseconds: u32,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub struct Attributes<'a> {
+        pub kind: Kind,
+        pub size: u64,
+        pub mtime: Time,
+        pub hidden: bool,
+        pub ids: [i32; 2],
+        /// Max length: 16
+        pub groups: &'a [u32],
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub struct Entry<'a> {
+        pub handle: [u8; 8],
+        /// Max length: 255
+        pub name: &'a [u8],
+        pub cookie: &'a [u8],
+        pub attributes: Option<Attributes<'a>>,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub struct Listing<'a> {
+        pub entries: &'a [Entry<'a>],
+        pub eof: bool,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub struct MaybeSize {
+        pub inner: Option<u64>,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub enum ListResult<'a> {
+        Ok(Listing<'a>),
+        Default(MaybeSize),
+    }
+    impl Default for Kind {
+        fn default() -> Self {
+            Kind::Regular
+        }
+    }
+    impl Kind {
+        pub fn serialize(&self, buf: &mut [u8]) -> usize {
+            let mut offset = 0;
+            let val: i32 = match self {
+                Kind::Regular => 1,
+                Kind::Directory => 2,
+            };
+            buf[offset..offset + 4].copy_from_slice(&val.to_be_bytes());
+            offset += 4;
+            offset
+        }
+        pub fn try_serialize(
+            &self,
+            buf: &mut [u8],
+        ) -> core::result::Result<usize, xdr_lib::BufferTooSmall> {
+            let needed = self.serialized_size();
+            if buf.len() < needed {
+                return Err(xdr_lib::BufferTooSmall {
+                    needed,
+                    available: buf.len(),
+                });
+            }
+            Ok(self.serialize(buf))
+        }
+        pub fn serialized_size(&self) -> usize {
+            4
+        }
+    }
+    impl Default for Status {
+        fn default() -> Self {
+            Status::Ok
+        }
+    }
+    impl Status {
+        pub fn serialize(&self, buf: &mut [u8]) -> usize {
+            let mut offset = 0;
+            let val: i32 = match self {
+                Status::Ok => 0,
+                Status::NotFound => 2,
+                Status::Denied => 13,
+            };
+            buf[offset..offset + 4].copy_from_slice(&val.to_be_bytes());
+            offset += 4;
+            offset
+        }
+        pub fn try_serialize(
+            &self,
+            buf: &mut [u8],
+        ) -> core::result::Result<usize, xdr_lib::BufferTooSmall> {
+            let needed = self.serialized_size();
+            if buf.len() < needed {
+                return Err(xdr_lib::BufferTooSmall {
+                    needed,
+                    available: buf.len(),
+                });
+            }
+            Ok(self.serialize(buf))
+        }
+        pub fn serialized_size(&self) -> usize {
+            4
+        }
+    }
+    impl Default for Time {
+        fn default() -> Self {
+            Time { seconds: 0, nseconds: 0 }
+        }
+    }
+    impl Time {
+        pub fn serialize(&self, buf: &mut [u8]) -> usize {
+            let mut offset = 0;
+            buf[offset..offset + 8].copy_from_slice(&self.seconds.to_be_bytes());
+            offset += 8;
+            buf[offset..offset + 4].copy_from_slice(&self.nseconds.to_be_bytes());
+            offset += 4;
+            offset
+        }
+        pub fn try_serialize(
+            &self,
+            buf: &mut [u8],
+        ) -> core::result::Result<usize, xdr_lib::BufferTooSmall> {
+            let needed = self.serialized_size();
+            if buf.len() < needed {
+                return Err(xdr_lib::BufferTooSmall {
+                    needed,
+                    available: buf.len(),
+                });
+            }
+            Ok(self.serialize(buf))
+        }
+        pub fn get_width(&self) -> usize {
+            12
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl<'a> Default for Attributes<'a> {
+        fn default() -> Self {
+            Attributes {
+                kind: Kind::default(),
+                size: 0,
+                mtime: Time::default(),
+                hidden: false,
+                ids: ::core::array::from_fn(|_| 0),
+                groups: &[],
+            }
+        }
+    }
+    impl<'a> Attributes<'a> {
+        pub fn serialize(&self, buf: &mut [u8]) -> usize {
+            let mut offset = 0;
+            offset += self.kind.serialize(&mut buf[offset..]);
+            buf[offset..offset + 8].copy_from_slice(&self.size.to_be_bytes());
+            offset += 8;
+            offset += self.mtime.serialize(&mut buf[offset..]);
+            buf[offset..offset + 4]
+                .copy_from_slice(&xdr_lib::serialize_bool(&self.hidden));
+            offset += 4;
+            for item in self.ids.iter() {
+                buf[offset..offset + 4].copy_from_slice(&item.to_be_bytes());
+                offset += 4;
+            }
+            assert!(self.groups.len() <= 16);
+            buf[offset..offset + 4]
+                .copy_from_slice(&(self.groups.len() as u32).to_be_bytes());
+            offset += 4;
+            for item in self.groups.iter() {
+                buf[offset..offset + 4].copy_from_slice(&item.to_be_bytes());
+                offset += 4;
+            }
+            offset
+        }
+        pub fn try_serialize(
+            &self,
+            buf: &mut [u8],
+        ) -> core::result::Result<usize, xdr_lib::BufferTooSmall> {
+            let needed = self.serialized_size();
+            if buf.len() < needed {
+                return Err(xdr_lib::BufferTooSmall {
+                    needed,
+                    available: buf.len(),
+                });
+            }
+            Ok(self.serialize(buf))
+        }
+        fn get_groups_width(&self) -> usize {
+            4usize + xdr_lib::padded_4byte(self.groups.len() * 4)
+        }
+        pub fn get_width(&self) -> usize {
+            self.get_groups_width() + 36
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl<'a> Default for Entry<'a> {
+        fn default() -> Self {
+            Entry {
+                handle: [0; 8],
+                name: &[],
+                cookie: &[],
+                attributes: None,
+            }
+        }
+    }
+    impl<'a> Entry<'a> {
+        pub fn serialize(&self, buf: &mut [u8]) -> usize {
+            let mut offset = 0;
+            buf[offset..offset + self.handle.len()].copy_from_slice(&self.handle);
+            offset += self.handle.len();
+            offset = xdr_lib::encode_padding(offset, buf);
+            assert!(self.name.len() <= 255);
+            buf[offset..offset + 4]
+                .copy_from_slice(&(self.name.len() as u32).to_be_bytes());
+            offset += 4;
+            buf[offset..offset + self.name.len()].copy_from_slice(&self.name.as_bytes());
+            offset += self.name.len();
+            offset = xdr_lib::encode_padding(offset, buf);
+            buf[offset..offset + 4]
+                .copy_from_slice(&(self.cookie.len() as u32).to_be_bytes());
+            offset += 4;
+            buf[offset..offset + self.cookie.len()].copy_from_slice(&self.cookie);
+            offset += self.cookie.len();
+            offset = xdr_lib::encode_padding(offset, buf);
+            match &self.attributes {
+                Some(inner) => {
+                    buf[offset..offset + 4].copy_from_slice(&(1_i32).to_be_bytes());
+                    offset += 4;
+                    offset += inner.serialize(&mut buf[offset..]);
+                }
+                None => {
+                    buf[offset..offset + 4].copy_from_slice(&(0_i32).to_be_bytes());
+                    offset += 4;
+                }
+            };
+            offset
+        }
+        pub fn try_serialize(
+            &self,
+            buf: &mut [u8],
+        ) -> core::result::Result<usize, xdr_lib::BufferTooSmall> {
+            let needed = self.serialized_size();
+            if buf.len() < needed {
+                return Err(xdr_lib::BufferTooSmall {
+                    needed,
+                    available: buf.len(),
+                });
+            }
+            Ok(self.serialize(buf))
+        }
+        fn get_name_width(&self) -> usize {
+            4usize + xdr_lib::padded_4byte(self.name.len())
+        }
+        fn get_cookie_width(&self) -> usize {
+            4usize + xdr_lib::padded_4byte(self.cookie.len())
+        }
+        fn get_attributes_width(&self) -> usize {
+            4usize
+                + match &self.attributes {
+                    None => 0,
+                    Some(_val) => _val.get_width(),
+                }
+        }
+        pub fn get_width(&self) -> usize {
+            self.get_name_width() + self.get_cookie_width() + self.get_attributes_width()
+                + 8
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl<'a> Default for Listing<'a> {
+        fn default() -> Self {
+            Listing {
+                entries: &[],
+                eof: false,
+            }
+        }
+    }
+    impl<'a> Listing<'a> {
+        pub fn serialize(&self, buf: &mut [u8]) -> usize {
+            let mut offset = 0;
+            for item in self.entries.iter() {
+                buf[offset..offset + 4].copy_from_slice(&(1_i32).to_be_bytes());
+                offset += 4;
+                offset += item.serialize(&mut buf[offset..]);
+            }
+            buf[offset..offset + 4].copy_from_slice(&(0_i32).to_be_bytes());
+            offset += 4;
+            buf[offset..offset + 4].copy_from_slice(&xdr_lib::serialize_bool(&self.eof));
+            offset += 4;
+            offset
+        }
+        pub fn try_serialize(
+            &self,
+            buf: &mut [u8],
+        ) -> core::result::Result<usize, xdr_lib::BufferTooSmall> {
+            let needed = self.serialized_size();
+            if buf.len() < needed {
+                return Err(xdr_lib::BufferTooSmall {
+                    needed,
+                    available: buf.len(),
+                });
+            }
+            Ok(self.serialize(buf))
+        }
+        fn get_entries_width(&self) -> usize {
+            4usize
+                + xdr_lib::padded_4byte(
+                    self.entries.iter().map(|_v| { 4usize + { _v.get_width() } }).sum(),
+                )
+        }
+        pub fn get_width(&self) -> usize {
+            self.get_entries_width() + 4
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl Default for MaybeSize {
+        fn default() -> Self {
+            Self { inner: None }
+        }
+    }
+    impl MaybeSize {
+        pub fn serialize(&self, buf: &mut [u8]) -> usize {
+            let mut offset = 0;
+            match &self.inner {
+                Some(val) => {
+                    buf[offset..offset + 4].copy_from_slice(&(1_i32).to_be_bytes());
+                    offset += 4;
+                    buf[offset..offset + 8].copy_from_slice(&val.to_be_bytes());
+                    offset += 8;
+                }
+                None => {
+                    buf[offset..offset + 4].copy_from_slice(&(0_i32).to_be_bytes());
+                    offset += 4;
+                }
+            }
+            offset
+        }
+        pub fn try_serialize(
+            &self,
+            buf: &mut [u8],
+        ) -> core::result::Result<usize, xdr_lib::BufferTooSmall> {
+            let needed = self.serialized_size();
+            if buf.len() < needed {
+                return Err(xdr_lib::BufferTooSmall {
+                    needed,
+                    available: buf.len(),
+                });
+            }
+            Ok(self.serialize(buf))
+        }
+        pub fn get_width(&self) -> usize {
+            4usize
+                + match &self.inner {
+                    None => 0,
+                    Some(_val) => 8,
+                }
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl<'a> Default for ListResult<'a> {
+        fn default() -> Self {
+            Self::Ok(Listing::default())
+        }
+    }
+    impl<'a> ListResult<'a> {
+        pub fn serialize(&self, buf: &mut [u8]) -> usize {
+            let mut offset = 0;
+            match self {
+                Self::Ok(inner) => {
+                    buf[offset..offset + 4].copy_from_slice(&(0_i32).to_be_bytes());
+                    offset += 4;
+                    offset += inner.serialize(&mut buf[offset..]);
+                }
+                Self::Default(inner) => {
+                    buf[offset..offset + 4].copy_from_slice(&(1_i32).to_be_bytes());
+                    offset += 4;
+                    offset += inner.serialize(&mut buf[offset..]);
+                }
+            }
+            offset
+        }
+        pub fn try_serialize(
+            &self,
+            buf: &mut [u8],
+        ) -> core::result::Result<usize, xdr_lib::BufferTooSmall> {
+            let needed = self.serialized_size();
+            if buf.len() < needed {
+                return Err(xdr_lib::BufferTooSmall {
+                    needed,
+                    available: buf.len(),
+                });
+            }
+            Ok(self.serialize(buf))
+        }
+        pub fn get_width(&self) -> usize {
+            4usize
+                + match &self {
+                    Self::Ok(_val) => _val.get_width(),
+                    Self::Default(_val) => _val.get_width(),
+                }
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    pub mod procedures {
+        pub const LISTER_PROGRAM: u32 = 400200;
+        pub mod LISTER_V1 {
+            pub const VERSION: u32 = 1;
+            pub const NULL: u32 = 0;
+            pub const LIST: u32 = 1;
+        }
+        /// The name of a procedure of this program, given its version and procedure numbers.
+        pub fn proc_name(version: u32, proc: u32) -> Option<&'static str> {
+            match (version, proc) {
+                (1, 0) => Some("NULL"),
+                (1, 1) => Some("LIST"),
+                _ => None,
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+/* A little of everything that the code generator handles, for the golden tests. */
+
+const MAX_NAME = 255;
+const HANDLE_SIZE = 8;
+
+typedef unsigned hyper Size;
+typedef opaque Handle[HANDLE_SIZE];
+typedef string Name<MAX_NAME>;
+
+enum Kind {
+	Regular = 1,
+	Directory = 2
+};
+
+enum Status {
+	Ok = 0,
+	NotFound = 2,
+	Denied = 13
+};
+
+struct Time {
+	hyper seconds;
+	unsigned int nseconds;
+};
+
+struct Attributes {
+	Kind kind;
+	Size size;
+	Time mtime;
+	bool hidden;
+	int ids[2];
+	unsigned int groups<16>;
+};
+
+struct Entry {
+	Handle handle;
+	Name name;
+	opaque cookie<>;
+	Attributes *attributes;
+	Entry *next;
+};
+
+struct Listing {
+	Entry *entries;
+	bool eof;
+};
+
+union MaybeSize switch (bool present) {
+case TRUE:
+	Size size;
+case FALSE:
+	void;
+};
+
+union ListResult switch (Status status) {
+case Ok:
+	Listing listing;
+default:
+	MaybeSize size;
+};
+
+program LISTER_PROGRAM {
+	version LISTER_V1 {
+		void NULL(void) = 0;
+		ListResult LIST(Handle) = 1;
+	} = 1;
+} = 400200;
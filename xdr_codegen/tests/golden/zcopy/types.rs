@@ -0,0 +1,866 @@
+#[allow(non_camel_case_types, non_snake_case, unused_assignments, clippy::all)]
+pub mod types {
+    use std::os::unix::ffi::OsStrExt;
+    #[allow(unused_imports)]
+    use xdr_lib::Reader;
+    pub const MAX_NAME: u32 = 255;
+    pub const HANDLE_SIZE: u32 = 8;
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub enum Kind {
+        Regular,
+        Directory,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub enum Status {
+        Ok,
+        NotFound,
+        Denied,
+    }
+    #[derive(Debug, PartialEq, Clone)]
+    pub struct TimeReader<'a> {
+        buf: &'a [u8],
+    }
+    impl<'a> xdr_lib::Reader<'a> for TimeReader<'a> {
+        fn from_buf(buf: &'a [u8]) -> xdr_lib::Result<Self> {
+            let me = Self { buf };
+            me.validate()
+        }
+        fn get_width(&self) -> xdr_lib::Result<usize> {
+            Ok(12)
+        }
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub struct Time {
+        pub seconds: i64,
+        pub nseconds: u32,
+    }
+    #[derive(Debug, PartialEq, Clone)]
+    pub struct AttributesReader<'a> {
+        buf: &'a [u8],
+        groups_width: usize,
+    }
+    impl<'a> xdr_lib::Reader<'a> for AttributesReader<'a> {
+        fn from_buf(buf: &'a [u8]) -> xdr_lib::Result<Self> {
+            let off = 36;
+            let _input = &buf.get(off..).ok_or(xdr_lib::DeserializeError)?;
+            let groups_width = {
+                if _input.len() < 4 {
+                    {
+                        return Err(xdr_lib::DeserializeError);
+                    }
+                }
+                let length: usize = xdr_lib::get_u32_infallible(_input) as usize;
+                if length > 16 {
+                    return Err(xdr_lib::DeserializeError);
+                }
+                let _array_count_size: usize = 4;
+                #[allow(unused_variables)]
+                let off = off + _array_count_size;
+                let _input = &_input[_array_count_size..];
+                let required = xdr_lib::geq_4byte_boundary(length * 4usize)
+                    + _array_count_size;
+                Ok(required)
+            }?;
+            let me = Self { buf, groups_width };
+            me.validate()
+        }
+        fn get_width(&self) -> xdr_lib::Result<usize> {
+            Ok(self.get_groups_width()? + 36)
+        }
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+    pub struct Attributes {
+        pub kind: Kind,
+        pub size: u64,
+        pub mtime: Time,
+        pub hidden: bool,
+        pub ids: [i32; 2],
+        /// Max length: 16
+        pub groups: Vec<u32>,
+    }
+    #[derive(Debug, PartialEq, Clone)]
+    pub struct EntryReader<'a> {
+        buf: &'a [u8],
+        name_width: usize,
+        cookie_width: usize,
+        attributes: Option<AttributesReader<'a>>,
+    }
+    impl<'a> xdr_lib::Reader<'a> for EntryReader<'a> {
+        fn from_buf(buf: &'a [u8]) -> xdr_lib::Result<Self> {
+            let off = 8;
+            let _input = &buf.get(off..).ok_or(xdr_lib::DeserializeError)?;
+            let name_width = {
+                if _input.len() < 4 {
+                    {
+                        return Err(xdr_lib::DeserializeError);
+                    }
+                }
+                let length: usize = xdr_lib::get_u32_infallible(_input) as usize;
+                if length > 255 {
+                    return Err(xdr_lib::DeserializeError);
+                }
+                let _array_count_size: usize = 4;
+                #[allow(unused_variables)]
+                let off = off + _array_count_size;
+                let _input = &_input[_array_count_size..];
+                let required = xdr_lib::geq_4byte_boundary(length * 1usize)
+                    + _array_count_size;
+                Ok(required)
+            }?;
+            let off = name_width + 8;
+            let _input = &buf.get(off..).ok_or(xdr_lib::DeserializeError)?;
+            let cookie_width = {
+                if _input.len() < 4 {
+                    {
+                        return Err(xdr_lib::DeserializeError);
+                    }
+                }
+                let length: usize = xdr_lib::get_u32_infallible(_input) as usize;
+                let _array_count_size: usize = 4;
+                #[allow(unused_variables)]
+                let off = off + _array_count_size;
+                let _input = &_input[_array_count_size..];
+                let required = xdr_lib::geq_4byte_boundary(length * 1usize)
+                    + _array_count_size;
+                Ok(required)
+            }?;
+            let off = name_width + cookie_width + 8;
+            let _input = &buf.get(off..).ok_or(xdr_lib::DeserializeError)?;
+            let attributes = Option::<AttributesReader<'a>>::from_buf(&buf[off..])?;
+            let me = Self {
+                buf,
+                name_width,
+                cookie_width,
+                attributes,
+            };
+            me.validate()
+        }
+        fn get_width(&self) -> xdr_lib::Result<usize> {
+            Ok(
+                self.get_name_width()? + self.get_cookie_width()?
+                    + self.get_attributes_width()? + 8,
+            )
+        }
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+    pub struct Entry {
+        pub handle: [u8; 8],
+        /// Max length: 255
+        pub name: std::ffi::OsString,
+        pub cookie: Vec<u8>,
+        pub attributes: Option<Attributes>,
+    }
+    #[derive(Debug, PartialEq, Clone)]
+    pub struct ListingReader<'a> {
+        buf: &'a [u8],
+        entries_width: usize,
+    }
+    impl<'a> xdr_lib::Reader<'a> for ListingReader<'a> {
+        fn from_buf(buf: &'a [u8]) -> xdr_lib::Result<Self> {
+            let entries_width = std::cell::OnceCell::<usize>::new();
+            let me = Self { buf, entries_width };
+            me.validate()
+        }
+        fn get_width(&self) -> xdr_lib::Result<usize> {
+            Ok(self.get_entries_width()? + 4)
+        }
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+    pub struct Listing {
+        pub entries: Vec<Entry>,
+        pub eof: bool,
+    }
+    impl<'a> xdr_lib::Reader<'a> for MaybeSizeReader<'a> {
+        fn from_buf(buf: &'a [u8]) -> xdr_lib::Result<Self> {
+            let off = 0;
+            let _input = &buf[off..];
+            let inner = {
+                if _input.len() < 4 {
+                    return Err(xdr_lib::DeserializeError);
+                }
+                let has_val = xdr_lib::get_i32_infallible(_input);
+                match has_val {
+                    0 => None,
+                    _ => {
+                        let val = {
+                            let off = off + 4;
+                            let _input = &buf[off..];
+                            if _input.len() < 8 {
+                                return Err(xdr_lib::DeserializeError);
+                            }
+                            xdr_lib::get_u64_infallible(_input)
+                        };
+                        Some(val)
+                    }
+                }
+            };
+            let me = Self { buf, inner };
+            let required = me.get_width()?;
+            if required > me.buf.len() {
+                return Err(xdr_lib::DeserializeError);
+            }
+            Ok(me)
+        }
+        fn get_width(&self) -> xdr_lib::Result<usize> {
+            let off = 0usize;
+            let _input = &self.buf[off..];
+            Ok(
+                4usize
+                    + match &self.inner {
+                        None => Ok(0),
+                        Some(_inner) => Ok(8),
+                    }?,
+            )
+        }
+    }
+    impl<'a> MaybeSizeReader<'a> {
+        pub fn new(buf: &'a [u8]) -> xdr_lib::Result<Self> {
+            Self::from_buf(buf)
+        }
+    }
+    #[derive(Debug, PartialEq, Clone)]
+    pub struct MaybeSizeReader<'a> {
+        buf: &'a [u8],
+        pub inner: Option<u64>,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub struct MaybeSize {
+        pub inner: Option<u64>,
+    }
+    impl<'a> xdr_lib::Reader<'a> for ListResultReader<'a> {
+        fn from_buf(buf: &'a [u8]) -> xdr_lib::Result<Self> {
+            let off = 0;
+            let _input = &buf[off..];
+            let inner = {
+                if _input.len() < 4 {
+                    return Err(xdr_lib::DeserializeError);
+                }
+                let discriminant = xdr_lib::get_i32_infallible(_input);
+                match discriminant {
+                    0 => {
+                        let off = off + 4;
+                        let _input = &buf[off..];
+                        let inner = { ListingReader::from_buf(_input)? };
+                        ListResultRet::<'a>::Ok(inner)
+                    }
+                    _ => {
+                        let off = off + 4;
+                        let _input = &buf[off..];
+                        let inner = { MaybeSizeReader::from_buf(_input)? };
+                        ListResultRet::<'a>::Default(inner)
+                    }
+                }
+            };
+            let me = Self { buf, inner };
+            let required = me.get_width()?;
+            if required > me.buf.len() {
+                return Err(xdr_lib::DeserializeError);
+            }
+            Ok(me)
+        }
+        fn get_width(&self) -> xdr_lib::Result<usize> {
+            let off = 0usize;
+            let _input = &self.buf[off..];
+            Ok(
+                4usize
+                    + match &self.inner {
+                        ListResultRet::Ok(_val) => _val.get_width(),
+                        ListResultRet::Default(_val) => &_val.get_width(),
+                    }?,
+            )
+        }
+    }
+    impl<'a> ListResultReader<'a> {
+        pub fn new(buf: &'a [u8]) -> xdr_lib::Result<Self> {
+            Self::from_buf(buf)
+        }
+    }
+    #[derive(Debug, PartialEq, Clone)]
+    pub enum ListResultRet<'a> {
+        Ok(ListingReader<'a>),
+        Default(MaybeSizeReader<'a>),
+    }
+    #[derive(Debug, PartialEq, Clone)]
+    pub struct ListResultReader<'a> {
+        buf: &'a [u8],
+        inner: ListResultRet<'a>,
+    }
+    #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+    pub enum ListResult {
+        Ok(Listing),
+        Default(MaybeSize),
+    }
+    impl Default for Kind {
+        fn default() -> Self {
+            Kind::Regular
+        }
+    }
+    impl Kind {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let val: i32 = match self {
+                Kind::Regular => 1,
+                Kind::Directory => 2,
+            };
+            val.to_be_bytes().to_vec()
+        }
+        pub fn deserialize(_input: &[u8]) -> xdr_lib::Result<Self> {
+            let val = xdr_lib::get_i32_infallible(_input);
+            match val {
+                1 => Ok(Kind::Regular),
+                2 => Ok(Kind::Directory),
+                _ => Err(xdr_lib::DeserializeError),
+            }
+        }
+        pub fn get_width(&self) -> usize {
+            4
+        }
+        pub fn serialized_size(&self) -> usize {
+            4
+        }
+    }
+    impl xdr_lib::Encode for Kind {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl Default for Status {
+        fn default() -> Self {
+            Status::Ok
+        }
+    }
+    impl Status {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let val: i32 = match self {
+                Status::Ok => 0,
+                Status::NotFound => 2,
+                Status::Denied => 13,
+            };
+            val.to_be_bytes().to_vec()
+        }
+        pub fn deserialize(_input: &[u8]) -> xdr_lib::Result<Self> {
+            let val = xdr_lib::get_i32_infallible(_input);
+            match val {
+                0 => Ok(Status::Ok),
+                2 => Ok(Status::NotFound),
+                13 => Ok(Status::Denied),
+                _ => Err(xdr_lib::DeserializeError),
+            }
+        }
+        pub fn get_width(&self) -> usize {
+            4
+        }
+        pub fn serialized_size(&self) -> usize {
+            4
+        }
+    }
+    impl xdr_lib::Encode for Status {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl Default for Time {
+        fn default() -> Self {
+            Time { seconds: 0, nseconds: 0 }
+        }
+    }
+    impl Time {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let mut buf = Vec::new();
+            let bytes = self.seconds.to_be_bytes();
+            buf.extend_from_slice(&bytes);
+            let bytes = self.nseconds.to_be_bytes();
+            buf.extend_from_slice(&bytes);
+            buf
+        }
+        pub fn get_width(&self) -> usize {
+            12
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl<'a> TimeReader<'a> {
+        pub fn new(buf: &'a [u8]) -> xdr_lib::Result<Self> {
+            Self::from_buf(buf)
+        }
+        fn validate(self) -> xdr_lib::Result<Self> {
+            let required = 12;
+            if required > self.buf.len() {
+                return Err(xdr_lib::DeserializeError);
+            }
+            Ok(self)
+        }
+        pub fn get_seconds(&self) -> i64 {
+            let off = 0;
+            let _input = &self.buf[off..];
+            xdr_lib::get_i64_infallible(_input)
+        }
+        pub fn get_nseconds(&self) -> u32 {
+            let off = 8;
+            let _input = &self.buf[off..];
+            xdr_lib::get_u32_infallible(_input)
+        }
+    }
+    impl xdr_lib::Encode for Time {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl Default for Attributes {
+        fn default() -> Self {
+            Attributes {
+                kind: Kind::default(),
+                size: 0,
+                mtime: Time::default(),
+                hidden: false,
+                ids: {
+                    let arr: [i32; 2] = ::core::array::from_fn(|_| { 0 });
+                    arr
+                },
+                groups: Vec::new(),
+            }
+        }
+    }
+    impl Attributes {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let mut buf = Vec::new();
+            let bytes = self.kind.serialize_alloc();
+            buf.extend_from_slice(&bytes);
+            let bytes = self.size.to_be_bytes();
+            buf.extend_from_slice(&bytes);
+            let bytes = self.mtime.serialize_alloc();
+            buf.extend_from_slice(&bytes);
+            let bytes = xdr_lib::serialize_bool(&self.hidden);
+            buf.extend_from_slice(&bytes);
+            for item in self.ids.iter() {
+                let bytes = item.to_be_bytes();
+                buf.extend_from_slice(&bytes);
+            }
+            buf.extend_from_slice(&(self.groups.len() as u32).to_be_bytes());
+            for item in self.groups.iter() {
+                let bytes = item.to_be_bytes();
+                buf.extend_from_slice(&bytes);
+            }
+            buf
+        }
+        fn get_groups_width(&self) -> usize {
+            4usize + xdr_lib::padded_4byte(self.groups.len() * 4)
+        }
+        pub fn get_width(&self) -> usize {
+            self.get_groups_width() + 36
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl<'a> AttributesReader<'a> {
+        pub fn new(buf: &'a [u8]) -> xdr_lib::Result<Self> {
+            Self::from_buf(buf)
+        }
+        fn validate(self) -> xdr_lib::Result<Self> {
+            let required = self.get_groups_width()? + 36;
+            if required > self.buf.len() {
+                return Err(xdr_lib::DeserializeError);
+            }
+            let off = 0;
+            let _input = &self.buf[off..];
+            Kind::deserialize(_input)?;
+            Ok(self)
+        }
+        pub fn get_groups_width(&self) -> xdr_lib::Result<usize> {
+            Ok(self.groups_width)
+        }
+        pub fn get_kind(&self) -> Kind {
+            let off = 0;
+            let _input = &self.buf[off..];
+            Kind::deserialize(_input).unwrap()
+        }
+        pub fn get_size(&self) -> u64 {
+            let off = 4;
+            let _input = &self.buf[off..];
+            xdr_lib::get_u64_infallible(_input)
+        }
+        pub fn get_mtime(&self) -> TimeReader<'a> {
+            let off = 12;
+            let _input = &self.buf[off..];
+            TimeReader::from_buf(_input).unwrap()
+        }
+        pub fn get_hidden(&self) -> bool {
+            let off = 24;
+            let _input = &self.buf[off..];
+            xdr_lib::get_bool_infallible(_input)
+        }
+        pub fn get_ids(&self) -> xdr_lib::ArrayIter<'a, i32> {
+            let off = 28;
+            let _input = &self.buf[off..];
+            let length: usize = 2;
+            let _array_count_size: usize = 0;
+            xdr_lib::ArrayIter::<'a, i32>::new(_input, length, None)
+        }
+        pub fn get_groups(&self) -> xdr_lib::ArrayIter<'a, u32> {
+            let off = 36;
+            let _input = &self.buf[off..];
+            let length: usize = xdr_lib::get_u32_infallible(_input) as usize;
+            let _array_count_size: usize = 4;
+            #[allow(unused_variables)]
+            let off = off + _array_count_size;
+            let _input = &_input[_array_count_size..];
+            xdr_lib::ArrayIter::<'a, u32>::new(_input, length, None)
+        }
+    }
+    impl xdr_lib::Encode for Attributes {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl Default for Entry {
+        fn default() -> Self {
+            Entry {
+                handle: {
+                    let arr: [u8; 8] = ::core::array::from_fn(|_| { 0_u8 });
+                    arr
+                },
+                name: std::ffi::OsString::new(),
+                cookie: Vec::new(),
+                attributes: None,
+            }
+        }
+    }
+    impl Entry {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let mut buf = Vec::new();
+            buf.extend_from_slice(&self.handle);
+            let padding = (4 - self.handle.len() % 4) % 4;
+            buf.extend_from_slice(&vec![0; padding]);
+            buf.extend_from_slice(&(self.name.len() as u32).to_be_bytes());
+            buf.extend_from_slice(self.name.as_bytes());
+            let padding = (4 - self.name.len() % 4) % 4;
+            buf.extend_from_slice(&vec![0; padding]);
+            buf.extend_from_slice(&(self.cookie.len() as u32).to_be_bytes());
+            buf.extend_from_slice(&self.cookie);
+            let padding = (4 - self.cookie.len() % 4) % 4;
+            buf.extend_from_slice(&vec![0; padding]);
+            match &self.attributes {
+                Some(inner) => {
+                    buf.extend_from_slice(&1_i32.to_be_bytes());
+                    let bytes = inner.serialize_alloc();
+                    buf.extend_from_slice(&bytes);
+                }
+                None => buf.extend_from_slice(&0_i32.to_be_bytes()),
+            };
+            buf
+        }
+        fn get_name_width(&self) -> usize {
+            4usize + xdr_lib::padded_4byte(self.name.len())
+        }
+        fn get_cookie_width(&self) -> usize {
+            4usize + xdr_lib::padded_4byte(self.cookie.len())
+        }
+        fn get_attributes_width(&self) -> usize {
+            4usize
+                + match &self.attributes {
+                    None => 0,
+                    Some(_val) => _val.get_width(),
+                }
+        }
+        pub fn get_width(&self) -> usize {
+            self.get_name_width() + self.get_cookie_width() + self.get_attributes_width()
+                + 8
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl<'a> EntryReader<'a> {
+        pub fn new(buf: &'a [u8]) -> xdr_lib::Result<Self> {
+            Self::from_buf(buf)
+        }
+        fn validate(self) -> xdr_lib::Result<Self> {
+            let required = self.get_name_width()? + self.get_cookie_width()?
+                + self.get_attributes_width()? + 8;
+            if required > self.buf.len() {
+                return Err(xdr_lib::DeserializeError);
+            }
+            Ok(self)
+        }
+        pub fn get_name_width(&self) -> xdr_lib::Result<usize> {
+            Ok(self.name_width)
+        }
+        pub fn get_cookie_width(&self) -> xdr_lib::Result<usize> {
+            Ok(self.cookie_width)
+        }
+        pub fn get_attributes_width(&self) -> xdr_lib::Result<usize> {
+            self.attributes.get_width()
+        }
+        pub fn get_handle(&self) -> &'a [u8] {
+            let off = 0;
+            let _input = &self.buf[off..];
+            let length: usize = 8;
+            let _array_count_size: usize = 0;
+            &_input[..length]
+        }
+        pub fn get_name(&self) -> &'a std::ffi::OsStr {
+            let off = 8;
+            let _input = &self.buf[off..];
+            let length: usize = xdr_lib::get_u32_infallible(_input) as usize;
+            let _array_count_size: usize = 4;
+            #[allow(unused_variables)]
+            let off = off + _array_count_size;
+            let _input = &_input[_array_count_size..];
+            std::ffi::OsStr::from_bytes(&_input[..length])
+        }
+        pub fn get_cookie(&self) -> &'a [u8] {
+            let off = self.get_name_width().unwrap() + 8;
+            let _input = &self.buf[off..];
+            let length: usize = xdr_lib::get_u32_infallible(_input) as usize;
+            let _array_count_size: usize = 4;
+            #[allow(unused_variables)]
+            let off = off + _array_count_size;
+            let _input = &_input[_array_count_size..];
+            &_input[..length]
+        }
+        pub fn get_attributes(&self) -> Option<AttributesReader<'a>> {
+            return self.attributes.clone();
+        }
+    }
+    impl xdr_lib::Encode for Entry {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl Default for Listing {
+        fn default() -> Self {
+            Listing {
+                entries: Vec::new(),
+                eof: false,
+            }
+        }
+    }
+    impl Listing {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let mut buf = Vec::new();
+            for item in self.entries.iter() {
+                buf.extend_from_slice(&1_i32.to_be_bytes());
+                let bytes = item.serialize_alloc();
+                buf.extend_from_slice(&bytes);
+            }
+            buf.extend_from_slice(&0_i32.to_be_bytes());
+            let bytes = xdr_lib::serialize_bool(&self.eof);
+            buf.extend_from_slice(&bytes);
+            buf
+        }
+        fn get_entries_width(&self) -> usize {
+            4usize
+                + xdr_lib::padded_4byte(
+                    self.entries.iter().map(|_v| { 4usize + { _v.get_width() } }).sum(),
+                )
+        }
+        pub fn get_width(&self) -> usize {
+            self.get_entries_width() + 4
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl<'a> ListingReader<'a> {
+        pub fn new(buf: &'a [u8]) -> xdr_lib::Result<Self> {
+            Self::from_buf(buf)
+        }
+        fn validate(self) -> xdr_lib::Result<Self> {
+            let required = self.get_entries_width()? + 4;
+            if required > self.buf.len() {
+                return Err(xdr_lib::DeserializeError);
+            }
+            Ok(self)
+        }
+        pub fn get_entries_width(&self) -> xdr_lib::Result<usize> {
+            let off = 0;
+            let _input = &self.buf[off..];
+            let has_optional = xdr_lib::get_i32_infallible(_input);
+            match has_optional {
+                0 => Ok(4),
+                _ => {
+                    let mut it = xdr_lib::LinkedListIter::<
+                        'a,
+                        EntryReader<'a>,
+                    >::new(_input, None);
+                    it.by_ref().for_each(drop);
+                    Ok(it.off)
+                }
+            }
+        }
+        pub fn get_entries(&self) -> xdr_lib::LinkedListIter<'a, EntryReader<'a>> {
+            let off = 0;
+            let _input = &self.buf[off..];
+            xdr_lib::LinkedListIter::<'a, EntryReader<'a>>::new(_input, None)
+        }
+        pub fn get_eof(&self) -> bool {
+            let off = self.get_entries_width().unwrap();
+            let _input = &self.buf[off..];
+            xdr_lib::get_bool_infallible(_input)
+        }
+    }
+    impl xdr_lib::Encode for Listing {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl Default for MaybeSize {
+        fn default() -> Self {
+            Self { inner: None }
+        }
+    }
+    impl MaybeSize {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            match &self.inner {
+                Some(val) => {
+                    let mut buf = 1_u32.to_be_bytes().to_vec();
+                    let bytes = val.to_be_bytes();
+                    buf.extend_from_slice(&bytes);
+                    buf
+                }
+                None => 0_u32.to_be_bytes().to_vec(),
+            }
+        }
+        pub fn get_width(&self) -> usize {
+            4usize
+                + match &self.inner {
+                    None => 0,
+                    Some(_val) => 8,
+                }
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+    }
+    impl<'a> MaybeSizeReader<'a> {
+        pub fn deserialize(&'a self) -> Option<u64> {
+            self.inner.clone()
+        }
+    }
+    impl xdr_lib::Encode for MaybeSize {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    impl Default for ListResult {
+        fn default() -> Self {
+            Self::Ok(Listing::default())
+        }
+    }
+    impl ListResult {
+        pub fn serialize_alloc(&self) -> Vec<u8> {
+            let mut buf = Vec::new();
+            match self {
+                Self::Ok(inner) => {
+                    buf.extend_from_slice(&(0_i32).to_be_bytes());
+                    let bytes = inner.serialize_alloc();
+                    buf.extend_from_slice(&bytes);
+                }
+                Self::Default(inner) => {
+                    buf.extend_from_slice(&(1_i32).to_be_bytes());
+                    let bytes = inner.serialize_alloc();
+                    buf.extend_from_slice(&bytes);
+                }
+            }
+            buf
+        }
+        pub fn get_width(&self) -> usize {
+            4usize
+                + match &self {
+                    Self::Ok(_val) => _val.get_width(),
+                    Self::Default(_val) => _val.get_width(),
+                }
+        }
+        pub fn serialized_size(&self) -> usize {
+            self.get_width()
+        }
+        /// Whether this is the `Ok` arm.
+        pub fn is_ok(&self) -> bool {
+            matches!(self, Self::Ok(_))
+        }
+        /// The body of the `Ok` arm, if this is it.
+        pub fn ok(self) -> Option<Listing> {
+            match self {
+                Self::Ok(inner) => Some(inner),
+                _ => None,
+            }
+        }
+        /// The body of the default arm, if this is it. The status it was decoded from is not kept.
+        pub fn err(self) -> Option<MaybeSize> {
+            match self {
+                Self::Default(inner) => Some(inner),
+                _ => None,
+            }
+        }
+        /// The body of the `Ok` arm as `Ok`, or that of the default arm as `Err`.
+        pub fn into_result(self) -> core::result::Result<Listing, MaybeSize> {
+            match self {
+                Self::Ok(inner) => core::result::Result::Ok(inner),
+                Self::Default(inner) => core::result::Result::Err(inner),
+            }
+        }
+    }
+    impl<'a> ListResultReader<'a> {
+        pub fn deserialize(&'a self) -> ListResultRet<'a> {
+            self.inner.clone()
+        }
+    }
+    impl xdr_lib::Encode for ListResult {
+        fn encode(&self) -> Vec<u8> {
+            self.serialize_alloc()
+        }
+    }
+    pub mod procedures {
+        pub const LISTER_PROGRAM: u32 = 400200;
+        pub mod LISTER_V1 {
+            pub const VERSION: u32 = 1;
+            pub const NULL: u32 = 0;
+            pub const LIST: u32 = 1;
+        }
+        /// The name of a procedure of this program, given its version and procedure numbers.
+        pub fn proc_name(version: u32, proc: u32) -> Option<&'static str> {
+            match (version, proc) {
+                (1, 0) => Some("NULL"),
+                (1, 1) => Some("LIST"),
+                _ => None,
+            }
+        }
+        /// Check that `result`, the encoded result of a procedure of this program, given its version
+        /// and procedure numbers, is the canonical encoding of a value of the procedure's result type
+        /// (see `xdr_lib::check_encoding()`). Returns None for a procedure that is not part of the
+        /// program, or whose result type is not checked.
+        pub fn check_result(
+            version: u32,
+            proc: u32,
+            result: &[u8],
+        ) -> Option<std::result::Result<(), xdr_lib::EncodingError>> {
+            match (version, proc) {
+                (1, 0) => Some(xdr_lib::check_encoding(result, |_| Ok(Vec::new()))),
+                (1, 1) => {
+                    Some(
+                        xdr_lib::check_encoding(
+                            result,
+                            |input| {
+                                let discriminant = input
+                                    .get(..4)
+                                    .unwrap_or_default()
+                                    .to_vec();
+                                let mut value = super::ListResult::default();
+                                value.deserialize(input)?;
+                                let mut encoded = value.serialize_alloc();
+                                if matches!(value, super::ListResult::Default { .. }) {
+                                    super::Status::default()
+                                        .deserialize(&mut discriminant.as_slice())?;
+                                    encoded[..4].copy_from_slice(&discriminant);
+                                }
+                                Ok(encoded)
+                            },
+                        ),
+                    )
+                }
+                _ => None,
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+//! Renderings of protocol values for the `Display` impls generated with
+//! `Compiler::enable_display()`: opaque data in hexadecimal, timestamps in RFC 3339, and sizes with
+//! binary units.
+
+use std::fmt;
+
+/// Opaque data, such as a file handle, in lowercase hexadecimal.
+pub struct Hex<'a>(pub &'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{byte:02x}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A time as seconds and nanoseconds since the Unix epoch, in RFC 3339 format in UTC, such as
+/// `2024-06-01T12:30:00.5Z`. The fraction of a second is only written if there is one.
+pub struct Timestamp {
+    pub seconds: i64,
+    pub nseconds: u32,
+}
+
+impl Timestamp {
+    /// Takes the members of the timestamp struct of a protocol, whichever integer types it gives
+    /// them.
+    pub fn new(seconds: impl TryInto<i64>, nseconds: impl TryInto<u32>) -> Self {
+        Timestamp {
+            seconds: seconds.try_into().unwrap_or(i64::MAX),
+            nseconds: nseconds.try_into().unwrap_or(u32::MAX),
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let days = self.seconds.div_euclid(86_400);
+        let time = self.seconds.rem_euclid(86_400);
+        let (year, month, day) = civil_from_days(days);
+        write!(
+            f,
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+            time / 3600,
+            time / 60 % 60,
+            time % 60
+        )?;
+        if self.nseconds != 0 {
+            // A server may send more than a second of nanoseconds, which is shown as it is:
+            let fraction = format!("{:09}", self.nseconds);
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        write!(f, "Z")
+    }
+}
+
+/// The year, month, and day of the date `days` after 1970-01-01, in the proleptic Gregorian
+/// calendar. (This is Howard Hinnant's `civil_from_days()`.)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// A size in bytes, followed by the size in the largest binary unit it reaches, if it is at least
+/// a KiB, such as `1536 (1.5 KiB)`.
+pub struct Size(pub u64);
+
+impl Size {
+    /// Takes a size of whichever integer type, or a reference to one, so that generated code needn't
+    /// know which it has.
+    pub fn of<T: AsSize + ?Sized>(size: &T) -> Self {
+        Size(size.as_size())
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+        write!(f, "{}", self.0)?;
+        let Some(power) = (1..=UNITS.len()).rev().find(|p| self.0 >> (10 * p) != 0) else {
+            return Ok(());
+        };
+        let scaled = self.0 as f64 / (1_u64 << (10 * power)) as f64;
+        write!(f, " ({scaled:.1} {})", UNITS[power - 1])
+    }
+}
+
+/// The integer types that XDR sizes are declared with.
+pub trait AsSize {
+    fn as_size(&self) -> u64;
+}
+
+impl AsSize for u32 {
+    fn as_size(&self) -> u64 {
+        u64::from(*self)
+    }
+}
+
+impl AsSize for u64 {
+    fn as_size(&self) -> u64 {
+        *self
+    }
+}
+
+impl AsSize for i32 {
+    fn as_size(&self) -> u64 {
+        *self as u64
+    }
+}
+
+impl AsSize for i64 {
+    fn as_size(&self) -> u64 {
+        *self as u64
+    }
+}
+
+impl<T: AsSize + ?Sized> AsSize for &T {
+    fn as_size(&self) -> u64 {
+        (**self).as_size()
+    }
+}
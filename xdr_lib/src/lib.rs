@@ -1,23 +1,57 @@
-use std::marker::PhantomData;
+// Without the `std` feature, only what the code generated with `Compiler::no_std()` uses is built,
+// on `core` alone.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::io::Read;
+
+#[cfg(feature = "std")]
+pub mod display;
+#[cfg(feature = "serde")]
+pub mod os_string;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct DeserializeError;
 
+#[cfg(feature = "std")]
 impl std::error::Error for DeserializeError {}
 
-impl std::fmt::Display for DeserializeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "Invalid input to deserialize method")
     }
 }
 
-pub type Result<T> = std::result::Result<T, DeserializeError>;
+pub type Result<T> = core::result::Result<T, DeserializeError>;
+
+/// Returned by the `try_serialize()` methods of generated types when the buffer is smaller than the
+/// encoded value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BufferTooSmall {
+    /// The size of the encoded value, as `serialized_size()` returns it.
+    pub needed: usize,
+    pub available: usize,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BufferTooSmall {}
+
+impl core::fmt::Display for BufferTooSmall {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "Buffer of {} bytes is too small for a value of {} bytes",
+            self.available, self.needed
+        )
+    }
+}
 
 pub fn get_i32(dst: &mut i32, input: &mut &[u8]) -> Result<()> {
     if input.len() < 4 {
         return Err(DeserializeError);
     }
-    let (int_bytes, rest) = input.split_at(std::mem::size_of::<i32>());
+    let (int_bytes, rest) = input.split_at(core::mem::size_of::<i32>());
     *input = rest;
     *dst = i32::from_be_bytes(int_bytes.try_into().unwrap());
     Ok(())
@@ -27,7 +61,7 @@ pub fn get_u32(dst: &mut u32, input: &mut &[u8]) -> Result<()> {
     if input.len() < 4 {
         return Err(DeserializeError);
     }
-    let (int_bytes, rest) = input.split_at(std::mem::size_of::<u32>());
+    let (int_bytes, rest) = input.split_at(core::mem::size_of::<u32>());
     *input = rest;
     *dst = u32::from_be_bytes(int_bytes.try_into().unwrap());
     Ok(())
@@ -37,7 +71,7 @@ pub fn get_i64(dst: &mut i64, input: &mut &[u8]) -> Result<()> {
     if input.len() < 8 {
         return Err(DeserializeError);
     }
-    let (int_bytes, rest) = input.split_at(std::mem::size_of::<i64>());
+    let (int_bytes, rest) = input.split_at(core::mem::size_of::<i64>());
     *input = rest;
     *dst = i64::from_be_bytes(int_bytes.try_into().unwrap());
     Ok(())
@@ -47,7 +81,7 @@ pub fn get_u64(dst: &mut u64, input: &mut &[u8]) -> Result<()> {
     if input.len() < 8 {
         return Err(DeserializeError);
     }
-    let (int_bytes, rest) = input.split_at(std::mem::size_of::<u64>());
+    let (int_bytes, rest) = input.split_at(core::mem::size_of::<u64>());
     *input = rest;
     *dst = u64::from_be_bytes(int_bytes.try_into().unwrap());
     Ok(())
@@ -57,12 +91,92 @@ pub fn get_bool(dst: &mut bool, input: &mut &[u8]) -> Result<()> {
     if input.len() < 4 {
         return Err(DeserializeError);
     }
-    let (bool_bytes, rest) = input.split_at(std::mem::size_of::<u32>());
+    let (bool_bytes, rest) = input.split_at(core::mem::size_of::<u32>());
     *input = rest;
     *dst = !matches!(u32::from_be_bytes(bool_bytes.try_into().unwrap()), 0);
     Ok(())
 }
 
+/// Take `len` bytes of opaque data or string, and the padding that follows them, from `input`.
+/// Fails rather than panicking if `input` is too short, since `len` usually comes from the input.
+pub fn get_opaque<'a>(len: usize, input: &mut &'a [u8]) -> Result<&'a [u8]> {
+    if input.len() < padded_4byte(len) {
+        return Err(DeserializeError);
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = &rest[padded_4byte(len) - len..];
+    Ok(bytes)
+}
+
+// The `read_` functions decode from any `Read`, for the `deserialize_from()` methods of generated
+// types. An I/O error, including running out of input, is reported as a `DeserializeError`.
+
+#[cfg(feature = "std")]
+fn read_bytes<const N: usize>(input: &mut (impl Read + ?Sized)) -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    input.read_exact(&mut bytes).map_err(|_| DeserializeError)?;
+    Ok(bytes)
+}
+
+#[cfg(feature = "std")]
+pub fn read_i32(dst: &mut i32, input: &mut (impl Read + ?Sized)) -> Result<()> {
+    *dst = i32::from_be_bytes(read_bytes(input)?);
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+pub fn read_u32(dst: &mut u32, input: &mut (impl Read + ?Sized)) -> Result<()> {
+    *dst = u32::from_be_bytes(read_bytes(input)?);
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+pub fn read_i64(dst: &mut i64, input: &mut (impl Read + ?Sized)) -> Result<()> {
+    *dst = i64::from_be_bytes(read_bytes(input)?);
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+pub fn read_u64(dst: &mut u64, input: &mut (impl Read + ?Sized)) -> Result<()> {
+    *dst = u64::from_be_bytes(read_bytes(input)?);
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+pub fn read_bool(dst: &mut bool, input: &mut (impl Read + ?Sized)) -> Result<()> {
+    *dst = !matches!(u32::from_be_bytes(read_bytes(input)?), 0);
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+/// Read `len` bytes of opaque data or string, and the padding that follows them, from `input`.
+pub fn read_opaque(len: usize, input: &mut (impl Read + ?Sized)) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    read_opaque_into(len, &mut bytes, input)?;
+    Ok(bytes)
+}
+
+#[cfg(feature = "std")]
+/// Like `read_opaque()`, but appends the bytes to `dst`. `dst` grows as data arrives, so a bogus
+/// `len` cannot cause a huge allocation by itself.
+pub fn read_opaque_into(
+    len: usize,
+    dst: &mut Vec<u8>,
+    input: &mut (impl Read + ?Sized),
+) -> Result<()> {
+    let start = dst.len();
+    let padded = padded_4byte(len);
+    input
+        .take(padded as u64)
+        .read_to_end(dst)
+        .map_err(|_| DeserializeError)?;
+    if dst.len() - start != padded {
+        return Err(DeserializeError);
+    }
+    dst.truncate(start + len);
+    Ok(())
+}
+
 pub fn serialize_bool(src: &bool) -> [u8; 4] {
     match src {
         true => 1_u32.to_be_bytes(),
@@ -80,28 +194,166 @@ pub fn encode_padding(offset: usize, buf: &mut [u8]) -> usize {
     padded_offset
 }
 
+/// A type that decodes from XDR, as the generated types do unless they are zero-copy, for code that
+/// is generic over them, such as `rpc_protocol::server::typed_procedure()`.
+pub trait Decode: Sized {
+    /// Decode a value from `input`, which is advanced past it.
+    fn decode(input: &mut &[u8]) -> Result<Self>;
+}
+
+/// A type that encodes to XDR, as the generated types do with their allocating routines, for code
+/// that is generic over them.
+#[cfg(feature = "std")]
+pub trait Encode {
+    fn encode(&self) -> Vec<u8>;
+}
+
+/// The void argument or result of a procedure, which is encoded as nothing.
+impl Decode for () {
+    fn decode(_input: &mut &[u8]) -> Result<Self> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Encode for () {
+    fn encode(&self) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+macro_rules! integer_codec {
+    ($($ty:ty => $get:ident),*) => {$(
+        impl Decode for $ty {
+            fn decode(input: &mut &[u8]) -> Result<Self> {
+                let mut value = Default::default();
+                $get(&mut value, input)?;
+                Ok(value)
+            }
+        }
+
+        #[cfg(feature = "std")]
+        impl Encode for $ty {
+            fn encode(&self) -> Vec<u8> {
+                self.to_be_bytes().to_vec()
+            }
+        }
+    )*};
+}
+
+integer_codec!(i32 => get_i32, u32 => get_u32, i64 => get_i64, u64 => get_u64);
+
+impl Decode for bool {
+    fn decode(input: &mut &[u8]) -> Result<Self> {
+        let mut value = false;
+        get_bool(&mut value, input)?;
+        Ok(value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Encode for bool {
+    fn encode(&self) -> Vec<u8> {
+        serialize_bool(self).to_vec()
+    }
+}
+
+/// The bytes of a string. Code generated with `Compiler::no_std()` holds strings as `&[u8]`, where it
+/// would otherwise hold `OsString`s, and imports this to encode either with `as_bytes()`.
+pub trait AsBytes {
+    fn as_bytes(&self) -> &[u8];
+}
+
+impl AsBytes for [u8] {
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+/// What `check_encoding()` found wrong with an encoded value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncodingError {
+    /// The encoding is this many bytes long, which is not a multiple of 4.
+    Unaligned(usize),
+
+    /// The encoding does not decode.
+    Undecodable,
+
+    /// This many bytes were left over after decoding the value.
+    TrailingBytes(usize),
+
+    /// Encoding the decoded value again differs from the encoding from this offset on: for
+    /// example, because padding in the encoding is not zero.
+    Mismatch(usize),
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EncodingError {}
+
+impl core::fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Unaligned(len) => write!(f, "is {len} bytes long, which is not a multiple of 4"),
+            Self::Undecodable => write!(f, "does not decode"),
+            Self::TrailingBytes(n) => write!(f, "has {n} bytes left over after decoding"),
+            Self::Mismatch(offset) => {
+                write!(f, "differs from its canonical encoding at byte {offset}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+/// Check that `encoded` is the canonical encoding of a value: a multiple of 4 bytes long, with all
+/// of its padding zero, and with nothing after the value. `recode` decodes the value from its
+/// input, which it advances, and returns the value encoded again.
+pub fn check_encoding(
+    encoded: &[u8],
+    recode: impl FnOnce(&mut &[u8]) -> Result<Vec<u8>>,
+) -> std::result::Result<(), EncodingError> {
+    if !encoded.len().is_multiple_of(4) {
+        return Err(EncodingError::Unaligned(encoded.len()));
+    }
+
+    let mut rest = encoded;
+    let recoded = recode(&mut rest).map_err(|_| EncodingError::Undecodable)?;
+    if !rest.is_empty() {
+        return Err(EncodingError::TrailingBytes(rest.len()));
+    }
+
+    // Decoding skips padding without looking at it, so padding that is not zero only shows up
+    // here:
+    match encoded.iter().zip(&recoded).position(|(a, b)| a != b) {
+        Some(offset) => Err(EncodingError::Mismatch(offset)),
+        None if recoded.len() != encoded.len() => {
+            Err(EncodingError::Mismatch(recoded.len().min(encoded.len())))
+        }
+        None => Ok(()),
+    }
+}
+
 pub fn get_i32_infallible(input: &[u8]) -> i32 {
-    let (int_bytes, _rest) = input.split_at(std::mem::size_of::<i32>());
+    let (int_bytes, _rest) = input.split_at(core::mem::size_of::<i32>());
     i32::from_be_bytes(int_bytes.try_into().unwrap())
 }
 
 pub fn get_u32_infallible(input: &[u8]) -> u32 {
-    let (int_bytes, _rest) = input.split_at(std::mem::size_of::<u32>());
+    let (int_bytes, _rest) = input.split_at(core::mem::size_of::<u32>());
     u32::from_be_bytes(int_bytes.try_into().unwrap())
 }
 
 pub fn get_i64_infallible(input: &[u8]) -> i64 {
-    let (int_bytes, _rest) = input.split_at(std::mem::size_of::<i64>());
+    let (int_bytes, _rest) = input.split_at(core::mem::size_of::<i64>());
     i64::from_be_bytes(int_bytes.try_into().unwrap())
 }
 
 pub fn get_u64_infallible(input: &[u8]) -> u64 {
-    let (int_bytes, _rest) = input.split_at(std::mem::size_of::<u64>());
+    let (int_bytes, _rest) = input.split_at(core::mem::size_of::<u64>());
     u64::from_be_bytes(int_bytes.try_into().unwrap())
 }
 
 pub fn get_bool_infallible(input: &[u8]) -> bool {
-    let (bool_bytes, _rest) = input.split_at(std::mem::size_of::<u32>());
+    let (bool_bytes, _rest) = input.split_at(core::mem::size_of::<u32>());
     !matches!(u32::from_be_bytes(bool_bytes.try_into().unwrap()), 0)
 }
 
@@ -0,0 +1,120 @@
+// SPDX-License-Identifier: BSD-3-Clause
+// Copyright 2025. Triad National Security, LLC.
+
+//! Serde support for the `OsString`s that XDR strings are decoded into, for use with
+//! `#[serde(with = "...")]` in code generated with `Compiler::derive_serde()`.
+//!
+//! Serde's own implementation encodes an `OsString` as an enum of its platform representation, such
+//! as `{"Unix": [104, 105]}`. Here, a string that is valid UTF-8 is encoded as a plain string, and
+//! only other strings are encoded as their bytes. Either form is accepted when deserializing.
+
+use std::{
+    ffi::{OsStr, OsString},
+    fmt,
+    os::unix::ffi::{OsStrExt, OsStringExt},
+};
+
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+pub fn serialize<S: Serializer>(s: &OsStr, serializer: S) -> Result<S::Ok, S::Error> {
+    match s.to_str() {
+        Some(s) => serializer.serialize_str(s),
+        None => serializer.serialize_bytes(s.as_bytes()),
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OsString, D::Error> {
+    deserializer.deserialize_any(OsStringVisitor)
+}
+
+struct OsStringVisitor;
+
+impl<'de> Visitor<'de> for OsStringVisitor {
+    type Value = OsString;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a string or an array of bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<OsString, E> {
+        Ok(v.into())
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<OsString, E> {
+        Ok(v.into())
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<OsString, E> {
+        Ok(OsStr::from_bytes(v).to_os_string())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<OsString, E> {
+        Ok(OsString::from_vec(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<OsString, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+
+        Ok(OsString::from_vec(bytes))
+    }
+}
+
+/// Gives an `OsString` in a container the encoding of this module when serialized.
+struct Encoded<'a>(&'a OsStr);
+
+impl Serialize for Encoded<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(self.0, serializer)
+    }
+}
+
+/// Gives an `OsString` in a container the encoding of this module when deserialized.
+struct Decoded(OsString);
+
+impl<'de> Deserialize<'de> for Decoded {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer).map(Decoded)
+    }
+}
+
+/// For `Option<OsString>`.
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        s: &Option<OsString>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        s.as_deref().map(Encoded).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<OsString>, D::Error> {
+        Ok(Option::<Decoded>::deserialize(deserializer)?.map(|s| s.0))
+    }
+}
+
+/// For `Vec<OsString>`.
+pub mod vec {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(v: &[OsString], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(v.iter().map(|s| Encoded(s)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<OsString>, D::Error> {
+        Ok(Vec::<Decoded>::deserialize(deserializer)?
+            .into_iter()
+            .map(|s| s.0)
+            .collect())
+    }
+}